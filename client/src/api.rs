@@ -1,12 +1,130 @@
+use rand::Rng;
+use reqwest::{Method, StatusCode};
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::config::Config;
 use crate::state::SessionState;
 
+const DEFAULT_ATTEMPTS: u32 = 3;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const BASE_DELAY: Duration = Duration::from_millis(250);
+const MAX_DELAY: Duration = Duration::from_secs(4);
+
+static RETRY_POLICY: OnceLock<RetryPolicy> = OnceLock::new();
+
+/// Why a REST call failed
+#[derive(Debug)]
+pub enum ApiError {
+    /// The server could not be reached, timed out or was overloaded, after every attempt
+    Retryable(String),
+    /// Another attempt would fail the same way
+    Fatal(String),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Retryable(reason) => write!(f, "{reason}, the server may be unreachable"),
+            ApiError::Fatal(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// How often and how patiently REST calls are attempted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { attempts: DEFAULT_ATTEMPTS, timeout: DEFAULT_TIMEOUT }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            attempts: config.request_attempts.unwrap_or(DEFAULT_ATTEMPTS).max(1),
+            timeout: config.request_timeout_secs.map(Duration::from_secs).unwrap_or(DEFAULT_TIMEOUT),
+        }
+    }
+
+    /// Exponential backoff with jitter, between half and all of the doubled delay
+    fn delay(&self, retry: u32) -> Duration {
+        let delay = BASE_DELAY.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1))).min(MAX_DELAY);
+        rand::thread_rng().gen_range(delay / 2..=delay)
+    }
+}
+
+/// Used by every REST call from now on
+pub fn set_retry_policy(policy: RetryPolicy) {
+    let _ = RETRY_POLICY.set(policy);
+}
+
+/// Send the request `build` makes, trying again with growing delays while the failure is retryable
+/// Requests other than POST are retried on any transient failure, POSTs only when the server was never reached
+pub async fn send(build: impl Fn(&reqwest::Client) -> reqwest::RequestBuilder) -> Result<reqwest::Response, ApiError> {
+    let policy = RETRY_POLICY.get().copied().unwrap_or_default();
+    let client = reqwest::Client::builder()
+        .timeout(policy.timeout)
+        .build()
+        .map_err(|e| ApiError::Fatal(e.to_string()))?;
+
+    let mut attempt = 1;
+    loop {
+        let request = build(&client).build().map_err(|e| ApiError::Fatal(e.to_string()))?;
+        let idempotent = request.method() != Method::POST;
+        let url = request.url().path().to_string();
+
+        let error = match client.execute(request).await {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => classify_status(response.status(), idempotent),
+            Err(e) if e.is_connect() || (idempotent && e.is_timeout()) => ApiError::Retryable(e.to_string()),
+            Err(e) => ApiError::Fatal(e.to_string()),
+        };
+        match error {
+            ApiError::Retryable(reason) if attempt < policy.attempts => {
+                let delay = policy.delay(attempt);
+                tracing::info!("{url} failed ({reason}), attempt {attempt} of {}, retrying in {delay:?}", policy.attempts);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            error => return Err(error),
+        }
+    }
+}
+
+fn classify_status(status: StatusCode, idempotent: bool) -> ApiError {
+    let transient = status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::REQUEST_TIMEOUT;
+    match status {
+        StatusCode::UNAUTHORIZED => ApiError::Fatal("Authentication failed - please log in again".to_string()),
+        _ if transient && idempotent => ApiError::Retryable(format!("Server error: {status}")),
+        _ => ApiError::Fatal(format!("Server error: {status}")),
+    }
+}
+
+/// Server URL and session token of a logged in player
+fn credentials(session: &SessionState) -> Result<(&str, &str), ApiError> {
+    let token = session.auth_token.as_deref().ok_or(ApiError::Fatal("No auth token".to_string()))?;
+    let server_url = session.config.server_url.as_deref().ok_or(ApiError::Fatal("No server URL".to_string()))?;
+    Ok((server_url, token))
+}
+
 /// Authentication API calls
 pub mod auth {
     use std::path::Path;
     use std::fs;
 
-    
-    use battld_common::api::{ChallengeRequest, ChallengeResponse, VerifyRequest, AuthResponse};
+    use battld_common::api::{ChallengeRequest, ChallengeResponse, VerifyRequest, AuthResponse, RebindKeyRequest, RecoveryRequest, RecoveryResponse};
+    use battld_common::HEADER_AUTH;
+
+    use super::*;
 
     pub async fn create_player(server_url: &str, name: &str, public_key_path: &str) -> std::result::Result<battld_common::Player, Box<dyn std::error::Error>> {
         let public_key_pem = fs::read_to_string(public_key_path)?;
@@ -23,15 +141,8 @@ pub mod auth {
             name: name.to_string(),
         };
 
-        let client = reqwest::Client::new();
         let url = format!("{server_url}/player");
-
-        let response = client
-            .post(&url)
-            .header("x-battld-client", "true")
-            .json(&request)
-            .send()
-            .await?;
+        let response = send(|client| client.post(&url).header("x-battld-client", "true").json(&request)).await?;
         let response_text = response.text().await?;
 
         let player: battld_common::Player = serde_json::from_str(&response_text)?;
@@ -43,7 +154,6 @@ pub mod auth {
         player_id: i64,
         public_key_hint: &str,
     ) -> std::result::Result<ChallengeResponse, Box<dyn std::error::Error>> {
-        let client = reqwest::Client::new();
         let url = format!("{server_url}/auth/challenge");
 
         let request = ChallengeRequest {
@@ -51,16 +161,9 @@ pub mod auth {
             public_key_hint: public_key_hint.to_string(),
         };
 
-        let response = client
-            .post(&url)
-            .header("x-battld-client", "true")
-            .json(&request)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(format!("Challenge request failed: {}", response.status()).into());
-        }
+        let response = send(|client| client.post(&url).header("x-battld-client", "true").json(&request))
+            .await
+            .map_err(|e| format!("Challenge request failed: {e}"))?;
 
         Ok(response.json().await?)
     }
@@ -71,7 +174,6 @@ pub mod auth {
         nonce: &str,
         signature: &str,
     ) -> std::result::Result<AuthResponse, Box<dyn std::error::Error>> {
-        let client = reqwest::Client::new();
         let url = format!("{server_url}/auth/verify");
 
         let request = VerifyRequest {
@@ -80,24 +182,47 @@ pub mod auth {
             signature: signature.to_string(),
         };
 
-        let response = client
-            .post(&url)
-            .header("x-battld-client", "true")
-            .json(&request)
-            .send()
-            .await?;
+        let response = send(|client| client.post(&url).header("x-battld-client", "true").json(&request))
+            .await
+            .map_err(|e| format!("Verification failed: {e}"))?;
 
-        if !response.status().is_success() {
-            return Err(format!("Verification failed: {}", response.status()).into());
-        }
+        Ok(response.json().await?)
+    }
+
+    /// The account a key derived from a recovery phrase belongs to, with a nonce to sign in with it
+    pub async fn recover(server_url: &str, public_key_pem: &str) -> std::result::Result<RecoveryResponse, Box<dyn std::error::Error>> {
+        let url = format!("{server_url}/auth/recover");
+        let request = RecoveryRequest { public_key: public_key_pem.to_string() };
+
+        let response = send(|client| client.post(&url).header("x-battld-client", "true").json(&request))
+            .await
+            .map_err(|e| format!("Recovery failed: {e}"))?;
 
         Ok(response.json().await?)
     }
+
+    pub async fn rebind_key(server_url: &str, session_token: &str, request: &RebindKeyRequest) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let url = format!("{server_url}/player/key");
+        send(|client| {
+            client
+                .post(&url)
+                .header(HEADER_AUTH, format!("Bearer {session_token}"))
+                .header("x-battld-client", "true")
+                .json(request)
+        })
+        .await
+        .map_err(|e| format!("Binding the new key failed: {e}"))?;
+
+        Ok(())
+    }
 }
 
 /// Player data API calls
 pub mod player {
-    use battld_common::{games::matches::Match, HEADER_AUTH};
+    use battld_common::{
+        games::{game_type::GameType, matches::Match}, ArenaDetails, ArenaInfo, LeaderboardResponse, MatchChallenge, PartyStatus, PlayerStats, Quest, QuietHours,
+        ReplayPrivacy, ReplayPrivacyRequest, ReplayResponse, ReplaySettings, TimePreferences, HEADER_AUTH,
+    };
 
     use super::*;
 
@@ -106,17 +231,9 @@ pub mod player {
             return Err("Not authenticated".into());
         }
 
-        let token = session.auth_token.as_ref().unwrap();
-        let server_url = session.config.server_url.as_ref().unwrap();
-
-        let client = reqwest::Client::new();
+        let (server_url, token) = credentials(session)?;
         let url = format!("{server_url}/player");
-
-        let response = client
-            .get(&url)
-            .header(HEADER_AUTH, format!("Bearer {token}"))
-            .send()
-            .await?;
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
 
         let response_text = response.text().await?;
         let player: battld_common::Player = serde_json::from_str(&response_text)?;
@@ -128,25 +245,241 @@ pub mod player {
             return Err("Not authenticated".into());
         }
 
-        let token = session.auth_token.as_ref().ok_or("No auth token")?;
-        let server_url = session.config.server_url.as_ref().ok_or("No server URL")?;
-
-        let client = reqwest::Client::new();
+        let (server_url, token) = credentials(session)?;
         let url = format!("{server_url}/matches/active");
-
-        let response = client
-            .get(&url)
-            .header(HEADER_AUTH, format!("Bearer {token}"))
-            .send()
-            .await?;
-
-        if response.status() == 401 {
-            return Err("Authentication failed - please log in again".into());
-        }
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
 
         let response_text = response.text().await?;
         let matches: Vec<Match> = serde_json::from_str(&response_text)?;
         Ok(matches)
     }
 
+    pub async fn fetch_stats(session: &SessionState) -> std::result::Result<PlayerStats, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/stats");
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.json().await?)
+    }
+
+    /// One page of the leaderboard, only the player's friends when `friends_only`,
+    /// ranked by the rating in `game_type` when given
+    pub async fn fetch_leaderboard(
+        session: &SessionState,
+        limit: i64,
+        offset: i64,
+        friends_only: bool,
+        game_type: Option<&GameType>,
+        season: Option<i64>,
+    ) -> std::result::Result<LeaderboardResponse, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let scope = if friends_only { "friends" } else { "global" };
+        let mut url = format!("{server_url}/leaderboard?limit={limit}&offset={offset}&scope={scope}");
+        if let Some(game_type) = game_type {
+            url.push_str(&format!("&game_type={game_type:?}"));
+        }
+        if let Some(season) = season {
+            url.push_str(&format!("&season={season}"));
+        }
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.json().await?)
+    }
+
+    /// Players named `name`, ignoring case
+    pub async fn find_players(session: &SessionState, name: &str) -> std::result::Result<Vec<battld_common::Player>, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/players");
+        let response = send(|client| client.get(&url).query(&[("name", name)]).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.json().await?)
+    }
+
+    pub async fn fetch_quests(session: &SessionState) -> std::result::Result<Vec<Quest>, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/quests");
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.json().await?)
+    }
+
+    pub async fn fetch_pending_challenges(session: &SessionState) -> std::result::Result<Vec<MatchChallenge>, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/challenges");
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.json().await?)
+    }
+
+    pub async fn fetch_party(session: &SessionState) -> std::result::Result<PartyStatus, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/party");
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.json().await?)
+    }
+
+    pub async fn fetch_arenas(session: &SessionState) -> std::result::Result<Vec<ArenaInfo>, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/arenas");
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.json().await?)
+    }
+
+    pub async fn fetch_arena(session: &SessionState, arena_id: i64) -> std::result::Result<ArenaDetails, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/arenas/{arena_id}");
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.json().await?)
+    }
+
+    pub async fn fetch_replay_settings(session: &SessionState) -> std::result::Result<ReplaySettings, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/replays");
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.json().await?)
+    }
+
+    pub async fn fetch_match_replay(session: &SessionState, match_id: i64) -> std::result::Result<ReplayResponse, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/matches/{match_id}/replay");
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.json().await?)
+    }
+
+    /// A finished match as a file, `format` being `pgn` for chess or `json`
+    pub async fn fetch_match_export(session: &SessionState, match_id: i64, format: &str) -> std::result::Result<String, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/matches/{match_id}/export?format={format}");
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.text().await?)
+    }
+
+    pub async fn set_replay_privacy(session: &SessionState, privacy: ReplayPrivacy) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/replays/privacy");
+        send(|client| {
+            client
+                .post(&url)
+                .header(HEADER_AUTH, format!("Bearer {token}"))
+                .header("x-battld-client", "true")
+                .json(&ReplayPrivacyRequest { privacy })
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn fetch_time_preferences(session: &SessionState) -> std::result::Result<TimePreferences, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/player/time-preferences");
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.json().await?)
+    }
+
+    pub async fn set_time_preferences(session: &SessionState, preferences: TimePreferences) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/player/time-preferences");
+        send(|client| {
+            client
+                .post(&url)
+                .header(HEADER_AUTH, format!("Bearer {token}"))
+                .header("x-battld-client", "true")
+                .json(&preferences)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn fetch_quiet_hours(session: &SessionState) -> std::result::Result<Option<QuietHours>, Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/player/quiet-hours");
+        let response = send(|client| client.get(&url).header(HEADER_AUTH, format!("Bearer {token}"))).await?;
+
+        Ok(response.json().await?)
+    }
+
+    pub async fn set_quiet_hours(session: &SessionState, quiet_hours: Option<&QuietHours>) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/player/quiet-hours");
+        send(|client| {
+            client
+                .post(&url)
+                .header(HEADER_AUTH, format!("Bearer {token}"))
+                .header("x-battld-client", "true")
+                .json(&quiet_hours)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Add or remove a player from the current player's friends
+    pub async fn set_friend(session: &SessionState, player_id: i64, friend: bool) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (server_url, token) = credentials(session)?;
+        let url = format!("{server_url}/friends/{player_id}");
+        send(|client| {
+            let request = if friend { client.post(&url) } else { client.delete(&url) };
+            request
+                .header(HEADER_AUTH, format!("Bearer {token}"))
+                .header("x-battld-client", "true")
+        })
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Public spectator API calls, no login needed
+pub mod live {
+    use battld_common::{games::matches::Match, LiveMatch};
+
+    use super::*;
+
+    pub async fn fetch_live_matches(server_url: &str) -> std::result::Result<Vec<LiveMatch>, Box<dyn std::error::Error>> {
+        let url = format!("{server_url}/live/matches");
+        let response = send(|client| client.get(&url)).await?;
+
+        Ok(response.json().await?)
+    }
+
+    pub async fn fetch_live_match(server_url: &str, match_id: i64) -> std::result::Result<Match, Box<dyn std::error::Error>> {
+        let url = format!("{server_url}/live/matches/{match_id}");
+        let response = send(|client| client.get(&url)).await?;
+
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_grows_with_jitter() {
+        let policy = RetryPolicy::default();
+        for _ in 0..20 {
+            let first = policy.delay(1);
+            assert!(first >= BASE_DELAY / 2 && first <= BASE_DELAY);
+            let third = policy.delay(3);
+            assert!(third >= BASE_DELAY * 2 && third <= BASE_DELAY * 4);
+            assert!(policy.delay(30) <= MAX_DELAY);
+        }
+    }
+
+    #[test]
+    fn test_classify_status() {
+        assert!(matches!(classify_status(StatusCode::SERVICE_UNAVAILABLE, true), ApiError::Retryable(_)));
+        assert!(matches!(classify_status(StatusCode::TOO_MANY_REQUESTS, true), ApiError::Retryable(_)));
+        assert!(matches!(classify_status(StatusCode::SERVICE_UNAVAILABLE, false), ApiError::Fatal(_)));
+        assert!(matches!(classify_status(StatusCode::NOT_FOUND, true), ApiError::Fatal(_)));
+        assert!(matches!(classify_status(StatusCode::UNAUTHORIZED, true), ApiError::Fatal(_)));
+    }
 }
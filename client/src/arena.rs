@@ -0,0 +1,215 @@
+use battld_common::{ArenaInfo, ArenaStanding, ClientMessage, ErrorCode, ServerMessage};
+use colored::*;
+use crossterm::{event::KeyCode, terminal};
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::api::player::{fetch_arena, fetch_arenas};
+use crate::state::*;
+use crate::ui::*;
+use crate::waiting_room::read_pending_keys;
+use crate::width::pad_right;
+
+pub async fn show_arenas(session: &mut SessionState) -> Result<(), Box<dyn std::error::Error>> {
+    if session.ws_client.is_none() {
+        session.connect_websocket().await?;
+    }
+
+    let mut notice: Option<String> = None;
+
+    loop {
+        clear_screen()?;
+        println!("\n{}", "Loading arenas...".cyan());
+
+        let arenas = fetch_arenas(session).await?;
+        let now = battld_common::time() as i64;
+
+        clear_screen()?;
+        println!();
+        println!("{}", "═══════════════════════════════════════════════════════════════════".bright_cyan());
+        println!("{}", "                               ARENAS                              ".bright_cyan().bold());
+        println!("{}", "═══════════════════════════════════════════════════════════════════".bright_cyan());
+        println!();
+
+        if arenas.is_empty() {
+            println!("{}", "  No arenas scheduled".dimmed());
+        }
+        for arena in &arenas {
+            println!(
+                "  [{}] {} {}",
+                arena.id.to_string().bright_yellow(),
+                pad_right(&arena.game_type.to_string(), 24),
+                arena_status(arena, now)
+            );
+        }
+        println!();
+        if let Some(notice) = notice.take() {
+            println!("  {}", notice.yellow());
+        }
+        println!("{}", "j N: join | s N: standings | r: refresh | q: back".dimmed());
+        print!("> ");
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let parts: Vec<&str> = input.split_whitespace().collect();
+        let arena_id = parts.get(1).and_then(|id| id.parse::<i64>().ok());
+
+        match (parts.first().copied(), arena_id) {
+            (Some("j"), Some(arena_id)) => notice = play_arena(session, arena_id).await?,
+            (Some("s"), Some(arena_id)) => {
+                let details = fetch_arena(session, arena_id).await?;
+                clear_screen()?;
+                println!();
+                println!("  {} {}", details.arena.game_type.to_string().bright_cyan().bold(), arena_status(&details.arena, now));
+                println!();
+                render_standings(&details.standings, session.player_id);
+                println!("\nPress any key to go back...");
+                wait_for_keypress()?;
+            }
+            (Some("q"), _) => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+/// Join the arena announced on the menu, straight into its live standings
+pub async fn join_announced_arena(session: &mut SessionState, arena_id: i64) -> Result<(), Box<dyn std::error::Error>> {
+    if session.ws_client.is_none() {
+        session.connect_websocket().await?;
+    }
+    if let Some(notice) = play_arena(session, arena_id).await? {
+        println!("\n{}", notice.yellow());
+        println!("\nPress any key to return to menu...");
+        wait_for_keypress()?;
+    }
+    Ok(())
+}
+
+/// Stay in an arena, playing every match it pairs us in, until it ends or we leave
+/// Returns a notice for the arena list, such as why we could not join
+async fn play_arena(session: &mut SessionState, arena_id: i64) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let ws_client = session.ws_client.clone().ok_or("Not connected to WebSocket")?;
+    ws_client.get_messages().await;
+    ws_client.send(ClientMessage::JoinArena { arena_id })?;
+
+    // The last match played stays current, only a newer one is ours to play
+    let mut played = ws_client.get_current_match().await.map(|match_data| match_data.id);
+    let mut joined: Option<ArenaInfo> = None;
+    let mut notices: Vec<String> = vec![];
+    let mut standings: Vec<ArenaStanding> = vec![];
+    // Standings are pushed after every arena match, fetched when joining and after playing as games take those messages
+    let mut stale = true;
+    let mut dirty = true;
+
+    loop {
+        for message in ws_client.get_messages().await {
+            match message {
+                ServerMessage::ArenaJoined { arena } => joined = Some(arena),
+                ServerMessage::ArenaScored { earned, points, streak, .. } => {
+                    notices.push(format!("+{earned} points, {points} in total, {streak} wins in a row"));
+                }
+                ServerMessage::ArenaStandings { arena_id: standings_of, standings: live } if standings_of == arena_id => {
+                    standings = live;
+                    stale = false;
+                }
+                ServerMessage::ArenaEnded { standings, .. } => {
+                    clear_screen()?;
+                    println!("\n{}\n", "The arena is over, final standings:".bright_cyan().bold());
+                    render_standings(&standings, session.player_id);
+                    println!("\nPress any key to go back...");
+                    wait_for_keypress()?;
+                    return Ok(None);
+                }
+                ServerMessage::Error { code: ErrorCode::ArenaNotFound, .. } => return Ok(error_text(&message)),
+                ServerMessage::Error { .. } => notices.extend(error_text(&message)),
+                _ => {}
+            }
+            dirty = true;
+        }
+
+        if let Some(match_data) = ws_client.get_current_match().await.filter(|m| m.in_progress && Some(m.id) != played) {
+            played = Some(match_data.id);
+            crate::games::resume_game(session, match_data).await?;
+            stale = true;
+        }
+
+        if let Some(arena) = joined.as_ref().filter(|_| stale) {
+            standings = fetch_arena(session, arena.id).await.map(|details| details.standings).unwrap_or(standings);
+            stale = false;
+            dirty = true;
+        }
+
+        if dirty {
+            render_waiting(joined.as_ref(), &standings, &notices, session.player_id)?;
+            dirty = false;
+        }
+
+        terminal::enable_raw_mode()?;
+        let keys = read_pending_keys();
+        terminal::disable_raw_mode()?;
+        if keys?.iter().any(|code| matches!(code, KeyCode::Char('q') | KeyCode::Esc)) {
+            ws_client.send(ClientMessage::LeaveArena)?;
+            return Ok(Some("You left the arena, your points are kept".to_string()));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+fn render_waiting(arena: Option<&ArenaInfo>, standings: &[ArenaStanding], notices: &[String], player_id: Option<i64>) -> io::Result<()> {
+    clear_screen()?;
+    println!();
+    let Some(arena) = arena else {
+        println!("{}", "  Joining the arena...".cyan());
+        return io::stdout().flush();
+    };
+    let now = battld_common::time() as i64;
+    println!("  {} {}", arena.game_type.to_string().bright_cyan().bold(), arena_status(arena, now));
+    if arena.starts_at > now {
+        println!("{}", "  Waiting for the arena to start...".cyan());
+    } else {
+        println!("{}", "  Waiting for your next opponent...".cyan());
+    }
+    println!();
+    render_standings(standings, player_id);
+    println!();
+    for notice in notices.iter().rev().take(3) {
+        println!("  {}", notice.yellow());
+    }
+    println!("{}", "  Press q to leave the arena".dimmed());
+    io::stdout().flush()
+}
+
+fn render_standings(standings: &[ArenaStanding], player_id: Option<i64>) {
+    if standings.is_empty() {
+        println!("{}", "  Nobody has played yet".dimmed());
+        return;
+    }
+    println!("  {:>4} {} {:>6} {:>6} {:>5} {:>7}",
+        "Rank".dimmed(), pad_right("Player", 24).dimmed(), "Points".dimmed(), "Games".dimmed(), "Wins".dimmed(), "Streak".dimmed());
+    for standing in standings {
+        let line = format!("  {:>4} {} {:>6} {:>6} {:>5} {:>7}",
+            format!("#{}", standing.rank),
+            pad_right(&standing.player_name, 24),
+            standing.points,
+            standing.games,
+            standing.wins,
+            standing.streak);
+        if Some(standing.player_id) == player_id {
+            println!("{}", line.bright_white().bold());
+        } else {
+            println!("{line}");
+        }
+    }
+}
+
+fn arena_status(arena: &ArenaInfo, now: i64) -> String {
+    let minutes = |secs: i64| (secs + 59) / 60;
+    if arena.finished || arena.ends_at <= now {
+        "ended".dimmed().to_string()
+    } else if arena.starts_at > now {
+        format!("starts in {} min", minutes(arena.starts_at - now)).yellow().to_string()
+    } else {
+        format!("running, {} min left", minutes(arena.ends_at - now)).bright_green().to_string()
+    }
+}
@@ -6,7 +6,11 @@ use rsa::{Pkcs1v15Sign, sha2::Sha256};
 use base64::{Engine as _, engine::general_purpose};
 use colored::*;
 
+use battld_common::api::RebindKeyRequest;
+
 use crate::api;
+use crate::config::Config;
+use crate::recovery;
 use crate::state::*;
 
 async fn perform_auth(
@@ -15,11 +19,7 @@ async fn perform_auth(
     private_key_path: &str,
     public_key_path: &str,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let public_key_hint = Path::new(public_key_path)
-        .file_name()
-        .and_then(|os_str| os_str.to_str())
-        .unwrap_or("unknown")
-        .to_string();
+    let public_key_hint = key_hint(public_key_path);
 
     let challenge_response = api::auth::request_challenge(server_url, player_id, &public_key_hint).await?;
     let signature = sign_data(&challenge_response.nonce, private_key_path)?;
@@ -44,13 +44,17 @@ pub async fn login_interactive(session: &mut SessionState) -> std::result::Resul
     match (player_id, has_keys) {
         // Case 1: No config.json or no player_id and no keys - new user, create everything
         (None, false) => {
+            if ask_yes_no("Restore an existing account from its recovery phrase? (y/N)")? {
+                return restore_account(session).await;
+            }
+
             println!("{}", "New user setup - generating key pair and creating account...".dimmed());
 
             let private_key_path = session.config.private_key_path.as_ref().unwrap();
             let public_key_path = session.config.public_key_path.as_ref().unwrap();
 
             // Generate key pair
-            generate_key_pair(private_key_path, public_key_path)?;
+            let phrase = generate_key_pair(private_key_path, public_key_path)?;
 
             // Get player name
             println!("Enter your player name:");
@@ -67,6 +71,7 @@ pub async fn login_interactive(session: &mut SessionState) -> std::result::Resul
             session.player_id = Some(player.id);
 
             println!("{}", format!("Account created successfully! Player ID: {}", player.id).dimmed());
+            show_recovery_phrase(&phrase);
 
             match perform_auth(
                 session.config.server_url.as_ref().unwrap(),
@@ -143,6 +148,10 @@ pub async fn login_interactive(session: &mut SessionState) -> std::result::Resul
 
         // Case 3: Has player_id but no keys - error, need keys for existing account
         (Some(pid), false) => {
+            println!("{}", format!("Found player ID {pid} but no keys.").dimmed());
+            if ask_yes_no("Restore the account from its recovery phrase? (y/N)")? {
+                return restore_account(session).await;
+            }
             println!("{}", format!("Error: Found player ID {pid} but no SSH keys.").dimmed());
             println!("{}", "You need the private/public key pair to login to an existing account.".dimmed());
             println!("{}", "Options:".dimmed());
@@ -189,49 +198,165 @@ pub async fn login_interactive(session: &mut SessionState) -> std::result::Resul
 }
 
 pub async fn try_auto_login(session: &mut SessionState) -> std::result::Result<bool, Box<dyn std::error::Error>> {
-    if let Some(player_id) = session.config.player_id {
-        if session.config.has_keys() {
-            println!("{}", "Attempting automatic login...".dimmed());
+    let Some(player_id) = session.config.player_id else {
+        return Ok(false);
+    };
+    match auto_login_token(&session.config).await {
+        Some(Ok(session_token)) => {
+            complete_auto_login(session, player_id, session_token).await;
+            Ok(true)
+        }
+        Some(Err(e)) => {
+            println!("{}", format!("Automatic login failed: {e}").red());
+            Ok(false)
+        }
+        None => Ok(false),
+    }
+}
 
-            // Perform authentication using new 2-step flow
-            match perform_auth(
-                session.config.server_url.as_ref().unwrap(),
-                player_id,
-                session.config.private_key_path.as_ref().unwrap(),
-                session.config.public_key_path.as_ref().unwrap(),
-            ).await {
-                Ok(session_token) => {
-                    session.set_authenticated(player_id, session_token);
-                    println!("{}", "Automatic login successful!".green());
+/// Session token for the configured account, None without an account and its keys
+pub async fn auto_login_token(config: &Config) -> Option<std::result::Result<String, Box<dyn std::error::Error>>> {
+    let player_id = config.player_id?;
+    if !config.has_keys() {
+        return None;
+    }
+    println!("{}", "Attempting automatic login...".dimmed());
+
+    // Perform authentication using new 2-step flow
+    Some(perform_auth(
+        config.server_url.as_ref()?,
+        player_id,
+        config.private_key_path.as_ref()?,
+        config.public_key_path.as_ref()?,
+    ).await)
+}
 
-                    // Connect WebSocket
-                    if let Err(e) = session.connect_websocket().await {
-                        println!("{}", format!("WebSocket connection failed: {e}").yellow());
-                    } else {
-                        println!("{}", "WebSocket connected".dimmed());
-                    }
+pub async fn complete_auto_login(session: &mut SessionState, player_id: i64, session_token: String) {
+    session.set_authenticated(player_id, session_token);
+    println!("{}", "Automatic login successful!".green());
 
-                    println!("{}", format!("Logged in as player {player_id}").dimmed());
-                    return Ok(true);
-                },
-                Err(e) => {
-                    println!("{}", format!("Automatic login failed: {e}").red());
-                    return Ok(false);
-                }
-            }
-        }
+    // Connect WebSocket
+    if let Err(e) = session.connect_websocket().await {
+        println!("{}", format!("WebSocket connection failed: {e}").yellow());
+    } else {
+        println!("{}", "WebSocket connected".dimmed());
     }
-    Ok(false)
+
+    println!("{}", format!("Logged in as player {player_id}").dimmed());
 }
 
+fn ask_yes_no(question: &str) -> std::io::Result<bool> {
+    println!("{question}");
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
 
-fn generate_key_pair(private_key_path: &str, public_key_path: &str) -> std::result::Result<(), Box<dyn std::error::Error>> {
-    use rand::rngs::OsRng;
+/// Sign back in to an account on a new machine with its recovery phrase, saving the keys it stands for
+pub async fn restore_account(session: &mut SessionState) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    println!("Enter your recovery phrase:");
+    let mut phrase = String::new();
+    std::io::stdin().read_line(&mut phrase)?;
+    let private_key = derive_from_phrase(phrase.trim())?;
+    let public_pem = RsaPublicKey::from(&private_key).to_pkcs1_pem(LineEnding::LF)?;
+
+    let server_url = session.config.server_url.clone().ok_or("No server URL")?;
+    let recovery = api::auth::recover(&server_url, &public_pem)
+        .await
+        .map_err(|_| "No account uses this recovery phrase")?;
+    let signature = sign_with(&private_key, &recovery.nonce)?;
+    let auth_response = api::auth::verify_challenge(&server_url, recovery.player_id, &recovery.nonce, &signature).await?;
+
+    let private_key_path = session.config.private_key_path.clone().unwrap();
+    let public_key_path = session.config.public_key_path.clone().unwrap();
+    // Signing in checks the key file name, which may differ on this machine
+    rebind_key(&server_url, &auth_response.session_token, recovery.player_id, &private_key, &public_key_path).await?;
+    write_key_pair(&private_key, &private_key_path, &public_key_path)?;
+
+    session.config.player_id = Some(recovery.player_id);
+    session.save_config()?;
+    session.player_id = Some(recovery.player_id);
+    session.set_authenticated(recovery.player_id, auth_response.session_token);
+    println!("{}", format!("Account of player {} restored!", recovery.player_id).green());
+
+    if let Err(e) = session.connect_websocket().await {
+        println!("{}", format!("WebSocket connection failed: {e}").yellow());
+    } else {
+        println!("{}", "WebSocket connected".dimmed());
+    }
+    Ok(())
+}
+
+/// Move the signed in account to a key derived from a new recovery phrase, for accounts made before there were phrases
+pub async fn set_up_recovery_phrase(session: &mut SessionState) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let (Some(player_id), Some(session_token)) = (session.player_id, session.auth_token.clone()) else {
+        return Err("Not authenticated".into());
+    };
+    let server_url = session.config.server_url.clone().ok_or("No server URL")?;
+    let private_key_path = session.config.private_key_path.clone().unwrap();
+    let public_key_path = session.config.public_key_path.clone().unwrap();
+
+    let phrase = recovery::generate_phrase();
+    let private_key = derive_from_phrase(&phrase)?;
+    rebind_key(&server_url, &session_token, player_id, &private_key, &public_key_path).await?;
+    show_recovery_phrase(&phrase);
+    write_key_pair(&private_key, &private_key_path, &public_key_path)?;
+    println!("{}", "Your account now signs in with the key of this phrase.".dimmed());
+    Ok(())
+}
+
+async fn rebind_key(
+    server_url: &str,
+    session_token: &str,
+    player_id: i64,
+    private_key: &RsaPrivateKey,
+    public_key_path: &str,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let request = RebindKeyRequest {
+        public_key_hint: key_hint(public_key_path),
+        public_key: RsaPublicKey::from(private_key).to_pkcs1_pem(LineEnding::LF)?,
+        signature: sign_with(private_key, &RebindKeyRequest::signed_text(player_id))?,
+    };
+    api::auth::rebind_key(server_url, session_token, &request).await
+}
+
+fn key_hint(public_key_path: &str) -> String {
+    Path::new(public_key_path)
+        .file_name()
+        .and_then(|os_str| os_str.to_str())
+        .unwrap_or("unknown")
+        .to_string()
+}
 
-    let mut rng = OsRng;
-    let bits = 2048;
-    let private_key = RsaPrivateKey::new(&mut rng, bits)?;
-    let public_key = RsaPublicKey::from(&private_key);
+fn derive_from_phrase(phrase: &str) -> std::result::Result<RsaPrivateKey, Box<dyn std::error::Error>> {
+    let entropy = recovery::parse_phrase(phrase)?;
+    println!("{}", "Deriving the key of the recovery phrase, this takes a moment...".dimmed());
+    Ok(recovery::derive_private_key(&entropy)?)
+}
+
+fn show_recovery_phrase(phrase: &str) {
+    println!();
+    println!("{}", "Your recovery phrase:".bold());
+    println!("  {}", phrase.bright_yellow());
+    println!("{}", "Write it down and keep it safe: it restores your account if this machine is lost, and anyone holding it can sign in as you.".dimmed());
+    println!();
+}
+
+/// Generate a key pair from a new recovery phrase and save it, returning the phrase
+fn generate_key_pair(private_key_path: &str, public_key_path: &str) -> std::result::Result<String, Box<dyn std::error::Error>> {
+    let phrase = recovery::generate_phrase();
+    let private_key = derive_from_phrase(&phrase)?;
+    write_key_pair(&private_key, private_key_path, public_key_path)?;
+
+    println!("{}", "Generated new RSA key pair:".dimmed());
+    println!("{}", format!("  Private key: {private_key_path}").dimmed());
+    println!("{}", format!("  Public key: {public_key_path}").dimmed());
+
+    Ok(phrase)
+}
+
+fn write_key_pair(private_key: &RsaPrivateKey, private_key_path: &str, public_key_path: &str) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let public_key = RsaPublicKey::from(private_key);
 
     // Save private key in PKCS#8 PEM format
     let private_pem = private_key.to_pkcs8_pem(LineEnding::LF)?;
@@ -241,17 +366,16 @@ fn generate_key_pair(private_key_path: &str, public_key_path: &str) -> std::resu
     let public_pem = public_key.to_pkcs1_pem(LineEnding::LF)?;
     fs::write(public_key_path, public_pem)?;
 
-    println!("{}", "Generated new RSA key pair:".dimmed());
-    println!("{}", format!("  Private key: {private_key_path}").dimmed());
-    println!("{}", format!("  Public key: {public_key_path}").dimmed());
-
     Ok(())
 }
 
 pub fn sign_data(data: &str, private_key_path: &str) -> std::result::Result<String, Box<dyn std::error::Error>> {
     let private_key_pem = fs::read_to_string(private_key_path)?;
     let private_key = RsaPrivateKey::from_pkcs8_pem(&private_key_pem)?;
+    sign_with(&private_key, data)
+}
 
+fn sign_with(private_key: &RsaPrivateKey, data: &str) -> std::result::Result<String, Box<dyn std::error::Error>> {
     use sha2::Digest;
     let mut hasher = Sha256::new();
     hasher.update(data.as_bytes());
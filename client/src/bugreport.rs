@@ -0,0 +1,113 @@
+use battld_common::{ClientMessage, ServerMessage};
+use colored::*;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::utils::VERSION;
+use crate::websocket::WebSocketClient;
+
+pub const COMMAND: &str = "/bugreport";
+
+/// Protocol messages kept for the next report
+const MAX_MESSAGES: usize = 50;
+
+/// Log lines taken from the end of the newest log file
+const MAX_LOG_LINES: usize = 200;
+
+static RECENT_MESSAGES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static LOG_FILE: OnceLock<String> = OnceLock::new();
+
+/// Where `logging::init` writes, so reports can include the latest lines
+pub fn set_log_file(log_file: &str) {
+    LOG_FILE.get_or_init(|| log_file.to_string());
+}
+
+pub fn is_command(input: &str) -> bool {
+    input.trim().eq_ignore_ascii_case(COMMAND)
+}
+
+pub fn record_sent(message: &ClientMessage) {
+    record("SEND", message);
+}
+
+pub fn record_received(message: &ServerMessage) {
+    record("RECV", message);
+}
+
+/// Keep a message, with credentials redacted, dropping the oldest past `MAX_MESSAGES`
+fn record<T: Serialize>(direction: &str, message: &T) {
+    let mut messages = RECENT_MESSAGES.lock().unwrap();
+    if messages.len() == MAX_MESSAGES {
+        messages.pop_front();
+    }
+    messages.push_back(format!("{} [{direction}] {}", battld_common::time(), crate::logging::message(message)));
+}
+
+/// Write a report to the working directory and tell the player where it is
+pub async fn run(ws_client: Option<&WebSocketClient>) {
+    let match_id = match ws_client {
+        Some(ws_client) => ws_client.get_current_match().await.map(|match_data| match_data.id),
+        None => None,
+    };
+    let path = PathBuf::from(format!("bugreport-{}.txt", battld_common::time()));
+    match std::fs::write(&path, build_report(match_id)) {
+        Ok(()) => println!("{}", format!("Bug report saved to {}, attach it to your report", path.display()).bright_cyan()),
+        Err(e) => println!("{}", format!("Could not save the bug report: {e}").red()),
+    }
+}
+
+fn build_report(match_id: Option<i64>) -> String {
+    let mut report = String::new();
+    report.push_str(&format!("battld client v{VERSION}\n"));
+    report.push_str(&format!("Created at: {}\n", battld_common::time()));
+    report.push_str(&format!("Platform: {} {}\n", std::env::consts::OS, std::env::consts::ARCH));
+    report.push_str(&format!("Terminal: {}\n", terminal_info()));
+    report.push_str(&format!(
+        "Current match: {}\n",
+        match_id.map(|id| id.to_string()).unwrap_or_else(|| "none".to_string())
+    ));
+
+    report.push_str("\n== Recent protocol messages ==\n");
+    for message in RECENT_MESSAGES.lock().unwrap().iter() {
+        report.push_str(message);
+        report.push('\n');
+    }
+
+    report.push_str("\n== Recent log lines ==\n");
+    match LOG_FILE.get().and_then(|log_file| latest_log_lines(Path::new(log_file))) {
+        Some(lines) => report.push_str(&lines),
+        None => report.push_str("No log file found\n"),
+    }
+    report
+}
+
+/// Size and the variables that tell terminals and their capabilities apart
+fn terminal_info() -> String {
+    let size = match crossterm::terminal::size() {
+        Ok((columns, rows)) => format!("{columns}x{rows}"),
+        Err(_) => "unknown size".to_string(),
+    };
+    let variables: Vec<String> = ["TERM", "TERM_PROGRAM", "COLORTERM", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok().map(|value| format!("{name}={value}")))
+        .collect();
+    format!("{size} {}", variables.join(" "))
+}
+
+/// Tail of the newest file the daily rotation made from `log_file`
+fn latest_log_lines(log_file: &Path) -> Option<String> {
+    let directory = log_file.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let prefix = log_file.file_name()?.to_str()?;
+    let newest = std::fs::read_dir(directory)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_str().is_some_and(|name| name.starts_with(prefix)))
+        .max_by_key(|entry| entry.file_name())?;
+
+    let contents = std::fs::read_to_string(newest.path()).ok()?;
+    let lines: Vec<&str> = contents.lines().collect();
+    let tail = &lines[lines.len().saturating_sub(MAX_LOG_LINES)..];
+    Some(tail.iter().map(|line| format!("{line}\n")).collect())
+}
@@ -0,0 +1,324 @@
+use battld_common::games::{
+    briscola::BriscolaGameState,
+    chess::{ChessGameState, ChessPosition},
+    game_type::GameType,
+    matches::{Match, MatchOutcome},
+    rock_paper_scissors::{RockPaperScissorsGameState, RockPaperScissorsMove},
+    tic_tac_toe::TicTacToeGameState,
+};
+use battld_common::LiveMatch;
+use colored::*;
+use crossterm::{event::{self, Event, KeyCode}, terminal};
+use rustyline::DefaultEditor;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::api::live::{fetch_live_match, fetch_live_matches};
+use crate::games::briscola::{card_view, card_width, suit_name};
+use crate::games::chess::get_piece_symbol;
+use crate::games::is_players_turn;
+use crate::state::SessionState;
+use crate::ui::clear_screen;
+use crate::width::{center, pad_left, pad_right, truncate};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How long a finished match stays on screen before moving on
+const FINISHED_MATCH_DELAY: Duration = Duration::from_secs(10);
+const WIDTH: usize = 60;
+
+/// Time each player spent on the move, as seen by the caster
+#[derive(Default)]
+struct Clocks {
+    elapsed: [Duration; 2],
+    waiting_on: [bool; 2],
+    last_tick: Option<Instant>,
+}
+
+impl Clocks {
+    fn tick(&mut self, match_data: &Match) {
+        let now = Instant::now();
+        if let Some(last_tick) = self.last_tick {
+            for (elapsed, waiting) in self.elapsed.iter_mut().zip(self.waiting_on) {
+                if waiting {
+                    *elapsed += now - last_tick;
+                }
+            }
+        }
+        self.last_tick = Some(now);
+        self.waiting_on = [
+            match_data.in_progress && is_players_turn(match_data, match_data.player1_id),
+            match_data.in_progress && is_players_turn(match_data, match_data.player2_id),
+        ];
+    }
+}
+
+/// Pick a live match and cast it without any prompt, moving on to the next match when it ends
+pub async fn show_cast(session: &mut SessionState) -> Result<(), Box<dyn std::error::Error>> {
+    let server_url = session.config.server_url.clone().ok_or("No server URL configured")?;
+    let live_matches = fetch_live_matches(&server_url).await?;
+
+    clear_screen()?;
+    if live_matches.is_empty() {
+        println!("\n{}", "No matches are being played right now.".yellow());
+        return Ok(());
+    }
+
+    println!("\n{}", "Live matches:".cyan().bold());
+    for (index, live_match) in live_matches.iter().enumerate() {
+        println!(
+            "  {}. {}: {} vs {}",
+            (index + 1).to_string().bright_yellow(),
+            live_match.game_type,
+            live_match.player1_name,
+            live_match.player2_name
+        );
+    }
+    println!();
+
+    let mut rl = DefaultEditor::new()?;
+    let choice = rl.readline("Match to cast (Enter for the first): ")?;
+    let index = match choice.trim() {
+        "" => 0,
+        choice => match choice.parse::<usize>() {
+            Ok(n) if (1..=live_matches.len()).contains(&n) => n - 1,
+            _ => return Err("Invalid choice".into()),
+        },
+    };
+
+    let mut watched = vec![];
+    let mut next = live_matches.into_iter().nth(index);
+    while let Some(live_match) = next.take() {
+        watched.push(live_match.match_id);
+        if !cast_match(&server_url, &live_match).await? {
+            return Ok(());
+        }
+
+        loop {
+            next = fetch_live_matches(&server_url)
+                .await?
+                .into_iter()
+                .find(|m| !watched.contains(&m.match_id));
+            if next.is_some() {
+                break;
+            }
+            clear_screen()?;
+            println!("\n{}", "Waiting for the next match... (q to quit)".dimmed());
+            if wait_for_quit(FINISHED_MATCH_DELAY)? {
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Follow a match until it ends, false if the caster quit
+async fn cast_match(server_url: &str, live_match: &LiveMatch) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut clocks = Clocks::default();
+
+    loop {
+        let match_data = fetch_live_match(server_url, live_match.match_id).await?;
+        clocks.tick(&match_data);
+        render(live_match, &match_data, &clocks)?;
+
+        if !match_data.in_progress {
+            return Ok(!wait_for_quit(FINISHED_MATCH_DELAY)?);
+        }
+        if wait_for_quit(POLL_INTERVAL)? {
+            return Ok(false);
+        }
+    }
+}
+
+/// Wait for `duration`, true if q or Esc was pressed meanwhile
+fn wait_for_quit(duration: Duration) -> io::Result<bool> {
+    let deadline = Instant::now() + duration;
+    terminal::enable_raw_mode()?;
+
+    let result = loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break Ok(false);
+        }
+        match event::poll(remaining) {
+            Ok(true) => match event::read() {
+                Ok(Event::Key(key)) if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) => break Ok(true),
+                Ok(_) => {}
+                Err(e) => break Err(e),
+            },
+            Ok(false) => {}
+            Err(e) => break Err(e),
+        }
+    };
+
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn render(live_match: &LiveMatch, match_data: &Match, clocks: &Clocks) -> io::Result<()> {
+    clear_screen()?;
+    render_header(live_match, clocks);
+    render_game(live_match, match_data);
+    Ok(())
+}
+
+/// Board and result of a match as spectators see it
+pub fn render_game(live_match: &LiveMatch, match_data: &Match) {
+    match match_data.game_type {
+        GameType::TicTacToe => render_tic_tac_toe(match_data),
+        GameType::RockPaperScissors => render_rock_paper_scissors(live_match, match_data),
+        GameType::Briscola => render_briscola(live_match, match_data),
+        GameType::Chess => render_chess(match_data),
+    }
+
+    println!();
+    if !match_data.in_progress {
+        let result = match match_data.outcome {
+            Some(MatchOutcome::Player1Win) => format!("{} wins!", live_match.player1_name),
+            Some(MatchOutcome::Player2Win) => format!("{} wins!", live_match.player2_name),
+            Some(MatchOutcome::Draw) => "It's a draw!".to_string(),
+            None => "Match over".to_string(),
+        };
+        println!("  {}", result.bright_green().bold());
+    }
+}
+
+fn render_header(live_match: &LiveMatch, clocks: &Clocks) {
+    let player = |name: &str, index: usize| {
+        let marker = if clocks.waiting_on[index] { "▶ " } else { "  " };
+        let elapsed = clocks.elapsed[index].as_secs();
+        format!("{marker}{name}  {:02}:{:02}", elapsed / 60, elapsed % 60)
+    };
+
+    println!();
+    println!("{}", "═".repeat(WIDTH).bright_cyan());
+    println!("  {}", format!("{} · Match #{}", live_match.game_type, live_match.match_id).bright_cyan().bold());
+    println!(
+        "{}    {}",
+        player(&live_match.player1_name, 0).bright_blue().bold(),
+        player(&live_match.player2_name, 1).bright_magenta().bold()
+    );
+    println!("{}", "═".repeat(WIDTH).bright_cyan());
+    println!();
+}
+
+fn render_tic_tac_toe(match_data: &Match) {
+    let Ok(game_state) = serde_json::from_value::<TicTacToeGameState>(match_data.game_state.clone()) else {
+        return;
+    };
+    let size = game_state.board_size;
+    let separator = vec!["───────"; size].join("┼");
+
+    for row in 0..size {
+        for line in 0..3 {
+            let cells: Vec<String> = (0..size)
+                .map(|col| {
+                    let symbol = match (line, game_state.board[row * size + col]) {
+                        (1, 1) => "X".bright_blue().bold().to_string(),
+                        (1, 2) => "O".bright_magenta().bold().to_string(),
+                        _ => " ".to_string(),
+                    };
+                    format!("   {symbol}   ")
+                })
+                .collect();
+            println!("  {}", cells.join("│"));
+        }
+        if row < size - 1 {
+            println!("  {separator}");
+        }
+    }
+}
+
+fn render_rock_paper_scissors(live_match: &LiveMatch, match_data: &Match) {
+    let Ok(game_state) = serde_json::from_value::<RockPaperScissorsGameState>(match_data.game_state.clone()) else {
+        return;
+    };
+    let (p1_score, p2_score) = game_state.get_score();
+    let move_name = |rps_move: &Option<RockPaperScissorsMove>| match rps_move {
+        Some(RockPaperScissorsMove::Redacted) => "ready".to_string(),
+        Some(rps_move) => format!("{rps_move:?}"),
+        None => "…".to_string(),
+    };
+
+    println!(
+        "  {} {} - {} {}   {}",
+        live_match.player1_name.bright_blue(),
+        p1_score.to_string().bold(),
+        p2_score.to_string().bold(),
+        live_match.player2_name.bright_magenta(),
+        format!("(best of {})", game_state.best_of).dimmed()
+    );
+    println!();
+    for (round, (p1_move, p2_move)) in game_state.rounds.iter().enumerate() {
+        println!("  Round {:<3} {}  vs  {}", round + 1, pad_left(&move_name(p1_move), 10), pad_right(&move_name(p2_move), 10));
+    }
+}
+
+fn render_briscola(live_match: &LiveMatch, match_data: &Match) {
+    let Ok(game_state) = serde_json::from_value::<BriscolaGameState>(match_data.game_state.clone()) else {
+        return;
+    };
+    let (p1_score, p2_score) = game_state.get_score();
+
+    println!(
+        "  {} {} - {} {}",
+        live_match.player1_name.bright_blue(),
+        p1_score.to_string().bold(),
+        p2_score.to_string().bold(),
+        live_match.player2_name.bright_magenta()
+    );
+    println!(
+        "  Briscola: {}   Cards in deck: {}",
+        suit_name(game_state.briscola_suit).bold(),
+        game_state.cards_remaining_in_deck
+    );
+    println!();
+
+    let mut cards: Vec<Vec<String>> = game_state.trump_card.iter().map(|card| card_view(card.suit, card.rank)).collect();
+    cards.extend(game_state.table.iter().map(|(card, _)| card_view(card.suit, card.rank)));
+    if cards.is_empty() {
+        return;
+    }
+    for line in 0..cards[0].len() {
+        let row: Vec<&str> = cards.iter().map(|card| card[line].as_str()).collect();
+        println!("  {}", row.join("   "));
+    }
+    let mut labels = vec![];
+    if game_state.trump_card.is_some() {
+        labels.push(center("trump", card_width()));
+    }
+    labels.extend(game_state.table.iter().map(|(_, player)| {
+        let name = if *player == 1 { &live_match.player1_name } else { &live_match.player2_name };
+        center(&truncate(name, card_width()), card_width())
+    }));
+    println!("  {}", labels.join("   ").dimmed());
+}
+
+fn render_chess(match_data: &Match) {
+    let Ok(game_state) = serde_json::from_value::<ChessGameState>(match_data.game_state.clone()) else {
+        return;
+    };
+
+    for rank in (0..8).rev() {
+        for line in 0..3 {
+            let label = if line == 1 { format!("{} ", rank + 1) } else { "  ".to_string() };
+            let squares: String = (0..8)
+                .map(|file| {
+                    let piece = match ChessPosition::new(rank as u8, file as u8).and_then(|pos| game_state.get_piece(pos)) {
+                        Some(piece) if line == 1 => get_piece_symbol(piece),
+                        _ => " ",
+                    };
+                    let square = format!("  {}", pad_right(piece, 4));
+                    if (rank + file) % 2 == 0 {
+                        square.black().on_truecolor(181, 136, 99).to_string()
+                    } else {
+                        square.black().on_truecolor(240, 217, 181).to_string()
+                    }
+                })
+                .collect();
+            println!("  {label}{squares}");
+        }
+    }
+    let files: String = ('a'..='h').map(|file| format!("  {file}   ")).collect();
+    println!("    {files}");
+}
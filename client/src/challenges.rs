@@ -0,0 +1,213 @@
+use battld_common::{games::{game_type::GameType, matches::Match}, ClientMessage, MatchChallenge, ServerMessage};
+use colored::*;
+use rustyline::DefaultEditor;
+use std::io::{self, Write};
+
+use crate::api::player::fetch_pending_challenges;
+use crate::games;
+use crate::state::*;
+use crate::ui::*;
+
+const CHALLENGE_GAMES: [GameType; 3] = [GameType::TicTacToe, GameType::RockPaperScissors, GameType::Briscola];
+
+/// How a sent challenge was resolved
+enum ChallengeResult {
+    Started(Match),
+    Declined,
+    Expired,
+}
+
+pub async fn show_challenges(session: &mut SessionState) -> Result<(), Box<dyn std::error::Error>> {
+    if session.ws_client.is_none() {
+        session.connect_websocket().await?;
+    }
+
+    loop {
+        clear_screen()?;
+        println!("\n{}", "Loading challenges...".cyan());
+
+        let challenges = fetch_pending_challenges(session).await?;
+
+        clear_screen()?;
+        println!();
+        println!("{}", "═══════════════════════════════════════════════════════════════════".bright_cyan());
+        println!("{}", "                            CHALLENGES                             ".bright_cyan().bold());
+        println!("{}", "═══════════════════════════════════════════════════════════════════".bright_cyan());
+        println!();
+
+        if challenges.is_empty() {
+            println!("{}", "  No pending challenges".dimmed());
+        }
+        let now = battld_common::time() as i64;
+        for (index, challenge) in challenges.iter().enumerate() {
+            let minutes_left = ((challenge.expires_at - now).max(0) + 59) / 60;
+            println!(
+                "  [{}] {} challenges you to {} {}",
+                index.to_string().bright_yellow(),
+                challenge.challenger_name.bright_white().bold(),
+                challenge.game_type,
+                format!("(expires in {minutes_left}m)").dimmed()
+            );
+        }
+
+        println!();
+        println!("{}", "a N: accept | d N: decline | n: new challenge | r: refresh | q: back".dimmed());
+        print!("> ");
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let parts: Vec<&str> = input.split_whitespace().collect();
+
+        match parts.as_slice() {
+            ["a" | "d", index] => {
+                let Some(challenge) = index.parse::<usize>().ok().and_then(|i| challenges.get(i)) else {
+                    continue;
+                };
+                let accept = parts[0] == "a";
+                if let Some(game_match) = respond(session, challenge, accept).await? {
+                    games::resume_game(session, game_match).await?;
+                    return Ok(());
+                }
+            }
+            ["n"] => {
+                if let Some(game_match) = send_challenge(session).await? {
+                    games::resume_game(session, game_match).await?;
+                    return Ok(());
+                }
+            }
+            ["q"] => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+async fn respond(
+    session: &SessionState,
+    challenge: &MatchChallenge,
+    accept: bool,
+) -> Result<Option<Match>, Box<dyn std::error::Error>> {
+    let ws_client = session.ws_client.as_ref().ok_or("Not connected to WebSocket")?;
+    ws_client.get_messages().await;
+    ws_client.send(ClientMessage::RespondToChallenge { challenge_id: challenge.id, accept })?;
+
+    loop {
+        for msg in ws_client.get_messages().await {
+            match msg {
+                ServerMessage::MatchFound { match_data } if accept => return Ok(Some(match_data)),
+                ServerMessage::ChallengeDeclined { challenge_id } if challenge_id == challenge.id => return Ok(None),
+                ServerMessage::ChallengeExpired { challenge_id } if challenge_id == challenge.id => {
+                    show_notice("That challenge has expired.")?;
+                    return Ok(None);
+                }
+                ServerMessage::Error { .. } => {
+                    show_notice(&crate::ui::error_text(&msg).unwrap_or_default())?;
+                    return Ok(None);
+                }
+                ServerMessage::ServerBusy { retry_after } => {
+                    show_server_busy(retry_after)?;
+                    return Ok(None);
+                }
+                _ => {}
+            }
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    }
+}
+
+async fn send_challenge(session: &SessionState) -> Result<Option<Match>, Box<dyn std::error::Error>> {
+    let mut rl = DefaultEditor::new()?;
+
+    let player_id = loop {
+        let line = rl.readline("Player ID to challenge: ")?;
+        if let Ok(id) = line.trim().parse::<i64>() {
+            break id;
+        }
+        println!("{}", "Please enter a numeric player ID.".red());
+    };
+
+    for (index, game_type) in CHALLENGE_GAMES.iter().enumerate() {
+        println!("  {}. {}", (index + 1).to_string().bright_yellow(), game_type);
+    }
+    let game_type = loop {
+        let line = rl.readline("Game: ")?;
+        if let Some(game_type) = line.trim().parse::<usize>().ok().and_then(|i| CHALLENGE_GAMES.get(i.wrapping_sub(1))) {
+            break game_type.clone();
+        }
+        println!("{}", format!("Please enter 1-{}.", CHALLENGE_GAMES.len()).red());
+    };
+
+    challenge_player(session, player_id, game_type).await
+}
+
+/// Challenge `player_id` to a game and wait for their answer, returns the match once they accept
+pub async fn challenge_player(session: &SessionState, player_id: i64, game_type: GameType) -> Result<Option<Match>, Box<dyn std::error::Error>> {
+    let ws_client = session.ws_client.as_ref().ok_or("Not connected to WebSocket")?;
+    ws_client.get_messages().await;
+    ws_client.send(ClientMessage::ChallengePlayer {
+        player_id,
+        game_type,
+        options: serde_json::Value::Null,
+    })?;
+
+    let mut sent: Option<MatchChallenge> = None;
+    loop {
+        for msg in ws_client.get_messages().await {
+            match msg {
+                ServerMessage::ChallengeSent { challenge, delivered } => {
+                    if !delivered {
+                        show_notice(&format!(
+                            "{} is offline, they will see your challenge next time they log in.",
+                            challenge.challenged_name
+                        ))?;
+                        return Ok(None);
+                    }
+                    println!();
+                    println!("{}", format!("Waiting for {} to respond...", challenge.challenged_name).yellow());
+                    sent = Some(challenge);
+                }
+                ServerMessage::Error { .. } => {
+                    show_notice(&crate::ui::error_text(&msg).unwrap_or_default())?;
+                    return Ok(None);
+                }
+                ServerMessage::ServerBusy { retry_after } => {
+                    show_server_busy(retry_after)?;
+                    return Ok(None);
+                }
+                other => {
+                    if let Some(challenge) = &sent {
+                        match wait_result(other, challenge.id) {
+                            Some(ChallengeResult::Started(game_match)) => return Ok(Some(game_match)),
+                            Some(ChallengeResult::Declined) => {
+                                show_notice(&format!("{} declined your challenge.", challenge.challenged_name))?;
+                                return Ok(None);
+                            }
+                            Some(ChallengeResult::Expired) => {
+                                show_notice(&format!("{} did not answer in time.", challenge.challenged_name))?;
+                                return Ok(None);
+                            }
+                            None => {}
+                        }
+                    }
+                }
+            }
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    }
+}
+
+fn wait_result(msg: ServerMessage, challenge_id: i64) -> Option<ChallengeResult> {
+    match msg {
+        ServerMessage::MatchFound { match_data } => Some(ChallengeResult::Started(match_data)),
+        ServerMessage::ChallengeDeclined { challenge_id: id } if id == challenge_id => Some(ChallengeResult::Declined),
+        ServerMessage::ChallengeExpired { challenge_id: id } if id == challenge_id => Some(ChallengeResult::Expired),
+        _ => None,
+    }
+}
+
+fn show_notice(message: &str) -> io::Result<()> {
+    println!();
+    println!("{}", message.yellow());
+    println!("\nPress any key to continue...");
+    wait_for_keypress()
+}
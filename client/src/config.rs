@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
 use std::fs;
 
@@ -8,6 +9,41 @@ pub struct Config {
     pub private_key_path: Option<String>,
     pub public_key_path: Option<String>,
     pub server_url: Option<String>,
+    /// Ask before sending a chess move or a Briscola card
+    #[serde(default)]
+    pub confirm_moves: bool,
+    /// Executable run with a JSON event on stdin when a match is found, it is your turn or a match ends
+    #[serde(default)]
+    pub plugin: Option<String>,
+    /// URL of a JSON list of community servers, for the server browser
+    #[serde(default)]
+    pub server_registry_url: Option<String>,
+    /// Player id on each server used before, by server URL
+    #[serde(default)]
+    pub known_players: HashMap<String, i64>,
+    /// The terminal font draws chess pieces and box drawing characters two columns wide
+    #[serde(default)]
+    pub wide_glyphs: bool,
+    /// Seconds before a REST call is given up on, 10 if unset
+    #[serde(default)]
+    pub request_timeout_secs: Option<u64>,
+    /// Times a REST call is tried before failing, 3 if unset
+    #[serde(default)]
+    pub request_attempts: Option<u32>,
+    /// How game boards and tables are oriented
+    #[serde(default)]
+    pub perspective: Perspective,
+}
+
+/// Per-game layout options, the defaults match the classic layouts
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Perspective {
+    /// Draw the chess board from Black's side when playing Black
+    #[serde(default)]
+    pub flip_chess_board: bool,
+    /// Show the Briscola table right to left, the played card first and the briscola last
+    #[serde(default)]
+    pub mirror_briscola_table: bool,
 }
 
 impl Default for Config {
@@ -23,6 +59,14 @@ impl Default for Config {
             private_key_path: Some("private_key.pem".to_string()),
             public_key_path: Some("public_key.pem".to_string()),
             server_url: Some(server_url),
+            confirm_moves: false,
+            plugin: None,
+            server_registry_url: None,
+            known_players: HashMap::new(),
+            wide_glyphs: false,
+            request_timeout_secs: None,
+            request_attempts: None,
+            perspective: Perspective::default(),
         }
     }
 }
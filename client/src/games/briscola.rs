@@ -1,13 +1,17 @@
 use battld_common::{
     games::{
-        briscola::{BriscolaGameState, Card, Rank, Suit},
+        briscola::{BriscolaGameState, BriscolaOptions, Card, Rank, RoundState, Suit, BLITZ_CARD_SECS},
         game_type::GameType,
         matches::{Match, MatchEndReason, MatchOutcome},
+        players::Seat,
     },
     *,
 };
+use crate::games::{MoveConfirmation, ThinkingIndicator};
 use crate::state::SessionState;
+use crate::width::{display_width, pad_right};
 use colored::*;
+use rustyline::DefaultEditor;
 use std::io::{self, Write};
 use tokio::io::AsyncBufReadExt;
 
@@ -32,17 +36,19 @@ enum BriscolaUiState {
 }
 
 impl BriscolaUiState {
-    fn render(&self, my_player_number: i32) {
-        crate::ui::clear_screen().ok();
+    fn render(&self, my_seat: Seat) {
+        let mut frame = crate::ui::Frame::default();
+        super::render_connection(&mut frame);
+        super::render_turn_clock(&mut frame);
 
         match self {
             BriscolaUiState::WaitingForOpponentToJoin => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Briscola".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                println!("{}", "  Waiting for opponent to join...".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Briscola".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                frame.println("  Waiting for opponent to join...".yellow());
+                frame.newline();
             }
             BriscolaUiState::PlayingGame {
                 match_data,
@@ -51,231 +57,317 @@ impl BriscolaUiState {
             } => {
                 let game_state = parse_game_state(match_data);
 
-                println!();
-
-                // Previous round information
-                if let Some((first_card, second_card, winner)) = game_state.previous_round {
-                    let first_str = format_card(&first_card);
-                    let second_str = format_card(&second_card);
-                    let winner_str = if winner == my_player_number { "You" } else { "Opponent" };
-                    println!("  Previous round: {first_str} vs {second_str} - {winner_str} won");
-                    println!();
+                frame.newline();
+                crate::games::render_opponent(&mut frame, match_data, my_seat);
+                if game_state.blitz {
+                    frame.println(format!("  ⚡ Blitz: {BLITZ_CARD_SECS}s per card, the lowest one is played when time runs out").bright_magenta());
                 }
 
-                // Get card arts for the layout
-                let trump_art = if let Some(trump) = game_state.trump_card {
-                    card_view(trump.suit, trump.rank)
-                } else {
-                    vec![]
-                };
-
-                // Check if there's a card on the table (show the first card played)
-                let table_card_art = if !game_state.table.is_empty() {
-                    let (card, player) = game_state.table[0];
-                    let first_player_is_me = player == my_player_number;
-                    Some((card_view(card.suit, card.rank), first_player_is_me))
+                let choosing_trump = game_state.round_state == RoundState::ChoosingTrump;
+                if crate::ui::is_compact() {
+                    render_compact_table(&mut frame, &game_state, my_seat);
                 } else {
-                    None
-                };
-
-                // Line 1: Headers
-                let briscola_suit_str = match game_state.briscola_suit {
-                    Suit::Bastoni => "Bastoni",
-                    Suit::Coppe => "Coppe",
-                    Suit::Denari => "Denari",
-                    Suit::Spade => "Spade",
-                };
-
-                let deck_header = if trump_art.is_empty() { "     " } else { "Deck:" };
+                    // Previous round information
+                    if let Some((first_card, second_card, winner)) = game_state.previous_round {
+                        let first_str = format_card(&first_card);
+                        let second_str = format_card(&second_card);
+                        let winner_str = if winner == my_seat.symbol() { "You" } else { "Opponent" };
+                        frame.println(format!("  Previous round: {first_str} vs {second_str} - {winner_str} won"));
+                        frame.newline();
+                    }
 
-                let table_header = if let Some((_, is_me)) = &table_card_art {
-                    if *is_me { "You played:" } else { "Opponent played:" }
-                } else {
-                    ""
-                };
-                println!("  Briscola:   {deck_header}          {table_header}   ");
-
-                // Lines 2-7: Cards side by side
-                // Prepare briscola text (suit name or empty if card shown)
-                let briscola_text = if trump_art.is_empty() {
-                    [
-                        briscola_suit_str.to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                    ]
-                } else {
-                    ["".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string()]
-                };
+                    // Get card arts for the layout
+                    let trump_art = if let Some(trump) = game_state.trump_card {
+                        card_view(trump.suit, trump.rank)
+                    } else {
+                        vec![]
+                    };
+
+                    // Check if there's a card on the table (show the first card played)
+                    let table_card_art = if !game_state.table.is_empty() {
+                        let (card, player) = game_state.table[0];
+                        let first_player_is_me = player == my_seat.symbol();
+                        Some((card_view(card.suit, card.rank), first_player_is_me))
+                    } else {
+                        None
+                    };
 
-                let deck_text = if trump_art.is_empty() {
-                    // No deck info when trump is drawn
-                    [
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                    ]
-                } else {
-                    [
-                        "".to_string(),
-                        format!("{}", game_state.cards_remaining_in_deck),
-                        "cards".to_string(),
-                        "left".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                    ]
-                };
+                    // Line 1: Headers
+                    let briscola_suit_str = if choosing_trump {
+                        "?"
+                    } else {
+                        suit_name(game_state.briscola_suit)
+                    };
 
-                for line_idx in 0..6 {
-                    print!("  ");
+                    let deck_header = if game_state.cards_remaining_in_deck == 0 { "     " } else { "Deck:" };
 
-                    // Briscola card or suit text
-                    if !trump_art.is_empty() {
-                        print!("{}", trump_art[line_idx].yellow());
+                    let table_header = if let Some((_, is_me)) = &table_card_art {
+                        if *is_me { "You played:" } else { "Opponent played:" }
+                    } else {
+                        ""
+                    };
+                    if crate::games::perspective().mirror_briscola_table {
+                        frame.println(format!("  {}   {}Briscola:", pad_right(table_header, card_width()), pad_right(deck_header, 15)));
                     } else {
-                        print!("{:<9}", briscola_text[line_idx]); // 9 chars for card width
+                        frame.println(format!("  Briscola:   {deck_header}          {table_header}   "));
                     }
 
-                    print!("   ");
-
-                    // Deck as text
-                    print!("{:<15}", deck_text[line_idx]);
-
-                    // Table card or empty space
-                    if let Some((art, _)) = &table_card_art {
-                        print!("{}", art[line_idx]);
+                    // Lines 2-7: Cards side by side
+                    // Prepare briscola text (suit name or empty if card shown)
+                    let briscola_text = if trump_art.is_empty() {
+                        [
+                            briscola_suit_str.to_string(),
+                            "".to_string(),
+                            "".to_string(),
+                            "".to_string(),
+                            "".to_string(),
+                            "".to_string(),
+                        ]
+                    } else {
+                        ["".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string()]
+                    };
+
+                    let deck_text = if game_state.cards_remaining_in_deck == 0 {
+                        // No deck info once the last card is drawn
+                        [
+                            "".to_string(),
+                            "".to_string(),
+                            "".to_string(),
+                            "".to_string(),
+                            "".to_string(),
+                            "".to_string(),
+                        ]
                     } else {
-                        print!("         "); // 9 spaces
+                        [
+                            "".to_string(),
+                            format!("{}", game_state.cards_remaining_in_deck),
+                            "cards".to_string(),
+                            "left".to_string(),
+                            "".to_string(),
+                            "".to_string(),
+                        ]
+                    };
+
+                    let mirrored = crate::games::perspective().mirror_briscola_table;
+                    for line_idx in 0..6 {
+                        // Briscola card or suit text
+                        let briscola_column = if !trump_art.is_empty() {
+                            trump_art[line_idx].yellow().to_string()
+                        } else {
+                            pad_right(&briscola_text[line_idx], card_width())
+                        };
+
+                        // Deck as text
+                        let deck_column = pad_right(&deck_text[line_idx], 15);
+
+                        // Table card or empty space
+                        let table_column = if let Some((art, _)) = &table_card_art {
+                            art[line_idx].clone()
+                        } else {
+                            " ".repeat(card_width())
+                        };
+
+                        if mirrored {
+                            frame.println(format!("  {table_column}   {deck_column}{briscola_column}"));
+                        } else {
+                            frame.println(format!("  {briscola_column}   {deck_column}{table_column}"));
+                        }
                     }
 
-                    println!();
-                }
-
-                println!();
+                    frame.newline();
 
-                // Your hand
-                let my_hand = if my_player_number == 1 {
-                    &game_state.player1_hand
-                } else {
-                    &game_state.player2_hand
-                };
-
-                println!("  Your hand:");
-
-                if !my_hand.is_empty() {
-                    let hand_arts: Vec<Vec<String>> = my_hand
-                        .iter()
-                        .map(|card| card_view(card.suit, card.rank))
-                        .collect();
-
-                    // Display cards side by side
-                    for line_idx in 0..6 {
-                        print!("  ");
-                        for card_art in &hand_arts {
-                            print!("{}  ", card_art[line_idx]);
+                    // Your hand
+                    let my_hand = if my_seat == Seat::FIRST {
+                        &game_state.player1_hand
+                    } else {
+                        &game_state.player2_hand
+                    };
+
+                    frame.println("  Your hand:");
+
+                    if !my_hand.is_empty() {
+                        let hand_arts: Vec<Vec<String>> = my_hand
+                            .iter()
+                            .map(|card| card_view(card.suit, card.rank))
+                            .collect();
+
+                        // Display cards side by side
+                        for line_idx in 0..6 {
+                            frame.print("  ");
+                            for card_art in &hand_arts {
+                                frame.print(format!("{}  ", card_art[line_idx]));
+                            }
+                            frame.newline();
                         }
-                        println!();
-                    }
 
-                    // Card indices
-                    print!("     ");
-                    for i in 0..my_hand.len() {
-                        print!("[{i}]        ");
+                        // Card indices
+                        frame.print("     ");
+                        for i in 0..my_hand.len() {
+                            frame.print(pad_right(&format!("[{i}]"), card_width() + 2));
+                        }
+                        frame.newline();
                     }
-                    println!();
                 }
 
-                println!();
+                frame.newline();
 
                 // Input prompt or waiting message
                 if *opponent_disconnected {
-                    println!("  {}", "Opponent disconnected. Waiting for reconnection...".yellow());
+                    frame.println(format!("  {}", "Opponent disconnected. Waiting for reconnection...".yellow()));
+                } else if choosing_trump && *your_turn {
+                    frame.println(format!("  {}", "Declare the briscola suit: [b]astoni, [c]oppe, [d]enari or [s]pade".bright_green().bold()));
+                    frame.print("  > ");
+                } else if choosing_trump {
+                    frame.println(format!("  {}", "Opponent is choosing the briscola suit...".dimmed()));
                 } else if *your_turn {
-                    println!("  {}", "Your turn! Enter card index:".bright_green().bold());
-                    print!("  > ");
-                    io::stdout().flush().ok();
+                    frame.println(format!("  {}", "Your turn! Enter card index:".bright_green().bold()));
+                    frame.print("  > ");
                 } else {
-                    println!("  {}", "Waiting for opponent...".dimmed());
+                    frame.println(format!("  {}", "Waiting for opponent...".dimmed()));
                 }
             }
             BriscolaUiState::WaitingForOpponentToReconnect { match_data } => {
                 let game_state = parse_game_state(match_data);
 
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Briscola".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Briscola".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
 
                 // Show current game state
                 let (p1_score, p2_score) = game_state.get_score();
-                let (my_score, opp_score) = if my_player_number == 1 {
+                let (my_score, opp_score) = if my_seat == Seat::FIRST {
                     (p1_score, p2_score)
                 } else {
                     (p2_score, p1_score)
                 };
-                println!("  Score: You {my_score} - {opp_score} Opponent");
-                println!();
+                let opponent = crate::games::opponent_label(match_data, my_seat);
+                frame.println(format!("  Score: You {my_score} - {opp_score} {opponent}"));
+                frame.newline();
 
-                println!("{}", "  Opponent disconnected. Waiting for reconnection...".yellow());
-                println!();
+                frame.println("  Opponent disconnected. Waiting for reconnection...".yellow());
+                frame.newline();
             }
             BriscolaUiState::MatchEndedYouWon(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Briscola".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_final_results(match_data, my_player_number);
-                println!();
-                println!("{}", "  YOU WON! 🎉".bright_green().bold());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Briscola".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_final_results(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  YOU WON! 🎉".bright_green().bold());
+                frame.newline();
             }
             BriscolaUiState::MatchEndedYouLost(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Briscola".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_final_results(match_data, my_player_number);
-                println!();
-                println!("{}", "  You lost.".red());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Briscola".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_final_results(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  You lost.".red());
+                frame.newline();
             }
             BriscolaUiState::MatchEndedDraw(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Briscola".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_final_results(match_data, my_player_number);
-                println!();
-                println!("{}", "  It's a draw!".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Briscola".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_final_results(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  It's a draw!".yellow());
+                frame.newline();
             }
             BriscolaUiState::MatchEndedOpponentDisconnected(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Briscola".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_final_results(match_data, my_player_number);
-                println!();
-                println!("{}", "  Match ended - Opponent disconnected.".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Briscola".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_final_results(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  Match ended - Opponent disconnected.".yellow());
+                frame.newline();
             }
         }
+        crate::ui::present(&frame).ok();
     }
 }
 
-/// Format a card for display
-fn format_card(card: &Card) -> String {
-    let suit_str = match card.suit {
+pub fn suit_name(suit: Suit) -> &'static str {
+    match suit {
         Suit::Bastoni => "Bastoni",
         Suit::Coppe => "Coppe",
         Suit::Denari => "Denari",
         Suit::Spade => "Spade",
+    }
+}
+
+fn parse_suit(input: &str) -> Option<Suit> {
+    [Suit::Bastoni, Suit::Coppe, Suit::Denari, Suit::Spade]
+        .into_iter()
+        .find(|suit| {
+            let name = suit_name(*suit).to_lowercase();
+            input == name || input == &name[..1]
+        })
+}
+
+/// Two columns for a card, such as "A♠", with the suits of the French deck standing in for the Italian ones
+pub fn mini_card(card: &Card) -> String {
+    let suit = match card.suit {
+        Suit::Bastoni => "♣",
+        Suit::Coppe => "♥",
+        Suit::Denari => "♦",
+        Suit::Spade => "♠",
     };
+    format!("{}{suit}", rank_symbol(card.rank))
+}
+
+fn rank_symbol(rank: Rank) -> &'static str {
+    match rank {
+        Rank::Ace => "A",
+        Rank::Two => "2",
+        Rank::Three => "3",
+        Rank::Four => "4",
+        Rank::Five => "5",
+        Rank::Six => "6",
+        Rank::Seven => "7",
+        Rank::Jack => "J",
+        Rank::Knight => "C", // Cavallo
+        Rank::King => "K",
+    }
+}
+
+/// The table and the hand in a few short lines, for narrow terminals
+fn render_compact_table(frame: &mut crate::ui::Frame, game_state: &BriscolaGameState, my_seat: Seat) {
+    if let Some((first_card, second_card, winner)) = game_state.previous_round {
+        let winner_str = if winner == my_seat.symbol() { "you" } else { "opp" };
+        frame.println(format!("  Last: {} {} - {winner_str}", mini_card(&first_card), mini_card(&second_card)));
+    }
+
+    let trump = match (game_state.trump_card, game_state.round_state == RoundState::ChoosingTrump) {
+        (_, true) => "?".to_string(),
+        (Some(trump), _) => mini_card(&trump).yellow().to_string(),
+        (None, _) => suit_name(game_state.briscola_suit).to_string(),
+    };
+    let mut line = format!("  Trump {trump}");
+    if game_state.cards_remaining_in_deck > 0 {
+        line.push_str(&format!("  Deck {}", game_state.cards_remaining_in_deck));
+    }
+    if let Some((card, player)) = game_state.table.first() {
+        let who = if *player == my_seat.symbol() { "you" } else { "opp" };
+        line.push_str(&format!("  Table {} ({who})", mini_card(card)));
+    }
+    frame.println(line);
+    frame.newline();
+
+    let my_hand = if my_seat == Seat::FIRST { &game_state.player1_hand } else { &game_state.player2_hand };
+    let hand: Vec<String> = my_hand.iter().enumerate().map(|(i, card)| format!("[{i}] {}", mini_card(card))).collect();
+    frame.println(format!("  {}", hand.join(" ")));
+}
+
+/// Format a card for display
+fn format_card(card: &Card) -> String {
+    let suit_str = suit_name(card.suit);
     let rank_str = match card.rank {
         Rank::Ace => "A",
         Rank::Two => "2",
@@ -291,24 +383,45 @@ fn format_card(card: &Card) -> String {
     format!("{rank_str} {suit_str}")
 }
 
-fn render_final_results(match_data: &Match, my_player_number: i32) {
+fn render_final_results(frame: &mut crate::ui::Frame, match_data: &Match, my_seat: Seat) {
     if let Ok(game_state) = serde_json::from_value::<BriscolaGameState>(match_data.game_state.clone()) {
         let (p1_score, p2_score) = game_state.get_score();
-        let (my_score, opp_score) = if my_player_number == 1 {
+        let (my_score, opp_score) = if my_seat == Seat::FIRST {
             (p1_score, p2_score)
         } else {
             (p2_score, p1_score)
         };
 
-        println!("{}", "  Final Score:".bold());
-        println!(
+        frame.println("  Final Score:".bold());
+        frame.println(format!(
             "    You: {} points",
             my_score.to_string().bright_green()
-        );
-        println!("    Opponent: {} points", opp_score.to_string().red());
+        ));
+        frame.println(format!(
+            "    {}: {} points",
+            crate::games::opponent_label(match_data, my_seat),
+            opp_score.to_string().red()
+        ));
     }
 }
 
+/// Rules and controls shown by `?`
+pub fn help(match_data: Option<&Match>) -> Vec<String> {
+    let mut lines = vec![
+        "Card points: Ace 11, Three 10, King 4, Knight 3, Jack 2, the others none".to_string(),
+        "From highest to lowest: Ace, Three, King, Knight, Jack, 7, 6, 5, 4, 2".to_string(),
+        "A trick goes to the highest briscola played, otherwise to the highest card of the suit led".to_string(),
+        "The winner of a trick draws first and leads the next one".to_string(),
+        "More than 60 of the 120 points wins the match, 60 each is a draw".to_string(),
+        "Enter the index of a card in your hand to play it, counting from 0".to_string(),
+        "Without a card turned up, the first player picks the briscola suit with b, c, d or s".to_string(),
+    ];
+    if match_data.is_some_and(|match_data| parse_game_state(match_data).blitz) {
+        lines.push(format!("Blitz: {BLITZ_CARD_SECS}s per card, the lowest one in your hand is played when time runs out"));
+    }
+    lines
+}
+
 fn parse_game_state(match_data: &Match) -> BriscolaGameState {
     serde_json::from_value::<BriscolaGameState>(match_data.game_state.clone())
         .unwrap_or_else(|_| BriscolaGameState::new())
@@ -319,7 +432,7 @@ fn handle_player_disconnected(
     my_player_id: i64,
     ui_state: &BriscolaUiState,
     opponent_disconnected: &mut bool,
-    _my_number: i32,
+    _my_seat: Seat,
 ) -> Option<BriscolaUiState> {
     if player_id == my_player_id {
         return None;
@@ -344,7 +457,7 @@ fn handle_player_disconnected(
 fn handle_match_ended(
     reason: &MatchEndReason,
     ui_state: &BriscolaUiState,
-    my_number: Option<i32>,
+    my_seat: Option<Seat>,
 ) -> BriscolaUiState {
     let final_match = match ui_state {
         BriscolaUiState::PlayingGame { match_data, .. }
@@ -356,25 +469,42 @@ fn handle_match_ended(
         MatchEndReason::Disconnection => {
             BriscolaUiState::MatchEndedOpponentDisconnected(final_match)
         }
-        MatchEndReason::Ended => determine_match_end_state(&final_match, my_number),
+        MatchEndReason::Ended | MatchEndReason::Forfeit | MatchEndReason::Resignation | MatchEndReason::DrawAgreed => determine_match_end_state(&final_match, my_seat),
     }
 }
 
+/// A late move was rejected because the match already ended, show the outcome the server reported
+fn handle_match_already_finished(
+    outcome: Option<MatchOutcome>,
+    ui_state: &BriscolaUiState,
+    my_seat: Option<Seat>,
+) -> BriscolaUiState {
+    let mut final_match = match ui_state {
+        BriscolaUiState::PlayingGame { match_data, .. }
+        | BriscolaUiState::WaitingForOpponentToReconnect { match_data } => match_data.clone(),
+        _ => return ui_state.clone(),
+    };
+
+    final_match.outcome = outcome;
+    final_match.in_progress = false;
+    determine_match_end_state(&final_match, my_seat)
+}
+
 fn determine_match_end_state(
     match_data: &Match,
-    my_number: Option<i32>,
+    my_seat: Option<Seat>,
 ) -> BriscolaUiState {
     if let Some(outcome) = &match_data.outcome {
         match outcome {
             MatchOutcome::Player1Win => {
-                if my_number == Some(1) {
+                if my_seat == Some(Seat::FIRST) {
                     BriscolaUiState::MatchEndedYouWon(match_data.clone())
                 } else {
                     BriscolaUiState::MatchEndedYouLost(match_data.clone())
                 }
             }
             MatchOutcome::Player2Win => {
-                if my_number == Some(2) {
+                if my_seat == Some(Seat::SECOND) {
                     BriscolaUiState::MatchEndedYouWon(match_data.clone())
                 } else {
                     BriscolaUiState::MatchEndedYouLost(match_data.clone())
@@ -390,29 +520,25 @@ fn determine_match_end_state(
 fn handle_match_found_or_update(
     match_data: &Match,
     my_player_id: i64,
-    my_number: &mut Option<i32>,
+    my_seat: &mut Option<Seat>,
     opponent_disconnected: &mut bool,
     ui_state: &BriscolaUiState,
 ) -> Result<Option<BriscolaUiState>, Box<dyn std::error::Error>> {
     // Determine player number
-    if my_number.is_none() {
-        *my_number = Some(if match_data.player1_id == my_player_id {
-            1
-        } else {
-            2
-        });
+    if my_seat.is_none() {
+        *my_seat = Some(super::my_seat(match_data, my_player_id));
     }
 
     // Check if match has ended
     if !match_data.in_progress {
-        return Ok(Some(determine_match_end_state(match_data, *my_number)));
+        return Ok(Some(determine_match_end_state(match_data, *my_seat)));
     }
 
     // Parse game state
     let game_state = serde_json::from_value::<BriscolaGameState>(match_data.game_state.clone())?;
 
     // Determine if it's your turn
-    let your_turn = game_state.current_player == my_number.unwrap();
+    let your_turn = game_state.current_player == my_seat.unwrap().symbol();
 
     // Check if we're transitioning to a state where we can play
     let was_waiting = matches!(
@@ -443,11 +569,11 @@ fn handle_game_state_update(
     match_data: &Match,
     ui_state: &BriscolaUiState,
     my_player_id: i64,
-    my_number: &mut Option<i32>,
+    my_seat: &mut Option<Seat>,
     opponent_disconnected: &mut bool,
 ) -> Option<BriscolaUiState> {
     // Use the same logic as match found/update
-    match handle_match_found_or_update(match_data, my_player_id, my_number, opponent_disconnected, ui_state)
+    match handle_match_found_or_update(match_data, my_player_id, my_seat, opponent_disconnected, ui_state)
     {
         Ok(Some(new_state)) => Some(new_state),
         _ => None,
@@ -459,8 +585,31 @@ fn handle_user_input(
     ui_state: &BriscolaUiState,
     opponent_disconnected: bool,
     ws_client: &crate::websocket::WebSocketClient,
-    my_number: i32,
+    confirmation: &mut MoveConfirmation,
+    my_seat: Seat,
 ) -> Result<Option<BriscolaUiState>, Box<dyn std::error::Error>> {
+    if let Some(sent) = confirmation.answer(input_str, ws_client)? {
+        return Ok(if sent { turn_passed(ui_state, opponent_disconnected) } else { None });
+    }
+
+    let choosing_trump = matches!(
+        ui_state,
+        BriscolaUiState::PlayingGame { match_data, .. } if parse_game_state(match_data).round_state == RoundState::ChoosingTrump
+    );
+
+    if choosing_trump {
+        let Some(suit) = parse_suit(input_str) else {
+            println!("{}", "Invalid suit. Please enter b, c, d or s.".red());
+            print!("  > ");
+            io::stdout().flush()?;
+            return Ok(None);
+        };
+        ws_client.send(ClientMessage::MakeMove {
+            move_data: serde_json::json!({ "trump_suit": suit }),
+        })?;
+        return Ok(None);
+    }
+
     // Parse card index
     let card_index = match input_str.parse::<usize>() {
         Ok(idx) => idx,
@@ -473,9 +622,10 @@ fn handle_user_input(
     };
 
     // Validate against hand size
+    let mut description = format!("Play card {card_index}");
     if let BriscolaUiState::PlayingGame { match_data, .. } = ui_state {
         let game_state = parse_game_state(match_data);
-        let my_hand = if my_number == 1 {
+        let my_hand = if my_seat == Seat::FIRST {
             &game_state.player1_hand
         } else {
             &game_state.player2_hand
@@ -494,47 +644,62 @@ fn handle_user_input(
             io::stdout().flush()?;
             return Ok(None);
         }
+        description = format!("Play {}", format_card(&my_hand[card_index]));
     }
 
     // Send move to server
     let move_data = serde_json::json!({
         "card_index": card_index
     });
-    ws_client.send(ClientMessage::MakeMove { move_data })?;
+    if !confirmation.submit(ws_client, &description, move_data)? {
+        return Ok(None);
+    }
 
-    // Update UI state
-    if let BriscolaUiState::PlayingGame { match_data, .. } = ui_state {
-        let new_state = if opponent_disconnected {
-            BriscolaUiState::WaitingForOpponentToReconnect {
-                match_data: match_data.clone(),
-            }
-        } else {
-            BriscolaUiState::PlayingGame {
-                match_data: match_data.clone(),
-                your_turn: false,
-                opponent_disconnected: false,
-            }
-        };
-        Ok(Some(new_state))
+    Ok(turn_passed(ui_state, opponent_disconnected))
+}
+
+/// Waiting screen once our card is sent
+fn turn_passed(ui_state: &BriscolaUiState, opponent_disconnected: bool) -> Option<BriscolaUiState> {
+    let BriscolaUiState::PlayingGame { match_data, .. } = ui_state else {
+        return None;
+    };
+    Some(if opponent_disconnected {
+        BriscolaUiState::WaitingForOpponentToReconnect {
+            match_data: match_data.clone(),
+        }
     } else {
-        Ok(None)
-    }
+        BriscolaUiState::PlayingGame {
+            match_data: match_data.clone(),
+            your_turn: false,
+            opponent_disconnected: false,
+        }
+    })
 }
 
 async fn run_game_loop(
     ws_client: &crate::websocket::WebSocketClient,
     my_player_id: i64,
     initial_state: BriscolaUiState,
-    initial_my_number: Option<i32>,
+    initial_my_seat: Option<Seat>,
+    confirm_moves: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut my_number = initial_my_number;
+    let mut my_seat = initial_my_seat;
     let mut ui_state = initial_state;
     let mut stdin_reader = tokio::io::BufReader::new(tokio::io::stdin());
     let mut input_line = String::new();
     let mut opponent_disconnected = false;
+    let mut thinking = ThinkingIndicator::default();
+    let mut confirmation = MoveConfirmation::new(confirm_moves);
+    let mut resumed = crate::suspend::resumed();
+    super::reset_turn_clock();
 
     // Initial render
-    ui_state.render(my_number.unwrap_or(1));
+    let mut waiting_room = crate::waiting_room::WaitingRoom::default();
+
+    ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+    if matches!(ui_state, BriscolaUiState::WaitingForOpponentToJoin) {
+        waiting_room.render();
+    }
 
     loop {
         let waiting_for_input = matches!(
@@ -546,33 +711,89 @@ async fn run_game_loop(
             }
         );
 
+        let opponent_turn = matches!(ui_state, BriscolaUiState::PlayingGame { your_turn: false, .. });
+
         tokio::select! {
+            Ok(()) = resumed.recv() => {
+                crate::games::request_state(ws_client).await;
+                ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                thinking.render();
+            }
+
             _ = tokio::time::sleep(tokio::time::Duration::from_millis(200)) => {
+                if matches!(ui_state, BriscolaUiState::WaitingForOpponentToJoin) && waiting_room.handle_keys()? {
+                    if waiting_room.take_leave_request() {
+                        ws_client.send(ClientMessage::LeaveMatchmaking)?;
+                    }
+                    ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                    waiting_room.render();
+                }
+
+                if waiting_for_input {
+                    thinking.send_heartbeat(ws_client);
+                }
+
+                super::warn_turn_clock()?;
+                if crate::websocket::take_connection_change() || super::turn_clock_ticked() {
+                    ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                }
                 let messages = ws_client.get_messages().await;
 
                 for msg in messages {
-                    if let ServerMessage::Error { message } = &msg {
+                    if super::show_idle_notice(&msg)? {
+                        continue;
+                    }
+
+                    if super::track_turn_clock(&msg, my_player_id) {
+                        ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                        continue;
+                    }
+
+                    match super::answer_ready_check(&msg, ws_client)? {
+                        super::ReadyCheckStep::Handled => continue,
+                        super::ReadyCheckStep::LeftQueue => return Ok(()),
+                        super::ReadyCheckStep::NotReadyCheck => {}
+                    }
+                    if matches!(msg, ServerMessage::MatchmakingCancelled) {
+                        return Ok(());
+                    }
+                    if super::explain_matchmaking_rejection(&msg, ws_client).await? {
+                        return Ok(());
+                    }
+
+                    if let Some(message) = crate::ui::error_text(&msg) {
                         println!("\n{}", format!("Error: {message}").red());
                         io::stdout().flush()?;
                         continue;
                     }
 
+                    if let ServerMessage::ServerBusy { retry_after } = &msg {
+                        crate::ui::show_server_busy(*retry_after)?;
+                        return Ok(());
+                    }
+
                     match &msg {
-                        ServerMessage::PlayerDisconnected { player_id } => {
+                        ServerMessage::PlayerDisconnected { player_id, .. } => {
                             if let Some(new_state) = handle_player_disconnected(
                                 *player_id,
                                 my_player_id,
                                 &ui_state,
                                 &mut opponent_disconnected,
-                                my_number.unwrap_or(1),
+                                my_seat.unwrap_or(Seat::FIRST),
                             ) {
                                 ui_state = new_state;
-                                ui_state.render(my_number.unwrap_or(1));
+                                ui_state.render(my_seat.unwrap_or(Seat::FIRST));
                             }
                         }
-                        ServerMessage::MatchEnded { reason } => {
-                            ui_state = handle_match_ended(reason, &ui_state, my_number);
-                            ui_state.render(my_number.unwrap_or(1));
+                        ServerMessage::MatchEnded { reason, summary } => {
+                            ui_state = handle_match_ended(reason, &ui_state, my_seat);
+                            ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                            super::render_summary(summary.as_ref());
+                            return super::offer_rematch(ws_client, my_player_id).await;
+                        }
+                        ServerMessage::MatchAlreadyFinished { outcome, .. } => {
+                            ui_state = handle_match_already_finished(outcome.clone(), &ui_state, my_seat);
+                            ui_state.render(my_seat.unwrap_or(Seat::FIRST));
                             println!("\nPress any key to return to main menu...");
                             io::stdout().flush()?;
                             crate::ui::wait_for_keypress()?;
@@ -582,7 +803,7 @@ async fn run_game_loop(
                             if let Ok(Some(new_state)) = handle_match_found_or_update(
                                 match_data,
                                 my_player_id,
-                                &mut my_number,
+                                &mut my_seat,
                                 &mut opponent_disconnected,
                                 &ui_state,
                             ) {
@@ -595,13 +816,10 @@ async fn run_game_loop(
                                 );
 
                                 ui_state = new_state;
-                                ui_state.render(my_number.unwrap());
+                                ui_state.render(my_seat.unwrap());
 
                                 if should_exit {
-                                    println!("\nPress any key to return to main menu...");
-                                    io::stdout().flush()?;
-                                    crate::ui::wait_for_keypress()?;
-                                    return Ok(());
+                                    return super::offer_rematch(ws_client, my_player_id).await;
                                 }
 
                                 input_line.clear();
@@ -612,17 +830,30 @@ async fn run_game_loop(
                                 match_data,
                                 &ui_state,
                                 my_player_id,
-                                &mut my_number,
+                                &mut my_seat,
                                 &mut opponent_disconnected,
                             ) {
+                                thinking.reset();
+                                confirmation.cancel();
                                 ui_state = new_state;
-                                ui_state.render(my_number.unwrap());
+                                ui_state.render(my_seat.unwrap());
                                 input_line.clear();
                             }
                         }
+                        ServerMessage::OpponentThinking { .. } => {
+                            let appeared = thinking.opponent_thinking();
+                            if appeared && opponent_turn {
+                                ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                                thinking.render();
+                            }
+                        }
                         _ => {}
                     }
                 }
+
+                if thinking.expired() && opponent_turn {
+                    ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                }
             }
 
             result = stdin_reader.read_line(&mut input_line), if waiting_for_input => {
@@ -633,16 +864,31 @@ async fn run_game_loop(
                     if input_str.is_empty() {
                         continue;
                     }
+                    if crate::bugreport::is_command(&input_str) {
+                        crate::bugreport::run(Some(ws_client)).await;
+                        print!("  > ");
+                        io::stdout().flush().ok();
+                        continue;
+                    }
+                    if super::handle_match_command(&input_str, ws_client, my_player_id).await? {
+                        continue;
+                    }
+                    if super::is_help_command(&input_str) {
+                        super::show_help(&GameType::Briscola, ws_client.get_current_match().await.as_ref())?;
+                        ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                        continue;
+                    }
 
                     if let Ok(Some(new_state)) = handle_user_input(
                         &input_str,
                         &ui_state,
                         opponent_disconnected,
                         ws_client,
-                        my_number.unwrap_or(1),
+                        &mut confirmation,
+                        my_seat.unwrap_or(Seat::FIRST),
                     ) {
                         ui_state = new_state;
-                        ui_state.render(my_number.unwrap());
+                        ui_state.render(my_seat.unwrap());
                     }
                 }
             }
@@ -661,17 +907,43 @@ pub async fn start_game(
     let ws_client = session.ws_client.as_ref().unwrap();
     let my_player_id = session.player_id.ok_or("No player ID in session")?;
 
-    ws_client.send(ClientMessage::JoinMatchmaking { game_type })?;
+    let options = read_game_options()?;
+    let series_length = super::read_series_length()?;
+    ws_client.send(ClientMessage::JoinMatchmaking {
+        game_type,
+        options: serde_json::to_value(options)?,
+        series_length,
+    })?;
 
     run_game_loop(
         ws_client,
         my_player_id,
         BriscolaUiState::WaitingForOpponentToJoin,
         None,
+        session.config.confirm_moves,
     )
     .await
 }
 
+fn read_game_options() -> Result<BriscolaOptions, Box<dyn std::error::Error>> {
+    let mut rl = DefaultEditor::new()?;
+    Ok(BriscolaOptions {
+        declared_trump: read_yes_no(&mut rl, "Declared briscola, the first player picks the trump suit? (y/N): ")?,
+        blitz: read_yes_no(&mut rl, &format!("Blitz, {BLITZ_CARD_SECS} seconds per card? (y/N): "))?,
+    })
+}
+
+fn read_yes_no(rl: &mut DefaultEditor, prompt: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    loop {
+        let line = rl.readline(prompt)?;
+        match line.trim().to_lowercase().as_str() {
+            "" | "n" | "no" => return Ok(false),
+            "y" | "yes" => return Ok(true),
+            _ => println!("{}", "Please answer y or n.".red()),
+        }
+    }
+}
+
 pub async fn resume_game(
     session: &mut SessionState,
     game_match: Match,
@@ -683,14 +955,10 @@ pub async fn resume_game(
     let ws_client = session.ws_client.as_ref().unwrap();
     let my_player_id = session.player_id.ok_or("No player ID in session")?;
 
-    let my_number = if game_match.player1_id == my_player_id {
-        Some(1)
-    } else {
-        Some(2)
-    };
+    let my_seat = super::my_seat(&game_match, my_player_id);
 
     let game_state = parse_game_state(&game_match);
-    let your_turn = game_state.current_player == my_number.unwrap();
+    let your_turn = game_state.current_player == my_seat.symbol();
 
     let initial_state = BriscolaUiState::PlayingGame {
         match_data: game_match,
@@ -698,7 +966,7 @@ pub async fn resume_game(
         opponent_disconnected: false,
     };
 
-    run_game_loop(ws_client, my_player_id, initial_state, my_number).await
+    run_game_loop(ws_client, my_player_id, initial_state, Some(my_seat), session.config.confirm_moves).await
 }
 
 pub fn covered_card() -> Vec<String> {
@@ -747,19 +1015,13 @@ pub fn covered_card() -> Vec<String> {
 }
 
 /// Returns ASCII art representation of a card as a vector of lines
+/// Columns taken by a card drawn by `card_view`
+pub fn card_width() -> usize {
+    display_width(&card_view(Suit::Bastoni, Rank::Ace)[0])
+}
+
 pub fn card_view(suit: Suit, rank: Rank) -> Vec<String> {
-    let rank_str = match rank {
-        Rank::Ace => "A",
-        Rank::Two => "2",
-        Rank::Three => "3",
-        Rank::Four => "4",
-        Rank::Five => "5",
-        Rank::Six => "6",
-        Rank::Seven => "7",
-        Rank::Jack => "J",
-        Rank::Knight => "C",
-        Rank::King => "K",
-    };
+    let rank_str = rank_symbol(rank);
 
     let suit_char = match suit {
         Suit::Bastoni => "B",
@@ -830,5 +1092,7 @@ pub fn card_view(suit: Suit, rank: Rank) -> Vec<String> {
     lines.extend(middle_rows);
     lines.push(String::from("╰───────╯"));
 
-    lines
+    // Wide glyphs make the borders longer than the rows with letters in them
+    let width = lines.iter().map(|line| display_width(line)).max().unwrap_or(0);
+    lines.iter().map(|line| pad_right(line, width)).collect()
 }
\ No newline at end of file
@@ -1,10 +1,12 @@
 use battld_common::games::{
-    chess::{ChessGameState, ChessPosition, ChessPiece, ChessPieceState, Player},
+    chess::{ChessAction, ChessGameState, ChessMove, ChessPosition, ChessPiece, ChessPieceState, DrawClaim, GameOverReason, Player, PROMOTION_PIECES},
     game_type::GameType,
     matches::{Match, MatchEndReason, MatchOutcome},
 };
 use battld_common::*;
+use crate::games::{MoveConfirmation, ThinkingIndicator};
 use crate::state::SessionState;
+use crate::width::{display_width, pad_right};
 use std::io::{self, Write};
 use tokio::io::AsyncBufReadExt;
 use colored::*;
@@ -23,96 +25,99 @@ enum ChessUiState {
 
 impl ChessUiState {
     fn render(&self, my_player: Player) {
-        crate::ui::clear_screen().ok();
+        let mut frame = crate::ui::Frame::default();
+        super::render_connection(&mut frame);
+        super::render_turn_clock(&mut frame);
 
         match self {
             ChessUiState::WaitingForOpponentToJoin => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Chess".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                println!("{}", "  Waiting for opponent to join...".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Chess".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                frame.println("  Waiting for opponent to join...".yellow());
+                frame.newline();
             }
             ChessUiState::MyTurn(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Chess".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player);
-                println!();
-                println!("{}", "  YOUR TURN".bright_green().bold());
-                println!();
-                println!("{}", "  Enter move (e.g., 'e2 e4'):".dimmed());
-                print!("  > ");
-                io::stdout().flush().ok();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Chess".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_player);
+                frame.newline();
+                frame.println("  YOUR TURN".bright_green().bold());
+                frame.newline();
+                render_draw_options(&mut frame, match_data, my_player);
+                frame.println("  Enter move (e.g., 'e2 e4', castle with 'e1 g1', promote with 'e7 e8 q'), or a square to see where its piece can go:".dimmed());
+                frame.print("  > ");
             }
             ChessUiState::OpponentTurn(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Chess".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player);
-                println!();
-                println!("{}", "  Waiting for opponent's move...".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Chess".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_player);
+                frame.newline();
+                frame.println("  Waiting for opponent's move...".yellow());
+                frame.newline();
             }
             ChessUiState::WaitingForOpponentToReconnect(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Chess".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player);
-                println!();
-                println!("{}", "  Opponent disconnected. Waiting for reconnection...".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Chess".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_player);
+                frame.newline();
+                frame.println("  Opponent disconnected. Waiting for reconnection...".yellow());
+                frame.newline();
             }
             ChessUiState::MatchEndedYouWon(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Chess".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player);
-                println!();
-                println!("{}", "  YOU WON!".bright_green().bold());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Chess".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_player);
+                frame.newline();
+                frame.println("  YOU WON!".bright_green().bold());
+                frame.newline();
             }
             ChessUiState::MatchEndedYouLost(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Chess".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player);
-                println!();
-                println!("{}", "  You lost.".red());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Chess".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_player);
+                frame.newline();
+                frame.println("  You lost.".red());
+                frame.newline();
             }
             ChessUiState::MatchEndedDraw(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Chess".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player);
-                println!();
-                println!("{}", "  It's a draw!".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Chess".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_player);
+                frame.newline();
+                frame.println(format!("  It's a draw!{}", draw_reason_suffix(match_data)).yellow());
+                frame.newline();
             }
             ChessUiState::MatchEndedOpponentDisconnected(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Chess".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player);
-                println!();
-                println!("{}", "  Match ended - Opponent disconnected.".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Chess".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_player);
+                frame.newline();
+                frame.println("  Match ended - Opponent disconnected.".yellow());
+                frame.newline();
             }
         }
+        crate::ui::present(&frame).ok();
     }
 }
 
-fn get_piece_symbol(piece: &ChessPieceState) -> &str {
-    return match (piece.player, piece.piece) {
+pub fn get_piece_symbol(piece: &ChessPieceState) -> &str {
+    match (piece.player, piece.piece) {
         (Player::White, ChessPiece::Pawn) => "♙",
         (Player::White, ChessPiece::Rook) => "♖",
         (Player::White, ChessPiece::Knight) => "♘",
@@ -125,42 +130,95 @@ fn get_piece_symbol(piece: &ChessPieceState) -> &str {
         (Player::Black, ChessPiece::Bishop) => "♝",
         (Player::Black, ChessPiece::Queen) => "♛",
         (Player::Black, ChessPiece::King) => "♚",
-    };
+    }
 }
 
-fn render_game_board(match_data: &Match, my_player: Player) {
+fn render_game_board(frame: &mut crate::ui::Frame, match_data: &Match, my_player: Player) {
     if let Ok(game_state) = serde_json::from_value::<ChessGameState>(match_data.game_state.clone()) {
-        println!("  You are: {}", if my_player == Player::White {
+        crate::games::render_opponent(frame, match_data, my_player.seat());
+        frame.println(format!("  You are: {}", if my_player == Player::White {
             "White (♙)".white()
         } else {
             "Black (♟)".bright_black()
-        });
+        }));
 
         if let Some(check_player) = game_state.check_state {
             if check_player == my_player {
-                println!("  {}", "CHECK!".red().bold());
+                frame.println(format!("  {}", "CHECK!".red().bold()));
             } else {
-                println!("  {}", "Opponent in check".yellow());
+                frame.println(format!("  {}", "Opponent in check".yellow()));
             }
         }
 
-        println!();
-        println!("  {}", "a b c d e f g h".dimmed());
+        // Squares fit the widest glyph, so the board stays aligned with wide pieces, with no gap on narrow terminals
+        let glyph = display_width(get_piece_symbol(&ChessPieceState { piece: ChessPiece::King, player: Player::White }));
+        let square = if crate::ui::is_compact() { glyph } else { glyph + 1 };
+        let flipped = my_player == Player::Black && crate::games::perspective().flip_chess_board;
+        let (rows, cols): (Vec<u8>, Vec<u8>) = if flipped {
+            ((0..8).collect(), (0..8).rev().collect())
+        } else {
+            ((0..8).rev().collect(), (0..8).collect())
+        };
+        let files: String = cols.iter().map(|col| pad_right(&char::from(b'a' + col).to_string(), square)).collect();
 
-        for row in (0..8).rev() {
-            print!("{} ", format!("{}", row + 1).dimmed());
-            for col in 0..8 {
+        frame.newline();
+        frame.println(format!("  {}", files.dimmed()));
+
+        for &row in &rows {
+            frame.print(format!("{} ", format!("{}", row + 1).dimmed()));
+            for &col in &cols {
                 let pos = ChessPosition::new(row, col).unwrap();
                 if let Some(piece) = game_state.get_piece(pos) {
-                    print!("{} ", get_piece_symbol(piece));
+                    frame.print(pad_right(get_piece_symbol(piece), square));
                 } else {
-                    print!("{} ", "·".dimmed());
+                    frame.print(pad_right("·", square).dimmed());
                 }
             }
-            println!("{}", format!("{}", row + 1).dimmed());
+            frame.println(format!("{}", row + 1).dimmed());
+        }
+
+        frame.println(format!("  {}", files.dimmed()));
+    }
+}
+
+fn render_draw_options(frame: &mut crate::ui::Frame, match_data: &Match, my_player: Player) {
+    if let Ok(game_state) = serde_json::from_value::<ChessGameState>(match_data.game_state.clone()) {
+        if game_state.draw_offer == Some(my_player.opponent()) {
+            frame.println("  Opponent offers a draw - type 'draw' to accept or move to decline".bright_yellow());
+        } else if game_state.draw_offer == Some(my_player) {
+            frame.println("  Draw offered, waiting for opponent's answer".dimmed());
+        } else {
+            frame.println("  Type 'draw' to offer a draw".dimmed());
+        }
+
+        match game_state.claimable_draw() {
+            Some(DrawClaim::ThreefoldRepetition) => {
+                frame.println("  Threefold repetition - type 'claim' to claim a draw".bright_yellow());
+            }
+            Some(DrawClaim::FiftyMoveRule) => {
+                frame.println("  Fifty-move rule - type 'claim' to claim a draw".bright_yellow());
+            }
+            None => {}
         }
+        frame.newline();
+    }
+}
+
+fn draw_reason_suffix(match_data: &Match) -> &'static str {
+    let game_state = match serde_json::from_value::<ChessGameState>(match_data.game_state.clone()) {
+        Ok(state) => state,
+        Err(_) => return "",
+    };
 
-        println!("  {}", "a b c d e f g h".dimmed());
+    match game_state.game_over {
+        Some(GameOverReason::Stalemate) => " (stalemate)",
+        Some(GameOverReason::DrawByAgreement) => " (agreed)",
+        Some(GameOverReason::ThreefoldRepetition) => " (threefold repetition)",
+        Some(GameOverReason::FiftyMoveRule) => " (fifty-move rule)",
+        Some(GameOverReason::InsufficientMaterial) => " (insufficient material)",
+        Some(GameOverReason::FivefoldRepetition) => " (fivefold repetition)",
+        Some(GameOverReason::SeventyFiveMoveRule) => " (75-move rule)",
+        _ => "",
     }
 }
 
@@ -202,12 +260,30 @@ fn handle_match_ended(
         MatchEndReason::Disconnection => {
             ChessUiState::MatchEndedOpponentDisconnected(final_match)
         }
-        MatchEndReason::Ended => {
+        MatchEndReason::Ended | MatchEndReason::Forfeit | MatchEndReason::Resignation | MatchEndReason::DrawAgreed => {
             determine_match_end_state(&final_match, my_player)
         }
     }
 }
 
+/// A late move was rejected because the match already ended, show the outcome the server reported
+fn handle_match_already_finished(
+    outcome: Option<MatchOutcome>,
+    ui_state: &ChessUiState,
+    my_player: Option<Player>,
+) -> ChessUiState {
+    let mut final_match = match ui_state {
+        ChessUiState::MyTurn(m) |
+        ChessUiState::OpponentTurn(m) |
+        ChessUiState::WaitingForOpponentToReconnect(m) => m.clone(),
+        _ => return ui_state.clone(),
+    };
+
+    final_match.outcome = outcome;
+    final_match.in_progress = false;
+    determine_match_end_state(&final_match, my_player)
+}
+
 fn determine_match_end_state(match_data: &Match, my_player: Option<Player>) -> ChessUiState {
     if let Some(outcome) = &match_data.outcome {
         match outcome {
@@ -242,11 +318,7 @@ fn handle_match_found_or_update(
     opponent_disconnected: bool,
 ) -> Result<Option<ChessUiState>, Box<dyn std::error::Error>> {
     if my_player.is_none() {
-        *my_player = Some(if match_data.player1_id == my_player_id {
-            Player::White
-        } else {
-            Player::Black
-        });
+        *my_player = Player::from_seat(super::my_seat(match_data, my_player_id));
     }
 
     if !match_data.in_progress {
@@ -281,12 +353,44 @@ fn handle_user_input(
     ui_state: &ChessUiState,
     opponent_disconnected: bool,
     ws_client: &crate::websocket::WebSocketClient,
+    confirmation: &mut MoveConfirmation,
     my_player: Player,
+    legal_moves: &[ChessMove],
 ) -> Result<Option<ChessUiState>, Box<dyn std::error::Error>> {
+    if let Some(sent) = confirmation.answer(input, ws_client)? {
+        return match ui_state {
+            ChessUiState::MyTurn(match_data) if sent => Ok(Some(turn_passed(match_data, opponent_disconnected))),
+            _ => Ok(None),
+        };
+    }
+
+    if let Some(action) = parse_action(input, ui_state, my_player) {
+        if let ChessUiState::MyTurn(match_data) = ui_state {
+            ws_client.send(ClientMessage::MakeMove {
+                move_data: serde_json::json!({ "action": action }),
+            })?;
+
+            // Offering a draw keeps the turn, so stay on the input prompt
+            if action == ChessAction::OfferDraw {
+                return Ok(None);
+            }
+
+            return Ok(Some(turn_passed(match_data, opponent_disconnected)));
+        }
+        return Ok(None);
+    }
+
     let parts: Vec<&str> = input.split_whitespace().collect();
 
-    if parts.len() != 2 {
-        println!("{}", "Invalid input format. Use 'from to' (e.g., 'e2 e4')".red());
+    if let [square] = parts[..] {
+        if let Some(from) = ChessPosition::from_algebraic(square) {
+            show_destinations(from, legal_moves)?;
+            return Ok(None);
+        }
+    }
+
+    if parts.len() != 2 && parts.len() != 3 {
+        println!("{}", "Invalid input format. Use 'from to' (e.g., 'e2 e4'), or 'from to piece' to promote (e.g., 'e7 e8 q')".red());
         print!("  > ");
         io::stdout().flush()?;
         return Ok(None);
@@ -302,12 +406,37 @@ fn handle_user_input(
         return Ok(None);
     }
 
+    let promotion = match parts.get(2) {
+        Some(letter) => match ChessPiece::from_letter(letter).filter(|piece| PROMOTION_PIECES.contains(piece)) {
+            Some(piece) => Some(piece),
+            None => {
+                println!("{}", "Promote to q, r, b or n (e.g., 'e7 e8 q')".red());
+                print!("  > ");
+                io::stdout().flush()?;
+                return Ok(None);
+            }
+        },
+        None => None,
+    };
+
     let from = from.unwrap();
     let to = to.unwrap();
-    let chess_move = battld_common::games::chess::ChessMove { from, to };
+    let chess_move = battld_common::games::chess::ChessMove { from, to, promotion };
 
     if let ChessUiState::MyTurn(match_data) = ui_state {
+        let mut description = format!("Move {} to {}", parts[0], parts[1]);
         if let Ok(game_state) = serde_json::from_value::<ChessGameState>(match_data.game_state.clone()) {
+            if let Some(piece) = game_state.get_piece(from) {
+                description = format!("Move {:?} {} to {}", piece.piece, parts[0], parts[1]);
+            }
+            if game_state.is_castling(&chess_move) {
+                let side = if to.col > from.col { "king" } else { "queen" };
+                description = format!("Castle {side}-side ({} {})", parts[0], parts[1]);
+            }
+            if game_state.is_promotion(&chess_move) {
+                let piece = promotion.unwrap_or(ChessPiece::Queen);
+                description = format!("Move Pawn {} to {} and promote to {piece:?}", parts[0], parts[1]);
+            }
             match game_state.is_valid_move(&chess_move, my_player) {
                 Ok(true) => {},
                 Ok(false) => {
@@ -330,49 +459,169 @@ fn handle_user_input(
             "to": to
         });
 
-        ws_client.send(ClientMessage::MakeMove { move_data })?;
-
-        let new_state = if opponent_disconnected {
-            ChessUiState::WaitingForOpponentToReconnect(match_data.clone())
-        } else {
-            ChessUiState::OpponentTurn(match_data.clone())
-        };
-        Ok(Some(new_state))
+        if !confirmation.submit(ws_client, &description, move_data)? {
+            return Ok(None);
+        }
+        Ok(Some(turn_passed(match_data, opponent_disconnected)))
     } else {
         Ok(None)
     }
 }
 
+/// Where the piece on a square can go, as listed by the server
+fn show_destinations(from: ChessPosition, legal_moves: &[ChessMove]) -> io::Result<()> {
+    let mut destinations: Vec<String> = legal_moves
+        .iter()
+        .filter(|chess_move| chess_move.from == from)
+        .map(|chess_move| chess_move.to.to_algebraic())
+        .collect();
+    // Promotions come once per piece
+    destinations.dedup();
+    if destinations.is_empty() {
+        println!("{}", format!("No legal moves from {}", from.to_algebraic()).yellow());
+    } else {
+        println!("{}", format!("{} can go to {}", from.to_algebraic(), destinations.join(", ")).bright_cyan());
+    }
+    print!("  > ");
+    io::stdout().flush()
+}
+
+/// Waiting screen once our move is sent
+fn turn_passed(match_data: &Match, opponent_disconnected: bool) -> ChessUiState {
+    if opponent_disconnected {
+        ChessUiState::WaitingForOpponentToReconnect(match_data.clone())
+    } else {
+        ChessUiState::OpponentTurn(match_data.clone())
+    }
+}
+
+/// Map 'draw' and 'claim' commands to chess actions
+/// Rules and controls shown by `?`
+pub fn help() -> Vec<String> {
+    [
+        "Checkmate the opposing king to win, stalemate and dead positions are draws",
+        "Enter a move as 'from to' in algebraic notation, such as 'e2 e4' or 'g1 f3'",
+        "Castle by moving the king two squares: 'e1 g1' king-side, 'e1 c1' queen-side",
+        "Promote by adding the piece, q, r, b or n: 'e7 e8 q'",
+        "Type a square alone, such as 'e2', to see where its piece can go",
+        "'draw' offers a draw or accepts your opponent's, 'claim' takes a draw by repetition or the 50-move rule",
+    ]
+    .iter()
+    .map(|line| line.to_string())
+    .collect()
+}
+
+fn parse_action(input: &str, ui_state: &ChessUiState, my_player: Player) -> Option<ChessAction> {
+    let command = input.trim().to_lowercase();
+    if command != "draw" && command != "claim" {
+        return None;
+    }
+
+    let game_state = match ui_state {
+        ChessUiState::MyTurn(match_data) => serde_json::from_value::<ChessGameState>(match_data.game_state.clone()).ok()?,
+        _ => return None,
+    };
+
+    if command == "claim" {
+        return Some(ChessAction::ClaimDraw);
+    }
+
+    if game_state.draw_offer == Some(my_player.opponent()) {
+        Some(ChessAction::AcceptDraw)
+    } else {
+        Some(ChessAction::OfferDraw)
+    }
+}
+
 async fn run_game_loop(
     ws_client: &crate::websocket::WebSocketClient,
     my_player_id: i64,
     initial_state: ChessUiState,
     initial_my_player: Option<Player>,
+    confirm_moves: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut my_player = initial_my_player;
     let mut ui_state = initial_state;
     let mut stdin_reader = tokio::io::BufReader::new(tokio::io::stdin());
     let mut input_line = String::new();
     let mut opponent_disconnected = false;
+    let mut thinking = ThinkingIndicator::default();
+    let mut confirmation = MoveConfirmation::new(confirm_moves);
+    let mut resumed = crate::suspend::resumed();
+    super::reset_turn_clock();
+    let mut legal_moves: Vec<ChessMove> = vec![];
+
+    let mut waiting_room = crate::waiting_room::WaitingRoom::default();
 
     ui_state.render(my_player.unwrap_or(Player::White));
+    if matches!(ui_state, ChessUiState::WaitingForOpponentToJoin) {
+        waiting_room.render();
+    }
 
     loop {
         let waiting_for_input = matches!(ui_state, ChessUiState::MyTurn(_));
 
         tokio::select! {
+            Ok(()) = resumed.recv() => {
+                crate::games::request_state(ws_client).await;
+                ui_state.render(my_player.unwrap_or(Player::White));
+                thinking.render();
+            }
+
             _ = tokio::time::sleep(tokio::time::Duration::from_millis(200)) => {
+                if matches!(ui_state, ChessUiState::WaitingForOpponentToJoin) && waiting_room.handle_keys()? {
+                    if waiting_room.take_leave_request() {
+                        ws_client.send(ClientMessage::LeaveMatchmaking)?;
+                    }
+                    ui_state.render(my_player.unwrap_or(Player::White));
+                    waiting_room.render();
+                }
+
+                if waiting_for_input {
+                    thinking.send_heartbeat(ws_client);
+                }
+
+                super::warn_turn_clock()?;
+                if crate::websocket::take_connection_change() {
+                    ui_state.render(my_player.unwrap_or(Player::White));
+                }
                 let messages = ws_client.get_messages().await;
 
                 for msg in messages {
-                    if let ServerMessage::Error { message } = &msg {
+                    if super::show_idle_notice(&msg)? {
+                        continue;
+                    }
+
+                    if super::track_turn_clock(&msg, my_player_id) {
+                        ui_state.render(my_player.unwrap_or(Player::White));
+                        continue;
+                    }
+
+                    match super::answer_ready_check(&msg, ws_client)? {
+                        super::ReadyCheckStep::Handled => continue,
+                        super::ReadyCheckStep::LeftQueue => return Ok(()),
+                        super::ReadyCheckStep::NotReadyCheck => {}
+                    }
+                    if matches!(msg, ServerMessage::MatchmakingCancelled) {
+                        return Ok(());
+                    }
+                    if super::explain_matchmaking_rejection(&msg, ws_client).await? {
+                        return Ok(());
+                    }
+
+                    if let Some(message) = crate::ui::error_text(&msg) {
                         println!("\n{}", format!("Error: {message}").red());
                         io::stdout().flush()?;
                         continue;
                     }
 
+                    if let ServerMessage::ServerBusy { retry_after } = &msg {
+                        crate::ui::show_server_busy(*retry_after)?;
+                        return Ok(());
+                    }
+
                     match &msg {
-                        ServerMessage::PlayerDisconnected { player_id } => {
+                        ServerMessage::PlayerDisconnected { player_id, .. } => {
                             if let Some(new_state) = handle_player_disconnected(
                                 *player_id,
                                 my_player_id,
@@ -384,9 +633,15 @@ async fn run_game_loop(
                                 ui_state.render(my_player.unwrap_or(Player::White));
                             }
                         }
-                        ServerMessage::MatchEnded { reason } => {
+                        ServerMessage::MatchEnded { reason, summary } => {
                             ui_state = handle_match_ended(reason, &ui_state, my_player);
                             ui_state.render(my_player.unwrap_or(Player::White));
+                            super::render_summary(summary.as_ref());
+                            return super::offer_rematch(ws_client, my_player_id).await;
+                        }
+                        ServerMessage::MatchAlreadyFinished { outcome, .. } => {
+                            ui_state = handle_match_already_finished(outcome.clone(), &ui_state, my_player);
+                            ui_state.render(my_player.unwrap_or(Player::White));
                             println!("\nPress any key to return to main menu...");
                             io::stdout().flush()?;
                             crate::ui::wait_for_keypress()?;
@@ -412,22 +667,38 @@ async fn run_game_loop(
                                     opponent_disconnected = false;
                                 }
 
+                                thinking.reset();
+                                confirmation.cancel();
                                 ui_state = new_state;
                                 ui_state.render(my_player.unwrap());
 
                                 if should_exit {
-                                    println!("\nPress any key to return to main menu...");
-                                    io::stdout().flush()?;
-                                    crate::ui::wait_for_keypress()?;
-                                    return Ok(());
+                                    return super::offer_rematch(ws_client, my_player_id).await;
                                 }
 
                                 input_line.clear();
                             }
                         }
+                        ServerMessage::LegalMoves { moves, .. } => {
+                            legal_moves = moves
+                                .iter()
+                                .filter_map(|move_data| serde_json::from_value(move_data.clone()).ok())
+                                .collect();
+                        }
+                        ServerMessage::OpponentThinking { .. } => {
+                            let appeared = thinking.opponent_thinking();
+                            if appeared && matches!(ui_state, ChessUiState::OpponentTurn(_)) {
+                                ui_state.render(my_player.unwrap_or(Player::White));
+                                thinking.render();
+                            }
+                        }
                         _ => {}
                     }
                 }
+
+                if thinking.expired() && matches!(ui_state, ChessUiState::OpponentTurn(_)) {
+                    ui_state.render(my_player.unwrap_or(Player::White));
+                }
             }
 
             result = stdin_reader.read_line(&mut input_line), if waiting_for_input => {
@@ -438,13 +709,29 @@ async fn run_game_loop(
                     if trimmed.is_empty() {
                         continue;
                     }
+                    if crate::bugreport::is_command(&trimmed) {
+                        crate::bugreport::run(Some(ws_client)).await;
+                        print!("  > ");
+                        io::stdout().flush().ok();
+                        continue;
+                    }
+                    if super::handle_match_command(&trimmed, ws_client, my_player_id).await? {
+                        continue;
+                    }
+                    if super::is_help_command(&trimmed) {
+                        super::show_help(&GameType::Chess, None)?;
+                        ui_state.render(my_player.unwrap_or(Player::White));
+                        continue;
+                    }
 
                     if let Ok(Some(new_state)) = handle_user_input(
                         &trimmed,
                         &ui_state,
                         opponent_disconnected,
                         ws_client,
+                        &mut confirmation,
                         my_player.unwrap(),
+                        &legal_moves,
                     ) {
                         ui_state = new_state;
                         ui_state.render(my_player.unwrap());
@@ -463,13 +750,15 @@ pub async fn start_game(session: &mut SessionState, game_type: GameType) -> Resu
     let ws_client = session.ws_client.as_ref().unwrap();
     let my_player_id = session.player_id.ok_or("No player ID in session")?;
 
-    ws_client.send(ClientMessage::JoinMatchmaking { game_type })?;
+    let series_length = super::read_series_length()?;
+    ws_client.send(ClientMessage::JoinMatchmaking { game_type, options: serde_json::Value::Null, series_length })?;
 
     run_game_loop(
         ws_client,
         my_player_id,
         ChessUiState::WaitingForOpponentToJoin,
         None,
+        session.config.confirm_moves,
     ).await
 }
 
@@ -477,11 +766,7 @@ pub async fn resume_game(session: &SessionState, game_match: Match) -> Result<()
     let ws_client = session.ws_client.as_ref().ok_or("Not connected to WebSocket")?;
     let my_player_id = session.player_id.ok_or("No player ID in session")?;
 
-    let my_player = if game_match.player1_id == my_player_id {
-        Player::White
-    } else {
-        Player::Black
-    };
+    let my_player = Player::from_seat(super::my_seat(&game_match, my_player_id)).ok_or("Chess is played by two")?;
 
     let initial_state = if let Ok(game_state) = serde_json::from_value::<ChessGameState>(game_match.game_state.clone()) {
         if game_state.current_turn == my_player && !game_state.is_finished() {
@@ -493,5 +778,5 @@ pub async fn resume_game(session: &SessionState, game_match: Match) -> Result<()
         ChessUiState::OpponentTurn(game_match.clone())
     };
 
-    run_game_loop(ws_client, my_player_id, initial_state, Some(my_player)).await
+    run_game_loop(ws_client, my_player_id, initial_state, Some(my_player), session.config.confirm_moves).await
 }
@@ -1,4 +1,657 @@
 pub mod rock_paper_scissors;
 pub mod tic_tac_toe;
 pub mod briscola;
-pub mod chess;
\ No newline at end of file
+pub mod chess;
+use battld_common::games::{
+    briscola::BriscolaGameState,
+    chess::{ChessGameState, Player},
+    game_type::GameType,
+    matches::{Match, MatchStatus},
+    players::Seat,
+    rock_paper_scissors::RockPaperScissorsGameState,
+    tic_tac_toe::TicTacToeGameState,
+};
+use battld_common::{ClientMessage, MatchSummary, MatchmakingRejection, SeriesInfo, ServerMessage};
+use colored::*;
+use std::io::{self, Write};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::config::Perspective;
+use crate::state::SessionState;
+use crate::websocket::WebSocketClient;
+
+static PERSPECTIVE: OnceLock<Perspective> = OnceLock::new();
+/// A rematch both players agreed to, or the next game of a series, played as soon as the finished match's screen closes
+static REMATCH: Mutex<Option<Match>> = Mutex::new(None);
+/// Score of the latest series we played in
+static SERIES: Mutex<Option<SeriesInfo>> = Mutex::new(None);
+static TURN_CLOCK: Mutex<TurnClock> = Mutex::new(TurnClock { deadline: None, mine: false, warned: false, timed_out: None, shown: None });
+
+/// The player's own clock is flagged once this little time is left
+const TURN_CLOCK_WARNING_SECS: u64 = 10;
+
+/// How often a player with the move prompt open tells the opponent
+const THINKING_INTERVAL: Duration = Duration::from_secs(3);
+/// The indicator goes away when no heartbeat arrived for this long
+const THINKING_TIMEOUT: Duration = Duration::from_secs(8);
+
+/// Sends our thinking heartbeats and tracks the opponent's
+#[derive(Default)]
+pub struct ThinkingIndicator {
+    last_sent: Option<Instant>,
+    opponent_seen: Option<Instant>,
+}
+
+impl ThinkingIndicator {
+    /// Heartbeat while the move prompt is open, at most once per interval
+    pub fn send_heartbeat(&mut self, ws_client: &WebSocketClient) {
+        if self.last_sent.is_some_and(|sent| sent.elapsed() < THINKING_INTERVAL) {
+            return;
+        }
+        if ws_client.send(ClientMessage::Thinking).is_ok() {
+            self.last_sent = Some(Instant::now());
+        }
+    }
+
+    /// Record a heartbeat from the opponent, true if the indicator was not showing yet
+    pub fn opponent_thinking(&mut self) -> bool {
+        let appeared = !self.is_visible();
+        self.opponent_seen = Some(Instant::now());
+        appeared
+    }
+
+    /// True once, when the opponent went quiet and the indicator should be hidden
+    pub fn expired(&mut self) -> bool {
+        if self.opponent_seen.is_some() && !self.is_visible() {
+            self.opponent_seen = None;
+            return true;
+        }
+        false
+    }
+
+    /// Forget the opponent's heartbeats, e.g. after they moved
+    pub fn reset(&mut self) {
+        self.opponent_seen = None;
+    }
+
+    pub fn render(&self) {
+        if self.is_visible() {
+            println!("{}", "  Opponent is thinking…".dimmed().italic());
+        }
+    }
+
+    fn is_visible(&self) -> bool {
+        self.opponent_seen.is_some_and(|seen| seen.elapsed() < THINKING_TIMEOUT)
+    }
+}
+
+/// The server's turn time limit, as last reported for the match on screen
+struct TurnClock {
+    deadline: Option<Instant>,
+    /// Whether the clock runs for us, rather than for the opponent
+    mine: bool,
+    warned: bool,
+    /// Set when a turn ran out, true if it was ours
+    timed_out: Option<bool>,
+    /// Seconds left when the clock was last drawn
+    shown: Option<u64>,
+}
+
+/// Follow the turn timers of the match, true if `message` changed the clock and the screen should be redrawn
+pub fn track_turn_clock(message: &ServerMessage, my_player_id: i64) -> bool {
+    let mut clock = TURN_CLOCK.lock().unwrap();
+    match message {
+        ServerMessage::TurnTimer { players, expires_in, .. } => {
+            *clock = TurnClock {
+                deadline: Some(Instant::now() + Duration::from_secs(*expires_in)),
+                mine: players.contains(&my_player_id),
+                warned: false,
+                timed_out: None,
+                shown: None,
+            };
+            true
+        }
+        ServerMessage::TurnTimeout { players, .. } => {
+            clock.deadline = None;
+            clock.timed_out = Some(players.contains(&my_player_id));
+            false
+        }
+        ServerMessage::MatchFound { match_data } | ServerMessage::GameStateUpdate { match_data }
+            if match_data.status != MatchStatus::Active =>
+        {
+            clock.deadline = None;
+            false
+        }
+        ServerMessage::MatchEnded { .. } => {
+            clock.deadline = None;
+            false
+        }
+        _ => false,
+    }
+}
+
+/// Forget the clock of the previous match
+pub fn reset_turn_clock() {
+    let mut clock = TURN_CLOCK.lock().unwrap();
+    clock.deadline = None;
+    clock.timed_out = None;
+}
+
+/// Time left to move, drawn at the top of the game screen while turns are timed
+pub fn render_turn_clock(frame: &mut crate::ui::Frame) {
+    let mut clock = TURN_CLOCK.lock().unwrap();
+    let line = match (clock.deadline, clock.timed_out) {
+        (Some(deadline), _) => {
+            let secs = deadline.saturating_duration_since(Instant::now()).as_secs();
+            clock.shown = Some(secs);
+            if !clock.mine {
+                format!("  ⏱ Opponent has {} to move", describe_duration(secs)).dimmed()
+            } else if secs <= TURN_CLOCK_WARNING_SECS {
+                format!("  ⏱ {} left to move", describe_duration(secs)).bright_red().bold()
+            } else {
+                format!("  ⏱ {} left to move", describe_duration(secs)).yellow()
+            }
+        }
+        (None, Some(true)) => "  You ran out of time".bright_red(),
+        (None, Some(false)) => "  Your opponent ran out of time".yellow(),
+        (None, None) => return,
+    };
+    frame.println(line);
+}
+
+/// Banner while the connection to the server is down, the game goes on by itself once it is back
+pub fn render_connection(frame: &mut crate::ui::Frame) {
+    match crate::websocket::connection() {
+        crate::websocket::Connection::Up => {}
+        crate::websocket::Connection::Reconnecting(attempt) => {
+            frame.println(format!("  ⚠ Reconnecting… (attempt {attempt})").bright_yellow().bold());
+        }
+        crate::websocket::Connection::Lost => {
+            frame.println("  ⚠ Connection lost, restart the client to resume the match".bright_red().bold());
+        }
+    }
+}
+
+/// Whether the clock on screen is in its last seconds and a second went by since it was drawn, to count down live
+pub fn turn_clock_ticked() -> bool {
+    let clock = TURN_CLOCK.lock().unwrap();
+    let Some(deadline) = clock.deadline else {
+        return false;
+    };
+    let secs = deadline.saturating_duration_since(Instant::now()).as_secs();
+    secs <= TURN_CLOCK_WARNING_SECS && clock.shown != Some(secs)
+}
+
+/// Print a warning once when our own clock is about to run out
+pub fn warn_turn_clock() -> io::Result<()> {
+    let mut clock = TURN_CLOCK.lock().unwrap();
+    let Some(deadline) = clock.deadline else {
+        return Ok(());
+    };
+    let secs = deadline.saturating_duration_since(Instant::now()).as_secs();
+    if !clock.mine || clock.warned || secs > TURN_CLOCK_WARNING_SECS {
+        return Ok(());
+    }
+    clock.warned = true;
+    println!("\n{}", format!("  Only {secs}s left to move!").bright_red().bold());
+    io::stdout().flush()
+}
+
+/// Holds a move until the player confirms it, when `confirm_moves` is on in the config
+pub struct MoveConfirmation {
+    enabled: bool,
+    pending: Option<serde_json::Value>,
+}
+
+impl MoveConfirmation {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, pending: None }
+    }
+
+    /// Send the move, or hold it and ask first, true if it was sent
+    pub fn submit(
+        &mut self,
+        ws_client: &WebSocketClient,
+        description: &str,
+        move_data: serde_json::Value,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        if !self.enabled {
+            ws_client.send(ClientMessage::MakeMove { move_data })?;
+            return Ok(true);
+        }
+        self.pending = Some(move_data);
+        print!("  {} ", format!("{description}, confirm? y/n").bright_yellow());
+        io::stdout().flush()?;
+        Ok(false)
+    }
+
+    /// Answer to the held move, None if there is none
+    /// Some(true) once it was sent, Some(false) if the player changed their mind
+    pub fn answer(&mut self, input: &str, ws_client: &WebSocketClient) -> Result<Option<bool>, Box<dyn std::error::Error>> {
+        let Some(move_data) = self.pending.take() else {
+            return Ok(None);
+        };
+        if matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+            ws_client.send(ClientMessage::MakeMove { move_data })?;
+            return Ok(Some(true));
+        }
+        println!("{}", "Move cancelled.".dimmed());
+        print!("  > ");
+        io::stdout().flush()?;
+        Ok(Some(false))
+    }
+
+    /// Drop the held move, the match changed under it
+    pub fn cancel(&mut self) {
+        self.pending = None;
+    }
+}
+
+/// Ask how many games to play against the opponent, None for a single match
+pub fn read_series_length() -> Result<Option<u32>, Box<dyn std::error::Error>> {
+    let mut rl = rustyline::DefaultEditor::new()?;
+    loop {
+        let line = rl.readline("Best of 1, 3, 5 or 7 games? (default 1): ")?;
+        match line.trim() {
+            "" | "1" => return Ok(None),
+            "3" | "5" | "7" => return Ok(line.trim().parse().ok()),
+            _ => println!("{}", "Please enter 1, 3, 5 or 7.".red()),
+        }
+    }
+}
+
+/// Keep the score of the series being played, called for every message received
+pub fn track_series(message: &ServerMessage) {
+    if let ServerMessage::SeriesUpdate { series } = message {
+        *SERIES.lock().unwrap() = Some(series.clone());
+    }
+}
+
+/// The series `match_id` is a game of, if any
+fn series_of(match_id: i64) -> Option<SeriesInfo> {
+    SERIES.lock().unwrap().clone().filter(|series| series.match_id == match_id)
+}
+
+/// "Game 2 of 3, you lead 1-0", or how the series ended
+fn describe_series(series: &SeriesInfo, my_player_id: i64) -> String {
+    let (mine, theirs) = series.score_for(my_player_id);
+    if series.finished {
+        return match series.winner_id {
+            Some(winner_id) if winner_id == my_player_id => format!("You won the series {mine}-{theirs}"),
+            Some(_) => format!("You lost the series {mine}-{theirs}"),
+            None => format!("The series ended level at {mine}-{theirs}"),
+        };
+    }
+    let standing = match mine.cmp(&theirs) {
+        std::cmp::Ordering::Greater => format!("you lead {mine}-{theirs}"),
+        std::cmp::Ordering::Less => format!("you trail {mine}-{theirs}"),
+        std::cmp::Ordering::Equal => format!("level at {mine}-{theirs}"),
+    };
+    format!("Game {} of {}, {standing}", series.game, series.length)
+}
+
+/// Used by the game renderers from now on
+pub fn set_perspective(perspective: Perspective) {
+    let _ = PERSPECTIVE.set(perspective);
+}
+
+pub fn perspective() -> Perspective {
+    PERSPECTIVE.get().copied().unwrap_or_default()
+}
+
+/// Ask the server for the latest state of the current match, e.g. after missing updates
+pub async fn request_state(ws_client: &WebSocketClient) {
+    if let Some(match_data) = ws_client.get_current_match().await {
+        let _ = ws_client.send(ClientMessage::RequestState { match_id: match_data.id });
+    }
+}
+
+/// Where the player sits in their match, first for anyone watching it
+pub fn my_seat(match_data: &Match, my_player_id: i64) -> Seat {
+    match_data.seat_of(my_player_id).unwrap_or(Seat::FIRST)
+}
+
+/// Whether the game is waiting on `player_id`, both players can be in Rock-Paper-Scissors
+pub fn is_players_turn(match_data: &Match, player_id: i64) -> bool {
+    let me = my_seat(match_data, player_id);
+    let state = match_data.game_state.clone();
+    match match_data.game_type {
+        GameType::TicTacToe => serde_json::from_value::<TicTacToeGameState>(state)
+            .is_ok_and(|s| !s.is_finished && s.current_player == me.symbol()),
+        GameType::Briscola => serde_json::from_value::<BriscolaGameState>(state)
+            .is_ok_and(|s| !s.is_finished() && s.current_player == me.symbol()),
+        GameType::Chess => serde_json::from_value::<ChessGameState>(state)
+            .is_ok_and(|s| !s.is_finished() && Some(s.current_turn) == Player::from_seat(me)),
+        GameType::RockPaperScissors => serde_json::from_value::<RockPaperScissorsGameState>(state)
+            .is_ok_and(|s| {
+                let own_move = s.rounds.last().and_then(|(p1, p2)| if me == Seat::FIRST { p1.as_ref() } else { p2.as_ref() });
+                !s.is_finished() && own_move.is_none()
+            }),
+    }
+}
+
+/// Print a turn reminder or an idle opponent notice, true if `message` was one
+pub fn show_idle_notice(message: &ServerMessage) -> io::Result<bool> {
+    let notice = match message {
+        ServerMessage::TurnReminder { idle_secs, forfeit_in: Some(forfeit_in), .. } => format!(
+            "It's your move, the match has been waiting for {} and is forfeited in {}",
+            describe_duration(*idle_secs),
+            describe_duration(*forfeit_in)
+        ),
+        ServerMessage::TurnReminder { idle_secs, .. } => {
+            format!("It's your move, the match has been waiting for {}", describe_duration(*idle_secs))
+        }
+        ServerMessage::OpponentIdle { idle_secs, .. } => {
+            format!("Your opponent has not moved for {}, they were reminded", describe_duration(*idle_secs))
+        }
+        _ => return Ok(false),
+    };
+    println!("\n{}", notice.yellow());
+    io::stdout().flush()?;
+    Ok(true)
+}
+
+/// What `answer_ready_check` made of a message
+pub enum ReadyCheckStep {
+    NotReadyCheck,
+    Handled,
+    /// The player declined or missed the ready check and is out of matchmaking
+    LeftQueue,
+}
+
+/// Ask the player to accept a found match and tell them how the ready check ended
+pub fn answer_ready_check(message: &ServerMessage, ws_client: &WebSocketClient) -> Result<ReadyCheckStep, Box<dyn std::error::Error>> {
+    match message {
+        ServerMessage::ReadyCheck { match_id, expires_in } => {
+            println!();
+            println!("{}", "  Match found!".bright_green().bold());
+            println!("{}", format!("  Accept within {expires_in}s? Enter/y to accept, n/Esc to decline").bright_yellow());
+            io::stdout().flush()?;
+            let accept = crate::ui::confirm_within(Duration::from_secs(*expires_in))?.unwrap_or(false);
+            ws_client.send(ClientMessage::AcceptMatch { match_id: *match_id, accept })?;
+            if accept {
+                println!("{}", "  Waiting for the opponent to accept...".yellow());
+                io::stdout().flush()?;
+            }
+            Ok(ReadyCheckStep::Handled)
+        }
+        ServerMessage::ReadyCheckFailed { requeued: true } => {
+            println!("{}", "  The opponent did not accept, you are back at the front of the queue".yellow());
+            io::stdout().flush()?;
+            Ok(ReadyCheckStep::Handled)
+        }
+        ServerMessage::ReadyCheckFailed { requeued: false } => {
+            println!("{}", "  You left matchmaking.".yellow());
+            println!("\nPress any key to return to main menu...");
+            io::stdout().flush()?;
+            crate::ui::wait_for_keypress()?;
+            Ok(ReadyCheckStep::LeftQueue)
+        }
+        _ => Ok(ReadyCheckStep::NotReadyCheck),
+    }
+}
+
+/// How long a player turned away for being in a match has to choose whether to go back to it
+const RESUME_PROMPT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait for the server to bring that match back
+const RESUME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tell the player why matchmaking turned them away, true if `message` was such a rejection
+/// Players already in a match can go straight back to it, which is then left for `take_rematch`
+pub async fn explain_matchmaking_rejection(message: &ServerMessage, ws_client: &WebSocketClient) -> Result<bool, Box<dyn std::error::Error>> {
+    let ServerMessage::MatchmakingRejected { reason } = message else {
+        return Ok(false);
+    };
+    println!();
+    match reason {
+        MatchmakingRejection::ServerFull { retry_after } => {
+            crate::ui::show_server_busy(*retry_after)?;
+            return Ok(true);
+        }
+        MatchmakingRejection::AlreadyInMatch { match_id, game_type } => {
+            println!("{}", format!("  You have an active {game_type} match - resume or forfeit it first").yellow().bold());
+            println!("{}", "  Resume it now? Enter/y to resume, n/Esc to return to main menu".bright_yellow());
+            io::stdout().flush()?;
+            if crate::ui::confirm_within(RESUME_PROMPT_TIMEOUT)?.unwrap_or(false) {
+                resume_rejected_match(*match_id, ws_client).await?;
+            }
+            return Ok(true);
+        }
+        MatchmakingRejection::ReadyCheckPending => {
+            println!("{}", "  A match is waiting for you to accept it, answer the ready check first".yellow());
+        }
+        MatchmakingRejection::Maintenance => {
+            println!("{}", "  The server is under maintenance and not starting new matches, try again later".yellow());
+        }
+        MatchmakingRejection::UnsupportedGame => {
+            println!("{}", "  This server does not run that game, update the client or pick another one".yellow());
+        }
+    }
+    println!("\nPress any key to return to main menu...");
+    io::stdout().flush()?;
+    crate::ui::wait_for_keypress()?;
+    Ok(true)
+}
+
+/// Ask the server for the match the player is in and keep it for `take_rematch`
+async fn resume_rejected_match(match_id: i64, ws_client: &WebSocketClient) -> Result<(), Box<dyn std::error::Error>> {
+    ws_client.send(ClientMessage::ResumeMatch)?;
+    let deadline = Instant::now() + RESUME_TIMEOUT;
+    while Instant::now() < deadline {
+        if let Some(resumed) = ws_client.get_current_match().await.filter(|m| m.id == match_id) {
+            *REMATCH.lock().unwrap() = Some(resumed);
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    println!("{}", "  The match could not be resumed, start the client with --resume to try again".red());
+    println!("\nPress any key to return to main menu...");
+    io::stdout().flush()?;
+    crate::ui::wait_for_keypress()?;
+    Ok(())
+}
+
+fn describe_duration(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{secs}s"),
+        _ => format!("{}m", secs / 60),
+    }
+}
+
+/// The game's own account of how the match went, such as "Checkmate in 31 moves"
+pub fn render_summary(summary: Option<&MatchSummary>) {
+    if let Some(summary) = summary {
+        println!("  {}", summary.text.bright_white());
+        println!();
+    }
+}
+
+/// Opponent name and score, or their id if the server did not send a profile
+pub fn opponent_label(match_data: &Match, my_seat: Seat) -> String {
+    let opponent_id = match_data.player_at(my_seat.opponent()).unwrap_or(match_data.player2_id);
+    match_data.player_label(opponent_id)
+}
+
+/// Opponent name, with the draw offer waiting for an answer if there is one
+pub fn render_opponent(frame: &mut crate::ui::Frame, match_data: &Match, my_seat: Seat) {
+    frame.println(format!("  Playing against: {}", opponent_label(match_data, my_seat).bright_magenta()));
+    let my_player_id = match_data.player_at(my_seat).unwrap_or(match_data.player1_id);
+    if match_data.player(my_player_id).is_some() {
+        frame.println(format!("  You: {}", match_data.player_label(my_player_id)).dimmed());
+    }
+    if match_data.players.iter().any(|player| player.bot) {
+        frame.println("  Nobody else was around, you are playing a bot - this match is unranked".dimmed());
+    }
+    if let Some(series) = series_of(match_data.id) {
+        frame.println(format!("  {}", describe_series(&series, my_player_id)).bright_cyan());
+    }
+    match match_data.draw_offered_by {
+        Some(_) if match_data.status != MatchStatus::Active => {}
+        Some(offered_by) if offered_by == my_player_id => {
+            frame.println("  Draw offered, waiting for your opponent's answer".dimmed());
+        }
+        Some(_) => frame.println("  Your opponent offers a draw - type /draw to accept or move to decline".bright_yellow()),
+        None => {}
+    }
+    frame.newline();
+}
+
+/// Commands that work in every game, `/resign` and `/draw`, true if `input` was one of them
+pub async fn handle_match_command(input: &str, ws_client: &WebSocketClient, my_player_id: i64) -> Result<bool, Box<dyn std::error::Error>> {
+    match input.trim().to_lowercase().as_str() {
+        "/resign" => {
+            print!("  Resign this match? (y/n) ");
+            io::stdout().flush()?;
+            let confirmed = crate::ui::confirm_within(Duration::from_secs(10))? == Some(true);
+            println!();
+            if confirmed {
+                ws_client.send(ClientMessage::Resign)?;
+            } else {
+                print!("  > ");
+                io::stdout().flush()?;
+            }
+        }
+        "/draw" => {
+            let offered_by = ws_client.get_current_match().await.and_then(|match_data| match_data.draw_offered_by);
+            let message = match offered_by {
+                Some(offered_by) if offered_by != my_player_id => ClientMessage::AcceptDraw,
+                _ => ClientMessage::OfferDraw,
+            };
+            ws_client.send(message)?;
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Whether `input` asks for the rules and controls of the game in play
+pub fn is_help_command(input: &str) -> bool {
+    input.trim() == "?"
+}
+
+/// Show the rules and controls of `game_type` until a key is pressed, the caller redraws the game afterwards
+pub fn show_help(game_type: &GameType, match_data: Option<&Match>) -> io::Result<()> {
+    let lines = match game_type {
+        GameType::TicTacToe => tic_tac_toe::help(match_data),
+        GameType::RockPaperScissors => rock_paper_scissors::help(match_data),
+        GameType::Briscola => briscola::help(match_data),
+        GameType::Chess => chess::help(),
+    };
+
+    crate::ui::clear_screen()?;
+    println!("{}", format!("  {game_type} - rules and controls").bright_cyan().bold());
+    println!();
+    for line in lines {
+        println!("  {line}");
+    }
+    println!();
+    println!("  {}", "/resign gives the match to your opponent, /draw offers a draw or accepts theirs".dimmed());
+    println!("
+{}", "Press any key to return to the game...".dimmed());
+    io::stdout().flush()?;
+    crate::ui::wait_for_keypress()
+}
+
+/// Close the screen of a finished match: r asks the opponent for a rematch, any other key returns to the main menu
+/// The rematch, once the opponent asked for it too, is left for `take_rematch`, as is the next game of a series
+pub async fn offer_rematch(ws_client: &WebSocketClient, my_player_id: i64) -> Result<(), Box<dyn std::error::Error>> {
+    let finished = ws_client.get_current_match().await.filter(|m| m.status == MatchStatus::Finished && !m.ephemeral);
+    if let Some(finished) = finished.as_ref().filter(|finished| series_of(finished.id).is_some()) {
+        if !follow_series(ws_client, finished, my_player_id).await? {
+            return Ok(());
+        }
+    }
+    let Some(finished) = finished else {
+        println!("\nPress any key to return to main menu...");
+        io::stdout().flush()?;
+        crate::ui::wait_for_keypress()?;
+        return Ok(());
+    };
+    println!("\nPress r for a rematch, any other key to return to main menu...");
+    io::stdout().flush()?;
+
+    let mut asked = false;
+    loop {
+        for message in ws_client.get_messages().await {
+            match &message {
+                ServerMessage::RematchOffered { match_id, player_id } if *match_id == finished.id && *player_id != my_player_id => {
+                    println!("{}", "  Your opponent wants a rematch, press r to play again".bright_yellow());
+                    io::stdout().flush()?;
+                }
+                ServerMessage::MatchFound { match_data } if asked => {
+                    *REMATCH.lock().unwrap() = Some(match_data.clone());
+                    return Ok(());
+                }
+                ServerMessage::Error { .. } if asked => {
+                    if let Some(text) = crate::ui::error_text(&message) {
+                        println!("  {}", text.red());
+                    }
+                    println!("\nPress any key to return to main menu...");
+                    io::stdout().flush()?;
+                    crate::ui::wait_for_keypress()?;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+
+        crossterm::terminal::enable_raw_mode()?;
+        let keys = crate::waiting_room::read_pending_keys();
+        crossterm::terminal::disable_raw_mode()?;
+        for key in keys? {
+            if key != crossterm::event::KeyCode::Char('r') || asked {
+                return Ok(());
+            }
+            ws_client.send(ClientMessage::RequestRematch { match_id: finished.id })?;
+            asked = true;
+            println!("{}", "  Waiting for your opponent to agree, any key to give up...".dimmed());
+            io::stdout().flush()?;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Wait for the next game of the series `finished` is part of, false if there is one or the player left
+async fn follow_series(ws_client: &WebSocketClient, finished: &Match, my_player_id: i64) -> Result<bool, Box<dyn std::error::Error>> {
+    println!("\n{}", "Waiting for the next game of the series, any key to return to main menu...".dimmed());
+    io::stdout().flush()?;
+    loop {
+        if let Some(next) = ws_client.get_current_match().await.filter(|m| m.id != finished.id && m.status == MatchStatus::Active) {
+            *REMATCH.lock().unwrap() = Some(next);
+            return Ok(false);
+        }
+        if let Some(series) = series_of(finished.id).filter(|series| series.finished) {
+            println!("  {}", describe_series(&series, my_player_id).bright_cyan().bold());
+            return Ok(true);
+        }
+
+        crossterm::terminal::enable_raw_mode()?;
+        let keys = crate::waiting_room::read_pending_keys();
+        crossterm::terminal::disable_raw_mode()?;
+        if !keys?.is_empty() {
+            return Ok(false);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// The rematch agreed to at the end of the last match, or the match a rejected player went back to, if any
+pub fn take_rematch() -> Option<Match> {
+    REMATCH.lock().unwrap().take()
+}
+
+/// Enter the game screen for a match that has already started, then for the rematches that follow it
+pub async fn resume_game(session: &mut SessionState, game_match: Match) -> Result<(), Box<dyn std::error::Error>> {
+    let mut next = Some(game_match);
+    while let Some(game_match) = next {
+        match game_match.game_type {
+            GameType::TicTacToe => tic_tac_toe::resume_game(session, game_match).await?,
+            GameType::RockPaperScissors => rock_paper_scissors::resume_game(session, game_match).await?,
+            GameType::Briscola => briscola::resume_game(session, game_match).await?,
+            GameType::Chess => chess::resume_game(session, game_match).await?,
+        }
+        next = take_rematch();
+    }
+    Ok(())
+}
@@ -1,8 +1,9 @@
-use battld_common::{games::{game_type::GameType, matches::{Match, MatchEndReason, MatchOutcome}, rock_paper_scissors::{RockPaperScissorsGameState, RockPaperScissorsMove}}, *};
+use battld_common::{games::{game_type::GameType, matches::{Match, MatchEndReason, MatchOutcome}, players::Seat, rock_paper_scissors::{RockPaperScissorsGameState, RockPaperScissorsMove, RockPaperScissorsOptions, DEFAULT_BEST_OF, MAX_BEST_OF}}, *};
 use crate::state::SessionState;
 use std::io::{self, Write};
 use tokio::io::AsyncBufReadExt;
 use colored::*;
+use rustyline::DefaultEditor;
 
 #[derive(Debug, Clone)]
 struct RoundResult {
@@ -23,213 +24,346 @@ enum RockPaperScissorsUiState {
         match_data: Match,
         previous_rounds: Vec<RoundResult>,
     },
+    /// Both choices of the round just played, counted down to before the result shows
+    RoundReveal {
+        match_data: Match,
+        previous_rounds: Vec<RoundResult>,
+        round: usize,
+        my_move: RockPaperScissorsMove,
+        opponent_move: RockPaperScissorsMove,
+        winner: RoundWinner,
+        step: usize,
+    },
     MatchEndedYouWon(Match),
     MatchEndedYouLost(Match),
     MatchEndedDraw(Match),
     MatchEndedOpponentDisconnected(Match),
 }
 
+/// Shown one per tick before a round's result
+const COUNTDOWN: [&str; 4] = ["Rock…", "Paper…", "Scissors…", "Shoot!"];
 
 impl RockPaperScissorsUiState {
-    fn render(&self, my_player_number: i32) {
-        crate::ui::clear_screen().ok();
+    fn render(&self, my_seat: Seat) {
+        let mut frame = crate::ui::Frame::default();
+        super::render_connection(&mut frame);
+        super::render_turn_clock(&mut frame);
 
         match self {
             RockPaperScissorsUiState::WaitingForOpponentToJoin => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Rock-Paper-Scissors".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                println!("{}", "  Waiting for opponent to join...".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Rock-Paper-Scissors".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                frame.println("  Waiting for opponent to join...".yellow());
+                frame.newline();
             }
             RockPaperScissorsUiState::SelectMove {
-                match_data: _,
+                match_data,
                 previous_rounds,
                 opponent_selected,
                 you_selected,
             } => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Rock-Paper-Scissors".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-
-                // Display previous rounds
-                if !previous_rounds.is_empty() {
-                    println!("{}", "  Previous Rounds:".bold());
-                    println!();
-                    for (i, round) in previous_rounds.iter().enumerate() {
-                        let (my_move, opponent_move) = if my_player_number == 1 {
-                            (&round.player1_move, &round.player2_move)
-                        } else {
-                            (&round.player2_move, &round.player1_move)
-                        };
-
-                        let result = determine_round_winner(my_move, opponent_move, my_player_number);
-                        let result_str = match result {
-                            RoundWinner::You => "WIN".bright_green().bold(),
-                            RoundWinner::Opponent => "LOSS".red(),
-                            RoundWinner::Draw => "DRAW".yellow(),
-                        };
-
-                        println!(
-                            "    Round {}: {} vs {} - {}",
-                            i + 1,
-                            format_move(my_move).bright_blue(),
-                            format_move(opponent_move).bright_magenta(),
-                            result_str
-                        );
-                    }
-                    println!();
-                }
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Rock-Paper-Scissors".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                crate::games::render_opponent(&mut frame, match_data, my_seat);
+                render_previous_rounds(&mut frame, previous_rounds, my_seat);
 
                 // Display current round status
-                println!("{}", "  Current Round:".bold());
-                println!();
+                frame.println("  Current Round:".bold());
+                frame.newline();
 
                 if *opponent_selected {
-                    println!("{}", "    Opponent has selected their move".dimmed());
+                    frame.println("    Opponent has selected their move".dimmed());
                 } else {
-                    println!("{}", "    Opponent is choosing...".dimmed());
+                    frame.println("    Opponent is choosing...".dimmed());
                 }
 
                 if *you_selected {
-                    println!("{}", "    You have selected your move".dimmed());
-                    println!();
-                    println!("{}", "  Waiting for results...".yellow());
+                    frame.println("    You have selected your move".dimmed());
+                    frame.newline();
+                    frame.println("  Waiting for results...".yellow());
                 } else {
-                    println!("{}", "    You haven't selected yet".dimmed());
-                    println!();
-                    println!("{}", "  SELECT YOUR MOVE".bright_green().bold());
-                    println!();
-                    println!("{}", "  Enter your choice (rock/paper/scissors):".dimmed());
-                    print!("  > ");
-                    io::stdout().flush().ok();
+                    frame.println("    You haven't selected yet".dimmed());
+                    frame.newline();
+                    frame.println("  SELECT YOUR MOVE".bright_green().bold());
+                    frame.newline();
+                    frame.println(format!("  Enter your choice ({}):", move_names(match_data).join("/")).dimmed());
+                    frame.print("  > ");
                 }
-                println!();
+                frame.newline();
             }
             RockPaperScissorsUiState::WaitingForOpponentToReconnect {
-                match_data: _,
+                match_data,
                 previous_rounds,
             } => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Rock-Paper-Scissors".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Rock-Paper-Scissors".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                crate::games::render_opponent(&mut frame, match_data, my_seat);
 
                 if !previous_rounds.is_empty() {
-                    println!("{}", "  Previous Rounds:".bold());
-                    println!();
+                    frame.println("  Previous Rounds:".bold());
+                    frame.newline();
                     for (i, round) in previous_rounds.iter().enumerate() {
-                        let (my_move, opponent_move) = if my_player_number == 1 {
+                        let (my_move, opponent_move) = if my_seat == Seat::FIRST {
                             (&round.player1_move, &round.player2_move)
                         } else {
                             (&round.player2_move, &round.player1_move)
                         };
 
-                        println!(
+                        frame.println(format!(
                             "    Round {}: {} vs {}",
                             i + 1,
                             format_move(my_move).bright_blue(),
                             format_move(opponent_move).bright_magenta()
-                        );
+                        ));
                     }
-                    println!();
+                    frame.newline();
                 }
 
-                println!("{}", "  Opponent disconnected. Waiting for reconnection...".yellow());
-                println!();
+                frame.println("  Opponent disconnected. Waiting for reconnection...".yellow());
+                frame.newline();
+            }
+            RockPaperScissorsUiState::RoundReveal {
+                match_data,
+                previous_rounds,
+                round,
+                my_move,
+                opponent_move,
+                winner,
+                step,
+            } => {
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Rock-Paper-Scissors".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                crate::games::render_opponent(&mut frame, match_data, my_seat);
+                render_previous_rounds(&mut frame, previous_rounds, my_seat);
+
+                frame.println(format!("  Round {}:", round + 1).bold());
+                frame.newline();
+                match COUNTDOWN.get(*step) {
+                    Some(word) => frame.println(format!("    {word}").bright_yellow().bold()),
+                    None => {
+                        frame.println(format!(
+                            "    {} vs {}",
+                            format_move(&Some(*my_move)).bright_blue().bold(),
+                            format_move(&Some(*opponent_move)).bright_magenta().bold()
+                        ));
+                        frame.newline();
+                        frame.println(match winner {
+                            RoundWinner::You => "  You win the round!".bright_green().bold(),
+                            RoundWinner::Opponent => "  You lose the round.".red(),
+                            RoundWinner::Draw => "  The round is a draw.".yellow(),
+                        });
+                    }
+                }
+                frame.newline();
             }
             RockPaperScissorsUiState::MatchEndedYouWon(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Rock-Paper-Scissors".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_final_results(match_data, my_player_number);
-                println!();
-                println!("{}", "  YOU WON! 🎉".bright_green().bold());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Rock-Paper-Scissors".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_final_results(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  YOU WON! 🎉".bright_green().bold());
+                frame.newline();
             }
             RockPaperScissorsUiState::MatchEndedYouLost(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Rock-Paper-Scissors".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_final_results(match_data, my_player_number);
-                println!();
-                println!("{}", "  You lost.".red());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Rock-Paper-Scissors".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_final_results(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  You lost.".red());
+                frame.newline();
             }
             RockPaperScissorsUiState::MatchEndedDraw(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Rock-Paper-Scissors".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_final_results(match_data, my_player_number);
-                println!();
-                println!("{}", "  It's a draw!".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Rock-Paper-Scissors".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_final_results(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  It's a draw!".yellow());
+                frame.newline();
             }
             RockPaperScissorsUiState::MatchEndedOpponentDisconnected(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Rock-Paper-Scissors".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_final_results(match_data, my_player_number);
-                println!();
-                println!("{}", "  Match ended - Opponent disconnected.".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Rock-Paper-Scissors".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_final_results(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  Match ended - Opponent disconnected.".yellow());
+                frame.newline();
             }
         }
+        crate::ui::present(&frame).ok();
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 enum RoundWinner {
     You,
     Opponent,
     Draw,
 }
 
-fn determine_round_winner(my_move: &Option<RockPaperScissorsMove>, opponent_move: &Option<RockPaperScissorsMove>, _my_player_number: i32) -> RoundWinner {
+fn determine_round_winner(my_move: &Option<RockPaperScissorsMove>, opponent_move: &Option<RockPaperScissorsMove>, _my_seat: Seat) -> RoundWinner {
     match (my_move, opponent_move) {
-        (Some(RockPaperScissorsMove::Rock), Some(RockPaperScissorsMove::Scissors)) => RoundWinner::You,
-        (Some(RockPaperScissorsMove::Paper), Some(RockPaperScissorsMove::Rock)) => RoundWinner::You,
-        (Some(RockPaperScissorsMove::Scissors), Some(RockPaperScissorsMove::Paper)) => RoundWinner::You,
-        (Some(RockPaperScissorsMove::Rock), Some(RockPaperScissorsMove::Paper)) => RoundWinner::Opponent,
-        (Some(RockPaperScissorsMove::Paper), Some(RockPaperScissorsMove::Scissors)) => RoundWinner::Opponent,
-        (Some(RockPaperScissorsMove::Scissors), Some(RockPaperScissorsMove::Rock)) => RoundWinner::Opponent,
-        (Some(a), Some(b)) if matches!((a, b), (RockPaperScissorsMove::Rock, RockPaperScissorsMove::Rock) | (RockPaperScissorsMove::Paper, RockPaperScissorsMove::Paper) | (RockPaperScissorsMove::Scissors, RockPaperScissorsMove::Scissors)) => RoundWinner::Draw,
+        (Some(mine), Some(theirs)) if mine.defeats(theirs) => RoundWinner::You,
+        (Some(mine), Some(theirs)) if theirs.defeats(mine) => RoundWinner::Opponent,
         _ => RoundWinner::Draw,
     }
 }
 
+fn render_previous_rounds(frame: &mut crate::ui::Frame, previous_rounds: &[RoundResult], my_seat: Seat) {
+    if previous_rounds.is_empty() {
+        return;
+    }
+    frame.println("  Previous Rounds:".bold());
+    frame.newline();
+    for (i, round) in previous_rounds.iter().enumerate() {
+        let (my_move, opponent_move) = if my_seat == Seat::FIRST {
+            (&round.player1_move, &round.player2_move)
+        } else {
+            (&round.player2_move, &round.player1_move)
+        };
+
+        let result = determine_round_winner(my_move, opponent_move, my_seat);
+        let result_str = match result {
+            RoundWinner::You => "WIN".bright_green().bold(),
+            RoundWinner::Opponent => "LOSS".red(),
+            RoundWinner::Draw => "DRAW".yellow(),
+        };
+
+        frame.println(format!(
+            "    Round {}: {} vs {} - {}",
+            i + 1,
+            format_move(my_move).bright_blue(),
+            format_move(opponent_move).bright_magenta(),
+            result_str
+        ));
+    }
+    frame.newline();
+}
+
+/// Start counting down to the result of a round both players have chosen in
+fn handle_round_result(
+    round: usize,
+    moves: (RockPaperScissorsMove, RockPaperScissorsMove),
+    winner: Option<i64>,
+    my_player_id: i64,
+    my_seat: Seat,
+    ui_state: &RockPaperScissorsUiState,
+) -> Option<RockPaperScissorsUiState> {
+    let (match_data, previous_rounds) = match ui_state {
+        RockPaperScissorsUiState::SelectMove { match_data, previous_rounds, .. } |
+        RockPaperScissorsUiState::WaitingForOpponentToReconnect { match_data, previous_rounds } => (match_data, previous_rounds),
+        _ => return None,
+    };
+    let (my_move, opponent_move) = if my_seat == Seat::FIRST { moves } else { (moves.1, moves.0) };
+    Some(RockPaperScissorsUiState::RoundReveal {
+        match_data: match_data.clone(),
+        previous_rounds: previous_rounds.clone(),
+        round,
+        my_move,
+        opponent_move,
+        winner: match winner {
+            Some(player_id) if player_id == my_player_id => RoundWinner::You,
+            Some(_) => RoundWinner::Opponent,
+            None => RoundWinner::Draw,
+        },
+        step: 0,
+    })
+}
+
+/// Move the countdown of a round on by one word, true if there was one left
+fn advance_reveal(ui_state: &mut RockPaperScissorsUiState) -> bool {
+    match ui_state {
+        RockPaperScissorsUiState::RoundReveal { step, .. } if *step < COUNTDOWN.len() => {
+            *step += 1;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Rules and controls shown by `?`
+pub fn help(match_data: Option<&Match>) -> Vec<String> {
+    let game_state = match_data
+        .and_then(|match_data| serde_json::from_value::<RockPaperScissorsGameState>(match_data.game_state.clone()).ok())
+        .unwrap_or_default();
+    let mut lines = if game_state.lizard_spock {
+        vec![
+            "Scissors cut paper and decapitate lizard, paper covers rock and disproves Spock,".to_string(),
+            "rock crushes lizard and scissors, lizard poisons Spock and eats paper,".to_string(),
+            "Spock smashes scissors and vaporizes rock".to_string(),
+        ]
+    } else {
+        vec!["Rock beats scissors, scissors beat paper, paper beats rock".to_string()]
+    };
+    lines.push(format!(
+        "Best of {}: the first to win {} rounds takes the match, drawn rounds do not count",
+        game_state.best_of,
+        game_state.best_of / 2 + 1
+    ));
+    let names = if game_state.lizard_spock { "rock, paper, scissors, lizard or spock" } else { "rock, paper or scissors" };
+    lines.push(format!("Type {names}, both choices are shown once you have both chosen"));
+    lines
+}
+
+fn is_lizard_spock(match_data: &Match) -> bool {
+    serde_json::from_value::<RockPaperScissorsGameState>(match_data.game_state.clone())
+        .map(|state| state.lizard_spock)
+        .unwrap_or(false)
+}
+
+fn move_names(match_data: &Match) -> Vec<&'static str> {
+    if is_lizard_spock(match_data) {
+        vec!["rock", "paper", "scissors", "lizard", "spock"]
+    } else {
+        vec!["rock", "paper", "scissors"]
+    }
+}
+
 fn format_move(m: &Option<RockPaperScissorsMove>) -> String {
     match m {
         Some(RockPaperScissorsMove::Rock) => "Rock".to_string(),
         Some(RockPaperScissorsMove::Paper) => "Paper".to_string(),
         Some(RockPaperScissorsMove::Scissors) => "Scissors".to_string(),
+        Some(RockPaperScissorsMove::Lizard) => "Lizard".to_string(),
+        Some(RockPaperScissorsMove::Spock) => "Spock".to_string(),
         Some(RockPaperScissorsMove::Redacted) => "???".to_string(),
         None => "---".to_string(),
     }
 }
 
-fn render_final_results(match_data: &Match, my_player_number: i32) {
+fn render_final_results(frame: &mut crate::ui::Frame, match_data: &Match, my_seat: Seat) {
     if let Ok(game_state) = serde_json::from_value::<RockPaperScissorsGameState>(match_data.game_state.clone()) {
-        println!("{}", "  Final Results:".bold());
-        println!();
+        crate::games::render_opponent(frame, match_data, my_seat);
+        frame.println("  Final Results:".bold());
+        frame.newline();
 
         let mut my_wins = 0;
         let mut opponent_wins = 0;
         let mut draws = 0;
 
         for (i, (p1_move, p2_move)) in game_state.rounds.iter().enumerate() {
-            let (my_move, opponent_move) = if my_player_number == 1 {
+            let (my_move, opponent_move) = if my_seat == Seat::FIRST {
                 (p1_move, p2_move)
             } else {
                 (p2_move, p1_move)
             };
 
-            let result = determine_round_winner(my_move, opponent_move, my_player_number);
+            let result = determine_round_winner(my_move, opponent_move, my_seat);
             let result_str = match result {
                 RoundWinner::You => {
                     my_wins += 1;
@@ -245,21 +379,21 @@ fn render_final_results(match_data: &Match, my_player_number: i32) {
                 }
             };
 
-            println!(
+            frame.println(format!(
                 "    Round {}: {} vs {} - {}",
                 i + 1,
                 format_move(my_move).bright_blue(),
                 format_move(opponent_move).bright_magenta(),
                 result_str
-            );
+            ));
         }
 
-        println!();
-        println!("  Score: {} - {} (Draws: {})",
+        frame.newline();
+        frame.println(format!("  Score: {} - {} (Draws: {})",
             my_wins.to_string().bright_green(),
             opponent_wins.to_string().red(),
             draws.to_string().yellow()
-        );
+        ));
     }
 }
 
@@ -278,7 +412,7 @@ fn handle_player_disconnected(
     my_player_id: i64,
     ui_state: &RockPaperScissorsUiState,
     opponent_disconnected: &mut bool,
-    _my_number: i32,
+    _my_seat: Seat,
 ) -> Option<RockPaperScissorsUiState> {
     if player_id == my_player_id {
         return None;
@@ -304,11 +438,12 @@ fn handle_player_disconnected(
 fn handle_match_ended(
     reason: &MatchEndReason,
     ui_state: &RockPaperScissorsUiState,
-    my_number: Option<i32>,
+    my_seat: Option<Seat>,
 ) -> RockPaperScissorsUiState {
     let final_match = match ui_state {
         RockPaperScissorsUiState::SelectMove { match_data, .. } |
-        RockPaperScissorsUiState::WaitingForOpponentToReconnect { match_data, .. } => match_data.clone(),
+        RockPaperScissorsUiState::WaitingForOpponentToReconnect { match_data, .. } |
+        RockPaperScissorsUiState::RoundReveal { match_data, .. } => match_data.clone(),
         _ => return ui_state.clone(),
     };
 
@@ -316,24 +451,42 @@ fn handle_match_ended(
         MatchEndReason::Disconnection => {
             RockPaperScissorsUiState::MatchEndedOpponentDisconnected(final_match)
         }
-        MatchEndReason::Ended => {
-            determine_match_end_state(&final_match, my_number)
+        MatchEndReason::Ended | MatchEndReason::Forfeit | MatchEndReason::Resignation | MatchEndReason::DrawAgreed => {
+            determine_match_end_state(&final_match, my_seat)
         }
     }
 }
 
-fn determine_match_end_state(match_data: &Match, my_number: Option<i32>) -> RockPaperScissorsUiState {
+/// A late move was rejected because the match already ended, show the outcome the server reported
+fn handle_match_already_finished(
+    outcome: Option<MatchOutcome>,
+    ui_state: &RockPaperScissorsUiState,
+    my_seat: Option<Seat>,
+) -> RockPaperScissorsUiState {
+    let mut final_match = match ui_state {
+        RockPaperScissorsUiState::SelectMove { match_data, .. } |
+        RockPaperScissorsUiState::WaitingForOpponentToReconnect { match_data, .. } |
+        RockPaperScissorsUiState::RoundReveal { match_data, .. } => match_data.clone(),
+        _ => return ui_state.clone(),
+    };
+
+    final_match.outcome = outcome;
+    final_match.in_progress = false;
+    determine_match_end_state(&final_match, my_seat)
+}
+
+fn determine_match_end_state(match_data: &Match, my_seat: Option<Seat>) -> RockPaperScissorsUiState {
     if let Some(outcome) = &match_data.outcome {
         match outcome {
             MatchOutcome::Player1Win => {
-                if my_number == Some(1) {
+                if my_seat == Some(Seat::FIRST) {
                     RockPaperScissorsUiState::MatchEndedYouWon(match_data.clone())
                 } else {
                     RockPaperScissorsUiState::MatchEndedYouLost(match_data.clone())
                 }
             }
             MatchOutcome::Player2Win => {
-                if my_number == Some(2) {
+                if my_seat == Some(Seat::SECOND) {
                     RockPaperScissorsUiState::MatchEndedYouWon(match_data.clone())
                 } else {
                     RockPaperScissorsUiState::MatchEndedYouLost(match_data.clone())
@@ -351,18 +504,18 @@ fn determine_match_end_state(match_data: &Match, my_number: Option<i32>) -> Rock
 fn handle_match_found_or_update(
     match_data: &Match,
     my_player_id: i64,
-    my_number: &mut Option<i32>,
+    my_seat: &mut Option<Seat>,
     ui_state: &RockPaperScissorsUiState,
     opponent_disconnected: &mut bool,
 ) -> Result<Option<RockPaperScissorsUiState>, Box<dyn std::error::Error>> {
     // Determine player number
-    if my_number.is_none() {
-        *my_number = Some(if match_data.player1_id == my_player_id { 1 } else { 2 });
+    if my_seat.is_none() {
+        *my_seat = Some(super::my_seat(match_data, my_player_id));
     }
 
     // Check if match has ended
     if !match_data.in_progress {
-        return Ok(Some(determine_match_end_state(match_data, *my_number)));
+        return Ok(Some(determine_match_end_state(match_data, *my_seat)));
     }
 
     // Parse game state
@@ -371,10 +524,9 @@ fn handle_match_found_or_update(
 
     // Check current round status
     if let Some(current_round) = game_state.rounds.last() {
-        let (you_selected, opponent_selected) = match my_number.unwrap() {
-            1 => (current_round.0.is_some(), current_round.1.is_some()),
-            2 => (current_round.1.is_some(), current_round.0.is_some()),
-            _ => (false, false),
+        let (you_selected, opponent_selected) = match my_seat.unwrap() {
+            Seat::FIRST => (current_round.0.is_some(), current_round.1.is_some()),
+            _ => (current_round.1.is_some(), current_round.0.is_some()),
         };
 
         // Check if we're transitioning to a state where we can select
@@ -382,6 +534,7 @@ fn handle_match_found_or_update(
             ui_state,
             RockPaperScissorsUiState::SelectMove { you_selected: true, .. } |
             RockPaperScissorsUiState::WaitingForOpponentToReconnect { .. } |
+            RockPaperScissorsUiState::RoundReveal { .. } |
             RockPaperScissorsUiState::WaitingForOpponentToJoin
         );
 
@@ -445,7 +598,7 @@ fn handle_game_state_update(
                 you_selected: new_you_selected,
             })
         }
-        RockPaperScissorsUiState::WaitingForOpponentToReconnect { .. } => {
+        RockPaperScissorsUiState::WaitingForOpponentToReconnect { .. } | RockPaperScissorsUiState::RoundReveal { .. } => {
             *opponent_disconnected = false;
             Some(RockPaperScissorsUiState::SelectMove {
                 match_data: match_data.clone(),
@@ -463,14 +616,13 @@ fn handle_user_input(
     ui_state: &RockPaperScissorsUiState,
     opponent_disconnected: bool,
     ws_client: &crate::websocket::WebSocketClient,
-    _my_number: i32,
+    _my_seat: Seat,
 ) -> Result<Option<RockPaperScissorsUiState>, Box<dyn std::error::Error>> {
-    let move_choice = match move_str {
-        "rock" => Some("rock"),
-        "paper" => Some("paper"),
-        "scissors" => Some("scissors"),
-        _ => None,
+    let allowed_moves = match ui_state {
+        RockPaperScissorsUiState::SelectMove { match_data, .. } => move_names(match_data),
+        _ => vec!["rock", "paper", "scissors"],
     };
+    let move_choice = allowed_moves.iter().find(|name| **name == move_str).copied();
 
     if let Some(move_name) = move_choice {
         let move_data = serde_json::json!({
@@ -502,7 +654,8 @@ fn handle_user_input(
             Ok(None)
         }
     } else {
-        println!("{}", "Invalid move. Please enter 'rock', 'paper', or 'scissors'.".red());
+        let choices = allowed_moves.iter().map(|name| format!("'{name}'")).collect::<Vec<_>>().join(", ");
+        println!("{}", format!("Invalid move. Please enter one of {choices}.").red());
         print!("  > ");
         io::stdout().flush()?;
         Ok(None)
@@ -513,16 +666,23 @@ async fn run_game_loop(
     ws_client: &crate::websocket::WebSocketClient,
     my_player_id: i64,
     initial_state: RockPaperScissorsUiState,
-    initial_my_number: Option<i32>,
+    initial_my_seat: Option<Seat>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut my_number = initial_my_number;
+    let mut my_seat = initial_my_seat;
     let mut ui_state = initial_state;
     let mut stdin_reader = tokio::io::BufReader::new(tokio::io::stdin());
     let mut input_line = String::new();
     let mut opponent_disconnected = false;
+    let mut resumed = crate::suspend::resumed();
+    super::reset_turn_clock();
 
     // Initial render
-    ui_state.render(my_number.unwrap_or(1));
+    let mut waiting_room = crate::waiting_room::WaitingRoom::default();
+
+    ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+    if matches!(ui_state, RockPaperScissorsUiState::WaitingForOpponentToJoin) {
+        waiting_room.render();
+    }
 
     loop {
         let waiting_for_input = matches!(
@@ -531,32 +691,94 @@ async fn run_game_loop(
         );
 
         tokio::select! {
+            Ok(()) = resumed.recv() => {
+                crate::games::request_state(ws_client).await;
+                ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+            }
+
             _ = tokio::time::sleep(tokio::time::Duration::from_millis(200)) => {
+                if matches!(ui_state, RockPaperScissorsUiState::WaitingForOpponentToJoin) && waiting_room.handle_keys()? {
+                    if waiting_room.take_leave_request() {
+                        ws_client.send(ClientMessage::LeaveMatchmaking)?;
+                    }
+                    ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                    waiting_room.render();
+                }
+
+                super::warn_turn_clock()?;
+                if crate::websocket::take_connection_change() || advance_reveal(&mut ui_state) {
+                    ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                }
                 let messages = ws_client.get_messages().await;
 
                 for msg in messages {
-                    if let ServerMessage::Error { message } = &msg {
+                    if super::show_idle_notice(&msg)? {
+                        continue;
+                    }
+
+                    if super::track_turn_clock(&msg, my_player_id) {
+                        ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                        continue;
+                    }
+
+                    match super::answer_ready_check(&msg, ws_client)? {
+                        super::ReadyCheckStep::Handled => continue,
+                        super::ReadyCheckStep::LeftQueue => return Ok(()),
+                        super::ReadyCheckStep::NotReadyCheck => {}
+                    }
+                    if matches!(msg, ServerMessage::MatchmakingCancelled) {
+                        return Ok(());
+                    }
+                    if super::explain_matchmaking_rejection(&msg, ws_client).await? {
+                        return Ok(());
+                    }
+
+                    if let Some(message) = crate::ui::error_text(&msg) {
                         println!("\n{}", format!("Error: {message}").red());
                         io::stdout().flush()?;
                         continue;
                     }
 
+                    if let ServerMessage::ServerBusy { retry_after } = &msg {
+                        crate::ui::show_server_busy(*retry_after)?;
+                        return Ok(());
+                    }
+
                     match &msg {
-                        ServerMessage::PlayerDisconnected { player_id } => {
+                        ServerMessage::RoundResult { round, moves, winner, .. } => {
+                            if let Some(new_state) = handle_round_result(
+                                *round,
+                                *moves,
+                                *winner,
+                                my_player_id,
+                                my_seat.unwrap_or(Seat::FIRST),
+                                &ui_state,
+                            ) {
+                                ui_state = new_state;
+                                ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                            }
+                        }
+                        ServerMessage::PlayerDisconnected { player_id, .. } => {
                             if let Some(new_state) = handle_player_disconnected(
                                 *player_id,
                                 my_player_id,
                                 &ui_state,
                                 &mut opponent_disconnected,
-                                my_number.unwrap_or(1),
+                                my_seat.unwrap_or(Seat::FIRST),
                             ) {
                                 ui_state = new_state;
-                                ui_state.render(my_number.unwrap_or(1));
+                                ui_state.render(my_seat.unwrap_or(Seat::FIRST));
                             }
                         }
-                        ServerMessage::MatchEnded { reason } => {
-                            ui_state = handle_match_ended(reason, &ui_state, my_number);
-                            ui_state.render(my_number.unwrap_or(1));
+                        ServerMessage::MatchEnded { reason, summary } => {
+                            ui_state = handle_match_ended(reason, &ui_state, my_seat);
+                            ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                            super::render_summary(summary.as_ref());
+                            return super::offer_rematch(ws_client, my_player_id).await;
+                        }
+                        ServerMessage::MatchAlreadyFinished { outcome, .. } => {
+                            ui_state = handle_match_already_finished(outcome.clone(), &ui_state, my_seat);
+                            ui_state.render(my_seat.unwrap_or(Seat::FIRST));
                             println!("\nPress any key to return to main menu...");
                             io::stdout().flush()?;
                             crate::ui::wait_for_keypress()?;
@@ -566,7 +788,7 @@ async fn run_game_loop(
                             if let Ok(Some(new_state)) = handle_match_found_or_update(
                                 match_data,
                                 my_player_id,
-                                &mut my_number,
+                                &mut my_seat,
                                 &ui_state,
                                 &mut opponent_disconnected,
                             ) {
@@ -579,13 +801,10 @@ async fn run_game_loop(
                                 );
 
                                 ui_state = new_state;
-                                ui_state.render(my_number.unwrap());
+                                ui_state.render(my_seat.unwrap());
 
                                 if should_exit {
-                                    println!("\nPress any key to return to main menu...");
-                                    io::stdout().flush()?;
-                                    crate::ui::wait_for_keypress()?;
-                                    return Ok(());
+                                    return super::offer_rematch(ws_client, my_player_id).await;
                                 }
 
                                 input_line.clear();
@@ -597,12 +816,12 @@ async fn run_game_loop(
                                 if let Ok(Some(new_state)) = handle_match_found_or_update(
                                     match_data,
                                     my_player_id,
-                                    &mut my_number,
+                                    &mut my_seat,
                                     &ui_state,
                                     &mut opponent_disconnected,
                                 ) {
                                     ui_state = new_state;
-                                    ui_state.render(my_number.unwrap());
+                                    ui_state.render(my_seat.unwrap());
                                     input_line.clear();
                                 }
                             } else if let Some(new_state) = handle_game_state_update(
@@ -611,7 +830,7 @@ async fn run_game_loop(
                                 &mut opponent_disconnected,
                             ) {
                                 ui_state = new_state;
-                                ui_state.render(my_number.unwrap());
+                                ui_state.render(my_seat.unwrap());
                             }
                         }
                         _ => {}
@@ -627,16 +846,30 @@ async fn run_game_loop(
                     if move_str.is_empty() {
                         continue;
                     }
+                    if crate::bugreport::is_command(&move_str) {
+                        crate::bugreport::run(Some(ws_client)).await;
+                        print!("  > ");
+                        io::stdout().flush().ok();
+                        continue;
+                    }
+                    if super::handle_match_command(&move_str, ws_client, my_player_id).await? {
+                        continue;
+                    }
+                    if super::is_help_command(&move_str) {
+                        super::show_help(&GameType::RockPaperScissors, ws_client.get_current_match().await.as_ref())?;
+                        ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                        continue;
+                    }
 
                     if let Ok(Some(new_state)) = handle_user_input(
                         &move_str,
                         &ui_state,
                         opponent_disconnected,
                         ws_client,
-                        my_number.unwrap_or(1),
+                        my_seat.unwrap_or(Seat::FIRST),
                     ) {
                         ui_state = new_state;
-                        ui_state.render(my_number.unwrap());
+                        ui_state.render(my_seat.unwrap());
                     }
                 }
             }
@@ -652,7 +885,13 @@ pub async fn start_game(session: &mut SessionState, game_type: GameType) -> Resu
     let ws_client = session.ws_client.as_ref().unwrap();
     let my_player_id = session.player_id.ok_or("No player ID in session")?;
 
-    ws_client.send(ClientMessage::JoinMatchmaking { game_type })?;
+    let options = read_game_options()?;
+    let series_length = super::read_series_length()?;
+    ws_client.send(ClientMessage::JoinMatchmaking {
+        game_type,
+        options: serde_json::to_value(options)?,
+        series_length,
+    })?;
 
     run_game_loop(
         ws_client,
@@ -662,6 +901,36 @@ pub async fn start_game(session: &mut SessionState, game_type: GameType) -> Resu
     ).await
 }
 
+fn read_game_options() -> Result<RockPaperScissorsOptions, Box<dyn std::error::Error>> {
+    let mut rl = DefaultEditor::new()?;
+    let mut options = RockPaperScissorsOptions::default();
+
+    loop {
+        let line = rl.readline("Play Rock-Paper-Scissors-Lizard-Spock? (y/N): ")?;
+        match line.trim().to_lowercase().as_str() {
+            "" | "n" | "no" => break,
+            "y" | "yes" => {
+                options.lizard_spock = true;
+                break;
+            }
+            _ => println!("{}", "Please answer y or n.".red()),
+        }
+    }
+
+    loop {
+        let line = rl.readline(&format!("Best of how many rounds? (odd, 1-{MAX_BEST_OF}, default {DEFAULT_BEST_OF}): "))?;
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(options);
+        }
+        options.best_of = line.parse().unwrap_or(0);
+        match options.validate() {
+            Ok(()) => return Ok(options),
+            Err(e) => println!("{}", e.red()),
+        }
+    }
+}
+
 pub async fn resume_game(session: &mut SessionState, game_match: Match) -> Result<(), Box<dyn std::error::Error>> {
     if session.ws_client.is_none() {
         session.connect_websocket().await?;
@@ -670,11 +939,7 @@ pub async fn resume_game(session: &mut SessionState, game_match: Match) -> Resul
     let ws_client = session.ws_client.as_ref().unwrap();
     let my_player_id = session.player_id.ok_or("No player ID in session")?;
 
-    let my_number = if game_match.player1_id == my_player_id {
-        Some(1)
-    } else {
-        Some(2)
-    };
+    let my_seat = Some(super::my_seat(&game_match, my_player_id));
 
     let previous_rounds = if let Ok(game_state) = serde_json::from_value::<RockPaperScissorsGameState>(game_match.game_state.clone()) {
         extract_previous_rounds(&game_state)
@@ -689,5 +954,5 @@ pub async fn resume_game(session: &mut SessionState, game_match: Match) -> Resul
         you_selected: false,
     };
 
-    run_game_loop(ws_client, my_player_id, initial_state, my_number).await
+    run_game_loop(ws_client, my_player_id, initial_state, my_seat).await
 }
@@ -1,8 +1,9 @@
-use battld_common::{games::{game_type::GameType, matches::{Match, MatchEndReason, MatchOutcome}, tic_tac_toe::TicTacToeGameState}, *};
+use battld_common::{games::{game_type::GameType, matches::{Match, MatchEndReason, MatchOutcome}, players::Seat, tic_tac_toe::{TicTacToeGameState, TicTacToeOptions, MAX_BOARD_SIZE, MIN_BOARD_SIZE}}, *};
 use crate::state::SessionState;
 use std::io::{self, Write};
 use tokio::io::AsyncBufReadExt;
 use colored::*;
+use rustyline::DefaultEditor;
 
 #[derive(Debug, Clone)]
 enum TicTacToeUiState {
@@ -17,105 +18,125 @@ enum TicTacToeUiState {
 }
 
 impl TicTacToeUiState {
-    fn render(&self, my_player_number: i32) {
-        crate::ui::clear_screen().ok();
+    fn render(&self, my_seat: Seat) {
+        let mut frame = crate::ui::Frame::default();
+        super::render_connection(&mut frame);
+        super::render_turn_clock(&mut frame);
 
         match self {
             TicTacToeUiState::WaitingForOpponentToJoin => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Tic-Tac-Toe".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                println!("{}", "  Waiting for opponent to join...".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Tic-Tac-Toe".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                frame.println("  Waiting for opponent to join...".yellow());
+                frame.newline();
             }
             TicTacToeUiState::MyTurn(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Tic-Tac-Toe".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player_number);
-                println!();
-                println!("{}", "  YOUR TURN".bright_green().bold());
-                println!();
-                println!("{}", "  Enter move as 'row col' (0-indexed, e.g., '1 2'):".dimmed());
-                print!("  > ");
-                io::stdout().flush().ok();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Tic-Tac-Toe".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  YOUR TURN".bright_green().bold());
+                frame.newline();
+                frame.println("  Enter move as 'row col' (0-indexed, e.g., '1 2'):".dimmed());
+                frame.print("  > ");
             }
             TicTacToeUiState::OpponentTurn(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Tic-Tac-Toe".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player_number);
-                println!();
-                println!("{}", "  Waiting for opponent's move...".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Tic-Tac-Toe".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  Waiting for opponent's move...".yellow());
+                frame.newline();
             }
             TicTacToeUiState::WaitingForOpponentToReconnect(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Tic-Tac-Toe".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player_number);
-                println!();
-                println!("{}", "  Opponent disconnected. Waiting for reconnection...".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Tic-Tac-Toe".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  Opponent disconnected. Waiting for reconnection...".yellow());
+                frame.newline();
             }
             TicTacToeUiState::MatchEndedYouWon(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Tic-Tac-Toe".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player_number);
-                println!();
-                println!("{}", "  YOU WON! 🎉".bright_green().bold());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Tic-Tac-Toe".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  YOU WON! 🎉".bright_green().bold());
+                frame.newline();
             }
             TicTacToeUiState::MatchEndedYouLost(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Tic-Tac-Toe".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player_number);
-                println!();
-                println!("{}", "  You lost.".red());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Tic-Tac-Toe".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  You lost.".red());
+                frame.newline();
             }
             TicTacToeUiState::MatchEndedDraw(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Tic-Tac-Toe".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player_number);
-                println!();
-                println!("{}", "  It's a draw!".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Tic-Tac-Toe".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  It's a draw!".yellow());
+                frame.newline();
             }
             TicTacToeUiState::MatchEndedOpponentDisconnected(match_data) => {
-                println!("\n{}", "=".repeat(50));
-                println!("{}", "  Tic-Tac-Toe".bright_cyan().bold());
-                println!("{}", "=".repeat(50));
-                println!();
-                render_game_board(match_data, my_player_number);
-                println!();
-                println!("{}", "  Match ended - Opponent disconnected.".yellow());
-                println!();
+                frame.println(format!("\n{}", "=".repeat(crate::ui::rule_width())));
+                frame.println("  Tic-Tac-Toe".bright_cyan().bold());
+                frame.println("=".repeat(crate::ui::rule_width()));
+                frame.newline();
+                render_game_board(&mut frame, match_data, my_seat);
+                frame.newline();
+                frame.println("  Match ended - Opponent disconnected.".yellow());
+                frame.newline();
             }
         }
+        crate::ui::present(&frame).ok();
     }
 }
 
-fn render_game_board(match_data: &Match, my_player_number: i32) {
+/// Rules and controls shown by `?`
+pub fn help(match_data: Option<&Match>) -> Vec<String> {
+    let game_state = match_data
+        .and_then(|match_data| serde_json::from_value::<TicTacToeGameState>(match_data.game_state.clone()).ok())
+        .unwrap_or_default();
+    let last = game_state.board_size - 1;
+    vec![
+        format!("Put {} of your marks in a row, a column or a diagonal to win, a full board is a draw", game_state.win_length),
+        format!("Enter a move as 'row col' counting from 0: '0 0' is the top left, '{last} {last}' the bottom right"),
+    ]
+}
+
+fn render_game_board(frame: &mut crate::ui::Frame, match_data: &Match, my_seat: Seat) {
     if let Ok(game_state) = serde_json::from_value::<TicTacToeGameState>(match_data.game_state.clone()) {
-        println!("  You are: {}", if my_player_number == 1 { "X".bright_blue() } else { "O".bright_magenta() });
-        println!();
-
-        // Render the board (3x3 grid from flat array)
-        for row in 0..3 {
-            print!("  ");
-            for col in 0..3 {
-                let idx = row * 3 + col;
+        crate::games::render_opponent(frame, match_data, my_seat);
+        frame.println(format!("  You are: {}", if my_seat == Seat::FIRST { "X".bright_blue() } else { "O".bright_magenta() }));
+        frame.newline();
+
+        if game_state.win_length != game_state.board_size {
+            frame.println(format!("  Get {} in a row to win", game_state.win_length));
+            frame.newline();
+        }
+
+        let size = game_state.board_size;
+        for row in 0..size {
+            frame.print("  ");
+            for col in 0..size {
+                let idx = row * size + col;
                 let cell = game_state.board[idx];
                 let cell_str = match cell {
                     0 => "·".dimmed().to_string(),
@@ -123,14 +144,14 @@ fn render_game_board(match_data: &Match, my_player_number: i32) {
                     2 => "O".bright_magenta().to_string(),
                     _ => " ".to_string(),
                 };
-                print!(" {cell_str} ");
-                if col < 2 {
-                    print!("{}", "|".dimmed());
+                frame.print(format!(" {cell_str} "));
+                if col < size - 1 {
+                    frame.print("|".dimmed());
                 }
             }
-            println!();
-            if row < 2 {
-                println!("  {}", "---+---+---".dimmed());
+            frame.newline();
+            if row < size - 1 {
+                frame.println(format!("  {}", vec!["---"; size].join("+").dimmed()));
             }
         }
     }
@@ -142,7 +163,7 @@ fn handle_player_disconnected(
     ui_state: &TicTacToeUiState,
     opponent_disconnected: &mut bool,
     waiting_for_input: bool,
-    _my_number: i32,
+    _my_seat: Seat,
 ) -> Option<TicTacToeUiState> {
     if player_id == my_player_id {
         return None;
@@ -162,7 +183,7 @@ fn handle_player_disconnected(
 fn handle_match_ended(
     reason: &MatchEndReason,
     ui_state: &TicTacToeUiState,
-    my_number: Option<i32>,
+    my_seat: Option<Seat>,
 ) -> TicTacToeUiState {
     let final_match = match ui_state {
         TicTacToeUiState::MyTurn(m) |
@@ -175,24 +196,42 @@ fn handle_match_ended(
         MatchEndReason::Disconnection => {
             TicTacToeUiState::MatchEndedOpponentDisconnected(final_match)
         }
-        MatchEndReason::Ended => {
-            determine_match_end_state(&final_match, my_number)
+        MatchEndReason::Ended | MatchEndReason::Forfeit | MatchEndReason::Resignation | MatchEndReason::DrawAgreed => {
+            determine_match_end_state(&final_match, my_seat)
         }
     }
 }
 
-fn determine_match_end_state(match_data: &Match, my_number: Option<i32>) -> TicTacToeUiState {
+/// A late move was rejected because the match already ended, show the outcome the server reported
+fn handle_match_already_finished(
+    outcome: Option<MatchOutcome>,
+    ui_state: &TicTacToeUiState,
+    my_seat: Option<Seat>,
+) -> TicTacToeUiState {
+    let mut final_match = match ui_state {
+        TicTacToeUiState::MyTurn(m) |
+        TicTacToeUiState::OpponentTurn(m) |
+        TicTacToeUiState::WaitingForOpponentToReconnect(m) => m.clone(),
+        _ => return ui_state.clone(),
+    };
+
+    final_match.outcome = outcome;
+    final_match.in_progress = false;
+    determine_match_end_state(&final_match, my_seat)
+}
+
+fn determine_match_end_state(match_data: &Match, my_seat: Option<Seat>) -> TicTacToeUiState {
     if let Some(outcome) = &match_data.outcome {
         match outcome {
             MatchOutcome::Player1Win => {
-                if my_number == Some(1) {
+                if my_seat == Some(Seat::FIRST) {
                     TicTacToeUiState::MatchEndedYouWon(match_data.clone())
                 } else {
                     TicTacToeUiState::MatchEndedYouLost(match_data.clone())
                 }
             }
             MatchOutcome::Player2Win => {
-                if my_number == Some(2) {
+                if my_seat == Some(Seat::SECOND) {
                     TicTacToeUiState::MatchEndedYouWon(match_data.clone())
                 } else {
                     TicTacToeUiState::MatchEndedYouLost(match_data.clone())
@@ -210,18 +249,18 @@ fn determine_match_end_state(match_data: &Match, my_number: Option<i32>) -> TicT
 fn handle_match_found_or_update(
     match_data: &Match,
     my_player_id: i64,
-    my_number: &mut Option<i32>,
+    my_seat: &mut Option<Seat>,
     ui_state: &TicTacToeUiState,
     opponent_disconnected: bool,
 ) -> Result<Option<TicTacToeUiState>, Box<dyn std::error::Error>> {
     // Determine player number
-    if my_number.is_none() {
-        *my_number = Some(if match_data.player1_id == my_player_id { 1 } else { 2 });
+    if my_seat.is_none() {
+        *my_seat = Some(super::my_seat(match_data, my_player_id));
     }
 
     // Check if match has ended
     if !match_data.in_progress {
-        return Ok(Some(determine_match_end_state(match_data, *my_number)));
+        return Ok(Some(determine_match_end_state(match_data, *my_seat)));
     }
 
     // Parse game state to determine whose turn it is
@@ -234,7 +273,7 @@ fn handle_match_found_or_update(
         TicTacToeUiState::WaitingForOpponentToJoin
     );
 
-    let new_state = if game_state.current_player == my_number.unwrap() && !game_state.is_finished {
+    let new_state = if game_state.current_player == my_seat.unwrap().symbol() && !game_state.is_finished {
         // If transitioning from opponent's turn to my turn, drain stdin buffer
         if was_opponent_turn {
             crate::ui::drain_stdin_buffer();
@@ -271,18 +310,14 @@ fn handle_user_input(
         return Ok(None);
     };
 
-    // Validate bounds
-    if row >= 3 || col >= 3 {
-        println!("{}", "Invalid move. Row and column must be between 0 and 2.".red());
-        print!("  > ");
-        io::stdout().flush()?;
-        return Ok(None);
-    }
-
-    // Validate cell is not already occupied
     if let TicTacToeUiState::MyTurn(match_data) = ui_state {
         if let Ok(game_state) = serde_json::from_value::<TicTacToeGameState>(match_data.game_state.clone()) {
-            let index = row * 3 + col;
+            let Some(index) = game_state.coords_to_index(row, col) else {
+                println!("{}", format!("Invalid move. Row and column must be between 0 and {}.", game_state.board_size - 1).red());
+                print!("  > ");
+                io::stdout().flush()?;
+                return Ok(None);
+            };
             if game_state.board[index] != 0 {
                 println!("{}", "Invalid move. That cell is already occupied.".red());
                 print!("  > ");
@@ -314,48 +349,104 @@ async fn run_game_loop(
     ws_client: &crate::websocket::WebSocketClient,
     my_player_id: i64,
     initial_state: TicTacToeUiState,
-    initial_my_number: Option<i32>,
+    initial_my_seat: Option<Seat>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut my_number = initial_my_number;
+    let mut my_seat = initial_my_seat;
     let mut ui_state = initial_state;
     let mut stdin_reader = tokio::io::BufReader::new(tokio::io::stdin());
     let mut input_line = String::new();
     let mut opponent_disconnected = false;
+    let mut resumed = crate::suspend::resumed();
+    super::reset_turn_clock();
 
     // Initial render
-    ui_state.render(my_number.unwrap_or(1));
+    let mut waiting_room = crate::waiting_room::WaitingRoom::default();
+
+    ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+    if matches!(ui_state, TicTacToeUiState::WaitingForOpponentToJoin) {
+        waiting_room.render();
+    }
 
     loop {
         let waiting_for_input = matches!(ui_state, TicTacToeUiState::MyTurn(_));
 
         tokio::select! {
+            Ok(()) = resumed.recv() => {
+                crate::games::request_state(ws_client).await;
+                ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+            }
+
             _ = tokio::time::sleep(tokio::time::Duration::from_millis(200)) => {
+                if matches!(ui_state, TicTacToeUiState::WaitingForOpponentToJoin) && waiting_room.handle_keys()? {
+                    if waiting_room.take_leave_request() {
+                        ws_client.send(ClientMessage::LeaveMatchmaking)?;
+                    }
+                    ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                    waiting_room.render();
+                }
+
+                super::warn_turn_clock()?;
+                if crate::websocket::take_connection_change() {
+                    ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                }
                 let messages = ws_client.get_messages().await;
 
                 for msg in messages {
-                    if let ServerMessage::Error { message } = &msg {
+                    if super::show_idle_notice(&msg)? {
+                        continue;
+                    }
+
+                    if super::track_turn_clock(&msg, my_player_id) {
+                        ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                        continue;
+                    }
+
+                    match super::answer_ready_check(&msg, ws_client)? {
+                        super::ReadyCheckStep::Handled => continue,
+                        super::ReadyCheckStep::LeftQueue => return Ok(()),
+                        super::ReadyCheckStep::NotReadyCheck => {}
+                    }
+                    if matches!(msg, ServerMessage::MatchmakingCancelled) {
+                        return Ok(());
+                    }
+                    if super::explain_matchmaking_rejection(&msg, ws_client).await? {
+                        return Ok(());
+                    }
+
+                    if let Some(message) = crate::ui::error_text(&msg) {
                         println!("\n{}", format!("Error: {message}").red());
                         io::stdout().flush()?;
                         continue;
                     }
 
+                    if let ServerMessage::ServerBusy { retry_after } = &msg {
+                        crate::ui::show_server_busy(*retry_after)?;
+                        return Ok(());
+                    }
+
                     match &msg {
-                        ServerMessage::PlayerDisconnected { player_id } => {
+                        ServerMessage::PlayerDisconnected { player_id, .. } => {
                             if let Some(new_state) = handle_player_disconnected(
                                 *player_id,
                                 my_player_id,
                                 &ui_state,
                                 &mut opponent_disconnected,
                                 waiting_for_input,
-                                my_number.unwrap_or(1),
+                                my_seat.unwrap_or(Seat::FIRST),
                             ) {
                                 ui_state = new_state;
-                                ui_state.render(my_number.unwrap_or(1));
+                                ui_state.render(my_seat.unwrap_or(Seat::FIRST));
                             }
                         }
-                        ServerMessage::MatchEnded { reason } => {
-                            ui_state = handle_match_ended(reason, &ui_state, my_number);
-                            ui_state.render(my_number.unwrap_or(1));
+                        ServerMessage::MatchEnded { reason, summary } => {
+                            ui_state = handle_match_ended(reason, &ui_state, my_seat);
+                            ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                            super::render_summary(summary.as_ref());
+                            return super::offer_rematch(ws_client, my_player_id).await;
+                        }
+                        ServerMessage::MatchAlreadyFinished { outcome, .. } => {
+                            ui_state = handle_match_already_finished(outcome.clone(), &ui_state, my_seat);
+                            ui_state.render(my_seat.unwrap_or(Seat::FIRST));
                             println!("\nPress any key to return to main menu...");
                             io::stdout().flush()?;
                             crate::ui::wait_for_keypress()?;
@@ -365,7 +456,7 @@ async fn run_game_loop(
                             if let Ok(Some(new_state)) = handle_match_found_or_update(
                                 match_data,
                                 my_player_id,
-                                &mut my_number,
+                                &mut my_seat,
                                 &ui_state,
                                 opponent_disconnected,
                             ) {
@@ -383,13 +474,10 @@ async fn run_game_loop(
                                 }
 
                                 ui_state = new_state;
-                                ui_state.render(my_number.unwrap());
+                                ui_state.render(my_seat.unwrap());
 
                                 if should_exit {
-                                    println!("\nPress any key to return to main menu...");
-                                    io::stdout().flush()?;
-                                    crate::ui::wait_for_keypress()?;
-                                    return Ok(());
+                                    return super::offer_rematch(ws_client, my_player_id).await;
                                 }
 
                                 input_line.clear();
@@ -408,6 +496,20 @@ async fn run_game_loop(
                     if trimmed.is_empty() {
                         continue;
                     }
+                    if crate::bugreport::is_command(&trimmed) {
+                        crate::bugreport::run(Some(ws_client)).await;
+                        print!("  > ");
+                        io::stdout().flush().ok();
+                        continue;
+                    }
+                    if super::handle_match_command(&trimmed, ws_client, my_player_id).await? {
+                        continue;
+                    }
+                    if super::is_help_command(&trimmed) {
+                        super::show_help(&GameType::TicTacToe, ws_client.get_current_match().await.as_ref())?;
+                        ui_state.render(my_seat.unwrap_or(Seat::FIRST));
+                        continue;
+                    }
 
                     if let Ok(Some(new_state)) = handle_user_input(
                         &trimmed,
@@ -416,7 +518,7 @@ async fn run_game_loop(
                         ws_client,
                     ) {
                         ui_state = new_state;
-                        ui_state.render(my_number.unwrap());
+                        ui_state.render(my_seat.unwrap());
                     }
                 }
             }
@@ -424,6 +526,47 @@ async fn run_game_loop(
     }
 }
 
+fn read_game_options() -> Result<TicTacToeOptions, Box<dyn std::error::Error>> {
+    let mut rl = DefaultEditor::new()?;
+
+    let board_size = read_number(
+        &mut rl,
+        &format!("Board size ({MIN_BOARD_SIZE}-{MAX_BOARD_SIZE}, default {MIN_BOARD_SIZE}): "),
+        MIN_BOARD_SIZE..=MAX_BOARD_SIZE,
+    )?
+    .unwrap_or(MIN_BOARD_SIZE);
+
+    let win_length = if board_size > MIN_BOARD_SIZE {
+        read_number(
+            &mut rl,
+            &format!("Marks in a row to win ({MIN_BOARD_SIZE}-{board_size}, default {board_size}): "),
+            MIN_BOARD_SIZE..=board_size,
+        )?
+    } else {
+        None
+    };
+
+    Ok(TicTacToeOptions { board_size, win_length })
+}
+
+fn read_number(
+    rl: &mut DefaultEditor,
+    prompt: &str,
+    range: std::ops::RangeInclusive<usize>,
+) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+    loop {
+        let line = rl.readline(prompt)?;
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        match line.parse::<usize>() {
+            Ok(value) if range.contains(&value) => return Ok(Some(value)),
+            _ => println!("{}", format!("Please enter a number between {} and {}.", range.start(), range.end()).red()),
+        }
+    }
+}
+
 pub async fn start_game(session: &mut SessionState, game_type: GameType) -> Result<(), Box<dyn std::error::Error>> {
     if session.ws_client.is_none() {
         session.connect_websocket().await?;
@@ -432,7 +575,13 @@ pub async fn start_game(session: &mut SessionState, game_type: GameType) -> Resu
     let ws_client = session.ws_client.as_ref().unwrap();
     let my_player_id = session.player_id.ok_or("No player ID in session")?;
 
-    ws_client.send(ClientMessage::JoinMatchmaking { game_type })?;
+    let options = read_game_options()?;
+    let series_length = super::read_series_length()?;
+    ws_client.send(ClientMessage::JoinMatchmaking {
+        game_type,
+        options: serde_json::to_value(options)?,
+        series_length,
+    })?;
 
     run_game_loop(
         ws_client,
@@ -446,10 +595,10 @@ pub async fn resume_game(session: &SessionState, game_match: Match) -> Result<()
     let ws_client = session.ws_client.as_ref().ok_or("Not connected to WebSocket")?;
     let my_player_id = session.player_id.ok_or("No player ID in session")?;
 
-    let my_number = Some(if game_match.player1_id == my_player_id { 1 } else { 2 });
+    let my_seat = Some(super::my_seat(&game_match, my_player_id));
 
     let initial_state = if let Ok(game_state) = serde_json::from_value::<TicTacToeGameState>(game_match.game_state.clone()) {
-        if Some(game_state.current_player) == my_number && !game_state.is_finished {
+        if Some(game_state.current_player) == my_seat.map(Seat::symbol) && !game_state.is_finished {
             TicTacToeUiState::MyTurn(game_match.clone())
         } else {
             TicTacToeUiState::OpponentTurn(game_match.clone())
@@ -458,5 +607,5 @@ pub async fn resume_game(session: &SessionState, game_match: Match) -> Result<()
         TicTacToeUiState::OpponentTurn(game_match.clone())
     };
 
-    run_game_loop(ws_client, my_player_id, initial_state, my_number).await
+    run_game_loop(ws_client, my_player_id, initial_state, my_seat).await
 }
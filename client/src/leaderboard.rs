@@ -1,19 +1,18 @@
-use battld_common::{HEADER_AUTH, LeaderboardResponse};
+use battld_common::games::game_type::GameType;
 use colored::*;
 use std::io::{self, Write};
 
+use crate::api::player::{fetch_leaderboard, fetch_time_preferences, set_friend};
 use crate::state::*;
+use crate::timestamps;
 use crate::ui::*;
+use crate::width::pad_right;
 
 pub async fn show_leaderboard(session: &mut SessionState) -> Result<(), Box<dyn std::error::Error>> {
     if !session.is_authenticated {
         return Err("Not authenticated".into());
     }
 
-    let config = &session.config;
-    let server_url = config.server_url.as_ref().ok_or("No server URL configured")?;
-    let token = session.auth_token.as_ref().ok_or("No auth token")?;
-
     let page_size = match crossterm::terminal::size() {
         Ok((_, h)) => {
             ((h as i64).saturating_sub(10)).max(5)
@@ -22,53 +21,70 @@ pub async fn show_leaderboard(session: &mut SessionState) -> Result<(), Box<dyn
     };
 
     let mut offset = 0i64;
+    let mut friends_only = false;
+    let mut game_type: Option<GameType> = None;
+    // None follows the current season
+    let mut season: Option<i64> = None;
+    let mut notice: Option<String> = None;
+    let preferences = fetch_time_preferences(session).await.unwrap_or_default();
 
     loop {
         clear_screen()?;
         println!("\n{}", "Loading leaderboard...".cyan());
 
-        let client = reqwest::Client::new();
-        let url = format!("{server_url}/leaderboard?limit={page_size}&offset={offset}");
-
-        let response = client
-            .get(&url)
-            .header(HEADER_AUTH, format!("Bearer {token}"))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(format!("Server error: {}", response.status()).into());
-        }
-
-        let leaderboard: LeaderboardResponse = response.json().await?;
+        let leaderboard = fetch_leaderboard(session, page_size, offset, friends_only, game_type.as_ref(), season).await?;
 
         clear_screen()?;
         println!();
         println!("{}", "═══════════════════════════════════════════════════════════════════".bright_cyan());
-        println!("{}", "                           LEADERBOARD                             ".bright_cyan().bold());
+        let mut title = "LEADERBOARD".to_string();
+        if let Some(game_type) = &game_type {
+            title.push_str(&format!(" - {}", game_type.to_string().to_uppercase()));
+        }
+        if friends_only {
+            title.push_str(" - FRIENDS");
+        }
+        if let Some(shown) = &leaderboard.season {
+            title.push_str(&format!(" - SEASON {}", shown.id));
+        }
+        println!("{}", format!("{title:^67}").bright_cyan().bold());
         println!("{}", "═══════════════════════════════════════════════════════════════════".bright_cyan());
         println!();
+        if let Some(shown) = &leaderboard.season {
+            let period = if shown.archived {
+                format!("Final standings, ended {}", timestamps::absolute(shown.ends_at, &preferences))
+            } else {
+                format!("Ends {}", timestamps::absolute(shown.ends_at, &preferences))
+            };
+            println!("{}", format!("Season {} - {period}", shown.id).bright_magenta());
+        }
 
         let current_page = (offset / page_size) + 1;
         let total_pages = ((leaderboard.total_count + page_size - 1) / page_size).max(1);
 
         println!("{}", format!("Page {} of {} (Total players: {})", current_page, total_pages, leaderboard.total_count).bright_yellow());
         println!("{}", "───────────────────────────────────────────────────────────────────".dimmed());
-        println!("{:>4} {:30} {:>10}",
-            "Rank".dimmed(), "Player".dimmed(), "Score".dimmed());
+        println!("{:>4} {:30} {:>10} {:>8}  {}",
+            "Rank".dimmed(), "Player".dimmed(), "Score".dimmed(), "Rating".dimmed(), "Tier".dimmed());
         println!("{}", "───────────────────────────────────────────────────────────────────".dimmed());
 
         for entry in &leaderboard.entries {
             let rank_str = format!("#{}", entry.rank);
-            println!("{:>4} {:30} {:>10}",
+            println!("{:>4} {} {:>10} {:>8}  {}",
                 rank_str,
-                entry.player_name,
-                entry.score);
+                pad_right(&entry.player_name, 30),
+                entry.score,
+                entry.rating_label(),
+                entry.tier.as_deref().unwrap_or("-"));
         }
 
         println!();
         println!("{}", "═══════════════════════════════════════════════════════════════════".bright_cyan());
 
+        if let Some(notice) = notice.take() {
+            println!("{}", notice.yellow());
+        }
+
         let mut controls = vec![];
         if offset > 0 {
             controls.push("p: previous");
@@ -76,6 +92,16 @@ pub async fn show_leaderboard(session: &mut SessionState) -> Result<(), Box<dyn
         if offset + page_size < leaderboard.total_count {
             controls.push("n: next");
         }
+        let shown = leaderboard.season.as_ref();
+        if shown.is_some_and(|shown| shown.id > 1) {
+            controls.push("<: previous season");
+        }
+        if shown.is_some_and(|shown| shown.archived) {
+            controls.push(">: next season");
+        }
+        controls.push(if friends_only { "f: show everyone" } else { "f: show friends" });
+        controls.push("g: rank by game rating");
+        controls.push("a N / x N: add / remove friend at rank N");
         controls.push("q: quit");
 
         println!("{}", controls.join(" | ").dimmed());
@@ -86,18 +112,63 @@ pub async fn show_leaderboard(session: &mut SessionState) -> Result<(), Box<dyn
         let mut input = String::new();
         io::stdin().read_line(&mut input)?;
         let choice = input.trim().to_lowercase();
+        let parts: Vec<&str> = choice.split_whitespace().collect();
 
-        match choice.as_str() {
-            "n" if offset + page_size < leaderboard.total_count => {
+        match parts.as_slice() {
+            ["n"] if offset + page_size < leaderboard.total_count => {
                 offset += page_size;
             }
-            "p" if offset > 0 => {
+            ["p"] if offset > 0 => {
                 offset = (offset - page_size).max(0);
             }
-            "q" => break,
+            ["f"] => {
+                friends_only = !friends_only;
+                offset = 0;
+            }
+            ["g"] => {
+                // Ratings in each game are only kept for the current season
+                game_type = next_game_type(game_type.as_ref());
+                season = None;
+                offset = 0;
+            }
+            ["<"] if shown.is_some_and(|shown| shown.id > 1) => {
+                season = shown.map(|shown| shown.id - 1);
+                game_type = None;
+                offset = 0;
+            }
+            [">"] if shown.is_some_and(|shown| shown.archived) => {
+                season = shown.map(|shown| shown.id + 1);
+                offset = 0;
+            }
+            ["a" | "x", rank] => {
+                let entry = rank
+                    .trim_start_matches('#')
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|rank| leaderboard.entries.iter().find(|e| e.rank == rank));
+                let Some(entry) = entry else {
+                    notice = Some("No player with that rank on this page".to_string());
+                    continue;
+                };
+                let add = parts[0] == "a";
+                notice = Some(match set_friend(session, entry.player_id, add).await {
+                    Ok(()) if add => format!("Added {} to your friends", entry.player_name),
+                    Ok(()) => format!("Removed {} from your friends", entry.player_name),
+                    Err(e) => format!("Could not update friends: {e}"),
+                });
+            }
+            ["q"] => break,
             _ => {}
         }
     }
 
     Ok(())
 }
+
+/// Overall ranking first, then each game in turn
+fn next_game_type(current: Option<&GameType>) -> Option<GameType> {
+    match current {
+        None => GameType::ALL.first().cloned(),
+        Some(current) => GameType::ALL.iter().skip_while(|game_type| *game_type != current).nth(1).cloned(),
+    }
+}
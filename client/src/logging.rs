@@ -0,0 +1,65 @@
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::path::Path;
+use tracing::Level;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+pub const DEFAULT_LOG_FILE: &str = "client.log";
+
+/// Rotated files kept next to the current one
+const MAX_LOG_FILES: usize = 7;
+
+const REDACTED: &str = "[redacted]";
+
+/// Fields holding credentials, never logged
+const SECRET_FIELDS: &[&str] = &["token", "session_token", "signature", "private_key"];
+
+/// Send logs to `log_file`, rotated daily, with message-level traffic only when `verbose`
+/// The returned guard flushes pending lines on drop, keep it alive until exit
+pub fn init(log_file: &str, verbose: bool) -> Result<WorkerGuard, Box<dyn std::error::Error>> {
+    let path = Path::new(log_file);
+    let directory = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or(DEFAULT_LOG_FILE);
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(file_name)
+        .max_log_files(MAX_LOG_FILES)
+        .build(directory)?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    tracing_subscriber::fmt()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_max_level(if verbose { Level::DEBUG } else { Level::INFO })
+        .try_init()
+        .map_err(|e| e.to_string())?;
+    Ok(guard)
+}
+
+/// JSON rendering of a message with credentials redacted
+pub fn message<T: Serialize>(message: &T) -> String {
+    match serde_json::to_value(message) {
+        Ok(value) => redact_json(value).to_string(),
+        Err(_) => "<unserializable message>".to_string(),
+    }
+}
+
+fn redact_json(value: JsonValue) -> JsonValue {
+    match value {
+        JsonValue::Object(map) => JsonValue::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    if SECRET_FIELDS.contains(&key.as_str()) && !value.is_null() {
+                        (key, JsonValue::String(REDACTED.to_string()))
+                    } else {
+                        (key, redact_json(value))
+                    }
+                })
+                .collect(),
+        ),
+        JsonValue::Array(items) => JsonValue::Array(items.into_iter().map(redact_json).collect()),
+        other => other,
+    }
+}
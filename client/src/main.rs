@@ -1,20 +1,36 @@
 pub mod api;
+pub mod arena;
 pub mod auth;
+pub mod bugreport;
+pub mod cast;
+pub mod challenges;
 pub mod config;
 pub mod leaderboard;
+pub mod logging;
+pub mod party;
+pub mod plugin;
+pub mod quests;
+pub mod recovery;
+pub mod rejoin;
+pub mod replay;
+pub mod servers;
 pub mod games;
 pub mod state;
 pub mod stats;
+pub mod suspend;
+pub mod timestamps;
 pub mod ui;
 pub mod utils;
+pub mod waiting_room;
 pub mod websocket;
+pub mod width;
 
 use std::io;
 
-use battld_common::games::{game_type::GameType, matches::Match};
+use battld_common::{games::{game_type::GameType, matches::{Match, MatchOutcome}, players::Seat}, MatchSummary};
 use colored::*;
 use crossterm::{event::{self, Event}, terminal};
-use rustyline::DefaultEditor;
+use rustyline::{DefaultEditor, ExternalPrinter};
 
 use auth::try_auto_login;
 use leaderboard::*;
@@ -23,45 +39,149 @@ use stats::*;
 use ui::*;
 use utils::VERSION;
 
-use crate::games::{rock_paper_scissors, tic_tac_toe, briscola, chess};
+/// How often the menu checks for challenges and arenas to announce
+const CHALLENGE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+const USAGE: &str = "Usage: client [--verbose] [--log-file <path>] [--queue <game> | --challenge <player> <game> | --resume | --stats | --recovery-phrase] [config.json]";
+
+/// Command line: `client [--verbose] [--log-file <path>] [shortcut] [config.json]`
+struct Args {
+    config_path: String,
+    log_file: String,
+    verbose: bool,
+    shortcut: Option<Shortcut>,
+}
+
+/// Flows started right after logging in, skipping the menu, the client exits once they are over
+enum Shortcut {
+    Queue(GameType),
+    /// Name or id of the player to challenge, and the game
+    Challenge(String, GameType),
+    Resume,
+    Stats,
+    /// Move the account to a key derived from a new recovery phrase
+    RecoveryPhrase,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args {
+            config_path: "config.json".to_string(),
+            log_file: logging::DEFAULT_LOG_FILE.to_string(),
+            verbose: false,
+            shortcut: None,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-v" | "--verbose" => parsed.verbose = true,
+                "--log-file" => {
+                    if let Some(log_file) = args.next() {
+                        parsed.log_file = log_file;
+                    }
+                }
+                "--queue" => {
+                    let game = args.next().ok_or("--queue needs a game")?;
+                    parsed.shortcut = Some(Shortcut::Queue(parse_game_type(&game)?));
+                }
+                "--challenge" => {
+                    let (Some(opponent), Some(game)) = (args.next(), args.next()) else {
+                        return Err("--challenge needs a player and a game".to_string());
+                    };
+                    parsed.shortcut = Some(Shortcut::Challenge(opponent, parse_game_type(&game)?));
+                }
+                "--resume" => parsed.shortcut = Some(Shortcut::Resume),
+                "--stats" => parsed.shortcut = Some(Shortcut::Stats),
+                "--recovery-phrase" => parsed.shortcut = Some(Shortcut::RecoveryPhrase),
+                _ => parsed.config_path = arg,
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// A game by its name, ignoring case and punctuation, so `tic-tac-toe` and `TicTacToe` both work
+fn parse_game_type(name: &str) -> Result<GameType, String> {
+    let simplified = |name: &str| name.chars().filter(char::is_ascii_alphanumeric).collect::<String>().to_lowercase();
+    GameType::ALL
+        .into_iter()
+        .find(|game_type| simplified(&game_type.to_string()) == simplified(name))
+        .ok_or_else(|| {
+            let known: Vec<String> = GameType::ALL.iter().map(|game_type| simplified(&game_type.to_string())).collect();
+            format!("Unknown game {name}, expected one of {}", known.join(", "))
+        })
+}
 
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
+    suspend::install();
 
-    let config_path = std::env::args()
-        .nth(1)
-        .unwrap_or_else(|| "config.json".to_string());
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    bugreport::set_log_file(&args.log_file);
+    let _log_guard = match logging::init(&args.log_file, args.verbose) {
+        Ok(guard) => Some(guard),
+        Err(e) => {
+            eprintln!("Logging disabled: {e}");
+            None
+        }
+    };
 
-    if let Err(e) = start_app(&config_path).await {
+    if let Err(e) = start_app(&args.config_path, args.shortcut).await {
         eprintln!("Error: {e}");
         std::process::exit(1);
     }
 }
 
-async fn start_app(config_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+async fn start_app(config_path: &str, shortcut: Option<Shortcut>) -> Result<(), Box<dyn std::error::Error>> {
     // Initialize session
     let mut session = SessionState::new_with_config(config_path)?;
+    width::set_wide_glyphs(session.config.wide_glyphs);
+    games::set_perspective(session.config.perspective);
+    api::set_retry_policy(api::RetryPolicy::from_config(&session.config));
+
+    // Rejoin the match a previous run left, logging in meanwhile
+    let rejoined = rejoin::fast_rejoin(&mut session).await;
 
     // Try automatic login
-    match try_auto_login(&mut session).await {
-        Ok(true) => {
-            println!("{}", "✓ Logged in successfully".green());
-        }
-        Ok(false) | Err(_) => {
-            println!("{}", "Please login or create an account:".dimmed());
+    if session.is_authenticated {
+        println!("{}", "✓ Logged in successfully".green());
+    } else {
+        match try_auto_login(&mut session).await {
+            Ok(true) => {
+                println!("{}", "✓ Logged in successfully".green());
+            }
+            Ok(false) | Err(_) => {
+                println!("{}", "Please login or create an account:".dimmed());
 
-            // If auto-login fails, try interactive login/registration
-            if let Err(e) = auth::handle_login_command(&mut session).await {
-                eprintln!("Login failed: {e}");
-                return Err("Authentication required".into());
+                // If auto-login fails, try interactive login/registration
+                if let Err(e) = auth::handle_login_command(&mut session).await {
+                    eprintln!("Login failed: {e}");
+                    return Err("Authentication required".into());
+                }
             }
         }
     }
 
     // Check for resumable match after login
-    if let Err(e) = check_and_handle_resumable_match(&mut session).await {
-        println!("{}", format!("Resume check error: {e}").yellow());
+    let resumed = if let Some(match_data) = rejoined {
+        if let Err(e) = games::resume_game(&mut session, match_data).await {
+            println!("{}", format!("Resume error: {e}").yellow());
+        }
+        true
+    } else {
+        check_and_handle_resumable_match(&mut session).await.unwrap_or_else(|e| {
+            println!("{}", format!("Resume check error: {e}").yellow());
+            false
+        })
+    };
+
+    if let Some(shortcut) = shortcut {
+        return run_shortcut(&mut session, shortcut, resumed).await;
     }
 
     // Enter main menu loop
@@ -97,6 +217,34 @@ async fn start_app(config_path: &str) -> Result<(), Box<dyn std::error::Error>>
             //         wait_for_keypress()?;
             //     }
             // }
+            MenuChoice::Challenges => {
+                if let Err(e) = challenges::show_challenges(&mut session).await {
+                    println!("{}", format!("Challenge error: {e}").red());
+                    println!("\nPress any key to return to menu...");
+                    wait_for_keypress()?;
+                }
+            }
+            MenuChoice::Party => {
+                if let Err(e) = party::show_party(&mut session).await {
+                    println!("{}", format!("Party error: {e}").red());
+                    println!("\nPress any key to return to menu...");
+                    wait_for_keypress()?;
+                }
+            }
+            MenuChoice::JoinArena(arena_id) => {
+                if let Err(e) = arena::join_announced_arena(&mut session, arena_id).await {
+                    println!("{}", format!("Arena error: {e}").red());
+                    println!("\nPress any key to return to menu...");
+                    wait_for_keypress()?;
+                }
+            }
+            MenuChoice::Arena => {
+                if let Err(e) = arena::show_arenas(&mut session).await {
+                    println!("{}", format!("Arena error: {e}").red());
+                    println!("\nPress any key to return to menu...");
+                    wait_for_keypress()?;
+                }
+            }
             MenuChoice::Stats => {
                 if let Err(e) = show_stats(&mut session).await {
                     println!("{}", format!("Error loading stats: {e}").red());
@@ -111,6 +259,27 @@ async fn start_app(config_path: &str) -> Result<(), Box<dyn std::error::Error>>
                 println!("\nPress any key to return to menu...");
                 wait_for_keypress()?;
             }
+            MenuChoice::Cast => {
+                if let Err(e) = cast::show_cast(&mut session).await {
+                    println!("{}", format!("Cast error: {e}").red());
+                }
+                println!("\nPress any key to return to menu...");
+                wait_for_keypress()?;
+            }
+            MenuChoice::Servers => {
+                if let Err(e) = servers::show_server_browser(&mut session).await {
+                    println!("{}", format!("Server browser error: {e}").red());
+                }
+                println!("\nPress any key to return to menu...");
+                wait_for_keypress()?;
+            }
+            MenuChoice::Quests => {
+                if let Err(e) = quests::show_quests(&session).await {
+                    println!("{}", format!("Error loading quests: {e}").red());
+                }
+                println!("\nPress any key to return to menu...");
+                wait_for_keypress()?;
+            }
             MenuChoice::Exit => {
                 println!("\n{}", "Goodbye!".cyan());
                 break;
@@ -126,8 +295,16 @@ enum MenuChoice {
     StartRockPaperScissors,
     StartBriscola,
     // StartChess,
+    Challenges,
+    Party,
+    Arena,
+    /// The arena announced last, joined with a single key
+    JoinArena(i64),
     Stats,
     Leaderboard,
+    Cast,
+    Servers,
+    Quests,
     Exit,
 }
 
@@ -137,9 +314,9 @@ fn display_menu(title: &str, items: &[(String, String)]) {
     // ASCII art logo
     println!();
     println!();
-    println!("{}", "░█▀▄░█▀█░▀█▀░▀█▀░█░░░█▀▄".bright_cyan());
-    println!("{}", "░█▀▄░█▀█░░█░░░█░░█░░░█░█".bright_cyan());
-    println!("{}", "░▀▀░░▀░▀░░▀░░░▀░░▀▀▀░▀▀░".bright_cyan());
+    for line in LOGO {
+        println!("{}", line.bright_cyan());
+    }
     println!();
 
     // Title
@@ -154,21 +331,51 @@ fn display_menu(title: &str, items: &[(String, String)]) {
     println!();
 }
 
-async fn read_menu_choice(_session: &mut SessionState) -> io::Result<MenuChoice> {
+async fn read_menu_choice(session: &mut SessionState) -> io::Result<MenuChoice> {
     let menu_items = vec![
         ("1".to_string(), "Start Tic-Tac-Toe Game".to_string()),
         ("2".to_string(), "Start Rock-Paper-Scissors Game".to_string()),
         ("3".to_string(), "Start Briscola Game".to_string()),
         // ("4".to_string(), "Start Chess Game".to_string()),
-        ("4".to_string(), "Your Stats".to_string()),
-        ("5".to_string(), "Leaderboard".to_string()),
-        ("6".to_string(), "Exit".to_string()),
+        ("4".to_string(), "Challenges".to_string()),
+        ("5".to_string(), "Party".to_string()),
+        ("6".to_string(), "Arena".to_string()),
+        ("7".to_string(), "Your Stats".to_string()),
+        ("8".to_string(), "Leaderboard".to_string()),
+        ("9".to_string(), "Cast Live Match".to_string()),
+        ("10".to_string(), "Servers".to_string()),
+        ("11".to_string(), "Quests".to_string()),
+        ("12".to_string(), "Exit".to_string()),
     ];
 
     let title = format!("v{VERSION}");
     display_menu(&title, &menu_items);
+    if let Some(ws_client) = &session.ws_client {
+        if let Some(motd) = ws_client.get_motd().await {
+            println!("{}", motd.bright_green());
+            println!();
+        }
+        if let Some(stats) = ws_client.stats_summary().await {
+            println!("{}", stats.bright_yellow());
+            println!();
+        }
+        if let Some(tier_change) = ws_client.take_tier_change().await {
+            println!("{}", tier_change.bright_magenta().bold());
+            println!();
+        }
+        for notice in ws_client.take_challenge_notices().await {
+            println!("{}", notice.bright_cyan().bold());
+            println!();
+        }
+        if let Some(arena) = ws_client.announced_arena().await {
+            ws_client.take_arena_notices().await;
+            println!("{}", format!("A {} arena is about to start - enter a to join it", arena.game_type).bright_magenta().bold());
+            println!();
+        }
+    }
 
     let mut rl = DefaultEditor::new().map_err(io::Error::other)?;
+    let _notifier = announce_challenges(session, &mut rl);
 
     loop {
         let readline = rl.readline("Select option: ");
@@ -180,11 +387,32 @@ async fn read_menu_choice(_session: &mut SessionState) -> io::Result<MenuChoice>
                     "2" => return Ok(MenuChoice::StartRockPaperScissors),
                     "3" => return Ok(MenuChoice::StartBriscola),
                     // "4" => return Ok(MenuChoice::StartChess),
-                    "4" => return Ok(MenuChoice::Stats),
-                    "5" => return Ok(MenuChoice::Leaderboard),
-                    "6" => return Ok(MenuChoice::Exit),
+                    "4" => return Ok(MenuChoice::Challenges),
+                    "5" => return Ok(MenuChoice::Party),
+                    "6" => return Ok(MenuChoice::Arena),
+                    "7" => return Ok(MenuChoice::Stats),
+                    "8" => return Ok(MenuChoice::Leaderboard),
+                    "9" => return Ok(MenuChoice::Cast),
+                    "10" => return Ok(MenuChoice::Servers),
+                    "11" => return Ok(MenuChoice::Quests),
+                    "12" => return Ok(MenuChoice::Exit),
+                    "a" => {
+                        let announced = match &session.ws_client {
+                            Some(ws_client) => ws_client.announced_arena().await,
+                            None => None,
+                        };
+                        match announced {
+                            Some(arena) => return Ok(MenuChoice::JoinArena(arena.id)),
+                            None => println!("{}", "No arena is about to start".red()),
+                        }
+                        continue;
+                    }
+                    _ if bugreport::is_command(choice) => {
+                        bugreport::run(session.ws_client.as_deref()).await;
+                        continue;
+                    }
                     _ => {
-                        println!("{}", format!("Invalid choice. Please enter 1-{}.", menu_items.len() + 1).red());
+                        println!("{}", format!("Invalid choice. Please enter 1-{}.", menu_items.len()).red());
                         continue;
                     }
                 }
@@ -196,7 +424,72 @@ async fn read_menu_choice(_session: &mut SessionState) -> io::Result<MenuChoice>
     }
 }
 
-async fn check_and_handle_resumable_match(session: &mut SessionState) -> Result<(), Box<dyn std::error::Error>> {
+/// Print challenges and arena announcements as they arrive while the menu waits for a choice
+fn announce_challenges(session: &SessionState, rl: &mut DefaultEditor) -> Option<Notifier> {
+    let ws_client = session.ws_client.clone()?;
+    let mut printer = rl.create_external_printer().ok()?;
+    Some(Notifier(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHALLENGE_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            for notice in ws_client.take_challenge_notices().await {
+                if printer.print(format!("{}", notice.bright_cyan().bold())).is_err() {
+                    return;
+                }
+            }
+            for notice in ws_client.take_arena_notices().await {
+                if printer.print(format!("{}", notice.bright_magenta().bold())).is_err() {
+                    return;
+                }
+            }
+        }
+    })))
+}
+
+/// Stops printing notices once the menu is left
+struct Notifier(tokio::task::JoinHandle<()>);
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Run the flow asked for on the command line instead of the menu
+async fn run_shortcut(session: &mut SessionState, shortcut: Shortcut, resumed: bool) -> Result<(), Box<dyn std::error::Error>> {
+    match shortcut {
+        Shortcut::Queue(game_type) => start_game_flow(session, game_type).await,
+        Shortcut::Challenge(opponent, game_type) => {
+            let player_id = find_player_id(session, &opponent).await?;
+            match challenges::challenge_player(session, player_id, game_type).await? {
+                Some(game_match) => games::resume_game(session, game_match).await,
+                None => Ok(()),
+            }
+        }
+        Shortcut::Resume if resumed => Ok(()),
+        Shortcut::Resume => Err("No match to resume".into()),
+        Shortcut::Stats => show_stats(session).await,
+        Shortcut::RecoveryPhrase => auth::set_up_recovery_phrase(session).await,
+    }
+}
+
+/// The id of the only player named `player`, or `player` itself when it is already an id
+async fn find_player_id(session: &SessionState, player: &str) -> Result<i64, Box<dyn std::error::Error>> {
+    if let Ok(player_id) = player.parse::<i64>() {
+        return Ok(player_id);
+    }
+    match api::player::find_players(session, player).await?.as_slice() {
+        [found] => Ok(found.id),
+        [] => Err(format!("No player named {player}").into()),
+        found => {
+            let ids: Vec<String> = found.iter().map(|found| found.id.to_string()).collect();
+            Err(format!("{} players are named {player}, challenge one by id: {}", found.len(), ids.join(", ")).into())
+        }
+    }
+}
+
+/// Resume the match left active, or show how it ended - returns whether there was one
+async fn check_and_handle_resumable_match(session: &mut SessionState) -> Result<bool, Box<dyn std::error::Error>> {
     use battld_common::*;
 
     let ws_client = session.ws_client.as_ref().ok_or("Not connected to WebSocket")?;
@@ -205,13 +498,28 @@ async fn check_and_handle_resumable_match(session: &mut SessionState) -> Result<
     tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
 
     let messages = ws_client.get_messages().await;
+    let final_state = messages.iter().find_map(|msg| match msg {
+        ServerMessage::GameStateUpdate { match_data } => Some(match_data.clone()),
+        _ => None,
+    });
 
     for msg in messages {
-        if let ServerMessage::ResumableMatch { match_data } = msg {
+        if let ServerMessage::MatchEnded { summary, .. } = &msg {
+            show_match_ended_while_away(session.player_id.unwrap(), final_state.as_ref(), summary.as_ref())?;
+            return Ok(true);
+        }
+
+        if let ServerMessage::ResumableMatch { match_data, last_move_at } = msg {
             clear_screen()?;
             println!("\n{}", "You have an active match!".yellow().bold());
             println!("{}", format!("Match ID: {}", match_data.id).dimmed());
-            println!("{}", format!("Opponent: Player {}", if match_data.player1_id == session.player_id.unwrap() { match_data.player2_id } else { match_data.player1_id }).dimmed());
+            let my_seat = crate::games::my_seat(&match_data, session.player_id.unwrap());
+            println!("{}", format!("Opponent: {}", crate::games::opponent_label(&match_data, my_seat)).dimmed());
+            if let Some(last_move_at) = last_move_at {
+                let preferences = crate::api::player::fetch_time_preferences(session).await.unwrap_or_default();
+                let last_move = crate::timestamps::describe(last_move_at, battld_common::time() as i64, &preferences);
+                println!("{}", format!("Last move: {last_move}").dimmed());
+            }
             println!();
 
             // Automatically resume
@@ -220,27 +528,35 @@ async fn check_and_handle_resumable_match(session: &mut SessionState) -> Result<
             println!("{}", "Resuming match...".cyan());
             let game_match = wait_for_game_state(ws_client).await?;
 
-            // Route to correct game based on game_type
-            match game_match.game_type {
-                GameType::TicTacToe => {
-                    tic_tac_toe::resume_game(session, game_match).await?;
-                }
-                GameType::RockPaperScissors => {
-                    rock_paper_scissors::resume_game(session, game_match).await?;
-                }
-                GameType::Briscola => {
-                    briscola::resume_game(session, game_match).await?;
-                }
-                GameType::Chess => {
-                    chess::resume_game(session, game_match).await?;
-                }
-            }
+            crate::games::resume_game(session, game_match).await?;
 
-            return Ok(());
+            return Ok(true);
         }
     }
 
-    Ok(())
+    Ok(false)
+}
+
+/// The match left behind was decided without the player, by a forfeit or an opponent who never came back
+fn show_match_ended_while_away(player_id: i64, final_state: Option<&Match>, summary: Option<&MatchSummary>) -> io::Result<()> {
+    clear_screen()?;
+    println!("\n{}", "Your last match ended while you were away".yellow().bold());
+    if let Some(match_data) = final_state {
+        let my_seat = crate::games::my_seat(match_data, player_id);
+        println!("{}", format!("Match ID: {}", match_data.id).dimmed());
+        println!("{}", format!("Opponent: {}", crate::games::opponent_label(match_data, my_seat)).dimmed());
+        println!();
+        match (&match_data.outcome, my_seat) {
+            (Some(MatchOutcome::Draw), _) => println!("  {}", "Draw".yellow()),
+            (Some(MatchOutcome::Player1Win), Seat::FIRST) | (Some(MatchOutcome::Player2Win), Seat::SECOND) => println!("  {}", "You won!".bright_green().bold()),
+            (Some(_), _) => println!("  {}", "You lost.".red()),
+            (None, _) => {}
+        }
+    }
+    println!();
+    crate::games::render_summary(summary);
+    println!("Press any key to return to menu...");
+    wait_for_keypress()
 }
 
 async fn wait_for_game_state(ws_client: &crate::websocket::WebSocketClient) -> Result<Match, Box<dyn std::error::Error>> {
@@ -273,6 +589,9 @@ async fn start_game_flow(session: &mut SessionState, game_type: GameType) -> Res
         GameType::Chess => games::chess::start_game(session, game_type).await?,
     }
 
+    if let Some(rematch) = games::take_rematch() {
+        games::resume_game(session, rematch).await?;
+    }
     Ok(())
 }
 
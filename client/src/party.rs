@@ -0,0 +1,113 @@
+use battld_common::{ClientMessage, ServerMessage};
+use colored::*;
+use rustyline::DefaultEditor;
+use std::io::{self, Write};
+
+use crate::api::player::fetch_party;
+use crate::state::*;
+use crate::ui::*;
+
+pub async fn show_party(session: &mut SessionState) -> Result<(), Box<dyn std::error::Error>> {
+    if session.ws_client.is_none() {
+        session.connect_websocket().await?;
+    }
+
+    let mut notices: Vec<String> = Vec::new();
+
+    loop {
+        clear_screen()?;
+        println!("\n{}", "Loading party...".cyan());
+
+        notices.extend(read_party_notices(session).await?);
+        let party = fetch_party(session).await?;
+
+        clear_screen()?;
+        println!();
+        println!("{}", "═══════════════════════════════════════════════════════════════════".bright_cyan());
+        println!("{}", "                               PARTY                               ".bright_cyan().bold());
+        println!("{}", "═══════════════════════════════════════════════════════════════════".bright_cyan());
+        println!();
+
+        for notice in notices.drain(..) {
+            println!("  {}", notice.yellow());
+        }
+
+        match &party.partner {
+            Some(partner) => {
+                println!("  In a party with {}", partner.name.bright_white().bold());
+                println!("{}", "  Start a game from the main menu, you will be matched with each other".dimmed());
+            }
+            None => println!("{}", "  Not in a party".dimmed()),
+        }
+        println!();
+
+        for (index, invite) in party.invites.iter().enumerate() {
+            println!(
+                "  [{}] {} invites you to their party",
+                index.to_string().bright_yellow(),
+                invite.name.bright_white().bold()
+            );
+        }
+
+        println!();
+        println!("{}", "a N: accept | d N: decline | i: invite | l: leave | r: refresh | q: back".dimmed());
+        print!("> ");
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let parts: Vec<&str> = input.split_whitespace().collect();
+        let ws_client = session.ws_client.as_ref().ok_or("Not connected to WebSocket")?;
+
+        match parts.as_slice() {
+            ["a" | "d", index] => {
+                let Some(invite) = index.parse::<usize>().ok().and_then(|i| party.invites.get(i)) else {
+                    continue;
+                };
+                ws_client.send(ClientMessage::RespondToPartyInvite {
+                    inviter_id: invite.player_id,
+                    accept: parts[0] == "a",
+                })?;
+            }
+            ["i"] => {
+                let mut rl = DefaultEditor::new()?;
+                let line = rl.readline("Player ID to invite: ")?;
+                match line.trim().parse::<i64>() {
+                    Ok(player_id) => {
+                        ws_client.send(ClientMessage::InviteToParty { player_id })?;
+                        notices.push("Invite sent".to_string());
+                    }
+                    Err(_) => notices.push("Please enter a numeric player ID.".to_string()),
+                }
+            }
+            ["l"] => ws_client.send(ClientMessage::LeaveParty)?,
+            ["q"] => return Ok(()),
+            _ => {}
+        }
+
+        // Give the server a moment to answer before refreshing
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+    }
+}
+
+/// Party updates received since the last refresh, as lines to show
+async fn read_party_notices(session: &SessionState) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let ws_client = session.ws_client.as_ref().ok_or("Not connected to WebSocket")?;
+
+    Ok(ws_client
+        .get_messages()
+        .await
+        .into_iter()
+        .filter_map(|msg| match msg {
+            ServerMessage::PartyInvite { from } => Some(format!("{} invited you to their party", from.name)),
+            ServerMessage::PartyInviteDeclined { player_id } => Some(format!("Player {player_id} declined your invite")),
+            ServerMessage::PartyFormed { partner } => Some(format!("You are now in a party with {}", partner.name)),
+            ServerMessage::PartyDisbanded { .. } => Some("The party was disbanded".to_string()),
+            ServerMessage::PartyQueued { game_type, .. } => {
+                Some(format!("Your partner is waiting for you in {game_type}"))
+            }
+            ServerMessage::Error { .. } => crate::ui::error_text(&msg),
+            _ => None,
+        })
+        .collect())
+}
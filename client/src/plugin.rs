@@ -0,0 +1,173 @@
+use battld_common::games::{
+    game_type::GameType,
+    matches::{Match, MatchEndReason, MatchOutcome},
+    players::Seat,
+};
+use battld_common::{NotificationChannel, QuietHours, ServerMessage};
+use serde::Serialize;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+/// Event handed to the plugin, one JSON object on its stdin per run
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum PluginEvent {
+    MatchFound { match_id: i64, game_type: GameType, opponent_id: i64 },
+    YourTurn { match_id: i64, game_type: GameType },
+    MatchEnded { match_id: i64, game_type: GameType, result: MatchResult, reason: MatchEndReason },
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchResult {
+    Won,
+    Lost,
+    Draw,
+    Unknown,
+}
+
+/// Runs the executable configured as `plugin` on matchmaking and match events
+/// Events are delivered one at a time, in order, a failing plugin never affects the game
+pub struct Plugin {
+    player_id: i64,
+    tx: mpsc::UnboundedSender<PluginEvent>,
+    current_match: Option<Match>,
+    turn_notified: bool,
+    ended_notified: bool,
+    /// Quiet hours of the player and their UTC offset, events meanwhile are dropped
+    quiet_hours: Option<(QuietHours, i32)>,
+}
+
+impl Plugin {
+    pub fn new(path: String, player_id: i64, quiet_hours: Option<(QuietHours, i32)>) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<PluginEvent>();
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                if let Err(e) = run(&path, &event).await {
+                    tracing::warn!("Plugin {path} failed on {event:?}: {e}");
+                }
+            }
+        });
+
+        Self {
+            player_id,
+            tx,
+            current_match: None,
+            turn_notified: false,
+            ended_notified: false,
+            quiet_hours,
+        }
+    }
+
+    /// Forward whatever `message` means for the player to the plugin, unless it is quiet hours
+    pub fn notify(&mut self, message: &ServerMessage) {
+        let events = self.events_for(message);
+        let now = battld_common::time() as i64;
+        if self.quiet_hours.as_ref().is_some_and(|(quiet_hours, utc_offset_minutes)| {
+            quiet_hours.silences(NotificationChannel::Plugin, now, *utc_offset_minutes)
+        }) {
+            return;
+        }
+        for event in events {
+            let _ = self.tx.send(event);
+        }
+    }
+
+    fn events_for(&mut self, message: &ServerMessage) -> Vec<PluginEvent> {
+        let mut events = vec![];
+        match message {
+            ServerMessage::MatchFound { match_data } => {
+                self.track(match_data);
+                events.push(PluginEvent::MatchFound {
+                    match_id: match_data.id,
+                    game_type: match_data.game_type.clone(),
+                    opponent_id: if match_data.player1_id == self.player_id {
+                        match_data.player2_id
+                    } else {
+                        match_data.player1_id
+                    },
+                });
+                events.extend(self.progress_events(match_data));
+            }
+            ServerMessage::GameStateUpdate { match_data } => {
+                self.track(match_data);
+                events.extend(self.progress_events(match_data));
+            }
+            ServerMessage::MatchEnded { reason, .. } => {
+                if let Some(match_data) = self.current_match.clone() {
+                    events.extend(self.ended_event(&match_data, reason.clone()));
+                }
+            }
+            _ => {}
+        }
+        events
+    }
+
+    fn track(&mut self, match_data: &Match) {
+        if self.current_match.as_ref().map(|m| m.id) != Some(match_data.id) {
+            self.turn_notified = false;
+            self.ended_notified = false;
+        }
+        self.current_match = Some(match_data.clone());
+    }
+
+    fn progress_events(&mut self, match_data: &Match) -> Vec<PluginEvent> {
+        if !match_data.in_progress {
+            return self.ended_event(match_data, MatchEndReason::Ended).into_iter().collect();
+        }
+
+        let your_turn = crate::games::is_players_turn(match_data, self.player_id);
+        let notify = your_turn && !self.turn_notified;
+        self.turn_notified = your_turn;
+        if !notify {
+            return vec![];
+        }
+        vec![PluginEvent::YourTurn {
+            match_id: match_data.id,
+            game_type: match_data.game_type.clone(),
+        }]
+    }
+
+    fn ended_event(&mut self, match_data: &Match, reason: MatchEndReason) -> Option<PluginEvent> {
+        if self.ended_notified {
+            return None;
+        }
+        self.ended_notified = true;
+
+        let me = crate::games::my_seat(match_data, self.player_id);
+        let result = match &match_data.outcome {
+            Some(MatchOutcome::Player1Win) if me == Seat::FIRST => MatchResult::Won,
+            Some(MatchOutcome::Player2Win) if me == Seat::SECOND => MatchResult::Won,
+            Some(MatchOutcome::Draw) => MatchResult::Draw,
+            Some(_) => MatchResult::Lost,
+            None => MatchResult::Unknown,
+        };
+        Some(PluginEvent::MatchEnded {
+            match_id: match_data.id,
+            game_type: match_data.game_type.clone(),
+            result,
+            reason,
+        })
+    }
+}
+
+async fn run(path: &str, event: &PluginEvent) -> Result<(), Box<dyn std::error::Error>> {
+    let mut child = Command::new(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let line = serde_json::to_string(event)?;
+        stdin.write_all(format!("{line}\n").as_bytes()).await?;
+    }
+
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(format!("exited with {status}").into());
+    }
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use battld_common::{Quest, QuestPeriod};
+use colored::*;
+
+use crate::api::player::fetch_quests;
+use crate::state::SessionState;
+use crate::ui::clear_screen;
+
+const BAR_WIDTH: usize = 20;
+
+/// List the running quests with how far the player got in each
+pub async fn show_quests(session: &SessionState) -> Result<(), Box<dyn std::error::Error>> {
+    clear_screen()?;
+    println!("\n{}", "Loading quests...".cyan());
+
+    let quests = fetch_quests(session).await?;
+    let now = battld_common::time() as i64;
+
+    clear_screen()?;
+    println!();
+    println!("{}", "═══════════════════════════════════════════════════════════════════".bright_cyan());
+    println!("{}", "                              QUESTS                               ".bright_cyan().bold());
+    println!("{}", "═══════════════════════════════════════════════════════════════════".bright_cyan());
+
+    for (period, title) in [(QuestPeriod::Daily, "Today"), (QuestPeriod::Weekly, "This week")] {
+        let mut quests = quests.iter().filter(|quest| quest.period == period).peekable();
+        let Some(first) = quests.peek() else {
+            continue;
+        };
+        println!();
+        println!("{} {}", title.bright_white().bold(), format!("(ends in {})", time_left(first.ends_at - now)).dimmed());
+        for quest in quests {
+            print_quest(quest);
+        }
+    }
+    Ok(())
+}
+
+fn print_quest(quest: &Quest) {
+    let filled = (quest.progress.min(quest.target) as usize * BAR_WIDTH) / quest.target.max(1) as usize;
+    let bar = format!("{}{}", "█".repeat(filled), "░".repeat(BAR_WIDTH - filled));
+    let bar = if quest.is_complete() { bar.bright_green() } else { bar.bright_yellow() };
+    let done = if quest.is_complete() { " ✓".bright_green().bold().to_string() } else { String::new() };
+    println!("  {}{done}", quest.description);
+    println!("  {bar} {}/{}", quest.progress, quest.target);
+}
+
+/// "5h", "2d" or "12m" until the quests are replaced
+fn time_left(secs: i64) -> String {
+    let minutes = secs.max(0) / 60;
+    match minutes {
+        0..60 => format!("{minutes}m"),
+        60..1440 => format!("{}h", minutes / 60),
+        _ => format!("{}d", minutes / 1440),
+    }
+}
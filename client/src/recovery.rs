@@ -0,0 +1,110 @@
+use rand::{rngs::OsRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rsa::RsaPrivateKey;
+use sha2::{Digest, Sha256};
+
+/// Words in a recovery phrase, one byte each: the entropy, then a checksum catching typos
+pub const PHRASE_WORDS: usize = 16;
+const ENTROPY_BYTES: usize = PHRASE_WORDS - 1;
+/// Hashing rounds between a phrase and its key, slowing down anyone guessing phrases
+const STRETCH_ROUNDS: u32 = 100_000;
+const KEY_BITS: usize = 2048;
+
+/// Their first four letters tell every word apart, so phrases can be typed short
+const WORDS: [&str; 256] = [
+    "acid", "acorn", "actor", "adapt", "admit", "adult", "agent", "alarm", "album", "alert",
+    "alien", "alley", "amber", "anchor", "angle", "ankle", "apple", "april", "arena", "armor",
+    "arrow", "artist", "atlas", "attic", "audio", "autumn", "badge", "bakery", "bamboo", "banana",
+    "banner", "barrel", "basket", "beach", "beard", "beaver", "bench", "berry", "bicycle", "bishop",
+    "blanket", "blossom", "border", "bottle", "bounce", "bracket", "brain", "breeze", "brick",
+    "bridge", "broom", "bubble", "bucket", "buffalo", "bundle", "butter", "cabin", "cactus",
+    "camel", "candle", "canoe", "canyon", "carpet", "carrot", "castle", "cattle", "cellar",
+    "cement", "cherry", "chess", "chimney", "circle", "citrus", "clerk", "cliff", "clock", "cloud",
+    "clover", "coach", "cobalt", "coconut", "comet", "copper", "coral", "cotton", "cousin",
+    "coyote", "crane", "crater", "cricket", "crystal", "cube", "cupboard", "curtain", "cushion",
+    "dagger", "dairy", "daisy", "dance", "delta", "desert", "diamond", "dinner", "dolphin",
+    "donkey", "dragon", "drawer", "dream", "drum", "eagle", "earth", "echo", "eclipse", "elbow",
+    "elephant", "ember", "engine", "envelope", "equator", "fabric", "falcon", "feather", "fence",
+    "ferry", "fiddle", "field", "figure", "finger", "flame", "flute", "forest", "fossil",
+    "fountain", "fox", "frost", "galaxy", "garden", "garlic", "gecko", "giant", "ginger", "giraffe",
+    "glacier", "globe", "goat", "gold", "gorilla", "grape", "gravel", "guitar", "hammer", "harbor",
+    "harvest", "hazel", "helmet", "hermit", "hockey", "honey", "horizon", "hotel", "humble",
+    "igloo", "island", "ivory", "jacket", "jaguar", "jelly", "jewel", "jigsaw", "jungle", "kayak",
+    "kernel", "kettle", "kingdom", "kitten", "koala", "ladder", "lagoon", "lantern", "laptop",
+    "lemon", "leopard", "letter", "lizard", "lobster", "locket", "lumber", "magnet", "mango",
+    "marble", "meadow", "melon", "meteor", "mirror", "monkey", "mosaic", "muffin", "museum",
+    "napkin", "nectar", "needle", "noodle", "ocean", "olive", "onion", "orange", "orchid", "otter",
+    "oyster", "paddle", "palace", "panda", "parrot", "pebble", "pencil", "pepper", "piano",
+    "pigeon", "pillow", "pirate", "planet", "pocket", "pony", "potato", "puzzle", "quartz",
+    "rabbit", "radar", "raven", "ribbon", "rocket", "saddle", "salmon", "sandal", "scarf", "shadow",
+    "shovel", "silver", "spider", "squirrel", "statue", "sunset", "tiger", "tomato", "tulip",
+    "turtle", "velvet", "violin", "walnut", "whale", "window", "winter", "wizard", "yogurt",
+    "zebra", "zipper",
+];
+
+/// A new random recovery phrase
+pub fn generate_phrase() -> String {
+    let mut entropy = [0u8; ENTROPY_BYTES];
+    OsRng.fill_bytes(&mut entropy);
+    encode(&entropy)
+}
+
+fn checksum(entropy: &[u8]) -> u8 {
+    Sha256::digest(entropy)[0]
+}
+
+fn encode(entropy: &[u8; ENTROPY_BYTES]) -> String {
+    entropy
+        .iter()
+        .chain([checksum(entropy)].iter())
+        .map(|byte| WORDS[*byte as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn word_value(word: &str) -> Option<u8> {
+    let word = word.to_lowercase();
+    let position = WORDS.iter().position(|known| *known == word || (word.len() >= 4 && known.starts_with(&word)))?;
+    Some(position as u8)
+}
+
+/// The entropy a phrase carries, ignoring case and accepting words cut to their first four letters
+pub fn parse_phrase(phrase: &str) -> Result<[u8; ENTROPY_BYTES], String> {
+    let bytes = phrase
+        .split_whitespace()
+        .map(|word| word_value(word).ok_or_else(|| format!("'{word}' is not a recovery phrase word")))
+        .collect::<Result<Vec<u8>, String>>()?;
+    if bytes.len() != PHRASE_WORDS {
+        return Err(format!("A recovery phrase has {PHRASE_WORDS} words, this one has {}", bytes.len()));
+    }
+    let (check, entropy) = bytes.split_last().unwrap();
+    if checksum(entropy) != *check {
+        return Err("The recovery phrase has a typo or its words are out of order".to_string());
+    }
+    Ok(entropy.try_into().unwrap())
+}
+
+/// The key pair a phrase stands for, the same every time and on every machine
+pub fn derive_private_key(entropy: &[u8; ENTROPY_BYTES]) -> Result<RsaPrivateKey, rsa::Error> {
+    let mut seed: [u8; 32] = Sha256::new().chain_update(b"battld-recovery").chain_update(entropy).finalize().into();
+    for _ in 0..STRETCH_ROUNDS {
+        seed = Sha256::digest(seed).into();
+    }
+    RsaPrivateKey::new(&mut ChaCha20Rng::from_seed(seed), KEY_BITS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsa::{pkcs1::EncodeRsaPublicKey, RsaPublicKey};
+
+    /// A change to key generation in rsa, rand_chacha or num-bigint-dig would lock out every account restored from a phrase
+    #[test]
+    fn test_phrase_always_derives_the_same_key() {
+        let phrase = "acid acorn actor adapt admit adult agent alarm album alert alien alley amber anchor angle eclipse";
+        let private_key = derive_private_key(&parse_phrase(phrase).unwrap()).unwrap();
+        let der = RsaPublicKey::from(&private_key).to_pkcs1_der().unwrap();
+        let fingerprint: String = Sha256::digest(der.as_bytes()).iter().map(|byte| format!("{byte:02x}")).collect();
+        assert_eq!(fingerprint, "8d25baf77231869a9a32154830b4bd9c6f4dedc44ec8264d8b829454eca63906");
+    }
+}
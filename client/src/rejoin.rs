@@ -0,0 +1,169 @@
+use base64::{Engine as _, engine::general_purpose};
+use battld_common::games::matches::Match;
+use battld_common::{ClientMessage, ServerMessage};
+use rsa::{Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey, pkcs1::DecodeRsaPublicKey, pkcs8::DecodePrivateKey};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::time::{Duration, Instant};
+
+use crate::auth;
+use crate::state::SessionState;
+use crate::websocket::WebSocketClient;
+
+const CACHE_FILE: &str = "session.cache";
+
+/// How long the cached session gets to bring the match back before the regular login takes over
+const REJOIN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Who the cache belongs to, in clear, and the session encrypted with the player's public key
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    server_url: String,
+    player_id: i64,
+    session: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedSession {
+    token: String,
+    match_id: i64,
+}
+
+/// Keeps the match in progress and its session token on disk, so a restarted client can rejoin it right away
+pub struct RejoinCache {
+    path: PathBuf,
+    server_url: String,
+    player_id: i64,
+    token: String,
+    public_key_path: String,
+    match_id: Option<i64>,
+}
+
+impl RejoinCache {
+    pub fn new(session: &SessionState, player_id: i64, token: &str) -> Option<Self> {
+        Some(Self {
+            path: cache_path(&session.config_path),
+            server_url: session.config.server_url.clone()?,
+            player_id,
+            token: token.to_string(),
+            public_key_path: session.config.public_key_path.clone()?,
+            match_id: None,
+        })
+    }
+
+    /// Cache the match while it is in progress, forget it once it ends
+    pub fn notify(&mut self, message: &ServerMessage) {
+        let match_id = match message {
+            ServerMessage::MatchFound { match_data } | ServerMessage::GameStateUpdate { match_data } => {
+                match_data.in_progress.then_some(match_data.id)
+            }
+            ServerMessage::MatchEnded { .. } => None,
+            _ => return,
+        };
+        if match_id == self.match_id {
+            return;
+        }
+        self.match_id = match_id;
+
+        let result = match match_id {
+            Some(match_id) => self.save(match_id),
+            None => clear(&self.path),
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to update {}: {e}", self.path.display());
+        }
+    }
+
+    fn save(&self, match_id: i64) -> Result<(), Box<dyn std::error::Error>> {
+        let session = serde_json::to_vec(&CachedSession { token: self.token.clone(), match_id })?;
+        let public_key = RsaPublicKey::from_pkcs1_pem(&fs::read_to_string(&self.public_key_path)?)?;
+        let encrypted = public_key.encrypt(&mut rand::rngs::OsRng, Pkcs1v15Encrypt, &session)?;
+        let file = CacheFile {
+            server_url: self.server_url.clone(),
+            player_id: self.player_id,
+            session: general_purpose::STANDARD.encode(encrypted),
+        };
+        fs::write(&self.path, serde_json::to_string(&file)?)?;
+        Ok(())
+    }
+}
+
+/// Resume the match a previous run was playing with its cached session, logging in alongside in case it fails
+/// Returns the match if it was rejoined, the session is authenticated whenever either succeeds
+pub async fn fast_rejoin(session: &mut SessionState) -> Option<Match> {
+    let player_id = session.config.player_id?;
+    let cached = load(session)?;
+    tracing::info!("Rejoining match {} with the cached session", cached.match_id);
+
+    let (rejoined, login) = tokio::join!(
+        resume(session, player_id, &cached),
+        auth::auto_login_token(&session.config)
+    );
+    match rejoined {
+        Some((ws_client, match_data)) => {
+            session.set_authenticated(player_id, cached.token);
+            session.ws_client = Some(Arc::new(ws_client));
+            Some(match_data)
+        }
+        None => {
+            tracing::info!("Cached session could not rejoin match {}", cached.match_id);
+            let _ = clear(&cache_path(&session.config_path));
+            if let Some(Ok(session_token)) = login {
+                auth::complete_auto_login(session, player_id, session_token).await;
+            }
+            None
+        }
+    }
+}
+
+async fn resume(session: &SessionState, player_id: i64, cached: &CachedSession) -> Option<(WebSocketClient, Match)> {
+    let ws_client = session.open_websocket(player_id, cached.token.clone()).await.ok()?;
+    if ws_client.send(ClientMessage::ResumeMatch).is_ok() {
+        let deadline = Instant::now() + REJOIN_TIMEOUT;
+        'waiting: while Instant::now() < deadline {
+            for message in ws_client.get_messages().await {
+                match message {
+                    ServerMessage::GameStateUpdate { match_data } if match_data.id == cached.match_id => {
+                        if match_data.in_progress {
+                            return Some((ws_client, match_data));
+                        }
+                        break 'waiting;
+                    }
+                    ServerMessage::AuthFailed { .. } | ServerMessage::Error { .. } => break 'waiting,
+                    _ => {}
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+    ws_client.close().await;
+    None
+}
+
+fn load(session: &SessionState) -> Option<CachedSession> {
+    let content = fs::read_to_string(cache_path(&session.config_path)).ok()?;
+    let file: CacheFile = serde_json::from_str(&content).ok()?;
+    if session.config.server_url.as_ref() != Some(&file.server_url) || session.config.player_id != Some(file.player_id) {
+        return None;
+    }
+
+    let private_key_pem = fs::read_to_string(session.config.private_key_path.as_ref()?).ok()?;
+    let private_key = RsaPrivateKey::from_pkcs8_pem(&private_key_pem).ok()?;
+    let encrypted = general_purpose::STANDARD.decode(file.session).ok()?;
+    let decrypted = private_key.decrypt(Pkcs1v15Encrypt, &encrypted).ok()?;
+    serde_json::from_slice(&decrypted).ok()
+}
+
+fn clear(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// The cache sits next to the config it belongs to
+fn cache_path(config_path: &str) -> PathBuf {
+    Path::new(config_path).with_file_name(CACHE_FILE)
+}
@@ -0,0 +1,102 @@
+use battld_common::{games::matches::Match, LiveMatch, ReplayResponse, TimePreferences};
+use colored::*;
+use crossterm::{event::{self, Event, KeyCode, KeyEventKind}, terminal};
+use std::io;
+
+use crate::api::player::{fetch_match_replay, fetch_time_preferences};
+use crate::cast::render_game;
+use crate::state::SessionState;
+use crate::timestamps;
+use crate::ui::clear_screen;
+
+const WIDTH: usize = 60;
+
+/// Step through the moves of a finished match, forward and back
+pub async fn show_replay(session: &SessionState, match_id: i64) -> Result<(), Box<dyn std::error::Error>> {
+    let replay = fetch_match_replay(session, match_id).await?;
+    if replay.frames.is_empty() {
+        println!("{}", "No moves were recorded for this match".yellow());
+        return Ok(());
+    }
+    let preferences = fetch_time_preferences(session).await.unwrap_or_default();
+    let match_data = &replay.match_data;
+    let name = |player_id: i64| match_data.player(player_id).map(|player| player.name.clone()).unwrap_or_else(|| format!("Player {player_id}"));
+    let live_match = LiveMatch {
+        match_id,
+        game_type: match_data.game_type.clone(),
+        player1_id: match_data.player1_id,
+        player1_name: name(match_data.player1_id),
+        player2_id: match_data.player2_id,
+        player2_name: name(match_data.player2_id),
+    };
+
+    let last = replay.frames.len() - 1;
+    let mut index = 0;
+    loop {
+        render_frame(&replay, &live_match, index, &preferences)?;
+        match read_key()? {
+            KeyCode::Right | KeyCode::Char('n') | KeyCode::Char(' ') => index = (index + 1).min(last),
+            KeyCode::Left | KeyCode::Char('p') => index = index.saturating_sub(1),
+            KeyCode::Home => index = 0,
+            KeyCode::End => index = last,
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+fn render_frame(replay: &ReplayResponse, live_match: &LiveMatch, index: usize, preferences: &TimePreferences) -> io::Result<()> {
+    let frame = &replay.frames[index];
+    let last = replay.frames.len() - 1;
+
+    clear_screen()?;
+    println!();
+    println!("{}", "═".repeat(WIDTH).bright_cyan());
+    println!("  {}", format!("Replay · {} · Match #{}", live_match.game_type, live_match.match_id).bright_cyan().bold());
+    println!(
+        "  {} vs {}",
+        live_match.player1_name.bright_blue().bold(),
+        live_match.player2_name.bright_magenta().bold()
+    );
+    println!("{}", "═".repeat(WIDTH).bright_cyan());
+
+    let mover = match frame.player_id {
+        Some(player_id) if player_id == live_match.player1_id => live_match.player1_name.as_str(),
+        Some(player_id) if player_id == live_match.player2_id => live_match.player2_name.as_str(),
+        Some(_) => "?",
+        None => "",
+    };
+    let step = match &frame.move_data {
+        _ if index == 0 => "Start".to_string(),
+        Some(move_data) => format!("Move {index} of {last}: {mover} played {move_data}"),
+        None => format!("Move {index} of {last}: {mover}"),
+    };
+    let played_at = frame.played_at.map(|at| format!(" ({})", timestamps::absolute(at, preferences))).unwrap_or_default();
+    println!("  {}{}", step.bright_white(), played_at.dimmed());
+    println!();
+
+    let shown = Match {
+        game_state: frame.game_state.clone(),
+        in_progress: index < last,
+        ..replay.match_data.clone()
+    };
+    render_game(live_match, &shown);
+
+    println!();
+    println!("{}", "←/p: back | →/n/Space: forward | Home/End: first/last | q: quit".dimmed());
+    Ok(())
+}
+
+/// Wait for the next key pressed
+fn read_key() -> io::Result<KeyCode> {
+    terminal::enable_raw_mode()?;
+    let key = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => break Ok(key.code),
+            Ok(_) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    terminal::disable_raw_mode()?;
+    key
+}
@@ -0,0 +1,125 @@
+use colored::*;
+use futures_util::future::join_all;
+use rustyline::DefaultEditor;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+use crate::api;
+use crate::auth;
+use crate::state::SessionState;
+use crate::ui::clear_screen;
+use crate::width::pad_right;
+
+const PING_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A community server, as listed by the registry at `server_registry_url`
+#[derive(Deserialize, Debug, Clone)]
+pub struct ServerListing {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Subset of `GET /capacity` shown in the browser
+#[derive(Deserialize)]
+struct ServerLoad {
+    connections: usize,
+    active_matches: i64,
+}
+
+struct ServerStatus {
+    listing: ServerListing,
+    ping: Option<Duration>,
+    load: Option<ServerLoad>,
+}
+
+/// List the servers of the configured registry and switch to the selected one
+pub async fn show_server_browser(session: &mut SessionState) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(registry_url) = session.config.server_registry_url.clone() else {
+        println!("\n{}", "No server registry configured.".yellow());
+        println!("{}", "Set \"server_registry_url\" in your config to the URL of a JSON server list.".dimmed());
+        return Ok(());
+    };
+
+    clear_screen()?;
+    println!("\n{}", "Loading servers...".cyan());
+
+    let listings: Vec<ServerListing> = api::send(|client| client.get(&registry_url)).await?.json().await?;
+    // Pings are a single round trip each, retrying would only hide slow servers
+    let client = reqwest::Client::builder().timeout(PING_TIMEOUT).build()?;
+    let servers = join_all(listings.into_iter().map(|listing| check_server(&client, listing))).await;
+
+    clear_screen()?;
+    println!("\n{}", "Servers".bright_cyan().bold());
+    println!("{}", "───────────────────────────────────────────────────────────────────".dimmed());
+    println!("{:>3} {:30} {:>8} {:>8} {:>8}", "", "Name".dimmed(), "Ping".dimmed(), "Online".dimmed(), "Matches".dimmed());
+    println!("{}", "───────────────────────────────────────────────────────────────────".dimmed());
+
+    let current_url = session.config.server_url.clone().unwrap_or_default();
+    for (index, server) in servers.iter().enumerate() {
+        let marker = if server.listing.url == current_url { "*" } else { " " };
+        let ping = server.ping.map(|ping| format!("{}ms", ping.as_millis())).unwrap_or_else(|| "-".to_string());
+        let (online, matches) = match &server.load {
+            Some(load) => (load.connections.to_string(), load.active_matches.to_string()),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        let line = format!("{:>2}{marker} {} {:>8} {:>8} {:>8}", index + 1, pad_right(&server.listing.name, 30), ping, online, matches);
+        if server.ping.is_some() {
+            println!("{line}");
+        } else {
+            println!("{}", line.dimmed());
+        }
+        if let Some(description) = &server.listing.description {
+            println!("    {}", description.dimmed());
+        }
+    }
+    println!();
+    println!("{}", "* current server".dimmed());
+    println!();
+
+    let mut rl = DefaultEditor::new()?;
+    let choice = rl.readline("Server to connect to (Enter to keep the current one): ")?;
+    if choice.trim().is_empty() {
+        return Ok(());
+    }
+    let server = choice
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| servers.get(n.wrapping_sub(1)))
+        .ok_or("Invalid choice")?;
+    if server.listing.url == current_url {
+        return Ok(());
+    }
+    if server.ping.is_none() {
+        return Err(format!("{} is not reachable", server.listing.name).into());
+    }
+
+    switch_server(session, &server.listing.url)?;
+    println!("{}", format!("Connecting to {}...", server.listing.name).cyan());
+    auth::handle_login_command(session).await
+}
+
+async fn check_server(client: &reqwest::Client, listing: ServerListing) -> ServerStatus {
+    let started = Instant::now();
+    let response = client.get(format!("{}/capacity", listing.url)).send().await;
+    let ping = response.is_ok().then(|| started.elapsed());
+    let load = match response {
+        Ok(response) if response.status().is_success() => response.json().await.ok(),
+        _ => None,
+    };
+    ServerStatus { listing, ping, load }
+}
+
+/// Point the config to `server_url`, keeping the account of each server apart
+fn switch_server(session: &mut SessionState, server_url: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &mut session.config;
+    if let (Some(current_url), Some(player_id)) = (config.server_url.clone(), config.player_id) {
+        config.known_players.insert(current_url, player_id);
+    }
+    config.player_id = config.known_players.get(server_url).copied();
+    config.server_url = Some(server_url.to_string());
+    session.player_id = session.config.player_id;
+    session.save_config()
+}
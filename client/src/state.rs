@@ -1,4 +1,7 @@
+use battld_common::QuietHours;
 use crate::config::*;
+use crate::plugin::Plugin;
+use crate::rejoin::RejoinCache;
 use crate::websocket::WebSocketClient;
 use std::sync::Arc;
 
@@ -36,18 +39,36 @@ impl SessionState {
     }
 
     pub async fn connect_websocket(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(token) = &self.auth_token {
-            let server_url = self.config.server_url.as_ref().ok_or("No server URL configured")?;
-            let ws_url = format!("{}/ws", server_url.replace("http", "ws"));
-            // Use session token directly (not player_id:signature format)
-            let client = WebSocketClient::connect(&ws_url, token.clone()).await?;
-            self.ws_client = Some(Arc::new(client));
-            Ok(())
-        } else {
-            Err("Not authenticated".into())
+        match (self.player_id, self.auth_token.clone()) {
+            (Some(player_id), Some(token)) => {
+                let client = self.open_websocket(player_id, token).await?;
+                self.ws_client = Some(Arc::new(client));
+                Ok(())
+            }
+            _ => Err("Not authenticated".into()),
         }
     }
 
+    /// WebSocket authenticated with `token`, with the plugin and the rejoin cache attached
+    pub async fn open_websocket(&self, player_id: i64, token: String) -> Result<WebSocketClient, Box<dyn std::error::Error>> {
+        let server_url = self.config.server_url.as_ref().ok_or("No server URL configured")?;
+        let ws_url = format!("{}/ws", server_url.replace("http", "ws"));
+        // Use session token directly (not player_id:signature format)
+        let plugin = match self.config.plugin.clone() {
+            Some(path) => Some(Plugin::new(path, player_id, self.quiet_hours().await)),
+            None => None,
+        };
+        let rejoin = RejoinCache::new(self, player_id, &token);
+        WebSocketClient::connect(&ws_url, token, plugin, rejoin).await
+    }
+
+    /// Quiet hours set on the server with the offset they are in, none if they could not be fetched
+    async fn quiet_hours(&self) -> Option<(QuietHours, i32)> {
+        let quiet_hours = crate::api::player::fetch_quiet_hours(self).await.ok()??;
+        let preferences = crate::api::player::fetch_time_preferences(self).await.unwrap_or_default();
+        Some((quiet_hours, preferences.utc_offset_minutes))
+    }
+
     pub fn logout(&mut self) {
         self.auth_token = None;
         self.is_authenticated = false;
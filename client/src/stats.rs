@@ -1,12 +1,15 @@
-use battld_common::HEADER_AUTH;
+use battld_common::{games::game_type::GameType, DateStyle, ReplayPrivacy};
 use colored::*;
+use std::io::{self, Write};
 
+use crate::api::player::{fetch_match_export, fetch_quiet_hours, fetch_replay_settings, fetch_stats, fetch_time_preferences, set_quiet_hours, set_replay_privacy, set_time_preferences};
+use crate::replay;
 use crate::state::*;
+use crate::timestamps;
 use crate::ui::*;
+use crate::width::pad_right;
 
 pub async fn show_stats(session: &mut SessionState) -> Result<(), Box<dyn std::error::Error>> {
-    use battld_common::PlayerStats;
-
     clear_screen()?;
     println!("\n{}", "Loading your stats...".cyan());
 
@@ -14,24 +17,8 @@ pub async fn show_stats(session: &mut SessionState) -> Result<(), Box<dyn std::e
         return Err("Not authenticated".into());
     }
 
-    let config = &session.config;
-    let server_url = config.server_url.as_ref().ok_or("No server URL configured")?;
-    let token = session.auth_token.as_ref().ok_or("No auth token")?;
-
-    let client = reqwest::Client::new();
-    let url = format!("{server_url}/stats");
-
-    let response = client
-        .get(&url)
-        .header(HEADER_AUTH, format!("Bearer {token}"))
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        return Err(format!("Server error: {}", response.status()).into());
-    }
-
-    let stats: PlayerStats = response.json().await?;
+    let server_url = session.config.server_url.clone().ok_or("No server URL configured")?;
+    let stats = fetch_stats(session).await?;
 
     clear_screen()?;
     println!();
@@ -46,8 +33,118 @@ pub async fn show_stats(session: &mut SessionState) -> Result<(), Box<dyn std::e
     println!("  {} {}", "Dropped:     ".dimmed(), stats.dropped.to_string().dimmed());
     println!();
     println!("  {} {}", "Score:       ".bright_yellow().bold(), stats.score.to_string().bright_yellow().bold());
+    println!("  {} {}", "Rating:      ".bright_yellow(), stats.rating_label().bright_yellow());
+    for game_rating in &stats.game_ratings {
+        println!(
+            "    {} {}",
+            pad_right(&game_rating.game_type.to_string(), 20).bright_white(),
+            format!("{} ({} games)", game_rating.rating_label(), game_rating.games).bright_yellow()
+        );
+    }
+    println!(
+        "  {} {}",
+        "Tier:        ".bright_yellow(),
+        stats.tier.as_deref().unwrap_or("Unranked").bright_magenta()
+    );
+    if stats.provisional || stats.game_ratings.iter().any(|game_rating| game_rating.provisional) {
+        println!("  {}", "? Provisional, the rating settles after a few more games".dimmed());
+    }
+    println!();
+    for (label, seat) in [("First seat: ", &stats.first_seat), ("Second seat:", &stats.second_seat)] {
+        println!(
+            "  {} {}",
+            label.bright_white(),
+            format!("{} won, {} lost, {} draw", seat.won, seat.lost, seat.draw).bright_yellow()
+        );
+    }
+    println!("  {}", "First seat plays X in Tic-Tac-Toe and White in chess".dimmed());
     println!();
     println!("{}", "═══════════════════════════════════════".bright_cyan());
 
+    show_replays(session, &server_url).await
+}
+
+async fn show_replays(session: &SessionState, server_url: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let settings = fetch_replay_settings(session).await?;
+    let mut preferences = fetch_time_preferences(session).await?;
+    let quiet_hours = fetch_quiet_hours(session).await?;
+    let now = battld_common::time() as i64;
+
+    println!();
+    println!("  {} {}", "Replays visible to:".bright_white(), settings.privacy.as_str().bright_yellow());
+    println!(
+        "  {} {}",
+        "Times shown in:    ".bright_white(),
+        format!("{}, {} dates", timestamps::offset_label(preferences.utc_offset_minutes), preferences.date_style.as_str()).bright_yellow()
+    );
+    println!(
+        "  {} {}",
+        "Quiet hours:       ".bright_white(),
+        quiet_hours.as_ref().map(timestamps::quiet_hours_label).unwrap_or_else(|| "off".to_string()).bright_yellow()
+    );
+    if settings.recent.is_empty() {
+        println!("  {}", "No finished matches yet".dimmed());
+    }
+    for (index, replay) in settings.recent.iter().enumerate() {
+        let finished = replay.finished_at
+            .map(|finished_at| timestamps::describe(finished_at, now, &preferences))
+            .unwrap_or_default();
+        println!(
+            "  [{}] {:20} {:9} {}",
+            index.to_string().bright_yellow(),
+            replay.game_type.to_string(),
+            replay.game_type.seat_name(replay.seat),
+            finished.dimmed()
+        );
+        println!("    {}", format!("{server_url}/replay/{}", replay.token).bright_cyan());
+    }
+    println!();
+    println!("{}", "Type v N to step through replay N, s N to save it to a file, public, friends or private to change who can watch your replays,".dimmed());
+    println!("{}", "a UTC offset like UTC+2 or iso, us or eu to change how times are shown,".dimmed());
+    println!("{}", "quiet 22:00-07:00, optionally with except plugin,turn_reminders,opponent_idle, or quiet off to hold back notifications, Enter to go back".dimmed());
+    print!("> ");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(());
+    }
+    if let Some(index) = input.strip_prefix("v ") {
+        let replay = index.trim().parse::<usize>().ok().and_then(|index| settings.recent.get(index)).ok_or_else(|| format!("No replay {index}"))?;
+        return replay::show_replay(session, replay.match_id).await;
+    }
+    if let Some(index) = input.strip_prefix("s ") {
+        let replay = index.trim().parse::<usize>().ok().and_then(|index| settings.recent.get(index)).ok_or_else(|| format!("No replay {index}"))?;
+        let format = if replay.game_type == GameType::Chess { "pgn" } else { "json" };
+        let path = format!("battld-{}.{format}", replay.match_id);
+        std::fs::write(&path, fetch_match_export(session, replay.match_id, format).await?)?;
+        println!("{}", format!("Match saved to {path}").green());
+        return Ok(());
+    }
+    if let Some(privacy) = ReplayPrivacy::parse(input) {
+        set_replay_privacy(session, privacy).await?;
+        println!("{}", format!("Replays are now visible to: {}", privacy.as_str()).green());
+        return Ok(());
+    }
+
+    if let Some(value) = input.strip_prefix("quiet ") {
+        let quiet_hours = timestamps::parse_quiet_hours(value).ok_or_else(|| format!("Unknown quiet hours {value}"))?;
+        set_quiet_hours(session, quiet_hours.as_ref()).await?;
+        let label = quiet_hours.as_ref().map(timestamps::quiet_hours_label).unwrap_or_else(|| "off".to_string());
+        println!("{}", format!("Quiet hours are now {label}").green());
+        return Ok(());
+    }
+
+    if let Some(date_style) = DateStyle::parse(input) {
+        preferences.date_style = date_style;
+    } else if let Some(utc_offset_minutes) = timestamps::parse_offset(input) {
+        preferences.utc_offset_minutes = utc_offset_minutes;
+    } else {
+        return Err(format!("Unknown setting {input}").into());
+    }
+    set_time_preferences(session, preferences).await?;
+    println!("{}", format!("Times are now shown as {}", timestamps::absolute(now, &preferences)).green());
     Ok(())
 }
\ No newline at end of file
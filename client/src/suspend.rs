@@ -0,0 +1,52 @@
+use std::io::{self, Write};
+use std::sync::OnceLock;
+use tokio::sync::broadcast;
+
+static RESUMED: OnceLock<broadcast::Sender<()>> = OnceLock::new();
+
+fn resumed_sender() -> &'static broadcast::Sender<()> {
+    RESUMED.get_or_init(|| broadcast::channel(4).0)
+}
+
+/// Handle Ctrl+Z for the whole client: the terminal is restored before stopping
+/// and screens subscribed with `resumed` are told to redraw once back in the foreground
+pub fn install() {
+    #[cfg(unix)]
+    tokio::spawn(async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let Ok(mut requests) = signal(SignalKind::from_raw(libc::SIGTSTP)) else {
+            return;
+        };
+        while requests.recv().await.is_some() {
+            suspend();
+            crate::ui::forget_frame();
+            let _ = resumed_sender().send(());
+        }
+    });
+}
+
+/// Notified every time the client comes back from a suspension
+pub fn resumed() -> broadcast::Receiver<()> {
+    resumed_sender().subscribe()
+}
+
+#[cfg(unix)]
+fn suspend() {
+    use crossterm::{cursor, execute, terminal};
+
+    let raw_mode = terminal::is_raw_mode_enabled().unwrap_or(false);
+    let _ = terminal::disable_raw_mode();
+    let _ = execute!(io::stdout(), cursor::Show);
+    println!("\nSuspended, run `fg` to get back to the game");
+    let _ = io::stdout().flush();
+
+    // SIGSTOP cannot be handled, the client stops here until it gets SIGCONT
+    unsafe {
+        libc::kill(libc::getpid(), libc::SIGSTOP);
+    }
+
+    if raw_mode {
+        let _ = terminal::enable_raw_mode();
+    }
+}
@@ -0,0 +1,175 @@
+use battld_common::{DateStyle, NotificationChannel, QuietHours, TimePreferences};
+
+const MINUTE: i64 = 60;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+
+/// "2 hours ago (2025-10-31 14:05)", `at` and `now` in UTC seconds
+pub fn describe(at: i64, now: i64, preferences: &TimePreferences) -> String {
+    format!("{} ({})", relative(at, now), absolute(at, preferences))
+}
+
+pub fn relative(at: i64, now: i64) -> String {
+    let elapsed = (now - at).max(0);
+    let (amount, unit) = match elapsed {
+        0..MINUTE => return "just now".to_string(),
+        MINUTE..HOUR => (elapsed / MINUTE, "minute"),
+        HOUR..DAY => (elapsed / HOUR, "hour"),
+        _ => (elapsed / DAY, "day"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    format!("{amount} {unit}{plural} ago")
+}
+
+/// Date and time in the player's time zone and date style
+pub fn absolute(at: i64, preferences: &TimePreferences) -> String {
+    let local = at + preferences.utc_offset_minutes as i64 * MINUTE;
+    let (year, month, day) = civil_date(local.div_euclid(DAY));
+    let seconds_of_day = local.rem_euclid(DAY);
+    let (hour, minute) = (seconds_of_day / HOUR, seconds_of_day % HOUR / MINUTE);
+
+    match preferences.date_style {
+        DateStyle::Iso => format!("{year}-{month:02}-{day:02} {hour:02}:{minute:02}"),
+        DateStyle::Eu => format!("{day:02}/{month:02}/{year} {hour:02}:{minute:02}"),
+        DateStyle::Us => {
+            let period = if hour < 12 { "AM" } else { "PM" };
+            let hour = match hour % 12 {
+                0 => 12,
+                hour => hour,
+            };
+            format!("{month:02}/{day:02}/{year} {hour}:{minute:02} {period}")
+        }
+    }
+}
+
+/// "UTC+05:30" style label of an offset
+pub fn offset_label(utc_offset_minutes: i32) -> String {
+    let sign = if utc_offset_minutes < 0 { '-' } else { '+' };
+    let minutes = utc_offset_minutes.abs();
+    format!("UTC{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Parse "+2", "-05:00" or "UTC+5:30" into minutes east of UTC
+pub fn parse_offset(value: &str) -> Option<i32> {
+    let value = value.trim();
+    let value = value.strip_prefix("UTC").or_else(|| value.strip_prefix("utc")).unwrap_or(value);
+    if value.is_empty() {
+        return Some(0);
+    }
+    let (sign, value) = match value.split_at(1) {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+    let (hours, minutes) = value.split_once(':').unwrap_or((value, "0"));
+    let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+    if !(0..60).contains(&minutes) {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// "22:00-07:00 except plugin" style label of quiet hours
+pub fn quiet_hours_label(quiet_hours: &QuietHours) -> String {
+    let time = |minute: u16| format!("{:02}:{:02}", minute / 60, minute % 60);
+    let mut label = format!("{}-{}", time(quiet_hours.start_minute), time(quiet_hours.end_minute));
+    if !quiet_hours.except.is_empty() {
+        let except: Vec<&str> = quiet_hours.except.iter().map(NotificationChannel::as_str).collect();
+        label.push_str(&format!(" except {}", except.join(",")));
+    }
+    label
+}
+
+/// Parse "22:00-07:00", optionally followed by "except plugin,opponent_idle", or "off" for none
+pub fn parse_quiet_hours(value: &str) -> Option<Option<QuietHours>> {
+    let value = value.trim();
+    if value == "off" {
+        return Some(None);
+    }
+    let (window, except) = match value.split_once(" except ") {
+        Some((window, except)) => (window, except.split(',').map(|channel| NotificationChannel::parse(channel.trim())).collect::<Option<Vec<_>>>()?),
+        None => (value, vec![]),
+    };
+    let minute = |time: &str| -> Option<u16> {
+        let (hours, minutes) = time.trim().split_once(':').unwrap_or((time.trim(), "0"));
+        let (hours, minutes) = (hours.parse::<u16>().ok()?, minutes.parse::<u16>().ok()?);
+        (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+    };
+    let (start, end) = window.split_once('-')?;
+    let quiet_hours = QuietHours { start_minute: minute(start)?, end_minute: minute(end)?, except };
+    quiet_hours.is_valid().then_some(Some(quiet_hours))
+}
+
+/// Year, month and day of a count of days since 1970-01-01, in the proleptic Gregorian calendar
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2025-10-31 14:05:00 UTC
+    const HALLOWEEN: i64 = 1_761_919_500;
+
+    fn preferences(utc_offset_minutes: i32, date_style: DateStyle) -> TimePreferences {
+        TimePreferences { utc_offset_minutes, date_style }
+    }
+
+    #[test]
+    fn test_absolute() {
+        assert_eq!(absolute(0, &TimePreferences::default()), "1970-01-01 00:00");
+        assert_eq!(absolute(HALLOWEEN, &preferences(0, DateStyle::Iso)), "2025-10-31 14:05");
+        assert_eq!(absolute(HALLOWEEN, &preferences(0, DateStyle::Us)), "10/31/2025 2:05 PM");
+        assert_eq!(absolute(HALLOWEEN, &preferences(600, DateStyle::Eu)), "01/11/2025 00:05");
+        assert_eq!(absolute(HALLOWEEN, &preferences(-14 * 60 - 5, DateStyle::Us)), "10/31/2025 12:00 AM");
+        assert_eq!(absolute(951_782_400, &TimePreferences::default()), "2000-02-29 00:00");
+    }
+
+    #[test]
+    fn test_relative() {
+        assert_eq!(relative(HALLOWEEN, HALLOWEEN + 30), "just now");
+        assert_eq!(relative(HALLOWEEN, HALLOWEEN + 60), "1 minute ago");
+        assert_eq!(relative(HALLOWEEN, HALLOWEEN + 2 * HOUR + 5), "2 hours ago");
+        assert_eq!(relative(HALLOWEEN, HALLOWEEN + 3 * DAY), "3 days ago");
+        assert_eq!(relative(HALLOWEEN + 10, HALLOWEEN), "just now");
+    }
+
+    #[test]
+    fn test_offsets() {
+        assert_eq!(parse_offset("+2"), Some(120));
+        assert_eq!(parse_offset("UTC-05:00"), Some(-300));
+        assert_eq!(parse_offset("utc+5:30"), Some(330));
+        assert_eq!(parse_offset("UTC"), Some(0));
+        assert_eq!(parse_offset("5"), None);
+        assert_eq!(parse_offset("+1:75"), None);
+        assert_eq!(offset_label(330), "UTC+05:30");
+        assert_eq!(offset_label(-300), "UTC-05:00");
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let quiet_hours = parse_quiet_hours("22:00-7 except plugin").unwrap().unwrap();
+        assert_eq!((quiet_hours.start_minute, quiet_hours.end_minute), (22 * 60, 7 * 60));
+        assert_eq!(quiet_hours.except, vec![NotificationChannel::Plugin]);
+        assert_eq!(quiet_hours_label(&quiet_hours), "22:00-07:00 except plugin");
+        assert_eq!(parse_quiet_hours("off"), Some(None));
+        assert_eq!(parse_quiet_hours("08:00-08:00"), None);
+        assert_eq!(parse_quiet_hours("22:00-25:00"), None);
+        assert_eq!(parse_quiet_hours("22:00-07:00 except email"), None);
+
+        // 23:05 in UTC+9 is 14:05 UTC
+        assert!(quiet_hours.silences(NotificationChannel::TurnReminders, HALLOWEEN, 9 * 60));
+        assert!(!quiet_hours.silences(NotificationChannel::Plugin, HALLOWEEN, 9 * 60));
+        assert!(!quiet_hours.silences(NotificationChannel::TurnReminders, HALLOWEEN, 0));
+    }
+}
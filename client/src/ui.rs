@@ -1,12 +1,100 @@
+use battld_common::{Language, ServerMessage};
+use colored::*;
+use std::fmt::Display;
 use std::io::{self, Write};
-use crossterm::{event::{self, Event}, terminal};
+use std::sync::Mutex;
+use crossterm::{event::{self, Event, KeyCode, KeyEventKind}, terminal};
+
+pub const LOGO: [&str; 3] = [
+    "░█▀▄░█▀█░▀█▀░▀█▀░█░░░█▀▄",
+    "░█▀▄░█▀█░░█░░░█░░█░░░█░█",
+    "░▀▀░░▀░▀░░▀░░░▀░░▀▀▀░▀▀░",
+];
+
+/// Lines of the last frame on screen, None when the screen holds something else
+static SCREEN: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// Rows kept free under a frame for what gets printed after it, such as errors and the thinking indicator,
+/// taller frames are redrawn in full since the terminal may have scrolled
+const FRAME_MARGIN_ROWS: usize = 5;
+
+/// Terminals narrower than this, such as split tmux panes, get the compact game screens
+const COMPACT_COLUMNS: u16 = 60;
+
+/// Whether the terminal is too narrow for the full game screens
+pub fn is_compact() -> bool {
+    terminal::size().is_ok_and(|(columns, _)| columns < COMPACT_COLUMNS)
+}
+
+/// Width of the `=` rules around game headers
+pub fn rule_width() -> usize {
+    if is_compact() { 30 } else { 50 }
+}
 
 pub fn clear_screen() -> io::Result<()> {
+    forget_frame();
     print!("\x1B[2J\x1B[1;1H");
     io::stdout().flush()?;
     Ok(())
 }
 
+/// A screen built like with `print!` and `println!`, drawn at once by `present`
+/// Text after the last line break stays as a prompt, with the cursor after it
+#[derive(Default)]
+pub struct Frame {
+    text: String,
+}
+
+impl Frame {
+    pub fn print(&mut self, text: impl Display) {
+        self.text.push_str(&text.to_string());
+    }
+
+    pub fn println(&mut self, text: impl Display) {
+        self.print(text);
+        self.newline();
+    }
+
+    pub fn newline(&mut self) {
+        self.text.push('\n');
+    }
+}
+
+/// Draw the next frame in full, for when something else may have changed the screen
+pub fn forget_frame() {
+    *SCREEN.lock().unwrap() = None;
+}
+
+/// Draw a frame, rewriting only the lines that changed since the previous one to avoid flicker
+pub fn present(frame: &Frame) -> io::Result<()> {
+    let mut lines: Vec<String> = frame.text.split('\n').map(str::to_string).collect();
+    let prompt = lines.pop().unwrap_or_default();
+    let rows = terminal::size().map(|(_, rows)| rows as usize).unwrap_or(0);
+
+    let mut screen = SCREEN.lock().unwrap();
+    let previous = screen.take().filter(|_| lines.len() + FRAME_MARGIN_ROWS <= rows);
+    let mut output = match &previous {
+        Some(previous) => frame_diff(previous, &lines),
+        None => format!("\x1B[2J\x1B[1;1H{}", lines.iter().map(|line| format!("{line}\n")).collect::<String>()),
+    };
+    output.push_str(&format!("\x1B[{};1H\x1B[J{prompt}", lines.len() + 1));
+    *screen = Some(lines);
+    drop(screen);
+
+    let mut stdout = io::stdout();
+    stdout.write_all(output.as_bytes())?;
+    stdout.flush()
+}
+
+/// Escape sequences turning the `previous` lines on screen into `next`, leaving alone those that match
+fn frame_diff(previous: &[String], next: &[String]) -> String {
+    next.iter()
+        .enumerate()
+        .filter(|(index, line)| previous.get(*index) != Some(*line))
+        .map(|(index, line)| format!("\x1B[{};1H{line}\x1B[K", index + 1))
+        .collect()
+}
+
 pub fn drain_stdin_buffer() {
     // Use crossterm to drain any buffered input
     let _ = terminal::enable_raw_mode();
@@ -40,3 +128,63 @@ pub fn wait_for_keypress() -> io::Result<()> {
     terminal::disable_raw_mode()?;
     Ok(())
 }
+
+/// Wait for y/Enter or n/Esc, None if no answer came within `timeout`
+pub fn confirm_within(timeout: std::time::Duration) -> io::Result<Option<bool>> {
+    terminal::enable_raw_mode()?;
+    while event::poll(std::time::Duration::from_millis(10))? {
+        event::read()?;
+    }
+
+    let deadline = std::time::Instant::now() + timeout;
+    let answer = loop {
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        if remaining.is_zero() {
+            break None;
+        }
+        if !event::poll(remaining.min(std::time::Duration::from_millis(100)))? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Enter | KeyCode::Char('y') | KeyCode::Char('Y') => break Some(true),
+                KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('N') => break Some(false),
+                _ => {}
+            }
+        }
+    };
+
+    terminal::disable_raw_mode()?;
+    Ok(answer)
+}
+
+pub fn show_server_busy(retry_after: u64) -> io::Result<()> {
+    println!();
+    println!("{}", "The server is busy right now.".yellow().bold());
+    println!("{}", format!("Please try again in about {retry_after} seconds.").yellow());
+    println!("\nPress any key to return to main menu...");
+    io::stdout().flush()?;
+    wait_for_keypress()
+}
+
+/// Text of a server error in the language of the `LANG` locale, None for other messages
+pub fn error_text(message: &ServerMessage) -> Option<String> {
+    let language = Language::from_locale(&std::env::var("LANG").unwrap_or_default());
+    message.error_text(language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_diff_rewrites_changed_lines_only() {
+        let previous = vec!["title".to_string(), "board 1".to_string(), "footer".to_string()];
+        let next = vec!["title".to_string(), "board 2".to_string(), "footer".to_string(), "extra".to_string()];
+        assert_eq!(frame_diff(&previous, &next), "\x1B[2;1Hboard 2\x1B[K\x1B[4;1Hextra\x1B[K");
+        assert_eq!(frame_diff(&next, &next), "");
+    }
+}
@@ -0,0 +1,150 @@
+use battld_common::games::tic_tac_toe::TicTacToeGameState;
+use colored::*;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    terminal,
+};
+use rand::seq::SliceRandom;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Tic-Tac-Toe puzzles to pass the time in the matchmaking queue
+/// Keys are read without blocking, so the game screen takes over as soon as a match is found
+#[derive(Default)]
+pub struct WaitingRoom {
+    puzzle: Option<TicTacToeGameState>,
+    solved: u32,
+    feedback: Option<String>,
+    /// Escape was pressed outside the puzzle, set until the game screen asks the server to leave the queue
+    leave_requested: bool,
+    leaving: bool,
+}
+
+impl WaitingRoom {
+    /// Handle the keys typed since the last call, true if the puzzle changed and needs redrawing
+    pub fn handle_keys(&mut self) -> io::Result<bool> {
+        terminal::enable_raw_mode()?;
+        let keys = read_pending_keys();
+        terminal::disable_raw_mode()?;
+        let mut changed = false;
+        for code in keys? {
+            changed |= self.handle_key(code);
+        }
+        Ok(changed)
+    }
+
+    /// True once after Escape was pressed to leave the queue, the caller sends `LeaveMatchmaking`
+    pub fn take_leave_request(&mut self) -> bool {
+        std::mem::take(&mut self.leave_requested)
+    }
+
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        let Some(puzzle) = &self.puzzle else {
+            match code {
+                KeyCode::Char('p') => {
+                    self.puzzle = Some(new_puzzle());
+                    self.feedback = None;
+                    return true;
+                }
+                KeyCode::Esc if !self.leaving => {
+                    self.leave_requested = true;
+                    self.leaving = true;
+                    return true;
+                }
+                _ => return false,
+            }
+        };
+
+        let cell = match code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.puzzle = None;
+                self.feedback = None;
+                return true;
+            }
+            KeyCode::Char(c @ '1'..='9') => c as usize - '1' as usize,
+            _ => return false,
+        };
+
+        let mut attempt = puzzle.clone();
+        if attempt.place_move(cell, 1).is_err() {
+            self.feedback = Some("That cell is taken".to_string());
+        } else if attempt.check_winner() == Some(1) {
+            self.solved += 1;
+            self.puzzle = Some(new_puzzle());
+            self.feedback = Some(format!("Solved! {} so far", self.solved));
+        } else {
+            self.feedback = Some("Not quite, X doesn't win there".to_string());
+        }
+        true
+    }
+
+    /// Drawn below the waiting screen
+    pub fn render(&self) {
+        if self.leaving {
+            println!("{}", "  Leaving matchmaking...".dimmed());
+            io::stdout().flush().ok();
+            return;
+        }
+        let Some(puzzle) = &self.puzzle else {
+            println!("{}", "  Press p for a Tic-Tac-Toe puzzle while you wait, Esc to leave matchmaking".dimmed());
+            io::stdout().flush().ok();
+            return;
+        };
+
+        println!("{}", "  Puzzle: X to move and win".bright_cyan());
+        println!();
+        for row in 0..puzzle.board_size {
+            let cells: Vec<String> = (0..puzzle.board_size)
+                .map(|col| {
+                    let index = row * puzzle.board_size + col;
+                    match puzzle.board[index] {
+                        1 => "X".bright_blue().to_string(),
+                        2 => "O".bright_magenta().to_string(),
+                        _ => (index + 1).to_string().dimmed().to_string(),
+                    }
+                })
+                .collect();
+            println!("   {}", cells.join(" | "));
+        }
+        println!();
+        if let Some(feedback) = &self.feedback {
+            println!("  {}", feedback.yellow());
+        }
+        println!("{}", "  Press the number of the winning cell, q to stop".dimmed());
+        io::stdout().flush().ok();
+    }
+}
+
+/// Keys pressed since the last call, without waiting for any
+pub fn read_pending_keys() -> io::Result<Vec<KeyCode>> {
+    let mut keys = vec![];
+    while event::poll(Duration::ZERO)? {
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                keys.push(key.code);
+            }
+        }
+    }
+    Ok(keys)
+}
+
+/// A position after two moves each where X, to move, can win at once
+fn new_puzzle() -> TicTacToeGameState {
+    let mut rng = rand::thread_rng();
+    loop {
+        let mut state = TicTacToeGameState::new();
+        let mut cells: Vec<usize> = (0..state.board.len()).collect();
+        cells.shuffle(&mut rng);
+        for (turn, cell) in cells.iter().take(4).enumerate() {
+            state.board[*cell] = if turn % 2 == 0 { 1 } else { 2 };
+        }
+
+        let wins = (0..state.board.len()).any(|cell| {
+            let mut attempt = state.clone();
+            attempt.place_move(cell, 1).is_ok() && attempt.check_winner() == Some(1)
+        });
+        if wins {
+            return state;
+        }
+    }
+}
@@ -1,18 +1,146 @@
-use battld_common::games::matches::Match;
-use battld_common::{ClientMessage, ServerMessage};
-use futures_util::{SinkExt, StreamExt};
-use std::sync::Arc;
+use battld_common::games::matches::{Match, MatchStatus};
+use battld_common::{ArenaInfo, ClientMessage, MatchChallenge, ServerMessage};
+use crate::plugin::Plugin;
+use crate::rejoin::RejoinCache;
+use futures_util::{Sink, SinkExt, StreamExt};
+use rand::Rng;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpStream;
 use tokio::sync::{mpsc, RwLock};
 use tokio::time::{Duration, interval};
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
-use std::fs::OpenOptions;
-use std::io::Write as _;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream, tungstenite::{self, protocol::Message}};
 
-/// WebSocket client for real-time game updates
-pub struct WebSocketClient {
-    tx: mpsc::UnboundedSender<ClientMessage>,
+type Stream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Wait before the first reconnection attempt, doubled after every failed one
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+/// Failed attempts in a row before giving up on the server
+const RECONNECT_ATTEMPTS: u32 = 10;
+
+/// State of the connection as shown by the game screens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connection {
+    Up,
+    /// Dropped, trying to get back with this attempt
+    Reconnecting(u32),
+    /// Every attempt failed
+    Lost,
+}
+
+/// The connection state and whether it changed since the screen was last drawn
+static CONNECTION: Mutex<(Connection, bool)> = Mutex::new((Connection::Up, false));
+
+/// Current state of the connection to the server
+pub fn connection() -> Connection {
+    CONNECTION.lock().unwrap().0
+}
+
+/// True once after the connection dropped or came back, for the screen on show to redraw its banner
+pub fn take_connection_change() -> bool {
+    std::mem::take(&mut CONNECTION.lock().unwrap().1)
+}
+
+fn set_connection(state: Connection) {
+    *CONNECTION.lock().unwrap() = (state, true);
+}
+
+/// What the receiving side keeps up to date for the screens
+#[derive(Clone, Default)]
+struct Inbox {
     server_messages: Arc<RwLock<Vec<ServerMessage>>>,
     current_match: Arc<RwLock<Option<Match>>>,
+    /// Message of the day sent when authenticating
+    motd: Arc<RwLock<Option<String>>>,
+    /// Latest ladder tier change, shown once back at the menu
+    tier_change: Arc<RwLock<Option<ServerMessage>>>,
+    /// Score and rank after the last match, kept for the menu
+    stats_delta: Arc<RwLock<Option<ServerMessage>>>,
+    /// Challenges received and still open that the menu has not announced yet
+    challenge_notices: Arc<RwLock<Vec<MatchChallenge>>>,
+    /// Arenas announced that the menu has not told about yet
+    arena_notices: Arc<RwLock<Vec<ArenaInfo>>>,
+    /// Latest arena announced, offered on the menu until it ends or we join it
+    announced_arena: Arc<RwLock<Option<ArenaInfo>>>,
+}
+
+impl Inbox {
+    async fn receive(&self, text: &str, plugin: &mut Option<Plugin>, rejoin: &mut Option<RejoinCache>) -> Option<ServerMessage> {
+        let server_msg = serde_json::from_str::<ServerMessage>(text).ok()?;
+        tracing::debug!("[RECV] {}", crate::logging::message(&server_msg));
+        crate::bugreport::record_received(&server_msg);
+
+        let server_msg = resume_from_current(server_msg, self.current_match.read().await.as_ref());
+        crate::games::track_series(&server_msg);
+
+        // Update current match state immediately for game state updates
+        match &server_msg {
+            ServerMessage::MatchFound { match_data } => {
+                *self.current_match.write().await = Some(match_data.clone());
+            }
+            ServerMessage::GameStateUpdate { match_data } => {
+                *self.current_match.write().await = Some(match_data.clone());
+            }
+            ServerMessage::AuthSuccess { motd, .. } => {
+                *self.motd.write().await = motd.clone();
+            }
+            ServerMessage::TierChanged { .. } => {
+                *self.tier_change.write().await = Some(server_msg.clone());
+            }
+            ServerMessage::StatsDelta { .. } => {
+                *self.stats_delta.write().await = Some(server_msg.clone());
+            }
+            ServerMessage::ChallengeReceived { challenge } => {
+                self.challenge_notices.write().await.push(challenge.clone());
+            }
+            ServerMessage::ChallengeDeclined { challenge_id } | ServerMessage::ChallengeExpired { challenge_id } => {
+                self.challenge_notices.write().await.retain(|challenge| challenge.id != *challenge_id);
+            }
+            ServerMessage::ArenaAnnounced { arena } => {
+                self.arena_notices.write().await.push(arena.clone());
+                *self.announced_arena.write().await = Some(arena.clone());
+            }
+            ServerMessage::ArenaJoined { arena: ArenaInfo { id: arena_id, .. } } | ServerMessage::ArenaEnded { arena_id, .. } => {
+                let mut announced = self.announced_arena.write().await;
+                if announced.as_ref().is_some_and(|arena| arena.id == *arena_id) {
+                    *announced = None;
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(plugin) = plugin.as_mut() {
+            plugin.notify(&server_msg);
+        }
+        if let Some(rejoin) = rejoin.as_mut() {
+            rejoin.notify(&server_msg);
+        }
+
+        // Always queue ALL messages so they can be printed/processed
+        self.server_messages.write().await.push(server_msg.clone());
+        Some(server_msg)
+    }
+
+    /// Whether we were in a match the server should bring back after a reconnection
+    async fn in_match(&self) -> bool {
+        self.current_match.read().await.as_ref().is_some_and(|match_data| match_data.in_progress)
+    }
+}
+
+/// How a connection came to an end
+enum Ended {
+    /// Closed on purpose by the client
+    Closed,
+    /// The session is no longer valid, reconnecting would not help
+    Rejected,
+    /// Dropped by the network or the server, with the message that could not be sent
+    Dropped(Option<ClientMessage>),
+}
+
+/// WebSocket client for real-time game updates, reconnecting on its own when the connection drops
+pub struct WebSocketClient {
+    tx: mpsc::UnboundedSender<ClientMessage>,
+    inbox: Inbox,
     connected: Arc<RwLock<bool>>,
     close_tx: Arc<RwLock<Option<mpsc::UnboundedSender<()>>>>,
     #[allow(dead_code)]
@@ -21,113 +149,33 @@ pub struct WebSocketClient {
 
 impl WebSocketClient {
     /// Connect to the WebSocket server and authenticate
-    pub async fn connect(ws_url: &str, auth_token: String) -> Result<Self, Box<dyn std::error::Error>> {
-        let (ws_stream, _) = connect_async(ws_url).await?;
-        let (mut write, mut read) = ws_stream.split();
+    pub async fn connect(
+        ws_url: &str,
+        auth_token: String,
+        plugin: Option<Plugin>,
+        rejoin: Option<RejoinCache>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let stream = open(ws_url, &auth_token).await?;
+        set_connection(Connection::Up);
 
         // Create channel for sending messages to server
-        let (tx, mut rx) = mpsc::unbounded_channel::<ClientMessage>();
-
-        // Send authentication as first message
-        let auth_msg = ClientMessage::Authenticate { token: auth_token.clone() };
-        let auth_json = serde_json::to_string(&auth_msg)?;
-        write.send(Message::Text(auth_json)).await?;
-
-        // Shared storage for server messages
-        let server_messages = Arc::new(RwLock::new(Vec::new()));
-        let server_messages_clone = server_messages.clone();
-
-        // Shared storage for current match state
-        let current_match = Arc::new(RwLock::new(None));
-        let current_match_clone = current_match.clone();
-
-        // Connection status
+        let (tx, rx) = mpsc::unbounded_channel::<ClientMessage>();
+        let inbox = Inbox::default();
         let connected = Arc::new(RwLock::new(true));
-        let connected_read = connected.clone();
-        let connected_write = connected.clone();
 
         // Channel for triggering close
-        let (close_tx, mut close_rx) = mpsc::unbounded_channel::<()>();
+        let (close_tx, close_rx) = mpsc::unbounded_channel::<()>();
         let close_tx_shared = Arc::new(RwLock::new(Some(close_tx)));
 
-        // Spawn task to send messages to server
-        tokio::spawn(async move {
-            loop {
-                tokio::select! {
-                    Some(msg) = rx.recv() => {
-                        // Log outgoing message
-                        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("client.log") {
-                            let _ = writeln!(file, "[SEND] {msg:?}");
-                        }
-
-                        if let Ok(json) = serde_json::to_string(&msg) {
-                            if write.send(Message::Text(json)).await.is_err() {
-                                eprintln!("WebSocket send failed - connection may be lost");
-                                *connected_write.write().await = false;
-                                break;
-                            }
-                        }
-                    }
-                    Some(_) = close_rx.recv() => {
-                        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("client.log") {
-                            let _ = writeln!(file, "[EVENT] Closing WebSocket connection");
-                        }
-                        let _ = write.send(Message::Close(None)).await;
-                        let _ = write.close().await;
-                        *connected_write.write().await = false;
-                        break;
-                    }
-                }
-            }
-        });
-
-        // Spawn task to receive messages from server
-        tokio::spawn(async move {
-            while let Some(msg) = read.next().await {
-                match msg {
-                    Ok(Message::Text(text)) => {
-                        if let Ok(server_msg) = serde_json::from_str::<ServerMessage>(&text) {
-                            // Log incoming message
-                            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("client.log") {
-                                let _ = writeln!(file, "[RECV] {server_msg:?}");
-                            }
-
-                            // Update current match state immediately for game state updates
-                            match &server_msg {
-                                ServerMessage::MatchFound { match_data } => {
-                                    *current_match_clone.write().await = Some(match_data.clone());
-                                }
-                                ServerMessage::GameStateUpdate { match_data } => {
-                                    *current_match_clone.write().await = Some(match_data.clone());
-                                }
-                                _ => {}
-                            }
-
-                            // Always queue ALL messages so they can be printed/processed
-                            let mut messages = server_messages_clone.write().await;
-                            messages.push(server_msg);
-                        }
-                    }
-                    Ok(Message::Close(_)) => {
-                        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("client.log") {
-                            let _ = writeln!(file, "[EVENT] WebSocket connection closed by server");
-                        }
-                        eprintln!("WebSocket connection closed by server");
-                        *connected_read.write().await = false;
-                        break;
-                    }
-                    Err(e) => {
-                        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("client.log") {
-                            let _ = writeln!(file, "[EVENT] WebSocket error: {e}");
-                        }
-                        eprintln!("WebSocket error: {e}");
-                        *connected_read.write().await = false;
-                        break;
-                    }
-                    _ => {}
-                }
-            }
-        });
+        tokio::spawn(run(
+            stream,
+            Reconnection { ws_url: ws_url.to_string(), token: auth_token, close_rx },
+            rx,
+            inbox.clone(),
+            connected.clone(),
+            plugin,
+            rejoin,
+        ));
 
         // Spawn keepalive/token-refresh task
         // Ping every 30 seconds to keep connection alive and auto-refresh session token
@@ -144,8 +192,7 @@ impl WebSocketClient {
 
         Ok(WebSocketClient {
             tx,
-            server_messages,
-            current_match,
+            inbox,
             connected,
             close_tx: close_tx_shared,
             keepalive_handle: Some(keepalive_handle),
@@ -160,15 +207,70 @@ impl WebSocketClient {
 
     /// Get and clear all pending server messages
     pub async fn get_messages(&self) -> Vec<ServerMessage> {
-        let mut messages = self.server_messages.write().await;
+        let mut messages = self.inbox.server_messages.write().await;
         let result = messages.clone();
         messages.clear();
         result
     }
 
+    /// Message of the day, if the server has one
+    pub async fn get_motd(&self) -> Option<String> {
+        self.inbox.motd.read().await.clone()
+    }
+
+    /// Latest ladder tier change not shown yet, as a line to print
+    pub async fn take_tier_change(&self) -> Option<String> {
+        match self.inbox.tier_change.write().await.take()? {
+            ServerMessage::TierChanged { tier: Some(tier), promoted: true, .. } => Some(format!("Promoted to {tier}!")),
+            ServerMessage::TierChanged { tier: Some(tier), .. } => Some(format!("Moved down to {tier}")),
+            ServerMessage::TierChanged { tier: None, .. } => Some("You are no longer ranked on the ladder".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Score and leaderboard place after the last match played, as a line to print
+    pub async fn stats_summary(&self) -> Option<String> {
+        match *self.inbox.stats_delta.read().await {
+            Some(ServerMessage::StatsDelta { score_change, new_score, new_rank }) => {
+                let rank = new_rank.map(|rank| format!(", #{rank} on the leaderboard")).unwrap_or_default();
+                Some(format!("Score {new_score} ({score_change:+} last match){rank}"))
+            }
+            _ => None,
+        }
+    }
+
+    /// Challenges received since the menu last asked, as lines to print
+    pub async fn take_challenge_notices(&self) -> Vec<String> {
+        let now = battld_common::time() as i64;
+        std::mem::take(&mut *self.inbox.challenge_notices.write().await)
+            .into_iter()
+            .filter(|challenge| challenge.expires_at > now)
+            .map(|challenge| format!("{} challenges you to {} - open Challenges to answer", challenge.challenger_name, challenge.game_type))
+            .collect()
+    }
+
+    /// Arenas announced since the menu last asked, as lines to print
+    pub async fn take_arena_notices(&self) -> Vec<String> {
+        let now = battld_common::time() as i64;
+        std::mem::take(&mut *self.inbox.arena_notices.write().await)
+            .into_iter()
+            .filter(|arena| arena.ends_at > now)
+            .map(|arena| {
+                let minutes = ((arena.starts_at - now).max(0) + 59) / 60;
+                format!("A {} arena starts in {minutes} min - enter a to join it", arena.game_type)
+            })
+            .collect()
+    }
+
+    /// The arena last announced while it is not over, to join from the menu
+    pub async fn announced_arena(&self) -> Option<ArenaInfo> {
+        let now = battld_common::time() as i64;
+        self.inbox.announced_arena.read().await.clone().filter(|arena| arena.ends_at > now)
+    }
+
     /// Get the current match state (updated in real-time)
     pub async fn get_current_match(&self) -> Option<Match> {
-        self.current_match.read().await.clone()
+        self.inbox.current_match.read().await.clone()
     }
 
     /// Check if the WebSocket is currently connected
@@ -183,3 +285,160 @@ impl WebSocketClient {
         }
     }
 }
+
+/// Open a connection and send the session token as the first message
+async fn open(ws_url: &str, token: &str) -> Result<Stream, tungstenite::Error> {
+    let (mut stream, _) = connect_async(ws_url).await?;
+    tracing::info!("Connected to {ws_url}");
+    send_message(&mut stream, &ClientMessage::Authenticate { token: token.to_string() }).await?;
+    Ok(stream)
+}
+
+async fn send_message<S>(write: &mut S, msg: &ClientMessage) -> Result<(), tungstenite::Error>
+where
+    S: Sink<Message, Error = tungstenite::Error> + Unpin,
+{
+    tracing::debug!("[SEND] {}", crate::logging::message(msg));
+    crate::bugreport::record_sent(msg);
+    match serde_json::to_string(msg) {
+        Ok(json) => write.send(Message::Text(json)).await,
+        Err(_) => Ok(()),
+    }
+}
+
+/// Where to reconnect to and with which session, until the client closes
+struct Reconnection {
+    ws_url: String,
+    token: String,
+    close_rx: mpsc::UnboundedReceiver<()>,
+}
+
+impl Reconnection {
+    /// Open a new connection with exponential backoff, None if the client closed meanwhile or every attempt failed
+    async fn open(&mut self) -> Option<Stream> {
+        for attempt in 1..=RECONNECT_ATTEMPTS {
+            set_connection(Connection::Reconnecting(attempt));
+            tokio::select! {
+                _ = tokio::time::sleep(reconnect_delay(attempt)) => {}
+                Some(_) = self.close_rx.recv() => return None,
+            }
+            match open(&self.ws_url, &self.token).await {
+                Ok(stream) => return Some(stream),
+                Err(e) => tracing::warn!("Reconnection attempt {attempt} failed: {e}"),
+            }
+        }
+        set_connection(Connection::Lost);
+        None
+    }
+}
+
+/// Doubling wait before each attempt, with some jitter so clients dropped together do not all come back together
+fn reconnect_delay(attempt: u32) -> Duration {
+    let delay = RECONNECT_BASE_DELAY.saturating_mul(1 << (attempt - 1).min(16)).min(RECONNECT_MAX_DELAY);
+    delay + delay.mul_f64(rand::thread_rng().gen_range(0.0..0.25))
+}
+
+/// Keep a connection to the server for as long as the client is open, reconnecting and resuming the match when it drops
+async fn run(
+    mut stream: Stream,
+    mut reconnection: Reconnection,
+    mut rx: mpsc::UnboundedReceiver<ClientMessage>,
+    inbox: Inbox,
+    connected: Arc<RwLock<bool>>,
+    mut plugin: Option<Plugin>,
+    mut rejoin: Option<RejoinCache>,
+) {
+    let mut unsent = None;
+    loop {
+        let ended = carry(stream, unsent.take(), &mut rx, &mut reconnection.close_rx, &inbox, &mut plugin, &mut rejoin).await;
+        *connected.write().await = false;
+        match ended {
+            Ended::Closed => return,
+            Ended::Rejected => {
+                set_connection(Connection::Lost);
+                return;
+            }
+            Ended::Dropped(msg) => unsent = msg,
+        }
+
+        let Some(mut reopened) = reconnection.open().await else {
+            return;
+        };
+        if inbox.in_match().await {
+            let _ = send_message(&mut reopened, &ClientMessage::ResumeMatch).await;
+        }
+        stream = reopened;
+        *connected.write().await = true;
+        set_connection(Connection::Up);
+    }
+}
+
+/// Carry messages both ways until the connection ends, starting with one the previous connection could not send
+async fn carry(
+    stream: Stream,
+    unsent: Option<ClientMessage>,
+    rx: &mut mpsc::UnboundedReceiver<ClientMessage>,
+    close_rx: &mut mpsc::UnboundedReceiver<()>,
+    inbox: &Inbox,
+    plugin: &mut Option<Plugin>,
+    rejoin: &mut Option<RejoinCache>,
+) -> Ended {
+    let (mut write, mut read) = stream.split();
+    if let Some(msg) = unsent {
+        if send_message(&mut write, &msg).await.is_err() {
+            return Ended::Dropped(Some(msg));
+        }
+    }
+
+    loop {
+        tokio::select! {
+            msg = rx.recv() => {
+                let Some(msg) = msg else {
+                    return Ended::Closed;
+                };
+                if send_message(&mut write, &msg).await.is_err() {
+                    tracing::warn!("WebSocket send failed - connection may be lost");
+                    return Ended::Dropped(Some(msg));
+                }
+            }
+            Some(_) = close_rx.recv() => {
+                tracing::info!("Closing WebSocket connection");
+                let _ = write.send(Message::Close(None)).await;
+                let _ = write.close().await;
+                return Ended::Closed;
+            }
+            msg = read.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    if let Some(ServerMessage::AuthFailed { .. }) = inbox.receive(&text, plugin, rejoin).await {
+                        return Ended::Rejected;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => {
+                    tracing::info!("WebSocket connection closed by server");
+                    return Ended::Dropped(None);
+                }
+                Some(Err(e)) => {
+                    tracing::warn!("WebSocket error: {e}");
+                    return Ended::Dropped(None);
+                }
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+}
+
+/// The opponent came back to a match we are up to date with, so it goes on from the state we have
+fn resume_from_current(message: ServerMessage, current: Option<&Match>) -> ServerMessage {
+    let ServerMessage::PlayerReconnected { match_id, seq, .. } = message else {
+        return message;
+    };
+    match current.filter(|match_data| match_data.id == match_id) {
+        Some(match_data) => {
+            let mut match_data = match_data.clone();
+            let _ = match_data.transition_to(MatchStatus::Active);
+            match_data.seq = seq;
+            ServerMessage::GameStateUpdate { match_data }
+        }
+        None => message,
+    }
+}
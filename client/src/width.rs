@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use unicode_width::UnicodeWidthChar;
+
+static WIDE_GLYPHS: AtomicBool = AtomicBool::new(false);
+
+/// Count symbols (chess pieces, box drawing, blocks) as two columns,
+/// for terminal fonts and themes that draw them as wide as emoji
+pub fn set_wide_glyphs(wide: bool) {
+    WIDE_GLYPHS.store(wide, Ordering::Relaxed);
+}
+
+/// Columns `text` takes on the terminal
+pub fn display_width(text: &str) -> usize {
+    width(text, WIDE_GLYPHS.load(Ordering::Relaxed))
+}
+
+fn width(text: &str, wide_glyphs: bool) -> usize {
+    text.chars()
+        .map(|c| match c.width().unwrap_or(0) {
+            1 if wide_glyphs && !c.is_ascii() => 2,
+            columns => columns,
+        })
+        .sum()
+}
+
+/// `text` followed by spaces up to `columns`
+pub fn pad_right(text: &str, columns: usize) -> String {
+    let fill = columns.saturating_sub(display_width(text));
+    format!("{text}{}", " ".repeat(fill))
+}
+
+/// `text` preceded by spaces up to `columns`
+pub fn pad_left(text: &str, columns: usize) -> String {
+    let fill = columns.saturating_sub(display_width(text));
+    format!("{}{text}", " ".repeat(fill))
+}
+
+/// `text` in the middle of `columns`, leaning left when the space is uneven
+pub fn center(text: &str, columns: usize) -> String {
+    let fill = columns.saturating_sub(display_width(text));
+    format!("{}{text}{}", " ".repeat(fill / 2), " ".repeat(fill - fill / 2))
+}
+
+/// The start of `text` that fits in `columns`, never splitting a wide glyph
+pub fn truncate(text: &str, columns: usize) -> String {
+    let mut truncated = String::new();
+    for c in text.chars() {
+        truncated.push(c);
+        if display_width(&truncated) > columns {
+            truncated.pop();
+            break;
+        }
+    }
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::briscola::{card_view, mini_card};
+    use crate::games::chess::get_piece_symbol;
+    use crate::ui::LOGO;
+    use battld_common::games::briscola::{Card, Rank, Suit};
+    use battld_common::games::chess::{ChessPiece, ChessPieceState, Player};
+    use std::sync::Mutex;
+
+    /// Tests switching `WIDE_GLYPHS` must not overlap
+    static WIDE_GLYPHS_LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn test_chess_pieces() {
+        let pieces = [ChessPiece::Pawn, ChessPiece::Rook, ChessPiece::Knight, ChessPiece::Bishop, ChessPiece::Queen, ChessPiece::King];
+        for player in [Player::White, Player::Black] {
+            for piece in pieces {
+                let piece = ChessPieceState { piece, player };
+                let symbol = get_piece_symbol(&piece);
+                assert_eq!(width(symbol, false), 1, "{symbol}");
+                assert_eq!(width(symbol, true), 2, "{symbol}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_suit_glyphs_keep_cards_aligned() {
+        let _lock = WIDE_GLYPHS_LOCK.lock().unwrap();
+        let ranks = [
+            Rank::Two, Rank::Four, Rank::Five, Rank::Six, Rank::Seven,
+            Rank::Jack, Rank::Knight, Rank::King, Rank::Three, Rank::Ace,
+        ];
+        for (wide_glyphs, card_width) in [(false, 9), (true, 18)] {
+            set_wide_glyphs(wide_glyphs);
+            for suit in [Suit::Bastoni, Suit::Coppe, Suit::Denari, Suit::Spade] {
+                for rank in ranks {
+                    for line in card_view(suit, rank) {
+                        assert_eq!(display_width(&line), card_width, "{line}");
+                    }
+                }
+            }
+        }
+        set_wide_glyphs(false);
+    }
+
+    #[test]
+    fn test_mini_cards_take_two_columns() {
+        for suit in [Suit::Bastoni, Suit::Coppe, Suit::Denari, Suit::Spade] {
+            for rank in [Rank::Ace, Rank::Seven, Rank::Knight, Rank::King] {
+                let card = mini_card(&Card { suit, rank });
+                assert_eq!(width(&card, false), 2, "{card}");
+                assert_eq!(width(&card, true), 3, "{card}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_logo_lines_have_the_same_width() {
+        for wide_glyphs in [false, true] {
+            let widths: Vec<usize> = LOGO.iter().map(|line| width(line, wide_glyphs)).collect();
+            assert!(widths.iter().all(|w| *w == widths[0]), "{widths:?}");
+        }
+        assert_eq!(width(LOGO[0], false), 24);
+        assert_eq!(width(LOGO[0], true), 48);
+    }
+
+    #[test]
+    fn test_padding() {
+        let _lock = WIDE_GLYPHS_LOCK.lock().unwrap();
+        assert_eq!(pad_right("ab", 4), "ab  ");
+        assert_eq!(pad_left("ab", 4), "  ab");
+        assert_eq!(center("ab", 5), " ab  ");
+        assert_eq!(pad_right("abcdef", 4), "abcdef");
+        assert_eq!(pad_right("名前", 6), "名前  ");
+        assert_eq!(truncate("名前です", 5), "名前");
+    }
+}
@@ -1,5 +1,7 @@
+use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
-use crate::games::{game_type::GameType, matches::{Match, MatchEndReason}};
+use crate::games::{game_type::GameType, matches::{Match, MatchEndReason, MatchOutcome}, players::Seat, rock_paper_scissors::RockPaperScissorsMove};
+use crate::errors::{ErrorCode, ErrorParams, Language};
 use crate::player::Player;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -23,47 +25,307 @@ pub enum ClientMessage {
     #[serde(rename = "authenticate")]
     Authenticate { token: String },
     #[serde(rename = "join_matchmaking")]
-    JoinMatchmaking { game_type: GameType },
+    JoinMatchmaking {
+        game_type: GameType,
+        #[serde(default)]
+        options: serde_json::Value,
+        /// Play a best-of-N series against the same opponent instead of a single match
+        #[serde(default)]
+        series_length: Option<u32>,
+    },
+    /// Leave the matchmaking queue before an opponent is found, answered with `MatchmakingCancelled`
+    #[serde(rename = "leave_matchmaking")]
+    LeaveMatchmaking,
     #[serde(rename = "resume_match")]
     ResumeMatch,
     #[serde(rename = "make_move")]
     MakeMove { move_data: serde_json::Value },
     #[serde(rename = "ping")]
     Ping,
+    #[serde(rename = "challenge_player")]
+    ChallengePlayer {
+        player_id: i64,
+        game_type: GameType,
+        #[serde(default)]
+        options: serde_json::Value,
+    },
+    #[serde(rename = "respond_to_challenge")]
+    RespondToChallenge { challenge_id: i64, accept: bool },
+    #[serde(rename = "invite_to_party")]
+    InviteToParty { player_id: i64 },
+    #[serde(rename = "respond_to_party_invite")]
+    RespondToPartyInvite { inviter_id: i64, accept: bool },
+    #[serde(rename = "leave_party")]
+    LeaveParty,
+    /// Heartbeat sent while the move prompt is open
+    #[serde(rename = "thinking")]
+    Thinking,
+    /// Ask for the latest state of a match, answered with a `GameStateUpdate`
+    #[serde(rename = "request_state")]
+    RequestState { match_id: i64 },
+    /// Answer to a `ReadyCheck`
+    #[serde(rename = "accept_match")]
+    AcceptMatch { match_id: i64, accept: bool },
+    /// Give up the match being played, the opponent wins
+    #[serde(rename = "resign")]
+    Resign,
+    /// Propose a draw in the match being played, standing until the opponent accepts or moves
+    #[serde(rename = "offer_draw")]
+    OfferDraw,
+    /// Accept the draw the opponent offered
+    #[serde(rename = "accept_draw")]
+    AcceptDraw,
+    /// Play a finished match again against the same opponent, who has to ask for it too
+    #[serde(rename = "request_rematch")]
+    RequestRematch { match_id: i64 },
+    /// Play as a guest without authenticating, in a match that is never saved
+    #[serde(rename = "join_quick_play")]
+    JoinQuickPlay { game_type: GameType },
+    /// Register the guest of this connection with their own key, keeping the quick play matches they finished
+    /// Without a name the guest name is kept, the new player signs in on a new connection
+    #[serde(rename = "upgrade_guest")]
+    UpgradeGuest {
+        #[serde(default)]
+        name: Option<String>,
+        public_key_hint: String,
+        public_key: String,
+    },
+    /// Be paired again and again in an arena until leaving it or it ends
+    #[serde(rename = "join_arena")]
+    JoinArena { arena_id: i64 },
+    /// Stop being paired, the match being played still counts
+    #[serde(rename = "leave_arena")]
+    LeaveArena,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(tag = "type")]
 pub enum ServerMessage {
     #[serde(rename = "auth_success")]
-    AuthSuccess { player_id: i64 },
+    AuthSuccess {
+        player_id: i64,
+        /// Message of the day, set by the server operator
+        #[serde(default)]
+        motd: Option<String>,
+    },
 
     #[serde(rename = "auth_failed")]
     AuthFailed { reason: String },
 
+    /// Answer to the first `JoinQuickPlay`, the guest id lasts as long as the connection
+    #[serde(rename = "guest_session")]
+    GuestSession { player_id: i64 },
+
+    /// Answer to `UpgradeGuest`, with how many finished matches were stored for the new player
+    #[serde(rename = "guest_upgraded")]
+    GuestUpgraded { player: Player, matches: usize },
+
     #[serde(rename = "waiting_for_opponent")]
     WaitingForOpponent,
 
+    /// The player is out of the matchmaking queue after `LeaveMatchmaking`
+    #[serde(rename = "matchmaking_cancelled")]
+    MatchmakingCancelled,
+
+    /// `JoinMatchmaking` was turned down, the player is not queued
+    #[serde(rename = "matchmaking_rejected")]
+    MatchmakingRejected { reason: MatchmakingRejection },
+
     #[serde(rename = "match_found")]
     MatchFound { match_data: Match },
 
     #[serde(rename = "game_state_update")]
     GameStateUpdate { match_data: Match },
 
+    /// Sent alongside the state update of every move, `seq` is the match sequence after the move
+    #[serde(rename = "move_applied")]
+    MoveApplied { match_id: i64, seq: i64, description: String },
+
+    /// Both choices of a finished Rock-Paper-Scissors round, `round` counting from 0 and `winner` None on a draw,
+    /// the state update for the next round follows after `shown_for_ms`
+    #[serde(rename = "round_result")]
+    RoundResult {
+        match_id: i64,
+        round: usize,
+        moves: (RockPaperScissorsMove, RockPaperScissorsMove),
+        winner: Option<i64>,
+        shown_for_ms: u64,
+    },
+
+    /// Follows every state a player gets of a match in play, each move in the shape `MakeMove` takes,
+    /// empty while it is not their turn
+    #[serde(rename = "legal_moves")]
+    LegalMoves { match_id: i64, moves: Vec<serde_json::Value> },
+
+    /// `seq` is the match sequence once it was paused for the player to come back
     #[serde(rename = "player_disconnected")]
-    PlayerDisconnected { player_id: i64 },
+    PlayerDisconnected {
+        player_id: i64,
+        #[serde(default)]
+        match_id: i64,
+        #[serde(default)]
+        seq: i64,
+    },
+
+    /// The player came back and the match goes on from the state last sent, now at `seq`
+    #[serde(rename = "player_reconnected")]
+    PlayerReconnected { player_id: i64, match_id: i64, seq: i64 },
 
     #[serde(rename = "resumable_match")]
-    ResumableMatch { match_data: Match },
+    ResumableMatch {
+        match_data: Match,
+        /// When the last move was played, UTC seconds
+        #[serde(default)]
+        last_move_at: Option<i64>,
+    },
 
+    /// `message` is the English text, `code` and `params` let clients react to it or show it in their own language
     #[serde(rename = "error")]
-    Error { message: String },
+    Error {
+        #[serde(default)]
+        code: ErrorCode,
+        #[serde(default)]
+        params: ErrorParams,
+        message: String,
+    },
 
     #[serde(rename = "match_ended")]
-    MatchEnded { reason: MatchEndReason },
+    MatchEnded {
+        reason: MatchEndReason,
+        /// Set when the game itself came to an end, rather than a disconnection or forfeit
+        #[serde(default)]
+        summary: Option<MatchSummary>,
+    },
 
     #[serde(rename = "pong")]
     Pong,
+
+    /// The opponent has the move prompt open, stale after a few seconds without another one
+    #[serde(rename = "opponent_thinking")]
+    OpponentThinking { match_id: i64 },
+
+    /// Answer to a move for a match that ended meanwhile, `outcome` is its final result
+    #[serde(rename = "match_already_finished")]
+    MatchAlreadyFinished { match_id: i64, outcome: Option<MatchOutcome> },
+
+    /// The match has been waiting on your move for `idle_secs`, `forfeit_in` is set when it will be forfeited
+    #[serde(rename = "turn_reminder")]
+    TurnReminder { match_id: i64, idle_secs: u64, forfeit_in: Option<u64> },
+
+    /// The opponent has not moved for `idle_secs` and was reminded to
+    #[serde(rename = "opponent_idle")]
+    OpponentIdle { match_id: i64, idle_secs: u64 },
+
+    /// Follows every state of a match with a turn time limit, `players` have `expires_in` seconds left to move
+    #[serde(rename = "turn_timer")]
+    TurnTimer { match_id: i64, players: Vec<i64>, expires_in: u64 },
+
+    /// `players` ran out of time, they forfeit the match or, in Rock-Paper-Scissors, give up the round
+    #[serde(rename = "turn_timeout")]
+    TurnTimeout { match_id: i64, players: Vec<i64> },
+
+    /// A finished match moved the player up or down the ladder, None is unranked
+    #[serde(rename = "tier_changed")]
+    TierChanged { previous_tier: Option<String>, tier: Option<String>, promoted: bool },
+
+    /// A finished match changed the player's score, `new_rank` is their place on the leaderboard, None while off it
+    #[serde(rename = "stats_delta")]
+    StatsDelta { score_change: i64, new_score: i64, new_rank: Option<i64> },
+
+    /// An opponent was found, the match starts once both players accept within `expires_in` seconds
+    #[serde(rename = "ready_check")]
+    ReadyCheck { match_id: i64, expires_in: u64 },
+
+    /// The ready check failed, `requeued` players are back in matchmaking, the others were dropped from it
+    #[serde(rename = "ready_check_failed")]
+    ReadyCheckFailed { requeued: bool },
+
+    /// Sent to the challenger, `delivered` is false if the opponent is offline
+    #[serde(rename = "challenge_sent")]
+    ChallengeSent { challenge: MatchChallenge, delivered: bool },
+
+    #[serde(rename = "challenge_received")]
+    ChallengeReceived { challenge: MatchChallenge },
+
+    #[serde(rename = "challenge_declined")]
+    ChallengeDeclined { challenge_id: i64 },
+
+    #[serde(rename = "challenge_expired")]
+    ChallengeExpired { challenge_id: i64 },
+
+    /// The server is at capacity, try again in `retry_after` seconds
+    #[serde(rename = "server_busy")]
+    ServerBusy { retry_after: u64 },
+
+    #[serde(rename = "party_invite")]
+    PartyInvite { from: PartyMember },
+
+    #[serde(rename = "party_invite_declined")]
+    PartyInviteDeclined { player_id: i64 },
+
+    #[serde(rename = "party_formed")]
+    PartyFormed { partner: PartyMember },
+
+    /// Sent to both members, `player_id` is the one who left
+    #[serde(rename = "party_disbanded")]
+    PartyDisbanded { player_id: i64 },
+
+    /// The partner queued and is waiting for you to join the same game
+    #[serde(rename = "party_queued")]
+    PartyQueued {
+        game_type: GameType,
+        #[serde(default)]
+        options: serde_json::Value,
+    },
+
+    /// Waiting for an opponent in the arena, matches start with `MatchFound`
+    #[serde(rename = "arena_joined")]
+    ArenaJoined { arena: ArenaInfo },
+
+    /// Points earned with the match just finished, `points` is the new total
+    #[serde(rename = "arena_scored")]
+    ArenaScored { arena_id: i64, earned: i64, points: i64, streak: i64 },
+
+    /// The arena is over, with its final standings
+    #[serde(rename = "arena_ended")]
+    ArenaEnded { arena_id: i64, standings: Vec<ArenaStanding> },
+
+    /// An arena starts soon, sent once to everyone connected and not in an arena, who may join it from now on
+    #[serde(rename = "arena_announced")]
+    ArenaAnnounced { arena: ArenaInfo },
+
+    /// Live standings of an arena, sent to its members after each of its matches
+    #[serde(rename = "arena_standings")]
+    ArenaStandings { arena_id: i64, standings: Vec<ArenaStanding> },
+
+    /// `player_id` asked to play `match_id` again, sent to both players
+    #[serde(rename = "rematch_offered")]
+    RematchOffered { match_id: i64, player_id: i64 },
+
+    /// Both players asked for a rematch, `new_match_id` follows with `MatchFound`
+    #[serde(rename = "rematch_accepted")]
+    RematchAccepted { match_id: i64, new_match_id: i64 },
+
+    /// Score of the series `series.match_id` belongs to, sent as each of its matches starts and ends
+    #[serde(rename = "series_update")]
+    SeriesUpdate { series: SeriesInfo },
+}
+
+impl ServerMessage {
+    /// An error with its English text filled in from the code and params
+    pub fn error(code: ErrorCode, params: &[(&str, &str)]) -> Self {
+        let params: ErrorParams = params.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        ServerMessage::Error { code, message: code.message(&params), params }
+    }
+
+    /// The text of an error in the language, or the English one for codes this build doesn't know
+    pub fn error_text(&self, language: Language) -> Option<String> {
+        match self {
+            ServerMessage::Error { code: ErrorCode::Unknown, message, .. } => Some(message.clone()),
+            ServerMessage::Error { code, params, .. } => Some(code.text(language, params)),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -75,6 +337,41 @@ pub struct PlayerStats {
     pub dropped: i64,
     pub total: i64,
     pub score: i64,
+    pub rating: i64,
+    /// Too few rated games for the rating to be reliable
+    pub provisional: bool,
+    /// Results as player 1, X in Tic-Tac-Toe and White in chess
+    pub first_seat: SeatStats,
+    pub second_seat: SeatStats,
+    /// Rating in each game played, most played first
+    #[serde(default)]
+    pub game_ratings: Vec<GameRating>,
+    /// Ladder tier of the overall rating, None while unranked
+    #[serde(default)]
+    pub tier: Option<String>,
+}
+
+/// Rating of a player in a single game type, apart from the overall `rating`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GameRating {
+    pub game_type: GameType,
+    pub rating: i64,
+    pub provisional: bool,
+    pub games: i64,
+}
+
+impl GameRating {
+    /// Rating with a "?" when provisional
+    pub fn rating_label(&self) -> String {
+        rating_label(self.rating, self.provisional)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SeatStats {
+    pub won: i64,
+    pub lost: i64,
+    pub draw: i64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -83,12 +380,214 @@ pub struct LeaderboardEntry {
     pub player_name: String,
     pub rank: i64,
     pub score: i64,
+    pub rating: i64,
+    /// Too few rated games for the rating to be reliable
+    pub provisional: bool,
+    /// Ladder tier of the overall rating, None while unranked
+    #[serde(default)]
+    pub tier: Option<String>,
+}
+
+impl LeaderboardEntry {
+    /// Rating with a "?" when provisional
+    pub fn rating_label(&self) -> String {
+        rating_label(self.rating, self.provisional)
+    }
+}
+
+impl PlayerStats {
+    /// Rating with a "?" when provisional
+    pub fn rating_label(&self) -> String {
+        rating_label(self.rating, self.provisional)
+    }
+}
+
+fn rating_label(rating: i64, provisional: bool) -> String {
+    if provisional {
+        format!("{rating}?")
+    } else {
+        rating.to_string()
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct LeaderboardResponse {
     pub entries: Vec<LeaderboardEntry>,
     pub total_count: i64,
+    /// Season the standings are from, None while the server runs no seasons
+    #[serde(default)]
+    pub season: Option<SeasonInfo>,
+}
+
+/// How long a quest runs before it is replaced
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum QuestPeriod {
+    Daily,
+    Weekly,
+}
+
+/// A goal to reach before `ends_at`, as listed by `GET /quests`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Quest {
+    pub id: String,
+    pub description: String,
+    pub period: QuestPeriod,
+    pub progress: u32,
+    pub target: u32,
+    pub ends_at: i64, // unix seconds
+}
+
+impl Quest {
+    pub fn is_complete(&self) -> bool {
+        self.progress >= self.target
+    }
+}
+
+/// A stretch of time scores are kept for, numbered from 1
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SeasonInfo {
+    pub id: i64,
+    pub starts_at: i64, // unix seconds
+    pub ends_at: i64, // unix seconds
+    /// Over, its standings are final
+    pub archived: bool,
+}
+
+/// A game and the options it accepts, as listed by `GET /games`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GameInfo {
+    pub game_type: GameType,
+    pub name: String,
+    pub options_schema: serde_json::Value,
+}
+
+/// A match currently being played, as listed on the live status page
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LiveMatch {
+    pub match_id: i64,
+    pub game_type: GameType,
+    pub player1_id: i64,
+    pub player1_name: String,
+    pub player2_id: i64,
+    pub player2_name: String,
+}
+
+/// Body of `POST /matches/:id/moves`, same payload as `ClientMessage::MakeMove`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MakeMoveRequest {
+    pub move_data: serde_json::Value,
+}
+
+/// A match as seen by one of its players, for clients polling over HTTP
+/// `seq` grows with every change, pass it back as `since_seq` to only get news
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MatchStateResponse {
+    pub seq: i64,
+    pub match_data: Match,
+}
+
+/// Answer to `POST /matches/:id/simulate`: the match as the caller would see it after the move, which is not saved
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimulatedMoveResponse {
+    pub match_data: Match,
+    pub description: String,
+}
+
+/// A direct invitation to play, pending until answered or expired
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MatchChallenge {
+    pub id: i64,
+    pub challenger_id: i64,
+    pub challenger_name: String,
+    pub challenged_id: i64,
+    pub challenged_name: String,
+    pub game_type: GameType,
+    #[serde(default)]
+    pub options: serde_json::Value,
+    pub expires_at: i64, // unix seconds
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PartyMember {
+    pub player_id: i64,
+    pub name: String,
+}
+
+/// Current party and pending invites of the authenticated player
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PartyStatus {
+    pub partner: Option<PartyMember>,
+    pub invites: Vec<PartyMember>,
+}
+
+/// A timed event where players are paired continuously in one game, as listed by `GET /arenas`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ArenaInfo {
+    pub id: i64,
+    pub game_type: GameType,
+    pub starts_at: i64, // unix seconds
+    pub ends_at: i64, // unix seconds
+    pub finished: bool,
+}
+
+/// A player's place in an arena
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ArenaStanding {
+    pub rank: i64,
+    pub player_id: i64,
+    pub player_name: String,
+    pub points: i64,
+    pub games: i64,
+    pub wins: i64,
+    /// Wins in a row, worth double from the third one
+    pub streak: i64,
+}
+
+/// An arena with its standings, live while it runs and final once it ended
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ArenaDetails {
+    pub arena: ArenaInfo,
+    pub standings: Vec<ArenaStanding>,
+}
+
+/// A best-of-N series between two players, played one match after the other with the seats swapped
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SeriesInfo {
+    pub id: i64,
+    pub length: u32,
+    /// Which game of the series `match_id` is, starting from 1
+    pub game: u32,
+    pub match_id: i64,
+    pub player1_id: i64,
+    pub player2_id: i64,
+    pub player1_wins: u32,
+    pub player2_wins: u32,
+    pub draws: u32,
+    pub finished: bool,
+    /// None while running, or if the series ended level
+    pub winner_id: Option<i64>,
+}
+
+impl SeriesInfo {
+    /// Wins of `player_id` and of their opponent
+    pub fn score_for(&self, player_id: i64) -> (u32, u32) {
+        if player_id == self.player1_id {
+            (self.player1_wins, self.player2_wins)
+        } else {
+            (self.player2_wins, self.player1_wins)
+        }
+    }
+}
+
+/// Body of `POST /admin/arenas`, the arena starts right away unless `starts_at` is given
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewArena {
+    pub game_type: GameType,
+    #[serde(default)]
+    pub starts_at: Option<i64>,
+    #[serde(default)]
+    pub duration_secs: Option<i64>,
 }
 
 // New auth flow types
@@ -123,3 +622,446 @@ pub struct AuthResponse {
 pub struct LogoutRequest {
     pub session_token: String,
 }
+
+/// Body of `POST /auth/recover`, with the public key derived from a recovery phrase
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecoveryRequest {
+    pub public_key: String,
+}
+
+/// The player holding the key, and a nonce to sign and send to `/auth/verify` as after `/auth/challenge`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecoveryResponse {
+    pub player_id: i64,
+    pub nonce: String,
+    pub expires_in: u64, // seconds
+}
+
+/// Body of `POST /player/key`, replacing the player's key with one they prove to hold
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RebindKeyRequest {
+    pub public_key_hint: String,
+    pub public_key: String,
+    /// `RebindKeyRequest::signed_text` signed with the new key, base64 encoded
+    pub signature: String,
+}
+
+impl RebindKeyRequest {
+    pub fn signed_text(player_id: i64) -> String {
+        format!("battld-rebind-key:{player_id}")
+    }
+}
+
+/// Who may watch replays of a player's matches, a match is shown only if both players allow it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayPrivacy {
+    #[default]
+    Public,
+    Friends,
+    Private,
+}
+
+impl ReplayPrivacy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReplayPrivacy::Public => "public",
+            ReplayPrivacy::Friends => "friends",
+            ReplayPrivacy::Private => "private",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "public" => Some(ReplayPrivacy::Public),
+            "friends" => Some(ReplayPrivacy::Friends),
+            "private" => Some(ReplayPrivacy::Private),
+            _ => None,
+        }
+    }
+}
+
+/// Body of `POST /replays/privacy`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplayPrivacyRequest {
+    pub privacy: ReplayPrivacy,
+}
+
+/// A finished match of the authenticated player, shareable at `/replay/:token`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplayLink {
+    pub match_id: i64,
+    pub game_type: GameType,
+    pub token: String,
+    /// Where the player sat, see `GameType::seat_name`
+    pub seat: Seat,
+    /// UTC seconds
+    #[serde(default)]
+    pub finished_at: Option<i64>,
+}
+
+/// Replay privacy and recent replays of the authenticated player
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplaySettings {
+    pub privacy: ReplayPrivacy,
+    pub recent: Vec<ReplayLink>,
+}
+
+/// How dates are written, whatever the time zone
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DateStyle {
+    /// 2025-10-31 14:05
+    #[default]
+    Iso,
+    /// 10/31/2025 2:05 PM
+    Us,
+    /// 31/10/2025 14:05
+    Eu,
+}
+
+impl DateStyle {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DateStyle::Iso => "iso",
+            DateStyle::Us => "us",
+            DateStyle::Eu => "eu",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "iso" => Some(DateStyle::Iso),
+            "us" => Some(DateStyle::Us),
+            "eu" => Some(DateStyle::Eu),
+            _ => None,
+        }
+    }
+}
+
+/// How a player wants times shown, they are stored and sent as UTC regardless
+/// Body of `POST /player/time-preferences` too
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TimePreferences {
+    /// Offset from UTC of the player's time zone
+    pub utc_offset_minutes: i32,
+    pub date_style: DateStyle,
+}
+
+/// Why `JoinMatchmaking` was turned down
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MatchmakingRejection {
+    /// The player has to resume or finish this match first
+    AlreadyInMatch { match_id: i64, game_type: GameType },
+    /// A match found earlier is still waiting to be accepted or declined
+    ReadyCheckPending,
+    /// Too many players or matches, joining again after `retry_after` seconds may work
+    ServerFull { retry_after: u64 },
+    /// No new matches while the server is in maintenance
+    Maintenance,
+    /// The server does not run the game asked for, such as one added by a newer version
+    UnsupportedGame,
+}
+
+/// Where a notification reaches the player, each can keep going through quiet hours
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationChannel {
+    /// `turn_reminder` messages sent by the server
+    TurnReminders,
+    /// `opponent_idle` messages sent by the server
+    OpponentIdle,
+    /// Events the client hands to its plugin
+    Plugin,
+}
+
+impl NotificationChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationChannel::TurnReminders => "turn_reminders",
+            NotificationChannel::OpponentIdle => "opponent_idle",
+            NotificationChannel::Plugin => "plugin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "turn_reminders" => Some(NotificationChannel::TurnReminders),
+            "opponent_idle" => Some(NotificationChannel::OpponentIdle),
+            "plugin" => Some(NotificationChannel::Plugin),
+            _ => None,
+        }
+    }
+}
+
+/// A daily window without notifications in the player's time zone, past midnight when it ends before it starts
+/// Body of `POST /player/quiet-hours` too, null turns them off
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct QuietHours {
+    /// Minutes past local midnight
+    pub start_minute: u16,
+    pub end_minute: u16,
+    /// Channels that keep notifying through the window
+    #[serde(default)]
+    pub except: Vec<NotificationChannel>,
+}
+
+impl QuietHours {
+    pub const MINUTES_PER_DAY: u16 = 24 * 60;
+
+    pub fn is_valid(&self) -> bool {
+        self.start_minute < Self::MINUTES_PER_DAY && self.end_minute < Self::MINUTES_PER_DAY && self.start_minute != self.end_minute
+    }
+
+    /// Whether `channel` is held back at `now` for a player `utc_offset_minutes` away from UTC
+    pub fn silences(&self, channel: NotificationChannel, now: i64, utc_offset_minutes: i32) -> bool {
+        if self.except.contains(&channel) {
+            return false;
+        }
+        let minute = (now.div_euclid(60) + utc_offset_minutes as i64).rem_euclid(Self::MINUTES_PER_DAY as i64) as u16;
+        if self.start_minute < self.end_minute {
+            (self.start_minute..self.end_minute).contains(&minute)
+        } else {
+            minute >= self.start_minute || minute < self.end_minute
+        }
+    }
+}
+
+/// One state of a replayed match, `player_id` is who moved into it, none for the initial state
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplayFrame {
+    pub player_id: Option<i64>,
+    pub game_state: serde_json::Value,
+    /// The move that led to this state, None for the first frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub move_data: Option<serde_json::Value>,
+    /// UTC seconds, None for matches recorded before moves were timed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub played_at: Option<i64>,
+}
+
+/// A finished match with every state it went through, redacted for spectators
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplayResponse {
+    pub match_data: Match,
+    pub frames: Vec<ReplayFrame>,
+    #[serde(default)]
+    pub summary: Option<MatchSummary>,
+    /// Engine evaluation of each chess move, empty until the match was analyzed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub analysis: Vec<MoveAnnotation>,
+}
+
+/// How bad a move was, by the evaluation it gave away
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveJudgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveJudgement {
+    /// The mark annotated games put after such a move
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            MoveJudgement::Inaccuracy => "?!",
+            MoveJudgement::Mistake => "?",
+            MoveJudgement::Blunder => "??",
+        }
+    }
+}
+
+/// A chess engine's view of one move of a match
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MoveAnnotation {
+    /// Index of the move in the match, 0 for White's first
+    pub ply: usize,
+    /// Evaluation after the move in centipawns, positive when White is better
+    pub eval_cp: i64,
+    /// Moves to mate when the engine sees one, positive when White mates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mate_in: Option<i64>,
+    /// Centipawns the move gave away compared to the engine's choice
+    pub loss_cp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub judgement: Option<MoveJudgement>,
+    /// What the engine would have played instead, in UCI notation such as `e2e4`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub best_move: Option<String>,
+}
+
+/// How a finished match went, in the words of its game, such as "Won 78–42 on points"
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MatchSummary {
+    pub outcome: Option<MatchOutcome>,
+    pub text: String,
+}
+
+/// What a database maintenance run did, as returned by `POST /admin/maintenance`
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MaintenanceReport {
+    pub compacted_matches: usize,
+    pub vacuumed: bool,
+    /// Storage key of the database backup taken
+    #[serde(default)]
+    pub backup: Option<String>,
+    /// Stored replays, exports and backups deleted by their lifecycle rules
+    #[serde(default)]
+    pub expired_objects: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditSeverity {
+    Low,
+    Medium,
+    High,
+}
+
+impl AuditSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditSeverity::Low => "low",
+            AuditSeverity::Medium => "medium",
+            AuditSeverity::High => "high",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "low" => Some(AuditSeverity::Low),
+            "medium" => Some(AuditSeverity::Medium),
+            "high" => Some(AuditSeverity::High),
+            _ => None,
+        }
+    }
+}
+
+/// A suspicious pattern between two players, as listed by `GET /admin/audit`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditFinding {
+    pub kind: String,
+    pub severity: AuditSeverity,
+    pub player1_id: i64,
+    pub player2_id: i64,
+    pub details: String,
+    pub detected_at: i64,
+}
+
+/// Keys of the settings whose changes are recorded
+pub mod setting_keys {
+    pub const NAME: &str = "name";
+    pub const UTC_OFFSET_MINUTES: &str = "utc_offset_minutes";
+    pub const DATE_STYLE: &str = "date_style";
+    pub const REPLAY_PRIVACY: &str = "replay_privacy";
+    /// Recorded as JSON, `off` when there are none
+    pub const QUIET_HOURS: &str = "quiet_hours";
+    /// Recorded as a fingerprint of the key
+    pub const PUBLIC_KEY: &str = "public_key";
+}
+
+/// A change to a player-visible setting, as listed by `GET /admin/players/:id/setting-changes`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SettingChange {
+    pub key: String,
+    pub old_value: Option<String>,
+    pub new_value: String,
+    /// The player themselves, or an admin acting for them
+    pub changed_by: i64,
+    pub changed_at: i64,
+}
+
+/// A finished match as `GET /matches/:id/export?format=json` writes it
+/// `format_version` goes up whenever a field changes meaning or goes away
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GameExport {
+    pub format_version: u32,
+    pub match_id: i64,
+    pub game_type: GameType,
+    /// In seat order
+    pub players: Vec<GameExportPlayer>,
+    pub outcome: Option<MatchOutcome>,
+    /// UTC seconds
+    pub finished_at: Option<i64>,
+    /// In the order they were played
+    pub moves: Vec<GameExportMove>,
+    /// As spectators see it, hidden cards stay hidden
+    pub final_state: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GameExportPlayer {
+    pub seat: Seat,
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GameExportMove {
+    /// Counted from 1
+    pub ply: usize,
+    pub player_id: Option<i64>,
+    /// The payload of `make_move`, in each game's own move format
+    pub move_data: serde_json::Value,
+    /// UTC seconds, None for matches recorded before moves were timed
+    pub played_at: Option<i64>,
+}
+
+/// Portable dump of an instance, written by `GET /admin/export` and `server export`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InstanceExport {
+    pub format_version: u32,
+    pub exported_at: i64,
+    pub players: Vec<ExportedPlayer>,
+    pub matches: Vec<ExportedMatch>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ExportedPlayer {
+    pub id: i64,
+    pub name: String,
+    pub public_key_hint: String,
+    pub public_key: String,
+    pub score: i64,
+    pub rating: f64,
+    pub rating_deviation: f64,
+    pub rated_games: i64,
+    pub replay_privacy: ReplayPrivacy,
+    #[serde(default)]
+    pub game_ratings: Vec<ExportedGameRating>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ExportedGameRating {
+    pub game_type: GameType,
+    pub rating: f64,
+    pub rating_deviation: f64,
+    pub rated_games: i64,
+}
+
+/// Finished match with its final state, recorded frames are not exported
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ExportedMatch {
+    pub id: i64,
+    pub player1_id: i64,
+    pub player2_id: i64,
+    pub game_type: GameType,
+    pub outcome: Option<MatchOutcome>,
+    pub game_state: serde_json::Value,
+    pub finished_at: Option<i64>,
+}
+
+/// What an import added, and the id each exported player and match has on this instance
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ImportReport {
+    /// Ids are kept as exported when importing into an empty instance
+    pub preserved_ids: bool,
+    pub players_imported: usize,
+    /// Players already registered here with the same public key
+    pub players_merged: usize,
+    pub matches_imported: usize,
+    pub player_ids: BTreeMap<i64, i64>,
+    pub match_ids: BTreeMap<i64, i64>,
+}
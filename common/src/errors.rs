@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Values filled into the `{name}` placeholders of an error text
+pub type ErrorParams = BTreeMap<String, String>;
+
+/// Every error the server reports, so clients can react to them and show them in their own language
+/// Codes are never renamed, clients built before a code existed get `Unknown` and fall back to the English text
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    NotAuthenticated,
+    GuestSignIn,
+    GuestsOnly,
+    /// `UpgradeGuest` from a connection that is not a guest's
+    NotAGuest,
+    /// The public key of a new player already belongs to someone
+    KeyInUse,
+    AlreadyInMatch,
+    MatchNotFound,
+    NoActiveMatch,
+    NoResumableMatch,
+    MatchNotActive,
+    MatchAlreadyFinished,
+    MatchUnavailable,
+    /// `reason`
+    IllegalMove,
+    NotYourTurn,
+    GameNotInProgress,
+    InvalidPlayer,
+    /// `reason`
+    InvalidOptions,
+    MatchPaused,
+    /// The match changed while the move was being applied, every retry included
+    MoveConflict,
+    NoDrawOffer,
+    MatchmakingFailed,
+    /// Nothing to leave, such as when an opponent was just found
+    NotInMatchmaking,
+    ReadyCheckPending,
+    ReadyCheckClosed,
+    OpponentUnavailable,
+    PlayerNotFound,
+    /// `name`
+    PlayerOffline,
+    /// `name`
+    PlayerBusy,
+    SelfChallenge,
+    ChallengePending,
+    ChallengeNotFound,
+    ChallengeClosed,
+    SelfInvite,
+    AlreadyInParty,
+    InviteNotFound,
+    NotInParty,
+    ArenaNotFound,
+    RematchUnavailable,
+    InvalidSeriesLength,
+    ServerError,
+    Maintenance,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+/// Languages error texts are available in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    Italian,
+}
+
+impl Language {
+    /// From a locale such as `it_IT.UTF-8` or `it`, English for anything not translated
+    pub fn from_locale(locale: &str) -> Self {
+        match locale.get(..2) {
+            Some("it") => Language::Italian,
+            _ => Language::English,
+        }
+    }
+}
+
+impl ErrorCode {
+    /// The error in English, as sent in `ServerMessage::Error::message`
+    pub fn message(&self, params: &ErrorParams) -> String {
+        self.text(Language::English, params)
+    }
+
+    /// The error in the language, with its placeholders filled from the params
+    pub fn text(&self, language: Language, params: &ErrorParams) -> String {
+        let mut text = self.template(language).to_string();
+        for (name, value) in params {
+            text = text.replace(&format!("{{{name}}}"), value);
+        }
+        text
+    }
+
+    fn template(&self, language: Language) -> &'static str {
+        match language {
+            Language::English => self.english(),
+            Language::Italian => self.italian(),
+        }
+    }
+
+    fn english(&self) -> &'static str {
+        match self {
+            ErrorCode::NotAuthenticated => "Not authenticated",
+            ErrorCode::GuestSignIn => "Guests can't sign in on the same connection",
+            ErrorCode::GuestsOnly => "Quick play is only open to guests",
+            ErrorCode::NotAGuest => "Only guests can register from here",
+            ErrorCode::KeyInUse => "This key already belongs to a player",
+            ErrorCode::AlreadyInMatch => "Already in a match",
+            ErrorCode::MatchNotFound => "Match not found",
+            ErrorCode::NoActiveMatch => "No active match found",
+            ErrorCode::NoResumableMatch => "No resumable match found",
+            ErrorCode::MatchNotActive => "Match is no longer active",
+            ErrorCode::MatchAlreadyFinished => "Match already finished",
+            ErrorCode::MatchUnavailable => "Failed to load match data",
+            ErrorCode::IllegalMove => "Illegal move: {reason}",
+            ErrorCode::NotYourTurn => "Not your turn",
+            ErrorCode::GameNotInProgress => "Game is not in progress",
+            ErrorCode::InvalidPlayer => "Invalid player",
+            ErrorCode::InvalidOptions => "Invalid game options: {reason}",
+            ErrorCode::MatchPaused => "Server error: the match could not be saved and is paused, please try again shortly",
+            ErrorCode::MoveConflict => "The match changed while your move was being played, please try again",
+            ErrorCode::NoDrawOffer => "Your opponent has not offered a draw",
+            ErrorCode::MatchmakingFailed => "Server error: could not join matchmaking, please try again",
+            ErrorCode::NotInMatchmaking => "You are not waiting for an opponent",
+            ErrorCode::ReadyCheckPending => "Accept or decline the match that was found first",
+            ErrorCode::ReadyCheckClosed => "This match is no longer waiting to be accepted",
+            ErrorCode::OpponentUnavailable => "The opponent is no longer available, please try again",
+            ErrorCode::PlayerNotFound => "Player not found",
+            ErrorCode::PlayerOffline => "{name} is offline, try again when they are back",
+            ErrorCode::PlayerBusy => "{name} is already playing or queued",
+            ErrorCode::SelfChallenge => "You can't challenge yourself",
+            ErrorCode::ChallengePending => "You already have a pending challenge for this player",
+            ErrorCode::ChallengeNotFound => "Challenge not found",
+            ErrorCode::ChallengeClosed => "Challenge is no longer open",
+            ErrorCode::SelfInvite => "You can't invite yourself",
+            ErrorCode::AlreadyInParty => "Already in a party",
+            ErrorCode::InviteNotFound => "Party invite not found",
+            ErrorCode::NotInParty => "You are not in a party",
+            ErrorCode::ArenaNotFound => "This arena is not running",
+            ErrorCode::RematchUnavailable => "This match can no longer be played again",
+            ErrorCode::InvalidSeriesLength => "A series is best of 3, 5 or 7 games",
+            ErrorCode::ServerError => "Server error, please try again",
+            ErrorCode::Maintenance => "The server is under maintenance, no new matches can start for now",
+            ErrorCode::Unknown => "Something went wrong",
+        }
+    }
+
+    fn italian(&self) -> &'static str {
+        match self {
+            ErrorCode::NotAuthenticated => "Non autenticato",
+            ErrorCode::GuestSignIn => "Gli ospiti non possono accedere dalla stessa connessione",
+            ErrorCode::GuestsOnly => "La partita rapida è aperta solo agli ospiti",
+            ErrorCode::NotAGuest => "Solo gli ospiti possono registrarsi da qui",
+            ErrorCode::KeyInUse => "Questa chiave appartiene già a un giocatore",
+            ErrorCode::AlreadyInMatch => "Sei già in una partita",
+            ErrorCode::MatchNotFound => "Partita non trovata",
+            ErrorCode::NoActiveMatch => "Nessuna partita in corso",
+            ErrorCode::NoResumableMatch => "Nessuna partita da riprendere",
+            ErrorCode::MatchNotActive => "La partita non è più in corso",
+            ErrorCode::MatchAlreadyFinished => "La partita è già finita",
+            ErrorCode::MatchUnavailable => "Impossibile caricare la partita",
+            ErrorCode::IllegalMove => "Mossa non valida: {reason}",
+            ErrorCode::NotYourTurn => "Non è il tuo turno",
+            ErrorCode::GameNotInProgress => "Il gioco non è in corso",
+            ErrorCode::InvalidPlayer => "Giocatore non valido",
+            ErrorCode::InvalidOptions => "Opzioni di gioco non valide: {reason}",
+            ErrorCode::MatchPaused => "Errore del server: la partita non è stata salvata ed è in pausa, riprova tra poco",
+            ErrorCode::MoveConflict => "La partita è cambiata mentre giocavi la mossa, riprova",
+            ErrorCode::NoDrawOffer => "Il tuo avversario non ha proposto la patta",
+            ErrorCode::MatchmakingFailed => "Errore del server: impossibile cercare un avversario, riprova",
+            ErrorCode::NotInMatchmaking => "Non stai aspettando un avversario",
+            ErrorCode::ReadyCheckPending => "Accetta o rifiuta prima la partita trovata",
+            ErrorCode::ReadyCheckClosed => "Questa partita non è più in attesa di conferma",
+            ErrorCode::OpponentUnavailable => "L'avversario non è più disponibile, riprova",
+            ErrorCode::PlayerNotFound => "Giocatore non trovato",
+            ErrorCode::PlayerOffline => "{name} non è in linea, riprova quando torna",
+            ErrorCode::PlayerBusy => "{name} sta già giocando o cercando una partita",
+            ErrorCode::SelfChallenge => "Non puoi sfidare te stesso",
+            ErrorCode::ChallengePending => "Hai già una sfida in attesa per questo giocatore",
+            ErrorCode::ChallengeNotFound => "Sfida non trovata",
+            ErrorCode::ChallengeClosed => "La sfida non è più aperta",
+            ErrorCode::SelfInvite => "Non puoi invitare te stesso",
+            ErrorCode::AlreadyInParty => "Sei già in un gruppo",
+            ErrorCode::InviteNotFound => "Invito al gruppo non trovato",
+            ErrorCode::NotInParty => "Non sei in un gruppo",
+            ErrorCode::ArenaNotFound => "Questa arena non è in corso",
+            ErrorCode::RematchUnavailable => "Questa partita non si può più rigiocare",
+            ErrorCode::InvalidSeriesLength => "Una serie si gioca al meglio di 3, 5 o 7 partite",
+            ErrorCode::ServerError => "Errore del server, riprova",
+            ErrorCode::Maintenance => "Il server è in manutenzione, per ora non si possono iniziare nuove partite",
+            ErrorCode::Unknown => "Qualcosa è andato storto",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServerMessage;
+
+    #[test]
+    fn test_error_text_in_each_language() {
+        let message = ServerMessage::error(ErrorCode::PlayerBusy, &[("name", "alice")]);
+        let ServerMessage::Error { code, message: text, .. } = &message else {
+            panic!("Expected Error, got {message:?}");
+        };
+        assert_eq!(*code, ErrorCode::PlayerBusy);
+        assert_eq!(text, "alice is already playing or queued");
+        assert_eq!(message.error_text(Language::from_locale("it_IT.UTF-8")).unwrap(), "alice sta già giocando o cercando una partita");
+        assert_eq!(Language::from_locale("C"), Language::English);
+    }
+
+    #[test]
+    fn test_unknown_codes_fall_back_to_the_english_text() {
+        let json = r#"{"type": "error", "code": "added_later", "message": "Something new"}"#;
+        let message: ServerMessage = serde_json::from_str(json).unwrap();
+        assert_eq!(message.error_text(Language::Italian).unwrap(), "Something new");
+
+        let message: ServerMessage = serde_json::from_str(r#"{"type": "error", "message": "Old server"}"#).unwrap();
+        assert!(matches!(&message, ServerMessage::Error { code: ErrorCode::Unknown, .. }));
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "server-helpers")]
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::games::players::PlayerSymbol;
@@ -38,15 +40,34 @@ pub enum CardView {
     Redacted,
 }
 
+/// Seconds each card has to be played in blitz Briscola
+pub const BLITZ_CARD_SECS: u64 = 10;
+
+/// Options selectable when queueing for Briscola
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server-helpers", derive(JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct BriscolaOptions {
+    /// Chiamata variant: no card is turned up, the dealer's opponent
+    /// (who leads the first trick) declares the briscola suit after seeing their hand
+    #[serde(default)]
+    pub declared_trump: bool,
+    /// Blitz: every card must be played within ten seconds, or the lowest one in hand is played instead
+    #[serde(default)]
+    pub blitz: bool,
+}
+
 /// A move in Briscola
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum BriscolaMove {
     PlayCard { card_index: usize }, // Index in player's hand (0-2, or fewer near end of game)
+    DeclareTrump { suit: Suit },    // Only in the declared-trump variant, before the first card
 }
 
 /// Current state of a round
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RoundState {
+    ChoosingTrump,      // Declared-trump variant: waiting for the briscola suit
     AwaitingFirstCard,  // Waiting for first player to play
     AwaitingSecondCard, // Waiting for second player to play
 }
@@ -69,7 +90,7 @@ pub struct BriscolaGameState {
     // Note: This counts deck.len() only, NOT including the trump card
     pub cards_remaining_in_deck: usize,
 
-    // The trump card (visible to both players, None after it's drawn)
+    // The trump card (visible to both players, None after it's drawn or when declared)
     pub trump_card: Option<Card>,
 
     // The briscola suit (always visible, even after trump card is drawn)
@@ -88,6 +109,10 @@ pub struct BriscolaGameState {
     // Previous round result: (first_card, second_card, winner)
     // None if no rounds have been completed yet
     pub previous_round: Option<(Card, Card, PlayerSymbol)>,
+
+    // Blitz variant: each card has BLITZ_CARD_SECS to be played
+    #[serde(default)]
+    pub blitz: bool,
 }
 
 impl BriscolaGameState {
@@ -106,10 +131,12 @@ impl BriscolaGameState {
             current_player: 1,
             round_state: RoundState::AwaitingFirstCard,
             previous_round: None,
+            blitz: false,
         }
     }
 
     /// Redact opponent's hand and deck for a specific player
+    #[cfg(feature = "server-helpers")]
     pub fn redact_for_player(&self, player: PlayerSymbol) -> Self {
         let mut redacted = self.clone();
 
@@ -127,6 +154,17 @@ impl BriscolaGameState {
         redacted
     }
 
+    /// Redact game state for someone watching the match
+    /// Both hands and the deck are hidden, the table stays visible
+    #[cfg(feature = "server-helpers")]
+    pub fn redact_for_spectator(&self) -> Self {
+        let mut redacted = self.clone();
+        redacted.player1_hand = Vec::new();
+        redacted.player2_hand = Vec::new();
+        redacted.deck = Vec::new();
+        redacted
+    }
+
     /// Calculate score from collected piles
     pub fn get_score(&self) -> (u8, u8) {
         let p1_score = self.player1_pile.iter().map(Self::card_points).sum();
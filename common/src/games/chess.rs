@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use crate::games::players::{PlayerSymbol, Seat};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ChessPiece {
@@ -24,20 +25,29 @@ impl Player {
         }
     }
 
-    pub fn to_symbol(&self) -> i32 {
+    /// White sits first
+    pub fn seat(&self) -> Seat {
         match self {
-            Player::White => 1,
-            Player::Black => 2,
+            Player::White => Seat::FIRST,
+            Player::Black => Seat::SECOND,
         }
     }
 
-    pub fn from_symbol(symbol: i32) -> Option<Player> {
-        match symbol {
-            1 => Some(Player::White),
-            2 => Some(Player::Black),
+    pub fn from_seat(seat: Seat) -> Option<Player> {
+        match seat {
+            Seat::FIRST => Some(Player::White),
+            Seat::SECOND => Some(Player::Black),
             _ => None,
         }
     }
+
+    pub fn to_symbol(&self) -> PlayerSymbol {
+        self.seat().symbol()
+    }
+
+    pub fn from_symbol(symbol: PlayerSymbol) -> Option<Player> {
+        Player::from_seat(Seat::from_symbol(symbol)?)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -93,12 +103,120 @@ pub struct ChessPieceState {
 pub struct ChessMove {
     pub from: ChessPosition,
     pub to: ChessPosition,
+    /// Piece a pawn reaching the last row becomes, a queen when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub promotion: Option<ChessPiece>,
+}
+
+/// Pieces a pawn can be promoted to
+pub const PROMOTION_PIECES: [ChessPiece; 4] = [ChessPiece::Queen, ChessPiece::Rook, ChessPiece::Bishop, ChessPiece::Knight];
+
+impl ChessPiece {
+    /// From the letter used in moves like `e7 e8 q`
+    pub fn from_letter(letter: &str) -> Option<ChessPiece> {
+        match letter.to_ascii_lowercase().as_str() {
+            "p" => Some(ChessPiece::Pawn),
+            "r" => Some(ChessPiece::Rook),
+            "n" => Some(ChessPiece::Knight),
+            "b" => Some(ChessPiece::Bishop),
+            "q" => Some(ChessPiece::Queen),
+            "k" => Some(ChessPiece::King),
+            _ => None,
+        }
+    }
+
+    /// Uppercase letter of the piece as algebraic notation writes it
+    pub fn letter(&self) -> char {
+        match self {
+            ChessPiece::Pawn => 'P',
+            ChessPiece::Rook => 'R',
+            ChessPiece::Knight => 'N',
+            ChessPiece::Bishop => 'B',
+            ChessPiece::Queen => 'Q',
+            ChessPiece::King => 'K',
+        }
+    }
+}
+
+/// Non-move actions a player can send instead of a piece move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChessAction {
+    OfferDraw,
+    AcceptDraw,
+    ClaimDraw,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GameOverReason {
     Checkmate(Player),
     Stalemate,
+    DrawByAgreement,
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    /// Neither side has the pieces left to checkmate
+    InsufficientMaterial,
+    /// Same position five times, a draw without anyone claiming it
+    FivefoldRepetition,
+    /// 75 moves each without a capture or pawn move, a draw without anyone claiming it
+    SeventyFiveMoveRule,
+}
+
+/// Castling each side still has, lost for good once the king or that rook moves or the rook is captured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool,
+}
+
+impl CastlingRights {
+    pub fn get(&self, player: Player, king_side: bool) -> bool {
+        match (player, king_side) {
+            (Player::White, true) => self.white_king_side,
+            (Player::White, false) => self.white_queen_side,
+            (Player::Black, true) => self.black_king_side,
+            (Player::Black, false) => self.black_queen_side,
+        }
+    }
+
+    /// Drop the rights tied to a square a move left or landed on
+    pub fn revoke(&mut self, pos: ChessPosition) {
+        match (pos.row, pos.col) {
+            (0, 4) => {
+                self.white_king_side = false;
+                self.white_queen_side = false;
+            }
+            (0, 0) => self.white_queen_side = false,
+            (0, 7) => self.white_king_side = false,
+            (7, 4) => {
+                self.black_king_side = false;
+                self.black_queen_side = false;
+            }
+            (7, 0) => self.black_queen_side = false,
+            (7, 7) => self.black_king_side = false,
+            _ => {}
+        }
+    }
+}
+
+impl Default for CastlingRights {
+    fn default() -> Self {
+        Self {
+            white_king_side: true,
+            white_queen_side: true,
+            black_king_side: true,
+            black_queen_side: true,
+        }
+    }
+}
+
+/// Draws a player can claim on their turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DrawClaim {
+    ThreefoldRepetition,
+    FiftyMoveRule,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -108,6 +226,21 @@ pub struct ChessGameState {
     pub check_state: Option<Player>,
     pub game_over: Option<GameOverReason>,
     pub move_history: Vec<ChessMove>,
+    /// Half-moves since the last capture or pawn move (fifty-move rule)
+    #[serde(default)]
+    pub halfmove_clock: u32,
+    /// Position keys after every move, starting with the initial position
+    #[serde(default)]
+    pub position_history: Vec<String>,
+    /// Player with a pending draw offer, if any
+    #[serde(default)]
+    pub draw_offer: Option<Player>,
+    /// States saved before castling existed get every right, castling still needs king and rook on their squares
+    #[serde(default)]
+    pub castling_rights: CastlingRights,
+    /// Square a pawn skipped with its double move on the last turn, where it can be captured en passant
+    #[serde(default)]
+    pub en_passant: Option<ChessPosition>,
 }
 
 impl ChessGameState {
@@ -144,15 +277,23 @@ impl ChessGameState {
             });
         }
 
-        Self {
+        let mut state = Self {
             board,
             current_turn: Player::White,
             check_state: None,
             game_over: None,
             move_history: Vec::new(),
-        }
+            halfmove_clock: 0,
+            position_history: Vec::new(),
+            draw_offer: None,
+            castling_rights: CastlingRights::default(),
+            en_passant: None,
+        };
+        state.position_history.push(state.position_key());
+        state
     }
 
+    #[cfg(feature = "server-helpers")]
     pub fn redact_for_player(&self, _player_symbol: i32) -> Self {
         self.clone()
     }
@@ -172,8 +313,102 @@ impl ChessGameState {
     pub fn get_winner(&self) -> Option<i32> {
         match &self.game_over {
             Some(GameOverReason::Checkmate(player)) => Some(player.to_symbol()),
-            Some(GameOverReason::Stalemate) => None,
-            None => None,
+            _ => None,
+        }
+    }
+
+    /// Compact key identifying the current position (board, castling rights, en passant capture and side to move)
+    /// The en passant square only counts when a pawn can actually capture there
+    pub fn position_key(&self) -> String {
+        let mut key = String::with_capacity(71);
+        for row in &self.board {
+            for square in row {
+                key.push(match square {
+                    Some(piece) => {
+                        let symbol = match piece.piece {
+                            ChessPiece::Pawn => 'p',
+                            ChessPiece::Rook => 'r',
+                            ChessPiece::Knight => 'n',
+                            ChessPiece::Bishop => 'b',
+                            ChessPiece::Queen => 'q',
+                            ChessPiece::King => 'k',
+                        };
+                        if piece.player == Player::White {
+                            symbol.to_ascii_uppercase()
+                        } else {
+                            symbol
+                        }
+                    }
+                    None => '.',
+                });
+            }
+        }
+        let rights = self.castling_rights;
+        for (allowed, symbol) in [
+            (rights.white_king_side, 'K'),
+            (rights.white_queen_side, 'Q'),
+            (rights.black_king_side, 'k'),
+            (rights.black_queen_side, 'q'),
+        ] {
+            key.push(if allowed { symbol } else { '-' });
+        }
+        if let Some(target) = self.en_passant.filter(|target| self.can_capture_en_passant(*target)) {
+            key.push_str(&target.to_algebraic());
+        }
+        key.push(if self.current_turn == Player::White { 'w' } else { 'b' });
+        key
+    }
+
+    /// How many times the current position has occurred
+    pub fn repetition_count(&self) -> usize {
+        let current = self.position_key();
+        self.position_history.iter().filter(|key| **key == current).count()
+    }
+
+    /// Draw the game ends in without anyone claiming it: dead positions, fivefold repetition and the 75-move rule
+    pub fn automatic_draw(&self) -> Option<GameOverReason> {
+        if self.has_insufficient_material() {
+            Some(GameOverReason::InsufficientMaterial)
+        } else if self.repetition_count() >= 5 {
+            Some(GameOverReason::FivefoldRepetition)
+        } else if self.halfmove_clock >= 150 {
+            Some(GameOverReason::SeventyFiveMoveRule)
+        } else {
+            None
+        }
+    }
+
+    /// King against king, with at most a single minor piece or bishops all on squares of one color
+    pub fn has_insufficient_material(&self) -> bool {
+        let mut knights = 0;
+        let mut bishop_square_colors = Vec::new();
+        for (row, squares) in self.board.iter().enumerate() {
+            for (col, square) in squares.iter().enumerate() {
+                match square.map(|piece| piece.piece) {
+                    None | Some(ChessPiece::King) => {}
+                    Some(ChessPiece::Knight) => knights += 1,
+                    Some(ChessPiece::Bishop) => bishop_square_colors.push((row + col) % 2),
+                    Some(_) => return false,
+                }
+            }
+        }
+        match (knights, bishop_square_colors.as_slice()) {
+            (0, [first, rest @ ..]) => rest.iter().all(|color| color == first),
+            (knights, bishops) => knights + bishops.len() <= 1,
+        }
+    }
+
+    /// The draw the player to move could claim right now, if any
+    pub fn claimable_draw(&self) -> Option<DrawClaim> {
+        if self.is_finished() {
+            return None;
+        }
+        if self.repetition_count() >= 3 {
+            Some(DrawClaim::ThreefoldRepetition)
+        } else if self.halfmove_clock >= 100 {
+            Some(DrawClaim::FiftyMoveRule)
+        } else {
+            None
         }
     }
 
@@ -191,10 +426,25 @@ impl ChessGameState {
             }
         }
 
-        if !self.is_valid_piece_move(chess_move, piece)? {
+        let is_valid = if self.is_castling(chess_move) {
+            self.can_castle(chess_move, player)
+        } else {
+            self.is_valid_piece_move(chess_move, piece)?
+        };
+        if !is_valid {
             return Ok(false);
         }
 
+        match chess_move.promotion {
+            Some(_) if !self.is_promotion(chess_move) => {
+                return Err("Only pawns reaching the last row can be promoted".to_string());
+            }
+            Some(promotion) if !PROMOTION_PIECES.contains(&promotion) => {
+                return Err("Pawns can only be promoted to a queen, rook, bishop or knight".to_string());
+            }
+            _ => {}
+        }
+
         if self.would_move_cause_check(chess_move, player) {
             return Ok(false);
         }
@@ -245,6 +495,83 @@ impl ChessGameState {
         }
     }
 
+    /// A pawn moving onto the last row
+    pub fn is_promotion(&self, chess_move: &ChessMove) -> bool {
+        self.get_piece(chess_move.from).is_some_and(|piece| {
+            let last_row = match piece.player {
+                Player::White => 7,
+                Player::Black => 0,
+            };
+            piece.piece == ChessPiece::Pawn && chess_move.to.row == last_row
+        })
+    }
+
+    /// Square of the pawn a move captures en passant
+    pub fn en_passant_capture(&self, chess_move: &ChessMove) -> Option<ChessPosition> {
+        let is_pawn = self.get_piece(chess_move.from).is_some_and(|piece| piece.piece == ChessPiece::Pawn);
+        if !is_pawn || self.en_passant != Some(chess_move.to) || chess_move.from.col == chess_move.to.col {
+            return None;
+        }
+        ChessPosition::new(chess_move.from.row, chess_move.to.col)
+    }
+
+    /// Whether the player to move has a legal en passant capture onto the target
+    fn can_capture_en_passant(&self, target: ChessPosition) -> bool {
+        let row = match self.current_turn {
+            Player::White => target.row.checked_sub(1),
+            Player::Black => target.row.checked_add(1),
+        };
+        [target.col.checked_sub(1), target.col.checked_add(1)]
+            .into_iter()
+            .filter_map(|col| ChessPosition::new(row?, col?))
+            .any(|from| self.is_valid_move(&ChessMove { from, to: target, promotion: None }, self.current_turn) == Ok(true))
+    }
+
+    /// A king moving two squares along its row
+    pub fn is_castling(&self, chess_move: &ChessMove) -> bool {
+        self.get_piece(chess_move.from).is_some_and(|piece| piece.piece == ChessPiece::King)
+            && chess_move.from.row == chess_move.to.row
+            && (chess_move.to.col as i8 - chess_move.from.col as i8).abs() == 2
+    }
+
+    /// The rook move that goes with a castling king move
+    pub fn castling_rook_move(&self, chess_move: &ChessMove) -> Option<ChessMove> {
+        if !self.is_castling(chess_move) {
+            return None;
+        }
+        let row = chess_move.from.row;
+        let (rook_col, rook_to_col) = if chess_move.to.col > chess_move.from.col { (7, 5) } else { (0, 3) };
+        Some(ChessMove {
+            from: ChessPosition::new(row, rook_col)?,
+            to: ChessPosition::new(row, rook_to_col)?,
+            promotion: None,
+        })
+    }
+
+    /// King and rook unmoved, nothing between them, and the king neither in check nor passing or landing on an attacked square
+    fn can_castle(&self, chess_move: &ChessMove, player: Player) -> bool {
+        let home_row = match player {
+            Player::White => 0,
+            Player::Black => 7,
+        };
+        let king_side = chess_move.to.col > chess_move.from.col;
+        if chess_move.from != (ChessPosition { row: home_row, col: 4 }) || !self.castling_rights.get(player, king_side) {
+            return false;
+        }
+        let Some(rook_move) = self.castling_rook_move(chess_move) else {
+            return false;
+        };
+        let rook = ChessPieceState { piece: ChessPiece::Rook, player };
+        if self.get_piece(rook_move.from) != Some(&rook) || self.is_path_clear(chess_move.from, rook_move.from) != Ok(true) {
+            return false;
+        }
+
+        let opponent = player.opponent();
+        [4, rook_move.to.col, chess_move.to.col]
+            .into_iter()
+            .all(|col| !self.is_square_attacked(ChessPosition { row: home_row, col }, opponent))
+    }
+
     fn is_valid_pawn_move(&self, chess_move: &ChessMove, player: Player) -> Result<bool, String> {
         let from = chess_move.from;
         let to = chess_move.to;
@@ -276,7 +603,7 @@ impl ChessGameState {
         }
 
         if row_diff == direction && col_diff == 1 {
-            return Ok(self.get_piece(to).is_some());
+            return Ok(self.get_piece(to).is_some() || self.en_passant == Some(to));
         }
 
         Ok(false)
@@ -311,6 +638,9 @@ impl ChessGameState {
             return true;
         }
 
+        if let Some(captured) = self.en_passant_capture(chess_move) {
+            *test_state.get_piece_mut(captured) = None;
+        }
         *test_state.get_piece_mut(chess_move.from) = None;
         *test_state.get_piece_mut(chess_move.to) = piece;
 
@@ -327,16 +657,24 @@ impl ChessGameState {
         self.is_square_attacked(king_pos, player.opponent())
     }
 
+    /// Pawns attack the squares diagonally ahead of them whether or not anything stands there
     fn is_square_attacked(&self, pos: ChessPosition, by_player: Player) -> bool {
         for row in 0..8 {
             for col in 0..8 {
                 let from = ChessPosition::new(row, col).unwrap();
                 if let Some(piece) = self.get_piece(from) {
-                    if piece.player == by_player {
-                        let test_move = ChessMove { from, to: pos };
-                        if let Ok(true) = self.is_valid_piece_move(&test_move, piece) {
-                            return true;
-                        }
+                    if piece.player != by_player {
+                        continue;
+                    }
+                    let attacks = if piece.piece == ChessPiece::Pawn {
+                        let direction: i8 = if by_player == Player::White { 1 } else { -1 };
+                        pos.row as i8 - from.row as i8 == direction && (pos.col as i8 - from.col as i8).abs() == 1
+                    } else {
+                        let test_move = ChessMove { from, to: pos, promotion: None };
+                        self.is_valid_piece_move(&test_move, piece) == Ok(true)
+                    };
+                    if attacks {
+                        return true;
                     }
                 }
             }
@@ -413,6 +751,111 @@ mod tests {
         assert_eq!(pos.to_algebraic(), "e4");
     }
 
+    #[test]
+    fn test_initial_position_recorded() {
+        let game = ChessGameState::new();
+        assert_eq!(game.position_history.len(), 1);
+        assert_eq!(game.repetition_count(), 1);
+        assert_eq!(game.claimable_draw(), None);
+    }
+
+    #[test]
+    fn test_position_key_includes_side_to_move() {
+        let mut game = ChessGameState::new();
+        let white_key = game.position_key();
+        game.current_turn = Player::Black;
+        assert_ne!(white_key, game.position_key());
+        assert!(white_key.ends_with('w'));
+    }
+
+    #[test]
+    fn test_claimable_draw_fifty_moves() {
+        let mut game = ChessGameState::new();
+        game.halfmove_clock = 99;
+        assert_eq!(game.claimable_draw(), None);
+        game.halfmove_clock = 100;
+        assert_eq!(game.claimable_draw(), Some(DrawClaim::FiftyMoveRule));
+    }
+
+    #[test]
+    fn test_claimable_draw_threefold() {
+        let mut game = ChessGameState::new();
+        let key = game.position_key();
+        game.position_history.push(key.clone());
+        assert_eq!(game.claimable_draw(), None);
+        game.position_history.push(key);
+        assert_eq!(game.claimable_draw(), Some(DrawClaim::ThreefoldRepetition));
+    }
+
+    #[test]
+    fn test_castling_rights_revoked_by_home_squares() {
+        let mut rights = CastlingRights::default();
+        rights.revoke(ChessPosition::from_algebraic("h1").unwrap());
+        assert!(!rights.get(Player::White, true));
+        assert!(rights.get(Player::White, false));
+
+        rights.revoke(ChessPosition::from_algebraic("e8").unwrap());
+        assert!(!rights.get(Player::Black, true));
+        assert!(!rights.get(Player::Black, false));
+        assert!(rights.get(Player::White, false));
+    }
+
+    #[test]
+    fn test_castling_rights_default_for_older_states() {
+        let mut json = serde_json::to_value(ChessGameState::new()).unwrap();
+        json.as_object_mut().unwrap().remove("castling_rights");
+        let game: ChessGameState = serde_json::from_value(json).unwrap();
+        assert_eq!(game.castling_rights, CastlingRights::default());
+
+        let mut moved = game.clone();
+        moved.castling_rights.white_king_side = false;
+        assert_ne!(game.position_key(), moved.position_key());
+    }
+
+    #[test]
+    fn test_moves_without_promotion_parse() {
+        let chess_move: ChessMove = serde_json::from_str(r#"{"from": {"row": 1, "col": 4}, "to": {"row": 3, "col": 4}}"#).unwrap();
+        assert_eq!(chess_move.promotion, None);
+        assert!(!serde_json::to_string(&chess_move).unwrap().contains("promotion"));
+        assert_eq!(ChessPiece::from_letter("Q"), Some(ChessPiece::Queen));
+    }
+
+    /// Board with only the given pieces
+    fn with_pieces(pieces: &[(&str, ChessPiece, Player)]) -> ChessGameState {
+        let mut game = ChessGameState::new();
+        game.board = [[None; 8]; 8];
+        for (square, piece, player) in pieces {
+            *game.get_piece_mut(ChessPosition::from_algebraic(square).unwrap()) = Some(ChessPieceState { piece: *piece, player: *player });
+        }
+        game
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let kings = [("e1", ChessPiece::King, Player::White), ("e8", ChessPiece::King, Player::Black)];
+        assert!(with_pieces(&kings).has_insufficient_material());
+        assert!(with_pieces(&[kings[0], kings[1], ("b1", ChessPiece::Knight, Player::White)]).has_insufficient_material());
+        assert!(with_pieces(&[kings[0], kings[1], ("c1", ChessPiece::Bishop, Player::White), ("f8", ChessPiece::Bishop, Player::Black)]).has_insufficient_material());
+
+        assert!(!with_pieces(&[kings[0], kings[1], ("c1", ChessPiece::Bishop, Player::White), ("c8", ChessPiece::Bishop, Player::Black)]).has_insufficient_material());
+        assert!(!with_pieces(&[kings[0], kings[1], ("b1", ChessPiece::Knight, Player::White), ("g1", ChessPiece::Knight, Player::White)]).has_insufficient_material());
+        assert!(!with_pieces(&[kings[0], kings[1], ("a2", ChessPiece::Pawn, Player::White)]).has_insufficient_material());
+        assert!(!ChessGameState::new().has_insufficient_material());
+    }
+
+    #[test]
+    fn test_automatic_draws() {
+        let mut game = ChessGameState::new();
+        assert_eq!(game.automatic_draw(), None);
+        game.halfmove_clock = 150;
+        assert_eq!(game.automatic_draw(), Some(GameOverReason::SeventyFiveMoveRule));
+
+        let mut game = ChessGameState::new();
+        let key = game.position_key();
+        game.position_history.extend(std::iter::repeat_n(key, 4));
+        assert_eq!(game.automatic_draw(), Some(GameOverReason::FivefoldRepetition));
+    }
+
     #[test]
     fn test_player_opponent() {
         assert_eq!(Player::White.opponent(), Player::Black);
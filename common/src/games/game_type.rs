@@ -1,8 +1,17 @@
+#[cfg(feature = "server-helpers")]
+use schemars::schema_for;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+#[cfg(all(feature = "server-helpers", feature = "games-briscola"))]
+use crate::games::briscola::BriscolaOptions;
+#[cfg(feature = "client-helpers")]
+use crate::games::players::Seat;
+#[cfg(feature = "server-helpers")]
+use crate::games::{rock_paper_scissors::RockPaperScissorsOptions, tic_tac_toe::TicTacToeOptions};
+
 /// Represents the type of game being played
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum GameType {
     TicTacToe,
     RockPaperScissors,
@@ -10,6 +19,43 @@ pub enum GameType {
     Chess,
 }
 
+impl GameType {
+    pub const ALL: [GameType; 4] = [
+        GameType::TicTacToe,
+        GameType::RockPaperScissors,
+        GameType::Briscola,
+        GameType::Chess,
+    ];
+
+    /// JSON schema of the options accepted when queueing, chess takes none
+    #[cfg(feature = "server-helpers")]
+    pub fn options_schema(&self) -> serde_json::Value {
+        let schema = match self {
+            GameType::TicTacToe => schema_for!(TicTacToeOptions),
+            GameType::RockPaperScissors => schema_for!(RockPaperScissorsOptions),
+            #[cfg(feature = "games-briscola")]
+            GameType::Briscola => schema_for!(BriscolaOptions),
+            #[cfg(not(feature = "games-briscola"))]
+            GameType::Briscola => schema_for!(()),
+            GameType::Chess => schema_for!(()),
+        };
+        serde_json::to_value(schema).unwrap()
+    }
+
+    /// What sitting first or second means in this game
+    #[cfg(feature = "client-helpers")]
+    pub fn seat_name(&self, seat: Seat) -> &'static str {
+        match (self, seat) {
+            (GameType::TicTacToe, Seat::FIRST) => "X",
+            (GameType::TicTacToe, _) => "O",
+            (GameType::Chess, Seat::FIRST) => "White",
+            (GameType::Chess, _) => "Black",
+            (_, Seat::FIRST) => "Player 1",
+            (_, _) => "Player 2",
+        }
+    }
+}
+
 impl fmt::Display for GameType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -20,16 +66,3 @@ impl fmt::Display for GameType {
         }
     }
 }
-
-#[derive(Debug, Clone)]
-pub struct GameConfig {
-    pub disconnect_timeout_secs: u64,
-}
-
-pub fn get_game_config(game_type: &GameType) -> GameConfig {
-    match game_type {
-        GameType::TicTacToe | GameType::RockPaperScissors | GameType::Briscola | GameType::Chess => GameConfig {
-            disconnect_timeout_secs: 30
-        }
-    }
-}
\ No newline at end of file
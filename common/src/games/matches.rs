@@ -1,18 +1,200 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use crate::games::game_type::GameType;
+use crate::games::players::Seat;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Match {
     pub id: i64,
+    /// Seat 1, kept for two-player games
     pub player1_id: i64,
+    /// Seat 2, kept for two-player games
     pub player2_id: i64,
+    /// Everyone seated, in seat order, empty from servers that only know `player1_id` and `player2_id`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seats: Vec<i64>,
     pub in_progress: bool,
+    #[serde(default)]
+    pub status: MatchStatus,
     pub outcome: Option<MatchOutcome>,
     pub game_type: GameType,
     pub game_state: serde_json::Value,
+    /// Display info for the participants, when known
+    #[serde(default)]
+    pub players: Vec<MatchPlayer>,
+    /// Guest quick play match, kept in memory only and never saved, rated or counted in stats
+    #[serde(default)]
+    pub ephemeral: bool,
+    /// Player whose draw offer is waiting for the opponent's answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub draw_offered_by: Option<i64>,
+    /// Grows with every saved change to the match, 0 for matches that are never saved
+    #[serde(default)]
+    pub seq: i64,
 }
 
+/// Public profile of a match participant
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MatchPlayer {
+    pub id: i64,
+    pub name: String,
+    pub score: i64,
+    /// Played by the server, matches against it are unranked
+    #[serde(default)]
+    pub bot: bool,
+    /// Rating in the game of the match, None until they played it rated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<i64>,
+}
+
+impl Match {
+    pub fn player(&self, player_id: i64) -> Option<&MatchPlayer> {
+        self.players.iter().find(|player| player.id == player_id)
+    }
+
+    /// Ids of the seated players in seat order, `player1_id` and `player2_id` when seats are unknown
+    pub fn seat_ids(&self) -> Vec<i64> {
+        if self.seats.is_empty() {
+            vec![self.player1_id, self.player2_id]
+        } else {
+            self.seats.clone()
+        }
+    }
+
+    /// Seat of a participant
+    pub fn seat_of(&self, player_id: i64) -> Option<Seat> {
+        Seat::new(self.seat_ids().iter().position(|id| *id == player_id)? + 1)
+    }
+
+    /// The player sitting at `seat`
+    pub fn player_at(&self, seat: Seat) -> Option<i64> {
+        self.seat_ids().get(seat.index()).copied()
+    }
+
+    /// Everyone seated but `player_id`
+    pub fn opponents_of(&self, player_id: i64) -> Vec<i64> {
+        self.seat_ids().into_iter().filter(|id| *id != player_id).collect()
+    }
+
+    /// Name, score and rating of a participant, falling back to the id
+    #[cfg(feature = "client-helpers")]
+    pub fn player_label(&self, player_id: i64) -> String {
+        match self.player(player_id) {
+            Some(MatchPlayer { name, score, rating: Some(rating), .. }) => format!("{name} ({score}, rated {rating})"),
+            Some(player) => format!("{} ({})", player.name, player.score),
+            None => format!("Player {player_id}"),
+        }
+    }
+
+    /// Move the match to a new lifecycle status, keeping `in_progress` in sync
+    pub fn transition_to(&mut self, next: MatchStatus) -> Result<(), InvalidTransition> {
+        self.status = self.status.transition_to(next)?;
+        self.in_progress = self.status.is_open();
+        Ok(())
+    }
+}
+
+/// Lifecycle of a match
+///
+/// ```text
+/// waiting -> active <-> paused
+///    |         |          |
+///    v         v          v
+/// aborted   finished / aborted
+/// ```
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchStatus {
+    /// Waiting in the matchmaking queue for an opponent
+    Waiting,
+    /// Both players are in and playing
+    #[default]
+    Active,
+    /// A player dropped and has a grace period to reconnect
+    Paused,
+    /// Played to the end, `outcome` is set
+    Finished,
+    /// Ended before it really started, e.g. left the queue
+    Aborted,
+}
+
+impl MatchStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchStatus::Waiting => "waiting",
+            MatchStatus::Active => "active",
+            MatchStatus::Paused => "paused",
+            MatchStatus::Finished => "finished",
+            MatchStatus::Aborted => "aborted",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "waiting" => Some(MatchStatus::Waiting),
+            "active" => Some(MatchStatus::Active),
+            "paused" => Some(MatchStatus::Paused),
+            "finished" => Some(MatchStatus::Finished),
+            "aborted" => Some(MatchStatus::Aborted),
+            _ => None,
+        }
+    }
+
+    /// Waiting, active or paused
+    pub fn is_open(&self) -> bool {
+        matches!(self, MatchStatus::Waiting | MatchStatus::Active | MatchStatus::Paused)
+    }
+
+    /// Both players are in the match, connected or not
+    pub fn is_playing(&self) -> bool {
+        matches!(self, MatchStatus::Active | MatchStatus::Paused)
+    }
+
+    pub fn can_transition_to(&self, next: MatchStatus) -> bool {
+        use MatchStatus::*;
+        matches!(
+            (self, next),
+            (Waiting, Active)
+                | (Waiting, Aborted)
+                | (Active, Paused)
+                | (Active, Finished)
+                | (Active, Aborted)
+                | (Paused, Active)
+                | (Paused, Finished)
+                | (Paused, Aborted)
+        )
+    }
+
+    /// Validate a transition, staying in the same status is always allowed
+    pub fn transition_to(self, next: MatchStatus) -> Result<MatchStatus, InvalidTransition> {
+        if self == next || self.can_transition_to(next) {
+            Ok(next)
+        } else {
+            Err(InvalidTransition { from: self, to: next })
+        }
+    }
+}
+
+impl fmt::Display for MatchStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidTransition {
+    pub from: MatchStatus,
+    pub to: MatchStatus,
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Match cannot go from {} to {}", self.from, self.to)
+    }
+}
+
+impl std::error::Error for InvalidTransition {}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum MatchOutcome {
     #[serde(rename = "p1_win")]
@@ -29,6 +211,13 @@ pub enum MatchEndReason {
     Ended,
     #[serde(rename = "disconnection")]
     Disconnection,
+    /// A player did not move for too long
+    #[serde(rename = "forfeit")]
+    Forfeit,
+    #[serde(rename = "resignation")]
+    Resignation,
+    #[serde(rename = "draw_agreed")]
+    DrawAgreed,
 }
 
 impl fmt::Display for MatchOutcome {
@@ -39,4 +228,87 @@ impl fmt::Display for MatchOutcome {
             MatchOutcome::Draw => write!(f, "draw"),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_lifecycle() {
+        let status = MatchStatus::Waiting
+            .transition_to(MatchStatus::Active).unwrap()
+            .transition_to(MatchStatus::Paused).unwrap()
+            .transition_to(MatchStatus::Active).unwrap()
+            .transition_to(MatchStatus::Finished).unwrap();
+        assert_eq!(status, MatchStatus::Finished);
+        assert!(!status.is_open());
+    }
+
+    #[test]
+    fn test_invalid_transitions() {
+        assert!(MatchStatus::Waiting.transition_to(MatchStatus::Finished).is_err());
+        assert!(MatchStatus::Waiting.transition_to(MatchStatus::Paused).is_err());
+        assert!(MatchStatus::Finished.transition_to(MatchStatus::Active).is_err());
+        assert_eq!(
+            MatchStatus::Aborted.transition_to(MatchStatus::Waiting),
+            Err(InvalidTransition { from: MatchStatus::Aborted, to: MatchStatus::Waiting })
+        );
+        assert_eq!(MatchStatus::Finished.transition_to(MatchStatus::Finished), Ok(MatchStatus::Finished));
+    }
+
+    #[test]
+    fn test_status_strings_round_trip() {
+        for status in [MatchStatus::Waiting, MatchStatus::Active, MatchStatus::Paused, MatchStatus::Finished, MatchStatus::Aborted] {
+            assert_eq!(MatchStatus::parse(status.as_str()), Some(status));
+            assert_eq!(serde_json::to_string(&status).unwrap(), format!("\"{status}\""));
+        }
+        assert_eq!(MatchStatus::parse("unknown"), None);
+    }
+
+    #[test]
+    fn test_seats_fall_back_to_both_players() {
+        let json = serde_json::json!({
+            "id": 1,
+            "player1_id": 17,
+            "player2_id": 42,
+            "in_progress": true,
+            "outcome": null,
+            "game_type": "Briscola",
+            "game_state": {}
+        });
+        let mut game_match: Match = serde_json::from_value(json).unwrap();
+        assert_eq!(game_match.seat_ids(), vec![17, 42]);
+        assert_eq!(game_match.seat_of(42), Some(Seat::SECOND));
+        assert!(!serde_json::to_string(&game_match).unwrap().contains("seats"));
+
+        game_match.seats = vec![17, 42, 8, 5];
+        assert_eq!(game_match.seat_of(8), Seat::new(3));
+        assert_eq!(game_match.seat_of(99), None);
+        assert_eq!(game_match.player_at(Seat::new(4).unwrap()), Some(5));
+        assert_eq!(game_match.player_at(Seat::new(5).unwrap()), None);
+        assert_eq!(game_match.opponents_of(42), vec![17, 8, 5]);
+    }
+
+    #[test]
+    #[cfg(feature = "client-helpers")]
+    fn test_player_label() {
+        let json = serde_json::json!({
+            "id": 1,
+            "player1_id": 17,
+            "player2_id": 42,
+            "in_progress": true,
+            "outcome": null,
+            "game_type": "TicTacToe",
+            "game_state": {},
+            "players": [{ "id": 17, "name": "alice", "score": 12 }]
+        });
+        let mut game_match: Match = serde_json::from_value(json).unwrap();
+
+        assert_eq!(game_match.player_label(17), "alice (12)");
+        assert_eq!(game_match.player_label(42), "Player 42");
+
+        game_match.players[0].rating = Some(1620);
+        assert_eq!(game_match.player_label(17), "alice (12, rated 1620)");
+    }
+}
@@ -1,6 +1,8 @@
 pub mod rock_paper_scissors;
 pub mod tic_tac_toe;
+#[cfg(feature = "games-briscola")]
 pub mod briscola;
+#[cfg(feature = "games-chess")]
 pub mod chess;
 pub mod game_type;
 pub mod matches;
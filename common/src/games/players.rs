@@ -1 +1,84 @@
-pub type PlayerSymbol = i32;
\ No newline at end of file
+use serde::{Deserialize, Serialize};
+
+/// A seat as game states store it, 1 for the first player
+pub type PlayerSymbol = i32;
+
+/// A place at the table, counted from 1 as games number their players
+/// Stored as its `PlayerSymbol`, and the one way to go between player ids, symbols and each game's colors or marks
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(try_from = "PlayerSymbol", into = "PlayerSymbol")]
+pub struct Seat(u8);
+
+impl Seat {
+    pub const FIRST: Seat = Seat(1);
+    pub const SECOND: Seat = Seat(2);
+
+    /// The seat numbered `number`, None for 0 or past the largest table
+    pub fn new(number: usize) -> Option<Seat> {
+        u8::try_from(number).ok().filter(|number| *number > 0).map(Seat)
+    }
+
+    pub fn from_symbol(symbol: PlayerSymbol) -> Option<Seat> {
+        Seat::new(usize::try_from(symbol).ok()?)
+    }
+
+    pub fn symbol(self) -> PlayerSymbol {
+        self.0 as PlayerSymbol
+    }
+
+    pub fn number(self) -> usize {
+        self.0 as usize
+    }
+
+    /// Position among the seats counted from 0
+    pub fn index(self) -> usize {
+        self.number() - 1
+    }
+
+    /// The seat playing after this one at a table of `seats`
+    pub fn next(self, seats: usize) -> Seat {
+        Seat::new(self.number() % seats.max(1) + 1).unwrap_or(Seat::FIRST)
+    }
+
+    /// The other seat of a two-player game
+    pub fn opponent(self) -> Seat {
+        self.next(2)
+    }
+}
+
+impl From<Seat> for PlayerSymbol {
+    fn from(seat: Seat) -> Self {
+        seat.symbol()
+    }
+}
+
+impl TryFrom<PlayerSymbol> for Seat {
+    type Error = String;
+
+    fn try_from(symbol: PlayerSymbol) -> Result<Self, Self::Error> {
+        Seat::from_symbol(symbol).ok_or_else(|| format!("Invalid seat {symbol}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seat_conversions() {
+        assert_eq!(Seat::from_symbol(1), Some(Seat::FIRST));
+        assert_eq!(Seat::from_symbol(0), None);
+        assert_eq!(Seat::from_symbol(-1), None);
+        assert_eq!(Seat::new(256), None);
+        assert_eq!(Seat::SECOND.symbol(), 2);
+        assert_eq!(Seat::SECOND.index(), 1);
+        assert_eq!(Seat::FIRST.opponent(), Seat::SECOND);
+        assert_eq!(Seat::SECOND.opponent(), Seat::FIRST);
+        assert_eq!(Seat::new(3).unwrap().next(4), Seat::new(4).unwrap());
+        assert_eq!(Seat::new(4).unwrap().next(4), Seat::FIRST);
+
+        assert_eq!(serde_json::to_value(Seat::SECOND).unwrap(), 2);
+        assert_eq!(serde_json::from_value::<Seat>(serde_json::json!(1)).unwrap(), Seat::FIRST);
+        assert!(serde_json::from_value::<Seat>(serde_json::json!(0)).is_err());
+    }
+}
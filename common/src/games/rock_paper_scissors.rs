@@ -1,7 +1,48 @@
+#[cfg(feature = "server-helpers")]
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::games::players::PlayerSymbol;
 
+pub const DEFAULT_BEST_OF: u8 = 3;
+pub const MAX_BEST_OF: u8 = 9;
+
+fn default_best_of() -> u8 {
+    DEFAULT_BEST_OF
+}
+
+/// Options selectable when queueing for Rock-Paper-Scissors
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server-helpers", derive(JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct RockPaperScissorsOptions {
+    /// Rock-Paper-Scissors-Lizard-Spock
+    #[serde(default)]
+    pub lizard_spock: bool,
+    /// Rounds in the match, odd so it cannot end even, draws do not count
+    #[serde(default = "default_best_of")]
+    #[cfg_attr(feature = "server-helpers", schemars(range(min = 1, max = 9)))]
+    pub best_of: u8,
+}
+
+impl Default for RockPaperScissorsOptions {
+    fn default() -> Self {
+        Self {
+            lizard_spock: false,
+            best_of: DEFAULT_BEST_OF,
+        }
+    }
+}
+
+impl RockPaperScissorsOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.best_of == 0 || self.best_of > MAX_BEST_OF || self.best_of.is_multiple_of(2) {
+            return Err(format!("Best of must be an odd number between 1 and {MAX_BEST_OF}"));
+        }
+        Ok(())
+    }
+}
+
 /// Represents a move in Rock-Paper-Scissors
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -9,22 +50,40 @@ pub enum RockPaperScissorsMove {
     Rock,
     Paper,
     Scissors,
+    Lizard,
+    Spock,
     Redacted,
 }
 
 impl RockPaperScissorsMove {
     /// Determine winner: returns Some(winning_move) or None for draw
     pub fn beats(&self, other: &RockPaperScissorsMove) -> Option<RockPaperScissorsMove> {
-        match (self, other) {
-            (RockPaperScissorsMove::Rock, RockPaperScissorsMove::Scissors) => Some(*self),
-            (RockPaperScissorsMove::Paper, RockPaperScissorsMove::Rock) => Some(*self),
-            (RockPaperScissorsMove::Scissors, RockPaperScissorsMove::Paper) => Some(*self),
-            (RockPaperScissorsMove::Scissors, RockPaperScissorsMove::Rock) => Some(*other),
-            (RockPaperScissorsMove::Rock, RockPaperScissorsMove::Paper) => Some(*other),
-            (RockPaperScissorsMove::Paper, RockPaperScissorsMove::Scissors) => Some(*other),
-            _ => None, // Draw
+        if self.defeats(other) {
+            Some(*self)
+        } else if other.defeats(self) {
+            Some(*other)
+        } else {
+            None // Draw
         }
     }
+
+    /// Whether this move wins against the other one
+    pub fn defeats(&self, other: &RockPaperScissorsMove) -> bool {
+        use RockPaperScissorsMove::*;
+        matches!(
+            (self, other),
+            (Rock, Scissors) | (Rock, Lizard)
+                | (Paper, Rock) | (Paper, Spock)
+                | (Scissors, Paper) | (Scissors, Lizard)
+                | (Lizard, Spock) | (Lizard, Paper)
+                | (Spock, Scissors) | (Spock, Rock)
+        )
+    }
+
+    /// Moves only available in Lizard-Spock mode
+    pub fn is_lizard_spock(&self) -> bool {
+        matches!(self, RockPaperScissorsMove::Lizard | RockPaperScissorsMove::Spock)
+    }
 }
 
 /// Represents the complete state of a Rock-Paper-Scissors game
@@ -33,6 +92,11 @@ pub struct RockPaperScissorsGameState {
     /// List of rounds: each round is (player1_move, player2_move)
     /// None means the player hasn't submitted their move yet
     pub rounds: Vec<(Option<RockPaperScissorsMove>, Option<RockPaperScissorsMove>)>,
+    /// Lizard and Spock are valid moves
+    #[serde(default)]
+    pub lizard_spock: bool,
+    #[serde(default = "default_best_of")]
+    pub best_of: u8,
 }
 
 impl Default for RockPaperScissorsGameState {
@@ -44,8 +108,14 @@ impl Default for RockPaperScissorsGameState {
 impl RockPaperScissorsGameState {
     /// Create a new RockPaperScissors game with initial round
     pub fn new() -> Self {
+        Self::from_options(&RockPaperScissorsOptions::default())
+    }
+
+    pub fn from_options(options: &RockPaperScissorsOptions) -> Self {
         Self {
             rounds: vec![(None, None)],
+            lizard_spock: options.lizard_spock,
+            best_of: options.best_of,
         }
     }
 
@@ -76,10 +146,15 @@ impl RockPaperScissorsGameState {
         (p1_wins, p2_wins)
     }
 
-    /// Check if the game is finished (either player has 2 wins)
+    /// Round wins needed to take the match
+    pub fn wins_needed(&self) -> u8 {
+        self.best_of / 2 + 1
+    }
+
+    /// Check if the game is finished (either player has a majority of the rounds)
     pub fn is_finished(&self) -> bool {
         let (p1_wins, p2_wins) = self.get_score();
-        p1_wins >= 2 || p2_wins >= 2
+        p1_wins >= self.wins_needed() || p2_wins >= self.wins_needed()
     }
 
     /// Get the winner (if game is finished)
@@ -89,9 +164,9 @@ impl RockPaperScissorsGameState {
         }
 
         let (p1_wins, p2_wins) = self.get_score();
-        if p1_wins >= 2 {
+        if p1_wins >= self.wins_needed() {
             Some(1)
-        } else if p2_wins >= 2 {
+        } else if p2_wins >= self.wins_needed() {
             Some(2)
         } else {
             None
@@ -100,6 +175,7 @@ impl RockPaperScissorsGameState {
 
     /// Redact opponent's moves for a specific player
     /// Player 1 sees their own moves but player 2's moves are redacted (and vice versa)
+    #[cfg(feature = "server-helpers")]
     pub fn redact_for_player(&self, player: PlayerSymbol) -> Self {
         let redacted_rounds = self.rounds.iter().map(|(p1_move, p2_move)| {
             match player {
@@ -131,6 +207,30 @@ impl RockPaperScissorsGameState {
 
         Self {
             rounds: redacted_rounds,
+            lizard_spock: self.lizard_spock,
+            best_of: self.best_of,
+        }
+    }
+
+    /// Redact moves for someone watching the match
+    /// Moves of an incomplete round are hidden for both players
+    #[cfg(feature = "server-helpers")]
+    pub fn redact_for_spectator(&self) -> Self {
+        let redacted_rounds = self.rounds.iter().map(|(p1_move, p2_move)| {
+            if p1_move.is_some() && p2_move.is_some() {
+                (*p1_move, *p2_move)
+            } else {
+                (
+                    p1_move.map(|_| RockPaperScissorsMove::Redacted),
+                    p2_move.map(|_| RockPaperScissorsMove::Redacted),
+                )
+            }
+        }).collect();
+
+        Self {
+            rounds: redacted_rounds,
+            lizard_spock: self.lizard_spock,
+            best_of: self.best_of,
         }
     }
 
@@ -1,37 +1,114 @@
+#[cfg(feature = "server-helpers")]
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::games::players::PlayerSymbol;
+
 pub type TitTacToeCellState = i32;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+pub const MIN_BOARD_SIZE: usize = 3;
+pub const MAX_BOARD_SIZE: usize = 7;
+
+fn default_board_size() -> usize {
+    MIN_BOARD_SIZE
+}
+
+/// Options selectable when queueing for Tic-Tac-Toe
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "server-helpers", derive(JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct TicTacToeOptions {
+    /// Side length of the square board
+    #[serde(default = "default_board_size")]
+    #[cfg_attr(feature = "server-helpers", schemars(range(min = 3, max = 7)))]
+    pub board_size: usize,
+    /// Marks in a row needed to win, defaults to the board size
+    #[serde(default)]
+    #[cfg_attr(feature = "server-helpers", schemars(range(min = 3, max = 7)))]
+    pub win_length: Option<usize>,
+}
+
+impl Default for TicTacToeOptions {
+    fn default() -> Self {
+        Self {
+            board_size: MIN_BOARD_SIZE,
+            win_length: None,
+        }
+    }
+}
+
+impl TicTacToeOptions {
+    /// Validate options, filling in the win length if missing
+    pub fn normalized(&self) -> Result<Self, String> {
+        if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&self.board_size) {
+            return Err(format!("Board size must be between {MIN_BOARD_SIZE} and {MAX_BOARD_SIZE}"));
+        }
+        let win_length = self.win_length.unwrap_or(self.board_size);
+        if !(MIN_BOARD_SIZE..=self.board_size).contains(&win_length) {
+            return Err(format!("Win length must be between {MIN_BOARD_SIZE} and {}", self.board_size));
+        }
+        Ok(Self {
+            board_size: self.board_size,
+            win_length: Some(win_length),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct TicTacToeGameState {
-    pub board: [TitTacToeCellState; 9],
-    pub current_player: i32,
-    pub winner: Option<i32>,
+    /// Row-major board cells (0 = empty, 1 = player1, 2 = player2)
+    pub board: Vec<TitTacToeCellState>,
+    pub current_player: PlayerSymbol,
+    pub winner: Option<PlayerSymbol>,
     pub is_finished: bool,
+    #[serde(default = "default_board_size")]
+    pub board_size: usize,
+    #[serde(default = "default_board_size")]
+    pub win_length: usize,
 }
 
 impl TicTacToeGameState {
     pub fn new() -> Self {
+        Self::with_size(MIN_BOARD_SIZE, MIN_BOARD_SIZE)
+    }
+
+    /// Create an empty board of the given size and win length
+    pub fn with_size(board_size: usize, win_length: usize) -> Self {
         Self {
-            board: [0; 9],
+            board: vec![0; board_size * board_size],
             current_player: 1,
             winner: None,
             is_finished: false,
+            board_size,
+            win_length,
         }
     }
 
+    /// Create an empty board from validated options
+    pub fn from_options(options: &TicTacToeOptions) -> Result<Self, String> {
+        let options = options.normalized()?;
+        Ok(Self::with_size(options.board_size, options.win_length.unwrap_or(options.board_size)))
+    }
+
+    /// Redact game state for a specific player
+    /// TicTacToe doesn't need redaction (all info is public), so returns clone
+    #[cfg(feature = "server-helpers")]
+    pub fn redact_for_player(&self, _player: PlayerSymbol) -> Self {
+        self.clone()
+    }
+
     /// Convert row and column (0-indexed) to board index
-    pub fn coords_to_index(row: usize, col: usize) -> Option<usize> {
-        if row < 3 && col < 3 {
-            Some(row * 3 + col)
+    pub fn coords_to_index(&self, row: usize, col: usize) -> Option<usize> {
+        if row < self.board_size && col < self.board_size {
+            Some(row * self.board_size + col)
         } else {
             None
         }
     }
 
     /// Place a move on the board
-    pub fn place_move(&mut self, index: usize, player: i32) -> Result<(), String> {
-        if index >= 9 {
+    pub fn place_move(&mut self, index: usize, player: PlayerSymbol) -> Result<(), String> {
+        if index >= self.board.len() {
             return Err("Invalid cell index".to_string());
         }
         if self.board[index] != 0 {
@@ -45,20 +122,29 @@ impl TicTacToeGameState {
     }
 
     /// Check if there's a winner. Returns Some(player_num) if there's a winner, None otherwise
-    pub fn check_winner(&self) -> Option<i32> {
-        // Winning combinations
-        let wins = [
-            [0, 1, 2], [3, 4, 5], [6, 7, 8], // rows
-            [0, 3, 6], [1, 4, 7], [2, 5, 8], // columns
-            [0, 4, 8], [2, 4, 6],            // diagonals
-        ];
-
-        for win in &wins {
-            let [a, b, c] = *win;
-            if self.board[a] != 0
-                && self.board[a] == self.board[b]
-                && self.board[b] == self.board[c] {
-                return Some(self.board[a]);
+    pub fn check_winner(&self) -> Option<PlayerSymbol> {
+        let size = self.board_size as isize;
+        let directions = [(0, 1), (1, 0), (1, 1), (1, -1)];
+
+        for row in 0..size {
+            for col in 0..size {
+                let player = self.board[(row * size + col) as usize];
+                if player == 0 {
+                    continue;
+                }
+
+                for (row_step, col_step) in directions {
+                    let in_a_row = (0..self.win_length as isize)
+                        .map(|step| (row + row_step * step, col + col_step * step))
+                        .take_while(|&(r, c)| {
+                            r >= 0 && r < size && c >= 0 && c < size
+                                && self.board[(r * size + c) as usize] == player
+                        })
+                        .count();
+                    if in_a_row == self.win_length {
+                        return Some(player);
+                    }
+                }
             }
         }
         None
@@ -92,14 +178,23 @@ mod tests {
 
     #[test]
     fn test_coords_to_index() {
-        assert_eq!(TicTacToeGameState::coords_to_index(0, 0), Some(0));
-        assert_eq!(TicTacToeGameState::coords_to_index(0, 1), Some(1));
-        assert_eq!(TicTacToeGameState::coords_to_index(0, 2), Some(2));
-        assert_eq!(TicTacToeGameState::coords_to_index(1, 0), Some(3));
-        assert_eq!(TicTacToeGameState::coords_to_index(1, 1), Some(4));
-        assert_eq!(TicTacToeGameState::coords_to_index(2, 2), Some(8));
-        assert_eq!(TicTacToeGameState::coords_to_index(3, 0), None);
-        assert_eq!(TicTacToeGameState::coords_to_index(0, 3), None);
+        let state = TicTacToeGameState::new();
+        assert_eq!(state.coords_to_index(0, 0), Some(0));
+        assert_eq!(state.coords_to_index(0, 1), Some(1));
+        assert_eq!(state.coords_to_index(0, 2), Some(2));
+        assert_eq!(state.coords_to_index(1, 0), Some(3));
+        assert_eq!(state.coords_to_index(1, 1), Some(4));
+        assert_eq!(state.coords_to_index(2, 2), Some(8));
+        assert_eq!(state.coords_to_index(3, 0), None);
+        assert_eq!(state.coords_to_index(0, 3), None);
+    }
+
+    #[test]
+    fn test_coords_to_index_larger_board() {
+        let state = TicTacToeGameState::with_size(5, 4);
+        assert_eq!(state.coords_to_index(1, 0), Some(5));
+        assert_eq!(state.coords_to_index(4, 4), Some(24));
+        assert_eq!(state.coords_to_index(5, 0), None);
     }
 
     #[test]
@@ -115,30 +210,72 @@ mod tests {
     #[test]
     fn test_check_winner_row() {
         let mut state = TicTacToeGameState::new();
-        state.board = [1, 1, 1, 0, 0, 0, 0, 0, 0];
+        state.board = vec![1, 1, 1, 0, 0, 0, 0, 0, 0];
         assert_eq!(state.check_winner(), Some(1));
     }
 
     #[test]
     fn test_check_winner_column() {
         let mut state = TicTacToeGameState::new();
-        state.board = [2, 0, 0, 2, 0, 0, 2, 0, 0];
+        state.board = vec![2, 0, 0, 2, 0, 0, 2, 0, 0];
         assert_eq!(state.check_winner(), Some(2));
     }
 
     #[test]
     fn test_check_winner_diagonal() {
         let mut state = TicTacToeGameState::new();
-        state.board = [1, 0, 0, 0, 1, 0, 0, 0, 1];
+        state.board = vec![1, 0, 0, 0, 1, 0, 0, 0, 1];
+        assert_eq!(state.check_winner(), Some(1));
+    }
+
+    #[test]
+    fn test_check_winner_connect_k() {
+        let mut state = TicTacToeGameState::with_size(5, 4);
+        for col in 1..4 {
+            state.board[2 * 5 + col] = 1;
+        }
+        assert_eq!(state.check_winner(), None);
+
+        state.board[2 * 5 + 4] = 1;
         assert_eq!(state.check_winner(), Some(1));
     }
 
+    #[test]
+    fn test_check_winner_anti_diagonal_larger_board() {
+        let mut state = TicTacToeGameState::with_size(4, 4);
+        for i in 0..4 {
+            state.board[i * 4 + (3 - i)] = 2;
+        }
+        assert_eq!(state.check_winner(), Some(2));
+    }
+
     #[test]
     fn test_is_full() {
         let mut state = TicTacToeGameState::new();
         assert!(!state.is_full());
 
-        state.board = [1, 2, 1, 2, 1, 2, 2, 1, 2];
+        state.board = vec![1, 2, 1, 2, 1, 2, 2, 1, 2];
         assert!(state.is_full());
     }
+
+    #[test]
+    fn test_options_normalized() {
+        let options = TicTacToeOptions { board_size: 5, win_length: None };
+        assert_eq!(options.normalized().unwrap().win_length, Some(5));
+
+        let options = TicTacToeOptions { board_size: 5, win_length: Some(4) };
+        assert_eq!(options.normalized().unwrap().win_length, Some(4));
+
+        assert!(TicTacToeOptions { board_size: 2, win_length: None }.normalized().is_err());
+        assert!(TicTacToeOptions { board_size: 8, win_length: None }.normalized().is_err());
+        assert!(TicTacToeOptions { board_size: 4, win_length: Some(5) }.normalized().is_err());
+    }
+
+    #[test]
+    fn test_legacy_state_deserializes_as_3x3() {
+        let json = r#"{"board":[0,0,0,0,0,0,0,0,0],"current_player":1,"winner":null,"is_finished":false}"#;
+        let state = TicTacToeGameState::from_json(json).unwrap();
+        assert_eq!(state.board_size, 3);
+        assert_eq!(state.win_length, 3);
+    }
 }
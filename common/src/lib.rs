@@ -3,9 +3,11 @@ mod player;
 
 pub mod games;
 pub mod api;
+pub mod errors;
 pub mod utils;
 
 pub use auth::*;
 pub use player::Player;
 pub use api::*;
+pub use errors::{ErrorCode, ErrorParams, Language};
 pub use utils::time;
@@ -7,4 +7,10 @@ pub struct Player {
     pub public_key: String,
     pub name: String,
     pub score: i64,
+    /// When the name last changed, none if it never did
+    #[serde(default)]
+    pub name_changed_at: Option<i64>,
+    /// When any setting, the name included, last changed
+    #[serde(default)]
+    pub settings_changed_at: Option<i64>,
 }
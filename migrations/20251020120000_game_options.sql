@@ -0,0 +1,2 @@
+-- Options chosen when queueing (e.g. board size), matched exactly by matchmaking
+ALTER TABLE matches ADD COLUMN game_options TEXT NOT NULL DEFAULT 'null';
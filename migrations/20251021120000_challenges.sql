@@ -0,0 +1,15 @@
+-- Direct challenges between players, kept until answered or expired
+CREATE TABLE IF NOT EXISTS challenges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    challenger_id INTEGER NOT NULL,
+    challenged_id INTEGER NOT NULL,
+    game_type TEXT NOT NULL,
+    game_options TEXT NOT NULL DEFAULT 'null',
+    status TEXT NOT NULL DEFAULT 'pending',
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    FOREIGN KEY (challenger_id) REFERENCES players (id),
+    FOREIGN KEY (challenged_id) REFERENCES players (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_challenges_challenged_status ON challenges (challenged_id, status);
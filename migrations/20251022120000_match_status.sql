@@ -0,0 +1,11 @@
+-- Explicit match lifecycle status, replacing inference from in_progress/player2_id/outcome
+ALTER TABLE matches ADD COLUMN status TEXT NOT NULL DEFAULT 'active';
+
+UPDATE matches SET status = CASE
+    WHEN in_progress = 1 AND player2_id IS NULL THEN 'waiting'
+    WHEN in_progress = 1 THEN 'active'
+    WHEN outcome IS NOT NULL THEN 'finished'
+    ELSE 'aborted'
+END;
+
+CREATE INDEX IF NOT EXISTS idx_matches_status ON matches (status);
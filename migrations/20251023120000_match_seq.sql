@@ -0,0 +1,2 @@
+-- Counter bumped on every change to a match, lets polling clients skip unchanged state
+ALTER TABLE matches ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
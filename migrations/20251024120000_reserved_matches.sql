@@ -0,0 +1,2 @@
+-- Waiting matches opened by a party member, only their partner can join them
+ALTER TABLE matches ADD COLUMN reserved_for INTEGER REFERENCES players (id);
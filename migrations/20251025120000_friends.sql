@@ -0,0 +1,9 @@
+-- Players a player has added as friends, one row per direction
+CREATE TABLE IF NOT EXISTS friends (
+    player_id INTEGER NOT NULL,
+    friend_id INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (player_id, friend_id),
+    FOREIGN KEY (player_id) REFERENCES players (id),
+    FOREIGN KEY (friend_id) REFERENCES players (id)
+);
@@ -0,0 +1,17 @@
+-- Every state a match went through, so finished matches can be replayed
+CREATE TABLE IF NOT EXISTS match_frames (
+    match_id INTEGER NOT NULL,
+    frame INTEGER NOT NULL,
+    player_id INTEGER,
+    game_state TEXT NOT NULL,
+    PRIMARY KEY (match_id, frame),
+    FOREIGN KEY (match_id) REFERENCES matches (id),
+    FOREIGN KEY (player_id) REFERENCES players (id)
+);
+
+-- Secret part of the shareable replay URL, set once the match is finished
+ALTER TABLE matches ADD COLUMN replay_token TEXT;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_matches_replay_token ON matches (replay_token);
+
+-- Who may watch replays of the player's matches: public, friends or private
+ALTER TABLE players ADD COLUMN replay_privacy TEXT NOT NULL DEFAULT 'public';
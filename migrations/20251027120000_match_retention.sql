@@ -0,0 +1,10 @@
+-- When a match finished and when its state was compacted by the retention policy
+ALTER TABLE matches ADD COLUMN finished_at INTEGER;
+ALTER TABLE matches ADD COLUMN compacted_at INTEGER;
+
+UPDATE matches SET finished_at = CAST(strftime('%s', 'now') AS INTEGER) WHERE status = 'finished';
+
+CREATE INDEX IF NOT EXISTS idx_matches_finished_at ON matches (finished_at);
+
+-- The move that led to each recorded state, kept when the states are compacted
+ALTER TABLE match_frames ADD COLUMN move_data TEXT;
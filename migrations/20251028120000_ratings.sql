@@ -0,0 +1,4 @@
+-- Glicko rating, its deviation and how many rated games it is based on
+ALTER TABLE players ADD COLUMN rating REAL NOT NULL DEFAULT 1500;
+ALTER TABLE players ADD COLUMN rating_deviation REAL NOT NULL DEFAULT 350;
+ALTER TABLE players ADD COLUMN rated_games INTEGER NOT NULL DEFAULT 0;
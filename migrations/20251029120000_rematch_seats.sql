@@ -0,0 +1,2 @@
+-- Previous match of a party series, seats alternate from one match to the next
+ALTER TABLE matches ADD COLUMN rematch_of INTEGER REFERENCES matches(id);
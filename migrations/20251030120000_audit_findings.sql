@@ -0,0 +1,13 @@
+-- Suspicious patterns found by the collusion analysis, one row per kind and pair of players
+CREATE TABLE IF NOT EXISTS audit_findings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    player1_id INTEGER NOT NULL,
+    player2_id INTEGER NOT NULL,
+    details TEXT NOT NULL,
+    detected_at INTEGER NOT NULL,
+    UNIQUE (kind, player1_id, player2_id),
+    FOREIGN KEY (player1_id) REFERENCES players (id),
+    FOREIGN KEY (player2_id) REFERENCES players (id)
+);
@@ -0,0 +1,6 @@
+-- How each player wants times shown, times are stored as UTC seconds regardless
+ALTER TABLE players ADD COLUMN utc_offset_minutes INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE players ADD COLUMN date_style TEXT NOT NULL DEFAULT 'iso';
+
+-- When a match last changed, shown when offering to resume it
+ALTER TABLE matches ADD COLUMN last_move_at INTEGER;
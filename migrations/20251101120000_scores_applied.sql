@@ -0,0 +1,3 @@
+-- Set in the same transaction as the score and rating changes of a finished match, so they apply exactly once
+ALTER TABLE matches ADD COLUMN scores_applied INTEGER NOT NULL DEFAULT 0;
+UPDATE matches SET scores_applied = 1 WHERE status = 'finished' AND outcome IS NOT NULL;
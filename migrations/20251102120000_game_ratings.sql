@@ -0,0 +1,13 @@
+-- Glicko rating of a player in a single game type, next to the overall one on players
+-- game_type holds the same JSON as matches.game_type, ratings start fresh from the first rated match
+CREATE TABLE player_game_ratings (
+    player_id INTEGER NOT NULL,
+    game_type TEXT NOT NULL,
+    rating REAL NOT NULL DEFAULT 1500,
+    rating_deviation REAL NOT NULL DEFAULT 350,
+    rated_games INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (player_id, game_type),
+    FOREIGN KEY (player_id) REFERENCES players (id)
+);
+
+CREATE INDEX idx_player_game_ratings_leaderboard ON player_game_ratings (game_type, rating DESC);
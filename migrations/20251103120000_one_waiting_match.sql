@@ -0,0 +1,6 @@
+-- A player waits in matchmaking at most once per game type, older duplicates left by unclean disconnects are aborted
+UPDATE matches SET status = 'aborted', in_progress = 0
+WHERE status = 'waiting'
+  AND id NOT IN (SELECT MAX(id) FROM matches WHERE status = 'waiting' GROUP BY player1_id, game_type);
+
+CREATE UNIQUE INDEX idx_matches_one_waiting ON matches (player1_id, game_type) WHERE status = 'waiting';
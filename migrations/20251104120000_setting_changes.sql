@@ -0,0 +1,13 @@
+-- Who changed which player-visible setting of a player, from what to what and when
+CREATE TABLE IF NOT EXISTS setting_changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    player_id INTEGER NOT NULL,
+    changed_by INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    old_value TEXT,
+    new_value TEXT NOT NULL,
+    changed_at INTEGER NOT NULL,
+    FOREIGN KEY (player_id) REFERENCES players (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_setting_changes_player ON setting_changes (player_id, changed_at);
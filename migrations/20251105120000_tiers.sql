@@ -0,0 +1,2 @@
+-- Ladder tier of the overall rating, NULL while unranked
+ALTER TABLE players ADD COLUMN tier TEXT;
@@ -0,0 +1,2 @@
+-- Player whose draw offer is waiting for the opponent's answer
+ALTER TABLE matches ADD COLUMN draw_offered_by INTEGER;
@@ -0,0 +1,21 @@
+-- Timed events where players are paired continuously in one game
+CREATE TABLE arenas (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    game_type TEXT NOT NULL,
+    starts_at INTEGER NOT NULL,
+    ends_at INTEGER NOT NULL,
+    finished INTEGER NOT NULL DEFAULT 0
+);
+
+-- Standings, kept once the arena ended
+CREATE TABLE arena_players (
+    arena_id INTEGER NOT NULL REFERENCES arenas(id),
+    player_id INTEGER NOT NULL REFERENCES players(id),
+    points INTEGER NOT NULL DEFAULT 0,
+    games INTEGER NOT NULL DEFAULT 0,
+    wins INTEGER NOT NULL DEFAULT 0,
+    streak INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (arena_id, player_id)
+);
+
+ALTER TABLE matches ADD COLUMN arena_id INTEGER REFERENCES arenas(id);
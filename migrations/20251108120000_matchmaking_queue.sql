@@ -0,0 +1,4 @@
+-- When a player started waiting in matchmaking, the rating window widens from there
+ALTER TABLE matches ADD COLUMN queued_at INTEGER;
+
+UPDATE matches SET queued_at = CAST(strftime('%s', 'now') AS INTEGER) WHERE status = 'waiting';
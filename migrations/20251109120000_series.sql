@@ -0,0 +1,17 @@
+-- Best-of-N series, played as a run of matches between the same two players
+CREATE TABLE series (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    game_type TEXT NOT NULL,
+    game_options TEXT NOT NULL DEFAULT 'null',
+    length INTEGER NOT NULL,
+    player1_id INTEGER NOT NULL REFERENCES players(id),
+    player2_id INTEGER NOT NULL REFERENCES players(id),
+    player1_wins INTEGER NOT NULL DEFAULT 0,
+    player2_wins INTEGER NOT NULL DEFAULT 0,
+    draws INTEGER NOT NULL DEFAULT 0,
+    finished INTEGER NOT NULL DEFAULT 0
+);
+
+-- Series length asked for while waiting in matchmaking, and the series a match is part of
+ALTER TABLE matches ADD COLUMN series_length INTEGER;
+ALTER TABLE matches ADD COLUMN series_id INTEGER REFERENCES series(id);
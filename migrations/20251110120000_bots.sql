@@ -0,0 +1,2 @@
+-- Accounts played by the server, used to fill in for opponents nobody else would face in time
+ALTER TABLE players ADD COLUMN is_bot INTEGER NOT NULL DEFAULT 0;
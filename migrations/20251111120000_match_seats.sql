@@ -0,0 +1,27 @@
+-- Everyone seated in a match, so games can seat more than two players
+-- Seats 1 and 2 follow player1_id and player2_id, which two-player games keep using
+CREATE TABLE match_seats (
+    match_id INTEGER NOT NULL,
+    seat INTEGER NOT NULL,
+    player_id INTEGER NOT NULL,
+    PRIMARY KEY (match_id, seat)
+);
+CREATE INDEX idx_match_seats_player ON match_seats (player_id);
+
+INSERT INTO match_seats (match_id, seat, player_id) SELECT id, 1, player1_id FROM matches WHERE player1_id IS NOT NULL;
+INSERT INTO match_seats (match_id, seat, player_id) SELECT id, 2, player2_id FROM matches WHERE player2_id IS NOT NULL;
+
+CREATE TRIGGER match_seats_on_insert AFTER INSERT ON matches BEGIN
+    INSERT INTO match_seats (match_id, seat, player_id) SELECT NEW.id, 1, NEW.player1_id WHERE NEW.player1_id IS NOT NULL;
+    INSERT INTO match_seats (match_id, seat, player_id) SELECT NEW.id, 2, NEW.player2_id WHERE NEW.player2_id IS NOT NULL;
+END;
+
+CREATE TRIGGER match_seats_on_update AFTER UPDATE OF player1_id, player2_id ON matches BEGIN
+    DELETE FROM match_seats WHERE match_id = NEW.id AND seat IN (1, 2);
+    INSERT INTO match_seats (match_id, seat, player_id) SELECT NEW.id, 1, NEW.player1_id WHERE NEW.player1_id IS NOT NULL;
+    INSERT INTO match_seats (match_id, seat, player_id) SELECT NEW.id, 2, NEW.player2_id WHERE NEW.player2_id IS NOT NULL;
+END;
+
+CREATE TRIGGER match_seats_on_delete AFTER DELETE ON matches BEGIN
+    DELETE FROM match_seats WHERE match_id = OLD.id;
+END;
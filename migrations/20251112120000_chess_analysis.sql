@@ -0,0 +1,6 @@
+-- Engine evaluation of every move of finished chess matches, shown with their replays
+CREATE TABLE match_analysis (
+    match_id INTEGER PRIMARY KEY,
+    annotations TEXT NOT NULL, -- JSON list of MoveAnnotation
+    analyzed_at INTEGER NOT NULL
+);
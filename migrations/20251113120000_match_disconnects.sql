@@ -0,0 +1,6 @@
+-- Players who dropped out of a match and until when they can come back, so the grace period survives restarts
+CREATE TABLE match_disconnects (
+    player_id INTEGER PRIMARY KEY,
+    match_id INTEGER NOT NULL,
+    deadline INTEGER NOT NULL
+);
@@ -0,0 +1,2 @@
+-- When a player does not want to be notified, as JSON in their time zone
+ALTER TABLE players ADD COLUMN quiet_hours TEXT;
@@ -0,0 +1,21 @@
+-- Stretches of time the score leaderboard is reset after
+CREATE TABLE seasons (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    starts_at INTEGER NOT NULL,
+    ends_at INTEGER NOT NULL,
+    archived INTEGER NOT NULL DEFAULT 0
+);
+
+-- Final standings, kept once the season ended
+CREATE TABLE season_standings (
+    season_id INTEGER NOT NULL REFERENCES seasons(id),
+    player_id INTEGER NOT NULL REFERENCES players(id),
+    rank INTEGER NOT NULL,
+    score INTEGER NOT NULL,
+    rating REAL NOT NULL,
+    rated_games INTEGER NOT NULL,
+    tier TEXT,
+    PRIMARY KEY (season_id, player_id)
+);
+
+CREATE INDEX idx_season_standings_rank ON season_standings(season_id, rank);
@@ -0,0 +1,7 @@
+-- How far each player got in the quests they made progress in, ids name the day or week they ran
+CREATE TABLE quest_progress (
+    player_id INTEGER NOT NULL REFERENCES players(id),
+    quest_id TEXT NOT NULL,
+    progress INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (player_id, quest_id)
+);
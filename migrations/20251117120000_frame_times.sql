@@ -0,0 +1,2 @@
+-- When each state was reached, UTC seconds, unknown for frames recorded before
+ALTER TABLE match_frames ADD COLUMN created_at INTEGER;
@@ -0,0 +1,3 @@
+-- Guests met in quick play by a guest who then registered, stored so that their matches can be
+-- They have no usable public key, so nobody can sign in as one, and are left out of player searches and seasons
+ALTER TABLE players ADD COLUMN is_guest INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,177 @@
+use battld_common::games::{
+    briscola::{BriscolaGameState, BriscolaMove, BriscolaOptions},
+    chess::{ChessGameState, ChessMove, ChessPosition},
+    game_type::GameType,
+    matches::{Match, MatchStatus},
+    rock_paper_scissors::{RockPaperScissorsGameState, RockPaperScissorsMove},
+    tic_tac_toe::TicTacToeGameState,
+};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use server::{
+    game_router,
+    games::{
+        briscola::BriscolaGameEngine,
+        chess::ChessEngine,
+        rock_paper_scissors::RockPaperScissorsEngine,
+        tic_tac_toe::{TicTacToeEngine, TicTacToeMove},
+        GameEngine,
+    },
+};
+
+fn chess_move(from: &str, to: &str) -> ChessMove {
+    ChessMove {
+        from: ChessPosition::from_algebraic(from).unwrap(),
+        to: ChessPosition::from_algebraic(to).unwrap(),
+        promotion: None,
+    }
+}
+
+/// A middlegame position with most pieces still on the board
+fn chess_middlegame() -> ChessGameState {
+    let engine = ChessEngine::new();
+    let opening = [
+        ("e2", "e4"), ("e7", "e5"),
+        ("g1", "f3"), ("b8", "c6"),
+        ("f1", "c4"), ("g8", "f6"),
+        ("d2", "d3"), ("f8", "c5"),
+    ];
+
+    opening.iter().enumerate().fold(ChessGameState::new(), |state, (index, (from, to))| {
+        let player = if index % 2 == 0 { 1 } else { 2 };
+        engine.update(&state, player, &chess_move(from, to)).unwrap()
+    })
+}
+
+fn match_with_state(game_type: GameType, game_state: serde_json::Value) -> Match {
+    Match {
+        id: 1,
+        player1_id: 1,
+        player2_id: 2,
+        seats: vec![],
+        in_progress: true,
+        status: MatchStatus::Active,
+        outcome: None,
+        game_type,
+        game_state,
+        players: vec![],
+        ephemeral: false,
+        draw_offered_by: None,
+        seq: 0,
+    }
+}
+
+fn bench_tic_tac_toe(c: &mut Criterion) {
+    let engine = TicTacToeEngine::new();
+    let state = TicTacToeGameState::new();
+    let game_move = TicTacToeMove { row: 1, col: 1 };
+
+    c.bench_function("tic_tac_toe/apply_move", |b| {
+        b.iter(|| engine.update(black_box(&state), 1, black_box(&game_move)).unwrap())
+    });
+    c.bench_function("tic_tac_toe/serialize", |b| {
+        b.iter(|| serde_json::to_string(black_box(&state)).unwrap())
+    });
+}
+
+fn bench_rock_paper_scissors(c: &mut Criterion) {
+    let engine = RockPaperScissorsEngine;
+    let state = RockPaperScissorsGameState::new();
+
+    c.bench_function("rock_paper_scissors/apply_move", |b| {
+        b.iter(|| engine.update(black_box(&state), 1, &RockPaperScissorsMove::Rock).unwrap())
+    });
+}
+
+fn bench_briscola(c: &mut Criterion) {
+    let engine = BriscolaGameEngine;
+    let mut state = BriscolaGameEngine::new_game(&BriscolaOptions::default());
+    state.current_player = 1;
+
+    let play_card = BriscolaMove::PlayCard { card_index: 0 };
+
+    c.bench_function("briscola/apply_move", |b| {
+        b.iter(|| engine.update(black_box(&state), 1, &play_card).unwrap())
+    });
+    c.bench_function("briscola/apply_move_in_place", |b| {
+        b.iter_batched_ref(|| state.clone(), |state| engine.apply(state, 1, &play_card).unwrap(), BatchSize::SmallInput)
+    });
+    c.bench_function("briscola/legal_moves", |b| {
+        b.iter(|| engine.legal_moves(black_box(&state), 1))
+    });
+
+    let serialized = serde_json::to_string(&state).unwrap();
+    c.bench_function("briscola/serialize", |b| {
+        b.iter(|| serde_json::to_string(black_box(&state)).unwrap())
+    });
+    c.bench_function("briscola/deserialize", |b| {
+        b.iter(|| serde_json::from_str::<BriscolaGameState>(black_box(&serialized)).unwrap())
+    });
+}
+
+fn bench_chess(c: &mut Criterion) {
+    let engine = ChessEngine::new();
+    let state = chess_middlegame();
+    let game_move = chess_move("c1", "g5");
+
+    c.bench_function("chess/apply_move", |b| {
+        b.iter(|| engine.update(black_box(&state), 1, black_box(&game_move)).unwrap())
+    });
+    c.bench_function("chess/apply_move_in_place", |b| {
+        b.iter_batched_ref(|| state.clone(), |state| engine.apply(state, 1, &game_move).unwrap(), BatchSize::SmallInput)
+    });
+    c.bench_function("chess/legal_moves", |b| {
+        b.iter(|| engine.legal_moves(black_box(&state), 1))
+    });
+
+    let serialized = serde_json::to_string(&state).unwrap();
+    c.bench_function("chess/serialize", |b| {
+        b.iter(|| serde_json::to_string(black_box(&state)).unwrap())
+    });
+    c.bench_function("chess/deserialize", |b| {
+        b.iter(|| serde_json::from_str::<ChessGameState>(black_box(&serialized)).unwrap())
+    });
+}
+
+fn bench_redaction(c: &mut Criterion) {
+    let briscola = BriscolaGameEngine::new_game(&BriscolaOptions::default());
+    let briscola_match = match_with_state(GameType::Briscola, serde_json::to_value(&briscola).unwrap());
+    let chess_match = match_with_state(GameType::Chess, serde_json::to_value(chess_middlegame()).unwrap());
+
+    c.bench_function("redaction/briscola_player", |b| {
+        b.iter(|| game_router::redact_match_for_player(black_box(&briscola_match), 1))
+    });
+    c.bench_function("redaction/briscola_spectator", |b| {
+        b.iter(|| game_router::redact_match_for_spectator(black_box(&briscola_match)))
+    });
+    c.bench_function("redaction/chess_player", |b| {
+        b.iter(|| game_router::redact_match_for_player(black_box(&chess_match), 1))
+    });
+}
+
+fn bench_router(c: &mut Criterion) {
+    let mut briscola = BriscolaGameEngine::new_game(&BriscolaOptions::default());
+    briscola.current_player = 1;
+    let briscola_match = match_with_state(GameType::Briscola, serde_json::to_value(&briscola).unwrap());
+    let chess_match = match_with_state(GameType::Chess, serde_json::to_value(chess_middlegame()).unwrap());
+
+    c.bench_function("router/briscola_move", |b| {
+        b.iter(|| game_router::handle_game_move(black_box(&briscola_match), 1, serde_json::json!({ "card_index": 0 })).unwrap())
+    });
+    c.bench_function("router/chess_move", |b| {
+        b.iter(|| {
+            let move_data = serde_json::to_value(chess_move("c1", "g5")).unwrap();
+            game_router::handle_game_move(black_box(&chess_match), 1, move_data).unwrap()
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_tic_tac_toe,
+    bench_rock_paper_scissors,
+    bench_briscola,
+    bench_chess,
+    bench_redaction,
+    bench_router
+);
+criterion_main!(benches);
@@ -0,0 +1,591 @@
+use axum::{extract::{Path, State}, http::{HeaderMap, StatusCode}, Json};
+use battld_common::games::game_type::GameType;
+use battld_common::games::matches::{Match, MatchOutcome};
+use battld_common::{ArenaDetails, ArenaInfo, ArenaStanding, ErrorCode, NewArena, ServerMessage};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::capacity::{self, Capacity};
+use crate::database::{ArenaRecord, ArenaStandingRecord, Database};
+use crate::events::{EventBus, MatchEvent};
+use crate::game_logic::{self, OutgoingMessage};
+use crate::websocket::SharedRegistry;
+use crate::{auth, game_router, AppState};
+
+const WIN_POINTS: i64 = 2;
+const DRAW_POINTS: i64 = 1;
+
+/// Wins in a row after which every further win scores double
+const STREAK_BONUS_FROM: i64 = 2;
+
+const DEFAULT_DURATION_SECS: i64 = 2 * 60 * 60;
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long before its start an arena is announced, players may join it from then on
+const ANNOUNCE_BEFORE_SECS: i64 = 5 * 60;
+
+/// Arenas listed by `GET /arenas`
+const LISTED_ARENAS: i64 = 20;
+
+#[derive(Default)]
+struct ArenaState {
+    /// Player -> arena they are playing in
+    members: HashMap<i64, i64>,
+    /// Arena -> members waiting for their next opponent, longest waiting first
+    waiting: HashMap<i64, Vec<i64>>,
+    /// Arenas already announced, each is announced once
+    announced: HashSet<i64>,
+}
+
+/// Who is playing in which arena and who is waiting to be paired
+/// Standings live in the database, membership only lasts while the player stays connected
+#[derive(Default)]
+pub struct Arenas {
+    state: Mutex<ArenaState>,
+}
+
+impl Arenas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn arena_of(&self, player_id: i64) -> Option<i64> {
+        self.state.lock().unwrap().members.get(&player_id).copied()
+    }
+
+    /// Make a player a member of an arena, leaving the one they were in
+    pub fn join(&self, arena_id: i64, player_id: i64) {
+        self.leave(player_id);
+        self.state.lock().unwrap().members.insert(player_id, arena_id);
+    }
+
+    /// Stop pairing a player, returning the arena they left
+    pub fn leave(&self, player_id: i64) -> Option<i64> {
+        let mut state = self.state.lock().unwrap();
+        let arena_id = state.members.remove(&player_id)?;
+        if let Some(waiting) = state.waiting.get_mut(&arena_id) {
+            waiting.retain(|id| *id != player_id);
+        }
+        Some(arena_id)
+    }
+
+    /// Put a member at the back of their arena's queue, returning the arena
+    pub fn enqueue(&self, player_id: i64) -> Option<i64> {
+        let mut state = self.state.lock().unwrap();
+        let arena_id = *state.members.get(&player_id)?;
+        let waiting = state.waiting.entry(arena_id).or_default();
+        if !waiting.contains(&player_id) {
+            waiting.push(player_id);
+        }
+        Some(arena_id)
+    }
+
+    /// The two members of an arena who waited longest
+    fn take_pair(&self, arena_id: i64) -> Option<(i64, i64)> {
+        let mut state = self.state.lock().unwrap();
+        let waiting = state.waiting.get_mut(&arena_id).filter(|waiting| waiting.len() >= 2)?;
+        let pair = (waiting[0], waiting[1]);
+        waiting.drain(..2);
+        Some(pair)
+    }
+
+    /// Return players taken from the queue to its front, unless they left the arena meanwhile
+    fn put_back(&self, arena_id: i64, player_ids: &[i64]) {
+        let mut state = self.state.lock().unwrap();
+        let members: Vec<i64> = player_ids.iter().copied().filter(|id| state.members.get(id) == Some(&arena_id)).collect();
+        let waiting = state.waiting.entry(arena_id).or_default();
+        waiting.retain(|id| !members.contains(id));
+        waiting.splice(0..0, members);
+    }
+
+    /// Players in an arena, whether waiting or playing
+    fn members(&self, arena_id: i64) -> Vec<i64> {
+        let state = self.state.lock().unwrap();
+        state.members.iter().filter(|(_, id)| **id == arena_id).map(|(player_id, _)| *player_id).collect()
+    }
+
+    /// True the first time an arena is asked about, for announcing it once
+    fn mark_announced(&self, arena_id: i64) -> bool {
+        self.state.lock().unwrap().announced.insert(arena_id)
+    }
+
+    /// Drop every member of an arena that ended, returning them
+    fn close(&self, arena_id: i64) -> Vec<i64> {
+        let mut state = self.state.lock().unwrap();
+        state.waiting.remove(&arena_id);
+        let members: Vec<i64> = state.members.iter().filter(|(_, id)| **id == arena_id).map(|(player_id, _)| *player_id).collect();
+        for player_id in &members {
+            state.members.remove(player_id);
+        }
+        members
+    }
+}
+
+/// Points earned for a match and the streak that follows it, wins after a streak of `STREAK_BONUS_FROM` score double
+pub fn score(outcome: &MatchOutcome, won: bool, streak: i64) -> (i64, i64) {
+    match (outcome, won) {
+        (MatchOutcome::Draw, _) => (DRAW_POINTS, 0),
+        (_, true) if streak >= STREAK_BONUS_FROM => (WIN_POINTS * 2, streak + 1),
+        (_, true) => (WIN_POINTS, streak + 1),
+        (_, false) => (0, 0),
+    }
+}
+
+fn now() -> i64 {
+    battld_common::time() as i64
+}
+
+/// Whether players can join at `now`, from its announcement until it ends
+fn is_open(arena: &ArenaRecord, now: i64) -> bool {
+    !arena.finished && arena.starts_at - ANNOUNCE_BEFORE_SECS <= now && now < arena.ends_at
+}
+
+fn error(player_id: i64, code: ErrorCode) -> Vec<OutgoingMessage> {
+    vec![OutgoingMessage {
+        player_id,
+        message: ServerMessage::error(code, &[]),
+    }]
+}
+
+fn to_standings(records: Vec<ArenaStandingRecord>) -> Vec<ArenaStanding> {
+    records
+        .into_iter()
+        .enumerate()
+        .map(|(index, record)| ArenaStanding {
+            rank: index as i64 + 1,
+            player_id: record.player_id,
+            player_name: record.player_name,
+            points: record.points,
+            games: record.games,
+            wins: record.wins,
+            streak: record.streak,
+        })
+        .collect()
+}
+
+/// Enter an arena that is running or announced and wait to be paired once it runs - returns messages to send
+pub async fn handle_join_arena_logic(
+    player_id: i64,
+    arena_id: i64,
+    arenas: &Arenas,
+    capacity: &Capacity,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let Some(arena) = db.get_arena(arena_id).await.filter(|arena| is_open(arena, now())) else {
+        return error(player_id, ErrorCode::ArenaNotFound);
+    };
+    let Some(info) = arena.to_info() else {
+        return error(player_id, ErrorCode::ServerError);
+    };
+    if let Some(messages) = game_logic::current_match_logic(player_id, db).await {
+        return messages;
+    }
+    if let Err(e) = db.join_arena(arena_id, player_id).await {
+        println!("Player {player_id} could not join arena {arena_id}: {e}");
+        return error(player_id, ErrorCode::ServerError);
+    }
+
+    println!("Player {player_id} joined arena {arena_id}");
+    arenas.join(arena_id, player_id);
+    arenas.enqueue(player_id);
+    let mut messages = vec![OutgoingMessage {
+        player_id,
+        message: ServerMessage::ArenaJoined { arena: info.clone() },
+    }];
+    if arena.is_running(now()) {
+        messages.extend(pair_logic(arena_id, &info.game_type, arenas, capacity, events, db).await);
+    }
+    messages
+}
+
+/// Start a match for every two members waiting in an arena
+/// Players still in another match are dropped from the queue, they come back once it finishes
+pub async fn pair_logic(
+    arena_id: i64,
+    game_type: &GameType,
+    arenas: &Arenas,
+    capacity: &Capacity,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let mut messages = vec![];
+    let game_type_json = serde_json::to_string(game_type).unwrap();
+    let options = game_router::normalize_game_options(game_type, &serde_json::Value::Null).unwrap_or_default();
+
+    while let Some((p1_id, p2_id)) = arenas.take_pair(arena_id) {
+        let mut free = vec![];
+        for player_id in [p1_id, p2_id] {
+            if db.get_active_match_for_player(player_id).await.is_none() {
+                free.push(player_id);
+            }
+        }
+        if free.len() < 2 {
+            arenas.put_back(arena_id, &free);
+            continue;
+        }
+
+        let load = capacity::load_matches(db).await.unwrap_or_default();
+        if let Err(limit) = capacity.check_new_match(&free, game_type, &load) {
+            println!("Arena {arena_id} could not pair {p1_id} and {p2_id}: {limit:?}");
+            arenas.put_back(arena_id, &free);
+            break;
+        }
+
+        let game_state_json = game_router::initialize_game_state(game_type, &options);
+        let match_id = match db.create_match(p1_id, p2_id, &game_state_json, &game_type_json).await {
+            Ok(match_id) => match_id,
+            Err(e) => {
+                println!("Arena {arena_id} could not create a match: {e}");
+                break;
+            }
+        };
+        let _ = db.set_match_arena(match_id, arena_id).await;
+        let Some(match_info) = db.get_match_by_id(match_id).await.and_then(|record| record.to_match()) else {
+            continue;
+        };
+
+        println!("Arena {arena_id} paired {p1_id} and {p2_id} in match {match_id}");
+        events.publish(MatchEvent::MatchStarted { match_data: match_info.clone() });
+        messages.extend([p1_id, p2_id].into_iter().map(|id| OutgoingMessage {
+            player_id: id,
+            message: ServerMessage::MatchFound {
+                match_data: game_router::redact_match_for_player(&match_info, id),
+            },
+        }));
+    }
+    messages
+}
+
+/// Score a finished arena match and pair its players again
+/// Matches still being played when their arena ends do not count
+pub async fn match_finished_logic(
+    match_data: &Match,
+    arenas: &Arenas,
+    capacity: &Capacity,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let mut messages = vec![];
+    let now = now();
+
+    if let (Some(arena_id), Some(outcome)) = (db.get_match_arena(match_data.id).await, &match_data.outcome) {
+        let running = db.get_arena(arena_id).await.is_some_and(|arena| arena.is_running(now));
+        for player_id in [match_data.player1_id, match_data.player2_id].into_iter().filter(|_| running) {
+            let won = matches!(
+                (outcome, player_id == match_data.player1_id),
+                (MatchOutcome::Player1Win, true) | (MatchOutcome::Player2Win, false)
+            );
+            let Ok(previous) = db.get_arena_streak(arena_id, player_id).await else {
+                continue;
+            };
+            let (earned, streak) = score(outcome, won, previous);
+            match db.record_arena_result(arena_id, player_id, earned, won, streak).await {
+                Ok(points) => messages.push(OutgoingMessage {
+                    player_id,
+                    message: ServerMessage::ArenaScored { arena_id, earned, points, streak },
+                }),
+                Err(e) => println!("Failed to score player {player_id} in arena {arena_id}: {e}"),
+            }
+        }
+        if running {
+            let standings = to_standings(db.get_arena_standings(arena_id).await.unwrap_or_default());
+            messages.extend(arenas.members(arena_id).into_iter().map(|player_id| OutgoingMessage {
+                player_id,
+                message: ServerMessage::ArenaStandings { arena_id, standings: standings.clone() },
+            }));
+        }
+    }
+
+    let requeued: HashSet<i64> = [match_data.player1_id, match_data.player2_id]
+        .into_iter()
+        .filter_map(|player_id| arenas.enqueue(player_id))
+        .collect();
+    for arena_id in requeued {
+        let Some(info) = db.get_arena(arena_id).await.filter(|arena| arena.is_running(now)).and_then(|arena| arena.to_info()) else {
+            continue;
+        };
+        messages.extend(pair_logic(arena_id, &info.game_type, arenas, capacity, events, db).await);
+    }
+    messages
+}
+
+/// Finish arenas whose time is up and send the final standings to everyone who played
+pub async fn close_arenas_logic(arenas: &Arenas, db: &Database) -> Vec<OutgoingMessage> {
+    let mut messages = vec![];
+    for arena in db.get_overdue_arenas(now()).await.unwrap_or_default() {
+        if let Err(e) = db.finish_arena(arena.id).await {
+            println!("Failed to finish arena {}: {e}", arena.id);
+            continue;
+        }
+        let standings = to_standings(db.get_arena_standings(arena.id).await.unwrap_or_default());
+        println!("Arena {} ended with {} players", arena.id, standings.len());
+
+        let mut players: HashSet<i64> = arenas.close(arena.id).into_iter().collect();
+        players.extend(standings.iter().map(|standing| standing.player_id));
+        messages.extend(players.into_iter().map(|player_id| OutgoingMessage {
+            player_id,
+            message: ServerMessage::ArenaEnded { arena_id: arena.id, standings: standings.clone() },
+        }));
+    }
+    messages
+}
+
+/// Tell the connected players who are not in an arena about those starting soon, once per arena
+/// Guests can't play in arenas and are left out
+pub async fn announce_arenas_logic(arenas: &Arenas, connected: &[i64], db: &Database) -> Vec<OutgoingMessage> {
+    let now = now();
+    let mut messages = vec![];
+    for arena in db.get_arenas_starting_by(now + ANNOUNCE_BEFORE_SECS, now).await.unwrap_or_default() {
+        let Some(info) = arena.to_info() else { continue };
+        if !arenas.mark_announced(arena.id) {
+            continue;
+        }
+        println!("Announcing arena {} of {}", arena.id, info.game_type);
+        messages.extend(
+            connected
+                .iter()
+                .filter(|player_id| **player_id > 0 && arenas.arena_of(**player_id).is_none())
+                .map(|player_id| OutgoingMessage {
+                    player_id: *player_id,
+                    message: ServerMessage::ArenaAnnounced { arena: info.clone() },
+                }),
+        );
+    }
+    messages
+}
+
+/// Score and pair again the players of every finished arena match, announce arenas starting soon,
+/// close arenas when their time is up and retry pairings that the capacity limits held back
+pub fn spawn_arenas(arenas: Arc<Arenas>, capacity: Arc<Capacity>, db: Arc<Database>, registry: SharedRegistry) {
+    let mut rx = registry.events().subscribe();
+    let (subscriber_arenas, subscriber_capacity, subscriber_db, subscriber_registry) =
+        (arenas.clone(), capacity.clone(), db.clone(), registry.clone());
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(MatchEvent::MatchFinished { match_data }) => {
+                    let messages = match_finished_logic(
+                        &match_data,
+                        &subscriber_arenas,
+                        &subscriber_capacity,
+                        subscriber_registry.events(),
+                        &subscriber_db,
+                    ).await;
+                    subscriber_registry.send_messages(messages).await;
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    println!("Arenas fell behind, {missed} events skipped");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let messages = close_arenas_logic(&arenas, &db).await;
+            registry.send_messages(messages).await;
+            let messages = announce_arenas_logic(&arenas, &registry.connected_players().await, &db).await;
+            registry.send_messages(messages).await;
+
+            for arena in db.get_running_arenas(now()).await.unwrap_or_default() {
+                let Some(info) = arena.to_info() else { continue };
+                let messages = pair_logic(info.id, &info.game_type, &arenas, &capacity, registry.events(), &db).await;
+                registry.send_messages(messages).await;
+            }
+        }
+    });
+}
+
+/// Running and upcoming arenas, then the latest ones that ended
+pub async fn get_arenas(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ArenaInfo>>, StatusCode> {
+    auth::authenticate_request(&state.session_cache, &headers).await?;
+    state.db.get_recent_arenas(LISTED_ARENAS)
+        .await
+        .map(|arenas| Json(arenas.iter().filter_map(|arena| arena.to_info()).collect()))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// An arena with its live or final standings
+pub async fn get_arena(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<Json<ArenaDetails>, StatusCode> {
+    auth::authenticate_request(&state.session_cache, &headers).await?;
+    let arena = state.db.get_arena(id).await.and_then(|arena| arena.to_info()).ok_or(StatusCode::NOT_FOUND)?;
+    let standings = state.db.get_arena_standings(id).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(ArenaDetails { arena, standings: to_standings(standings) }))
+}
+
+/// Schedule an arena, admins only
+pub async fn post_arena(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(new_arena): Json<NewArena>,
+) -> Result<Json<ArenaInfo>, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+    if !state.capacity.is_admin(player_id) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let duration = new_arena.duration_secs.unwrap_or(DEFAULT_DURATION_SECS);
+    if duration <= 0 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let starts_at = new_arena.starts_at.unwrap_or_else(now);
+    let game_type_json = serde_json::to_string(&new_arena.game_type).unwrap();
+
+    let id = state.db.create_arena(&game_type_json, starts_at, starts_at + duration)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    println!("Player {player_id} scheduled arena {id} of {}", new_arena.game_type);
+    state.db.get_arena(id).await.and_then(|arena| arena.to_info()).map(Json).ok_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::matches::MatchEndReason;
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str) -> i64 {
+        db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name).await.unwrap()
+    }
+
+    async fn create_test_arena(db: &Database, starts_at: i64, ends_at: i64) -> i64 {
+        db.create_arena(&serde_json::to_string(&GameType::TicTacToe).unwrap(), starts_at, ends_at).await.unwrap()
+    }
+
+    fn match_found(messages: &[OutgoingMessage]) -> Option<Match> {
+        messages.iter().find_map(|m| match &m.message {
+            ServerMessage::MatchFound { match_data } => Some(match_data.clone()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_streaks_score_double() {
+        assert_eq!(score(&MatchOutcome::Player1Win, true, 0), (2, 1));
+        assert_eq!(score(&MatchOutcome::Player1Win, true, 1), (2, 2));
+        assert_eq!(score(&MatchOutcome::Player1Win, true, 2), (4, 3));
+        assert_eq!(score(&MatchOutcome::Draw, false, 5), (1, 0));
+        assert_eq!(score(&MatchOutcome::Player2Win, false, 5), (0, 0));
+    }
+
+    #[test]
+    fn test_members_are_paired_longest_waiting_first() {
+        let arenas = Arenas::new();
+        for player_id in [1, 2, 3] {
+            arenas.join(7, player_id);
+            arenas.enqueue(player_id);
+        }
+        assert_eq!(arenas.take_pair(7), Some((1, 2)));
+        assert_eq!(arenas.take_pair(7), None);
+
+        assert_eq!(arenas.leave(3), Some(7));
+        assert_eq!(arenas.enqueue(3), None);
+        arenas.join(8, 1);
+        assert_eq!(arenas.close(7), vec![2]);
+        assert_eq!(arenas.arena_of(1), Some(8));
+    }
+
+    #[tokio::test]
+    async fn test_arena_pairs_scores_and_ends() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let arena_id = create_test_arena(&db, now() - 60, now() + 3600).await;
+        let arenas = Arenas::new();
+        let capacity = Capacity::default();
+        let events = EventBus::new();
+
+        let messages = handle_join_arena_logic(p1, arena_id, &arenas, &capacity, &events, &db).await;
+        assert!(matches!(&messages[..], [OutgoingMessage { message: ServerMessage::ArenaJoined { .. }, .. }]));
+        let messages = handle_join_arena_logic(p2, arena_id, &arenas, &capacity, &events, &db).await;
+        let match_data = match_found(&messages).unwrap();
+        assert_eq!(db.get_match_arena(match_data.id).await, Some(arena_id));
+
+        let messages = game_logic::finish_match_logic(
+            match_data.clone(),
+            MatchOutcome::Player1Win,
+            MatchEndReason::Resignation,
+            None,
+            &events,
+            &db,
+        ).await;
+        assert!(!messages.is_empty());
+        let finished = db.get_match_by_id(match_data.id).await.unwrap().to_match().unwrap();
+        let messages = match_finished_logic(&finished, &arenas, &capacity, &events, &db).await;
+        assert!(messages.iter().any(|m| m.player_id == finished.player1_id
+            && matches!(m.message, ServerMessage::ArenaScored { earned: 2, points: 2, streak: 1, .. })));
+        assert!(match_found(&messages).is_some(), "Players are paired again");
+        let live: Vec<_> = messages.iter().filter(|m| matches!(&m.message, ServerMessage::ArenaStandings { standings, .. } if standings.len() == 2)).collect();
+        assert_eq!(live.len(), 2);
+
+        let standings = to_standings(db.get_arena_standings(arena_id).await.unwrap());
+        assert_eq!(standings[0].player_id, finished.player1_id);
+        assert_eq!((standings[0].points, standings[1].points), (2, 0));
+
+        sqlx::query("UPDATE arenas SET ends_at = ? WHERE id = ?").bind(now()).bind(arena_id).execute(db.pool()).await.unwrap();
+        let messages = close_arenas_logic(&arenas, &db).await;
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(&messages[0].message, ServerMessage::ArenaEnded { standings, .. } if standings.len() == 2));
+        assert_eq!(arenas.arena_of(p1), None);
+        assert!(close_arenas_logic(&arenas, &db).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cannot_join_an_arena_before_it_is_announced() {
+        let db = create_test_db().await;
+        let player_id = create_test_player(&db, "player").await;
+        let later = create_test_arena(&db, now() + ANNOUNCE_BEFORE_SECS + 60, now() + 3600).await;
+        let arenas = Arenas::new();
+
+        let messages = handle_join_arena_logic(player_id, later, &arenas, &Capacity::default(), &EventBus::new(), &db).await;
+        assert!(matches!(&messages[0].message, ServerMessage::Error { code: ErrorCode::ArenaNotFound, .. }));
+        assert_eq!(arenas.arena_of(player_id), None);
+    }
+
+    #[tokio::test]
+    async fn test_announced_arenas_pair_once_they_start() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let p3 = create_test_player(&db, "player3").await;
+        let arena_id = create_test_arena(&db, now() + 60, now() + 3600).await;
+        create_test_arena(&db, now() + ANNOUNCE_BEFORE_SECS + 60, now() + 3600).await;
+        let arenas = Arenas::new();
+        let capacity = Capacity::default();
+        let events = EventBus::new();
+
+        arenas.join(99, p3);
+        let messages = announce_arenas_logic(&arenas, &[p1, p2, p3, -1], &db).await;
+        assert_eq!(messages.iter().map(|m| m.player_id).collect::<Vec<_>>(), vec![p1, p2]);
+        assert!(matches!(&messages[0].message, ServerMessage::ArenaAnnounced { arena } if arena.id == arena_id));
+        assert!(announce_arenas_logic(&arenas, &[p1, p2], &db).await.is_empty());
+
+        handle_join_arena_logic(p1, arena_id, &arenas, &capacity, &events, &db).await;
+        let messages = handle_join_arena_logic(p2, arena_id, &arenas, &capacity, &events, &db).await;
+        assert!(matches!(&messages[..], [OutgoingMessage { message: ServerMessage::ArenaJoined { .. }, .. }]));
+        assert_eq!(arenas.arena_of(p2), Some(arena_id));
+
+        sqlx::query("UPDATE arenas SET starts_at = ? WHERE id = ?").bind(now()).bind(arena_id).execute(db.pool()).await.unwrap();
+        let messages = pair_logic(arena_id, &GameType::TicTacToe, &arenas, &capacity, &events, &db).await;
+        assert!(match_found(&messages).is_some());
+    }
+}
@@ -4,7 +4,7 @@ use axum::{
 };
 use battld_common::*;
 
-use crate::repository;
+use crate::{log_privacy, repository};
 use crate::AppState;
 
 pub async fn create_player(
@@ -12,7 +12,7 @@ pub async fn create_player(
     Json(request): Json<CreatePlayerRequest>
 ) -> Result<Json<Player>, StatusCode> {
     let db = &state.db;
-    println!("API: Creating new player '{}'", request.name);
+    println!("API: Creating new player '{}'", log_privacy::name(&request.name));
 
     // Create player using repository
     let user_id = match repository::create_player(db, &request.name, &request.public_key_hint, &request.public_key).await {
@@ -35,7 +35,7 @@ pub async fn create_player(
         }
     };
 
-    println!("API: Successfully created player '{}' with ID {}", request.name, user_id);
+    println!("API: Successfully created player '{}' with ID {}", log_privacy::name(&request.name), user_id);
 
     Ok(Json(player))
 }
@@ -59,21 +59,30 @@ pub fn verify_signature_for_nonce(
     player: &crate::database::PlayerRecord,
     encrypted_token: &str,
     nonce: &str,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    verify_signature(&player.public_key, encrypted_token, nonce)
+}
+
+/// Whether `text` was signed with the private half of a PEM public key
+pub fn verify_signature(
+    public_key_pem: &str,
+    encrypted_token: &str,
+    text: &str,
 ) -> Result<bool, Box<dyn std::error::Error>> {
     use rsa::{RsaPublicKey, pkcs8::DecodePublicKey, pkcs1::DecodeRsaPublicKey, Pkcs1v15Sign};
     use rsa::sha2::Sha256;
     use base64::{Engine as _, engine::general_purpose};
     use sha2::Digest;
 
-    let public_key = match RsaPublicKey::from_pkcs1_pem(&player.public_key) {
+    let public_key = match RsaPublicKey::from_pkcs1_pem(public_key_pem) {
         Ok(key) => key,
-        Err(_) => RsaPublicKey::from_public_key_pem(&player.public_key)?,
+        Err(_) => RsaPublicKey::from_public_key_pem(public_key_pem)?,
     };
 
     let signature = general_purpose::STANDARD.decode(encrypted_token)?;
 
     let mut hasher = Sha256::new();
-    hasher.update(nonce.as_bytes());
+    hasher.update(text.as_bytes());
     let hashed = hasher.finalize();
 
     let padding = Pkcs1v15Sign::new::<Sha256>();
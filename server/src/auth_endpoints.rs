@@ -1,10 +1,10 @@
 use axum::{
     extract::{Json, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
 };
 use std::time::{SystemTime, UNIX_EPOCH};
 use subtle::ConstantTimeEq;
-use crate::{AppState, repository};
+use crate::{auth, log_privacy, AppState, repository};
 use battld_common::api::*;
 
 pub async fn request_challenge(
@@ -28,6 +28,49 @@ pub async fn request_challenge(
     }))
 }
 
+/// Find the player a key derived from a recovery phrase belongs to, and challenge the client to sign in with it
+pub async fn recover_account(
+    State(state): State<AppState>,
+    Json(request): Json<RecoveryRequest>,
+) -> Result<Json<RecoveryResponse>, StatusCode> {
+    let player_record = state.db.get_player_by_public_key(&request.public_key)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    println!("API: Recovery requested for player {}", player_record.id);
+    let nonce = state.nonce_cache.create_nonce(player_record.id).await;
+
+    Ok(Json(RecoveryResponse {
+        player_id: player_record.id,
+        nonce,
+        expires_in: 60,
+    }))
+}
+
+/// Bind a new key to the signed in player, such as one derived from a recovery phrase
+pub async fn rebind_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<RebindKeyRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+
+    let signed_text = RebindKeyRequest::signed_text(player_id);
+    if !auth::verify_signature(&request.public_key, &request.signature, &signed_text).unwrap_or(false) {
+        println!("Key rebinding rejected for player {player_id}, bad signature");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if state.db.get_player_by_public_key(&request.public_key).await.is_some_and(|holder| holder.id != player_id) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    state.db.set_player_key(player_id, &request.public_key_hint, &request.public_key, player_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    println!("API: Player {player_id} bound key {}", log_privacy::fingerprint(&request.public_key));
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn verify_challenge(
     State(state): State<AppState>,
     Json(request): Json<VerifyRequest>,
@@ -0,0 +1,314 @@
+use battld_common::games::game_type::GameType;
+use battld_common::games::matches::{Match, MatchOutcome, MatchStatus};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde_json::Value as JsonValue;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::capacity::{self, Capacity};
+use crate::database::Database;
+use crate::events::{EventBus, MatchEvent};
+use crate::game_logic::{self, OutgoingMessage};
+use crate::game_router;
+use crate::ready_check::ReadyChecks;
+use crate::websocket::SharedRegistry;
+
+/// Pause before a bot moves, so its moves don't land on the same frame as the player's
+const THINKING_TIME: Duration = Duration::from_millis(800);
+
+/// How well a bot plays, picked from the rating of the player it stands in for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BotLevel {
+    /// Any legal move
+    Easy,
+    /// Takes a win when it sees one
+    Medium,
+    /// Also avoids moves that hand the opponent a win on their next move
+    Hard,
+}
+
+impl BotLevel {
+    pub fn for_rating(rating: f64) -> Self {
+        if rating < 1400.0 {
+            BotLevel::Easy
+        } else if rating < 1600.0 {
+            BotLevel::Medium
+        } else {
+            BotLevel::Hard
+        }
+    }
+
+    /// Stored as the public key hint of the bot accounts of this level
+    pub fn key(&self) -> &'static str {
+        match self {
+            BotLevel::Easy => "bot:easy",
+            BotLevel::Medium => "bot:medium",
+            BotLevel::Hard => "bot:hard",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        [BotLevel::Easy, BotLevel::Medium, BotLevel::Hard].into_iter().find(|level| level.key() == key)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BotLevel::Easy => "Bot (easy)",
+            BotLevel::Medium => "Bot (medium)",
+            BotLevel::Hard => "Bot (hard)",
+        }
+    }
+}
+
+/// The match after `player_id` plays `game_move`, None if the move is not legal
+fn after_move(match_data: &Match, player_id: i64, game_move: &JsonValue) -> Option<Match> {
+    let result = game_router::handle_game_move(match_data, player_id, game_move.clone()).ok()?;
+    let mut next = match_data.clone();
+    next.game_state = result.new_state;
+    next.outcome = result.outcome;
+    Some(next)
+}
+
+fn won_by(match_data: &Match, player_id: i64) -> bool {
+    match match_data.outcome {
+        Some(MatchOutcome::Player1Win) => match_data.player1_id == player_id,
+        Some(MatchOutcome::Player2Win) => match_data.player2_id == player_id,
+        Some(MatchOutcome::Draw) | None => false,
+    }
+}
+
+/// Whether the opponent can win with their next move
+fn opponent_wins_next(match_data: &Match, bot_id: i64) -> bool {
+    let opponent_id = if match_data.player1_id == bot_id { match_data.player2_id } else { match_data.player1_id };
+    game_router::players_to_move(match_data).contains(&opponent_id)
+        && game_router::legal_moves(match_data, opponent_id)
+            .iter()
+            .filter_map(|reply| after_move(match_data, opponent_id, reply))
+            .any(|next| won_by(&next, opponent_id))
+}
+
+/// The move the bot makes, None when it has nothing to play
+pub fn choose_move(match_data: &Match, bot_id: i64, level: BotLevel, rng: &mut impl Rng) -> Option<JsonValue> {
+    let moves = game_router::legal_moves(match_data, bot_id);
+    if level == BotLevel::Easy {
+        return moves.choose(rng).cloned();
+    }
+
+    let outcomes: Vec<(JsonValue, Match)> = moves
+        .into_iter()
+        .filter_map(|game_move| after_move(match_data, bot_id, &game_move).map(|next| (game_move, next)))
+        .collect();
+    if let Some((game_move, _)) = outcomes.iter().find(|(_, next)| won_by(next, bot_id)) {
+        return Some(game_move.clone());
+    }
+    if level == BotLevel::Hard {
+        let safe: Vec<&JsonValue> = outcomes
+            .iter()
+            .filter(|(_, next)| !opponent_wins_next(next, bot_id))
+            .map(|(game_move, _)| game_move)
+            .collect();
+        if let Some(game_move) = safe.choose(rng) {
+            return Some((*game_move).clone());
+        }
+    }
+    outcomes.choose(rng).map(|(game_move, _)| game_move.clone())
+}
+
+/// Seat a bot against every player who has been waiting in matchmaking for `after` - returns messages to send
+/// Players in a ready check are left alone, they have an opponent already
+pub async fn backfill_logic(
+    after: Duration,
+    now: i64,
+    capacity: &Capacity,
+    ready_checks: &ReadyChecks,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let pool = db.get_matchmaking_pool().await.unwrap_or_default();
+    let mut messages = vec![];
+    for waiting in pool {
+        if now - waiting.queued_at < after.as_secs() as i64 || ready_checks.is_pending(waiting.player_id) {
+            continue;
+        }
+        let Ok(game_type) = serde_json::from_str::<GameType>(&waiting.game_type) else { continue };
+        let options: JsonValue = serde_json::from_str(&waiting.game_options).unwrap_or_default();
+        let load = capacity::load_matches(db).await.unwrap_or_default();
+        if capacity.check_new_match(&[waiting.player_id], &game_type, &load).is_err() {
+            continue;
+        }
+
+        let level = BotLevel::for_rating(waiting.rating);
+        let bot_id = match db.get_idle_bot(level.key(), level.name()).await {
+            Ok(bot_id) => bot_id,
+            Err(e) => {
+                println!("Failed to find a bot for player {}: {e}", waiting.player_id);
+                continue;
+            }
+        };
+        println!("Player {} waited too long, matching them with bot {bot_id} for game type: {game_type}", waiting.player_id);
+        if let Some(sent) = game_logic::start_matched_game(waiting.match_id, &[waiting.player_id, bot_id], &game_type, &options, events, db).await {
+            messages.extend(sent.into_iter().filter(|m| m.player_id != bot_id));
+        }
+    }
+    messages
+}
+
+/// Make the bot's move in its current match, if it is its turn - returns messages to send
+pub async fn play_turn_logic(bot_id: i64, level: BotLevel, events: &EventBus, db: &Database) -> Vec<OutgoingMessage> {
+    let Some(match_data) = db
+        .get_active_match_for_player(bot_id)
+        .await
+        .and_then(|record| record.to_match())
+        .filter(|match_data| match_data.status == MatchStatus::Active)
+    else {
+        return vec![];
+    };
+    if !game_router::players_to_move(&match_data).contains(&bot_id) {
+        return vec![];
+    }
+    let Some(game_move) = choose_move(&match_data, bot_id, level, &mut rand::thread_rng()) else {
+        return vec![];
+    };
+    game_logic::handle_make_move_logic(bot_id, game_move, events, db)
+        .await
+        .into_iter()
+        .filter(|m| m.player_id != bot_id)
+        .collect()
+}
+
+/// Bots in the match that have to move next
+fn bots_to_move(match_data: &Match) -> Vec<i64> {
+    let to_move = game_router::players_to_move(match_data);
+    match_data.players.iter().filter(|player| player.bot && to_move.contains(&player.id)).map(|player| player.id).collect()
+}
+
+/// Play the bots' moves as their matches start and move on
+pub fn spawn_bots(db: std::sync::Arc<Database>, registry: SharedRegistry) -> tokio::task::JoinHandle<()> {
+    let mut rx = registry.events().subscribe();
+    tokio::spawn(async move {
+        loop {
+            let match_data = match rx.recv().await {
+                Ok(MatchEvent::MatchStarted { match_data }) | Ok(MatchEvent::MoveMade { match_data, .. }) => match_data,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    println!("Bots fell behind, {missed} events skipped");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            for bot_id in bots_to_move(&match_data) {
+                let db = db.clone();
+                let registry = registry.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(THINKING_TIME).await;
+                    let Some(level) = db.get_player_by_id(bot_id).await.and_then(|bot| BotLevel::from_key(&bot.public_key_hint)) else {
+                        return;
+                    };
+                    let messages = play_turn_logic(bot_id, level, registry.events(), &db).await;
+                    registry.send_messages(messages).await;
+                });
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::game_type::GameType;
+    use battld_common::games::matches::MatchPlayer;
+    use battld_common::ServerMessage;
+    use serde_json::json;
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    fn tic_tac_toe(board: [i32; 9], current_player: i32) -> Match {
+        Match {
+            id: 1,
+            player1_id: 10,
+            player2_id: 20,
+            seats: vec![],
+            in_progress: true,
+            status: MatchStatus::Active,
+            outcome: None,
+            game_type: GameType::TicTacToe,
+            game_state: json!({ "board": board, "current_player": current_player, "is_finished": false }),
+            players: vec![MatchPlayer { id: 20, name: BotLevel::Hard.name().to_string(), score: 0, bot: true, rating: None }],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
+        }
+    }
+
+    #[test]
+    fn test_levels_follow_the_rating() {
+        assert_eq!(BotLevel::for_rating(1200.0), BotLevel::Easy);
+        assert_eq!(BotLevel::for_rating(1500.0), BotLevel::Medium);
+        assert_eq!(BotLevel::for_rating(1900.0), BotLevel::Hard);
+        assert_eq!(BotLevel::from_key(BotLevel::Hard.key()), Some(BotLevel::Hard));
+        assert_eq!(BotLevel::from_key("hint"), None);
+    }
+
+    #[test]
+    fn test_bot_takes_a_win_and_blocks_one() {
+        let mut rng = rand::thread_rng();
+        // The bot, O, can complete the middle row
+        let winning = tic_tac_toe([1, 0, 1, 2, 2, 0, 1, 0, 0], 2);
+        for level in [BotLevel::Medium, BotLevel::Hard] {
+            assert_eq!(choose_move(&winning, 20, level, &mut rng), Some(json!({ "row": 1, "col": 2 })));
+        }
+
+        // X threatens the top row
+        let threatened = tic_tac_toe([1, 1, 0, 0, 2, 0, 0, 0, 0], 2);
+        for _ in 0..10 {
+            assert_eq!(choose_move(&threatened, 20, BotLevel::Hard, &mut rng), Some(json!({ "row": 0, "col": 2 })));
+        }
+        assert!(choose_move(&threatened, 20, BotLevel::Easy, &mut rng).is_some());
+        assert_eq!(bots_to_move(&threatened), vec![20]);
+    }
+
+    #[tokio::test]
+    async fn test_long_waits_are_backfilled_with_an_unranked_bot() {
+        let db = create_test_db().await;
+        let player = db.create_player("player_hint", "player_key", "player").await.unwrap();
+        let game_type = serde_json::to_string(&GameType::TicTacToe).unwrap();
+        let match_id = db.create_waiting_match(player, &game_type, "null", None).await.unwrap();
+        let queued_at = battld_common::time() as i64;
+        let after = Duration::from_secs(30);
+        let (capacity, ready_checks, events) = (Capacity::default(), ReadyChecks::default(), EventBus::new());
+        let backfill = |now| backfill_logic(after, now, &capacity, &ready_checks, &events, &db);
+
+        assert!(backfill(queued_at + 10).await.is_empty());
+        let messages = backfill(queued_at + 30).await;
+        assert_eq!(messages.len(), 1);
+        let ServerMessage::MatchFound { match_data } = &messages[0].message else {
+            panic!("Expected MatchFound, got {:?}", messages[0].message);
+        };
+        assert_eq!(match_data.id, match_id);
+        let bot = match_data.player(match_data.player2_id).unwrap();
+        assert!(bot.bot);
+        assert_eq!(bot.name, BotLevel::Medium.name());
+
+        // Either may go first, the bot answers once it is its turn
+        if game_router::players_to_move(match_data) == vec![player] {
+            game_logic::handle_make_move_logic(player, json!({ "row": 0, "col": 0 }), &EventBus::new(), &db).await;
+        }
+        let messages = play_turn_logic(bot.id, BotLevel::Medium, &EventBus::new(), &db).await;
+        assert!(messages.iter().all(|m| m.player_id == player));
+        assert!(messages.iter().any(|m| matches!(m.message, ServerMessage::MoveApplied { .. })));
+
+        game_logic::handle_resign_logic(player, &EventBus::new(), &db).await;
+        let finished = db.get_match_by_id(match_id).await.unwrap();
+        db.update_player_scores_from_match(&finished).await.unwrap();
+        let record = db.get_player_by_id(player).await.unwrap();
+        assert_eq!(record.score, 0, "Matches against bots are unranked");
+        assert_eq!(db.get_idle_bot(BotLevel::Medium.key(), BotLevel::Medium.name()).await.unwrap(), bot.id, "The bot is free again");
+    }
+}
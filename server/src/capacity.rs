@@ -0,0 +1,338 @@
+use axum::{extract::State, http::StatusCode, Json};
+use battld_common::{games::game_type::GameType, MatchmakingRejection, ServerMessage};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::database::Database;
+use crate::settings::Swap;
+use crate::AppState;
+
+const DEFAULT_RETRY_AFTER_SECS: u64 = 30;
+
+/// Server capacity limits, all optional (unset means unlimited)
+#[derive(Debug, Clone)]
+pub struct CapacityConfig {
+    /// Maximum number of authenticated WebSocket connections
+    pub max_connections: Option<usize>,
+    /// Maximum number of matches being played, across all games
+    pub max_concurrent_matches: Option<i64>,
+    /// Maximum number of players waiting for an opponent
+    pub max_queue_size: Option<i64>,
+    /// Maximum number of matches being played, per game
+    pub max_matches_per_game: HashMap<GameType, i64>,
+    /// Suggested wait before trying again, sent with `ServerBusy`
+    pub retry_after_secs: u64,
+    /// Players who are never turned away
+    pub admin_player_ids: HashSet<i64>,
+}
+
+impl Default for CapacityConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: None,
+            max_concurrent_matches: None,
+            max_queue_size: None,
+            max_matches_per_game: HashMap::new(),
+            retry_after_secs: DEFAULT_RETRY_AFTER_SECS,
+            admin_player_ids: HashSet::new(),
+        }
+    }
+}
+
+impl CapacityConfig {
+    /// Read from MAX_CONNECTIONS, MAX_CONCURRENT_MATCHES, MAX_QUEUE_SIZE,
+    /// MAX_MATCHES_PER_GAME (e.g. "Chess=20,TicTacToe=100"), SERVER_BUSY_RETRY_AFTER_SECS
+    /// and ADMIN_PLAYER_IDS (comma-separated)
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok();
+
+        let max_matches_per_game = var("MAX_MATCHES_PER_GAME")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| {
+                let (game, limit) = entry.split_once('=')?;
+                let game_type = serde_json::from_value(serde_json::Value::String(game.trim().to_string())).ok()?;
+                Some((game_type, limit.trim().parse().ok()?))
+            })
+            .collect();
+
+        let admin_player_ids = var("ADMIN_PLAYER_IDS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|id| id.trim().parse().ok())
+            .collect();
+
+        Self {
+            max_connections: var("MAX_CONNECTIONS").and_then(|v| v.parse().ok()),
+            max_concurrent_matches: var("MAX_CONCURRENT_MATCHES").and_then(|v| v.parse().ok()),
+            max_queue_size: var("MAX_QUEUE_SIZE").and_then(|v| v.parse().ok()),
+            max_matches_per_game,
+            retry_after_secs: var("SERVER_BUSY_RETRY_AFTER_SECS")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_RETRY_AFTER_SECS),
+            admin_player_ids,
+        }
+    }
+}
+
+/// Matches currently open on the server
+#[derive(Debug, Default, Clone)]
+pub struct MatchLoad {
+    pub active_by_game: HashMap<GameType, i64>,
+    pub waiting: i64,
+}
+
+impl MatchLoad {
+    pub fn active(&self) -> i64 {
+        self.active_by_game.values().sum()
+    }
+}
+
+/// Which limit turned a request away
+#[derive(Debug, Clone, PartialEq)]
+pub enum Limit {
+    Connections,
+    ConcurrentMatches,
+    GameMatches(GameType),
+    QueueSize,
+}
+
+/// Enforces the capacity limits and counts how often they kick in
+#[derive(Debug, Default)]
+pub struct Capacity {
+    config: Swap<CapacityConfig>,
+    rejected_connections: AtomicU64,
+    rejected_matches: AtomicU64,
+    rejected_queue: AtomicU64,
+}
+
+impl Capacity {
+    pub fn new(config: CapacityConfig) -> Self {
+        Self {
+            config: Swap::new(config),
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> Arc<CapacityConfig> {
+        self.config.load()
+    }
+
+    /// Apply new limits, the rejection counters carry on
+    pub fn reload(&self, config: CapacityConfig) {
+        self.config.store(config);
+    }
+
+    pub fn is_admin(&self, player_id: i64) -> bool {
+        self.config().admin_player_ids.contains(&player_id)
+    }
+
+    /// Check whether one more connection fits
+    pub fn check_connection(&self, player_id: i64, connections: usize) -> Result<(), Limit> {
+        if self.is_admin(player_id) {
+            return Ok(());
+        }
+        match self.config().max_connections {
+            Some(max) if connections >= max => {
+                self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+                Err(Limit::Connections)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check whether a new match of the given game can start
+    pub fn check_new_match(&self, player_ids: &[i64], game_type: &GameType, load: &MatchLoad) -> Result<(), Limit> {
+        if player_ids.iter().any(|id| self.is_admin(*id)) {
+            return Ok(());
+        }
+
+        let config = self.config();
+        let limit = if config.max_concurrent_matches.is_some_and(|max| load.active() >= max) {
+            Some(Limit::ConcurrentMatches)
+        } else {
+            let active = load.active_by_game.get(game_type).copied().unwrap_or(0);
+            config
+                .max_matches_per_game
+                .get(game_type)
+                .filter(|max| active >= **max)
+                .map(|_| Limit::GameMatches(game_type.clone()))
+        };
+
+        match limit {
+            Some(limit) => {
+                self.rejected_matches.fetch_add(1, Ordering::Relaxed);
+                Err(limit)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Check whether one more player can wait for an opponent
+    pub fn check_queue(&self, player_id: i64, load: &MatchLoad) -> Result<(), Limit> {
+        if self.is_admin(player_id) {
+            return Ok(());
+        }
+        match self.config().max_queue_size {
+            Some(max) if load.waiting >= max => {
+                self.rejected_queue.fetch_add(1, Ordering::Relaxed);
+                Err(Limit::QueueSize)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Message telling a player to come back later
+    pub fn busy_message(&self) -> ServerMessage {
+        ServerMessage::ServerBusy {
+            retry_after: self.config().retry_after_secs,
+        }
+    }
+
+    /// The same for a player turned away from matchmaking
+    pub fn full_rejection(&self) -> MatchmakingRejection {
+        MatchmakingRejection::ServerFull {
+            retry_after: self.config().retry_after_secs,
+        }
+    }
+}
+
+/// Current load against the configured limits
+#[derive(Serialize)]
+pub struct CapacityStatus {
+    pub connections: usize,
+    pub max_connections: Option<usize>,
+    pub active_matches: i64,
+    pub max_concurrent_matches: Option<i64>,
+    pub active_matches_by_game: HashMap<String, i64>,
+    pub max_matches_per_game: HashMap<String, i64>,
+    pub waiting_players: i64,
+    pub max_queue_size: Option<i64>,
+    pub rejected_connections: u64,
+    pub rejected_matches: u64,
+    pub rejected_queue: u64,
+}
+
+/// Load the open matches, grouped for the capacity checks
+pub async fn load_matches(db: &Database) -> Result<MatchLoad, sqlx::Error> {
+    let mut load = MatchLoad::default();
+    for (game_type, active, waiting) in db.count_open_matches().await? {
+        if let Ok(game_type) = serde_json::from_str::<GameType>(&game_type) {
+            *load.active_by_game.entry(game_type).or_default() += active;
+        }
+        load.waiting += waiting;
+    }
+    Ok(load)
+}
+
+/// Capacity metrics for monitoring
+pub async fn get_capacity(
+    State(state): State<AppState>,
+) -> Result<Json<CapacityStatus>, StatusCode> {
+    let load = load_matches(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let capacity = &state.capacity;
+    let config = capacity.config();
+    let by_name = |counts: &HashMap<GameType, i64>| {
+        counts.iter().map(|(game_type, count)| (format!("{game_type:?}"), *count)).collect()
+    };
+
+    Ok(Json(CapacityStatus {
+        connections: state.registry.connection_count().await,
+        max_connections: config.max_connections,
+        active_matches: load.active(),
+        max_concurrent_matches: config.max_concurrent_matches,
+        active_matches_by_game: by_name(&load.active_by_game),
+        max_matches_per_game: by_name(&config.max_matches_per_game),
+        waiting_players: load.waiting,
+        max_queue_size: config.max_queue_size,
+        rejected_connections: capacity.rejected_connections.load(Ordering::Relaxed),
+        rejected_matches: capacity.rejected_matches.load(Ordering::Relaxed),
+        rejected_queue: capacity.rejected_queue.load(Ordering::Relaxed),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    fn limited() -> Capacity {
+        Capacity::new(CapacityConfig {
+            max_connections: Some(2),
+            max_concurrent_matches: Some(3),
+            max_queue_size: Some(1),
+            max_matches_per_game: HashMap::from([(GameType::Chess, 1)]),
+            admin_player_ids: HashSet::from([42]),
+            ..Default::default()
+        })
+    }
+
+    fn load(tic_tac_toe: i64, chess: i64, waiting: i64) -> MatchLoad {
+        MatchLoad {
+            active_by_game: HashMap::from([(GameType::TicTacToe, tic_tac_toe), (GameType::Chess, chess)]),
+            waiting,
+        }
+    }
+
+    #[test]
+    fn test_unlimited_by_default() {
+        let capacity = Capacity::default();
+        assert!(capacity.check_connection(1, 10_000).is_ok());
+        assert!(capacity.check_new_match(&[1, 2], &GameType::Chess, &load(500, 500, 0)).is_ok());
+        assert!(capacity.check_queue(1, &load(0, 0, 500)).is_ok());
+    }
+
+    #[test]
+    fn test_limits_are_enforced_and_counted() {
+        let capacity = limited();
+
+        assert!(capacity.check_connection(1, 1).is_ok());
+        assert_eq!(capacity.check_connection(1, 2), Err(Limit::Connections));
+
+        assert!(capacity.check_new_match(&[1, 2], &GameType::TicTacToe, &load(1, 1, 0)).is_ok());
+        assert_eq!(capacity.check_new_match(&[1, 2], &GameType::TicTacToe, &load(2, 1, 0)), Err(Limit::ConcurrentMatches));
+        assert_eq!(
+            capacity.check_new_match(&[1, 2], &GameType::Chess, &load(0, 1, 0)),
+            Err(Limit::GameMatches(GameType::Chess))
+        );
+
+        assert!(capacity.check_queue(1, &load(0, 0, 0)).is_ok());
+        assert_eq!(capacity.check_queue(1, &load(0, 0, 1)), Err(Limit::QueueSize));
+
+        assert_eq!(capacity.rejected_connections.load(Ordering::Relaxed), 1);
+        assert_eq!(capacity.rejected_matches.load(Ordering::Relaxed), 2);
+        assert_eq!(capacity.rejected_queue.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_admins_bypass_limits() {
+        let capacity = limited();
+        assert!(capacity.check_connection(42, 100).is_ok());
+        assert!(capacity.check_new_match(&[1, 42], &GameType::Chess, &load(10, 10, 0)).is_ok());
+        assert!(capacity.check_queue(42, &load(0, 0, 10)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_load_matches() {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+
+        let p1 = db.create_player("p1_hint", "p1_key", "p1").await.unwrap();
+        let p2 = db.create_player("p2_hint", "p2_key", "p2").await.unwrap();
+        let p3 = db.create_player("p3_hint", "p3_key", "p3").await.unwrap();
+
+        let tic_tac_toe = serde_json::to_string(&GameType::TicTacToe).unwrap();
+        db.create_match(p1, p2, "{}", &tic_tac_toe).await.unwrap();
+        db.create_waiting_match(p3, &tic_tac_toe, "null", None).await.unwrap();
+
+        let load = load_matches(&db).await.unwrap();
+        assert_eq!(load.active(), 1);
+        assert_eq!(load.active_by_game.get(&GameType::TicTacToe), Some(&1));
+        assert_eq!(load.waiting, 1);
+    }
+}
@@ -0,0 +1,40 @@
+use axum::Json;
+use battld_common::{games::game_type::GameType, GameInfo};
+
+/// Every game with the schema of its options, so clients can build option pickers
+pub async fn get_games() -> Json<Vec<GameInfo>> {
+    Json(game_catalog())
+}
+
+fn game_catalog() -> Vec<GameInfo> {
+    GameType::ALL
+        .iter()
+        .map(|game_type| GameInfo {
+            game_type: game_type.clone(),
+            name: game_type.to_string(),
+            options_schema: game_type.options_schema(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_schemas() {
+        let catalog = game_catalog();
+        assert_eq!(catalog.len(), GameType::ALL.len());
+
+        let tic_tac_toe = &catalog.iter().find(|game| game.game_type == GameType::TicTacToe).unwrap().options_schema;
+        assert_eq!(tic_tac_toe["properties"]["board_size"]["minimum"], 3.0);
+        assert_eq!(tic_tac_toe["properties"]["board_size"]["maximum"], 7.0);
+        assert_eq!(tic_tac_toe["additionalProperties"], false);
+
+        let rock_paper_scissors = &catalog.iter().find(|game| game.game_type == GameType::RockPaperScissors).unwrap().options_schema;
+        assert_eq!(rock_paper_scissors["properties"]["best_of"]["default"], 3);
+
+        let chess = &catalog.iter().find(|game| game.game_type == GameType::Chess).unwrap().options_schema;
+        assert_eq!(chess["type"], "null");
+    }
+}
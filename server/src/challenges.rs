@@ -0,0 +1,428 @@
+use axum::{extract::State, http::{HeaderMap, StatusCode}, Json};
+use battld_common::{games::game_type::GameType, ErrorCode, MatchChallenge, ServerMessage};
+
+use crate::capacity::{self, Capacity};
+use crate::database::{ChallengeRecord, Database};
+use crate::game_logic::OutgoingMessage;
+use crate::events::{EventBus, MatchEvent};
+use crate::{auth, game_router, AppState};
+
+const DEFAULT_ONLINE_EXPIRY_SECS: i64 = 120;
+const DEFAULT_OFFLINE_EXPIRY_SECS: i64 = 24 * 60 * 60;
+
+/// How long challenges stay open, depending on whether the opponent is around to answer
+#[derive(Debug, Clone)]
+pub struct ChallengeConfig {
+    /// Expiry for challenges to players who are online
+    pub online_expiry_secs: i64,
+    /// Expiry for challenges queued for offline players
+    pub offline_expiry_secs: i64,
+}
+
+impl Default for ChallengeConfig {
+    fn default() -> Self {
+        Self {
+            online_expiry_secs: DEFAULT_ONLINE_EXPIRY_SECS,
+            offline_expiry_secs: DEFAULT_OFFLINE_EXPIRY_SECS,
+        }
+    }
+}
+
+impl ChallengeConfig {
+    /// Read from CHALLENGE_ONLINE_EXPIRY_SECS and CHALLENGE_OFFLINE_EXPIRY_SECS
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: i64| {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        };
+        Self {
+            online_expiry_secs: read("CHALLENGE_ONLINE_EXPIRY_SECS", DEFAULT_ONLINE_EXPIRY_SECS),
+            offline_expiry_secs: read("CHALLENGE_OFFLINE_EXPIRY_SECS", DEFAULT_OFFLINE_EXPIRY_SECS),
+        }
+    }
+}
+
+fn now() -> i64 {
+    battld_common::time() as i64
+}
+
+fn error(player_id: i64, code: ErrorCode, params: &[(&str, &str)]) -> Vec<OutgoingMessage> {
+    vec![OutgoingMessage {
+        player_id,
+        message: ServerMessage::error(code, params),
+    }]
+}
+
+/// Handle a challenge request - returns messages to send
+/// Online opponents get the challenge right away, offline ones on their next login
+pub async fn handle_challenge_player_logic(
+    challenger_id: i64,
+    challenged_id: i64,
+    game_type: GameType,
+    options: serde_json::Value,
+    challenged_online: bool,
+    config: &ChallengeConfig,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    if challenger_id == challenged_id {
+        return error(challenger_id, ErrorCode::SelfChallenge, &[]);
+    }
+
+    if db.get_player_by_id(challenged_id).await.is_none() {
+        return error(challenger_id, ErrorCode::PlayerNotFound, &[]);
+    }
+
+    let options = match game_router::normalize_game_options(&game_type, &options) {
+        Ok(options) => options,
+        Err(e) => return vec![OutgoingMessage { player_id: challenger_id, message: e.to_options_message() }],
+    };
+
+    if db.find_pending_challenge(challenger_id, challenged_id).await.is_some() {
+        return error(challenger_id, ErrorCode::ChallengePending, &[]);
+    }
+
+    let created_at = now();
+    let expires_at = created_at + if challenged_online {
+        config.online_expiry_secs
+    } else {
+        config.offline_expiry_secs
+    };
+
+    let game_type_json = serde_json::to_string(&game_type).unwrap();
+    let challenge = match db
+        .create_challenge(challenger_id, challenged_id, &game_type_json, &options.to_string(), created_at, expires_at)
+        .await
+    {
+        Ok(id) => db.get_challenge_by_id(id).await.and_then(|record| record.to_challenge()),
+        Err(e) => {
+            println!("Failed to create challenge from {challenger_id} to {challenged_id}: {e}");
+            None
+        }
+    };
+
+    let Some(challenge) = challenge else {
+        return error(challenger_id, ErrorCode::ServerError, &[]);
+    };
+
+    println!("Player {challenger_id} challenged player {challenged_id} (online: {challenged_online}) to {game_type}");
+
+    let mut messages = vec![OutgoingMessage {
+        player_id: challenger_id,
+        message: ServerMessage::ChallengeSent {
+            challenge: challenge.clone(),
+            delivered: challenged_online,
+        },
+    }];
+    if challenged_online {
+        messages.push(OutgoingMessage {
+            player_id: challenged_id,
+            message: ServerMessage::ChallengeReceived { challenge },
+        });
+    }
+    messages
+}
+
+/// Handle an answer to a challenge - returns messages to send
+/// Accepting starts the match right away, so the challenger has to be online
+pub async fn handle_respond_to_challenge_logic(
+    player_id: i64,
+    challenge_id: i64,
+    accept: bool,
+    challenger_online: bool,
+    capacity: &Capacity,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let record = match db.get_challenge_by_id(challenge_id).await {
+        Some(record) if record.challenged_id == player_id => record,
+        _ => return error(player_id, ErrorCode::ChallengeNotFound, &[]),
+    };
+
+    if record.status != "pending" {
+        return error(player_id, ErrorCode::ChallengeClosed, &[]);
+    }
+
+    if record.expires_at <= now() {
+        let _ = db.update_challenge_status(challenge_id, "expired").await;
+        return notify_both(&record, ServerMessage::ChallengeExpired { challenge_id });
+    }
+
+    if !accept {
+        if db.update_challenge_status(challenge_id, "declined").await.is_err() {
+            return error(player_id, ErrorCode::ServerError, &[]);
+        }
+        println!("Player {player_id} declined challenge {challenge_id}");
+        return notify_both(&record, ServerMessage::ChallengeDeclined { challenge_id });
+    }
+
+    if !challenger_online {
+        return error(player_id, ErrorCode::PlayerOffline, &[("name", &record.challenger_name)]);
+    }
+
+    for (id, name) in [(record.challenger_id, &record.challenger_name), (record.challenged_id, &record.challenged_name)] {
+        if db.get_active_match_for_player(id).await.is_some() {
+            return error(player_id, ErrorCode::PlayerBusy, &[("name", name)]);
+        }
+    }
+
+    let Some(challenge) = record.to_challenge() else {
+        return error(player_id, ErrorCode::ServerError, &[]);
+    };
+
+    let load = capacity::load_matches(db).await.unwrap_or_default();
+    if let Err(limit) = capacity.check_new_match(&[record.challenger_id, record.challenged_id], &challenge.game_type, &load) {
+        println!("Challenge {challenge_id} could not start: {limit:?}");
+        return vec![OutgoingMessage { player_id, message: capacity.busy_message() }];
+    }
+
+    let game_state_json = game_router::initialize_game_state(&challenge.game_type, &challenge.options);
+    let match_id = match db.create_match(record.challenger_id, record.challenged_id, &game_state_json, &record.game_type).await {
+        Ok(id) => id,
+        Err(e) => {
+            println!("Failed to create match for challenge {challenge_id}: {e}");
+            return error(player_id, ErrorCode::ServerError, &[]);
+        }
+    };
+    let _ = db.update_challenge_status(challenge_id, "accepted").await;
+
+    let Some(match_info) = db.get_match_by_id(match_id).await.and_then(|r| r.to_match()) else {
+        return error(player_id, ErrorCode::MatchUnavailable, &[]);
+    };
+
+    println!("Challenge {challenge_id} accepted, started match {match_id}");
+    events.publish(MatchEvent::MatchStarted { match_data: match_info.clone() });
+
+    [record.challenger_id, record.challenged_id]
+        .into_iter()
+        .map(|id| OutgoingMessage {
+            player_id: id,
+            message: ServerMessage::MatchFound {
+                match_data: game_router::redact_match_for_player(&match_info, id),
+            },
+        })
+        .collect()
+}
+
+/// Deliver the challenge inbox to a player who just came online
+/// Queued challenges now expire as if they were sent to an online player
+pub async fn handle_player_online_logic(
+    player_id: i64,
+    config: &ChallengeConfig,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let now = now();
+    let _ = db.cap_pending_challenge_expiry(player_id, now + config.online_expiry_secs).await;
+
+    db.get_pending_challenges_for_player(player_id, now)
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|record| record.to_challenge())
+        .map(|challenge| OutgoingMessage {
+            player_id,
+            message: ServerMessage::ChallengeReceived { challenge },
+        })
+        .collect()
+}
+
+/// Expire overdue challenges - returns messages to send to both sides
+pub async fn expire_challenges_logic(now: i64, db: &Database) -> Vec<OutgoingMessage> {
+    match db.expire_challenges(now).await {
+        Ok(expired) => expired
+            .iter()
+            .flat_map(|record| notify_both(record, ServerMessage::ChallengeExpired { challenge_id: record.id }))
+            .collect(),
+        Err(e) => {
+            println!("Failed to expire challenges: {e}");
+            vec![]
+        }
+    }
+}
+
+fn notify_both(record: &ChallengeRecord, message: ServerMessage) -> Vec<OutgoingMessage> {
+    [record.challenger_id, record.challenged_id]
+        .into_iter()
+        .map(|player_id| OutgoingMessage {
+            player_id,
+            message: message.clone(),
+        })
+        .collect()
+}
+
+/// Pending challenges addressed to the authenticated player
+pub async fn get_pending_challenges(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<MatchChallenge>>, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+
+    let records = state.db.get_pending_challenges_for_player(player_id, now())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(records.iter().filter_map(|r| r.to_challenge()).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str) -> i64 {
+        db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name).await.unwrap()
+    }
+
+    async fn challenge(db: &Database, from: i64, to: i64, online: bool) -> MatchChallenge {
+        let messages = handle_challenge_player_logic(
+            from, to, GameType::TicTacToe, serde_json::Value::Null, online, &ChallengeConfig::default(), db,
+        ).await;
+        match &messages[0].message {
+            ServerMessage::ChallengeSent { challenge, .. } => challenge.clone(),
+            other => panic!("Expected ChallengeSent, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_challenge_online_player_is_delivered() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "alice").await;
+        let p2 = create_test_player(&db, "bob").await;
+
+        let messages = handle_challenge_player_logic(
+            p1, p2, GameType::TicTacToe, serde_json::Value::Null, true, &ChallengeConfig::default(), &db,
+        ).await;
+
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(messages[0].message, ServerMessage::ChallengeSent { delivered: true, .. }));
+        match &messages[1].message {
+            ServerMessage::ChallengeReceived { challenge } => {
+                assert_eq!(messages[1].player_id, p2);
+                assert_eq!(challenge.challenger_name, "alice");
+                assert!(challenge.expires_at <= now() + DEFAULT_ONLINE_EXPIRY_SECS);
+            }
+            other => panic!("Expected ChallengeReceived, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_offline_challenge_is_queued_until_login() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "alice").await;
+        let p2 = create_test_player(&db, "bob").await;
+
+        let messages = handle_challenge_player_logic(
+            p1, p2, GameType::TicTacToe, serde_json::Value::Null, false, &ChallengeConfig::default(), &db,
+        ).await;
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0].message, ServerMessage::ChallengeSent { delivered: false, .. }));
+
+        let messages = handle_player_online_logic(p2, &ChallengeConfig::default(), &db).await;
+        assert_eq!(messages.len(), 1);
+        match &messages[0].message {
+            ServerMessage::ChallengeReceived { challenge } => {
+                assert_eq!(challenge.challenger_id, p1);
+                // Once the player is online the long offline expiry no longer applies
+                assert!(challenge.expires_at <= now() + DEFAULT_ONLINE_EXPIRY_SECS);
+            }
+            other => panic!("Expected ChallengeReceived, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_challenges_are_rejected() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "alice").await;
+        let p2 = create_test_player(&db, "bob").await;
+        let config = ChallengeConfig::default();
+
+        let messages = handle_challenge_player_logic(p1, p1, GameType::Chess, serde_json::Value::Null, true, &config, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { .. }));
+
+        let messages = handle_challenge_player_logic(p1, 999, GameType::Chess, serde_json::Value::Null, true, &config, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { .. }));
+
+        challenge(&db, p1, p2, true).await;
+        let messages = handle_challenge_player_logic(p1, p2, GameType::Chess, serde_json::Value::Null, true, &config, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_accept_challenge_starts_match() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "alice").await;
+        let p2 = create_test_player(&db, "bob").await;
+        let challenge = challenge(&db, p1, p2, true).await;
+
+        // Only the challenged player can answer
+        let messages = handle_respond_to_challenge_logic(p1, challenge.id, true, true, &Capacity::default(), &EventBus::new(), &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { .. }));
+
+        let messages = handle_respond_to_challenge_logic(p2, challenge.id, true, true, &Capacity::default(), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 2);
+        for msg in &messages {
+            match &msg.message {
+                ServerMessage::MatchFound { match_data } => {
+                    assert_eq!(match_data.player1_id, p1);
+                    assert_eq!(match_data.player2_id, p2);
+                    assert_eq!(match_data.game_type, GameType::TicTacToe);
+                }
+                other => panic!("Expected MatchFound, got {other:?}"),
+            }
+        }
+
+        let record = db.get_challenge_by_id(challenge.id).await.unwrap();
+        assert_eq!(record.status, "accepted");
+    }
+
+    #[tokio::test]
+    async fn test_accept_requires_challenger_online() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "alice").await;
+        let p2 = create_test_player(&db, "bob").await;
+        let challenge = challenge(&db, p1, p2, false).await;
+
+        let messages = handle_respond_to_challenge_logic(p2, challenge.id, true, false, &Capacity::default(), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0].message, ServerMessage::Error { .. }));
+        assert_eq!(db.get_challenge_by_id(challenge.id).await.unwrap().status, "pending");
+        assert!(db.get_active_match_for_player(p2).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_decline_challenge_notifies_both() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "alice").await;
+        let p2 = create_test_player(&db, "bob").await;
+        let challenge = challenge(&db, p1, p2, true).await;
+
+        let messages = handle_respond_to_challenge_logic(p2, challenge.id, false, true, &Capacity::default(), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| matches!(m.message, ServerMessage::ChallengeDeclined { .. })));
+
+        // Answering twice is rejected
+        let messages = handle_respond_to_challenge_logic(p2, challenge.id, true, true, &Capacity::default(), &EventBus::new(), &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_expire_challenges() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "alice").await;
+        let p2 = create_test_player(&db, "bob").await;
+        let challenge = challenge(&db, p1, p2, true).await;
+
+        assert!(expire_challenges_logic(now(), &db).await.is_empty());
+
+        let messages = expire_challenges_logic(challenge.expires_at, &db).await;
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| matches!(m.message, ServerMessage::ChallengeExpired { .. })));
+        assert_eq!(db.get_challenge_by_id(challenge.id).await.unwrap().status, "expired");
+        assert!(handle_player_online_logic(p2, &ChallengeConfig::default(), &db).await.is_empty());
+    }
+}
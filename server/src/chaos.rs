@@ -0,0 +1,182 @@
+use std::cell::Cell;
+use std::sync::OnceLock;
+
+/// Places in the move pipeline where chaos testing stops it, as if the server died right there
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrashPoint {
+    /// The move is validated but not saved
+    BeforeWrite,
+    /// The move is saved, but scores, events and messages are not done
+    AfterWrite,
+    /// Everything is saved and published, nobody was told yet
+    BeforeSend,
+}
+
+impl CrashPoint {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "before_write" => Some(CrashPoint::BeforeWrite),
+            "after_write" => Some(CrashPoint::AfterWrite),
+            "before_send" => Some(CrashPoint::BeforeSend),
+            _ => None,
+        }
+    }
+}
+
+thread_local! {
+    /// Armed by tests, drops the pipeline of the next move on this thread instead of killing the server
+    static ARMED: Cell<Option<CrashPoint>> = const { Cell::new(None) };
+}
+
+static FROM_ENV: OnceLock<Option<CrashPoint>> = OnceLock::new();
+
+/// Whether the pipeline stops at `point`, never in release builds
+/// CHAOS_CRASH_POINT (before_write, after_write or before_send) aborts the server the first time a move gets there,
+/// to try restarts by hand
+pub fn crash_at(point: CrashPoint) -> bool {
+    if !cfg!(debug_assertions) {
+        return false;
+    }
+    if ARMED.get() == Some(point) {
+        ARMED.set(None);
+        println!("Chaos: dropping the move pipeline at {point:?}");
+        return true;
+    }
+    let from_env = FROM_ENV.get_or_init(|| std::env::var("CHAOS_CRASH_POINT").ok().and_then(|value| CrashPoint::parse(&value)));
+    if *from_env == Some(point) {
+        println!("Chaos: killing the server at {point:?}");
+        std::process::abort();
+    }
+    false
+}
+
+/// Stop the next move made on this thread at `point`
+#[cfg(test)]
+pub fn arm(point: CrashPoint) {
+    ARMED.set(Some(point));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::events::EventBus;
+    use crate::game_logic::{self, OutgoingMessage};
+    use crate::game_router;
+    use battld_common::games::game_type::GameType;
+    use battld_common::{ErrorCode, ServerMessage};
+    use serde_json::json;
+    use sqlx::SqlitePool;
+    use std::collections::HashMap;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    /// A new Tic-Tac-Toe match, as (player moving first, the other player, match id)
+    async fn create_match(db: &Database) -> (i64, i64, i64) {
+        let p1 = db.create_player("player1_hint", "player1_key", "player1").await.unwrap();
+        let p2 = db.create_player("player2_hint", "player2_key", "player2").await.unwrap();
+        let game_state = game_router::initialize_game_state(&GameType::TicTacToe, &serde_json::Value::Null);
+        let match_id = db.create_match(p1, p2, &game_state, &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
+        let match_data = db.get_match_by_id(match_id).await.unwrap().to_match().unwrap();
+        if game_router::players_to_move(&match_data) == vec![p1] { (p1, p2, match_id) } else { (p2, p1, match_id) }
+    }
+
+    async fn play(player_id: i64, row: usize, col: usize, db: &Database) -> Vec<OutgoingMessage> {
+        game_logic::handle_make_move_logic(player_id, json!({ "row": row, "col": col }), &EventBus::new(), db).await
+    }
+
+    async fn marks(match_id: i64, db: &Database) -> usize {
+        let match_data = db.get_match_by_id(match_id).await.unwrap().to_match().unwrap();
+        match_data.game_state["board"].as_array().unwrap().iter().filter(|cell| cell.as_i64() != Some(0)).count()
+    }
+
+    fn is_error(messages: &[OutgoingMessage]) -> bool {
+        matches!(messages[..], [OutgoingMessage { message: ServerMessage::Error { .. }, .. }])
+    }
+
+    #[tokio::test]
+    async fn test_crash_before_write_loses_nothing_saved() {
+        let db = create_test_db().await;
+        let (p1, _, match_id) = create_match(&db).await;
+        let seq = db.get_match_by_id(match_id).await.unwrap().seq;
+
+        arm(CrashPoint::BeforeWrite);
+        assert!(play(p1, 0, 0, &db).await.is_empty());
+        assert_eq!(db.get_match_by_id(match_id).await.unwrap().seq, seq);
+        assert_eq!(marks(match_id, &db).await, 0);
+
+        // The player sends the move again once reconnected, it is applied once
+        assert!(!is_error(&play(p1, 0, 0, &db).await));
+        assert_eq!(marks(match_id, &db).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_crash_after_write_keeps_the_move_once() {
+        let db = create_test_db().await;
+        let (p1, p2, match_id) = create_match(&db).await;
+
+        arm(CrashPoint::AfterWrite);
+        assert!(play(p1, 0, 0, &db).await.is_empty());
+        assert_eq!(marks(match_id, &db).await, 1);
+
+        // Both players get the saved state back as they resume
+        let messages = game_logic::handle_resume_match_logic(p1, Some(match_id), &HashMap::new(), &db).await;
+        assert_eq!(messages.len(), 2);
+        for message in &messages {
+            let ServerMessage::GameStateUpdate { match_data } = &message.message else {
+                panic!("Expected GameStateUpdate, got {:?}", message.message);
+            };
+            assert_eq!(game_router::players_to_move(match_data), vec![p2]);
+        }
+
+        // Sending the move again does not play it twice
+        assert!(is_error(&play(p1, 0, 0, &db).await));
+        assert_eq!(marks(match_id, &db).await, 1);
+        assert!(!is_error(&play(p2, 1, 1, &db).await));
+    }
+
+    #[tokio::test]
+    async fn test_crash_after_the_last_write_scores_once_on_restart() {
+        let db = create_test_db().await;
+        let (p1, p2, match_id) = create_match(&db).await;
+        for (player_id, row, col) in [(p1, 0, 0), (p2, 1, 0), (p1, 0, 1), (p2, 1, 1)] {
+            play(player_id, row, col, &db).await;
+        }
+
+        arm(CrashPoint::AfterWrite);
+        assert!(play(p1, 0, 2, &db).await.is_empty());
+        assert_eq!(db.get_match_by_id(match_id).await.unwrap().status, "finished");
+        assert_eq!(db.get_player_by_id(p1).await.unwrap().score, 0);
+
+        game_logic::recover_scores(&db).await;
+        game_logic::recover_scores(&db).await;
+        assert_eq!(db.get_player_by_id(p1).await.unwrap().score, 3);
+        assert_eq!(db.get_player_by_id(p2).await.unwrap().score, -1);
+    }
+
+    #[tokio::test]
+    async fn test_crash_before_send_tells_late_moves_the_match_is_over() {
+        let db = create_test_db().await;
+        let (p1, p2, match_id) = create_match(&db).await;
+        for (player_id, row, col) in [(p1, 0, 0), (p2, 1, 0), (p1, 0, 1), (p2, 1, 1)] {
+            play(player_id, row, col, &db).await;
+        }
+
+        arm(CrashPoint::BeforeSend);
+        assert!(play(p1, 0, 2, &db).await.is_empty());
+        assert_eq!(db.get_player_by_id(p1).await.unwrap().score, 3);
+
+        let messages = play(p1, 0, 2, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::MatchAlreadyFinished { match_id: id, .. } if id == match_id));
+        let messages = game_logic::handle_resume_match_logic(p2, Some(match_id), &HashMap::new(), &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { code: ErrorCode::MatchNotActive, .. }));
+
+        game_logic::recover_scores(&db).await;
+        assert_eq!(db.get_player_by_id(p1).await.unwrap().score, 3);
+    }
+}
@@ -0,0 +1,361 @@
+use battld_common::games::chess::{ChessGameState, ChessMove, ChessPiece};
+use battld_common::games::game_type::GameType;
+use battld_common::{MoveAnnotation, MoveJudgement};
+use server::games::chess::ChessEngine;
+use server::games::GameEngine;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::database::Database;
+use crate::events::{EventBus, MatchEvent};
+
+const DEFAULT_DEPTH: u32 = 12;
+const POSITION_TIMEOUT: Duration = Duration::from_secs(30);
+/// Evaluations past this count as won, so a lead going from +15 to +8 is no blunder
+const EVAL_CAP: i64 = 1000;
+const MATE_CP: i64 = 10_000;
+const INACCURACY_CP: i64 = 50;
+const MISTAKE_CP: i64 = 100;
+const BLUNDER_CP: i64 = 300;
+
+/// UCI engine used to annotate chess matches once they are finished
+#[derive(Debug, Clone)]
+pub struct AnalysisConfig {
+    /// Matches are not analyzed without one
+    pub engine_path: Option<PathBuf>,
+    /// Plies the engine searches in every position
+    pub depth: u32,
+}
+
+impl AnalysisConfig {
+    /// Read from CHESS_ENGINE_PATH, such as /usr/bin/stockfish, and CHESS_ANALYSIS_DEPTH
+    pub fn from_env() -> Self {
+        Self {
+            engine_path: std::env::var("CHESS_ENGINE_PATH").ok().filter(|path| !path.is_empty()).map(PathBuf::from),
+            depth: std::env::var("CHESS_ANALYSIS_DEPTH").ok().and_then(|value| value.parse().ok()).unwrap_or(DEFAULT_DEPTH),
+        }
+    }
+}
+
+/// What the engine thinks of a position, for the side to move
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Score {
+    Centipawns(i64),
+    /// Moves to mate, zero or less when the side to move gets mated
+    Mate(i64),
+}
+
+impl Score {
+    fn centipawns(&self) -> i64 {
+        match *self {
+            Score::Centipawns(cp) => cp,
+            Score::Mate(moves) if moves > 0 => MATE_CP,
+            Score::Mate(_) => -MATE_CP,
+        }
+    }
+}
+
+/// The engine's verdict on one position
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub score: Score,
+    pub best_move: Option<String>,
+}
+
+/// Moves of a match in UCI notation, such as `e7e8q`, replayed to spell out promotions left to the default queen
+pub fn uci_moves(history: &[ChessMove]) -> Vec<String> {
+    let engine = ChessEngine::new();
+    let mut state = ChessGameState::new();
+    let mut moves = Vec::new();
+    for chess_move in history {
+        let mut uci = format!("{}{}", chess_move.from.to_algebraic(), chess_move.to.to_algebraic());
+        if state.is_promotion(chess_move) {
+            uci.push(match chess_move.promotion.unwrap_or(ChessPiece::Queen) {
+                ChessPiece::Rook => 'r',
+                ChessPiece::Bishop => 'b',
+                ChessPiece::Knight => 'n',
+                _ => 'q',
+            });
+        }
+        let player = state.current_turn.to_symbol();
+        if engine.apply(&mut state, player, chess_move).is_err() {
+            break;
+        }
+        moves.push(uci);
+    }
+    moves
+}
+
+/// Score of an `info` line, None for lines without one
+pub fn parse_score(line: &str) -> Option<Score> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words.first() != Some(&"info") {
+        return None;
+    }
+    let at = words.iter().position(|word| *word == "score")?;
+    let value = words.get(at + 2)?.parse().ok()?;
+    match *words.get(at + 1)? {
+        "cp" => Some(Score::Centipawns(value)),
+        "mate" => Some(Score::Mate(value)),
+        _ => None,
+    }
+}
+
+fn judge(loss_cp: i64) -> Option<MoveJudgement> {
+    match loss_cp {
+        loss if loss >= BLUNDER_CP => Some(MoveJudgement::Blunder),
+        loss if loss >= MISTAKE_CP => Some(MoveJudgement::Mistake),
+        loss if loss >= INACCURACY_CP => Some(MoveJudgement::Inaccuracy),
+        _ => None,
+    }
+}
+
+/// Annotate every move from the evaluations of the positions before and after it
+/// `evaluations[0]` is the starting position, `evaluations[i]` the one after `moves[i - 1]`
+pub fn annotate(moves: &[String], evaluations: &[Evaluation]) -> Vec<MoveAnnotation> {
+    moves
+        .iter()
+        .enumerate()
+        .filter_map(|(ply, played)| {
+            let (before, after) = (evaluations.get(ply)?, evaluations.get(ply + 1)?);
+            // White moves on even plies, the engine scores for the side to move
+            let mover = if ply % 2 == 0 { 1 } else { -1 };
+            let before_cp = mover * before.score.centipawns();
+            let after_cp = -mover * after.score.centipawns();
+            let loss_cp = (mover * (before_cp.clamp(-EVAL_CAP, EVAL_CAP) - after_cp.clamp(-EVAL_CAP, EVAL_CAP))).max(0);
+            Some(MoveAnnotation {
+                ply,
+                eval_cp: after_cp,
+                mate_in: match after.score {
+                    Score::Mate(moves) if moves != 0 => Some(-mover * moves),
+                    _ => None,
+                },
+                loss_cp,
+                judgement: judge(loss_cp),
+                best_move: before.best_move.clone().filter(|best| best != played),
+            })
+        })
+        .collect()
+}
+
+struct UciEngine {
+    _child: Child,
+    stdin: ChildStdin,
+    lines: Lines<BufReader<ChildStdout>>,
+}
+
+impl UciEngine {
+    async fn start(path: &Path) -> Result<Self, String> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {e}", path.display()))?;
+        let stdin = child.stdin.take().ok_or("The engine has no input")?;
+        let stdout = child.stdout.take().ok_or("The engine has no output")?;
+        let mut engine = Self { _child: child, stdin, lines: BufReader::new(stdout).lines() };
+        engine.send("uci").await?;
+        engine.wait_for("uciok").await?;
+        engine.send("ucinewgame").await?;
+        engine.send("isready").await?;
+        engine.wait_for("readyok").await?;
+        Ok(engine)
+    }
+
+    async fn send(&mut self, command: &str) -> Result<(), String> {
+        self.stdin.write_all(format!("{command}\n").as_bytes()).await.map_err(|e| e.to_string())?;
+        self.stdin.flush().await.map_err(|e| e.to_string())
+    }
+
+    async fn next_line(&mut self) -> Result<String, String> {
+        match tokio::time::timeout(POSITION_TIMEOUT, self.lines.next_line()).await {
+            Ok(Ok(Some(line))) => Ok(line),
+            Ok(Ok(None)) => Err("The engine quit".to_string()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err("The engine did not answer in time".to_string()),
+        }
+    }
+
+    async fn wait_for(&mut self, expected: &str) -> Result<(), String> {
+        while self.next_line().await?.trim() != expected {}
+        Ok(())
+    }
+
+    async fn evaluate(&mut self, moves: &[String], depth: u32) -> Result<Evaluation, String> {
+        let position = if moves.is_empty() {
+            "position startpos".to_string()
+        } else {
+            format!("position startpos moves {}", moves.join(" "))
+        };
+        self.send(&position).await?;
+        self.send(&format!("go depth {depth}")).await?;
+
+        let mut score = Score::Centipawns(0);
+        loop {
+            let line = self.next_line().await?;
+            if let Some(best) = line.strip_prefix("bestmove") {
+                let best_move = best.split_whitespace().next().filter(|best| *best != "(none)").map(str::to_string);
+                return Ok(Evaluation { score, best_move });
+            }
+            if let Some(found) = parse_score(&line) {
+                score = found;
+            }
+        }
+    }
+}
+
+/// Evaluate every position of a finished chess match and save the annotations, returns how many moves were annotated
+pub async fn analyze_match(config: &AnalysisConfig, match_id: i64, history: &[ChessMove], db: &Database) -> Result<usize, String> {
+    let Some(path) = &config.engine_path else {
+        return Ok(0);
+    };
+    let moves = uci_moves(history);
+    if moves.is_empty() {
+        return Ok(0);
+    }
+
+    let mut engine = UciEngine::start(path).await?;
+    let mut evaluations = Vec::with_capacity(moves.len() + 1);
+    for ply in 0..=moves.len() {
+        evaluations.push(engine.evaluate(&moves[..ply], config.depth).await?);
+    }
+
+    let annotations = annotate(&moves, &evaluations);
+    db.save_match_analysis(match_id, &annotations).await.map_err(|e| e.to_string())?;
+    Ok(annotations.len())
+}
+
+/// Annotate chess matches one at a time as they finish, does nothing without an engine
+pub fn spawn_chess_analysis(bus: &EventBus, db: Arc<Database>, config: AnalysisConfig) -> tokio::task::JoinHandle<()> {
+    let mut rx = bus.subscribe();
+    tokio::spawn(async move {
+        if config.engine_path.is_none() {
+            return;
+        }
+        loop {
+            match rx.recv().await {
+                Ok(MatchEvent::MatchFinished { match_data }) if match_data.game_type == GameType::Chess && !match_data.ephemeral => {
+                    let Ok(state) = serde_json::from_value::<ChessGameState>(match_data.game_state.clone()) else {
+                        continue;
+                    };
+                    match analyze_match(&config, match_data.id, &state.move_history, &db).await {
+                        Ok(count) => println!("Analyzed {count} moves of chess match {}", match_data.id),
+                        Err(e) => println!("Failed to analyze chess match {}: {e}", match_data.id),
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    println!("Chess analysis fell behind, {missed} events skipped");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::chess::ChessPosition;
+
+    fn chess_move(from: &str, to: &str) -> ChessMove {
+        ChessMove {
+            from: ChessPosition::from_algebraic(from).unwrap(),
+            to: ChessPosition::from_algebraic(to).unwrap(),
+            promotion: None,
+        }
+    }
+
+    fn evaluation(score: Score, best_move: &str) -> Evaluation {
+        Evaluation { score, best_move: Some(best_move.to_string()) }
+    }
+
+    #[test]
+    fn test_parse_score() {
+        assert_eq!(parse_score("info depth 12 seldepth 18 score cp 34 nodes 1200 pv e2e4"), Some(Score::Centipawns(34)));
+        assert_eq!(parse_score("info depth 12 score cp -120 upperbound"), Some(Score::Centipawns(-120)));
+        assert_eq!(parse_score("info depth 5 score mate -2 pv h7h8"), Some(Score::Mate(-2)));
+        assert_eq!(parse_score("info string NNUE evaluation enabled"), None);
+        assert_eq!(parse_score("bestmove e2e4 ponder e7e5"), None);
+    }
+
+    #[test]
+    fn test_uci_moves_spell_out_promotions() {
+        let mut history: Vec<ChessMove> = [
+            ("b2", "b4"), ("a7", "a5"), ("b4", "a5"), ("b7", "b6"), ("a5", "b6"),
+            ("b8", "c6"), ("b6", "b7"), ("g8", "f6"), ("b7", "a8"),
+        ]
+        .iter()
+        .map(|(from, to)| chess_move(from, to))
+        .collect();
+
+        let moves = uci_moves(&history);
+        assert_eq!(moves.len(), 9);
+        assert_eq!(moves[0], "b2b4");
+        assert_eq!(moves[8], "b7a8q");
+
+        history[8].promotion = Some(ChessPiece::Knight);
+        assert_eq!(uci_moves(&history)[8], "b7a8n");
+
+        // Moves past an illegal one are left out
+        history.insert(1, chess_move("e2", "e4"));
+        assert_eq!(uci_moves(&history), vec!["b2b4"]);
+    }
+
+    #[test]
+    fn test_annotate_finds_blunders() {
+        let moves = vec!["e2e4".to_string(), "d8h4".to_string(), "f1c4".to_string()];
+        let evaluations = [
+            evaluation(Score::Centipawns(30), "e2e4"),
+            // Black to move, down a little
+            evaluation(Score::Centipawns(-30), "e7e5"),
+            // White to move after a queen blunder
+            evaluation(Score::Centipawns(900), "g1f3"),
+            // Black to move, White kept most of it
+            evaluation(Score::Centipawns(-820), "h4e7"),
+        ];
+
+        let annotations = annotate(&moves, &evaluations);
+        assert_eq!(annotations.len(), 3);
+
+        assert_eq!(annotations[0].ply, 0);
+        assert_eq!(annotations[0].eval_cp, 30);
+        assert_eq!(annotations[0].loss_cp, 0);
+        assert_eq!(annotations[0].judgement, None);
+        assert_eq!(annotations[0].best_move, None);
+
+        assert_eq!(annotations[1].eval_cp, 900);
+        assert_eq!(annotations[1].loss_cp, 870);
+        assert_eq!(annotations[1].judgement, Some(MoveJudgement::Blunder));
+        assert_eq!(annotations[1].best_move.as_deref(), Some("e7e5"));
+
+        assert_eq!(annotations[2].eval_cp, 820);
+        assert_eq!(annotations[2].loss_cp, 80);
+        assert_eq!(annotations[2].judgement, Some(MoveJudgement::Inaccuracy));
+    }
+
+    #[test]
+    fn test_annotate_mates() {
+        let moves = vec!["d1h5".to_string(), "g7g6".to_string()];
+        let evaluations = [
+            evaluation(Score::Centipawns(200), "d1h5"),
+            evaluation(Score::Centipawns(-150), "g8f6"),
+            evaluation(Score::Mate(1), "h5f7"),
+        ];
+
+        let annotations = annotate(&moves, &evaluations);
+        assert_eq!(annotations[0].mate_in, None);
+        assert_eq!(annotations[1].mate_in, Some(1));
+        assert_eq!(annotations[1].eval_cp, MATE_CP);
+        // Past the cap the loss only counts up to a won position
+        assert_eq!(annotations[1].loss_cp, EVAL_CAP - 150);
+        assert_eq!(annotations[1].judgement, Some(MoveJudgement::Blunder));
+    }
+}
@@ -0,0 +1,207 @@
+use axum::{extract::State, http::{HeaderMap, StatusCode}, Json};
+use battld_common::{games::matches::MatchOutcome, AuditFinding, AuditSeverity};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::database::{Database, FinishedMatchRecord};
+use crate::{auth, AppState};
+
+const ANALYSIS_INTERVAL: Duration = Duration::from_secs(3600);
+const ANALYSIS_WINDOW_SECS: i64 = 30 * 24 * 3600;
+/// Matches between the same two players within the window before it is worth a look
+const REPEATED_PAIR_MATCHES: usize = 10;
+/// Decisive results needed before judging how often the winner flips
+const ALTERNATING_MIN_RESULTS: usize = 6;
+const ALTERNATING_RATIO: f64 = 0.8;
+/// A rematch finished this soon after the previous one with the other winner looks like a traded win
+const QUICK_TRADE_SECS: i64 = 120;
+const QUICK_TRADES: usize = 3;
+
+/// Analyse recent matches and store what looks suspicious, returns how many findings were stored
+pub async fn run_analysis(db: &Database, now: i64) -> Result<usize, sqlx::Error> {
+    let matches = db.get_finished_matches_since(now - ANALYSIS_WINDOW_SECS).await?;
+    let findings = analyze(&matches, now);
+    for finding in &findings {
+        db.upsert_audit_finding(finding).await?;
+    }
+    println!("Collusion analysis: {} findings over {} matches", findings.len(), matches.len());
+    Ok(findings.len())
+}
+
+/// Run the analysis every hour
+pub fn spawn_analysis(db: Arc<Database>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(ANALYSIS_INTERVAL).await;
+            if let Err(e) = run_analysis(&db, battld_common::time() as i64).await {
+                println!("Collusion analysis failed: {e}");
+            }
+        }
+    })
+}
+
+/// Findings of the collusion analysis, most recent first, admins only
+pub async fn get_audit_findings(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<AuditFinding>>, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+    if !state.capacity.is_admin(player_id) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    state.db.get_audit_findings()
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn winner(record: &FinishedMatchRecord) -> Option<i64> {
+    let outcome: MatchOutcome = serde_json::from_str(record.outcome.as_deref()?).ok()?;
+    match outcome {
+        MatchOutcome::Player1Win => Some(record.player1_id),
+        MatchOutcome::Player2Win => Some(record.player2_id),
+        MatchOutcome::Draw => None,
+    }
+}
+
+/// Look for pairs of players who keep meeting and handing wins to each other
+fn analyze(matches: &[FinishedMatchRecord], now: i64) -> Vec<AuditFinding> {
+    let mut pairs: BTreeMap<(i64, i64), Vec<&FinishedMatchRecord>> = BTreeMap::new();
+    for record in matches {
+        let pair = (record.player1_id.min(record.player2_id), record.player1_id.max(record.player2_id));
+        pairs.entry(pair).or_default().push(record);
+    }
+
+    let mut findings = vec![];
+    for ((player1_id, player2_id), matches) in pairs {
+        let finding = |kind: &str, severity: AuditSeverity, details: String| AuditFinding {
+            kind: kind.to_string(),
+            severity,
+            player1_id,
+            player2_id,
+            details,
+            detected_at: now,
+        };
+
+        if matches.len() >= REPEATED_PAIR_MATCHES {
+            let severity = if matches.len() >= REPEATED_PAIR_MATCHES * 2 { AuditSeverity::Medium } else { AuditSeverity::Low };
+            findings.push(finding(
+                "repeated_pairing",
+                severity,
+                format!("{} matches against each other in the last 30 days", matches.len()),
+            ));
+        }
+
+        let results: Vec<(i64, i64)> = matches
+            .iter()
+            .filter_map(|record| Some((winner(record)?, record.finished_at)))
+            .collect();
+        let flips = results.windows(2).filter(|pair| pair[0].0 != pair[1].0).count();
+        if results.len() >= ALTERNATING_MIN_RESULTS {
+            let ratio = flips as f64 / (results.len() - 1) as f64;
+            if ratio >= ALTERNATING_RATIO {
+                let severity = if results.len() >= ALTERNATING_MIN_RESULTS * 2 { AuditSeverity::High } else { AuditSeverity::Medium };
+                findings.push(finding(
+                    "alternating_wins",
+                    severity,
+                    format!("The winner changed {flips} times over {} decisive matches", results.len()),
+                ));
+            }
+        }
+
+        let quick_trades = results
+            .windows(2)
+            .filter(|pair| pair[0].0 != pair[1].0 && pair[1].1 - pair[0].1 <= QUICK_TRADE_SECS)
+            .count();
+        if quick_trades >= QUICK_TRADES {
+            let severity = if quick_trades >= QUICK_TRADES * 2 { AuditSeverity::High } else { AuditSeverity::Medium };
+            findings.push(finding(
+                "quick_win_trading",
+                severity,
+                format!("{quick_trades} wins traded within {QUICK_TRADE_SECS}s of the previous match"),
+            ));
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    fn record(id: i64, player1_id: i64, player2_id: i64, outcome: MatchOutcome, finished_at: i64) -> FinishedMatchRecord {
+        FinishedMatchRecord {
+            id,
+            player1_id,
+            player2_id,
+            outcome: Some(serde_json::to_string(&outcome).unwrap()),
+            finished_at,
+        }
+    }
+
+    /// `count` matches between 1 and 2, `gap` seconds apart, with the winner picked by `winner_of`
+    fn series(count: i64, gap: i64, winner_of: impl Fn(i64) -> MatchOutcome) -> Vec<FinishedMatchRecord> {
+        (0..count).map(|i| record(i, 1, 2, winner_of(i), 1_000 + i * gap)).collect()
+    }
+
+    fn kinds(findings: &[AuditFinding]) -> Vec<(&str, AuditSeverity)> {
+        findings.iter().map(|f| (f.kind.as_str(), f.severity)).collect()
+    }
+
+    #[test]
+    fn test_occasional_rematches_are_fine() {
+        let matches = series(5, 3600, |i| if i % 2 == 0 { MatchOutcome::Player1Win } else { MatchOutcome::Player2Win });
+        assert!(analyze(&matches, 0).is_empty());
+
+        let matches = series(12, 3600, |_| MatchOutcome::Player1Win);
+        assert_eq!(kinds(&analyze(&matches, 0)), vec![("repeated_pairing", AuditSeverity::Low)]);
+    }
+
+    #[test]
+    fn test_alternating_wins() {
+        let matches = series(8, 3600, |i| if i % 2 == 0 { MatchOutcome::Player1Win } else { MatchOutcome::Player2Win });
+        assert_eq!(kinds(&analyze(&matches, 0)), vec![("alternating_wins", AuditSeverity::Medium)]);
+    }
+
+    #[test]
+    fn test_quick_win_trading() {
+        // Seats alternate, so the same player winning from the other seat is still a traded win
+        let matches = series(12, 60, |i| if i % 4 < 2 { MatchOutcome::Player1Win } else { MatchOutcome::Player2Win });
+        let findings = analyze(&matches, 0);
+        assert_eq!(
+            kinds(&findings),
+            vec![("repeated_pairing", AuditSeverity::Low), ("quick_win_trading", AuditSeverity::Medium)]
+        );
+        assert_eq!((findings[1].player1_id, findings[1].player2_id), (1, 2));
+    }
+
+    #[tokio::test]
+    async fn test_findings_are_refreshed() {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        let p1 = db.create_player("p1_hint", "p1_key", "player1").await.unwrap();
+        let p2 = db.create_player("p2_hint", "p2_key", "player2").await.unwrap();
+
+        let mut finding = AuditFinding {
+            kind: "alternating_wins".to_string(),
+            severity: AuditSeverity::Medium,
+            player1_id: p1,
+            player2_id: p2,
+            details: "first".to_string(),
+            detected_at: 1,
+        };
+        db.upsert_audit_finding(&finding).await.unwrap();
+        finding.severity = AuditSeverity::High;
+        finding.details = "second".to_string();
+        db.upsert_audit_finding(&finding).await.unwrap();
+
+        let findings = db.get_audit_findings().await.unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, AuditSeverity::High);
+        assert_eq!(findings[0].details, "second");
+    }
+}
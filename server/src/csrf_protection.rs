@@ -1,6 +1,6 @@
 use axum::{
     extract::Request,
-    http::{StatusCode, Method},
+    http::{header, HeaderMap, StatusCode, Method},
     middleware::Next,
     response::Response,
 };
@@ -31,3 +31,66 @@ pub async fn csrf_protection_middleware(
 
     Ok(next.run(request).await)
 }
+
+/// Origin check for WebSocket upgrades, which browsers never preflight
+/// Native clients send no Origin header; browser pages must be served by this server
+/// or listed in ALLOWED_ORIGINS (comma-separated, e.g. "https://play.example.com")
+pub fn is_websocket_origin_allowed(headers: &HeaderMap) -> bool {
+    let allowed_origins: Vec<String> = std::env::var("ALLOWED_ORIGINS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|origin| origin.trim().trim_end_matches('/').to_lowercase())
+        .filter(|origin| !origin.is_empty())
+        .collect();
+
+    let origin = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok());
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    origin_allowed(origin, host, &allowed_origins)
+}
+
+fn origin_allowed(origin: Option<&str>, host: Option<&str>, allowed_origins: &[String]) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+    let origin = origin.trim_end_matches('/').to_lowercase();
+
+    if allowed_origins.contains(&origin) {
+        return true;
+    }
+
+    // Same-origin: the page was served by this server
+    let origin_host = origin
+        .strip_prefix("https://")
+        .or_else(|| origin.strip_prefix("http://"));
+    matches!((origin_host, host), (Some(origin_host), Some(host)) if origin_host == host.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_clients_without_origin_are_allowed() {
+        assert!(origin_allowed(None, Some("battld.example.com"), &[]));
+    }
+
+    #[test]
+    fn test_same_origin_is_allowed() {
+        assert!(origin_allowed(Some("https://battld.example.com"), Some("battld.example.com"), &[]));
+        assert!(origin_allowed(Some("http://localhost:3000"), Some("localhost:3000"), &[]));
+    }
+
+    #[test]
+    fn test_foreign_origin_is_rejected() {
+        assert!(!origin_allowed(Some("https://evil.example.com"), Some("battld.example.com"), &[]));
+        assert!(!origin_allowed(Some("null"), Some("battld.example.com"), &[]));
+        assert!(!origin_allowed(Some("https://battld.example.com.evil.com"), Some("battld.example.com"), &[]));
+    }
+
+    #[test]
+    fn test_configured_origins_are_allowed() {
+        let allowed = vec!["https://play.example.com".to_string()];
+        assert!(origin_allowed(Some("https://play.example.com/"), Some("api.example.com"), &allowed));
+        assert!(!origin_allowed(Some("https://other.example.com"), Some("api.example.com"), &allowed));
+    }
+}
@@ -1,9 +1,62 @@
-use sqlx::{SqlitePool, FromRow};
-use battld_common::games::{game_type::GameType, matches::{Match, MatchOutcome}};
+use sqlx::{SqliteExecutor, SqlitePool, FromRow};
+use std::{collections::{HashMap, HashSet}, future::Future, sync::{Arc, Mutex}, time::Duration};
+use battld_common::{games::{game_type::GameType, matches::{Match, MatchOutcome, MatchPlayer, MatchStatus}}, ArenaInfo, AuditFinding, AuditSeverity, DateStyle, ImportReport, InstanceExport, LiveMatch, MatchChallenge, MoveAnnotation, QuietHours, ReplayPrivacy, SeasonInfo, SeriesInfo, SettingChange, TimePreferences, setting_keys};
+
+use crate::log_privacy;
+use crate::query_metrics::{MeteredPool, QueryMetrics};
+use crate::rating::Rating;
+
+const WRITE_ATTEMPTS: u32 = 3;
+const WRITE_BACKOFF_MS: u64 = 50;
+
+/// Matches joined with their players' public profile, rating in the game and seats, filter on `m.`
+const SELECT_MATCHES: &str =
+    "SELECT m.*, p1.name AS player1_name, p1.score AS player1_score, p1.is_bot AS player1_bot, r1.rating AS player1_rating,
+            p2.name AS player2_name, p2.score AS player2_score, p2.is_bot AS player2_bot, r2.rating AS player2_rating,
+            (SELECT GROUP_CONCAT(seated.player_id) FROM (SELECT player_id FROM match_seats WHERE match_id = m.id ORDER BY seat) seated) AS seat_ids
+     FROM matches m
+     LEFT JOIN players p1 ON p1.id = m.player1_id
+     LEFT JOIN players p2 ON p2.id = m.player2_id
+     LEFT JOIN player_game_ratings r1 ON r1.player_id = m.player1_id AND r1.game_type = m.game_type AND r1.rated_games > 0
+     LEFT JOIN player_game_ratings r2 ON r2.player_id = m.player2_id AND r2.game_type = m.game_type AND r2.rated_games > 0";
+
+const SELECT_SERIES: &str =
+    "SELECT id, game_type, game_options, length, player1_id, player2_id, player1_wins, player2_wins, draws, finished FROM series";
 
 #[derive(Clone)]
 pub struct Database {
-    pool: SqlitePool,
+    pool: MeteredPool,
+    /// Matches whose last write failed; moves are refused until storage works again
+    quarantined_matches: Arc<Mutex<HashSet<i64>>>,
+}
+
+/// Whether a failed query is worth retrying (busy or locked database, pool exhaustion, I/O)
+fn is_transient(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::PoolTimedOut | sqlx::Error::Io(_) => true,
+        // SQLITE_BUSY, SQLITE_LOCKED and their extended codes
+        sqlx::Error::Database(e) => matches!(e.code().as_deref(), Some("5" | "6" | "261" | "262" | "517")),
+        _ => false,
+    }
+}
+
+/// Run a write, retrying transient failures with exponential backoff
+pub async fn with_retry<T, F, Fut>(mut operation: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(e) if attempt < WRITE_ATTEMPTS && is_transient(&e) => {
+                println!("DB: Transient error on attempt {attempt}, retrying: {e}");
+                tokio::time::sleep(Duration::from_millis(WRITE_BACKOFF_MS << (attempt - 1))).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
 }
 
 #[derive(Debug, FromRow)]
@@ -21,37 +74,332 @@ pub struct MatchRecord {
     pub player1_id: i64,
     pub player2_id: i64,
     pub in_progress: i64,
+    pub status: String,
     pub outcome: Option<String>, // JSON string
     pub game_type: String, // JSON string
     pub game_state: String, // JSON string
+    pub seq: i64,
+    pub replay_token: Option<String>,
+    pub rematch_of: Option<i64>,
+    pub finished_at: Option<i64>,
+    pub last_move_at: Option<i64>,
+    pub draw_offered_by: Option<i64>,
+    pub player1_name: Option<String>,
+    pub player1_score: Option<i64>,
+    pub player1_bot: Option<i64>,
+    pub player1_rating: Option<f64>,
+    pub player2_name: Option<String>,
+    pub player2_score: Option<i64>,
+    pub player2_bot: Option<i64>,
+    pub player2_rating: Option<f64>,
+    /// Player ids in seat order, separated by commas
+    pub seat_ids: Option<String>,
 }
 
 impl MatchRecord {
+    pub fn outcome(&self) -> Option<MatchOutcome> {
+        self.outcome.as_ref().and_then(|s| serde_json::from_str(s).ok())
+    }
+
     pub fn to_match(&self) -> Option<Match> {
         let game_type: GameType = serde_json::from_str(&self.game_type).ok()?;
         let game_state: serde_json::Value = serde_json::from_str(&self.game_state).ok()?;
-        let outcome: Option<MatchOutcome> = self.outcome.as_ref()
-            .and_then(|s| serde_json::from_str(s).ok());
+        let outcome = self.outcome();
+
+        let status = MatchStatus::parse(&self.status)?;
+
+        let players = [
+            (self.player1_id, &self.player1_name, self.player1_score, self.player1_bot, self.player1_rating),
+            (self.player2_id, &self.player2_name, self.player2_score, self.player2_bot, self.player2_rating),
+        ]
+        .into_iter()
+        .filter_map(|(id, name, score, bot, rating)| {
+            Some(MatchPlayer {
+                id,
+                name: name.clone()?,
+                score: score.unwrap_or(0),
+                bot: bot == Some(1),
+                rating: rating.map(|rating| rating.round() as i64),
+            })
+        })
+        .collect();
+
+        let seats = self.seat_ids
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|id| id.parse().ok())
+            .collect();
 
         Some(Match {
             id: self.id,
             player1_id: self.player1_id,
             player2_id: self.player2_id,
-            in_progress: self.in_progress != 0,
+            seats,
+            in_progress: status.is_open(),
+            status,
             outcome,
             game_type,
             game_state,
+            players,
+            ephemeral: false,
+            draw_offered_by: self.draw_offered_by,
+            seq: self.seq,
+        })
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct LiveMatchRecord {
+    pub id: i64,
+    pub game_type: String, // JSON string
+    pub player1_id: i64,
+    pub player1_name: String,
+    pub player2_id: i64,
+    pub player2_name: String,
+}
+
+impl LiveMatchRecord {
+    pub fn to_live_match(&self) -> Option<LiveMatch> {
+        Some(LiveMatch {
+            match_id: self.id,
+            game_type: serde_json::from_str(&self.game_type).ok()?,
+            player1_id: self.player1_id,
+            player1_name: self.player1_name.clone(),
+            player2_id: self.player2_id,
+            player2_name: self.player2_name.clone(),
+        })
+    }
+}
+
+/// A player waiting in matchmaking, rated in the game they queued for
+#[derive(Debug, Clone, FromRow)]
+pub struct WaitingPlayer {
+    pub match_id: i64,
+    pub player_id: i64,
+    pub game_type: String, // JSON string
+    pub game_options: String, // JSON string
+    pub rating: f64,
+    pub queued_at: i64,
+    pub series_length: Option<u32>,
+}
+
+/// A quick play match a guest finished, kept in memory until they leave or register
+#[derive(Debug, Clone)]
+pub struct GuestMatch {
+    pub match_data: Match,
+    pub finished_at: i64,
+}
+
+/// Finished match as seen by the collusion analysis
+#[derive(Debug, Clone, FromRow)]
+pub struct FinishedMatchRecord {
+    pub id: i64,
+    pub player1_id: i64,
+    pub player2_id: i64,
+    pub outcome: Option<String>, // JSON string
+    pub finished_at: i64,
+}
+
+#[derive(Debug, FromRow)]
+pub struct AuditFindingRecord {
+    pub kind: String,
+    pub severity: String,
+    pub player1_id: i64,
+    pub player2_id: i64,
+    pub details: String,
+    pub detected_at: i64,
+}
+
+#[derive(Debug, FromRow)]
+pub struct ExportedPlayerRecord {
+    pub id: i64,
+    pub name: String,
+    pub public_key_hint: String,
+    pub public_key: String,
+    pub score: i64,
+    pub rating: f64,
+    pub rating_deviation: f64,
+    pub rated_games: i64,
+    pub replay_privacy: String,
+}
+
+#[derive(Debug, FromRow)]
+pub struct ExportedMatchRecord {
+    pub id: i64,
+    pub player1_id: i64,
+    pub player2_id: i64,
+    pub game_type: String, // JSON string
+    pub outcome: Option<String>, // JSON string
+    pub game_state: String, // JSON string
+    pub finished_at: Option<i64>,
+}
+
+#[derive(Debug, FromRow, serde::Serialize, serde::Deserialize)]
+pub struct MatchFrameRecord {
+    pub player_id: Option<i64>,
+    pub move_data: Option<String>, // JSON string
+    pub game_state: String, // JSON string
+    /// UTC seconds, None for frames recorded before moves were timed
+    #[serde(default)]
+    pub created_at: Option<i64>,
+}
+
+/// A player who dropped out of a match, ending it unless they are back by `deadline`
+#[derive(Debug, PartialEq, FromRow)]
+pub struct DisconnectRecord {
+    pub player_id: i64,
+    pub match_id: i64,
+    pub deadline: i64,
+}
+
+#[derive(Debug, FromRow)]
+pub struct ChallengeRecord {
+    pub id: i64,
+    pub challenger_id: i64,
+    pub challenger_name: String,
+    pub challenged_id: i64,
+    pub challenged_name: String,
+    pub game_type: String, // JSON string
+    pub game_options: String, // JSON string
+    pub status: String,
+    pub expires_at: i64,
+}
+
+impl ChallengeRecord {
+    pub fn to_challenge(&self) -> Option<MatchChallenge> {
+        Some(MatchChallenge {
+            id: self.id,
+            challenger_id: self.challenger_id,
+            challenger_name: self.challenger_name.clone(),
+            challenged_id: self.challenged_id,
+            challenged_name: self.challenged_name.clone(),
+            game_type: serde_json::from_str(&self.game_type).ok()?,
+            options: serde_json::from_str(&self.game_options).ok()?,
+            expires_at: self.expires_at,
+        })
+    }
+}
+
+const CHALLENGE_SELECT: &str =
+    "SELECT c.id, c.challenger_id, p1.name AS challenger_name, c.challenged_id, p2.name AS challenged_name,
+            c.game_type, c.game_options, c.status, c.expires_at
+     FROM challenges c
+     JOIN players p1 ON p1.id = c.challenger_id
+     JOIN players p2 ON p2.id = c.challenged_id";
+
+#[derive(Debug, FromRow)]
+pub struct ArenaRecord {
+    pub id: i64,
+    pub game_type: String, // JSON string
+    pub starts_at: i64,
+    pub ends_at: i64,
+    pub finished: bool,
+}
+
+impl ArenaRecord {
+    pub fn to_info(&self) -> Option<ArenaInfo> {
+        Some(ArenaInfo {
+            id: self.id,
+            game_type: serde_json::from_str(&self.game_type).ok()?,
+            starts_at: self.starts_at,
+            ends_at: self.ends_at,
+            finished: self.finished,
         })
     }
+
+    /// Whether players can join and be paired at `now`
+    pub fn is_running(&self, now: i64) -> bool {
+        !self.finished && self.starts_at <= now && now < self.ends_at
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct SeasonRecord {
+    pub id: i64,
+    pub starts_at: i64,
+    pub ends_at: i64,
+    pub archived: bool,
+}
+
+impl SeasonRecord {
+    pub fn to_info(&self) -> SeasonInfo {
+        SeasonInfo {
+            id: self.id,
+            starts_at: self.starts_at,
+            ends_at: self.ends_at,
+            archived: self.archived,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct SeriesRecord {
+    pub id: i64,
+    pub game_type: String, // JSON string
+    pub game_options: String, // JSON string
+    pub length: u32,
+    pub player1_id: i64,
+    pub player2_id: i64,
+    pub player1_wins: u32,
+    pub player2_wins: u32,
+    pub draws: u32,
+    pub finished: bool,
+}
+
+impl SeriesRecord {
+    /// The series as seen from its `game`-th match, `match_id`
+    pub fn to_info(&self, match_id: i64, game: u32) -> SeriesInfo {
+        let winner_id = match self.player1_wins.cmp(&self.player2_wins) {
+            _ if !self.finished => None,
+            std::cmp::Ordering::Greater => Some(self.player1_id),
+            std::cmp::Ordering::Less => Some(self.player2_id),
+            std::cmp::Ordering::Equal => None,
+        };
+        SeriesInfo {
+            id: self.id,
+            length: self.length,
+            game,
+            match_id,
+            player1_id: self.player1_id,
+            player2_id: self.player2_id,
+            player1_wins: self.player1_wins,
+            player2_wins: self.player2_wins,
+            draws: self.draws,
+            finished: self.finished,
+            winner_id,
+        }
+    }
+
+    pub fn games_played(&self) -> u32 {
+        self.player1_wins + self.player2_wins + self.draws
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct ArenaStandingRecord {
+    pub player_id: i64,
+    pub player_name: String,
+    pub points: i64,
+    pub games: i64,
+    pub wins: i64,
+    pub streak: i64,
 }
 
 impl Database {
-    pub fn pool(&self) -> &SqlitePool {
+    pub fn pool(&self) -> &MeteredPool {
         &self.pool
     }
 
     pub fn from_pool(pool: SqlitePool) -> Self {
-        Database { pool }
+        Self::with_metrics(pool, QueryMetrics::default())
+    }
+
+    fn with_metrics(pool: SqlitePool, metrics: QueryMetrics) -> Self {
+        Database {
+            pool: MeteredPool::new(pool, metrics),
+            quarantined_matches: Arc::new(Mutex::new(HashSet::new())),
+        }
     }
 
     pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
@@ -63,13 +411,25 @@ impl Database {
         }
 
         let pool = SqlitePool::connect(database_url).await?;
-        Ok(Database { pool })
+        Ok(Self::with_metrics(pool, QueryMetrics::from_env()))
+    }
+
+    pub fn quarantine_match(&self, match_id: i64) {
+        self.quarantined_matches.lock().unwrap().insert(match_id);
+    }
+
+    pub fn release_match(&self, match_id: i64) {
+        self.quarantined_matches.lock().unwrap().remove(&match_id);
+    }
+
+    pub fn is_match_quarantined(&self, match_id: i64) -> bool {
+        self.quarantined_matches.lock().unwrap().contains(&match_id)
     }
 
     pub async fn initialize(&self) -> Result<(), sqlx::Error> {
         // Run migrations from the migrations directory
         sqlx::migrate!("../migrations")
-            .run(&self.pool)
+            .run(self.pool.inner())
             .await?;
         Ok(())
     }
@@ -80,7 +440,11 @@ impl Database {
         public_key: &str,
         name: &str,
     ) -> Option<i64> {
-        println!("DB: Inserting player into database: name='{name}', hint='{public_key_hint}'");
+        println!(
+            "DB: Inserting player into database: name='{}', hint='{}'",
+            log_privacy::name(name),
+            log_privacy::secret(public_key_hint)
+        );
 
         let result = sqlx::query(
             "INSERT INTO players (public_key_hint, public_key, name) VALUES (?, ?, ?)"
@@ -104,6 +468,40 @@ impl Database {
         }
     }
 
+    /// A bot account with the given key that is not playing, created if they all are
+    /// Bots have no usable public key, so nobody can sign in as one
+    pub async fn get_idle_bot(&self, key: &str, name: &str) -> Result<i64, sqlx::Error> {
+        let idle: Option<(i64,)> = sqlx::query_as(
+            "SELECT p.id FROM players p
+             WHERE p.is_bot = 1 AND p.public_key_hint = ? AND NOT EXISTS (
+                SELECT 1 FROM matches m
+                WHERE (m.player1_id = p.id OR m.player2_id = p.id) AND m.status IN ('waiting', 'active', 'paused')
+             )
+             ORDER BY p.id LIMIT 1"
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some((bot_id,)) = idle {
+            return Ok(bot_id);
+        }
+
+        let result = sqlx::query("INSERT INTO players (public_key_hint, public_key, name, is_bot) VALUES (?, '', ?, 1)")
+            .bind(key)
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Players named `name`, ignoring case, bots left out
+    pub async fn find_players_by_name(&self, name: &str) -> Result<Vec<PlayerRecord>, sqlx::Error> {
+        sqlx::query_as::<_, PlayerRecord>("SELECT * FROM players WHERE name = ? COLLATE NOCASE AND is_bot = 0 AND is_guest = 0 ORDER BY id LIMIT 10")
+            .bind(name.trim())
+            .fetch_all(&self.pool)
+            .await
+    }
+
     pub async fn get_player_by_id(&self, id: i64) -> Option<PlayerRecord> {
         println!("DB: Querying player by ID: {id}");
 
@@ -114,7 +512,7 @@ impl Database {
 
         match player {
             Ok(Some(p)) => {
-                println!("DB: Found player: id={}, name='{}'", p.id, p.name);
+                println!("DB: Found player: id={}, name='{}'", p.id, log_privacy::name(&p.name));
                 Some(p)
             }
             Ok(None) => {
@@ -128,6 +526,37 @@ impl Database {
         }
     }
 
+    /// The player registered with a public key, bots have none
+    pub async fn get_player_by_public_key(&self, public_key: &str) -> Option<PlayerRecord> {
+        if public_key.is_empty() {
+            return None;
+        }
+        sqlx::query_as::<_, PlayerRecord>("SELECT * FROM players WHERE public_key = ?")
+            .bind(public_key)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Replace the key a player signs in with, recording a fingerprint of both keys
+    pub async fn set_player_key(&self, player_id: i64, public_key_hint: &str, public_key: &str, changed_by: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let (old_key,): (String,) = sqlx::query_as("SELECT public_key FROM players WHERE id = ?")
+            .bind(player_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE players SET public_key_hint = ?, public_key = ? WHERE id = ?")
+            .bind(public_key_hint)
+            .bind(public_key)
+            .bind(player_id)
+            .execute(&mut *tx)
+            .await?;
+        let changes = [(setting_keys::PUBLIC_KEY, log_privacy::fingerprint(&old_key), log_privacy::fingerprint(public_key))];
+        record_setting_changes(&mut tx, player_id, changed_by, &changes).await?;
+        tx.commit().await
+    }
+
     // Match operations
     pub async fn create_match(
         &self,
@@ -137,164 +566,1575 @@ impl Database {
         game_type: &str,
     ) -> Result<i64, sqlx::Error> {
         let result = sqlx::query(
-            "INSERT INTO matches (player1_id, player2_id, in_progress, game_type, game_state)
-             VALUES (?, ?, 1, ?, ?)"
+            "INSERT INTO matches (player1_id, player2_id, in_progress, status, game_type, game_state, last_move_at)
+             VALUES (?, ?, 1, 'active', ?, ?, ?)"
         )
         .bind(player1_id)
         .bind(player2_id)
         .bind(game_type)
         .bind(game_state)
+        .bind(battld_common::time() as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Queue a player, or update the options of the match they already wait in for this game type
+    pub async fn create_waiting_match(
+        &self,
+        player1_id: i64,
+        game_type: &str,
+        game_options: &str,
+        series_length: Option<u32>,
+    ) -> Result<i64, sqlx::Error> {
+        let (match_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO matches (player1_id, player2_id, in_progress, status, game_type, game_options, queued_at, series_length)
+             VALUES (?, NULL, 1, 'waiting', ?, ?, ?, ?)
+             ON CONFLICT (player1_id, game_type) WHERE status = 'waiting' DO UPDATE SET
+                game_options = excluded.game_options, series_length = excluded.series_length
+             RETURNING id"
+        )
+        .bind(player1_id)
+        .bind(game_type)
+        .bind(game_options)
+        .bind(battld_common::time() as i64)
+        .bind(series_length)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(match_id)
+    }
+
+    /// Waiting match reserved by a party member for their partner
+    pub async fn create_reserved_match(
+        &self,
+        player1_id: i64,
+        reserved_for: i64,
+        game_type: &str,
+        game_options: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let (match_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO matches (player1_id, player2_id, in_progress, status, game_type, game_options, reserved_for)
+             VALUES (?, NULL, 1, 'waiting', ?, ?, ?)
+             ON CONFLICT (player1_id, game_type) WHERE status = 'waiting'
+             DO UPDATE SET game_options = excluded.game_options, reserved_for = excluded.reserved_for
+             RETURNING id"
+        )
+        .bind(player1_id)
+        .bind(game_type)
+        .bind(game_options)
+        .bind(reserved_for)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(match_id)
+    }
+
+    /// Waiting match of `player1_id` reserved for `reserved_for`
+    pub async fn find_reserved_match(
+        &self,
+        player1_id: i64,
+        reserved_for: i64,
+        game_type: &str,
+        game_options: &str,
+    ) -> Option<MatchRecord> {
+        let sql = format!(
+            "{SELECT_MATCHES} WHERE m.status = 'waiting' AND m.player1_id = ? AND m.reserved_for = ? AND m.game_type = ? AND m.game_options = ? LIMIT 1"
+        );
+        sqlx::query_as::<_, MatchRecord>(&sql)
+        .bind(player1_id)
+        .bind(reserved_for)
+        .bind(game_type)
+        .bind(game_options)
+        .fetch_optional(&self.pool)
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Everyone waiting in matchmaking, longest waiting first
+    /// Matches reserved for someone are never handed to strangers, so they are left out
+    pub async fn get_matchmaking_pool(&self) -> Result<Vec<WaitingPlayer>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT m.id AS match_id, m.player1_id AS player_id, m.game_type, m.game_options,
+                    COALESCE(r.rating, ?) AS rating, COALESCE(m.queued_at, 0) AS queued_at, m.series_length
+             FROM matches m
+             LEFT JOIN player_game_ratings r ON r.player_id = m.player1_id AND r.game_type = m.game_type
+             WHERE m.status = 'waiting' AND m.reserved_for IS NULL
+             ORDER BY queued_at, m.id"
+        )
+        .bind(Rating::default().rating)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Rating of a player in one game type, the starting rating if they never played it rated
+    pub async fn get_game_rating(&self, player_id: i64, game_type: &str) -> Result<Rating, sqlx::Error> {
+        read_game_rating(&self.pool, player_id, game_type).await
+    }
+
+    /// Hold a waiting match for `player_id` while both players confirm, false if it was taken meanwhile
+    pub async fn reserve_waiting_match(&self, match_id: i64, player_id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE matches SET reserved_for = ? WHERE id = ? AND status = 'waiting' AND reserved_for IS NULL"
+        )
+        .bind(player_id)
+        .bind(match_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Fails with `RowNotFound` if the match is no longer waiting for an opponent
+    pub async fn join_waiting_match(
+        &self,
+        match_id: i64,
+        player2_id: i64,
+        game_state: &str,
+    ) -> Result<(), sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE matches SET player2_id = ?, game_state = ?, status = 'active', seq = seq + 1, last_move_at = ?
+             WHERE id = ? AND status = 'waiting'"
+        )
+        .bind(player2_id)
+        .bind(game_state)
+        .bind(battld_common::time() as i64)
+        .bind(match_id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+
+    /// Start a new match replaying a finished one, with its game type and options
+    pub async fn create_rematch(
+        &self,
+        rematch_of: i64,
+        player1_id: i64,
+        player2_id: i64,
+        game_state: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT INTO matches (player1_id, player2_id, in_progress, status, game_type, game_options, game_state, rematch_of, last_move_at)
+             SELECT ?, ?, 1, 'active', game_type, game_options, ?, id, ? FROM matches WHERE id = ?"
+        )
+        .bind(player1_id)
+        .bind(player2_id)
+        .bind(game_state)
+        .bind(battld_common::time() as i64)
+        .bind(rematch_of)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get_match_options(&self, match_id: i64) -> Option<serde_json::Value> {
+        sqlx::query_as::<_, (String,)>("SELECT game_options FROM matches WHERE id = ?")
+            .bind(match_id)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|(options,)| serde_json::from_str(&options).ok())
+    }
+
+    pub async fn start_rematch(
+        &self,
+        match_id: i64,
+        player1_id: i64,
+        player2_id: i64,
+        game_state: &str,
+        rematch_of: Option<i64>,
+    ) -> Result<(), sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE matches SET player1_id = ?, player2_id = ?, game_state = ?, rematch_of = ?, status = 'active', seq = seq + 1,
+                last_move_at = ?
+             WHERE id = ? AND status = 'waiting'"
+        )
+        .bind(player1_id)
+        .bind(player2_id)
+        .bind(game_state)
+        .bind(rematch_of)
+        .bind(battld_common::time() as i64)
+        .bind(match_id)
         .execute(&self.pool)
         .await?;
 
-        Ok(result.last_insert_rowid())
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+
+    /// Seat more players in a match, after player1_id and player2_id, for games played by more than two
+    pub async fn add_seats(&self, match_id: i64, player_ids: &[i64]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for player_id in player_ids {
+            sqlx::query(
+                "INSERT INTO match_seats (match_id, seat, player_id)
+                 SELECT ?, MAX(3, COALESCE(MAX(seat), 0) + 1), ? FROM match_seats WHERE match_id = ?"
+            )
+            .bind(match_id)
+            .bind(player_id)
+            .bind(match_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn get_active_match_for_player(&self, player_id: i64) -> Option<MatchRecord> {
+        let sql = format!(
+            "{SELECT_MATCHES} WHERE m.id IN (SELECT match_id FROM match_seats WHERE player_id = ?) AND m.status IN ('waiting', 'active', 'paused')"
+        );
+        sqlx::query_as::<_, MatchRecord>(&sql)
+        .bind(player_id)
+        .fetch_optional(&self.pool)
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Active matches, and paused ones waiting for a player to reconnect
+    pub async fn get_matches_in_play(&self) -> Result<Vec<MatchRecord>, sqlx::Error> {
+        let sql = format!("{SELECT_MATCHES} WHERE m.status IN ('active', 'paused') ORDER BY m.id");
+        sqlx::query_as::<_, MatchRecord>(&sql)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Most recent match of a player that was played to the end
+    pub async fn get_last_finished_match_for_player(&self, player_id: i64) -> Option<MatchRecord> {
+        let sql = format!(
+            "{SELECT_MATCHES} WHERE (m.player1_id = ? OR m.player2_id = ?) AND m.status = 'finished' ORDER BY m.id DESC LIMIT 1"
+        );
+        sqlx::query_as::<_, MatchRecord>(&sql)
+        .bind(player_id)
+        .bind(player_id)
+        .fetch_optional(&self.pool)
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Most recent finished match between two players, whoever sat first
+    pub async fn get_last_match_between(&self, player_a: i64, player_b: i64) -> Option<MatchRecord> {
+        let sql = format!(
+            "{SELECT_MATCHES} WHERE ((m.player1_id = ? AND m.player2_id = ?) OR (m.player1_id = ? AND m.player2_id = ?))
+             AND m.status = 'finished' ORDER BY m.id DESC LIMIT 1"
+        );
+        sqlx::query_as::<_, MatchRecord>(&sql)
+        .bind(player_a)
+        .bind(player_b)
+        .bind(player_b)
+        .bind(player_a)
+        .fetch_optional(&self.pool)
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Overwrite a match whatever was written to it meanwhile, matches in play go through `update_match_at_seq`
+    pub async fn update_match(
+        &self,
+        match_id: i64,
+        game_state: &str,
+        status: MatchStatus,
+        outcome: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let now = battld_common::time() as i64;
+        sqlx::query(
+            "UPDATE matches SET game_state = ?, in_progress = ?, status = ?, outcome = ?, seq = seq + 1, last_move_at = ?,
+                finished_at = CASE WHEN ? = 'finished' THEN COALESCE(finished_at, ?) ELSE finished_at END
+             WHERE id = ?"
+        )
+        .bind(game_state)
+        .bind(if status.is_open() { 1 } else { 0 })
+        .bind(status.as_str())
+        .bind(outcome)
+        .bind(now)
+        .bind(status.as_str())
+        .bind(now)
+        .bind(match_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Write the state a move led to, only if the match is still at `seq` when the move was worked out
+    /// False when another write got in first, in which case nothing is written
+    pub async fn update_match_at_seq(
+        &self,
+        match_id: i64,
+        seq: i64,
+        game_state: &str,
+        status: MatchStatus,
+        outcome: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        let now = battld_common::time() as i64;
+        let result = sqlx::query(
+            "UPDATE matches SET game_state = ?, in_progress = ?, status = ?, outcome = ?, seq = seq + 1, last_move_at = ?,
+                finished_at = CASE WHEN ? = 'finished' THEN COALESCE(finished_at, ?) ELSE finished_at END
+             WHERE id = ? AND seq = ?"
+        )
+        .bind(game_state)
+        .bind(if status.is_open() { 1 } else { 0 })
+        .bind(status.as_str())
+        .bind(outcome)
+        .bind(now)
+        .bind(status.as_str())
+        .bind(now)
+        .bind(match_id)
+        .bind(seq)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Record a draw offer waiting for the opponent, or clear it with None
+    pub async fn set_draw_offer(&self, match_id: i64, offered_by: Option<i64>) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE matches SET draw_offered_by = ? WHERE id = ?")
+            .bind(offered_by)
+            .bind(match_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Start a player's grace period to come back to a match, replacing any earlier one
+    pub async fn record_disconnect(&self, player_id: i64, match_id: i64, deadline: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR REPLACE INTO match_disconnects (player_id, match_id, deadline) VALUES (?, ?, ?)")
+            .bind(player_id)
+            .bind(match_id)
+            .bind(deadline)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_disconnect(&self, player_id: i64) -> Option<DisconnectRecord> {
+        sqlx::query_as::<_, DisconnectRecord>("SELECT player_id, match_id, deadline FROM match_disconnects WHERE player_id = ?")
+            .bind(player_id)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    pub async fn get_disconnects(&self) -> Result<Vec<DisconnectRecord>, sqlx::Error> {
+        sqlx::query_as::<_, DisconnectRecord>("SELECT player_id, match_id, deadline FROM match_disconnects ORDER BY deadline")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// End a player's grace period in a match, false if it was already over,
+    /// so only one of the servers sharing the database acts on it
+    pub async fn take_disconnect(&self, player_id: i64, match_id: Option<i64>) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM match_disconnects WHERE player_id = ? AND (? IS NULL OR match_id = ?)")
+            .bind(player_id)
+            .bind(match_id)
+            .bind(match_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_waiting_match_for_player(&self, player_id: i64) -> Option<MatchRecord> {
+        let sql = format!("{SELECT_MATCHES} WHERE m.player1_id = ? AND m.status = 'waiting'");
+        sqlx::query_as::<_, MatchRecord>(&sql)
+        .bind(player_id)
+        .fetch_optional(&self.pool)
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Move a match from one status to another
+    /// Returns false if the transition is invalid or the match is no longer in `from`
+    pub async fn transition_match(&self, match_id: i64, from: MatchStatus, to: MatchStatus) -> Result<bool, sqlx::Error> {
+        if !from.can_transition_to(to) {
+            return Ok(false);
+        }
+
+        let result = sqlx::query(
+            "UPDATE matches SET status = ?, in_progress = ?, seq = seq + 1 WHERE id = ? AND status = ?"
+        )
+        .bind(to.as_str())
+        .bind(if to.is_open() { 1 } else { 0 })
+        .bind(match_id)
+        .bind(from.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// In-progress matches with both players, newest first, with player names
+    pub async fn get_live_matches(&self, limit: i64) -> Result<Vec<LiveMatchRecord>, sqlx::Error> {
+        sqlx::query_as::<_, LiveMatchRecord>(
+            "SELECT m.id, m.game_type, m.player1_id, p1.name AS player1_name, m.player2_id, p2.name AS player2_name
+             FROM matches m
+             JOIN players p1 ON p1.id = m.player1_id
+             JOIN players p2 ON p2.id = m.player2_id
+             WHERE m.status IN ('active', 'paused')
+             ORDER BY m.id DESC
+             LIMIT ?"
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Open matches per game type as (game_type, active, waiting)
+    pub async fn count_open_matches(&self) -> Result<Vec<(String, i64, i64)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT game_type,
+                    SUM(CASE WHEN status IN ('active', 'paused') THEN 1 ELSE 0 END),
+                    SUM(CASE WHEN status = 'waiting' THEN 1 ELSE 0 END)
+             FROM matches
+             WHERE status IN ('waiting', 'active', 'paused')
+             GROUP BY game_type"
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Finished matches with an outcome whose scores were never applied
+    pub async fn get_unscored_matches(&self) -> Result<Vec<MatchRecord>, sqlx::Error> {
+        let sql = format!("{SELECT_MATCHES} WHERE m.status = 'finished' AND m.outcome IS NOT NULL AND m.scores_applied = 0 ORDER BY m.id");
+        sqlx::query_as::<_, MatchRecord>(&sql)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn get_match_by_id(&self, match_id: i64) -> Option<MatchRecord> {
+        let sql = format!("{SELECT_MATCHES} WHERE m.id = ?");
+        sqlx::query_as::<_, MatchRecord>(&sql)
+            .bind(match_id)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    // Challenge operations
+    pub async fn create_challenge(
+        &self,
+        challenger_id: i64,
+        challenged_id: i64,
+        game_type: &str,
+        game_options: &str,
+        created_at: i64,
+        expires_at: i64,
+    ) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT INTO challenges (challenger_id, challenged_id, game_type, game_options, created_at, expires_at)
+             VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(challenger_id)
+        .bind(challenged_id)
+        .bind(game_type)
+        .bind(game_options)
+        .bind(created_at)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get_challenge_by_id(&self, challenge_id: i64) -> Option<ChallengeRecord> {
+        sqlx::query_as::<_, ChallengeRecord>(&format!("{CHALLENGE_SELECT} WHERE c.id = ?"))
+            .bind(challenge_id)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Pending challenges sent by `challenger_id` to `challenged_id`
+    pub async fn find_pending_challenge(&self, challenger_id: i64, challenged_id: i64) -> Option<ChallengeRecord> {
+        sqlx::query_as::<_, ChallengeRecord>(&format!(
+            "{CHALLENGE_SELECT} WHERE c.challenger_id = ? AND c.challenged_id = ? AND c.status = 'pending' LIMIT 1"
+        ))
+        .bind(challenger_id)
+        .bind(challenged_id)
+        .fetch_optional(&self.pool)
+        .await
+        .ok()
+        .flatten()
+    }
+
+    pub async fn create_arena(&self, game_type: &str, starts_at: i64, ends_at: i64) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query("INSERT INTO arenas (game_type, starts_at, ends_at) VALUES (?, ?, ?)")
+            .bind(game_type)
+            .bind(starts_at)
+            .bind(ends_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get_arena(&self, arena_id: i64) -> Option<ArenaRecord> {
+        sqlx::query_as("SELECT id, game_type, starts_at, ends_at, finished FROM arenas WHERE id = ?")
+            .bind(arena_id)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Arenas not over yet, then the latest ones that ended, soonest ending first
+    pub async fn get_recent_arenas(&self, limit: i64) -> Result<Vec<ArenaRecord>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, game_type, starts_at, ends_at, finished FROM arenas
+             ORDER BY finished, CASE WHEN finished = 0 THEN ends_at ELSE -ends_at END LIMIT ?"
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Arenas that are not finished yet although their time is up
+    pub async fn get_overdue_arenas(&self, now: i64) -> Result<Vec<ArenaRecord>, sqlx::Error> {
+        sqlx::query_as("SELECT id, game_type, starts_at, ends_at, finished FROM arenas WHERE finished = 0 AND ends_at <= ?")
+            .bind(now)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Arenas starting by `until` that are not over at `now`
+    pub async fn get_arenas_starting_by(&self, until: i64, now: i64) -> Result<Vec<ArenaRecord>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, game_type, starts_at, ends_at, finished FROM arenas WHERE finished = 0 AND starts_at <= ? AND ends_at > ?"
+        )
+        .bind(until)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_running_arenas(&self, now: i64) -> Result<Vec<ArenaRecord>, sqlx::Error> {
+        self.get_arenas_starting_by(now, now).await
+    }
+
+    pub async fn finish_arena(&self, arena_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE arenas SET finished = 1 WHERE id = ?")
+            .bind(arena_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Put a player in the standings of an arena, keeping their points if they were already in it
+    pub async fn join_arena(&self, arena_id: i64, player_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR IGNORE INTO arena_players (arena_id, player_id) VALUES (?, ?)")
+            .bind(arena_id)
+            .bind(player_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_match_arena(&self, match_id: i64, arena_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE matches SET arena_id = ? WHERE id = ?")
+            .bind(arena_id)
+            .bind(match_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// The arena a match was paired in, if any
+    pub async fn get_match_arena(&self, match_id: i64) -> Option<i64> {
+        sqlx::query_as::<_, (Option<i64>,)>("SELECT arena_id FROM matches WHERE id = ?")
+            .bind(match_id)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|(arena_id,)| arena_id)
+    }
+
+    /// Standings of an arena, most points first
+    pub async fn get_arena_standings(&self, arena_id: i64) -> Result<Vec<ArenaStandingRecord>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT a.player_id, p.name AS player_name, a.points, a.games, a.wins, a.streak
+             FROM arena_players a
+             JOIN players p ON p.id = a.player_id
+             WHERE a.arena_id = ?
+             ORDER BY a.points DESC, a.wins DESC, a.games, a.player_id"
+        )
+        .bind(arena_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Current win streak of a player in an arena, 0 if they are not in it
+    pub async fn get_arena_streak(&self, arena_id: i64, player_id: i64) -> Result<i64, sqlx::Error> {
+        let streak: Option<(i64,)> = sqlx::query_as("SELECT streak FROM arena_players WHERE arena_id = ? AND player_id = ?")
+            .bind(arena_id)
+            .bind(player_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(streak.map(|(streak,)| streak).unwrap_or(0))
+    }
+
+    /// Add a finished match to a player's arena standing, returning their new total
+    pub async fn record_arena_result(&self, arena_id: i64, player_id: i64, earned: i64, won: bool, streak: i64) -> Result<i64, sqlx::Error> {
+        let (points,): (i64,) = sqlx::query_as(
+            "INSERT INTO arena_players (arena_id, player_id, points, games, wins, streak) VALUES (?, ?, ?, 1, ?, ?)
+             ON CONFLICT (arena_id, player_id) DO UPDATE SET
+                points = points + excluded.points, games = games + 1, wins = wins + excluded.wins, streak = excluded.streak
+             RETURNING points"
+        )
+        .bind(arena_id)
+        .bind(player_id)
+        .bind(earned)
+        .bind(won as i64)
+        .bind(streak)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(points)
+    }
+
+    /// Open a season, archived once `ends_at` passed
+    pub async fn create_season(&self, starts_at: i64, ends_at: i64) -> Result<SeasonRecord, sqlx::Error> {
+        sqlx::query_as("INSERT INTO seasons (starts_at, ends_at) VALUES (?, ?) RETURNING id, starts_at, ends_at, archived")
+            .bind(starts_at)
+            .bind(ends_at)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn get_season(&self, season_id: i64) -> Option<SeasonRecord> {
+        sqlx::query_as("SELECT id, starts_at, ends_at, archived FROM seasons WHERE id = ?")
+            .bind(season_id)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// The season being played, None before the first one started
+    pub async fn get_current_season(&self) -> Result<Option<SeasonRecord>, sqlx::Error> {
+        sqlx::query_as("SELECT id, starts_at, ends_at, archived FROM seasons WHERE archived = 0 ORDER BY id DESC LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Keep the final standings of a season and reset every score for the next one
+    /// Everyone with a score or a match finished since the season started is ranked, whatever their score
+    /// Ratings and tiers carry over, the standings keep them as they were at the end
+    pub async fn archive_season(&self, season_id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let claimed = sqlx::query("UPDATE seasons SET archived = 1 WHERE id = ? AND archived = 0")
+            .bind(season_id)
+            .execute(&mut *tx)
+            .await?;
+        if claimed.rows_affected() == 0 {
+            return tx.commit().await;
+        }
+        sqlx::query(
+            "INSERT INTO season_standings (season_id, player_id, rank, score, rating, rated_games, tier)
+             SELECT ?, id, ROW_NUMBER() OVER (ORDER BY score DESC, id ASC), score, rating, rated_games, tier
+             FROM players
+             WHERE is_guest = 0 AND (score != 0 OR id IN (
+                 SELECT s.player_id FROM match_seats s JOIN matches m ON m.id = s.match_id
+                 WHERE m.status = 'finished' AND m.finished_at >= (SELECT starts_at FROM seasons WHERE id = ?)
+             ))"
+        )
+        .bind(season_id)
+        .bind(season_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("UPDATE players SET score = 0 WHERE score != 0")
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    /// Add to a player's progress in a quest, never past its target
+    pub async fn add_quest_progress(&self, player_id: i64, quest_id: &str, amount: u32, target: u32) -> Result<u32, sqlx::Error> {
+        let (progress,): (u32,) = sqlx::query_as(
+            "INSERT INTO quest_progress (player_id, quest_id, progress) VALUES (?, ?, MIN(?, ?))
+             ON CONFLICT(player_id, quest_id) DO UPDATE SET progress = MIN(quest_progress.progress + excluded.progress, ?)
+             RETURNING progress"
+        )
+        .bind(player_id)
+        .bind(quest_id)
+        .bind(amount)
+        .bind(target)
+        .bind(target)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(progress)
+    }
+
+    /// Progress of a player in a quest, 0 before they made any
+    pub async fn get_quest_progress(&self, player_id: i64, quest_id: &str) -> Result<u32, sqlx::Error> {
+        let progress: Option<(u32,)> = sqlx::query_as("SELECT progress FROM quest_progress WHERE player_id = ? AND quest_id = ?")
+            .bind(player_id)
+            .bind(quest_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(progress.map_or(0, |(progress,)| progress))
+    }
+
+    /// Make a match just found in matchmaking the first game of the series its players asked for
+    /// None if they asked for a single match
+    pub async fn start_series(&self, match_id: i64) -> Result<Option<SeriesRecord>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let series_id: Option<(i64,)> = sqlx::query_as(
+            "INSERT INTO series (game_type, game_options, length, player1_id, player2_id)
+             SELECT game_type, game_options, series_length, player1_id, player2_id FROM matches
+             WHERE id = ? AND series_length IS NOT NULL AND series_id IS NULL
+             RETURNING id"
+        )
+        .bind(match_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some((series_id,)) = series_id else {
+            return Ok(None);
+        };
+
+        sqlx::query("UPDATE matches SET series_id = ? WHERE id = ?")
+            .bind(series_id)
+            .bind(match_id)
+            .execute(&mut *tx)
+            .await?;
+        let series = sqlx::query_as(&format!("{SELECT_SERIES} WHERE id = ?"))
+            .bind(series_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(Some(series))
+    }
+
+    /// The series a match is part of, if any
+    pub async fn get_match_series(&self, match_id: i64) -> Option<SeriesRecord> {
+        sqlx::query_as(&format!("{SELECT_SERIES} WHERE id = (SELECT series_id FROM matches WHERE id = ?)"))
+            .bind(match_id)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Series length a waiting or aborted match asked for, None for a single match
+    pub async fn get_series_length(&self, match_id: i64) -> Option<u32> {
+        sqlx::query_as::<_, (Option<u32>,)>("SELECT series_length FROM matches WHERE id = ?")
+            .bind(match_id)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|(length,)| length)
+    }
+
+    /// Count a finished match of a series, `winner_id` None for a draw
+    pub async fn record_series_result(&self, series_id: i64, winner_id: Option<i64>) -> Result<SeriesRecord, sqlx::Error> {
+        sqlx::query_as(
+            "UPDATE series SET
+                player1_wins = player1_wins + (player1_id IS ?),
+                player2_wins = player2_wins + (player2_id IS ?),
+                draws = draws + (? IS NULL)
+             WHERE id = ? AND finished = 0
+             RETURNING id, game_type, game_options, length, player1_id, player2_id, player1_wins, player2_wins, draws, finished"
+        )
+        .bind(winner_id)
+        .bind(winner_id)
+        .bind(winner_id)
+        .bind(series_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn finish_series(&self, series_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE series SET finished = 1 WHERE id = ?")
+            .bind(series_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Start the next match of a series, with its game type and options
+    pub async fn create_series_match(
+        &self,
+        series_id: i64,
+        player1_id: i64,
+        player2_id: i64,
+        game_state: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT INTO matches (player1_id, player2_id, in_progress, status, game_type, game_options, game_state, series_id, last_move_at)
+             SELECT ?, ?, 1, 'active', game_type, game_options, ?, id, ? FROM series WHERE id = ?"
+        )
+        .bind(player1_id)
+        .bind(player2_id)
+        .bind(game_state)
+        .bind(battld_common::time() as i64)
+        .bind(series_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Pending, not yet expired challenges addressed to a player, oldest first
+    pub async fn get_pending_challenges_for_player(&self, player_id: i64, now: i64) -> Result<Vec<ChallengeRecord>, sqlx::Error> {
+        sqlx::query_as::<_, ChallengeRecord>(&format!(
+            "{CHALLENGE_SELECT} WHERE c.challenged_id = ? AND c.status = 'pending' AND c.expires_at > ? ORDER BY c.id"
+        ))
+        .bind(player_id)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn update_challenge_status(&self, challenge_id: i64, status: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE challenges SET status = ? WHERE id = ?")
+            .bind(status)
+            .bind(challenge_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Shorten the expiry of a player's pending challenges to at most `expires_at`
+    pub async fn cap_pending_challenge_expiry(&self, player_id: i64, expires_at: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE challenges SET expires_at = MIN(expires_at, ?) WHERE challenged_id = ? AND status = 'pending'")
+            .bind(expires_at)
+            .bind(player_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Mark pending challenges past their expiry as expired, returning them
+    pub async fn expire_challenges(&self, now: i64) -> Result<Vec<ChallengeRecord>, sqlx::Error> {
+        let expired = sqlx::query_as::<_, ChallengeRecord>(&format!(
+            "{CHALLENGE_SELECT} WHERE c.status = 'pending' AND c.expires_at <= ?"
+        ))
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        for challenge in &expired {
+            self.update_challenge_status(challenge.id, "expired").await?;
+        }
+        Ok(expired)
+    }
+
+    pub async fn add_friend(&self, player_id: i64, friend_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR IGNORE INTO friends (player_id, friend_id, created_at) VALUES (?, ?, ?)")
+            .bind(player_id)
+            .bind(friend_id)
+            .bind(battld_common::time() as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn remove_friend(&self, player_id: i64, friend_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM friends WHERE player_id = ? AND friend_id = ?")
+            .bind(player_id)
+            .bind(friend_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn is_friend(&self, player_id: i64, friend_id: i64) -> Result<bool, sqlx::Error> {
+        let row: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM friends WHERE player_id = ? AND friend_id = ?")
+            .bind(player_id)
+            .bind(friend_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    pub async fn get_replay_privacy(&self, player_id: i64) -> Result<ReplayPrivacy, sqlx::Error> {
+        let (privacy,): (String,) = sqlx::query_as("SELECT replay_privacy FROM players WHERE id = ?")
+            .bind(player_id)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(ReplayPrivacy::parse(&privacy).unwrap_or(ReplayPrivacy::Private))
+    }
+
+    /// Change the replay privacy of a player, recording the change when there is one
+    pub async fn set_replay_privacy(&self, player_id: i64, privacy: ReplayPrivacy, changed_by: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let (old_privacy,): (String,) = sqlx::query_as("SELECT replay_privacy FROM players WHERE id = ?")
+            .bind(player_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE players SET replay_privacy = ? WHERE id = ?")
+            .bind(privacy.as_str())
+            .bind(player_id)
+            .execute(&mut *tx)
+            .await?;
+        let changes = [(setting_keys::REPLAY_PRIVACY, old_privacy, privacy.as_str().to_string())];
+        record_setting_changes(&mut tx, player_id, changed_by, &changes).await?;
+        tx.commit().await
+    }
+
+    pub async fn get_time_preferences(&self, player_id: i64) -> Result<TimePreferences, sqlx::Error> {
+        let (utc_offset_minutes, date_style): (i32, String) =
+            sqlx::query_as("SELECT utc_offset_minutes, date_style FROM players WHERE id = ?")
+                .bind(player_id)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(TimePreferences {
+            utc_offset_minutes,
+            date_style: DateStyle::parse(&date_style).unwrap_or_default(),
+        })
+    }
+
+    /// Change how a player wants times shown, recording the values that changed
+    pub async fn set_time_preferences(&self, player_id: i64, preferences: TimePreferences, changed_by: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let (old_offset, old_style): (i32, String) =
+            sqlx::query_as("SELECT utc_offset_minutes, date_style FROM players WHERE id = ?")
+                .bind(player_id)
+                .fetch_one(&mut *tx)
+                .await?;
+        sqlx::query("UPDATE players SET utc_offset_minutes = ?, date_style = ? WHERE id = ?")
+            .bind(preferences.utc_offset_minutes)
+            .bind(preferences.date_style.as_str())
+            .bind(player_id)
+            .execute(&mut *tx)
+            .await?;
+        let changes = [
+            (setting_keys::UTC_OFFSET_MINUTES, old_offset.to_string(), preferences.utc_offset_minutes.to_string()),
+            (setting_keys::DATE_STYLE, old_style, preferences.date_style.as_str().to_string()),
+        ];
+        record_setting_changes(&mut tx, player_id, changed_by, &changes).await?;
+        tx.commit().await
+    }
+
+    pub async fn get_quiet_hours(&self, player_id: i64) -> Result<Option<QuietHours>, sqlx::Error> {
+        let (quiet_hours,): (Option<String>,) = sqlx::query_as("SELECT quiet_hours FROM players WHERE id = ?")
+            .bind(player_id)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(quiet_hours.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    /// Quiet hours and UTC offset of every player who set some, by player id
+    pub async fn get_quiet_hours_schedules(&self) -> Result<HashMap<i64, (QuietHours, i32)>, sqlx::Error> {
+        let rows: Vec<(i64, String, i32)> =
+            sqlx::query_as("SELECT id, quiet_hours, utc_offset_minutes FROM players WHERE quiet_hours IS NOT NULL")
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(player_id, json, utc_offset_minutes)| {
+                serde_json::from_str(&json).ok().map(|quiet_hours| (player_id, (quiet_hours, utc_offset_minutes)))
+            })
+            .collect())
+    }
+
+    /// Change or turn off a player's quiet hours, recording the change
+    pub async fn set_quiet_hours(&self, player_id: i64, quiet_hours: Option<&QuietHours>, changed_by: i64) -> Result<(), sqlx::Error> {
+        let json = quiet_hours.map(|quiet_hours| serde_json::to_string(quiet_hours).unwrap_or_default());
+        let mut tx = self.pool.begin().await?;
+        let (old_json,): (Option<String>,) = sqlx::query_as("SELECT quiet_hours FROM players WHERE id = ?")
+            .bind(player_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE players SET quiet_hours = ? WHERE id = ?")
+            .bind(&json)
+            .bind(player_id)
+            .execute(&mut *tx)
+            .await?;
+        let off = || "off".to_string();
+        let changes = [(setting_keys::QUIET_HOURS, old_json.unwrap_or_else(off), json.unwrap_or_else(off))];
+        record_setting_changes(&mut tx, player_id, changed_by, &changes).await?;
+        tx.commit().await
+    }
+
+    /// Every recorded setting change of a player, newest first
+    pub async fn get_setting_changes(&self, player_id: i64) -> Result<Vec<SettingChange>, sqlx::Error> {
+        let rows: Vec<(String, Option<String>, String, i64, i64)> = sqlx::query_as(
+            "SELECT key, old_value, new_value, changed_by, changed_at FROM setting_changes
+             WHERE player_id = ? ORDER BY changed_at DESC, id DESC"
+        )
+        .bind(player_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(key, old_value, new_value, changed_by, changed_at)| SettingChange { key, old_value, new_value, changed_by, changed_at })
+            .collect())
+    }
+
+    /// When the name and when any setting of a player last changed
+    pub async fn get_last_setting_changes(&self, player_id: i64) -> Result<(Option<i64>, Option<i64>), sqlx::Error> {
+        sqlx::query_as(
+            "SELECT MAX(CASE WHEN key = ? THEN changed_at END), MAX(changed_at) FROM setting_changes WHERE player_id = ?"
+        )
+        .bind(setting_keys::NAME)
+        .bind(player_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Append a state to the replay of a match, `player_id` played `move_data` to get there
+    pub async fn add_match_frame(
+        &self,
+        match_id: i64,
+        player_id: Option<i64>,
+        move_data: Option<&str>,
+        game_state: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO match_frames (match_id, frame, player_id, move_data, game_state, created_at)
+             SELECT ?, COALESCE(MAX(frame) + 1, 0), ?, ?, ?, ? FROM match_frames WHERE match_id = ?"
+        )
+        .bind(match_id)
+        .bind(player_id)
+        .bind(move_data)
+        .bind(game_state)
+        .bind(battld_common::time() as i64)
+        .bind(match_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Replace the engine annotations of a match
+    pub async fn save_match_analysis(&self, match_id: i64, annotations: &[MoveAnnotation]) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR REPLACE INTO match_analysis (match_id, annotations, analyzed_at) VALUES (?, ?, ?)")
+            .bind(match_id)
+            .bind(serde_json::to_string(annotations).unwrap())
+            .bind(battld_common::time() as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Engine annotations of a match, empty when it was not analyzed
+    pub async fn get_match_analysis(&self, match_id: i64) -> Result<Vec<MoveAnnotation>, sqlx::Error> {
+        let row: Option<(String,)> = sqlx::query_as("SELECT annotations FROM match_analysis WHERE match_id = ?")
+            .bind(match_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.and_then(|(annotations,)| serde_json::from_str(&annotations).ok()).unwrap_or_default())
+    }
+
+    pub async fn get_match_frames(&self, match_id: i64) -> Result<Vec<MatchFrameRecord>, sqlx::Error> {
+        sqlx::query_as::<_, MatchFrameRecord>("SELECT player_id, move_data, game_state, created_at FROM match_frames WHERE match_id = ? ORDER BY frame")
+            .bind(match_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Keeps the first token, a shared link never changes
+    pub async fn set_replay_token(&self, match_id: i64, token: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE matches SET replay_token = ? WHERE id = ? AND replay_token IS NULL")
+            .bind(token)
+            .bind(match_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_match_by_replay_token(&self, token: &str) -> Option<MatchRecord> {
+        let sql = format!("{SELECT_MATCHES} WHERE m.replay_token = ?");
+        sqlx::query_as::<_, MatchRecord>(&sql)
+            .bind(token)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
     }
 
-    pub async fn create_waiting_match(&self, player1_id: i64, game_type: &str) -> Result<i64, sqlx::Error> {
-        let result = sqlx::query(
-            "INSERT INTO matches (player1_id, player2_id, in_progress, game_type)
-             VALUES (?, NULL, 1, ?)"
+    /// Latest finished matches of a player that can be replayed
+    pub async fn get_replayable_matches_for_player(&self, player_id: i64, limit: i64) -> Result<Vec<MatchRecord>, sqlx::Error> {
+        let sql = format!(
+            "{SELECT_MATCHES} WHERE (m.player1_id = ? OR m.player2_id = ?) AND m.replay_token IS NOT NULL ORDER BY m.id DESC LIMIT ?"
+        );
+        sqlx::query_as::<_, MatchRecord>(&sql)
+            .bind(player_id)
+            .bind(player_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Finished matches older than `before` whose state was not compacted yet
+    pub async fn get_matches_to_compact(&self, before: i64) -> Result<Vec<i64>, sqlx::Error> {
+        let rows: Vec<(i64,)> = sqlx::query_as(
+            "SELECT id FROM matches WHERE status = 'finished' AND finished_at < ? AND compacted_at IS NULL ORDER BY id"
         )
-        .bind(player1_id)
-        .bind(game_type)
-        .execute(&self.pool)
+        .bind(before)
+        .fetch_all(&self.pool)
         .await?;
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
 
-        Ok(result.last_insert_rowid())
+    /// Replace the state of a finished match with its summary and drop its recorded states
+    pub async fn compact_match(&self, match_id: i64, summary: &str) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE matches SET game_state = ?, compacted_at = ? WHERE id = ?")
+            .bind(summary)
+            .bind(battld_common::time() as i64)
+            .bind(match_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM match_frames WHERE match_id = ?")
+            .bind(match_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    /// Give the space freed by deleted rows back to the file system
+    pub async fn vacuum(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("VACUUM").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Write a consistent copy of the whole database to a new file at `path`
+    pub async fn backup_into(&self, path: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("VACUUM INTO ?").bind(path).execute(&self.pool).await?;
+        Ok(())
     }
 
-    pub async fn find_waiting_match(&self, player_id: i64, game_type: &str) -> Option<MatchRecord> {
-        sqlx::query_as::<_, MatchRecord>(
-            "SELECT * FROM matches WHERE player2_id IS NULL AND player1_id != ? AND in_progress = 1 AND game_type = ? LIMIT 1"
+    /// Finished matches since `since`, oldest first
+    pub async fn get_finished_matches_since(&self, since: i64) -> Result<Vec<FinishedMatchRecord>, sqlx::Error> {
+        sqlx::query_as::<_, FinishedMatchRecord>(
+            "SELECT id, player1_id, player2_id, outcome, finished_at FROM matches
+             WHERE status = 'finished' AND finished_at >= ? ORDER BY finished_at, id"
         )
-        .bind(player_id)
-        .bind(game_type)
-        .fetch_optional(&self.pool)
+        .bind(since)
+        .fetch_all(&self.pool)
         .await
-        .ok()
-        .flatten()
     }
 
-    pub async fn join_waiting_match(
-        &self,
-        match_id: i64,
-        player2_id: i64,
-        game_state: &str,
-    ) -> Result<(), sqlx::Error> {
+    /// Add a finding, or refresh the one with the same kind and players
+    pub async fn upsert_audit_finding(&self, finding: &AuditFinding) -> Result<(), sqlx::Error> {
         sqlx::query(
-            "UPDATE matches SET player2_id = ?, game_state = ? WHERE id = ?"
+            "INSERT INTO audit_findings (kind, severity, player1_id, player2_id, details, detected_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT (kind, player1_id, player2_id)
+             DO UPDATE SET severity = excluded.severity, details = excluded.details, detected_at = excluded.detected_at"
         )
-        .bind(player2_id)
-        .bind(game_state)
-        .bind(match_id)
+        .bind(&finding.kind)
+        .bind(finding.severity.as_str())
+        .bind(finding.player1_id)
+        .bind(finding.player2_id)
+        .bind(&finding.details)
+        .bind(finding.detected_at)
         .execute(&self.pool)
         .await?;
-
         Ok(())
     }
 
-    pub async fn get_active_match_for_player(&self, player_id: i64) -> Option<MatchRecord> {
-        sqlx::query_as::<_, MatchRecord>(
-            "SELECT * FROM matches WHERE (player1_id = ? OR player2_id = ?) AND in_progress = 1"
+    pub async fn get_audit_findings(&self) -> Result<Vec<AuditFinding>, sqlx::Error> {
+        let records = sqlx::query_as::<_, AuditFindingRecord>(
+            "SELECT kind, severity, player1_id, player2_id, details, detected_at FROM audit_findings ORDER BY detected_at DESC, id DESC"
         )
-        .bind(player_id)
-        .bind(player_id)
-        .fetch_optional(&self.pool)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .filter_map(|record| {
+                Some(AuditFinding {
+                    severity: AuditSeverity::parse(&record.severity)?,
+                    kind: record.kind,
+                    player1_id: record.player1_id,
+                    player2_id: record.player2_id,
+                    details: record.details,
+                    detected_at: record.detected_at,
+                })
+            })
+            .collect())
+    }
+
+    pub async fn get_exported_players(&self) -> Result<Vec<ExportedPlayerRecord>, sqlx::Error> {
+        sqlx::query_as::<_, ExportedPlayerRecord>(
+            "SELECT id, name, public_key_hint, public_key, score, rating, rating_deviation, rated_games, replay_privacy
+             FROM players ORDER BY id"
+        )
+        .fetch_all(&self.pool)
         .await
-        .ok()
-        .flatten()
     }
 
-    pub async fn update_match(
-        &self,
-        match_id: i64,
-        game_state: &str,
-        in_progress: bool,
-        outcome: Option<&str>,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query(
-            "UPDATE matches SET game_state = ?, in_progress = ?, outcome = ? WHERE id = ?"
+    pub async fn get_exported_matches(&self) -> Result<Vec<ExportedMatchRecord>, sqlx::Error> {
+        sqlx::query_as::<_, ExportedMatchRecord>(
+            "SELECT id, player1_id, player2_id, game_type, outcome, game_state, finished_at
+             FROM matches WHERE status = 'finished' ORDER BY id"
         )
-        .bind(game_state)
-        .bind(if in_progress { 1 } else { 0 })
-        .bind(outcome)
-        .bind(match_id)
-        .execute(&self.pool)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Add the players and matches of an export in one transaction
+    /// Ids are kept when this instance is empty, otherwise new ones are assigned and
+    /// players whose public key is already registered are merged into the existing account
+    pub async fn import_instance(&self, export: &InstanceExport) -> Result<ImportReport, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let (existing_rows,): (i64,) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM players) + (SELECT COUNT(*) FROM matches)"
+        )
+        .fetch_one(&mut *tx)
         .await?;
 
-        Ok(())
+        let mut report = ImportReport {
+            preserved_ids: existing_rows == 0,
+            ..Default::default()
+        };
+
+        for player in &export.players {
+            if !report.preserved_ids {
+                let existing: Option<(i64,)> = sqlx::query_as("SELECT id FROM players WHERE public_key = ?")
+                    .bind(&player.public_key)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                if let Some((player_id,)) = existing {
+                    report.player_ids.insert(player.id, player_id);
+                    report.players_merged += 1;
+                    continue;
+                }
+            }
+
+            let result = sqlx::query(
+                "INSERT INTO players (id, name, public_key_hint, public_key, score, rating, rating_deviation, rated_games, replay_privacy)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(report.preserved_ids.then_some(player.id))
+            .bind(&player.name)
+            .bind(&player.public_key_hint)
+            .bind(&player.public_key)
+            .bind(player.score)
+            .bind(player.rating)
+            .bind(player.rating_deviation)
+            .bind(player.rated_games)
+            .bind(player.replay_privacy.as_str())
+            .execute(&mut *tx)
+            .await?;
+            for game_rating in &player.game_ratings {
+                let rating = Rating {
+                    rating: game_rating.rating,
+                    deviation: game_rating.rating_deviation,
+                    games: game_rating.rated_games,
+                };
+                let game_type = serde_json::to_string(&game_rating.game_type).unwrap();
+                write_game_rating(&mut *tx, result.last_insert_rowid(), &game_type, &rating).await?;
+            }
+            report.player_ids.insert(player.id, result.last_insert_rowid());
+            report.players_imported += 1;
+        }
+
+        for exported in &export.matches {
+            let (Some(player1_id), Some(player2_id)) = (
+                report.player_ids.get(&exported.player1_id).copied(),
+                report.player_ids.get(&exported.player2_id).copied(),
+            ) else {
+                return Err(sqlx::Error::RowNotFound);
+            };
+            let outcome = exported.outcome.as_ref().map(|outcome| serde_json::to_string(outcome).unwrap());
+
+            let result = sqlx::query(
+                "INSERT INTO matches (id, player1_id, player2_id, in_progress, status, outcome, game_type, game_state, finished_at, scores_applied)
+                 VALUES (?, ?, ?, 0, 'finished', ?, ?, ?, ?, 1)"
+            )
+            .bind(report.preserved_ids.then_some(exported.id))
+            .bind(player1_id)
+            .bind(player2_id)
+            .bind(outcome)
+            .bind(serde_json::to_string(&exported.game_type).unwrap())
+            .bind(exported.game_state.to_string())
+            .bind(exported.finished_at)
+            .execute(&mut *tx)
+            .await?;
+            report.match_ids.insert(exported.id, result.last_insert_rowid());
+            report.matches_imported += 1;
+        }
+
+        tx.commit().await?;
+        Ok(report)
     }
 
-    pub async fn get_waiting_match_for_player(&self, player_id: i64) -> Option<MatchRecord> {
-        sqlx::query_as::<_, MatchRecord>(
-            "SELECT * FROM matches WHERE player1_id = ? AND player2_id IS NULL AND in_progress = 1"
+    /// Register a guest as a player, storing the quick play matches they finished with the score and ratings they earned
+    /// Their opponents are stored as guest players, all in one transaction so nothing is kept if any write fails
+    pub async fn upgrade_guest(
+        &self,
+        guest_id: i64,
+        public_key_hint: &str,
+        public_key: &str,
+        name: &str,
+        matches: &[GuestMatch],
+    ) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let player_id = sqlx::query("INSERT INTO players (public_key_hint, public_key, name) VALUES (?, ?, ?)")
+            .bind(public_key_hint)
+            .bind(public_key)
+            .bind(name)
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+
+        let mut opponents: HashMap<i64, i64> = HashMap::new();
+        for GuestMatch { match_data, finished_at } in matches {
+            let Some(outcome) = &match_data.outcome else {
+                continue;
+            };
+            let opponent_guest_id = if match_data.player1_id == guest_id { match_data.player2_id } else { match_data.player1_id };
+            let opponent_id = match opponents.get(&opponent_guest_id) {
+                Some(opponent_id) => *opponent_id,
+                None => {
+                    let opponent_name = match_data.player(opponent_guest_id).map(|player| player.name.as_str()).unwrap_or_default();
+                    let opponent_id = sqlx::query("INSERT INTO players (public_key_hint, public_key, name, is_guest) VALUES ('', '', ?, 1)")
+                        .bind(opponent_name)
+                        .execute(&mut *tx)
+                        .await?
+                        .last_insert_rowid();
+                    opponents.insert(opponent_guest_id, opponent_id);
+                    opponent_id
+                }
+            };
+
+            let as_player1 = match_data.player1_id == guest_id;
+            let (player1_id, player2_id) = if as_player1 { (player_id, opponent_id) } else { (opponent_id, player_id) };
+            let game_type = serde_json::to_string(&match_data.game_type).unwrap();
+            sqlx::query(
+                "INSERT INTO matches (player1_id, player2_id, in_progress, status, outcome, game_type, game_state, finished_at, scores_applied)
+                 VALUES (?, ?, 0, 'finished', ?, ?, ?, ?, 1)"
+            )
+            .bind(player1_id)
+            .bind(player2_id)
+            .bind(serde_json::to_string(outcome).unwrap())
+            .bind(&game_type)
+            .bind(match_data.game_state.to_string())
+            .bind(finished_at)
+            .execute(&mut *tx)
+            .await?;
+
+            let (player1_delta, player2_delta) = score_deltas(outcome);
+            let result = match (outcome, as_player1) {
+                (MatchOutcome::Draw, _) => 0.5,
+                (MatchOutcome::Player1Win, true) | (MatchOutcome::Player2Win, false) => 1.0,
+                _ => 0.0,
+            };
+            sqlx::query("UPDATE players SET score = score + ? WHERE id = ?")
+                .bind(if as_player1 { player1_delta } else { player2_delta })
+                .bind(player_id)
+                .execute(&mut *tx)
+                .await?;
+            let rating = read_rating(&mut *tx, player_id).await?;
+            write_rating(&mut *tx, player_id, &rating.update(&Rating::default(), result)).await?;
+            let game_rating = read_game_rating(&mut *tx, player_id, &game_type).await?;
+            write_game_rating(&mut *tx, player_id, &game_type, &game_rating.update(&Rating::default(), result)).await?;
+        }
+
+        tx.commit().await?;
+        Ok(player_id)
+    }
+
+    /// Place of a player on the score leaderboard, None while their score keeps them off it
+    pub async fn get_score_rank(&self, player_id: i64) -> Result<Option<i64>, sqlx::Error> {
+        let rank: Option<(i64,)> = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) + 1 FROM players o WHERE o.score > p.score OR (o.score = p.score AND o.id < p.id))
+             FROM players p WHERE p.id = ? AND p.score > 0"
         )
         .bind(player_id)
         .fetch_optional(&self.pool)
-        .await
-        .ok()
-        .flatten()
+        .await?;
+        Ok(rank.map(|(rank,)| rank))
     }
 
-    pub async fn delete_match(&self, match_id: i64) -> Result<(), sqlx::Error> {
-        sqlx::query("DELETE FROM matches WHERE id = ?")
-            .bind(match_id)
+    pub async fn get_rating(&self, player_id: i64) -> Result<Rating, sqlx::Error> {
+        read_rating(&self.pool, player_id).await
+    }
+
+    /// Ladder tier of a player, None while unranked
+    pub async fn get_tier(&self, player_id: i64) -> Result<Option<String>, sqlx::Error> {
+        let (tier,): (Option<String>,) = sqlx::query_as("SELECT tier FROM players WHERE id = ?")
+            .bind(player_id)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(tier)
+    }
+
+    pub async fn set_tier(&self, player_id: i64, tier: Option<&str>) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE players SET tier = ? WHERE id = ?")
+            .bind(tier)
+            .bind(player_id)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
-    pub async fn get_match_by_id(&self, match_id: i64) -> Option<MatchRecord> {
-        sqlx::query_as::<_, MatchRecord>("SELECT * FROM matches WHERE id = ?")
-            .bind(match_id)
-            .fetch_optional(&self.pool)
-            .await
-            .ok()
-            .flatten()
+    /// Ratings of a player in every game type they played rated matches of
+    pub async fn get_game_ratings(&self, player_id: i64) -> Result<Vec<(GameType, Rating)>, sqlx::Error> {
+        let rows: Vec<(String, f64, f64, i64)> = sqlx::query_as(
+            "SELECT game_type, rating, rating_deviation, rated_games FROM player_game_ratings
+             WHERE player_id = ? AND rated_games > 0 ORDER BY rated_games DESC, game_type ASC"
+        )
+        .bind(player_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(game_type, rating, deviation, games)| {
+                let game_type = serde_json::from_str(&game_type).ok()?;
+                Some((game_type, Rating { rating, deviation, games }))
+            })
+            .collect())
     }
 
+    /// Update scores and ratings of both players of a finished match
+    /// The match is marked in the same transaction, so running this twice for a match has no effect
     pub async fn update_player_scores_from_match(&self, match_record: &MatchRecord) -> Result<(), sqlx::Error> {
-        if let Some(outcome_str) = &match_record.outcome {
-            let outcome: MatchOutcome = match serde_json::from_str(outcome_str) {
-                Ok(o) => o,
-                Err(_) => return Ok(()), // Invalid outcome, skip
-            };
-
-            let player1_score_delta;
-            let player2_score_delta;
-
-            match outcome {
-                MatchOutcome::Player1Win => {
-                    player1_score_delta = 3;
-                    player2_score_delta = -1;
-                }
-                MatchOutcome::Player2Win => {
-                    player1_score_delta = -1;
-                    player2_score_delta = 3;
-                }
-                MatchOutcome::Draw => {
-                    player1_score_delta = 1;
-                    player2_score_delta = 1;
-                }
-            }
-
-            // Update player1 score
-            sqlx::query("UPDATE players SET score = score + ? WHERE id = ?")
-                .bind(player1_score_delta)
-                .bind(match_record.player1_id)
-                .execute(&self.pool)
-                .await?;
+        let Some(outcome) = match_record.outcome() else {
+            return Ok(()); // Missing or invalid outcome, skip
+        };
+
+        let (player1_score_delta, player2_score_delta) = score_deltas(&outcome);
+        let player1_result = match outcome {
+            MatchOutcome::Player1Win => 1.0,
+            MatchOutcome::Player2Win => 0.0,
+            MatchOutcome::Draw => 0.5,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        let claimed = sqlx::query("UPDATE matches SET scores_applied = 1 WHERE id = ? AND scores_applied = 0")
+            .bind(match_record.id)
+            .execute(&mut *tx)
+            .await?;
+        if claimed.rows_affected() == 0 {
+            println!("Scores of match {} were already applied", match_record.id);
+            return tx.commit().await;
+        }
+        let (bots,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM players WHERE id IN (?, ?) AND is_bot = 1")
+            .bind(match_record.player1_id)
+            .bind(match_record.player2_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if bots > 0 {
+            println!("Match {} was played against a bot, scores and ratings stay as they are", match_record.id);
+            return tx.commit().await;
+        }
 
-            // Update player2 score
+        for (player_id, delta) in [(match_record.player1_id, player1_score_delta), (match_record.player2_id, player2_score_delta)] {
             sqlx::query("UPDATE players SET score = score + ? WHERE id = ?")
-                .bind(player2_score_delta)
-                .bind(match_record.player2_id)
-                .execute(&self.pool)
+                .bind(delta)
+                .bind(player_id)
+                .execute(&mut *tx)
                 .await?;
         }
 
-        Ok(())
+        let player1_rating = read_rating(&mut *tx, match_record.player1_id).await?;
+        let player2_rating = read_rating(&mut *tx, match_record.player2_id).await?;
+        write_rating(&mut *tx, match_record.player1_id, &player1_rating.update(&player2_rating, player1_result)).await?;
+        write_rating(&mut *tx, match_record.player2_id, &player2_rating.update(&player1_rating, 1.0 - player1_result)).await?;
+
+        let game_type = &match_record.game_type;
+        let player1_rating = read_game_rating(&mut *tx, match_record.player1_id, game_type).await?;
+        let player2_rating = read_game_rating(&mut *tx, match_record.player2_id, game_type).await?;
+        write_game_rating(&mut *tx, match_record.player1_id, game_type, &player1_rating.update(&player2_rating, player1_result)).await?;
+        write_game_rating(&mut *tx, match_record.player2_id, game_type, &player2_rating.update(&player1_rating, 1.0 - player1_result)).await?;
+        tx.commit().await
+    }
+}
+
+/// Score each player of a ranked match gets for its outcome, player 1 first
+pub fn score_deltas(outcome: &MatchOutcome) -> (i64, i64) {
+    match outcome {
+        MatchOutcome::Player1Win => (3, -1),
+        MatchOutcome::Player2Win => (-1, 3),
+        MatchOutcome::Draw => (1, 1),
+    }
+}
+
+async fn read_rating<'e>(executor: impl SqliteExecutor<'e>, player_id: i64) -> Result<Rating, sqlx::Error> {
+    let row: Option<(f64, f64, i64)> = sqlx::query_as(
+        "SELECT rating, rating_deviation, rated_games FROM players WHERE id = ?"
+    )
+    .bind(player_id)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(row
+        .map(|(rating, deviation, games)| Rating { rating, deviation, games })
+        .unwrap_or_default())
+}
+
+async fn read_game_rating<'e>(executor: impl SqliteExecutor<'e>, player_id: i64, game_type: &str) -> Result<Rating, sqlx::Error> {
+    let row: Option<(f64, f64, i64)> = sqlx::query_as(
+        "SELECT rating, rating_deviation, rated_games FROM player_game_ratings WHERE player_id = ? AND game_type = ?"
+    )
+    .bind(player_id)
+    .bind(game_type)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(row
+        .map(|(rating, deviation, games)| Rating { rating, deviation, games })
+        .unwrap_or_default())
+}
+
+async fn write_game_rating<'e>(executor: impl SqliteExecutor<'e>, player_id: i64, game_type: &str, rating: &Rating) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO player_game_ratings (player_id, game_type, rating, rating_deviation, rated_games) VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT (player_id, game_type) DO UPDATE SET
+            rating = excluded.rating, rating_deviation = excluded.rating_deviation, rated_games = excluded.rated_games"
+    )
+    .bind(player_id)
+    .bind(game_type)
+    .bind(rating.rating)
+    .bind(rating.deviation)
+    .bind(rating.games)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+async fn write_rating<'e>(executor: impl SqliteExecutor<'e>, player_id: i64, rating: &Rating) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE players SET rating = ?, rating_deviation = ?, rated_games = ? WHERE id = ?")
+        .bind(rating.rating)
+        .bind(rating.deviation)
+        .bind(rating.games)
+        .bind(player_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Record the settings whose value differs, as part of the transaction changing them
+async fn record_setting_changes(
+    connection: &mut sqlx::SqliteConnection,
+    player_id: i64,
+    changed_by: i64,
+    changes: &[(&str, String, String)],
+) -> Result<(), sqlx::Error> {
+    let now = battld_common::time() as i64;
+    for (key, old_value, new_value) in changes.iter().filter(|(_, old_value, new_value)| old_value != new_value) {
+        sqlx::query(
+            "INSERT INTO setting_changes (player_id, changed_by, key, old_value, new_value, changed_at) VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(player_id)
+        .bind(changed_by)
+        .bind(key)
+        .bind(old_value)
+        .bind(new_value)
+        .bind(now)
+        .execute(&mut *connection)
+        .await?;
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -322,7 +2162,7 @@ mod tests {
 
         // Create a match with p1 winning
         let match_id = db.create_match(p1, p2, "{}", &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
-        db.update_match(match_id, "{}", false, Some(&serde_json::to_string(&MatchOutcome::Player1Win).unwrap())).await.unwrap();
+        db.update_match(match_id, "{}", MatchStatus::Finished, Some(&serde_json::to_string(&MatchOutcome::Player1Win).unwrap())).await.unwrap();
 
         let match_record = db.get_match_by_id(match_id).await.unwrap();
         db.update_player_scores_from_match(&match_record).await.unwrap();
@@ -333,6 +2173,98 @@ mod tests {
 
         assert_eq!(p1_record.score, 3, "Player 1 (winner) should have +3 points");
         assert_eq!(p2_record.score, -1, "Player 2 (loser) should have -1 points");
+
+        let p1_rating = db.get_rating(p1).await.unwrap();
+        let p2_rating = db.get_rating(p2).await.unwrap();
+        assert!(p1_rating.rating > 1500.0 && p2_rating.rating < 1500.0);
+        assert!(p1_rating.deviation < 350.0);
+        assert_eq!((p1_rating.games, p2_rating.games), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_player_waits_once_per_game_type() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let tic_tac_toe = serde_json::to_string(&GameType::TicTacToe).unwrap();
+        let chess = serde_json::to_string(&GameType::Chess).unwrap();
+
+        let first = db.create_waiting_match(p1, &tic_tac_toe, "{}", None).await.unwrap();
+        let again = db.create_waiting_match(p1, &tic_tac_toe, r#"{"board_size":4}"#, None).await.unwrap();
+        assert_eq!(first, again);
+        assert_eq!(db.get_match_by_id(first).await.unwrap().status, "waiting");
+        let (waiting,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM matches WHERE status = 'waiting'")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(waiting, 1);
+        let pool = db.get_matchmaking_pool().await.unwrap();
+        assert!(pool.iter().any(|waiting| waiting.match_id == first && waiting.game_options == r#"{"board_size":4}"#));
+
+        assert_ne!(db.create_waiting_match(p1, &chess, "{}", None).await.unwrap(), first);
+
+        db.transition_match(first, MatchStatus::Waiting, MatchStatus::Aborted).await.unwrap();
+        assert_ne!(db.create_waiting_match(p1, &tic_tac_toe, "{}", None).await.unwrap(), first);
+    }
+
+    #[tokio::test]
+    async fn test_game_ratings_are_kept_per_game_type() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+
+        for (game_type, outcome) in [
+            (GameType::TicTacToe, MatchOutcome::Player1Win),
+            (GameType::TicTacToe, MatchOutcome::Player1Win),
+            (GameType::Chess, MatchOutcome::Player2Win),
+        ] {
+            let match_id = db.create_match(p1, p2, "{}", &serde_json::to_string(&game_type).unwrap()).await.unwrap();
+            db.update_match(match_id, "{}", MatchStatus::Finished, Some(&serde_json::to_string(&outcome).unwrap())).await.unwrap();
+            db.update_player_scores_from_match(&db.get_match_by_id(match_id).await.unwrap()).await.unwrap();
+        }
+
+        let p1_ratings = db.get_game_ratings(p1).await.unwrap();
+        assert_eq!(p1_ratings.len(), 2);
+        let (game_type, tic_tac_toe) = &p1_ratings[0];
+        assert_eq!(*game_type, GameType::TicTacToe);
+        assert_eq!(tic_tac_toe.games, 2);
+        assert!(tic_tac_toe.rating > 1500.0);
+        let (game_type, chess) = &p1_ratings[1];
+        assert_eq!(*game_type, GameType::Chess);
+        assert!(chess.rating < 1500.0);
+
+        assert_eq!(db.get_rating(p1).await.unwrap().games, 3);
+        assert!(db.get_game_ratings(create_test_player(&db, "newcomer").await).await.unwrap().is_empty());
+
+        // Matches carry each player's rating in their game, none for games not played rated yet
+        let game_type = serde_json::to_string(&GameType::Chess).unwrap();
+        let match_data = db.get_match_by_id(db.create_match(p1, p2, "{}", &game_type).await.unwrap()).await.unwrap().to_match().unwrap();
+        assert_eq!(match_data.player(p1).unwrap().rating, Some(chess.rating.round() as i64));
+        let game_type = serde_json::to_string(&GameType::Briscola).unwrap();
+        let match_data = db.get_match_by_id(db.create_match(p1, p2, "{}", &game_type).await.unwrap()).await.unwrap().to_match().unwrap();
+        assert_eq!(match_data.player(p1).unwrap().rating, None);
+    }
+
+    #[tokio::test]
+    async fn test_update_player_scores_applies_once() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+
+        let match_id = db.create_match(p1, p2, "{}", &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
+        db.update_match(match_id, "{}", MatchStatus::Finished, Some(&serde_json::to_string(&MatchOutcome::Player1Win).unwrap())).await.unwrap();
+
+        let match_record = db.get_match_by_id(match_id).await.unwrap();
+        let (first, second) = tokio::join!(
+            db.update_player_scores_from_match(&match_record),
+            db.update_player_scores_from_match(&match_record),
+        );
+        first.unwrap();
+        second.unwrap();
+        db.update_player_scores_from_match(&match_record).await.unwrap();
+
+        assert_eq!(db.get_player_by_id(p1).await.unwrap().score, 3);
+        assert_eq!(db.get_player_by_id(p2).await.unwrap().score, -1);
+        assert_eq!(db.get_rating(p1).await.unwrap().games, 1);
     }
 
     #[tokio::test]
@@ -343,7 +2275,7 @@ mod tests {
 
         // Create a match with p2 winning
         let match_id = db.create_match(p1, p2, "{}", &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
-        db.update_match(match_id, "{}", false, Some(&serde_json::to_string(&MatchOutcome::Player2Win).unwrap())).await.unwrap();
+        db.update_match(match_id, "{}", MatchStatus::Finished, Some(&serde_json::to_string(&MatchOutcome::Player2Win).unwrap())).await.unwrap();
 
         let match_record = db.get_match_by_id(match_id).await.unwrap();
         db.update_player_scores_from_match(&match_record).await.unwrap();
@@ -364,7 +2296,7 @@ mod tests {
 
         // Create a match with draw
         let match_id = db.create_match(p1, p2, "{}", &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
-        db.update_match(match_id, "{}", false, Some(&serde_json::to_string(&MatchOutcome::Draw).unwrap())).await.unwrap();
+        db.update_match(match_id, "{}", MatchStatus::Finished, Some(&serde_json::to_string(&MatchOutcome::Draw).unwrap())).await.unwrap();
 
         let match_record = db.get_match_by_id(match_id).await.unwrap();
         db.update_player_scores_from_match(&match_record).await.unwrap();
@@ -385,19 +2317,19 @@ mod tests {
 
         // Match 1: p1 wins
         let match1 = db.create_match(p1, p2, "{}", &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
-        db.update_match(match1, "{}", false, Some(&serde_json::to_string(&MatchOutcome::Player1Win).unwrap())).await.unwrap();
+        db.update_match(match1, "{}", MatchStatus::Finished, Some(&serde_json::to_string(&MatchOutcome::Player1Win).unwrap())).await.unwrap();
         let match1_record = db.get_match_by_id(match1).await.unwrap();
         db.update_player_scores_from_match(&match1_record).await.unwrap();
 
         // Match 2: p2 wins
         let match2 = db.create_match(p1, p2, "{}", &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
-        db.update_match(match2, "{}", false, Some(&serde_json::to_string(&MatchOutcome::Player2Win).unwrap())).await.unwrap();
+        db.update_match(match2, "{}", MatchStatus::Finished, Some(&serde_json::to_string(&MatchOutcome::Player2Win).unwrap())).await.unwrap();
         let match2_record = db.get_match_by_id(match2).await.unwrap();
         db.update_player_scores_from_match(&match2_record).await.unwrap();
 
         // Match 3: draw
         let match3 = db.create_match(p1, p2, "{}", &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
-        db.update_match(match3, "{}", false, Some(&serde_json::to_string(&MatchOutcome::Draw).unwrap())).await.unwrap();
+        db.update_match(match3, "{}", MatchStatus::Finished, Some(&serde_json::to_string(&MatchOutcome::Draw).unwrap())).await.unwrap();
         let match3_record = db.get_match_by_id(match3).await.unwrap();
         db.update_player_scores_from_match(&match3_record).await.unwrap();
 
@@ -437,7 +2369,7 @@ mod tests {
 
         // Create a match with an unknown/invalid outcome
         let match_id = db.create_match(p1, p2, "{}", &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
-        db.update_match(match_id, "{}", false, Some("unknown")).await.unwrap();
+        db.update_match(match_id, "{}", MatchStatus::Finished, Some("unknown")).await.unwrap();
 
         let match_record = db.get_match_by_id(match_id).await.unwrap();
         db.update_player_scores_from_match(&match_record).await.unwrap();
@@ -449,4 +2381,262 @@ mod tests {
         assert_eq!(p1_record.score, 0, "Player 1 score should be 0 (unknown outcome)");
         assert_eq!(p2_record.score, 0, "Player 2 score should be 0 (unknown outcome)");
     }
+
+    #[tokio::test]
+    async fn test_get_live_matches() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let p3 = create_test_player(&db, "player3").await;
+        let game_type = serde_json::to_string(&GameType::TicTacToe).unwrap();
+
+        let live_id = db.create_match(p1, p2, "{}", &game_type).await.unwrap();
+        let finished_id = db.create_match(p1, p2, "{}", &game_type).await.unwrap();
+        db.update_match(finished_id, "{}", MatchStatus::Finished, Some(&serde_json::to_string(&MatchOutcome::Draw).unwrap())).await.unwrap();
+        db.create_waiting_match(p3, &game_type, "null", None).await.unwrap();
+
+        let live = db.get_live_matches(10).await.unwrap();
+        assert_eq!(live.len(), 1);
+
+        let live_match = live[0].to_live_match().unwrap();
+        assert_eq!(live_match.match_id, live_id);
+        assert_eq!(live_match.game_type, GameType::TicTacToe);
+        assert_eq!(live_match.player1_name, "player1");
+        assert_eq!(live_match.player2_name, "player2");
+    }
+
+    #[tokio::test]
+    async fn test_with_retry_recovers_from_transient_errors() {
+        let mut attempts = 0;
+        let result = with_retry(|| {
+            attempts += 1;
+            let outcome = if attempts < 3 { Err(sqlx::Error::PoolTimedOut) } else { Ok(attempts) };
+            async move { outcome }
+        }).await;
+
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_with_retry_gives_up() {
+        let mut attempts = 0;
+        let result: Result<(), _> = with_retry(|| {
+            attempts += 1;
+            async { Err(sqlx::Error::PoolTimedOut) }
+        }).await;
+        assert!(result.is_err());
+        assert_eq!(attempts, WRITE_ATTEMPTS);
+
+        // Permanent errors are not retried
+        let mut attempts = 0;
+        let result: Result<(), _> = with_retry(|| {
+            attempts += 1;
+            async { Err(sqlx::Error::RowNotFound) }
+        }).await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_quarantine() {
+        let db = create_test_db().await;
+
+        assert!(!db.is_match_quarantined(1));
+        db.quarantine_match(1);
+        assert!(db.is_match_quarantined(1));
+        assert!(db.clone().is_match_quarantined(1));
+        db.release_match(1);
+        assert!(!db.is_match_quarantined(1));
+    }
+
+    #[tokio::test]
+    async fn test_match_status_transitions() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let p3 = create_test_player(&db, "player3").await;
+        let game_type = serde_json::to_string(&GameType::TicTacToe).unwrap();
+
+        let match_id = db.create_waiting_match(p1, &game_type, "null", None).await.unwrap();
+        assert_eq!(db.get_waiting_match_for_player(p1).await.unwrap().status, "waiting");
+
+        // Only one opponent can join
+        db.join_waiting_match(match_id, p2, "{}").await.unwrap();
+        assert!(matches!(db.join_waiting_match(match_id, p3, "{}").await, Err(sqlx::Error::RowNotFound)));
+
+        // Invalid or stale transitions are refused
+        assert!(!db.transition_match(match_id, MatchStatus::Active, MatchStatus::Waiting).await.unwrap());
+        assert!(!db.transition_match(match_id, MatchStatus::Paused, MatchStatus::Active).await.unwrap());
+
+        assert!(db.transition_match(match_id, MatchStatus::Active, MatchStatus::Paused).await.unwrap());
+        let match_info = db.get_match_by_id(match_id).await.unwrap().to_match().unwrap();
+        assert_eq!(match_info.status, MatchStatus::Paused);
+        assert!(match_info.in_progress);
+        assert_eq!(db.get_active_match_for_player(p2).await.unwrap().id, match_id);
+    }
+
+    #[tokio::test]
+    async fn test_time_preferences() {
+        let db = create_test_db().await;
+        let player_id = create_test_player(&db, "player1").await;
+        assert_eq!(db.get_time_preferences(player_id).await.unwrap(), TimePreferences::default());
+
+        let preferences = TimePreferences { utc_offset_minutes: -300, date_style: DateStyle::Us };
+        db.set_time_preferences(player_id, preferences, player_id).await.unwrap();
+        assert_eq!(db.get_time_preferences(player_id).await.unwrap(), preferences);
+    }
+
+    #[tokio::test]
+    async fn test_stale_move_is_not_written() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let match_id = db.create_match(p1, p2, "{}", "\"TicTacToe\"").await.unwrap();
+        let seq = db.get_match_by_id(match_id).await.unwrap().seq;
+
+        assert!(db.update_match_at_seq(match_id, seq, "{\"first\":1}", MatchStatus::Active, None).await.unwrap());
+        assert!(!db.update_match_at_seq(match_id, seq, "{\"second\":1}", MatchStatus::Active, None).await.unwrap());
+
+        let record = db.get_match_by_id(match_id).await.unwrap();
+        assert_eq!(record.game_state, "{\"first\":1}");
+        assert_eq!(record.seq, seq + 1);
+    }
+
+    #[tokio::test]
+    async fn test_quiet_hours() {
+        let db = create_test_db().await;
+        let player_id = create_test_player(&db, "player1").await;
+        create_test_player(&db, "player2").await;
+        assert_eq!(db.get_quiet_hours(player_id).await.unwrap(), None);
+
+        let quiet_hours = QuietHours { start_minute: 22 * 60, end_minute: 7 * 60, except: vec![] };
+        db.set_time_preferences(player_id, TimePreferences { utc_offset_minutes: 60, date_style: DateStyle::Iso }, player_id).await.unwrap();
+        db.set_quiet_hours(player_id, Some(&quiet_hours), player_id).await.unwrap();
+        assert_eq!(db.get_quiet_hours(player_id).await.unwrap(), Some(quiet_hours.clone()));
+        let schedules = db.get_quiet_hours_schedules().await.unwrap();
+        assert_eq!(schedules.len(), 1);
+        assert_eq!(schedules[&player_id], (quiet_hours, 60));
+
+        db.set_quiet_hours(player_id, None, player_id).await.unwrap();
+        assert!(db.get_quiet_hours_schedules().await.unwrap().is_empty());
+        let changes = db.get_setting_changes(player_id).await.unwrap();
+        assert_eq!(changes[0].key, setting_keys::QUIET_HOURS);
+        assert_eq!(changes[0].new_value, "off");
+    }
+
+    #[tokio::test]
+    async fn test_setting_changes_recorded() {
+        let db = create_test_db().await;
+        let player_id = create_test_player(&db, "player1").await;
+        assert_eq!(db.get_last_setting_changes(player_id).await.unwrap(), (None, None));
+
+        let preferences = TimePreferences { utc_offset_minutes: 120, date_style: DateStyle::Iso };
+        db.set_time_preferences(player_id, preferences, player_id).await.unwrap();
+        db.set_replay_privacy(player_id, ReplayPrivacy::Friends, player_id).await.unwrap();
+
+        let changes = db.get_setting_changes(player_id).await.unwrap();
+        let keys: Vec<&str> = changes.iter().map(|change| change.key.as_str()).collect();
+        assert_eq!(keys, vec![setting_keys::REPLAY_PRIVACY, setting_keys::UTC_OFFSET_MINUTES]);
+        assert_eq!(changes[1].old_value.as_deref(), Some("0"));
+        assert_eq!(changes[1].new_value, "120");
+        assert_eq!(changes[1].changed_by, player_id);
+
+        let (name_changed_at, settings_changed_at) = db.get_last_setting_changes(player_id).await.unwrap();
+        assert_eq!(name_changed_at, None);
+        assert!(settings_changed_at.is_some());
+
+        // Saving the same values again changes nothing
+        db.set_time_preferences(player_id, preferences, player_id).await.unwrap();
+        assert_eq!(db.get_setting_changes(player_id).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rebinding_a_key_finds_the_player_by_it() {
+        let db = create_test_db().await;
+        let player_id = create_test_player(&db, "player1").await;
+        assert!(db.get_player_by_public_key("recovered_key").await.is_none());
+        assert!(db.get_player_by_public_key("").await.is_none());
+
+        db.set_player_key(player_id, "public_key.pem", "recovered_key", player_id).await.unwrap();
+        let player = db.get_player_by_public_key("recovered_key").await.unwrap();
+        assert_eq!(player.id, player_id);
+        assert_eq!(player.public_key_hint, "public_key.pem");
+
+        let changes = db.get_setting_changes(player_id).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].key, setting_keys::PUBLIC_KEY);
+        assert_eq!(changes[0].new_value, log_privacy::fingerprint("recovered_key"));
+    }
+
+    #[tokio::test]
+    async fn test_seats_follow_both_players_and_take_more() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let p3 = create_test_player(&db, "player3").await;
+        let p4 = create_test_player(&db, "player4").await;
+        let game_type = serde_json::to_string(&GameType::Briscola).unwrap();
+
+        let match_id = db.create_waiting_match(p1, &game_type, "{}", None).await.unwrap();
+        db.join_waiting_match(match_id, p2, "{}").await.unwrap();
+        db.add_seats(match_id, &[p3, p4]).await.unwrap();
+
+        let match_data = db.get_match_by_id(match_id).await.unwrap().to_match().unwrap();
+        assert_eq!(match_data.seats, vec![p1, p2, p3, p4]);
+        assert_eq!((match_data.player1_id, match_data.player2_id), (p1, p2));
+        assert_eq!(db.get_active_match_for_player(p4).await.unwrap().id, match_id);
+    }
+
+    #[tokio::test]
+    async fn test_find_players_by_name() {
+        let db = create_test_db().await;
+        let alice = create_test_player(&db, "Alice").await;
+        create_test_player(&db, "bob").await;
+        db.get_idle_bot("bot:alice", "alice").await.unwrap();
+
+        let found: Vec<i64> = db.find_players_by_name(" alice ").await.unwrap().iter().map(|player| player.id).collect();
+        assert_eq!(found, vec![alice]);
+        assert!(db.find_players_by_name("ali").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_match_analysis_round_trip() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let match_id = db.create_match(p1, p2, "{}", &serde_json::to_string(&GameType::Chess).unwrap()).await.unwrap();
+        assert!(db.get_match_analysis(match_id).await.unwrap().is_empty());
+
+        let annotation = MoveAnnotation {
+            ply: 0,
+            eval_cp: -350,
+            mate_in: None,
+            loss_cp: 380,
+            judgement: Some(battld_common::MoveJudgement::Blunder),
+            best_move: Some("e2e4".to_string()),
+        };
+        db.save_match_analysis(match_id, std::slice::from_ref(&annotation)).await.unwrap();
+        db.save_match_analysis(match_id, std::slice::from_ref(&annotation)).await.unwrap();
+        assert_eq!(db.get_match_analysis(match_id).await.unwrap(), vec![annotation]);
+    }
+
+    #[tokio::test]
+    async fn test_disconnects_are_taken_once() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let match_id = db.create_match(p1, p2, "{}", &serde_json::to_string(&GameType::Chess).unwrap()).await.unwrap();
+
+        db.record_disconnect(p1, match_id, 100).await.unwrap();
+        db.record_disconnect(p2, match_id, 50).await.unwrap();
+        db.record_disconnect(p1, match_id, 200).await.unwrap();
+        assert_eq!(db.get_disconnect(p1).await, Some(DisconnectRecord { player_id: p1, match_id, deadline: 200 }));
+        assert_eq!(db.get_disconnects().await.unwrap().iter().map(|record| record.player_id).collect::<Vec<_>>(), vec![p2, p1]);
+
+        assert!(!db.take_disconnect(p1, Some(match_id + 1)).await.unwrap());
+        assert!(db.take_disconnect(p1, Some(match_id)).await.unwrap());
+        assert!(!db.take_disconnect(p1, Some(match_id)).await.unwrap());
+        assert!(db.take_disconnect(p2, None).await.unwrap());
+        assert!(db.get_disconnects().await.unwrap().is_empty());
+    }
 }
@@ -0,0 +1,141 @@
+use battld_common::games::matches::Match;
+use tokio::sync::broadcast;
+
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Something that happened to a match, published once it is saved
+#[derive(Debug, Clone)]
+pub enum MatchEvent {
+    /// Both players are in and the game state is initialized
+    MatchStarted { match_data: Match },
+    /// A move was applied, the match may have finished with it
+    MoveMade { match_data: Match, player_id: i64, move_data: serde_json::Value },
+    /// The match reached its outcome, by play or by forfeit
+    MatchFinished { match_data: Match },
+}
+
+impl MatchEvent {
+    pub fn match_data(&self) -> &Match {
+        match self {
+            MatchEvent::MatchStarted { match_data }
+            | MatchEvent::MoveMade { match_data, .. }
+            | MatchEvent::MatchFinished { match_data } => match_data,
+        }
+    }
+}
+
+/// In-process fan-out of match events, so subsystems can react to matches
+/// without being wired into the game logic
+/// Match data is not redacted, subscribers must not forward it to players as is
+pub struct EventBus {
+    tx: broadcast::Sender<MatchEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { tx }
+    }
+
+    /// Receive every event published from now on
+    /// A subscriber that falls behind loses the oldest events instead of slowing down the game
+    pub fn subscribe(&self) -> broadcast::Receiver<MatchEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Publish an event, dropping it if nobody is subscribed
+    pub fn publish(&self, event: MatchEvent) {
+        let _ = self.tx.send(event);
+    }
+}
+
+/// Run `handler` on every event until the bus is gone
+pub fn spawn_subscriber<F>(bus: &EventBus, name: &'static str, mut handler: F) -> tokio::task::JoinHandle<()>
+where
+    F: FnMut(MatchEvent) + Send + 'static,
+{
+    let mut rx = bus.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => handler(event),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    println!("Event subscriber {name} fell behind, {missed} events skipped");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// Subscriber writing one log line per match lifecycle change
+pub fn log_match_events(event: MatchEvent) {
+    match &event {
+        MatchEvent::MatchStarted { match_data } => {
+            println!("[EVENT] Match {} started: {}", match_data.id, match_data.game_type);
+        }
+        MatchEvent::MatchFinished { match_data } => {
+            println!("[EVENT] Match {} finished: {:?}", match_data.id, match_data.outcome);
+        }
+        MatchEvent::MoveMade { .. } => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::{game_type::GameType, matches::MatchStatus};
+
+    fn test_match(id: i64) -> Match {
+        Match {
+            id,
+            player1_id: 1,
+            player2_id: 2,
+            seats: vec![],
+            in_progress: true,
+            status: MatchStatus::Active,
+            outcome: None,
+            game_type: GameType::TicTacToe,
+            game_state: serde_json::json!({}),
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_every_subscriber_receives_events() {
+        let bus = EventBus::new();
+        let mut first = bus.subscribe();
+        let mut second = bus.subscribe();
+
+        bus.publish(MatchEvent::MatchStarted { match_data: test_match(3) });
+
+        assert_eq!(first.recv().await.unwrap().match_data().id, 3);
+        assert_eq!(second.recv().await.unwrap().match_data().id, 3);
+    }
+
+    #[tokio::test]
+    async fn test_spawned_subscriber_handles_events() {
+        let bus = EventBus::new();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        spawn_subscriber(&bus, "test", move |event| {
+            let _ = tx.send(event.match_data().id);
+        });
+
+        bus.publish(MatchEvent::MatchFinished { match_data: test_match(5) });
+        assert_eq!(rx.recv().await, Some(5));
+    }
+
+    #[test]
+    fn test_publish_without_subscribers() {
+        EventBus::new().publish(MatchEvent::MatchStarted { match_data: test_match(1) });
+    }
+}
@@ -0,0 +1,240 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use battld_common::games::{chess::ChessGameState, game_type::GameType, matches::{Match, MatchOutcome, MatchStatus}};
+use battld_common::{GameExport, GameExportMove, GameExportPlayer, ReplayResponse};
+use serde::Deserialize;
+use server::games::chess::san_moves;
+
+use crate::{auth, replays, storage, AppState};
+
+/// Bumped whenever a field of `GameExport` changes meaning or goes away
+const EXPORT_FORMAT_VERSION: u32 = 1;
+const PGN_LINE_WIDTH: usize = 80;
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    /// Portable Game Notation, chess only
+    Pgn,
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// A finished match as a file, for whoever may watch its replay
+pub async fn get_match_export(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(match_id): Path<i64>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, StatusCode> {
+    let viewer = auth::authenticate_request(&state.session_cache, &headers).await?;
+    let record = state.db.get_match_by_id(match_id).await.ok_or(StatusCode::NOT_FOUND)?;
+
+    if !replays::can_watch(&state.db, &record, Some(viewer)).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if MatchStatus::parse(&record.status) != Some(MatchStatus::Finished) {
+        return Err(StatusCode::CONFLICT);
+    }
+    let replay = replays::load_replay(&state, &record).await?;
+
+    match query.format {
+        ExportFormat::Json => Ok(Json(game_export(&replay, record.finished_at)).into_response()),
+        ExportFormat::Pgn => {
+            let pgn = pgn(&replay.match_data, record.finished_at).ok_or(StatusCode::BAD_REQUEST)?;
+            Ok(([(header::CONTENT_TYPE, "application/x-chess-pgn")], pgn).into_response())
+        }
+    }
+}
+
+fn player_name(match_data: &Match, player_id: i64) -> String {
+    match_data.player(player_id).map(|player| player.name.clone()).unwrap_or_else(|| format!("Player {player_id}"))
+}
+
+fn game_export(replay: &ReplayResponse, finished_at: Option<i64>) -> GameExport {
+    let match_data = &replay.match_data;
+    let players = match_data
+        .seat_ids()
+        .into_iter()
+        .filter_map(|id| Some(GameExportPlayer { seat: match_data.seat_of(id)?, id, name: player_name(match_data, id) }))
+        .collect();
+    let moves = replay
+        .frames
+        .iter()
+        .filter_map(|frame| Some((frame.player_id, frame.move_data.clone()?, frame.played_at)))
+        .enumerate()
+        .map(|(index, (player_id, move_data, played_at))| GameExportMove { ply: index + 1, player_id, move_data, played_at })
+        .collect();
+
+    GameExport {
+        format_version: EXPORT_FORMAT_VERSION,
+        match_id: match_data.id,
+        game_type: match_data.game_type.clone(),
+        players,
+        outcome: match_data.outcome.clone(),
+        finished_at,
+        moves,
+        final_state: match_data.game_state.clone(),
+    }
+}
+
+/// PGN of a chess match with the seven required tags, None for other games
+fn pgn(match_data: &Match, finished_at: Option<i64>) -> Option<String> {
+    if match_data.game_type != GameType::Chess {
+        return None;
+    }
+    let state: ChessGameState = serde_json::from_value(match_data.game_state.clone()).ok()?;
+    let result = match match_data.outcome {
+        Some(MatchOutcome::Player1Win) => "1-0",
+        Some(MatchOutcome::Player2Win) => "0-1",
+        Some(MatchOutcome::Draw) => "1/2-1/2",
+        None => "*",
+    };
+    let date = finished_at.map_or("????.??.??".to_string(), |finished_at| {
+        let (year, month, day) = storage::civil_date(finished_at.div_euclid(DAY_SECS));
+        format!("{year:04}.{month:02}.{day:02}")
+    });
+    let tags = [
+        ("Event", "Battld match".to_string()),
+        ("Site", "Battld".to_string()),
+        ("Date", date),
+        ("Round", "-".to_string()),
+        ("White", player_name(match_data, match_data.player1_id)),
+        ("Black", player_name(match_data, match_data.player2_id)),
+        ("Result", result.to_string()),
+    ];
+
+    let mut pgn: String = tags
+        .iter()
+        .map(|(name, value)| format!("[{name} \"{}\"]\n", value.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    pgn.push('\n');
+
+    let tokens = san_moves(&state.move_history)
+        .into_iter()
+        .enumerate()
+        .map(|(index, san)| if index % 2 == 0 { format!("{}. {san}", index / 2 + 1) } else { san })
+        .chain(std::iter::once(result.to_string()));
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > PGN_LINE_WIDTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+    Some(pgn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::{chess::{ChessMove, ChessPosition}, matches::MatchPlayer};
+    use battld_common::ReplayFrame;
+
+    fn finished_match(game_type: GameType, game_state: serde_json::Value, outcome: MatchOutcome) -> Match {
+        let player = |id: i64, name: &str| MatchPlayer { id, name: name.to_string(), score: 0, bot: false, rating: None };
+        Match {
+            id: 7,
+            player1_id: 1,
+            player2_id: 2,
+            seats: vec![],
+            in_progress: false,
+            status: MatchStatus::Finished,
+            outcome: Some(outcome),
+            game_type,
+            game_state,
+            players: vec![player(1, "alice"), player(2, "bob \"the rook\"")],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
+        }
+    }
+
+    fn chess_move(from: &str, to: &str) -> ChessMove {
+        ChessMove {
+            from: ChessPosition::from_algebraic(from).unwrap(),
+            to: ChessPosition::from_algebraic(to).unwrap(),
+            promotion: None,
+        }
+    }
+
+    #[test]
+    fn test_pgn_of_a_finished_chess_match() {
+        let mut state = ChessGameState::new();
+        state.move_history = [("e2", "e4"), ("e7", "e5"), ("f1", "c4"), ("b8", "c6"), ("d1", "h5"), ("g8", "f6"), ("h5", "f7")]
+            .iter()
+            .map(|(from, to)| chess_move(from, to))
+            .collect();
+        let match_data = finished_match(GameType::Chess, serde_json::to_value(&state).unwrap(), MatchOutcome::Player1Win);
+
+        let exported = pgn(&match_data, Some(20409 * DAY_SECS + 3600)).unwrap();
+        assert!(exported.starts_with("[Event \"Battld match\"]\n"));
+        assert!(exported.contains("[Date \"2025.11.17\"]\n"));
+        assert!(exported.contains("[White \"alice\"]\n"));
+        assert!(exported.contains("[Black \"bob \\\"the rook\\\"\"]\n"));
+        assert!(exported.contains("[Result \"1-0\"]\n\n"));
+        assert!(exported.ends_with("\n1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n"));
+
+        state.move_history = (0..30).flat_map(|_| [chess_move("g1", "f3"), chess_move("g8", "f6"), chess_move("f3", "g1"), chess_move("f6", "g8")]).collect();
+        let long = finished_match(GameType::Chess, serde_json::to_value(&state).unwrap(), MatchOutcome::Draw);
+        let exported = pgn(&long, None).unwrap();
+        assert!(exported.contains("[Date \"????.??.??\"]\n"));
+        assert!(exported.lines().all(|line| line.len() <= PGN_LINE_WIDTH));
+        assert!(exported.ends_with(" 1/2-1/2\n"));
+
+        let tic_tac_toe = finished_match(GameType::TicTacToe, serde_json::json!({}), MatchOutcome::Draw);
+        assert!(pgn(&tic_tac_toe, None).is_none());
+    }
+
+    #[test]
+    fn test_json_export_lists_the_moves() {
+        let final_state = serde_json::json!({ "board": [1, 2, 0, 0, 1, 0, 0, 2, 1] });
+        let frame = |player_id: Option<i64>, move_data: Option<serde_json::Value>, played_at: Option<i64>| ReplayFrame {
+            player_id,
+            game_state: final_state.clone(),
+            move_data,
+            played_at,
+        };
+        let replay = ReplayResponse {
+            match_data: finished_match(GameType::TicTacToe, final_state.clone(), MatchOutcome::Player1Win),
+            frames: vec![
+                frame(None, None, Some(100)),
+                frame(Some(1), Some(serde_json::json!({ "row": 0, "col": 0 })), Some(105)),
+                frame(Some(2), Some(serde_json::json!({ "row": 0, "col": 1 })), None),
+            ],
+            summary: None,
+            analysis: vec![],
+        };
+
+        let export = game_export(&replay, Some(200));
+        assert_eq!(export.format_version, EXPORT_FORMAT_VERSION);
+        assert_eq!(export.match_id, 7);
+        assert_eq!(export.players.len(), 2);
+        assert_eq!(export.players[1].name, "bob \"the rook\"");
+        assert_eq!(export.moves.len(), 2);
+        assert_eq!(export.moves[0].ply, 1);
+        assert_eq!(export.moves[0].player_id, Some(1));
+        assert_eq!(export.moves[0].played_at, Some(105));
+        assert_eq!(export.moves[1].move_data, serde_json::json!({ "row": 0, "col": 1 }));
+        assert_eq!(export.final_state, final_state);
+        assert_eq!(export.outcome, Some(MatchOutcome::Player1Win));
+    }
+}
@@ -1,9 +1,25 @@
-use battld_common::{games::{game_type::GameType, matches::{MatchEndReason, MatchOutcome}}, ServerMessage};
-use crate::database::Database;
+use battld_common::{games::{game_type::GameType, matches::{Match, MatchEndReason, MatchOutcome, MatchStatus}, rock_paper_scissors::RockPaperScissorsMove}, ErrorCode, MatchSummary, MatchmakingRejection, ServerMessage};
+use std::collections::HashMap;
+use std::time::Duration;
+use crate::capacity::{self, Capacity};
+use crate::chaos::{self, CrashPoint};
+use crate::database::{self, Database};
 use crate::game_router;
+use crate::matchmaking::{self, RatingWindow};
+use server::games::TimeoutOutcome;
+use crate::events::{EventBus, MatchEvent};
+use crate::ready_check::{self, ReadyChecks};
+use crate::series;
 
 // Match is used in game_router functions called from this module
 
+/// How long players see the result of a Rock-Paper-Scissors round before the next one starts
+pub const ROUND_RESULT_PAUSE: Duration = Duration::from_millis(2500);
+
+/// Times a move is worked out again from a fresh read when another write to its match got in first
+const MOVE_ATTEMPTS: u32 = 3;
+
+
 /// Represents a message to be sent to a specific player
 #[derive(Debug, Clone)]
 pub struct OutgoingMessage {
@@ -12,9 +28,12 @@ pub struct OutgoingMessage {
 }
 
 /// Handle resume match request - returns messages to send
+/// `delivered` has the sequence of the latest state each connected player got, an opponent
+/// who is up to date only hears that the player is back instead of getting the whole state again
 pub async fn handle_resume_match_logic(
     player_id: i64,
     resumable_match_id: Option<i64>,
+    delivered: &HashMap<i64, i64>,
     db: &Database,
 ) -> Vec<OutgoingMessage> {
     let match_id = match resumable_match_id {
@@ -22,9 +41,7 @@ pub async fn handle_resume_match_logic(
         None => {
             return vec![OutgoingMessage {
                 player_id,
-                message: ServerMessage::Error {
-                    message: "No resumable match found".to_string(),
-                },
+                message: ServerMessage::error(ErrorCode::NoResumableMatch, &[]),
             }];
         }
     };
@@ -35,115 +52,178 @@ pub async fn handle_resume_match_logic(
         None => {
             return vec![OutgoingMessage {
                 player_id,
-                message: ServerMessage::Error {
-                    message: "Match not found".to_string(),
-                },
+                message: ServerMessage::error(ErrorCode::MatchNotFound, &[]),
             }];
         }
     };
 
-    let match_info = match match_record.to_match() {
+    let mut match_info = match match_record.to_match() {
         Some(m) => m,
         None => {
             return vec![OutgoingMessage {
                 player_id,
-                message: ServerMessage::Error {
-                    message: "Failed to load match data".to_string(),
-                },
+                message: ServerMessage::error(ErrorCode::MatchUnavailable, &[]),
             }];
         }
     };
 
-    if !match_info.in_progress {
+    if !match_info.status.is_playing() {
         return vec![OutgoingMessage {
             player_id,
-            message: ServerMessage::Error {
-                message: "Match is no longer active".to_string(),
-            },
+            message: ServerMessage::error(ErrorCode::MatchNotActive, &[]),
         }];
     }
 
-    println!("Player {player_id} resumed match {match_id}");
+    if match_info.status == MatchStatus::Paused {
+        match db.transition_match(match_id, MatchStatus::Paused, MatchStatus::Active).await {
+            Ok(true) => {
+                let _ = match_info.transition_to(MatchStatus::Active);
+                match_info.seq += 1;
+            }
+            Ok(false) => println!("Match {match_id} was no longer paused when player {player_id} resumed"),
+            Err(e) => println!("Failed to mark match {match_id} active again: {e}"),
+        }
+    }
 
-    // Send GameStateUpdate to both players
-    let opponent_id = if match_info.player1_id == player_id {
-        match_info.player2_id
-    } else {
-        match_info.player1_id
-    };
+    println!("Player {player_id} resumed match {match_id}");
 
-    vec![
-        OutgoingMessage {
-            player_id,
-            message: ServerMessage::GameStateUpdate {
-                match_data: game_router::redact_match_for_player(&match_info, player_id),
-            },
+    // Send GameStateUpdate to every seat, or just the reconnection to opponents already up to date
+    let mut messages = vec![OutgoingMessage {
+        player_id,
+        message: ServerMessage::GameStateUpdate {
+            match_data: game_router::redact_match_for_player(&match_info, player_id),
         },
-        OutgoingMessage {
-            player_id: opponent_id,
-            message: ServerMessage::GameStateUpdate {
+    }];
+    messages.extend(match_info.opponents_of(player_id).into_iter().map(|opponent_id| {
+        let message = if delivered.get(&opponent_id) == Some(&match_record.seq) {
+            ServerMessage::PlayerReconnected { player_id, match_id, seq: match_info.seq }
+        } else {
+            ServerMessage::GameStateUpdate {
                 match_data: game_router::redact_match_for_player(&match_info, opponent_id),
-            },
+            }
+        };
+        OutgoingMessage { player_id: opponent_id, message }
+    }));
+    messages
+}
+
+/// The state of the match a player is already in, None if they are free to queue
+pub async fn current_match_logic(player_id: i64, db: &Database) -> Option<Vec<OutgoingMessage>> {
+    let match_record = db.get_active_match_for_player(player_id).await?;
+    println!("Player {player_id} already in match {}", match_record.id);
+    let Some(match_info) = match_record.to_match() else {
+        return Some(vec![]);
+    };
+    Some(vec![OutgoingMessage {
+        player_id,
+        message: ServerMessage::GameStateUpdate {
+            match_data: game_router::redact_match_for_player(&match_info, player_id),
         },
-    ]
+    }])
+}
+
+/// What a player asking to queue while already seated in a match is told, None if they are free to queue
+/// Players still waiting for an opponent stay in the queue they are in
+pub async fn queued_or_playing_logic(player_id: i64, db: &Database) -> Option<Vec<OutgoingMessage>> {
+    let match_record = db.get_active_match_for_player(player_id).await?;
+    println!("Player {player_id} already in match {}", match_record.id);
+    let Some(match_info) = match_record.to_match().filter(|m| m.status != MatchStatus::Waiting) else {
+        return Some(vec![]);
+    };
+    Some(matchmaking_rejected(
+        player_id,
+        MatchmakingRejection::AlreadyInMatch { match_id: match_info.id, game_type: match_info.game_type },
+    ))
+}
+
+pub fn matchmaking_rejected(player_id: i64, reason: MatchmakingRejection) -> Vec<OutgoingMessage> {
+    vec![OutgoingMessage { player_id, message: ServerMessage::MatchmakingRejected { reason } }]
 }
 
 /// Handle matchmaking request - returns messages to send
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_join_matchmaking_logic(
     player_id: i64,
     game_type: GameType,
+    options: serde_json::Value,
+    series_length: Option<u32>,
+    capacity: &Capacity,
+    window: &RatingWindow,
+    ready_checks: &ReadyChecks,
+    events: &EventBus,
     db: &Database,
 ) -> Vec<OutgoingMessage> {
-    // Check if player already has an active match
-    if let Some(match_record) = db.get_active_match_for_player(player_id).await {
-        println!("Player {player_id} already in match {}", match_record.id);
-        if let Some(match_info) = match_record.to_match() {
+    if let Some(messages) = queued_or_playing_logic(player_id, db).await {
+        return messages;
+    }
+
+    if ready_checks.is_pending(player_id) {
+        return matchmaking_rejected(player_id, MatchmakingRejection::ReadyCheckPending);
+    }
+
+    let options = match game_router::normalize_game_options(&game_type, &options) {
+        Ok(options) => options,
+        Err(e) => {
             return vec![OutgoingMessage {
                 player_id,
-                message: ServerMessage::GameStateUpdate {
-                    match_data: game_router::redact_match_for_player(&match_info, player_id),
-                },
+                message: e.to_options_message(),
             }];
         }
-        return vec![];
-    }
+    };
 
-    let game_type_json = serde_json::to_string(&game_type).unwrap();
+    let series_length = match series::validate_length(series_length) {
+        Ok(series_length) => series_length,
+        Err(code) => {
+            return vec![OutgoingMessage {
+                player_id,
+                message: ServerMessage::error(code, &[]),
+            }];
+        }
+    };
 
-    // Try to find a waiting opponent
-    if let Some(waiting_match) = db.find_waiting_match(player_id, &game_type_json).await {
-        let p1_id = waiting_match.player1_id;
+    let game_type_json = serde_json::to_string(&game_type).unwrap();
+    let options_json = options.to_string();
+    let load = capacity::load_matches(db).await.unwrap_or_default();
+
+    // Try to find a waiting opponent with the same options, series length and a close rating
+    let rating = db.get_game_rating(player_id, &game_type_json).await.unwrap_or_default().rating;
+    let pool = db.get_matchmaking_pool().await.unwrap_or_default();
+    let candidates = pool.iter().filter(|waiting| {
+        waiting.player_id != player_id
+            && waiting.game_type == game_type_json
+            && waiting.game_options == options_json
+            && waiting.series_length == series_length
+    });
+    // Tables of more than two are filled by the matchmaking sweep
+    let opponent = (game_router::seat_count(&game_type) == 2)
+        .then(|| matchmaking::pick_opponent(rating, candidates, window, battld_common::time() as i64))
+        .flatten();
+    if let Some(waiting_match) = opponent {
+        let p1_id = waiting_match.player_id;
         let p2_id = player_id;
-        println!("Matching player {player_id} with waiting player {p1_id} for game type: {game_type}");
 
-        // Initialize game state based on game type
-        let game_state_json = game_router::initialize_game_state(&game_type);
-
-        // Update the waiting match
-        if (db.join_waiting_match(waiting_match.id, p2_id, &game_state_json).await).is_ok() {
-            if let Some(match_record) = db.get_match_by_id(waiting_match.id).await {
-                if let Some(match_info) = match_record.to_match() {
-                    // Notify both players
-                    return vec![
-                        OutgoingMessage {
-                            player_id: p1_id,
-                            message: ServerMessage::MatchFound {
-                                match_data: game_router::redact_match_for_player(&match_info, p1_id),
-                            },
-                        },
-                        OutgoingMessage {
-                            player_id: p2_id,
-                            message: ServerMessage::MatchFound {
-                                match_data: game_router::redact_match_for_player(&match_info, p2_id),
-                            },
-                        },
-                    ];
-                }
-            }
+        if let Err(limit) = capacity.check_new_match(&[p1_id, p2_id], &game_type, &load) {
+            println!("Player {player_id} turned away from matchmaking: {limit:?}");
+            return matchmaking_rejected(player_id, capacity.full_rejection());
+        }
+
+        if ready_checks.is_enabled() {
+            println!("Player {player_id} found waiting player {p1_id}, starting ready check");
+            return ready_check::start_ready_check_logic(waiting_match.match_id, p1_id, p2_id, game_type, options, ready_checks, db).await;
+        }
+
+        println!("Matching player {player_id} with waiting player {p1_id} for game type: {game_type}");
+        if let Some(messages) = start_matched_game(waiting_match.match_id, &[p1_id, p2_id], &game_type, &options, events, db).await {
+            return messages;
         }
     } else {
+        if let Err(limit) = capacity.check_queue(player_id, &load) {
+            println!("Player {player_id} turned away from matchmaking: {limit:?}");
+            return matchmaking_rejected(player_id, capacity.full_rejection());
+        }
+
         // No opponent found, create a waiting match
-        if (db.create_waiting_match(player_id, &game_type_json).await).is_ok() {
+        if database::with_retry(|| db.create_waiting_match(player_id, &game_type_json, &options_json, series_length)).await.is_ok() {
             println!("Player {player_id} created waiting match for game type: {game_type}");
             return vec![OutgoingMessage {
                 player_id,
@@ -152,126 +232,528 @@ pub async fn handle_join_matchmaking_logic(
         }
     }
 
-    vec![]
+    println!("Matchmaking failed for player {player_id}: could not save match");
+    vec![OutgoingMessage {
+        player_id,
+        message: ServerMessage::error(ErrorCode::MatchmakingFailed, &[]),
+    }]
+}
+
+/// Take the player out of the matchmaking queue, too late once an opponent was found
+pub async fn handle_leave_matchmaking_logic(player_id: i64, ready_checks: &ReadyChecks, db: &Database) -> Vec<OutgoingMessage> {
+    // The ready check has its own way out, declining the match
+    if ready_checks.is_pending(player_id) {
+        return vec![OutgoingMessage {
+            player_id,
+            message: ServerMessage::error(ErrorCode::ReadyCheckPending, &[]),
+        }];
+    }
+
+    let message = match db.get_waiting_match_for_player(player_id).await {
+        Some(waiting_match) => match db.transition_match(waiting_match.id, MatchStatus::Waiting, MatchStatus::Aborted).await {
+            Ok(true) => {
+                println!("Player {player_id} left matchmaking");
+                ServerMessage::MatchmakingCancelled
+            }
+            Ok(false) => ServerMessage::error(ErrorCode::NotInMatchmaking, &[]),
+            Err(e) => {
+                println!("Failed to take player {player_id} out of matchmaking: {e}");
+                ServerMessage::error(ErrorCode::ServerError, &[])
+            }
+        },
+        None => ServerMessage::error(ErrorCode::NotInMatchmaking, &[]),
+    };
+    vec![OutgoingMessage { player_id, message }]
+}
+
+/// Seat everyone after the first of `seats` in the waiting match of the first and start it, None if the match could not be saved
+pub async fn start_matched_game(
+    match_id: i64,
+    seats: &[i64],
+    game_type: &GameType,
+    options: &serde_json::Value,
+    events: &EventBus,
+    db: &Database,
+) -> Option<Vec<OutgoingMessage>> {
+    let [_, second, extra @ ..] = seats else {
+        return None;
+    };
+
+    // Initialize game state based on game type
+    let game_state_json = game_router::initialize_game_state(game_type, options);
+
+    // Update the waiting match
+    database::with_retry(|| db.join_waiting_match(match_id, *second, &game_state_json)).await.ok()?;
+    if !extra.is_empty() {
+        database::with_retry(|| db.add_seats(match_id, extra)).await.ok()?;
+    }
+    let match_info = db.get_match_by_id(match_id).await?.to_match()?;
+    events.publish(MatchEvent::MatchStarted { match_data: match_info.clone() });
+    let mut messages = series::start_series_logic(match_id, db).await;
+
+    // Notify everyone seated
+    messages.extend(seats.iter().map(|&player_id| OutgoingMessage {
+        player_id,
+        message: ServerMessage::MatchFound {
+            match_data: game_router::redact_match_for_player(&match_info, player_id),
+        },
+    }));
+    Some(messages)
 }
 
 /// Handle a move request - returns messages to send
 pub async fn handle_make_move_logic(
     player_id: i64,
     move_data: serde_json::Value,
+    events: &EventBus,
     db: &Database,
 ) -> Vec<OutgoingMessage> {
+    let mut attempt = 1;
+    let WrittenMove { mut game_match, move_result, completed_round } = loop {
+        match write_move_logic(player_id, &move_data, db).await {
+            Ok(Some(written)) => break written,
+            Ok(None) if attempt < MOVE_ATTEMPTS => {
+                println!("Match of player {player_id} changed while their move was applied, trying again");
+                attempt += 1;
+            }
+            Ok(None) => {
+                return vec![OutgoingMessage {
+                    player_id,
+                    message: ServerMessage::error(ErrorCode::MoveConflict, &[]),
+                }];
+            }
+            Err(messages) => return messages,
+        }
+    };
+    let seq = game_match.seq;
+    let in_progress = game_match.in_progress;
+    if game_match.draw_offered_by.is_some_and(|offered_by| offered_by != player_id) {
+        if let Err(e) = db.set_draw_offer(game_match.id, None).await {
+            println!("Failed to clear the draw offer of match {}: {e}", game_match.id);
+        }
+        game_match.draw_offered_by = None;
+    }
+
+    // Update match struct with new values
+    game_match.game_state = move_result.new_state;
+    game_match.outcome = move_result.outcome;
+
+    println!("Player {player_id} made move. Match {}: in_progress={}, outcome={:?}",
+        game_match.id, in_progress, game_match.outcome);
+
+    // If match ended, update player scores
+    if !in_progress {
+        if let Some(match_record) = db.get_match_by_id(game_match.id).await {
+            if let Err(e) = db.update_player_scores_from_match(&match_record).await {
+                println!("Failed to update scores for match {}: {e}", game_match.id);
+            }
+        }
+    }
+
+    events.publish(MatchEvent::MoveMade { match_data: game_match.clone(), player_id, move_data });
+    if !in_progress {
+        events.publish(MatchEvent::MatchFinished { match_data: game_match.clone() });
+    }
+
+    // The move comes first, so spectators get it before a final state closes their stream
+    let seat_ids = game_match.seat_ids();
+    let mut messages: Vec<OutgoingMessage> = seat_ids
+        .iter()
+        .map(|&player_id| OutgoingMessage {
+            player_id,
+            message: ServerMessage::MoveApplied {
+                match_id: game_match.id,
+                seq,
+                description: move_result.description.clone(),
+            },
+        })
+        .collect();
+    if let Some((round, p1_move, p2_move)) = completed_round {
+        let winner = match p1_move.beats(&p2_move) {
+            Some(winning_move) if winning_move == p1_move => Some(game_match.player1_id),
+            Some(_) => Some(game_match.player2_id),
+            None => None,
+        };
+        messages.extend(seat_ids.iter().map(|&player_id| OutgoingMessage {
+            player_id,
+            message: ServerMessage::RoundResult {
+                match_id: game_match.id,
+                round,
+                moves: (p1_move, p2_move),
+                winner,
+                shown_for_ms: ROUND_RESULT_PAUSE.as_millis() as u64,
+            },
+        }));
+    }
+    messages.extend(seat_ids.iter().map(|&player_id| OutgoingMessage {
+        player_id,
+        message: ServerMessage::GameStateUpdate {
+            match_data: game_router::redact_match_for_player(&game_match, player_id),
+        },
+    }));
+
+    // If match ended, send MatchEnded (clients will close their own connections)
+    if !in_progress {
+        let summary = game_router::match_summary(&game_match);
+        messages.extend(seat_ids.iter().map(|&player_id| OutgoingMessage {
+            player_id,
+            message: ServerMessage::MatchEnded {
+                reason: MatchEndReason::Ended,
+                summary: summary.clone(),
+            },
+        }));
+    }
+
+    if chaos::crash_at(CrashPoint::BeforeSend) {
+        return vec![];
+    }
+    messages
+}
+
+/// A move written to the database, with the match moved to the status and seq it led to
+struct WrittenMove {
+    game_match: Match,
+    move_result: game_router::GameMoveResult,
+    completed_round: Option<(usize, RockPaperScissorsMove, RockPaperScissorsMove)>,
+}
+
+/// Read the player's match, work out the move and write it, as long as nothing else wrote to the match in between
+/// None when something did, Err with the messages to send when the move goes no further
+async fn write_move_logic(player_id: i64, move_data: &serde_json::Value, db: &Database) -> Result<Option<WrittenMove>, Vec<OutgoingMessage>> {
     // Get active match for this player
     let match_record = match db.get_active_match_for_player(player_id).await {
         Some(m) => m,
         None => {
-            return vec![OutgoingMessage {
-                player_id,
-                message: ServerMessage::Error {
-                    message: "No active match found".to_string(),
+            // A late move for a match that just ended, let the client catch up
+            let message = match db.get_last_finished_match_for_player(player_id).await {
+                Some(finished) => ServerMessage::MatchAlreadyFinished {
+                    match_id: finished.id,
+                    outcome: finished.outcome(),
                 },
-            }];
+                None => ServerMessage::error(ErrorCode::NoActiveMatch, &[]),
+            };
+            return Err(vec![OutgoingMessage { player_id, message }]);
         }
     };
 
     let mut game_match = match match_record.to_match() {
         Some(m) => m,
         None => {
-            return vec![OutgoingMessage {
+            return Err(vec![OutgoingMessage {
                 player_id,
-                message: ServerMessage::Error {
-                    message: "Failed to load match data".to_string(),
-                },
-            }];
+                message: ServerMessage::error(ErrorCode::MatchUnavailable, &[]),
+            }]);
         }
     };
 
     // Verify match is still in progress
-    if !game_match.in_progress {
-        return vec![OutgoingMessage {
+    if !game_match.status.is_playing() {
+        return Err(vec![OutgoingMessage {
             player_id,
-            message: ServerMessage::Error {
-                message: "Match already finished".to_string(),
-            },
-        }];
+            message: ServerMessage::error(ErrorCode::MatchAlreadyFinished, &[]),
+        }]);
+    }
+
+    let mut seq = match_record.seq;
+
+    // A quarantined match only accepts moves once storage works again,
+    // probed by rewriting the unchanged state
+    if db.is_match_quarantined(game_match.id) {
+        let current_state_str = serde_json::to_string(&game_match.game_state).unwrap();
+        match database::with_retry(|| db.update_match_at_seq(game_match.id, seq, &current_state_str, game_match.status, None)).await {
+            Ok(true) => {}
+            Ok(false) => return Ok(None),
+            Err(_) => {
+                return Err(vec![OutgoingMessage {
+                    player_id,
+                    message: ServerMessage::error(ErrorCode::MatchPaused, &[]),
+                }]);
+            }
+        }
+        println!("Storage recovered, releasing quarantined match {}", game_match.id);
+        db.release_match(game_match.id);
+        seq += 1;
     }
 
     // Use game router to process the move
-    let move_result = match game_router::handle_game_move(&game_match, player_id, move_data) {
+    let move_result = match game_router::handle_game_move(&game_match, player_id, move_data.clone()) {
         Ok(result) => result,
         Err(e) => {
-            return vec![OutgoingMessage {
+            return Err(vec![OutgoingMessage {
                 player_id,
-                message: ServerMessage::Error {
-                    message: e.to_string(),
-                },
-            }];
+                message: e.to_server_message(),
+            }]);
         }
     };
 
-    let in_progress = !move_result.is_finished;
+    let next_status = if move_result.is_finished { MatchStatus::Finished } else { game_match.status };
+    if let Err(e) = game_match.transition_to(next_status) {
+        println!("Move rejected for match {}: {e}", game_match.id);
+        return Err(vec![OutgoingMessage {
+            player_id,
+            message: ServerMessage::error(ErrorCode::MatchNotActive, &[]),
+        }]);
+    }
     let outcome_json = move_result.outcome.as_ref().map(|o| serde_json::to_string(o).unwrap());
+    let completed_round = game_router::completed_round(&game_match, &move_result.new_state);
 
     // Serialize state to string for database
     let new_state_str = serde_json::to_string(&move_result.new_state).unwrap();
+    if chaos::crash_at(CrashPoint::BeforeWrite) {
+        return Err(vec![]);
+    }
 
-    // Update match in database
-    if (db.update_match(
+    // Update match in database, unless another write got to it since it was read
+    match database::with_retry(|| db.update_match_at_seq(
         game_match.id,
+        seq,
         &new_state_str,
-        in_progress,
+        game_match.status,
         outcome_json.as_deref(),
-    ).await).is_ok() {
-        // Update match struct with new values
-        game_match.game_state = move_result.new_state;
-        game_match.in_progress = in_progress;
-        game_match.outcome = move_result.outcome;
-
-        println!("Player {player_id} made move. Match {}: in_progress={}, outcome={:?}",
-            game_match.id, in_progress, game_match.outcome);
-
-        // If match ended, update player scores
-        if !in_progress {
-            if let Some(match_record) = db.get_match_by_id(game_match.id).await {
-                let _ = db.update_player_scores_from_match(&match_record).await;
-            }
+    )).await {
+        Ok(true) => {}
+        Ok(false) => return Ok(None),
+        Err(e) => {
+            println!("Failed to save move for match {}, quarantining: {e}", game_match.id);
+            db.quarantine_match(game_match.id);
+            return Err(game_match.seat_ids()
+                .into_iter()
+                .map(|player_id| OutgoingMessage {
+                    player_id,
+                    message: ServerMessage::error(ErrorCode::MatchPaused, &[]),
+                })
+                .collect());
         }
+    }
 
-        let mut messages = vec![
-            OutgoingMessage {
-                player_id: game_match.player1_id,
-                message: ServerMessage::GameStateUpdate {
-                    match_data: game_router::redact_match_for_player(&game_match, game_match.player1_id),
-                },
-            },
-            OutgoingMessage {
-                player_id: game_match.player2_id,
-                message: ServerMessage::GameStateUpdate {
-                    match_data: game_router::redact_match_for_player(&game_match, game_match.player2_id),
-                },
+    if chaos::crash_at(CrashPoint::AfterWrite) {
+        return Err(vec![]);
+    }
+
+    game_match.seq = seq + 1;
+    Ok(Some(WrittenMove { game_match, move_result, completed_round }))
+}
+
+/// Apply the scores of finished matches the server stopped before scoring, such as by crashing right after the last move
+/// Scores are applied once per match, so running this again is harmless
+pub async fn recover_scores(db: &Database) {
+    let matches = match db.get_unscored_matches().await {
+        Ok(matches) => matches,
+        Err(e) => {
+            println!("Failed to look for unscored matches: {e}");
+            return;
+        }
+    };
+    for match_record in matches {
+        println!("Applying the missing scores of match {}", match_record.id);
+        if let Err(e) = db.update_player_scores_from_match(&match_record).await {
+            println!("Failed to update scores for match {}: {e}", match_record.id);
+        }
+    }
+}
+
+/// The match a player is in the middle of, active or waiting for a player to reconnect
+async fn playing_match(player_id: i64, db: &Database) -> Option<Match> {
+    db.get_active_match_for_player(player_id)
+        .await
+        .and_then(|record| record.to_match())
+        .filter(|match_data| match_data.status.is_playing())
+}
+
+fn no_active_match(player_id: i64) -> Vec<OutgoingMessage> {
+    vec![OutgoingMessage { player_id, message: ServerMessage::error(ErrorCode::NoActiveMatch, &[]) }]
+}
+
+/// End the player's match with their opponent as the winner
+pub async fn handle_resign_logic(player_id: i64, events: &EventBus, db: &Database) -> Vec<OutgoingMessage> {
+    let Some(match_data) = playing_match(player_id, db).await else {
+        return no_active_match(player_id);
+    };
+    let outcome = if match_data.player1_id == player_id { MatchOutcome::Player2Win } else { MatchOutcome::Player1Win };
+    let name = match_data.player(player_id).map(|player| player.name.clone()).unwrap_or_else(|| format!("Player {player_id}"));
+    println!("Player {player_id} resigned match {}", match_data.id);
+
+    let summary = MatchSummary { outcome: Some(outcome.clone()), text: format!("{name} resigned") };
+    finish_match_logic(match_data, outcome, MatchEndReason::Resignation, Some(summary), events, db).await
+}
+
+/// Offer the opponent a draw, or agree to one if they offered it first
+pub async fn handle_offer_draw_logic(player_id: i64, events: &EventBus, db: &Database) -> Vec<OutgoingMessage> {
+    let Some(mut match_data) = playing_match(player_id, db).await.filter(|m| m.status == MatchStatus::Active) else {
+        return no_active_match(player_id);
+    };
+    if match_data.draw_offered_by.is_some_and(|offered_by| match_data.opponents_of(player_id).contains(&offered_by)) {
+        return agree_draw(match_data, events, db).await;
+    }
+    if let Err(e) = db.set_draw_offer(match_data.id, Some(player_id)).await {
+        println!("Failed to save the draw offer of match {}: {e}", match_data.id);
+        return vec![OutgoingMessage { player_id, message: ServerMessage::error(ErrorCode::ServerError, &[]) }];
+    }
+    match_data.draw_offered_by = Some(player_id);
+    println!("Player {player_id} offered a draw in match {}", match_data.id);
+
+    match_data.seat_ids()
+        .into_iter()
+        .map(|recipient| OutgoingMessage {
+            player_id: recipient,
+            message: ServerMessage::GameStateUpdate {
+                match_data: game_router::redact_match_for_player(&match_data, recipient),
             },
-        ];
-
-        // If match ended, send MatchEnded (clients will close their own connections)
-        if !in_progress {
-            messages.push(OutgoingMessage {
-                player_id: game_match.player1_id,
-                message: ServerMessage::MatchEnded {
-                    reason: MatchEndReason::Ended,
+        })
+        .collect()
+}
+
+/// End the match in a draw the opponent offered
+pub async fn handle_accept_draw_logic(player_id: i64, events: &EventBus, db: &Database) -> Vec<OutgoingMessage> {
+    let Some(match_data) = playing_match(player_id, db).await.filter(|m| m.status == MatchStatus::Active) else {
+        return no_active_match(player_id);
+    };
+    if !match_data.draw_offered_by.is_some_and(|offered_by| match_data.opponents_of(player_id).contains(&offered_by)) {
+        return vec![OutgoingMessage { player_id, message: ServerMessage::error(ErrorCode::NoDrawOffer, &[]) }];
+    }
+    agree_draw(match_data, events, db).await
+}
+
+async fn agree_draw(match_data: Match, events: &EventBus, db: &Database) -> Vec<OutgoingMessage> {
+    println!("Match {} drawn by agreement", match_data.id);
+    let summary = MatchSummary { outcome: Some(MatchOutcome::Draw), text: "Draw agreed".to_string() };
+    finish_match_logic(match_data, MatchOutcome::Draw, MatchEndReason::DrawAgreed, Some(summary), events, db).await
+}
+
+/// Write a match as finished with `outcome`, as long as nothing else wrote to it since it was read
+/// When something did, such as a move, the match is read again and ended as it is now, unless `retry` is false
+/// None if the match finished meanwhile or kept changing
+async fn write_finished_match(mut match_data: Match, outcome: &MatchOutcome, retry: bool, db: &Database) -> Result<Option<Match>, sqlx::Error> {
+    let outcome_json = serde_json::to_string(outcome).unwrap();
+    for attempt in 1..=MOVE_ATTEMPTS {
+        if match_data.transition_to(MatchStatus::Finished).is_err() {
+            return Ok(None);
+        }
+        let game_state_str = serde_json::to_string(&match_data.game_state).unwrap();
+        if database::with_retry(|| db.update_match_at_seq(
+            match_data.id,
+            match_data.seq,
+            &game_state_str,
+            MatchStatus::Finished,
+            Some(&outcome_json),
+        )).await? {
+            match_data.seq += 1;
+            return Ok(Some(match_data));
+        }
+        if !retry || attempt == MOVE_ATTEMPTS {
+            break;
+        }
+        println!("Match {} changed while it was being ended, trying again", match_data.id);
+        match db.get_match_by_id(match_data.id).await.and_then(|record| record.to_match()) {
+            Some(current) => match_data = current,
+            None => break,
+        }
+    }
+    Ok(None)
+}
+
+/// End a match outside of its game's rules, saving the outcome and scores and sending every seat the final state
+/// Nothing happens if the match already finished, or for a forfeit, if a move got in meanwhile
+pub async fn finish_match_logic(
+    match_data: Match,
+    outcome: MatchOutcome,
+    reason: MatchEndReason,
+    summary: Option<MatchSummary>,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let match_id = match_data.id;
+    let retry = !matches!(reason, MatchEndReason::Forfeit);
+    let mut match_data = match write_finished_match(match_data, &outcome, retry, db).await {
+        Ok(Some(match_data)) => match_data,
+        Ok(None) => return vec![],
+        Err(e) => {
+            println!("Failed to end match {match_id}: {e}");
+            return vec![];
+        }
+    };
+    match_data.outcome = Some(outcome);
+    match_data.draw_offered_by = None;
+
+    if let Some(match_record) = db.get_match_by_id(match_data.id).await {
+        if let Err(e) = db.update_player_scores_from_match(&match_record).await {
+            println!("Failed to update scores for match {}: {e}", match_data.id);
+        }
+    }
+    events.publish(MatchEvent::MatchFinished { match_data: match_data.clone() });
+
+    match_data.seat_ids()
+        .into_iter()
+        .flat_map(|player_id| {
+            [
+                OutgoingMessage {
+                    player_id,
+                    message: ServerMessage::GameStateUpdate {
+                        match_data: game_router::redact_match_for_player(&match_data, player_id),
+                    },
                 },
-            });
-            messages.push(OutgoingMessage {
-                player_id: game_match.player2_id,
-                message: ServerMessage::MatchEnded {
-                    reason: MatchEndReason::Ended,
+                OutgoingMessage {
+                    player_id,
+                    message: ServerMessage::MatchEnded { reason: reason.clone(), summary: summary.clone() },
                 },
-            });
-        }
+            ]
+        })
+        .collect()
+}
 
-        return messages;
+/// The legal moves to send along with a state of a match in play, None for spectators and other messages
+pub fn legal_moves_for(message: &OutgoingMessage) -> Option<OutgoingMessage> {
+    let (ServerMessage::MatchFound { match_data } | ServerMessage::GameStateUpdate { match_data }) = &message.message else {
+        return None;
+    };
+    if match_data.status != MatchStatus::Active || match_data.seat_of(message.player_id).is_none() {
+        return None;
     }
+    Some(OutgoingMessage {
+        player_id: message.player_id,
+        message: ServerMessage::LegalMoves {
+            match_id: match_data.id,
+            moves: game_router::legal_moves(match_data, message.player_id),
+        },
+    })
+}
 
-    vec![]
+/// Relay a thinking heartbeat to the opponents, only while everyone is playing
+pub async fn handle_thinking_logic(player_id: i64, db: &Database) -> Vec<OutgoingMessage> {
+    let Some(match_data) = db.get_active_match_for_player(player_id).await.and_then(|record| record.to_match()) else {
+        return vec![];
+    };
+    if match_data.status != MatchStatus::Active {
+        return vec![];
+    }
+
+    match_data.opponents_of(player_id)
+        .into_iter()
+        .map(|opponent_id| OutgoingMessage {
+            player_id: opponent_id,
+            message: ServerMessage::OpponentThinking { match_id: match_data.id },
+        })
+        .collect()
+}
+
+/// Latest state of a match, redacted for the player or, if they are not playing it, for spectators
+pub async fn handle_request_state_logic(player_id: i64, match_id: i64, db: &Database) -> Vec<OutgoingMessage> {
+    let Some(match_data) = db.get_match_by_id(match_id).await.and_then(|record| record.to_match()) else {
+        return vec![OutgoingMessage {
+            player_id,
+            message: ServerMessage::error(ErrorCode::MatchNotFound, &[]),
+        }];
+    };
+
+    let match_data = if match_data.seat_of(player_id).is_some() {
+        game_router::redact_match_for_player(&match_data, player_id)
+    } else {
+        game_router::redact_match_for_spectator(&match_data)
+    };
+    vec![OutgoingMessage {
+        player_id,
+        message: ServerMessage::GameStateUpdate { match_data },
+    }]
 }
 
 /// Handle disconnect - returns messages to send and whether to start a disconnect timer
@@ -282,7 +764,7 @@ pub async fn handle_disconnect_logic(
     // Check if player is in matchmaking
     if let Some(waiting_match) = db.get_waiting_match_for_player(player_id).await {
         // Remove from matchmaking queue on disconnect
-        let _ = db.delete_match(waiting_match.id).await;
+        let _ = db.transition_match(waiting_match.id, MatchStatus::Waiting, MatchStatus::Aborted).await;
         println!("Player {player_id} disconnected from matchmaking");
         return (vec![], None);
     }
@@ -298,24 +780,28 @@ pub async fn handle_disconnect_logic(
         None => return (vec![], None),
     };
 
-    if !game_match.in_progress {
+    if !game_match.status.is_playing() {
         return (vec![], None); // Match already finished
     }
 
-    // Get opponent's ID
-    let opponent_id = if game_match.player1_id == player_id {
-        game_match.player2_id
-    } else {
-        game_match.player1_id
-    };
+    let mut seq = match_record.seq;
+    if game_match.status == MatchStatus::Active {
+        match db.transition_match(game_match.id, MatchStatus::Active, MatchStatus::Paused).await {
+            Ok(paused) => seq += paused as i64,
+            Err(e) => println!("Failed to pause match {}: {e}", game_match.id),
+        }
+    }
 
-    println!("Player {player_id} disconnected from active match {}, starting 10s grace period", game_match.id);
+    println!("Player {player_id} disconnected from active match {}, starting grace period", game_match.id);
 
-    // Notify opponent that this player disconnected
-    let messages = vec![OutgoingMessage {
-        player_id: opponent_id,
-        message: ServerMessage::PlayerDisconnected { player_id },
-    }];
+    // Notify opponents that this player disconnected
+    let messages = game_match.opponents_of(player_id)
+        .into_iter()
+        .map(|opponent_id| OutgoingMessage {
+            player_id: opponent_id,
+            message: ServerMessage::PlayerDisconnected { player_id, match_id: game_match.id, seq },
+        })
+        .collect();
 
     // Return messages and match_id to start timer
     (messages, Some(game_match.id))
@@ -325,6 +811,7 @@ pub async fn handle_disconnect_logic(
 pub async fn handle_disconnect_timeout_logic(
     player_id: i64,
     match_id: i64,
+    events: &EventBus,
     db: &Database,
 ) -> Vec<OutgoingMessage> {
     // Get the match
@@ -338,48 +825,143 @@ pub async fn handle_disconnect_timeout_logic(
         None => return vec![],
     };
 
-    if !game_match.in_progress {
-        return vec![]; // Match already finished
-    }
+    let opponent_ids = game_match.opponents_of(player_id);
 
-    // Get opponent's ID
-    let opponent_id = if game_match.player1_id == player_id {
-        game_match.player2_id
-    } else {
-        game_match.player1_id
+    let policy = game_router::disconnect_policy(&game_match.game_type);
+    let outcome = match policy.on_timeout {
+        TimeoutOutcome::Draw => MatchOutcome::Draw,
+        TimeoutOutcome::Forfeit if game_match.player1_id == player_id => MatchOutcome::Player2Win,
+        TimeoutOutcome::Forfeit => MatchOutcome::Player1Win,
     };
+    match write_finished_match(game_match, &outcome, true, db).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return vec![], // Match already finished
+        Err(e) => {
+            println!("Failed to end match {match_id} after disconnect timeout, quarantining: {e}");
+            db.quarantine_match(match_id);
+            return opponent_ids
+                .into_iter()
+                .map(|opponent_id| OutgoingMessage {
+                    player_id: opponent_id,
+                    message: ServerMessage::error(ErrorCode::MatchPaused, &[]),
+                })
+                .collect();
+        }
+    }
 
-    // Mark match as draw due to disconnect timeout
-    let game_state_str = serde_json::to_string(&game_match.game_state).unwrap();
-    let outcome_json = serde_json::to_string(&MatchOutcome::Draw).unwrap();
-    let _ = db.update_match(
-        game_match.id,
-        &game_state_str,
-        false, // not in progress
-        Some(&outcome_json),
-    ).await;
-
-    println!("Player {player_id} failed to reconnect to match {match_id} within 10s - ending match");
+    println!("Player {player_id} failed to reconnect to match {match_id} within {}s - ending match", policy.grace.as_secs());
 
-    // Update player scores for the draw
+    // Update player scores for the outcome
     if let Some(match_record) = db.get_match_by_id(match_id).await {
         let _ = db.update_player_scores_from_match(&match_record).await;
+        if let Some(match_data) = match_record.to_match() {
+            events.publish(MatchEvent::MatchFinished { match_data });
+        }
     }
 
-    // Send MatchEnded to opponent (if still connected)
-    vec![OutgoingMessage {
-        player_id: opponent_id,
-        message: ServerMessage::MatchEnded {
-            reason: MatchEndReason::Disconnection,
+    // Send MatchEnded to opponents (if still connected)
+    opponent_ids
+        .into_iter()
+        .map(|opponent_id| OutgoingMessage {
+            player_id: opponent_id,
+            message: ServerMessage::MatchEnded {
+                reason: MatchEndReason::Disconnection,
+                summary: None,
+            },
+        })
+        .collect()
+}
+
+/// Settle the match a player left behind before they get to resume it - returns messages to send
+/// and the opponent to start a disconnect timer for when nothing keeps track of their absence
+/// A match that finished meanwhile, or whose opponent has been gone past the grace period, is reported as ended
+pub async fn handle_login_reconciliation_logic(
+    player_id: i64,
+    match_id: i64,
+    opponent_present: bool,
+    grace: Duration,
+    events: &EventBus,
+    db: &Database,
+) -> (Vec<OutgoingMessage>, Option<i64>) {
+    let Some(match_record) = db.get_match_by_id(match_id).await else {
+        return (vec![], None);
+    };
+    let Some(match_info) = match_record.to_match() else {
+        return (vec![], None);
+    };
+
+    if match_info.status == MatchStatus::Finished {
+        println!("Match {match_id} ended while player {player_id} was away");
+        return (ended_while_away(player_id, &match_info), None);
+    }
+    if !match_info.status.is_playing() {
+        return (vec![], None);
+    }
+
+    let opponent_id = if match_info.player1_id == player_id {
+        match_info.player2_id
+    } else {
+        match_info.player1_id
+    };
+
+    if !opponent_present {
+        let idle_secs = battld_common::time() as i64 - match_record.last_move_at.unwrap_or(0);
+        if idle_secs >= grace.as_secs() as i64 {
+            println!("Opponent {opponent_id} of player {player_id} is long gone - ending match {match_id}");
+            let messages = handle_disconnect_timeout_logic(opponent_id, match_id, events, db).await;
+            let ended = db.get_match_by_id(match_id).await.and_then(|record| record.to_match());
+            return match ended {
+                Some(ended) if ended.status == MatchStatus::Finished => (ended_while_away(player_id, &ended), None),
+                _ => (messages, None),
+            };
+        }
+    }
+
+    println!("Player {player_id} has resumable match {match_id}");
+    let messages = vec![OutgoingMessage {
+        player_id,
+        message: ServerMessage::ResumableMatch {
+            match_data: game_router::redact_match_for_player(&match_info, player_id),
+            last_move_at: match_record.last_move_at,
         },
-    }]
+    }];
+    (messages, (!opponent_present).then_some(opponent_id))
+}
+
+/// The final state and how the match ended, for a player who was not there to see it
+/// Without the game's own account, a draw means a player never came back and a win a forfeit
+fn ended_while_away(player_id: i64, match_info: &Match) -> Vec<OutgoingMessage> {
+    let (reason, summary) = match game_router::match_summary(match_info) {
+        Some(summary) => (MatchEndReason::Ended, summary),
+        None => {
+            let (reason, text) = match match_info.outcome {
+                Some(MatchOutcome::Player1Win | MatchOutcome::Player2Win) => (MatchEndReason::Forfeit, "Forfeited while you were away"),
+                _ => (MatchEndReason::Disconnection, "Called a draw, a player did not come back in time"),
+            };
+            (reason, MatchSummary { outcome: match_info.outcome.clone(), text: text.to_string() })
+        }
+    };
+
+    vec![
+        OutgoingMessage {
+            player_id,
+            message: ServerMessage::GameStateUpdate {
+                match_data: game_router::redact_match_for_player(match_info, player_id),
+            },
+        },
+        OutgoingMessage {
+            player_id,
+            message: ServerMessage::MatchEnded { reason, summary: Some(summary) },
+        },
+    ]
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use sqlx::SqlitePool;
-    use crate::games::tic_tac_toe::TicTacToeGameState;
+    use server::games::tic_tac_toe::TicTacToeGameState;
+    use battld_common::games::rock_paper_scissors::{RockPaperScissorsGameState, RockPaperScissorsMove};
 
     // Helper function to create a test database
     async fn create_test_db() -> Database {
@@ -400,12 +982,13 @@ mod tests {
 
         // Try to make a move when player has no active match
         let move_data = serde_json::json!({"row": 0, "col": 0});
-        let messages = handle_make_move_logic(999, move_data, &db).await;
+        let messages = handle_make_move_logic(999, move_data, &EventBus::new(), &db).await;
 
         assert_eq!(messages.len(), 1);
         assert_eq!(messages[0].player_id, 999);
         match &messages[0].message {
-            ServerMessage::Error { message } => {
+            ServerMessage::Error { code, message, .. } => {
+                assert_eq!(*code, ErrorCode::NoActiveMatch);
                 assert_eq!(message, "No active match found");
             }
             _ => panic!("Expected Error message"),
@@ -428,12 +1011,13 @@ mod tests {
 
         // Try to make a move as player 2 (not their turn)
         let move_data = serde_json::json!({"row": 0, "col": 0});
-        let messages = handle_make_move_logic(p2, move_data, &db).await;
+        let messages = handle_make_move_logic(p2, move_data, &EventBus::new(), &db).await;
 
         assert_eq!(messages.len(), 1);
         assert_eq!(messages[0].player_id, p2);
         match &messages[0].message {
-            ServerMessage::Error { message } => {
+            ServerMessage::Error { code, message, .. } => {
+                assert_eq!(*code, ErrorCode::NotYourTurn);
                 assert_eq!(message, "Not your turn");
             }
             _ => panic!("Expected Error message"),
@@ -455,19 +1039,23 @@ mod tests {
 
         // Make a valid move as player 1
         let move_data = serde_json::json!({"row": 0, "col": 0});
-        let messages = handle_make_move_logic(p1, move_data, &db).await;
+        let messages = handle_make_move_logic(p1, move_data, &EventBus::new(), &db).await;
 
-        // Should send GameStateUpdate to both players
-        assert_eq!(messages.len(), 2);
+        // Should send MoveApplied and GameStateUpdate to both players
+        assert_eq!(messages.len(), 4);
 
         // Check both players get the update
         let player_ids: Vec<i64> = messages.iter().map(|m| m.player_id).collect();
         assert!(player_ids.contains(&p1));
         assert!(player_ids.contains(&p2));
 
-        // All should be GameStateUpdate messages
         for msg in &messages {
             match &msg.message {
+                ServerMessage::MoveApplied { match_id: id, seq, description } => {
+                    assert_eq!(*id, match_id);
+                    assert_eq!(*seq, db.get_match_by_id(match_id).await.unwrap().seq);
+                    assert_eq!(description, "X at row 1, column 1");
+                }
                 ServerMessage::GameStateUpdate { match_data } => {
                     assert_eq!(match_data.id, match_id);
                     // Extract current_player from game_state
@@ -475,7 +1063,7 @@ mod tests {
                     assert_eq!(state.current_player, 2); // Turn should switch to player 2
                     assert!(match_data.in_progress);
                 }
-                _ => panic!("Expected GameStateUpdate message"),
+                _ => panic!("Expected MoveApplied or GameStateUpdate message"),
             }
         }
     }
@@ -502,10 +1090,11 @@ mod tests {
 
         // Make the winning move as player 1
         let move_data = serde_json::json!({"row": 0, "col": 2});
-        let messages = handle_make_move_logic(p1, move_data, &db).await;
+        let messages = handle_make_move_logic(p1, move_data, &EventBus::new(), &db).await;
 
-        // Should send GameStateUpdate and MatchEnded to both players
-        assert_eq!(messages.len(), 4); // 2 GameStateUpdate + 2 MatchEnded
+        // Should send MoveApplied, GameStateUpdate and MatchEnded to both players
+        assert_eq!(messages.len(), 6);
+        assert!(matches!(messages[0].message, ServerMessage::MoveApplied { .. }));
 
         // Verify we get the right message types
         let mut state_updates = 0;
@@ -519,9 +1108,11 @@ mod tests {
                     assert_eq!(match_data.outcome, Some(MatchOutcome::Player1Win));
                     state_updates += 1;
                 }
-                ServerMessage::MatchEnded { .. } => {
+                ServerMessage::MatchEnded { summary, .. } => {
+                    assert_eq!(summary.as_ref().map(|s| s.text.as_str()), Some("3 in a row in 5 moves"));
                     match_ended += 1;
                 }
+                ServerMessage::MoveApplied { .. } => {}
                 _ => panic!("Unexpected message type"),
             }
         }
@@ -530,6 +1121,230 @@ mod tests {
         assert_eq!(match_ended, 2);
     }
 
+    #[tokio::test]
+    async fn test_every_seat_gets_the_states_and_the_match_end() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let p3 = create_test_player(&db, "player3").await;
+        let match_id = start_tic_tac_toe_match(&db, p1, p2).await;
+        db.add_seats(match_id, &[p3]).await.unwrap();
+        let events = EventBus::new();
+
+        let messages = handle_make_move_logic(p1, serde_json::json!({"row": 0, "col": 0}), &events, &db).await;
+        assert!(messages.iter().any(|m| m.player_id == p3 && matches!(m.message, ServerMessage::GameStateUpdate { .. })));
+
+        let messages = handle_resign_logic(p2, &events, &db).await;
+        for player_id in [p1, p2, p3] {
+            assert!(messages.iter().any(|m| m.player_id == player_id && matches!(m.message, ServerMessage::MatchEnded { .. })));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_make_move_after_match_finished() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+
+        let mut game_state = TicTacToeGameState::new();
+        game_state.board[0] = 1;
+        game_state.board[3] = 2;
+        game_state.board[1] = 1;
+        game_state.board[4] = 2;
+        let game_state_json = serde_json::to_string(&game_state).unwrap();
+        let match_id = db.create_match(p1, p2, &game_state_json, &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
+
+        handle_make_move_logic(p1, serde_json::json!({"row": 0, "col": 2}), &EventBus::new(), &db).await;
+
+        // Player 2 did not see the end yet and still plays
+        let messages = handle_make_move_logic(p2, serde_json::json!({"row": 2, "col": 2}), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].player_id, p2);
+        match &messages[0].message {
+            ServerMessage::MatchAlreadyFinished { match_id: finished_id, outcome } => {
+                assert_eq!(*finished_id, match_id);
+                assert_eq!(*outcome, Some(MatchOutcome::Player1Win));
+            }
+            other => panic!("Expected MatchAlreadyFinished, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_match_events_published() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let events = EventBus::new();
+        let mut rx = events.subscribe();
+
+        handle_join_matchmaking_logic(p1, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &events, &db).await;
+        handle_join_matchmaking_logic(p2, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &events, &db).await;
+        let match_id = match rx.try_recv().unwrap() {
+            MatchEvent::MatchStarted { match_data } => match_data.id,
+            other => panic!("Expected MatchStarted, got {other:?}"),
+        };
+
+        let mut game_state = TicTacToeGameState::new();
+        game_state.board[0] = 1;
+        game_state.board[3] = 2;
+        game_state.board[1] = 1;
+        game_state.board[4] = 2;
+        let game_state_json = serde_json::to_string(&game_state).unwrap();
+        db.update_match(match_id, &game_state_json, MatchStatus::Active, None).await.unwrap();
+
+        handle_make_move_logic(p1, serde_json::json!({"row": 0, "col": 2}), &events, &db).await;
+        match rx.try_recv().unwrap() {
+            MatchEvent::MoveMade { match_data, player_id, .. } => {
+                assert_eq!(match_data.id, match_id);
+                assert_eq!(player_id, p1);
+            }
+            other => panic!("Expected MoveMade, got {other:?}"),
+        }
+        match rx.try_recv().unwrap() {
+            MatchEvent::MatchFinished { match_data } => {
+                assert_eq!(match_data.outcome, Some(MatchOutcome::Player1Win));
+            }
+            other => panic!("Expected MatchFinished, got {other:?}"),
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_thinking_relayed_to_opponent() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+
+        assert!(handle_thinking_logic(p1, &db).await.is_empty());
+
+        let game_state_json = serde_json::to_string(&TicTacToeGameState::new()).unwrap();
+        let match_id = db.create_match(p1, p2, &game_state_json, &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
+
+        let messages = handle_thinking_logic(p1, &db).await;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].player_id, p2);
+        assert!(matches!(messages[0].message, ServerMessage::OpponentThinking { match_id: id } if id == match_id));
+    }
+
+    #[tokio::test]
+    async fn test_request_state() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let spectator = create_test_player(&db, "spectator").await;
+
+        let mut game_state = RockPaperScissorsGameState::new();
+        game_state.rounds[0].0 = Some(RockPaperScissorsMove::Rock);
+        let game_state_json = serde_json::to_string(&game_state).unwrap();
+        let match_id = db.create_match(p1, p2, &game_state_json, &serde_json::to_string(&GameType::RockPaperScissors).unwrap()).await.unwrap();
+
+        let own_move = |messages: &[OutgoingMessage]| match &messages[0].message {
+            ServerMessage::GameStateUpdate { match_data } => match_data.game_state["rounds"][0][0].clone(),
+            other => panic!("Expected GameStateUpdate, got {other:?}"),
+        };
+
+        let messages = handle_request_state_logic(p1, match_id, &db).await;
+        assert_eq!(messages[0].player_id, p1);
+        assert_eq!(own_move(&messages), serde_json::json!("rock"));
+
+        let messages = handle_request_state_logic(spectator, match_id, &db).await;
+        assert_eq!(messages[0].player_id, spectator);
+        assert_eq!(own_move(&messages), serde_json::json!("redacted"));
+
+        let messages = handle_request_state_logic(p1, match_id + 1, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_legal_moves_follow_states_sent_to_players() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let spectator = create_test_player(&db, "spectator").await;
+
+        let mut game_state = RockPaperScissorsGameState::new();
+        game_state.rounds[0].0 = Some(RockPaperScissorsMove::Rock);
+        let game_state_json = serde_json::to_string(&game_state).unwrap();
+        let match_id = db.create_match(p1, p2, &game_state_json, &serde_json::to_string(&GameType::RockPaperScissors).unwrap()).await.unwrap();
+
+        let db = &db;
+        let legal_moves = |player_id| async move {
+            let messages = handle_request_state_logic(player_id, match_id, db).await;
+            legal_moves_for(&messages[0]).map(|message| match message.message {
+                ServerMessage::LegalMoves { match_id: id, moves } if id == match_id && message.player_id == player_id => moves,
+                other => panic!("Expected LegalMoves, got {other:?}"),
+            })
+        };
+
+        assert_eq!(legal_moves(p1).await, Some(vec![]));
+        assert_eq!(legal_moves(p2).await.unwrap().len(), 3);
+        assert_eq!(legal_moves(spectator).await, None);
+
+        let pong = OutgoingMessage { player_id: p1, message: ServerMessage::Pong };
+        assert!(legal_moves_for(&pong).is_none());
+
+        let mut match_data = db.get_match_by_id(match_id).await.unwrap().to_match().unwrap();
+        match_data.seats = vec![p1, p2, spectator];
+        let third_seat = OutgoingMessage { player_id: spectator, message: ServerMessage::GameStateUpdate { match_data } };
+        assert!(legal_moves_for(&third_seat).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_completed_round_result_comes_before_the_next_state() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+
+        let game_state_json = serde_json::to_string(&RockPaperScissorsGameState::new()).unwrap();
+        db.create_match(p1, p2, &game_state_json, &serde_json::to_string(&GameType::RockPaperScissors).unwrap()).await.unwrap();
+
+        let messages = handle_make_move_logic(p1, serde_json::json!({ "choice": "rock" }), &EventBus::new(), &db).await;
+        assert!(!messages.iter().any(|m| matches!(m.message, ServerMessage::RoundResult { .. })));
+
+        let messages = handle_make_move_logic(p2, serde_json::json!({ "choice": "paper" }), &EventBus::new(), &db).await;
+        let results: Vec<&OutgoingMessage> = messages.iter().filter(|m| matches!(m.message, ServerMessage::RoundResult { .. })).collect();
+        assert_eq!(results.len(), 2);
+        for result in results {
+            match &result.message {
+                ServerMessage::RoundResult { round, moves, winner, .. } => {
+                    assert_eq!(*round, 0);
+                    assert_eq!(*moves, (RockPaperScissorsMove::Rock, RockPaperScissorsMove::Paper));
+                    assert_eq!(*winner, Some(p2));
+                }
+                _ => unreachable!(),
+            }
+        }
+        let last_result = messages.iter().rposition(|m| matches!(m.message, ServerMessage::RoundResult { .. })).unwrap();
+        let first_state = messages.iter().position(|m| matches!(m.message, ServerMessage::GameStateUpdate { .. })).unwrap();
+        assert!(last_result < first_state);
+    }
+
+    #[tokio::test]
+    async fn test_simultaneous_moves_both_land() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+
+        let game_state_json = serde_json::to_string(&RockPaperScissorsGameState::new()).unwrap();
+        let match_id = db.create_match(p1, p2, &game_state_json, &serde_json::to_string(&GameType::RockPaperScissors).unwrap()).await.unwrap();
+
+        let events = EventBus::new();
+        let (first, second) = tokio::join!(
+            handle_make_move_logic(p1, serde_json::json!({ "choice": "rock" }), &events, &db),
+            handle_make_move_logic(p2, serde_json::json!({ "choice": "paper" }), &events, &db),
+        );
+        for messages in [&first, &second] {
+            assert!(!messages.iter().any(|m| matches!(m.message, ServerMessage::Error { .. })));
+        }
+        let round_results = first.iter().chain(&second).filter(|m| matches!(m.message, ServerMessage::RoundResult { .. })).count();
+        assert_eq!(round_results, 2);
+
+        let record = db.get_match_by_id(match_id).await.unwrap();
+        let game_state: RockPaperScissorsGameState = serde_json::from_str(&record.game_state).unwrap();
+        assert_eq!(game_state.rounds[0], (Some(RockPaperScissorsMove::Rock), Some(RockPaperScissorsMove::Paper)));
+        assert_eq!(record.seq, 2);
+    }
+
     #[tokio::test]
     async fn test_disconnect_from_active_match() {
         let db = create_test_db().await;
@@ -552,11 +1367,68 @@ mod tests {
         assert_eq!(messages[0].player_id, p2);
 
         match &messages[0].message {
-            ServerMessage::PlayerDisconnected { player_id } => {
+            ServerMessage::PlayerDisconnected { player_id, .. } => {
                 assert_eq!(*player_id, p1);
             }
             _ => panic!("Expected PlayerDisconnected message"),
         }
+
+        let match_info = db.get_match_by_id(match_id).await.unwrap().to_match().unwrap();
+        assert_eq!(match_info.status, MatchStatus::Paused);
+
+        // Resuming makes the match active again
+        let messages = handle_resume_match_logic(p1, Some(match_id), &HashMap::new(), &db).await;
+        match &messages[0].message {
+            ServerMessage::GameStateUpdate { match_data } => assert_eq!(match_data.status, MatchStatus::Active),
+            _ => panic!("Expected GameStateUpdate message"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_spares_an_up_to_date_opponent_the_state() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let game_state_json = serde_json::to_string(&TicTacToeGameState::new()).unwrap();
+        let match_id = db.create_match(p1, p2, &game_state_json, &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
+
+        let (messages, _) = handle_disconnect_logic(p1, &db).await;
+        let ServerMessage::PlayerDisconnected { seq, .. } = messages[0].message else {
+            panic!("Expected PlayerDisconnected message");
+        };
+        assert_eq!(seq, db.get_match_by_id(match_id).await.unwrap().seq);
+
+        // An opponent who missed a state gets all of it
+        let behind = HashMap::from([(p2, seq - 1)]);
+        let messages = handle_resume_match_logic(p1, Some(match_id), &behind, &db).await;
+        assert!(matches!(messages[1].message, ServerMessage::GameStateUpdate { .. }));
+
+        let (messages, _) = handle_disconnect_logic(p1, &db).await;
+        let ServerMessage::PlayerDisconnected { seq, .. } = messages[0].message else {
+            panic!("Expected PlayerDisconnected message");
+        };
+        let messages = handle_resume_match_logic(p1, Some(match_id), &HashMap::from([(p2, seq)]), &db).await;
+        assert!(matches!(&messages[0].message, ServerMessage::GameStateUpdate { match_data } if match_data.seq == seq + 1));
+        assert_eq!(messages[1].player_id, p2);
+        assert!(matches!(messages[1].message, ServerMessage::PlayerReconnected { player_id, seq: now, .. } if player_id == p1 && now == seq + 1));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_from_matchmaking_aborts_waiting_match() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+
+        let _ = handle_join_matchmaking_logic(p1, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        let match_id = db.get_waiting_match_for_player(p1).await.unwrap().id;
+
+        let (messages, match_id_opt) = handle_disconnect_logic(p1, &db).await;
+        assert!(messages.is_empty());
+        assert_eq!(match_id_opt, None);
+
+        let match_record = db.get_match_by_id(match_id).await.unwrap();
+        assert_eq!(match_record.status, "aborted");
+        assert_eq!(match_record.in_progress, 0);
+        assert!(db.get_active_match_for_player(p1).await.is_none());
     }
 
     #[tokio::test]
@@ -573,7 +1445,7 @@ mod tests {
         let match_id = db.create_match(p1, p2, &game_state_json, &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
 
         // Timeout occurs
-        let messages = handle_disconnect_timeout_logic(p1, match_id, &db).await;
+        let messages = handle_disconnect_timeout_logic(p1, match_id, &EventBus::new(), &db).await;
 
         // Should send MatchEnded to opponent
         assert_eq!(messages.len(), 1);
@@ -587,10 +1459,78 @@ mod tests {
         // Match should be marked as draw (JSON serialized in DB)
         let match_record = db.get_match_by_id(match_id).await.unwrap();
         assert_eq!(match_record.in_progress, 0);
+        assert_eq!(match_record.status, "finished");
         let expected_outcome = serde_json::to_string(&MatchOutcome::Draw).unwrap();
         assert_eq!(match_record.outcome.as_deref(), Some(expected_outcome.as_str()));
     }
 
+    #[tokio::test]
+    async fn test_disconnect_timeout_forfeits_chess() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let game_state = game_router::initialize_game_state(&GameType::Chess, &serde_json::Value::Null);
+        let match_id = db.create_match(p1, p2, &game_state, &serde_json::to_string(&GameType::Chess).unwrap()).await.unwrap();
+
+        let messages = handle_disconnect_timeout_logic(p2, match_id, &EventBus::new(), &db).await;
+        assert_eq!(messages[0].player_id, p1);
+        assert_eq!(db.get_match_by_id(match_id).await.unwrap().outcome(), Some(MatchOutcome::Player1Win));
+    }
+
+    #[tokio::test]
+    async fn test_login_reconciliation_offers_resume_while_opponent_is_around() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let game_state_json = serde_json::to_string(&TicTacToeGameState::new()).unwrap();
+        let match_id = db.create_match(p1, p2, &game_state_json, &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
+
+        let (messages, absent_opponent) = handle_login_reconciliation_logic(p1, match_id, true, game_router::disconnect_policy(&GameType::TicTacToe).grace, &EventBus::new(), &db).await;
+        assert_eq!(absent_opponent, None);
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(&messages[0].message, ServerMessage::ResumableMatch { match_data, .. } if match_data.id == match_id));
+
+        // An opponent nobody tracks yet but who left only just now gets the usual grace period
+        let (messages, absent_opponent) = handle_login_reconciliation_logic(p1, match_id, false, game_router::disconnect_policy(&GameType::TicTacToe).grace, &EventBus::new(), &db).await;
+        assert_eq!(absent_opponent, Some(p2));
+        assert!(matches!(&messages[0].message, ServerMessage::ResumableMatch { .. }));
+        assert_eq!(db.get_match_by_id(match_id).await.unwrap().status, "active");
+    }
+
+    #[tokio::test]
+    async fn test_login_reconciliation_ends_match_of_long_gone_opponent() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let game_state_json = serde_json::to_string(&TicTacToeGameState::new()).unwrap();
+        let match_id = db.create_match(p1, p2, &game_state_json, &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
+        sqlx::query("UPDATE matches SET last_move_at = last_move_at - 3600 WHERE id = ?")
+            .bind(match_id)
+            .execute(db.pool())
+            .await
+            .unwrap();
+
+        let (messages, absent_opponent) = handle_login_reconciliation_logic(p1, match_id, false, game_router::disconnect_policy(&GameType::TicTacToe).grace, &EventBus::new(), &db).await;
+        assert_eq!(absent_opponent, None);
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| m.player_id == p1));
+        assert!(matches!(&messages[0].message, ServerMessage::GameStateUpdate { match_data } if match_data.status == MatchStatus::Finished));
+        match &messages[1].message {
+            ServerMessage::MatchEnded { reason, summary: Some(summary) } => {
+                assert!(matches!(reason, MatchEndReason::Disconnection));
+                assert_eq!(summary.outcome, Some(MatchOutcome::Draw));
+            }
+            other => panic!("Expected MatchEnded with a summary, got {other:?}"),
+        }
+
+        let match_record = db.get_match_by_id(match_id).await.unwrap();
+        assert_eq!(match_record.status, "finished");
+
+        // Coming back again still tells what happened, without touching the match
+        let (messages, _) = handle_login_reconciliation_logic(p1, match_id, false, game_router::disconnect_policy(&GameType::TicTacToe).grace, &EventBus::new(), &db).await;
+        assert!(matches!(&messages[1].message, ServerMessage::MatchEnded { summary: Some(_), .. }));
+    }
+
     #[tokio::test]
     async fn test_join_matchmaking_creates_waiting_match() {
         let db = create_test_db().await;
@@ -599,7 +1539,7 @@ mod tests {
         let p1 = create_test_player(&db, "player1").await;
 
         // Join matchmaking
-        let messages = handle_join_matchmaking_logic(p1, GameType::TicTacToe, &db).await;
+        let messages = handle_join_matchmaking_logic(p1, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
 
         // Should send WaitingForOpponent
         assert_eq!(messages.len(), 1);
@@ -611,6 +1551,52 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_leave_matchmaking() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let ready_checks = ReadyChecks::default();
+
+        let messages = handle_leave_matchmaking_logic(p1, &ready_checks, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { code: ErrorCode::NotInMatchmaking, .. }));
+
+        handle_join_matchmaking_logic(p1, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ready_checks, &EventBus::new(), &db).await;
+        let messages = handle_leave_matchmaking_logic(p1, &ready_checks, &db).await;
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0].message, ServerMessage::MatchmakingCancelled));
+        assert!(db.get_waiting_match_for_player(p1).await.is_none());
+
+        // Nobody is left in the queue to be paired with
+        let messages = handle_join_matchmaking_logic(p2, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ready_checks, &EventBus::new(), &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::WaitingForOpponent));
+    }
+
+    #[tokio::test]
+    async fn test_join_matchmaking_while_playing() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+
+        // Still waiting: stays in the queue it is in
+        handle_join_matchmaking_logic(p1, GameType::Briscola, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        let messages = handle_join_matchmaking_logic(p1, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        assert!(messages.is_empty());
+
+        handle_join_matchmaking_logic(p2, GameType::Briscola, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        let match_id = db.get_active_match_for_player(p1).await.unwrap().id;
+
+        let messages = handle_join_matchmaking_logic(p1, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 1);
+        match &messages[0].message {
+            ServerMessage::MatchmakingRejected { reason: MatchmakingRejection::AlreadyInMatch { match_id: id, game_type } } => {
+                assert_eq!(*id, match_id);
+                assert_eq!(*game_type, GameType::Briscola);
+            }
+            other => panic!("Expected AlreadyInMatch, got {other:?}"),
+        }
+    }
+
     #[tokio::test]
     async fn test_join_matchmaking_finds_opponent() {
         let db = create_test_db().await;
@@ -620,10 +1606,10 @@ mod tests {
         let p2 = create_test_player(&db, "player2").await;
 
         // Player 1 joins matchmaking (creates waiting match)
-        let _ = handle_join_matchmaking_logic(p1, GameType::TicTacToe, &db).await;
+        let _ = handle_join_matchmaking_logic(p1, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
 
         // Player 2 joins matchmaking (should match with player 1)
-        let messages = handle_join_matchmaking_logic(p2, GameType::TicTacToe, &db).await;
+        let messages = handle_join_matchmaking_logic(p2, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
 
         // Should send MatchFound to both players
         assert_eq!(messages.len(), 2);
@@ -638,6 +1624,8 @@ mod tests {
                     assert_eq!(match_data.player1_id, p1);
                     assert_eq!(match_data.player2_id, p2);
                     assert!(match_data.in_progress);
+                    assert_eq!(match_data.player(p1).unwrap().name, "player1");
+                    assert_eq!(match_data.player(p2).unwrap().name, "player2");
                 }
                 _ => panic!("Expected MatchFound message"),
             }
@@ -653,7 +1641,7 @@ mod tests {
         let p2 = create_test_player(&db, "player2").await;
 
         // Player 1 joins TicTacToe matchmaking
-        let messages1 = handle_join_matchmaking_logic(p1, GameType::TicTacToe, &db).await;
+        let messages1 = handle_join_matchmaking_logic(p1, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
 
         // Should be waiting for opponent
         assert_eq!(messages1.len(), 1);
@@ -663,7 +1651,7 @@ mod tests {
         }
 
         // Player 2 joins RockPaperScissors matchmaking (different game type)
-        let messages2 = handle_join_matchmaking_logic(p2, GameType::RockPaperScissors, &db).await;
+        let messages2 = handle_join_matchmaking_logic(p2, GameType::RockPaperScissors, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
 
         // Should also be waiting (not matched with player 1)
         assert_eq!(messages2.len(), 1);
@@ -674,7 +1662,7 @@ mod tests {
 
         // Now if a third player joins TicTacToe, they should match with player 1
         let p3 = create_test_player(&db, "player3").await;
-        let messages3 = handle_join_matchmaking_logic(p3, GameType::TicTacToe, &db).await;
+        let messages3 = handle_join_matchmaking_logic(p3, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
 
         // Should send MatchFound to p1 and p3
         assert_eq!(messages3.len(), 2);
@@ -684,4 +1672,240 @@ mod tests {
         assert!(player_ids.contains(&p3));
         assert!(!player_ids.contains(&p2)); // p2 not in this match
     }
+
+    #[tokio::test]
+    async fn test_matchmaking_respects_game_options() {
+        let db = create_test_db().await;
+
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let p3 = create_test_player(&db, "player3").await;
+        let large_board = serde_json::json!({ "board_size": 5, "win_length": 4 });
+
+        let _ = handle_join_matchmaking_logic(p1, GameType::TicTacToe, large_board.clone(), None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+
+        // Classic 3x3 should not match the 5x5 queue
+        let messages = handle_join_matchmaking_logic(p2, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::WaitingForOpponent));
+
+        let messages = handle_join_matchmaking_logic(p3, GameType::TicTacToe, large_board, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 2);
+        match &messages[0].message {
+            ServerMessage::MatchFound { match_data } => {
+                assert_eq!(match_data.player1_id, p1);
+                assert_eq!(match_data.game_state["board_size"], 5);
+                assert_eq!(match_data.game_state["win_length"], 4);
+            }
+            _ => panic!("Expected MatchFound message"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_matchmaking_prefers_a_close_rating() {
+        let db = create_test_db().await;
+        let strong = create_test_player(&db, "strong").await;
+        let close = create_test_player(&db, "close").await;
+        let newcomer = create_test_player(&db, "newcomer").await;
+        let game_type = serde_json::to_string(&GameType::TicTacToe).unwrap();
+        for (player_id, rating) in [(strong, 1900.0), (close, 1550.0)] {
+            sqlx::query("INSERT INTO player_game_ratings (player_id, game_type, rating, rating_deviation, rated_games) VALUES (?, ?, ?, 80.0, 20)")
+                .bind(player_id)
+                .bind(&game_type)
+                .bind(rating)
+                .execute(db.pool())
+                .await
+                .unwrap();
+        }
+        let window = RatingWindow { initial: 100.0, growth_per_sec: 0.0 };
+
+        let _ = handle_join_matchmaking_logic(strong, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &window, &ReadyChecks::default(), &EventBus::new(), &db).await;
+        let messages = handle_join_matchmaking_logic(newcomer, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &window, &ReadyChecks::default(), &EventBus::new(), &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::WaitingForOpponent), "400 points apart, out of the window");
+
+        let messages = handle_join_matchmaking_logic(close, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &window, &ReadyChecks::default(), &EventBus::new(), &db).await;
+        match &messages[0].message {
+            ServerMessage::MatchFound { match_data } => assert_eq!(match_data.player1_id, newcomer, "The newcomer at 1500 is closer than the 1900 player"),
+            _ => panic!("Expected MatchFound message"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_matchmaking_rejects_invalid_options() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+
+        let messages = handle_join_matchmaking_logic(p1, GameType::TicTacToe, serde_json::json!({ "board_size": 12 }), None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0].message, ServerMessage::Error { .. }));
+        assert!(db.get_active_match_for_player(p1).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_matchmaking_at_capacity() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let p3 = create_test_player(&db, "player3").await;
+        let capacity = Capacity::new(capacity::CapacityConfig {
+            max_concurrent_matches: Some(1),
+            max_queue_size: Some(1),
+            ..Default::default()
+        });
+
+        let _ = handle_join_matchmaking_logic(p1, GameType::TicTacToe, serde_json::Value::Null, None, &capacity, &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+
+        // Queue is full for other games
+        let messages = handle_join_matchmaking_logic(p2, GameType::Chess, serde_json::Value::Null, None, &capacity, &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        assert!(matches!(
+            messages[0].message,
+            ServerMessage::MatchmakingRejected { reason: MatchmakingRejection::ServerFull { retry_after: 30 } }
+        ));
+        assert!(db.get_active_match_for_player(p2).await.is_none());
+
+        // Joining a waiting opponent is still allowed
+        let messages = handle_join_matchmaking_logic(p2, GameType::TicTacToe, serde_json::Value::Null, None, &capacity, &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::MatchFound { .. }));
+
+        // No room for a second match
+        let p4 = create_test_player(&db, "player4").await;
+        let _ = handle_join_matchmaking_logic(p3, GameType::TicTacToe, serde_json::Value::Null, None, &capacity, &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        let messages = handle_join_matchmaking_logic(p4, GameType::TicTacToe, serde_json::Value::Null, None, &capacity, &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0].message, ServerMessage::MatchmakingRejected { reason: MatchmakingRejection::ServerFull { .. } }));
+    }
+
+    async fn start_tic_tac_toe_match(db: &Database, p1: i64, p2: i64) -> i64 {
+        let mut state = TicTacToeGameState::new();
+        state.current_player = 1;
+        let game_type = serde_json::to_string(&GameType::TicTacToe).unwrap();
+        db.create_match(p1, p2, &serde_json::to_string(&state).unwrap(), &game_type).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resign_gives_the_match_to_the_opponent() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let match_id = start_tic_tac_toe_match(&db, p1, p2).await;
+
+        let messages = handle_resign_logic(p1, &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 4);
+        assert!(messages.iter().any(|m| m.player_id == p2 && matches!(
+            &m.message,
+            ServerMessage::MatchEnded { reason: MatchEndReason::Resignation, summary: Some(summary) } if summary.text == "player1 resigned"
+        )));
+        assert_eq!(db.get_match_by_id(match_id).await.unwrap().outcome(), Some(MatchOutcome::Player2Win));
+        assert!(matches!(
+            handle_resign_logic(p1, &EventBus::new(), &db).await[0].message,
+            ServerMessage::Error { code: ErrorCode::NoActiveMatch, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_resign_racing_a_move_keeps_the_move() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let match_id = start_tic_tac_toe_match(&db, p1, p2).await;
+        let events = EventBus::new();
+
+        // Both read the match before either writes, the move gets in first
+        let read_by_resign = playing_match(p2, &db).await.unwrap();
+        let read_by_forfeit = read_by_resign.clone();
+        handle_make_move_logic(p1, serde_json::json!({"row": 0, "col": 0}), &events, &db).await;
+
+        // A forfeit for being idle no longer holds once a move was made
+        assert!(finish_match_logic(read_by_forfeit, MatchOutcome::Player2Win, MatchEndReason::Forfeit, None, &events, &db).await.is_empty());
+        assert_eq!(db.get_match_by_id(match_id).await.unwrap().status, MatchStatus::Active.as_str());
+
+        let messages = finish_match_logic(read_by_resign, MatchOutcome::Player1Win, MatchEndReason::Resignation, None, &events, &db).await;
+        let record = db.get_match_by_id(match_id).await.unwrap();
+        assert_eq!(record.outcome(), Some(MatchOutcome::Player1Win));
+        let game_state: serde_json::Value = serde_json::from_str(&record.game_state).unwrap();
+        assert_eq!(game_state["board"][0], 1);
+        assert!(messages.iter().any(|m| matches!(
+            &m.message,
+            ServerMessage::GameStateUpdate { match_data } if match_data.seq == record.seq && match_data.game_state["board"][0] == 1
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_draw_offer_is_declined_by_moving_and_ends_the_match_once_accepted() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let match_id = start_tic_tac_toe_match(&db, p1, p2).await;
+        let events = EventBus::new();
+
+        let messages = handle_accept_draw_logic(p1, &events, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { code: ErrorCode::NoDrawOffer, .. }));
+
+        let messages = handle_offer_draw_logic(p1, &events, &db).await;
+        assert!(messages.iter().all(|m| matches!(
+            &m.message,
+            ServerMessage::GameStateUpdate { match_data } if match_data.draw_offered_by == Some(p1)
+        )));
+
+        // The offerer's own move keeps the offer, the opponent's declines it
+        handle_make_move_logic(p1, serde_json::json!({"row": 0, "col": 0}), &events, &db).await;
+        assert_eq!(db.get_match_by_id(match_id).await.unwrap().draw_offered_by, Some(p1));
+        handle_make_move_logic(p2, serde_json::json!({"row": 1, "col": 1}), &events, &db).await;
+        assert_eq!(db.get_match_by_id(match_id).await.unwrap().draw_offered_by, None);
+
+        handle_offer_draw_logic(p2, &events, &db).await;
+        let messages = handle_accept_draw_logic(p1, &events, &db).await;
+        assert!(messages.iter().any(|m| matches!(m.message, ServerMessage::MatchEnded { reason: MatchEndReason::DrawAgreed, .. })));
+        assert_eq!(db.get_match_by_id(match_id).await.unwrap().outcome(), Some(MatchOutcome::Draw));
+    }
+
+    #[tokio::test]
+    async fn test_failed_move_write_notifies_both_players_and_quarantines() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let match_id = start_tic_tac_toe_match(&db, p1, p2).await;
+
+        sqlx::query("CREATE TRIGGER fail_match_updates BEFORE UPDATE ON matches BEGIN SELECT RAISE(ABORT, 'disk unavailable'); END")
+            .execute(db.pool())
+            .await
+            .unwrap();
+
+        let messages = handle_make_move_logic(p1, serde_json::json!({ "row": 0, "col": 0 }), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 2);
+        let player_ids: Vec<i64> = messages.iter().map(|m| m.player_id).collect();
+        assert!(player_ids.contains(&p1));
+        assert!(player_ids.contains(&p2));
+        assert!(messages.iter().all(|m| matches!(m.message, ServerMessage::Error { .. })));
+        assert!(db.is_match_quarantined(match_id));
+
+        // While storage is still failing the match stays paused
+        let messages = handle_make_move_logic(p1, serde_json::json!({ "row": 0, "col": 0 }), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0].message, ServerMessage::Error { .. }));
+
+        // Once storage recovers the move goes through and the match is released
+        sqlx::query("DROP TRIGGER fail_match_updates").execute(db.pool()).await.unwrap();
+        let messages = handle_make_move_logic(p1, serde_json::json!({ "row": 0, "col": 0 }), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 4);
+        assert!(messages.iter().all(|m| matches!(m.message, ServerMessage::MoveApplied { .. } | ServerMessage::GameStateUpdate { .. })));
+        assert!(!db.is_match_quarantined(match_id));
+
+        let state: TicTacToeGameState = serde_json::from_str(&db.get_match_by_id(match_id).await.unwrap().game_state).unwrap();
+        assert_eq!(state.board[0], 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_matchmaking_write_reports_error() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+
+        sqlx::query("CREATE TRIGGER fail_match_inserts BEFORE INSERT ON matches BEGIN SELECT RAISE(ABORT, 'disk unavailable'); END")
+            .execute(db.pool())
+            .await
+            .unwrap();
+
+        let messages = handle_join_matchmaking_logic(p1, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0].message, ServerMessage::Error { .. }));
+    }
 }
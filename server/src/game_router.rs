@@ -1,19 +1,26 @@
-use crate::games::{tic_tac_toe::*, rock_paper_scissors::*, briscola::*, chess::*, GameError};
+use crate::games::{tic_tac_toe::*, rock_paper_scissors::*, briscola::*, chess::*, DisconnectPolicy, GameEngine, GameError};
 use battld_common::games::{
     game_type::GameType,
     matches::{Match, MatchOutcome},
-    rock_paper_scissors::{RockPaperScissorsGameState, RockPaperScissorsMove},
-    briscola::{BriscolaGameState, BriscolaMove},
-    chess::{ChessGameState, ChessMove},
+    players::Seat,
+    rock_paper_scissors::{RockPaperScissorsGameState, RockPaperScissorsMove, RockPaperScissorsOptions},
+    briscola::{BriscolaGameState, BriscolaMove, BriscolaOptions, Suit},
+    chess::{ChessAction, ChessGameState, ChessMove, ChessPiece, GameOverReason},
+    tic_tac_toe::TicTacToeOptions,
 };
-use serde_json::Value as JsonValue;
+use battld_common::MatchSummary;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value as JsonValue};
 use rand::Rng;
+use std::time::Duration;
 
 /// Result of processing a game move
 pub struct GameMoveResult {
     pub new_state: JsonValue,
     pub is_finished: bool,
     pub outcome: Option<MatchOutcome>,
+    /// Human readable description of the move, safe to show to spectators
+    pub description: String,
 }
 
 /// Routes game moves to the appropriate game engine based on game type
@@ -32,14 +39,11 @@ pub fn handle_game_move(
 
 /// Redact match data for a specific player based on game type
 pub fn redact_match_for_player(match_data: &Match, player_id: i64) -> Match {
-    // Determine which player number this is (1 or 2)
-    let player_num = if player_id == match_data.player1_id {
-        1
-    } else if player_id == match_data.player2_id {
-        2
-    } else {
+    // Determine which seat this player is in
+    let Some(seat) = match_data.seat_of(player_id) else {
         return match_data.clone(); // Not a player in this match
     };
+    let player_num = seat.symbol();
 
     // Route to appropriate game redaction logic
     let redacted_state = match match_data.game_type {
@@ -85,19 +89,235 @@ pub fn redact_match_for_player(match_data: &Match, player_id: i64) -> Match {
 
     // Create a new Match with redacted game state
     Match {
-        id: match_data.id,
-        player1_id: match_data.player1_id,
-        player2_id: match_data.player2_id,
-        in_progress: match_data.in_progress,
-        outcome: match_data.outcome.clone(),
-        game_type: match_data.game_type.clone(),
         game_state: redacted_state,
+        ..match_data.clone()
+    }
+}
+
+/// Redact match data for spectators, hiding anything neither player would show the other
+pub fn redact_match_for_spectator(match_data: &Match) -> Match {
+    let redacted_state = match match_data.game_type {
+        GameType::RockPaperScissors => {
+            match serde_json::from_value::<RockPaperScissorsGameState>(match_data.game_state.clone()) {
+                Ok(state) => serde_json::to_value(state.redact_for_spectator()).unwrap_or(JsonValue::Null),
+                Err(_) => JsonValue::Null,
+            }
+        }
+        GameType::Briscola => {
+            match serde_json::from_value::<BriscolaGameState>(match_data.game_state.clone()) {
+                Ok(state) => serde_json::to_value(state.redact_for_spectator()).unwrap_or(JsonValue::Null),
+                Err(_) => JsonValue::Null,
+            }
+        }
+        GameType::TicTacToe | GameType::Chess => match_data.game_state.clone(),
+    };
+
+    Match {
+        game_state: redacted_state,
+        ..match_data.clone()
+    }
+}
+
+/// The game's own account of a finished match, None while it is still going
+pub fn match_summary(match_data: &Match) -> Option<MatchSummary> {
+    let state = match_data.game_state.clone();
+    let text = match match_data.game_type {
+        GameType::TicTacToe => TicTacToeEngine.summary(&serde_json::from_value(state).ok()?),
+        GameType::RockPaperScissors => RockPaperScissorsEngine.summary(&serde_json::from_value(state).ok()?),
+        GameType::Briscola => BriscolaGameEngine.summary(&serde_json::from_value(state).ok()?),
+        GameType::Chess => ChessEngine::new().summary(&serde_json::from_value(state).ok()?),
+    }?;
+    Some(MatchSummary { outcome: match_data.outcome.clone(), text })
+}
+
+/// What happens to a match of this game when a player drops out of it
+pub fn disconnect_policy(game_type: &GameType) -> DisconnectPolicy {
+    match game_type {
+        GameType::TicTacToe => TicTacToeEngine.disconnect_policy(),
+        GameType::RockPaperScissors => RockPaperScissorsEngine.disconnect_policy(),
+        GameType::Briscola => BriscolaGameEngine.disconnect_policy(),
+        GameType::Chess => ChessEngine::new().disconnect_policy(),
+    }
+}
+
+/// How many players sit at a match of this game
+pub fn seat_count(game_type: &GameType) -> usize {
+    match game_type {
+        GameType::TicTacToe => TicTacToeEngine.seats(),
+        GameType::RockPaperScissors => RockPaperScissorsEngine.seats(),
+        GameType::Briscola => BriscolaGameEngine.seats(),
+        GameType::Chess => ChessEngine::new().seats(),
+    }
+}
+
+/// Players the match is waiting on, in Rock-Paper-Scissors both until they picked their move
+pub fn players_to_move(match_data: &Match) -> Vec<i64> {
+    let state = match_data.game_state.clone();
+    let seats = match match_data.game_type {
+        GameType::TicTacToe => match serde_json::from_value::<TicTacToeGameState>(state) {
+            Ok(state) if !state.is_finished => vec![state.current_player],
+            _ => vec![],
+        },
+        GameType::Briscola => match serde_json::from_value::<BriscolaGameState>(state) {
+            Ok(state) if !state.is_finished() => vec![state.current_player],
+            _ => vec![],
+        },
+        GameType::Chess => match serde_json::from_value::<ChessGameState>(state) {
+            Ok(state) if !state.is_finished() => vec![state.current_turn.to_symbol()],
+            _ => vec![],
+        },
+        GameType::RockPaperScissors => match serde_json::from_value::<RockPaperScissorsGameState>(state) {
+            Ok(state) if !state.is_finished() => match state.rounds.last() {
+                Some((p1_move, p2_move)) if p1_move.is_none() || p2_move.is_none() => [(1, p1_move), (2, p2_move)]
+                    .into_iter()
+                    .filter(|(_, choice)| choice.is_none())
+                    .map(|(seat, _)| seat)
+                    .collect(),
+                _ => vec![1, 2],
+            },
+            _ => vec![],
+        },
+    };
+
+    seats
+        .into_iter()
+        .filter_map(Seat::from_symbol)
+        .filter_map(|seat| match_data.player_at(seat))
+        .collect()
+}
+
+/// Moves the player can make right now, each in the shape `handle_game_move` takes
+/// Chess draw actions are not listed, they are always available on the player's turn
+pub fn legal_moves(match_data: &Match, player_id: i64) -> Vec<JsonValue> {
+    let Some(player_symbol) = match_data.seat_of(player_id).map(Seat::symbol) else {
+        return vec![];
+    };
+
+    let state = match_data.game_state.clone();
+    match match_data.game_type {
+        GameType::TicTacToe => match serde_json::from_value::<TicTacToeGameState>(state) {
+            Ok(state) => TicTacToeEngine.legal_moves(&state, player_symbol)
+                .into_iter()
+                .map(|game_move| json!(game_move))
+                .collect(),
+            Err(_) => vec![],
+        },
+        GameType::RockPaperScissors => match serde_json::from_value::<RockPaperScissorsGameState>(state) {
+            Ok(state) => RockPaperScissorsEngine.legal_moves(&state, player_symbol)
+                .into_iter()
+                .map(|choice| json!({ "choice": choice }))
+                .collect(),
+            Err(_) => vec![],
+        },
+        GameType::Briscola => match serde_json::from_value::<BriscolaGameState>(state) {
+            Ok(state) => BriscolaGameEngine.legal_moves(&state, player_symbol)
+                .into_iter()
+                .map(|game_move| match game_move {
+                    BriscolaMove::PlayCard { card_index } => json!({ "card_index": card_index }),
+                    BriscolaMove::DeclareTrump { suit } => json!({ "trump_suit": suit }),
+                })
+                .collect(),
+            Err(_) => vec![],
+        },
+        GameType::Chess => match serde_json::from_value::<ChessGameState>(state) {
+            Ok(state) => ChessEngine::new().legal_moves(&state, player_symbol)
+                .into_iter()
+                .map(|chess_move| json!(ChessMoveData::Move(chess_move)))
+                .collect(),
+            Err(_) => vec![],
+        },
+    }
+}
+
+/// Time limit the match sets for the turn, such as in blitz Briscola, None to use the server's
+pub fn turn_limit(match_data: &Match) -> Option<Duration> {
+    match match_data.game_type {
+        GameType::Briscola => BriscolaGameEngine.turn_limit(&serde_json::from_value(match_data.game_state.clone()).ok()?),
+        _ => None,
     }
 }
 
+/// The round a Rock-Paper-Scissors move completed, with both choices, by comparing the state before it with the one after
+pub fn completed_round(before: &Match, after_state: &JsonValue) -> Option<(usize, RockPaperScissorsMove, RockPaperScissorsMove)> {
+    if before.game_type != GameType::RockPaperScissors {
+        return None;
+    }
+    let before: RockPaperScissorsGameState = serde_json::from_value(before.game_state.clone()).ok()?;
+    let after: RockPaperScissorsGameState = serde_json::from_value(after_state.clone()).ok()?;
+    let round = before.rounds.len().checked_sub(1)?;
+    match after.rounds.get(round)? {
+        (Some(p1_move), Some(p2_move)) => Some((round, *p1_move, *p2_move)),
+        _ => None,
+    }
+}
+
+/// The move played for a player who ran out of time, in the shape `MakeMove` takes
+/// None when the game forfeits the match instead
+pub fn timeout_move(match_data: &Match, player_id: i64) -> Option<JsonValue> {
+    let player_symbol = match_data.seat_of(player_id)?.symbol();
+
+    let state = match_data.game_state.clone();
+    match match_data.game_type {
+        GameType::TicTacToe => TicTacToeEngine
+            .timeout_move(&serde_json::from_value(state).ok()?, player_symbol)
+            .map(|game_move| json!(game_move)),
+        GameType::RockPaperScissors => RockPaperScissorsEngine
+            .timeout_move(&serde_json::from_value(state).ok()?, player_symbol)
+            .map(|choice| json!({ "choice": choice })),
+        GameType::Briscola => BriscolaGameEngine
+            .timeout_move(&serde_json::from_value(state).ok()?, player_symbol)
+            .map(|game_move| match game_move {
+                BriscolaMove::PlayCard { card_index } => json!({ "card_index": card_index }),
+                BriscolaMove::DeclareTrump { suit } => json!({ "trump_suit": suit }),
+            }),
+        GameType::Chess => ChessEngine::new()
+            .timeout_move(&serde_json::from_value(state).ok()?, player_symbol)
+            .map(|chess_move| json!(ChessMoveData::Move(chess_move))),
+    }
+}
+
+/// Validate the options requested for a game type against its typed options,
+/// the ones described by `GameType::options_schema`, filling in defaults
+/// Games without options only accept null
+pub fn normalize_game_options(game_type: &GameType, options: &JsonValue) -> Result<JsonValue, GameError> {
+    match game_type {
+        GameType::TicTacToe => {
+            let options: TicTacToeOptions = parse_options(options)?;
+            let options = options.normalized().map_err(GameError::IllegalMove)?;
+            Ok(serde_json::to_value(options).unwrap())
+        }
+        GameType::Briscola => {
+            let options: BriscolaOptions = parse_options(options)?;
+            Ok(serde_json::to_value(options).unwrap())
+        }
+        GameType::RockPaperScissors => {
+            let options: RockPaperScissorsOptions = parse_options(options)?;
+            options.validate().map_err(GameError::IllegalMove)?;
+            Ok(serde_json::to_value(options).unwrap())
+        }
+        GameType::Chess => {
+            if options.is_null() {
+                Ok(JsonValue::Null)
+            } else {
+                Err(GameError::IllegalMove(format!("{game_type} has no game options")))
+            }
+        }
+    }
+}
+
+/// Null stands for the default options
+fn parse_options<T: DeserializeOwned + Default>(options: &JsonValue) -> Result<T, GameError> {
+    if options.is_null() {
+        return Ok(T::default());
+    }
+    serde_json::from_value(options.clone())
+        .map_err(|e| GameError::IllegalMove(e.to_string()))
+}
+
 /// Initialize a new game state for a given game type
+/// Expects options already checked by `normalize_game_options`
 /// Returns the serialized game state as a JSON string
-pub fn initialize_game_state(game_type: &GameType) -> String {
+pub fn initialize_game_state(game_type: &GameType, options: &JsonValue) -> String {
     // Randomize who goes first
     let first_player = {
         let mut rng = rand::thread_rng();
@@ -106,16 +326,19 @@ pub fn initialize_game_state(game_type: &GameType) -> String {
 
     match game_type {
         GameType::TicTacToe => {
-            let mut state = TicTacToeGameState::new();
+            let options: TicTacToeOptions = serde_json::from_value(options.clone()).unwrap_or_default();
+            let mut state = TicTacToeGameState::from_options(&options).unwrap_or_default();
             state.current_player = first_player;
             serde_json::to_string(&state).unwrap()
         }
         GameType::RockPaperScissors => {
-            let state = RockPaperScissorsGameState::new();
+            let options: RockPaperScissorsOptions = serde_json::from_value(options.clone()).unwrap_or_default();
+            let state = RockPaperScissorsGameState::from_options(&options);
             serde_json::to_string(&state).unwrap()
         }
         GameType::Briscola => {
-            let mut state = BriscolaGameEngine::new_game();
+            let options: BriscolaOptions = serde_json::from_value(options.clone()).unwrap_or_default();
+            let mut state = BriscolaGameEngine::new_game(&options);
             state.current_player = first_player;
             serde_json::to_string(&state).unwrap()
         }
@@ -132,7 +355,7 @@ fn handle_tic_tac_toe_move(
     move_data: JsonValue,
 ) -> Result<GameMoveResult, GameError> {
     // Deserialize the current game state from JSON
-    let current_state: TicTacToeGameState = serde_json::from_value(game_match.game_state.clone())
+    let mut state = TicTacToeGameState::deserialize(&game_match.game_state)
         .map_err(|e| GameError::IllegalMove(format!("Invalid game state: {e}")))?;
 
     // Deserialize the move data
@@ -140,25 +363,25 @@ fn handle_tic_tac_toe_move(
         .map_err(|e| GameError::IllegalMove(format!("Invalid move data: {e}")))?;
 
     // Determine which player symbol this player is
-    let player_symbol = if player_id == game_match.player1_id {
-        1
-    } else if player_id == game_match.player2_id {
-        2
-    } else {
-        return Err(GameError::InvalidPlayer);
-    };
+    let seat = game_match.seat_of(player_id).ok_or(GameError::InvalidPlayer)?;
 
     // Call the TicTacToe engine to process the move
     let engine = TicTacToeEngine;
-    let new_state = engine.update(&current_state, player_symbol, &tic_tac_toe_move)?;
+    engine.apply(&mut state, seat.symbol(), &tic_tac_toe_move)?;
+    let description = format!(
+        "{} at row {}, column {}",
+        if seat == Seat::FIRST { "X" } else { "O" },
+        tic_tac_toe_move.row + 1,
+        tic_tac_toe_move.col + 1
+    );
 
     // Serialize the new state back to JSON
-    let new_state_json = serde_json::to_value(&new_state)
+    let new_state_json = serde_json::to_value(&state)
         .map_err(|e| GameError::IllegalMove(format!("Failed to serialize state: {e}")))?;
 
     // Determine outcome if game is finished
-    let outcome = if new_state.is_finished {
-        match new_state.winner {
+    let outcome = if state.is_finished {
+        match state.winner {
             Some(1) => Some(MatchOutcome::Player1Win),
             Some(2) => Some(MatchOutcome::Player2Win),
             _ => Some(MatchOutcome::Draw),
@@ -169,8 +392,9 @@ fn handle_tic_tac_toe_move(
 
     Ok(GameMoveResult {
         new_state: new_state_json,
-        is_finished: new_state.is_finished,
+        is_finished: state.is_finished,
         outcome,
+        description,
     })
 }
 
@@ -180,7 +404,7 @@ fn handle_rock_paper_scissors_move(
     move_data: JsonValue,
 ) -> Result<GameMoveResult, GameError> {
     // Deserialize the current game state from JSON
-    let current_state: RockPaperScissorsGameState = serde_json::from_value(game_match.game_state.clone())
+    let mut state = RockPaperScissorsGameState::deserialize(&game_match.game_state)
         .map_err(|e| GameError::IllegalMove(format!("Invalid game state: {e}")))?;
 
     // Deserialize the move data - expects {"choice": "rock"|"paper"|"scissors"}
@@ -193,25 +417,21 @@ fn handle_rock_paper_scissors_move(
         .map_err(|e| GameError::IllegalMove(format!("Invalid move data: {e}")))?;
 
     // Determine which player symbol this player is
-    let player_symbol = if player_id == game_match.player1_id {
-        1
-    } else if player_id == game_match.player2_id {
-        2
-    } else {
-        return Err(GameError::InvalidPlayer);
-    };
+    let seat = game_match.seat_of(player_id).ok_or(GameError::InvalidPlayer)?;
 
     // Call the RockPaperScissors engine to process the move
     let engine = RockPaperScissorsEngine;
-    let new_state = engine.update(&current_state, player_symbol, move_data.choice)?;
+    let round_index = state.rounds.len() - 1;
+    engine.apply(&mut state, seat.symbol(), &move_data.choice)?;
+    let description = describe_rock_paper_scissors_round(&state, round_index, seat);
 
     // Serialize the new state back to JSON
-    let new_state_json = serde_json::to_value(&new_state)
+    let new_state_json = serde_json::to_value(&state)
         .map_err(|e| GameError::IllegalMove(format!("Failed to serialize state: {e}")))?;
 
     // Determine outcome if game is finished
-    let outcome = if new_state.is_finished() {
-        match new_state.get_winner() {
+    let outcome = if state.is_finished() {
+        match state.get_winner() {
             Some(1) => Some(MatchOutcome::Player1Win),
             Some(2) => Some(MatchOutcome::Player2Win),
             _ => Some(MatchOutcome::Draw), // Should not happen with "first to 2 wins" logic
@@ -222,8 +442,9 @@ fn handle_rock_paper_scissors_move(
 
     Ok(GameMoveResult {
         new_state: new_state_json,
-        is_finished: new_state.is_finished(),
+        is_finished: state.is_finished(),
         outcome,
+        description,
     })
 }
 
@@ -233,38 +454,59 @@ fn handle_briscola_move(
     move_data: JsonValue,
 ) -> Result<GameMoveResult, GameError> {
     // Deserialize the current game state from JSON
-    let current_state: BriscolaGameState = serde_json::from_value(game_match.game_state.clone())
+    let mut state = BriscolaGameState::deserialize(&game_match.game_state)
         .map_err(|e| GameError::IllegalMove(format!("Invalid game state: {e}")))?;
 
-    // Deserialize the move data - expects {"card_index": 0}
+    // Deserialize the move data - expects {"card_index": 0} or {"trump_suit": "Coppe"}
     #[derive(serde::Deserialize)]
-    struct BriscolaMoveData {
-        card_index: usize,
+    #[serde(untagged)]
+    enum BriscolaMoveData {
+        PlayCard { card_index: usize },
+        DeclareTrump { trump_suit: Suit },
     }
 
     let move_data: BriscolaMoveData = serde_json::from_value(move_data)
         .map_err(|e| GameError::IllegalMove(format!("Invalid move data: {e}")))?;
+    let move_choice = match move_data {
+        BriscolaMoveData::PlayCard { card_index } => BriscolaMove::PlayCard { card_index },
+        BriscolaMoveData::DeclareTrump { trump_suit } => BriscolaMove::DeclareTrump { suit: trump_suit },
+    };
 
     // Determine which player symbol this player is
-    let player_symbol = if player_id == game_match.player1_id {
-        1
-    } else if player_id == game_match.player2_id {
-        2
-    } else {
-        return Err(GameError::InvalidPlayer);
-    };
+    let seat = game_match.seat_of(player_id).ok_or(GameError::InvalidPlayer)?;
 
     // Call the Briscola engine to process the move
     let engine = BriscolaGameEngine;
-    let new_state = engine.update(&current_state, player_symbol, BriscolaMove::PlayCard { card_index: move_data.card_index })?;
+    let played_card = match move_choice {
+        BriscolaMove::PlayCard { card_index } => {
+            let hand = if seat == Seat::FIRST { &state.player1_hand } else { &state.player2_hand };
+            hand.get(card_index).copied()
+        }
+        BriscolaMove::DeclareTrump { .. } => None,
+    };
+    let player1_pile = state.player1_pile.len();
+    let player2_pile = state.player2_pile.len();
+    engine.apply(&mut state, seat.symbol(), &move_choice)?;
+
+    let seat_number = seat.number();
+    let mut description = match (move_choice, played_card) {
+        (BriscolaMove::DeclareTrump { suit }, _) => format!("Player {seat_number} declared {suit:?} as briscola"),
+        (_, Some(card)) => format!("Player {seat_number} played the {:?} of {:?}", card.rank, card.suit),
+        (_, None) => format!("Player {seat_number} played a card"),
+    };
+    if state.player1_pile.len() > player1_pile {
+        description.push_str(", player 1 takes the trick");
+    } else if state.player2_pile.len() > player2_pile {
+        description.push_str(", player 2 takes the trick");
+    }
 
     // Serialize the new state back to JSON
-    let new_state_json = serde_json::to_value(&new_state)
+    let new_state_json = serde_json::to_value(&state)
         .map_err(|e| GameError::IllegalMove(format!("Failed to serialize state: {e}")))?;
 
     // Determine outcome if game is finished
-    let outcome = if new_state.is_finished() {
-        match new_state.get_winner() {
+    let outcome = if state.is_finished() {
+        match state.get_winner() {
             Some(1) => Some(MatchOutcome::Player1Win),
             Some(2) => Some(MatchOutcome::Player2Win),
             _ => Some(MatchOutcome::Draw),  // Tie is valid
@@ -275,8 +517,9 @@ fn handle_briscola_move(
 
     Ok(GameMoveResult {
         new_state: new_state_json,
-        is_finished: new_state.is_finished(),
+        is_finished: state.is_finished(),
         outcome,
+        description,
     })
 }
 
@@ -285,28 +528,37 @@ fn handle_chess_move(
     player_id: i64,
     move_data: JsonValue,
 ) -> Result<GameMoveResult, GameError> {
-    let current_state: ChessGameState = serde_json::from_value(game_match.game_state.clone())
+    let mut state = ChessGameState::deserialize(&game_match.game_state)
         .map_err(|e| GameError::IllegalMove(format!("Invalid game state: {e}")))?;
 
-    let chess_move: ChessMove = serde_json::from_value(move_data)
+    let move_data: ChessMoveData = serde_json::from_value(move_data)
         .map_err(|e| GameError::IllegalMove(format!("Invalid move data: {e}")))?;
 
-    let player_symbol = if player_id == game_match.player1_id {
-        1
-    } else if player_id == game_match.player2_id {
-        2
-    } else {
-        return Err(GameError::InvalidPlayer);
-    };
+    let seat = game_match.seat_of(player_id).ok_or(GameError::InvalidPlayer)?;
 
     let engine = ChessEngine::new();
-    let new_state = engine.update(&current_state, player_symbol, &chess_move)?;
+    let description = match move_data {
+        ChessMoveData::Move(chess_move) => {
+            let before = state.clone();
+            engine.apply(&mut state, seat.symbol(), &chess_move)?;
+            describe_chess_move(&before, &state, &chess_move)
+        }
+        ChessMoveData::Action { action } => {
+            engine.apply_action(&mut state, seat.symbol(), action)?;
+            let player = if seat == Seat::FIRST { "White" } else { "Black" };
+            match action {
+                ChessAction::OfferDraw => format!("{player} offers a draw"),
+                ChessAction::AcceptDraw => format!("{player} accepts the draw"),
+                ChessAction::ClaimDraw => format!("{player} claims a draw"),
+            }
+        }
+    };
 
-    let new_state_json = serde_json::to_value(&new_state)
+    let new_state_json = serde_json::to_value(&state)
         .map_err(|e| GameError::IllegalMove(format!("Failed to serialize state: {e}")))?;
 
-    let outcome = if new_state.is_finished() {
-        match new_state.get_winner() {
+    let outcome = if state.is_finished() {
+        match state.get_winner() {
             Some(1) => Some(MatchOutcome::Player1Win),
             Some(2) => Some(MatchOutcome::Player2Win),
             _ => Some(MatchOutcome::Draw),
@@ -317,14 +569,76 @@ fn handle_chess_move(
 
     Ok(GameMoveResult {
         new_state: new_state_json,
-        is_finished: new_state.is_finished(),
+        is_finished: state.is_finished(),
         outcome,
+        description,
     })
 }
 
+fn describe_rock_paper_scissors_round(
+    state: &RockPaperScissorsGameState,
+    round_index: usize,
+    seat: Seat,
+) -> String {
+    // Choices are only revealed once both players have made theirs
+    match state.rounds.get(round_index) {
+        Some((Some(p1_move), Some(p2_move))) => {
+            let result = match RockPaperScissorsGameState::compute_round_winner(*p1_move, *p2_move) {
+                Some(winner) => format!("player {winner} wins the round"),
+                None => "the round is a draw".to_string(),
+            };
+            let name = |choice: &RockPaperScissorsMove| format!("{choice:?}").to_lowercase();
+            format!("Round {}: {} vs {}, {result}", round_index + 1, name(p1_move), name(p2_move))
+        }
+        _ => format!("Player {} made their choice", seat.number()),
+    }
+}
+
+/// Long algebraic notation, such as `Ng1-f3`, `e4xd5`, `O-O` or `Qd1-h5#`
+fn describe_chess_move(before: &ChessGameState, after: &ChessGameState, chess_move: &ChessMove) -> String {
+    let Some(moving) = before.get_piece(chess_move.from) else {
+        return format!("{}-{}", chess_move.from.to_algebraic(), chess_move.to.to_algebraic());
+    };
+    let col_distance = chess_move.to.col as i32 - chess_move.from.col as i32;
+
+    let mut description = if moving.piece == ChessPiece::King && col_distance.abs() == 2 {
+        if col_distance > 0 { "O-O".to_string() } else { "O-O-O".to_string() }
+    } else {
+        let is_capture = before.get_piece(chess_move.to).is_some()
+            || (moving.piece == ChessPiece::Pawn && col_distance != 0);
+        let letter = |piece: ChessPiece| match piece {
+            ChessPiece::Pawn => "",
+            ChessPiece::Rook => "R",
+            ChessPiece::Knight => "N",
+            ChessPiece::Bishop => "B",
+            ChessPiece::Queen => "Q",
+            ChessPiece::King => "K",
+        };
+        let promotion = match after.get_piece(chess_move.to) {
+            Some(promoted) if before.is_promotion(chess_move) => format!("={}", letter(promoted.piece)),
+            _ => String::new(),
+        };
+        format!(
+            "{}{}{}{}{promotion}",
+            letter(moving.piece),
+            chess_move.from.to_algebraic(),
+            if is_capture { "x" } else { "-" },
+            chess_move.to.to_algebraic()
+        )
+    };
+
+    if matches!(after.game_over, Some(GameOverReason::Checkmate(_))) {
+        description.push('#');
+    } else if after.check_state.is_some() {
+        description.push('+');
+    }
+    description
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use battld_common::games::{chess::ChessPosition, matches::MatchStatus};
 
 
     #[test]
@@ -337,10 +651,16 @@ mod tests {
             id: 1,
             player1_id: 100,
             player2_id: 200,
+            seats: vec![],
             in_progress: true,
+            status: MatchStatus::Active,
             outcome: None,
             game_type: GameType::TicTacToe,
             game_state: state_json,
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
         };
 
         // Player 1 makes a move
@@ -349,6 +669,7 @@ mod tests {
 
         assert!(!result.is_finished);
         assert!(result.outcome.is_none());
+        assert_eq!(result.description, "X at row 1, column 1");
 
         // Verify the state was updated
         let new_state: TicTacToeGameState = serde_json::from_value(result.new_state).unwrap();
@@ -365,10 +686,16 @@ mod tests {
             id: 1,
             player1_id: 100,
             player2_id: 200,
+            seats: vec![],
             in_progress: true,
+            status: MatchStatus::Active,
             outcome: None,
             game_type: GameType::TicTacToe,
             game_state: state_json,
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
         };
 
         // Invalid player ID tries to make a move
@@ -387,10 +714,16 @@ mod tests {
             id: 1,
             player1_id: 100,
             player2_id: 200,
+            seats: vec![],
             in_progress: true,
+            status: MatchStatus::Active,
             outcome: None,
             game_type: GameType::TicTacToe,
             game_state: state_json,
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
         };
 
         // Player 2 tries to move when it's Player 1's turn
@@ -400,6 +733,242 @@ mod tests {
         assert!(matches!(result, Err(GameError::WrongTurn)));
     }
 
+    #[test]
+    fn test_spectator_redaction_hides_briscola_hands() {
+        let state = BriscolaGameEngine::new_game(&BriscolaOptions::default());
+        let game_match = Match {
+            id: 1,
+            player1_id: 100,
+            player2_id: 200,
+            seats: vec![],
+            in_progress: true,
+            status: MatchStatus::Active,
+            outcome: None,
+            game_type: GameType::Briscola,
+            game_state: serde_json::to_value(&state).unwrap(),
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
+        };
+
+        let redacted = redact_match_for_spectator(&game_match);
+        let redacted_state: BriscolaGameState = serde_json::from_value(redacted.game_state).unwrap();
+        assert!(redacted_state.player1_hand.is_empty());
+        assert!(redacted_state.player2_hand.is_empty());
+        assert!(redacted_state.deck.is_empty());
+        assert_eq!(redacted_state.trump_card, state.trump_card);
+        assert_eq!(redacted.player1_id, 100);
+    }
+
+    #[test]
+    fn test_spectator_redaction_hides_pending_rock_paper_scissors_move() {
+        let mut state = RockPaperScissorsGameState::new();
+        state.rounds = vec![
+            (Some(RockPaperScissorsMove::Rock), Some(RockPaperScissorsMove::Paper)),
+            (Some(RockPaperScissorsMove::Scissors), None),
+        ];
+        let game_match = Match {
+            id: 1,
+            player1_id: 100,
+            player2_id: 200,
+            seats: vec![],
+            in_progress: true,
+            status: MatchStatus::Active,
+            outcome: None,
+            game_type: GameType::RockPaperScissors,
+            game_state: serde_json::to_value(&state).unwrap(),
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
+        };
+
+        let redacted = redact_match_for_spectator(&game_match);
+        let redacted_state: RockPaperScissorsGameState = serde_json::from_value(redacted.game_state).unwrap();
+        assert_eq!(redacted_state.rounds[0], (Some(RockPaperScissorsMove::Rock), Some(RockPaperScissorsMove::Paper)));
+        assert_eq!(redacted_state.rounds[1], (Some(RockPaperScissorsMove::Redacted), None));
+    }
+
+    #[test]
+    fn test_normalize_tic_tac_toe_options() {
+        let options = normalize_game_options(&GameType::TicTacToe, &JsonValue::Null).unwrap();
+        assert_eq!(options, serde_json::json!({ "board_size": 3, "win_length": 3 }));
+
+        let options = normalize_game_options(&GameType::TicTacToe, &serde_json::json!({ "board_size": 5, "win_length": 4 })).unwrap();
+        let state: TicTacToeGameState = serde_json::from_str(&initialize_game_state(&GameType::TicTacToe, &options)).unwrap();
+        assert_eq!(state.board.len(), 25);
+        assert_eq!(state.win_length, 4);
+
+        let invalid = serde_json::json!({ "board_size": 9 });
+        assert!(matches!(normalize_game_options(&GameType::TicTacToe, &invalid), Err(GameError::IllegalMove(_))));
+    }
+
+    #[test]
+    fn test_normalize_briscola_options() {
+        let options = normalize_game_options(&GameType::Briscola, &JsonValue::Null).unwrap();
+        assert_eq!(options, serde_json::json!({ "declared_trump": false, "blitz": false }));
+
+        let options = normalize_game_options(&GameType::Briscola, &serde_json::json!({ "declared_trump": true })).unwrap();
+        let state: BriscolaGameState = serde_json::from_str(&initialize_game_state(&GameType::Briscola, &options)).unwrap();
+        assert_eq!(state.round_state, battld_common::games::briscola::RoundState::ChoosingTrump);
+        assert!(!state.blitz);
+
+        let options = normalize_game_options(&GameType::Briscola, &serde_json::json!({ "blitz": true })).unwrap();
+        let state: BriscolaGameState = serde_json::from_str(&initialize_game_state(&GameType::Briscola, &options)).unwrap();
+        assert!(state.blitz);
+
+        assert!(normalize_game_options(&GameType::Briscola, &serde_json::json!({ "board_size": 5 })).is_err());
+    }
+
+    #[test]
+    fn test_briscola_declare_trump_move() {
+        let mut state = BriscolaGameEngine::new_game(&BriscolaOptions { declared_trump: true, ..Default::default() });
+        state.current_player = 1;
+        let game_match = Match {
+            id: 1,
+            player1_id: 100,
+            player2_id: 200,
+            seats: vec![],
+            in_progress: true,
+            status: MatchStatus::Active,
+            outcome: None,
+            game_type: GameType::Briscola,
+            game_state: serde_json::to_value(&state).unwrap(),
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
+        };
+
+        let result = handle_game_move(&game_match, 100, serde_json::json!({ "trump_suit": "Spade" })).unwrap();
+        assert_eq!(result.description, "Player 1 declared Spade as briscola");
+        let new_state: BriscolaGameState = serde_json::from_value(result.new_state).unwrap();
+        assert_eq!(new_state.briscola_suit, Suit::Spade);
+        assert!(!result.is_finished);
+    }
+
+    #[test]
+    fn test_normalize_rock_paper_scissors_options() {
+        let options = normalize_game_options(&GameType::RockPaperScissors, &JsonValue::Null).unwrap();
+        assert_eq!(options, serde_json::json!({ "lizard_spock": false, "best_of": 3 }));
+
+        let options = normalize_game_options(&GameType::RockPaperScissors, &serde_json::json!({ "lizard_spock": true })).unwrap();
+        let state: RockPaperScissorsGameState =
+            serde_json::from_str(&initialize_game_state(&GameType::RockPaperScissors, &options)).unwrap();
+        assert!(state.lizard_spock);
+
+        assert!(normalize_game_options(&GameType::RockPaperScissors, &serde_json::json!({ "board_size": 5 })).is_err());
+        assert!(normalize_game_options(&GameType::RockPaperScissors, &serde_json::json!({ "best_of": 4 })).is_err());
+        assert!(normalize_game_options(&GameType::RockPaperScissors, &serde_json::json!({ "best_of": 11 })).is_err());
+
+        let options = normalize_game_options(&GameType::RockPaperScissors, &serde_json::json!({ "best_of": 5 })).unwrap();
+        let state: RockPaperScissorsGameState =
+            serde_json::from_str(&initialize_game_state(&GameType::RockPaperScissors, &options)).unwrap();
+        assert_eq!(state.wins_needed(), 3);
+    }
+
+    #[test]
+    fn test_rock_paper_scissors_lizard_spock_move() {
+        let classic = Match {
+            id: 1,
+            player1_id: 100,
+            player2_id: 200,
+            seats: vec![],
+            in_progress: true,
+            status: MatchStatus::Active,
+            outcome: None,
+            game_type: GameType::RockPaperScissors,
+            game_state: serde_json::to_value(RockPaperScissorsGameState::new()).unwrap(),
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
+        };
+        assert!(handle_game_move(&classic, 100, serde_json::json!({ "choice": "spock" })).is_err());
+
+        let extended = Match {
+            game_state: serde_json::to_value(RockPaperScissorsGameState::from_options(&RockPaperScissorsOptions {
+                lizard_spock: true,
+                ..Default::default()
+            }))
+            .unwrap(),
+            ..classic
+        };
+        let result = handle_game_move(&extended, 100, serde_json::json!({ "choice": "spock" })).unwrap();
+        let new_state: RockPaperScissorsGameState = serde_json::from_value(result.new_state).unwrap();
+        assert_eq!(new_state.rounds[0].0, Some(RockPaperScissorsMove::Spock));
+    }
+
+    #[test]
+    fn test_rock_paper_scissors_description_hides_pending_choice() {
+        let mut game_match = Match {
+            id: 1,
+            player1_id: 100,
+            player2_id: 200,
+            seats: vec![],
+            in_progress: true,
+            status: MatchStatus::Active,
+            outcome: None,
+            game_type: GameType::RockPaperScissors,
+            game_state: serde_json::to_value(RockPaperScissorsGameState::new()).unwrap(),
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
+        };
+
+        let result = handle_game_move(&game_match, 100, serde_json::json!({ "choice": "rock" })).unwrap();
+        assert_eq!(result.description, "Player 1 made their choice");
+
+        game_match.game_state = result.new_state;
+        let result = handle_game_move(&game_match, 200, serde_json::json!({ "choice": "paper" })).unwrap();
+        assert_eq!(result.description, "Round 1: rock vs paper, player 2 wins the round");
+    }
+
+    #[test]
+    fn test_chess_move_description() {
+        let mut game_match = Match {
+            id: 1,
+            player1_id: 100,
+            player2_id: 200,
+            seats: vec![],
+            in_progress: true,
+            status: MatchStatus::Active,
+            outcome: None,
+            game_type: GameType::Chess,
+            game_state: serde_json::to_value(ChessGameState::new()).unwrap(),
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
+        };
+        let mut play = |player_id: i64, from: (u8, u8), to: (u8, u8)| {
+            let move_data = serde_json::json!({
+                "from": { "row": from.0, "col": from.1 },
+                "to": { "row": to.0, "col": to.1 },
+            });
+            let result = handle_game_move(&game_match, player_id, move_data).unwrap();
+            game_match.game_state = result.new_state;
+            result.description
+        };
+
+        assert_eq!(play(100, (1, 4), (3, 4)), "e2-e4");
+        assert_eq!(play(200, (6, 3), (4, 3)), "d7-d5");
+        assert_eq!(play(100, (3, 4), (4, 3)), "e4xd5");
+        assert_eq!(play(200, (7, 6), (5, 5)), "Ng8-f6");
+        assert_eq!(play(100, (0, 5), (4, 1)), "Bf1-b5+");
+
+        let result = handle_game_move(&game_match, 200, serde_json::json!({ "action": "offer_draw" })).unwrap();
+        assert_eq!(result.description, "Black offers a draw");
+    }
+
+    #[test]
+    fn test_normalize_options_rejected_for_games_without_options() {
+        assert!(normalize_game_options(&GameType::Chess, &JsonValue::Null).is_ok());
+        assert!(normalize_game_options(&GameType::Chess, &serde_json::json!({ "board_size": 5 })).is_err());
+    }
+
     #[test]
     fn test_rock_paper_scissors_valid_move() {
         // Create initial RockPaperScissors state
@@ -410,10 +979,16 @@ mod tests {
             id: 1,
             player1_id: 100,
             player2_id: 200,
+            seats: vec![],
             in_progress: true,
+            status: MatchStatus::Active,
             outcome: None,
             game_type: GameType::RockPaperScissors,
             game_state: state_json,
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
         };
 
         // Player 1 makes a move
@@ -428,4 +1003,68 @@ mod tests {
         assert_eq!(new_state.rounds[0].0, Some(RockPaperScissorsMove::Rock));
         assert_eq!(new_state.rounds[0].1, None);
     }
+
+    #[test]
+    fn test_legal_moves_are_accepted_by_every_engine() {
+        for game_type in GameType::ALL {
+            let options = normalize_game_options(&game_type, &JsonValue::Null).unwrap();
+            let game_match = Match {
+                id: 1,
+                player1_id: 100,
+                player2_id: 200,
+                seats: vec![],
+                in_progress: true,
+                status: MatchStatus::Active,
+                outcome: None,
+                game_type: game_type.clone(),
+                game_state: serde_json::from_str(&initialize_game_state(&game_type, &options)).unwrap(),
+                players: vec![],
+                ephemeral: false,
+                draw_offered_by: None,
+                seq: 0,
+            };
+            let to_move = players_to_move(&game_match);
+
+            for player_id in [100, 200] {
+                let moves = legal_moves(&game_match, player_id);
+                assert_eq!(moves.is_empty(), !to_move.contains(&player_id), "{game_type}");
+                for move_data in moves {
+                    assert!(handle_game_move(&game_match, player_id, move_data.clone()).is_ok(), "{game_type}: {move_data}");
+                }
+            }
+            assert!(legal_moves(&game_match, 300).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_legal_moves_skip_occupied_cells_and_illegal_chess_moves() {
+        let mut state = TicTacToeGameState::new();
+        state.board[4] = 2;
+        let mut game_match = Match {
+            id: 1,
+            player1_id: 100,
+            player2_id: 200,
+            seats: vec![],
+            in_progress: true,
+            status: MatchStatus::Active,
+            outcome: None,
+            game_type: GameType::TicTacToe,
+            game_state: serde_json::to_value(&state).unwrap(),
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
+        };
+        let moves = legal_moves(&game_match, 100);
+        assert_eq!(moves.len(), 8);
+        assert!(!moves.contains(&json!({ "row": 1, "col": 1 })));
+
+        game_match.game_type = GameType::Chess;
+        game_match.game_state = serde_json::to_value(ChessGameState::new()).unwrap();
+        let moves = legal_moves(&game_match, 100);
+        assert_eq!(moves.len(), 20);
+        let e2_e4 = ChessMove { from: ChessPosition::from_algebraic("e2").unwrap(), to: ChessPosition::from_algebraic("e4").unwrap(), promotion: None };
+        assert!(moves.contains(&json!(e2_e4)));
+        assert!(!moves.contains(&json!({ "from": { "row": 0, "col": 0 }, "to": { "row": 2, "col": 0 } })));
+    }
 }
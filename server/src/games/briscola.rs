@@ -1,18 +1,22 @@
 use battld_common::games::{
-    briscola::{BriscolaGameState, BriscolaMove, Card, Rank, RoundState, Suit},
-    players::PlayerSymbol,
+    briscola::{BriscolaGameState, BriscolaMove, BriscolaOptions, Card, Rank, RoundState, Suit, BLITZ_CARD_SECS},
+    players::{PlayerSymbol, Seat},
 };
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
-use super::GameError;
+use super::{DisconnectPolicy, GameEngine, GameError, TimeoutOutcome};
+use std::time::Duration;
+
+const SUITS: [Suit; 4] = [Suit::Bastoni, Suit::Coppe, Suit::Denari, Suit::Spade];
 
 /// Stateless Briscola game engine
 pub struct BriscolaGameEngine;
 
 impl BriscolaGameEngine {
     /// Create a new game with shuffled deck
-    pub fn new_game() -> BriscolaGameState {
+    /// In the declared-trump variant no card is turned up and the trump is undecided
+    pub fn new_game(options: &BriscolaOptions) -> BriscolaGameState {
         let mut deck = Self::create_and_shuffle_deck();
 
         // Deal 3 cards to each player
@@ -25,11 +29,15 @@ impl BriscolaGameEngine {
             player2_hand.push(deck.pop().unwrap());
         }
 
-        // Set trump card
-        let trump_card = deck.pop().unwrap();
-        let briscola_suit = trump_card.suit;
+        // Set trump card, or keep every card in the deck until a suit is declared
+        let (trump_card, briscola_suit, round_state) = if options.declared_trump {
+            (None, Suit::Bastoni, RoundState::ChoosingTrump)
+        } else {
+            let trump_card = deck.pop().unwrap();
+            (Some(trump_card), trump_card.suit, RoundState::AwaitingFirstCard)
+        };
 
-        // Remaining deck has 33 cards
+        // Remaining deck has 33 cards (34 with a declared trump)
         let cards_remaining_in_deck = deck.len();
 
         BriscolaGameState {
@@ -38,87 +46,23 @@ impl BriscolaGameEngine {
             table: Vec::new(),
             deck,
             cards_remaining_in_deck,
-            trump_card: Some(trump_card),
+            trump_card,
             briscola_suit,
             player1_pile: Vec::new(),
             player2_pile: Vec::new(),
             current_player: 1, // Will be randomized in initialize_game_state
-            round_state: RoundState::AwaitingFirstCard,
+            round_state,
             previous_round: None,
+            blitz: options.blitz,
         }
     }
 
-    /// Update game state with a player's move
-    pub fn update(
-        &self,
-        state: &BriscolaGameState,
-        player: PlayerSymbol,
-        move_choice: BriscolaMove,
-    ) -> Result<BriscolaGameState, GameError> {
-        // 1. Validate game is in progress
-        if state.is_finished() {
-            return Err(GameError::GameNotInProgress);
-        }
-
-        // 2. Validate player number
-        if player != 1 && player != 2 {
-            return Err(GameError::InvalidPlayer);
-        }
-
-        // 3. Validate it's the player's turn
-        if state.current_player != player {
-            return Err(GameError::WrongTurn);
-        }
-
-        // 4. Extract card index from move
-        let BriscolaMove::PlayCard { card_index } = move_choice;
-
-        // 5. Validate player has card at that index
-        let hand = if player == 1 {
-            &state.player1_hand
-        } else {
-            &state.player2_hand
-        };
-        if card_index >= hand.len() {
-            return Err(GameError::IllegalMove("Invalid card index".to_string()));
-        }
-
-        // 6. Get the card being played
-        let card = hand[card_index];
-
-        // 7. Create new state and remove card from hand
-        let mut new_state = state.clone();
-        if player == 1 {
-            new_state.player1_hand.remove(card_index);
-        } else {
-            new_state.player2_hand.remove(card_index);
-        }
-
-        // 8. Add card to table
-        new_state.table.push((card, player));
-
-        // 9. Handle based on round state
-        match state.round_state {
-            RoundState::AwaitingFirstCard => {
-                // First card played, switch to waiting for second
-                new_state.round_state = RoundState::AwaitingSecondCard;
-                new_state.current_player = if player == 1 { 2 } else { 1 };
-            }
-            RoundState::AwaitingSecondCard => {
-                // Second card played, resolve the round
-                new_state = Self::resolve_round(new_state)?;
-            }
-        }
-
-        Ok(new_state)
-    }
-
     /// Create and shuffle a 40-card deck
     fn create_and_shuffle_deck() -> Vec<Card> {
         let mut deck = Vec::new();
 
         // Create all 40 cards
-        for suit in [Suit::Bastoni, Suit::Coppe, Suit::Denari, Suit::Spade] {
+        for suit in SUITS {
             for rank in [
                 Rank::Ace,
                 Rank::Two,
@@ -142,7 +86,7 @@ impl BriscolaGameEngine {
     }
 
     /// Resolve a round after both players have played
-    fn resolve_round(mut state: BriscolaGameState) -> Result<BriscolaGameState, GameError> {
+    fn resolve_round(state: &mut BriscolaGameState) {
         // 1. Determine round winner
         let (first_card, first_player) = state.table[0];
         let (second_card, _second_player) = state.table[1];
@@ -170,20 +114,18 @@ impl BriscolaGameEngine {
         // 5. Draw new cards (if deck not empty or trump available)
         if !state.deck.is_empty() || state.trump_card.is_some() {
             // Winner draws first
-            Self::draw_card_to_player(&mut state, round_winner);
+            Self::draw_card_to_player(state, round_winner);
 
             // Loser draws second (if cards still available)
             if !state.deck.is_empty() || state.trump_card.is_some() {
                 let other_player = if round_winner == 1 { 2 } else { 1 };
-                Self::draw_card_to_player(&mut state, other_player);
+                Self::draw_card_to_player(state, other_player);
             }
         }
 
         // 6. Winner of round starts next round
         state.current_player = round_winner;
         state.round_state = RoundState::AwaitingFirstCard;
-
-        Ok(state)
     }
 
     /// Determine the winner of a round based on Briscola rules
@@ -279,6 +221,135 @@ impl BriscolaGameEngine {
     }
 }
 
+impl GameEngine for BriscolaGameEngine {
+    type State = BriscolaGameState;
+    type Move = BriscolaMove;
+
+    /// Play a card or declare the trump for the player
+    fn apply(
+        &self,
+        state: &mut BriscolaGameState,
+        player: PlayerSymbol,
+        move_choice: &BriscolaMove,
+    ) -> Result<(), GameError> {
+        // 1. Validate game is in progress
+        if state.is_finished() {
+            return Err(GameError::GameNotInProgress);
+        }
+
+        // 2. Validate player number
+        let seat = self.seat(player)?;
+
+        // 3. Validate it's the player's turn
+        if state.current_player != player {
+            return Err(GameError::WrongTurn);
+        }
+
+        // 4. Declare the trump or extract card index from move
+        let card_index = match (*move_choice, &state.round_state) {
+            (BriscolaMove::DeclareTrump { suit }, RoundState::ChoosingTrump) => {
+                state.briscola_suit = suit;
+                state.round_state = RoundState::AwaitingFirstCard;
+                return Ok(());
+            }
+            (BriscolaMove::DeclareTrump { .. }, _) => {
+                return Err(GameError::IllegalMove("The briscola suit has already been chosen".to_string()));
+            }
+            (BriscolaMove::PlayCard { .. }, RoundState::ChoosingTrump) => {
+                return Err(GameError::IllegalMove("The briscola suit must be declared first".to_string()));
+            }
+            (BriscolaMove::PlayCard { card_index }, _) => card_index,
+        };
+
+        // 5. Validate player has card at that index
+        let hand = if seat == Seat::FIRST {
+            &mut state.player1_hand
+        } else {
+            &mut state.player2_hand
+        };
+        if card_index >= hand.len() {
+            return Err(GameError::IllegalMove("Invalid card index".to_string()));
+        }
+
+        // 6. Move the card from the hand to the table
+        let card = hand.remove(card_index);
+        state.table.push((card, player));
+
+        // 7. Handle based on round state
+        match state.round_state {
+            RoundState::AwaitingFirstCard | RoundState::ChoosingTrump => {
+                // First card played, switch to waiting for second
+                state.round_state = RoundState::AwaitingSecondCard;
+                state.current_player = seat.opponent().symbol();
+            }
+            RoundState::AwaitingSecondCard => {
+                // Second card played, resolve the round
+                Self::resolve_round(state);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn summary(&self, state: &BriscolaGameState) -> Option<String> {
+        if !state.is_finished() {
+            return None;
+        }
+        let (p1_points, p2_points) = state.get_score();
+        let (high, low) = (p1_points.max(p2_points), p1_points.min(p2_points));
+        Some(match state.get_winner() {
+            Some(_) => format!("Won {high}–{low} on points"),
+            None => format!("Drawn {high}–{low} on points"),
+        })
+    }
+
+    fn legal_moves(&self, state: &BriscolaGameState, player: PlayerSymbol) -> Vec<BriscolaMove> {
+        if state.is_finished() || state.current_player != player {
+            return vec![];
+        }
+        if state.round_state == RoundState::ChoosingTrump {
+            return SUITS.into_iter().map(|suit| BriscolaMove::DeclareTrump { suit }).collect();
+        }
+        let hand = match player {
+            1 => &state.player1_hand,
+            2 => &state.player2_hand,
+            _ => return vec![],
+        };
+        (0..hand.len()).map(|card_index| BriscolaMove::PlayCard { card_index }).collect()
+    }
+
+    fn disconnect_policy(&self) -> DisconnectPolicy {
+        DisconnectPolicy {
+            grace: Duration::from_secs(60),
+            on_timeout: TimeoutOutcome::Forfeit,
+            clocks_keep_running: false,
+        }
+    }
+
+    fn turn_limit(&self, state: &BriscolaGameState) -> Option<Duration> {
+        state.blitz.then_some(Duration::from_secs(BLITZ_CARD_SECS))
+    }
+
+    /// In blitz the lowest card in hand is played, keeping briscole, or the suit held most is declared
+    fn timeout_move(&self, state: &BriscolaGameState, player: PlayerSymbol) -> Option<BriscolaMove> {
+        if !state.blitz {
+            return None;
+        }
+        let hand = match player {
+            1 => &state.player1_hand,
+            2 => &state.player2_hand,
+            _ => return None,
+        };
+        self.legal_moves(state, player).into_iter().min_by_key(|game_move| match *game_move {
+            BriscolaMove::PlayCard { card_index } => {
+                let card = hand[card_index];
+                (card.suit == state.briscola_suit, BriscolaGameState::card_points(&card), Self::rank_value(card.rank))
+            }
+            BriscolaMove::DeclareTrump { suit } => (false, 0, u8::MAX - hand.iter().filter(|card| card.suit == suit).count() as u8),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -296,7 +367,7 @@ mod tests {
 
     #[test]
     fn test_new_game_initialization() {
-        let state = BriscolaGameEngine::new_game();
+        let state = BriscolaGameEngine::new_game(&BriscolaOptions::default());
 
         // Each player should have 3 cards
         assert_eq!(state.player1_hand.len(), 3);
@@ -396,7 +467,7 @@ mod tests {
         state.trump_card = Some(Card { suit: Suit::Spade, rank: Rank::Three });
 
         let engine = BriscolaGameEngine;
-        let new_state = engine.update(&state, 1, BriscolaMove::PlayCard { card_index: 0 }).unwrap();
+        let new_state = engine.update(&state, 1, &BriscolaMove::PlayCard { card_index: 0 }).unwrap();
 
         // Hand should have one less card
         assert_eq!(new_state.player1_hand.len(), 1);
@@ -420,7 +491,7 @@ mod tests {
         state.current_player = 1;
 
         let engine = BriscolaGameEngine;
-        let result = engine.update(&state, 1, BriscolaMove::PlayCard { card_index: 5 });
+        let result = engine.update(&state, 1, &BriscolaMove::PlayCard { card_index: 5 });
 
         assert!(matches!(result, Err(GameError::IllegalMove(_))));
     }
@@ -433,11 +504,30 @@ mod tests {
         state.current_player = 1;
 
         let engine = BriscolaGameEngine;
-        let result = engine.update(&state, 2, BriscolaMove::PlayCard { card_index: 0 });
+        let result = engine.update(&state, 2, &BriscolaMove::PlayCard { card_index: 0 });
 
         assert!(matches!(result, Err(GameError::WrongTurn)));
     }
 
+    #[test]
+    fn test_apply_in_place() {
+        let mut state = BriscolaGameState::new();
+        state.player1_hand = vec![Card { suit: Suit::Bastoni, rank: Rank::Ace }];
+        state.player2_hand = vec![Card { suit: Suit::Coppe, rank: Rank::King }];
+        state.current_player = 1;
+        let snapshot = state.clone();
+
+        let engine = BriscolaGameEngine;
+        let result = engine.apply(&mut state, 1, &BriscolaMove::PlayCard { card_index: 3 });
+        assert!(matches!(result, Err(GameError::IllegalMove(_))));
+        assert_eq!(state, snapshot);
+
+        engine.apply(&mut state, 1, &BriscolaMove::PlayCard { card_index: 0 }).unwrap();
+        assert!(state.player1_hand.is_empty());
+        assert_eq!(state.table.len(), 1);
+        assert_eq!(state.current_player, 2);
+    }
+
     #[test]
     fn test_round_resolution() {
         let mut state = BriscolaGameState::new();
@@ -452,10 +542,10 @@ mod tests {
         let engine = BriscolaGameEngine;
 
         // Player 1 plays ace of trump
-        let state = engine.update(&state, 1, BriscolaMove::PlayCard { card_index: 0 }).unwrap();
+        let state = engine.update(&state, 1, &BriscolaMove::PlayCard { card_index: 0 }).unwrap();
 
         // Player 2 plays two of coppe
-        let state = engine.update(&state, 2, BriscolaMove::PlayCard { card_index: 0 }).unwrap();
+        let state = engine.update(&state, 2, &BriscolaMove::PlayCard { card_index: 0 }).unwrap();
 
         // Player 1 should win (trump beats non-trump)
         assert_eq!(state.player1_pile.len(), 2);
@@ -492,8 +582,8 @@ mod tests {
         let engine = BriscolaGameEngine;
 
         // Play a round
-        let state = engine.update(&state, 1, BriscolaMove::PlayCard { card_index: 0 }).unwrap();
-        let state = engine.update(&state, 2, BriscolaMove::PlayCard { card_index: 0 }).unwrap();
+        let state = engine.update(&state, 1, &BriscolaMove::PlayCard { card_index: 0 }).unwrap();
+        let state = engine.update(&state, 2, &BriscolaMove::PlayCard { card_index: 0 }).unwrap();
 
         // Winner (player 1) draws last deck card, loser draws trump
         assert_eq!(state.deck.len(), 0);
@@ -613,7 +703,7 @@ mod tests {
         let engine = BriscolaGameEngine;
 
         // Make a move
-        let _new_state = engine.update(&state, 1, BriscolaMove::PlayCard { card_index: 0 }).unwrap();
+        let _new_state = engine.update(&state, 1, &BriscolaMove::PlayCard { card_index: 0 }).unwrap();
 
         // Original state should be unchanged
         assert_eq!(state.player1_hand, original_hand);
@@ -630,7 +720,7 @@ mod tests {
         state.trump_card = None;
 
         let engine = BriscolaGameEngine;
-        let result = engine.update(&state, 1, BriscolaMove::PlayCard { card_index: 0 });
+        let result = engine.update(&state, 1, &BriscolaMove::PlayCard { card_index: 0 });
 
         assert!(matches!(result, Err(GameError::GameNotInProgress)));
     }
@@ -644,10 +734,93 @@ mod tests {
 
         let engine = BriscolaGameEngine;
 
-        let result = engine.update(&state, 3, BriscolaMove::PlayCard { card_index: 0 });
+        let result = engine.update(&state, 3, &BriscolaMove::PlayCard { card_index: 0 });
         assert!(matches!(result, Err(GameError::InvalidPlayer)));
 
-        let result = engine.update(&state, 0, BriscolaMove::PlayCard { card_index: 0 });
+        let result = engine.update(&state, 0, &BriscolaMove::PlayCard { card_index: 0 });
         assert!(matches!(result, Err(GameError::InvalidPlayer)));
     }
+
+    #[test]
+    fn test_declared_trump_new_game() {
+        let state = BriscolaGameEngine::new_game(&BriscolaOptions { declared_trump: true, ..Default::default() });
+
+        assert_eq!(state.round_state, RoundState::ChoosingTrump);
+        assert!(state.trump_card.is_none());
+        assert_eq!(state.deck.len(), 34);
+        assert_eq!(state.cards_remaining_in_deck, 34);
+        assert!(!state.is_finished());
+    }
+
+    #[test]
+    fn test_declared_trump_flow() {
+        let mut state = BriscolaGameEngine::new_game(&BriscolaOptions { declared_trump: true, ..Default::default() });
+        state.current_player = 2;
+        let engine = BriscolaGameEngine;
+
+        // Cards cannot be played before the suit is declared
+        let result = engine.update(&state, 2, &BriscolaMove::PlayCard { card_index: 0 });
+        assert!(matches!(result, Err(GameError::IllegalMove(_))));
+
+        // Only the player leading the first trick declares
+        let result = engine.update(&state, 1, &BriscolaMove::DeclareTrump { suit: Suit::Coppe });
+        assert!(matches!(result, Err(GameError::WrongTurn)));
+
+        let state = engine.update(&state, 2, &BriscolaMove::DeclareTrump { suit: Suit::Coppe }).unwrap();
+        assert_eq!(state.briscola_suit, Suit::Coppe);
+        assert_eq!(state.round_state, RoundState::AwaitingFirstCard);
+        assert_eq!(state.current_player, 2);
+        assert_eq!(state.player2_hand.len(), 3);
+
+        // The suit cannot be changed afterwards
+        let result = engine.update(&state, 2, &BriscolaMove::DeclareTrump { suit: Suit::Spade });
+        assert!(matches!(result, Err(GameError::IllegalMove(_))));
+
+        let state = engine.update(&state, 2, &BriscolaMove::PlayCard { card_index: 0 }).unwrap();
+        assert_eq!(state.round_state, RoundState::AwaitingSecondCard);
+    }
+
+    #[test]
+    fn test_blitz_timeout_move() {
+        let engine = BriscolaGameEngine;
+        let mut state = BriscolaGameEngine::new_game(&BriscolaOptions::default());
+        state.current_player = 2;
+        state.briscola_suit = Suit::Bastoni;
+        state.player2_hand = vec![
+            Card { suit: Suit::Bastoni, rank: Rank::Two },
+            Card { suit: Suit::Spade, rank: Rank::King },
+            Card { suit: Suit::Denari, rank: Rank::Seven },
+        ];
+        assert_eq!(engine.turn_limit(&state), None);
+        assert_eq!(engine.timeout_move(&state, 2), None);
+
+        state.blitz = true;
+        assert_eq!(engine.turn_limit(&state), Some(Duration::from_secs(BLITZ_CARD_SECS)));
+        assert_eq!(engine.timeout_move(&state, 2), Some(BriscolaMove::PlayCard { card_index: 2 }));
+        assert_eq!(engine.timeout_move(&state, 1), None);
+
+        let mut state = BriscolaGameEngine::new_game(&BriscolaOptions { declared_trump: true, blitz: true });
+        state.current_player = 1;
+        state.player1_hand = vec![
+            Card { suit: Suit::Coppe, rank: Rank::Two },
+            Card { suit: Suit::Spade, rank: Rank::Ace },
+            Card { suit: Suit::Coppe, rank: Rank::Five },
+        ];
+        assert_eq!(engine.timeout_move(&state, 1), Some(BriscolaMove::DeclareTrump { suit: Suit::Coppe }));
+    }
+
+    #[test]
+    fn test_declared_trump_full_game() {
+        let mut state = BriscolaGameEngine::new_game(&BriscolaOptions { declared_trump: true, ..Default::default() });
+        let engine = BriscolaGameEngine;
+        state = engine.update(&state, state.current_player, &BriscolaMove::DeclareTrump { suit: Suit::Denari }).unwrap();
+
+        while !state.is_finished() {
+            state = engine.update(&state, state.current_player, &BriscolaMove::PlayCard { card_index: 0 }).unwrap();
+        }
+
+        let (p1_score, p2_score) = state.get_score();
+        assert_eq!(p1_score as u32 + p2_score as u32, 120);
+        assert_eq!(state.player1_pile.len() + state.player2_pile.len(), 40);
+    }
 }
@@ -1,12 +1,15 @@
-use super::GameError;
+use super::{DisconnectPolicy, GameEngine, GameError, TimeoutOutcome};
+use std::time::Duration;
 use battld_common::games::chess::*;
 use battld_common::games::players::PlayerSymbol;
 use serde::{Deserialize, Serialize};
 
+/// Move payload sent by clients: either a piece move or a draw action
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ChessMoveData {
-    pub from: ChessPosition,
-    pub to: ChessPosition,
+#[serde(untagged)]
+pub enum ChessMoveData {
+    Move(ChessMove),
+    Action { action: ChessAction },
 }
 
 pub struct ChessEngine;
@@ -16,59 +19,86 @@ impl ChessEngine {
         Self
     }
 
-    pub fn update(
+    /// Apply a draw offer, acceptance or claim for the player to move
+    pub fn apply_action(
         &self,
-        state: &ChessGameState,
+        state: &mut ChessGameState,
         player: PlayerSymbol,
-        chess_move: &ChessMove,
-    ) -> Result<ChessGameState, GameError> {
-        if player != 1 && player != 2 {
-            return Err(GameError::InvalidPlayer);
+        action: ChessAction,
+    ) -> Result<(), GameError> {
+        let player_color = self.validate_turn(state, player)?;
+
+        match action {
+            ChessAction::OfferDraw => {
+                if state.draw_offer == Some(player_color.opponent()) {
+                    state.draw_offer = None;
+                    state.game_over = Some(GameOverReason::DrawByAgreement);
+                } else if state.draw_offer == Some(player_color) {
+                    return Err(GameError::IllegalMove("Draw already offered".to_string()));
+                } else {
+                    state.draw_offer = Some(player_color);
+                }
+            }
+            ChessAction::AcceptDraw => {
+                if state.draw_offer != Some(player_color.opponent()) {
+                    return Err(GameError::IllegalMove("No draw offer to accept".to_string()));
+                }
+                state.draw_offer = None;
+                state.game_over = Some(GameOverReason::DrawByAgreement);
+            }
+            ChessAction::ClaimDraw => {
+                state.game_over = match state.claimable_draw() {
+                    Some(DrawClaim::ThreefoldRepetition) => Some(GameOverReason::ThreefoldRepetition),
+                    Some(DrawClaim::FiftyMoveRule) => Some(GameOverReason::FiftyMoveRule),
+                    None => return Err(GameError::IllegalMove("No draw available to claim".to_string())),
+                };
+            }
         }
 
+        Ok(())
+    }
+
+    fn validate_turn(&self, state: &ChessGameState, player: PlayerSymbol) -> Result<Player, GameError> {
+        let seat = self.seat(player)?;
+
         if state.is_finished() {
             return Err(GameError::GameNotInProgress);
         }
 
-        let player_color = Player::from_symbol(player).ok_or(GameError::InvalidPlayer)?;
+        let player_color = Player::from_seat(seat).ok_or(GameError::InvalidPlayer)?;
 
         if state.current_turn != player_color {
             return Err(GameError::WrongTurn);
         }
 
-        match state.is_valid_move(chess_move, player_color) {
-            Ok(true) => {},
-            Ok(false) => return Err(GameError::IllegalMove("Invalid move".to_string())),
-            Err(msg) => return Err(GameError::IllegalMove(msg)),
-        }
-
-        let mut new_state = state.clone();
-        self.apply_move(&mut new_state, chess_move)?;
+        Ok(player_color)
+    }
 
-        new_state.move_history.push(chess_move.clone());
-        new_state.current_turn = player_color.opponent();
+    fn apply_move(&self, state: &mut ChessGameState, chess_move: &ChessMove) -> Result<(), GameError> {
+        let mut piece = state.get_piece(chess_move.from).cloned()
+            .ok_or_else(|| GameError::IllegalMove("No piece at source position".to_string()))?;
 
-        new_state.check_state = if new_state.is_in_check(new_state.current_turn) {
-            Some(new_state.current_turn)
+        if let Some(rook_move) = state.castling_rook_move(chess_move) {
+            let rook = state.get_piece_mut(rook_move.from).take();
+            *state.get_piece_mut(rook_move.to) = rook;
+        }
+        if let Some(captured) = state.en_passant_capture(chess_move) {
+            *state.get_piece_mut(captured) = None;
+        }
+        if state.is_promotion(chess_move) {
+            piece.piece = chess_move.promotion.unwrap_or(ChessPiece::Queen);
+        }
+        let is_double_pawn_move = piece.piece == ChessPiece::Pawn && chess_move.from.row.abs_diff(chess_move.to.row) == 2;
+        state.en_passant = if is_double_pawn_move {
+            ChessPosition::new((chess_move.from.row + chess_move.to.row) / 2, chess_move.from.col)
         } else {
             None
         };
 
-        if self.is_checkmate(&new_state, new_state.current_turn) {
-            new_state.game_over = Some(GameOverReason::Checkmate(player_color));
-        } else if self.is_stalemate(&new_state, new_state.current_turn) {
-            new_state.game_over = Some(GameOverReason::Stalemate);
-        }
-
-        Ok(new_state)
-    }
-
-    fn apply_move(&self, state: &mut ChessGameState, chess_move: &ChessMove) -> Result<(), GameError> {
-        let piece = state.get_piece(chess_move.from).cloned()
-            .ok_or_else(|| GameError::IllegalMove("No piece at source position".to_string()))?;
-
         *state.get_piece_mut(chess_move.from) = None;
         *state.get_piece_mut(chess_move.to) = Some(piece);
+        state.castling_rights.revoke(chess_move.from);
+        state.castling_rights.revoke(chess_move.to);
 
         Ok(())
     }
@@ -98,7 +128,7 @@ impl ChessEngine {
                         for to_row in 0..8 {
                             for to_col in 0..8 {
                                 let to = ChessPosition::new(to_row, to_col).unwrap();
-                                let test_move = ChessMove { from, to };
+                                let test_move = ChessMove { from, to, promotion: None };
                                 if state.is_valid_move(&test_move, player).unwrap_or(false) {
                                     return true;
                                 }
@@ -112,6 +142,184 @@ impl ChessEngine {
     }
 }
 
+impl GameEngine for ChessEngine {
+    type State = ChessGameState;
+    type Move = ChessMove;
+
+    /// Move a piece for the player, then update check and game over
+    fn apply(
+        &self,
+        state: &mut ChessGameState,
+        player: PlayerSymbol,
+        chess_move: &ChessMove,
+    ) -> Result<(), GameError> {
+        let player_color = self.validate_turn(state, player)?;
+
+        match state.is_valid_move(chess_move, player_color) {
+            Ok(true) => {},
+            Ok(false) => return Err(GameError::IllegalMove("Invalid move".to_string())),
+            Err(msg) => return Err(GameError::IllegalMove(msg)),
+        }
+
+        let is_capture = state.get_piece(chess_move.to).is_some();
+        let is_pawn_move = state.get_piece(chess_move.from)
+            .is_some_and(|piece| piece.piece == ChessPiece::Pawn);
+
+        self.apply_move(state, chess_move)?;
+
+        state.move_history.push(chess_move.clone());
+        state.current_turn = player_color.opponent();
+
+        state.halfmove_clock = if is_capture || is_pawn_move {
+            0
+        } else {
+            state.halfmove_clock + 1
+        };
+        let position_key = state.position_key();
+        state.position_history.push(position_key);
+
+        // Moving instead of accepting declines the opponent's offer
+        if state.draw_offer == Some(player_color.opponent()) {
+            state.draw_offer = None;
+        }
+
+        state.check_state = if state.is_in_check(state.current_turn) {
+            Some(state.current_turn)
+        } else {
+            None
+        };
+
+        if self.is_checkmate(state, state.current_turn) {
+            state.game_over = Some(GameOverReason::Checkmate(player_color));
+        } else if self.is_stalemate(state, state.current_turn) {
+            state.game_over = Some(GameOverReason::Stalemate);
+        } else if let Some(draw) = state.automatic_draw() {
+            state.game_over = Some(draw);
+        }
+
+        Ok(())
+    }
+
+    fn summary(&self, state: &ChessGameState) -> Option<String> {
+        let moves = state.move_history.len().div_ceil(2);
+        let plural = if moves == 1 { "" } else { "s" };
+        Some(match state.game_over.as_ref()? {
+            GameOverReason::Checkmate(_) => format!("Checkmate in {moves} move{plural}"),
+            GameOverReason::Stalemate => format!("Stalemate after {moves} move{plural}"),
+            GameOverReason::DrawByAgreement => format!("Drawn by agreement after {moves} move{plural}"),
+            GameOverReason::ThreefoldRepetition => format!("Drawn by threefold repetition after {moves} move{plural}"),
+            GameOverReason::FiftyMoveRule => format!("Drawn by the fifty-move rule after {moves} move{plural}"),
+            GameOverReason::InsufficientMaterial => format!("Drawn by insufficient material after {moves} move{plural}"),
+            GameOverReason::FivefoldRepetition => format!("Drawn by fivefold repetition after {moves} move{plural}"),
+            GameOverReason::SeventyFiveMoveRule => format!("Drawn by the 75-move rule after {moves} move{plural}"),
+        })
+    }
+
+    /// Piece moves only, draw offers and claims are actions rather than moves
+    /// Promotions are listed once per piece the pawn can become
+    fn legal_moves(&self, state: &ChessGameState, player: PlayerSymbol) -> Vec<ChessMove> {
+        let Ok(player_color) = self.validate_turn(state, player) else {
+            return vec![];
+        };
+        let squares: Vec<ChessPosition> = (0..8)
+            .flat_map(|row| (0..8).filter_map(move |col| ChessPosition::new(row, col)))
+            .collect();
+
+        squares
+            .iter()
+            .filter(|from| state.get_piece(**from).is_some_and(|piece| piece.player == player_color))
+            .flat_map(|from| squares.iter().map(move |to| ChessMove { from: *from, to: *to, promotion: None }))
+            .filter(|chess_move| state.is_valid_move(chess_move, player_color) == Ok(true))
+            .flat_map(|chess_move| {
+                if state.is_promotion(&chess_move) {
+                    PROMOTION_PIECES.iter().map(|piece| ChessMove { promotion: Some(*piece), ..chess_move.clone() }).collect()
+                } else {
+                    vec![chess_move]
+                }
+            })
+            .collect()
+    }
+
+    /// Chess positions take long to build, so a player gets time to come back and their clock stops meanwhile
+    fn disconnect_policy(&self) -> DisconnectPolicy {
+        DisconnectPolicy {
+            grace: Duration::from_secs(120),
+            on_timeout: TimeoutOutcome::Forfeit,
+            clocks_keep_running: false,
+        }
+    }
+}
+
+/// Moves of a game in Standard Algebraic Notation, such as `Nbd7`, `exd6` or `e8=Q#`, as PGN writes them
+/// Stops at the first move the engine does not accept
+pub fn san_moves(history: &[ChessMove]) -> Vec<String> {
+    let engine = ChessEngine::new();
+    let mut state = ChessGameState::new();
+    let mut moves = Vec::new();
+    for chess_move in history {
+        let Some((san, next)) = san_move(&engine, &state, chess_move) else {
+            break;
+        };
+        moves.push(san);
+        state = next;
+    }
+    moves
+}
+
+/// A move in Standard Algebraic Notation and the state it leads to
+fn san_move(engine: &ChessEngine, state: &ChessGameState, chess_move: &ChessMove) -> Option<(String, ChessGameState)> {
+    let piece = state.get_piece(chess_move.from)?.piece;
+    let player = state.current_turn.to_symbol();
+    let mut san = String::new();
+
+    if state.is_castling(chess_move) {
+        san.push_str(if chess_move.to.col > chess_move.from.col { "O-O" } else { "O-O-O" });
+    } else {
+        let from = chess_move.from.to_algebraic();
+        let capture = state.get_piece(chess_move.to).is_some() || state.en_passant_capture(chess_move).is_some();
+        if piece == ChessPiece::Pawn {
+            if capture {
+                san.push_str(&from[..1]);
+            }
+        } else {
+            san.push(piece.letter());
+            // Name the file, the rank or both when another piece of the same kind could move there too
+            let rivals: Vec<ChessPosition> = engine
+                .legal_moves(state, player)
+                .into_iter()
+                .filter(|other| other.to == chess_move.to && other.from != chess_move.from)
+                .filter(|other| state.get_piece(other.from).is_some_and(|other_piece| other_piece.piece == piece))
+                .map(|other| other.from)
+                .collect();
+            if !rivals.is_empty() {
+                if rivals.iter().all(|rival| rival.col != chess_move.from.col) {
+                    san.push_str(&from[..1]);
+                } else if rivals.iter().all(|rival| rival.row != chess_move.from.row) {
+                    san.push_str(&from[1..]);
+                } else {
+                    san.push_str(&from);
+                }
+            }
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&chess_move.to.to_algebraic());
+        if state.is_promotion(chess_move) {
+            san.push('=');
+            san.push(chess_move.promotion.unwrap_or(ChessPiece::Queen).letter());
+        }
+    }
+
+    let next = engine.update(state, player, chess_move).ok()?;
+    if matches!(next.game_over, Some(GameOverReason::Checkmate(_))) {
+        san.push('#');
+    } else if next.check_state.is_some() {
+        san.push('+');
+    }
+    Some((san, next))
+}
+
 impl Default for ChessEngine {
     fn default() -> Self {
         Self::new()
@@ -137,6 +345,7 @@ mod tests {
         let chess_move = ChessMove {
             from: ChessPosition::new(1, 4).unwrap(),
             to: ChessPosition::new(2, 4).unwrap(),
+            promotion: None,
         };
 
         let new_state = engine.update(&state, 1, &chess_move).unwrap();
@@ -144,6 +353,23 @@ mod tests {
         assert!(new_state.get_piece(ChessPosition::new(2, 4).unwrap()).is_some());
     }
 
+    #[test]
+    fn test_summary() {
+        let engine = ChessEngine::new();
+        let mut state = ChessGameState::new();
+        assert_eq!(engine.summary(&state), None);
+
+        for (player, (from, to)) in [(1, ("f2", "f3")), (2, ("e7", "e5")), (1, ("g2", "g4")), (2, ("d8", "h4"))] {
+            let chess_move = ChessMove {
+                from: ChessPosition::from_algebraic(from).unwrap(),
+                to: ChessPosition::from_algebraic(to).unwrap(),
+                promotion: None,
+            };
+            engine.apply(&mut state, player, &chess_move).unwrap();
+        }
+        assert_eq!(engine.summary(&state).as_deref(), Some("Checkmate in 2 moves"));
+    }
+
     #[test]
     fn test_invalid_move_empty_square() {
         let engine = ChessEngine::new();
@@ -152,6 +378,7 @@ mod tests {
         let chess_move = ChessMove {
             from: ChessPosition::new(3, 3).unwrap(),
             to: ChessPosition::new(4, 4).unwrap(),
+            promotion: None,
         };
 
         let result = engine.update(&state, 1, &chess_move);
@@ -166,6 +393,7 @@ mod tests {
         let chess_move = ChessMove {
             from: ChessPosition::new(6, 4).unwrap(),
             to: ChessPosition::new(5, 4).unwrap(),
+            promotion: None,
         };
 
         let result = engine.update(&state, 2, &chess_move);
@@ -180,6 +408,7 @@ mod tests {
         let chess_move = ChessMove {
             from: ChessPosition::new(0, 1).unwrap(),
             to: ChessPosition::new(1, 3).unwrap(),
+            promotion: None,
         };
 
         let result = engine.update(&state, 1, &chess_move);
@@ -194,12 +423,146 @@ mod tests {
         let chess_move = ChessMove {
             from: ChessPosition::new(0, 1).unwrap(),
             to: ChessPosition::new(2, 2).unwrap(),
+            promotion: None,
         };
 
         let new_state = engine.update(&state, 1, &chess_move).unwrap();
         assert!(new_state.get_piece(ChessPosition::new(2, 2).unwrap()).is_some());
     }
 
+    /// Snapshot of the state after a draw action
+    fn apply_action(engine: &ChessEngine, state: &ChessGameState, player: PlayerSymbol, action: ChessAction) -> Result<ChessGameState, GameError> {
+        let mut new_state = state.clone();
+        engine.apply_action(&mut new_state, player, action)?;
+        Ok(new_state)
+    }
+
+    fn knight_shuffle(engine: &ChessEngine, state: &ChessGameState) -> ChessGameState {
+        let moves = [
+            ((0, 6), (2, 5)),
+            ((7, 6), (5, 5)),
+            ((2, 5), (0, 6)),
+            ((5, 5), (7, 6)),
+        ];
+        let mut state = state.clone();
+        for ((from_row, from_col), (to_row, to_col)) in moves {
+            let chess_move = ChessMove {
+                from: ChessPosition::new(from_row, from_col).unwrap(),
+                to: ChessPosition::new(to_row, to_col).unwrap(),
+                promotion: None,
+            };
+            let player = state.current_turn.to_symbol();
+            state = engine.update(&state, player, &chess_move).unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn test_halfmove_clock() {
+        let engine = ChessEngine::new();
+        let state = ChessGameState::new();
+
+        let knight_move = ChessMove {
+            from: ChessPosition::new(0, 6).unwrap(),
+            to: ChessPosition::new(2, 5).unwrap(),
+            promotion: None,
+        };
+        let state = engine.update(&state, 1, &knight_move).unwrap();
+        assert_eq!(state.halfmove_clock, 1);
+
+        let pawn_move = ChessMove {
+            from: ChessPosition::new(6, 4).unwrap(),
+            to: ChessPosition::new(4, 4).unwrap(),
+            promotion: None,
+        };
+        let state = engine.update(&state, 2, &pawn_move).unwrap();
+        assert_eq!(state.halfmove_clock, 0);
+    }
+
+    #[test]
+    fn test_claim_threefold_repetition() {
+        let engine = ChessEngine::new();
+        let state = ChessGameState::new();
+
+        let result = apply_action(&engine, &state, 1, ChessAction::ClaimDraw);
+        assert!(matches!(result, Err(GameError::IllegalMove(_))));
+
+        let state = knight_shuffle(&engine, &state);
+        assert_eq!(state.repetition_count(), 2);
+        assert_eq!(state.claimable_draw(), None);
+
+        let state = knight_shuffle(&engine, &state);
+        assert_eq!(state.claimable_draw(), Some(DrawClaim::ThreefoldRepetition));
+
+        let result = apply_action(&engine, &state, 2, ChessAction::ClaimDraw);
+        assert!(matches!(result, Err(GameError::WrongTurn)));
+
+        let state = apply_action(&engine, &state, 1, ChessAction::ClaimDraw).unwrap();
+        assert_eq!(state.game_over, Some(GameOverReason::ThreefoldRepetition));
+        assert_eq!(state.get_winner(), None);
+    }
+
+    #[test]
+    fn test_claim_fifty_move_rule() {
+        let engine = ChessEngine::new();
+        let mut state = ChessGameState::new();
+        state.halfmove_clock = 100;
+
+        let state = apply_action(&engine, &state, 1, ChessAction::ClaimDraw).unwrap();
+        assert_eq!(state.game_over, Some(GameOverReason::FiftyMoveRule));
+    }
+
+    #[test]
+    fn test_draw_by_agreement() {
+        let engine = ChessEngine::new();
+        let state = ChessGameState::new();
+
+        let result = apply_action(&engine, &state, 1, ChessAction::AcceptDraw);
+        assert!(matches!(result, Err(GameError::IllegalMove(_))));
+
+        let state = apply_action(&engine, &state, 1, ChessAction::OfferDraw).unwrap();
+        assert_eq!(state.draw_offer, Some(Player::White));
+        assert!(!state.is_finished());
+
+        let pawn_move = ChessMove {
+            from: ChessPosition::new(1, 4).unwrap(),
+            to: ChessPosition::new(3, 4).unwrap(),
+            promotion: None,
+        };
+        let state = engine.update(&state, 1, &pawn_move).unwrap();
+        assert_eq!(state.draw_offer, Some(Player::White));
+
+        let state = apply_action(&engine, &state, 2, ChessAction::AcceptDraw).unwrap();
+        assert_eq!(state.game_over, Some(GameOverReason::DrawByAgreement));
+    }
+
+    #[test]
+    fn test_moving_declines_draw_offer() {
+        let engine = ChessEngine::new();
+        let mut state = ChessGameState::new();
+        state.draw_offer = Some(Player::Black);
+
+        let pawn_move = ChessMove {
+            from: ChessPosition::new(1, 4).unwrap(),
+            to: ChessPosition::new(3, 4).unwrap(),
+            promotion: None,
+        };
+        let state = engine.update(&state, 1, &pawn_move).unwrap();
+        assert_eq!(state.draw_offer, None);
+    }
+
+    #[test]
+    fn test_move_data_parsing() {
+        let data: ChessMoveData = serde_json::from_value(serde_json::json!({
+            "from": { "row": 1, "col": 4 },
+            "to": { "row": 3, "col": 4 },
+        })).unwrap();
+        assert!(matches!(data, ChessMoveData::Move(_)));
+
+        let data: ChessMoveData = serde_json::from_value(serde_json::json!({ "action": "claim_draw" })).unwrap();
+        assert!(matches!(data, ChessMoveData::Action { action: ChessAction::ClaimDraw }));
+    }
+
     #[test]
     fn test_pawn_double_move() {
         let engine = ChessEngine::new();
@@ -208,9 +571,217 @@ mod tests {
         let chess_move = ChessMove {
             from: ChessPosition::new(1, 4).unwrap(),
             to: ChessPosition::new(3, 4).unwrap(),
+            promotion: None,
         };
 
         let new_state = engine.update(&state, 1, &chess_move).unwrap();
         assert!(new_state.get_piece(ChessPosition::new(3, 4).unwrap()).is_some());
     }
+
+    fn algebraic_move(from: &str, to: &str) -> ChessMove {
+        ChessMove {
+            from: ChessPosition::from_algebraic(from).unwrap(),
+            to: ChessPosition::from_algebraic(to).unwrap(),
+            promotion: None,
+        }
+    }
+
+    /// Starting position without the pieces on the given squares
+    fn cleared(squares: &[&str]) -> ChessGameState {
+        let mut state = ChessGameState::new();
+        for square in squares {
+            *state.get_piece_mut(ChessPosition::from_algebraic(square).unwrap()) = None;
+        }
+        state
+    }
+
+    #[test]
+    fn test_castling_both_sides() {
+        let engine = ChessEngine::new();
+        let state = cleared(&["f1", "g1", "b8", "c8", "d8"]);
+        assert!(engine.legal_moves(&state, 1).contains(&algebraic_move("e1", "g1")));
+
+        let state = engine.update(&state, 1, &algebraic_move("e1", "g1")).unwrap();
+        assert_eq!(state.get_piece(ChessPosition::from_algebraic("g1").unwrap()).unwrap().piece, ChessPiece::King);
+        assert_eq!(state.get_piece(ChessPosition::from_algebraic("f1").unwrap()).unwrap().piece, ChessPiece::Rook);
+        assert!(state.get_piece(ChessPosition::from_algebraic("h1").unwrap()).is_none());
+        assert!(!state.castling_rights.white_king_side && !state.castling_rights.white_queen_side);
+
+        let state = engine.update(&state, 2, &algebraic_move("e8", "c8")).unwrap();
+        assert_eq!(state.get_piece(ChessPosition::from_algebraic("c8").unwrap()).unwrap().piece, ChessPiece::King);
+        assert_eq!(state.get_piece(ChessPosition::from_algebraic("d8").unwrap()).unwrap().piece, ChessPiece::Rook);
+        assert!(state.get_piece(ChessPosition::from_algebraic("a8").unwrap()).is_none());
+        assert_eq!(state.halfmove_clock, 2);
+    }
+
+    #[test]
+    fn test_castling_blocked() {
+        let engine = ChessEngine::new();
+        let state = ChessGameState::new();
+        assert!(engine.update(&state, 1, &algebraic_move("e1", "g1")).is_err());
+
+        // Knight still between king and rook on the queen side
+        let state = cleared(&["c1", "d1"]);
+        assert!(engine.update(&state, 1, &algebraic_move("e1", "c1")).is_err());
+    }
+
+    #[test]
+    fn test_castling_out_of_or_through_check() {
+        let engine = ChessEngine::new();
+        let black_rook = Some(ChessPieceState { piece: ChessPiece::Rook, player: Player::Black });
+
+        let mut state = cleared(&["f1", "g1", "f2"]);
+        *state.get_piece_mut(ChessPosition::from_algebraic("f5").unwrap()) = black_rook;
+        assert!(engine.update(&state, 1, &algebraic_move("e1", "g1")).is_err());
+
+        let mut state = cleared(&["f1", "g1", "e2"]);
+        *state.get_piece_mut(ChessPosition::from_algebraic("e5").unwrap()) = black_rook;
+        assert!(engine.update(&state, 1, &algebraic_move("e1", "g1")).is_err());
+
+        let mut state = cleared(&["f1", "g1", "g2"]);
+        *state.get_piece_mut(ChessPosition::from_algebraic("g5").unwrap()) = black_rook;
+        assert!(engine.update(&state, 1, &algebraic_move("e1", "g1")).is_err());
+
+        // Only the squares the king crosses matter, b1 may be attacked
+        let mut state = cleared(&["b1", "c1", "d1", "b2"]);
+        *state.get_piece_mut(ChessPosition::from_algebraic("b5").unwrap()) = black_rook;
+        assert!(engine.update(&state, 1, &algebraic_move("e1", "c1")).is_ok());
+    }
+
+    #[test]
+    fn test_castling_rights_lost_after_moving() {
+        let engine = ChessEngine::new();
+        let state = cleared(&["f1", "g1", "e2"]);
+        let state = engine.update(&state, 1, &algebraic_move("e1", "e2")).unwrap();
+        let state = engine.update(&state, 2, &algebraic_move("b8", "c6")).unwrap();
+        let state = engine.update(&state, 1, &algebraic_move("e2", "e1")).unwrap();
+        let state = engine.update(&state, 2, &algebraic_move("c6", "b8")).unwrap();
+        assert!(engine.update(&state, 1, &algebraic_move("e1", "g1")).is_err());
+
+        let state = cleared(&["f1", "g1", "h2"]);
+        let state = engine.update(&state, 1, &algebraic_move("h1", "h2")).unwrap();
+        let state = engine.update(&state, 2, &algebraic_move("b8", "c6")).unwrap();
+        let state = engine.update(&state, 1, &algebraic_move("h2", "h1")).unwrap();
+        let state = engine.update(&state, 2, &algebraic_move("c6", "b8")).unwrap();
+        assert!(engine.update(&state, 1, &algebraic_move("e1", "g1")).is_err());
+        assert!(state.castling_rights.white_queen_side);
+    }
+
+    #[test]
+    fn test_castling_through_square_attacked_by_pawn() {
+        let engine = ChessEngine::new();
+        let mut state = cleared(&["f1", "g1", "g2"]);
+        *state.get_piece_mut(ChessPosition::from_algebraic("g2").unwrap()) =
+            Some(ChessPieceState { piece: ChessPiece::Pawn, player: Player::Black });
+        assert!(engine.update(&state, 1, &algebraic_move("e1", "g1")).is_err());
+    }
+
+    #[test]
+    fn test_en_passant() {
+        let engine = ChessEngine::new();
+        let mut state = ChessGameState::new();
+        for (player, from, to) in [(1, "e2", "e4"), (2, "a7", "a6"), (1, "e4", "e5"), (2, "d7", "d5")] {
+            state = engine.update(&state, player, &algebraic_move(from, to)).unwrap();
+        }
+        assert_eq!(state.en_passant, ChessPosition::from_algebraic("d6"));
+        assert!(engine.legal_moves(&state, 1).contains(&algebraic_move("e5", "d6")));
+
+        let captured = engine.update(&state, 1, &algebraic_move("e5", "d6")).unwrap();
+        assert!(captured.get_piece(ChessPosition::from_algebraic("d5").unwrap()).is_none());
+        assert_eq!(captured.get_piece(ChessPosition::from_algebraic("d6").unwrap()).unwrap().piece, ChessPiece::Pawn);
+        assert_eq!(captured.en_passant, None);
+
+        // Only right after the double move
+        let state = engine.update(&state, 1, &algebraic_move("b1", "c3")).unwrap();
+        let state = engine.update(&state, 2, &algebraic_move("a6", "a5")).unwrap();
+        assert!(engine.update(&state, 1, &algebraic_move("e5", "d6")).is_err());
+    }
+
+    #[test]
+    fn test_en_passant_square_in_position_key_only_when_capturable() {
+        let engine = ChessEngine::new();
+        let state = engine.update(&ChessGameState::new(), 1, &algebraic_move("e2", "e4")).unwrap();
+        assert_eq!(state.en_passant, ChessPosition::from_algebraic("e3"));
+        assert!(!state.position_key().contains("e3"));
+
+        let mut state = ChessGameState::new();
+        for (player, from, to) in [(1, "e2", "e4"), (2, "a7", "a6"), (1, "e4", "e5"), (2, "d7", "d5")] {
+            state = engine.update(&state, player, &algebraic_move(from, to)).unwrap();
+        }
+        assert!(state.position_key().contains("d6"));
+    }
+
+    #[test]
+    fn test_promotion() {
+        let engine = ChessEngine::new();
+        let mut state = cleared(&["a7", "a8", "b8"]);
+        *state.get_piece_mut(ChessPosition::from_algebraic("a7").unwrap()) =
+            Some(ChessPieceState { piece: ChessPiece::Pawn, player: Player::White });
+
+        let promotions: Vec<ChessMove> = engine
+            .legal_moves(&state, 1)
+            .into_iter()
+            .filter(|chess_move| chess_move.from == ChessPosition::from_algebraic("a7").unwrap())
+            .collect();
+        assert_eq!(promotions.len(), 4);
+        assert!(promotions.iter().all(|chess_move| chess_move.promotion.is_some()));
+
+        let knight = ChessMove { promotion: Some(ChessPiece::Knight), ..algebraic_move("a7", "a8") };
+        let promoted = engine.update(&state, 1, &knight).unwrap();
+        assert_eq!(promoted.get_piece(ChessPosition::from_algebraic("a8").unwrap()).unwrap().piece, ChessPiece::Knight);
+
+        let queen = engine.update(&state, 1, &algebraic_move("a7", "a8")).unwrap();
+        assert_eq!(queen.get_piece(ChessPosition::from_algebraic("a8").unwrap()).unwrap().piece, ChessPiece::Queen);
+
+        let king = ChessMove { promotion: Some(ChessPiece::King), ..algebraic_move("a7", "a8") };
+        assert!(engine.update(&state, 1, &king).is_err());
+        let early = ChessMove { promotion: Some(ChessPiece::Queen), ..algebraic_move("b2", "b3") };
+        assert!(engine.update(&state, 1, &early).is_err());
+    }
+
+    #[test]
+    fn test_san_moves() {
+        let moves = |squares: &[(&str, &str)]| -> Vec<ChessMove> { squares.iter().map(|(from, to)| algebraic_move(from, to)).collect() };
+
+        let scholars_mate = moves(&[("e2", "e4"), ("e7", "e5"), ("f1", "c4"), ("b8", "c6"), ("d1", "h5"), ("g8", "f6"), ("h5", "f7")]);
+        assert_eq!(san_moves(&scholars_mate), vec!["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"]);
+
+        let knights = moves(&[("b1", "c3"), ("a7", "a6"), ("g1", "f3"), ("a6", "a5"), ("f3", "g5"), ("b7", "b6"), ("g5", "e4"), ("e7", "e5"), ("e4", "f6")]);
+        assert_eq!(san_moves(&knights), vec!["Nc3", "a6", "Nf3", "a5", "Ng5", "b6", "Nge4", "e5", "Nf6+"]);
+
+        let illegal = moves(&[("e2", "e4"), ("e2", "e4")]);
+        assert_eq!(san_moves(&illegal), vec!["e4"]);
+    }
+
+    #[test]
+    fn test_capturing_the_last_piece_draws_by_insufficient_material() {
+        let engine = ChessEngine::new();
+        let mut state = ChessGameState::new();
+        state.board = [[None; 8]; 8];
+        for (square, piece, player) in [
+            ("e1", ChessPiece::King, Player::White),
+            ("e8", ChessPiece::King, Player::Black),
+            ("d4", ChessPiece::Bishop, Player::White),
+            ("a7", ChessPiece::Rook, Player::Black),
+        ] {
+            *state.get_piece_mut(ChessPosition::from_algebraic(square).unwrap()) = Some(ChessPieceState { piece, player });
+        }
+
+        let state = engine.update(&state, 1, &algebraic_move("d4", "a7")).unwrap();
+        assert_eq!(state.game_over, Some(GameOverReason::InsufficientMaterial));
+        assert_eq!(state.get_winner(), None);
+        assert_eq!(engine.summary(&state).unwrap(), "Drawn by insufficient material after 1 move");
+    }
+
+    #[test]
+    fn test_fivefold_repetition_ends_the_game() {
+        let engine = ChessEngine::new();
+        let mut state = ChessGameState::new();
+        for _ in 0..3 {
+            state = knight_shuffle(&engine, &state);
+        }
+        assert_eq!(state.game_over, None);
+        state = knight_shuffle(&engine, &state);
+        assert_eq!(state.game_over, Some(GameOverReason::FivefoldRepetition));
+    }
 }
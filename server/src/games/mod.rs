@@ -3,7 +3,87 @@ pub mod rock_paper_scissors;
 pub mod briscola;
 pub mod chess;
 
+use battld_common::games::players::{PlayerSymbol, Seat};
+use battld_common::{ErrorCode, ServerMessage};
 use std::fmt;
+use std::time::Duration;
+
+/// Applies moves to the state of one game
+/// Moves are validated before anything is touched, so a rejected move leaves the state as it was
+pub trait GameEngine {
+    type State: Clone;
+    type Move;
+
+    /// Apply a move to the state in place
+    fn apply(&self, state: &mut Self::State, player: PlayerSymbol, game_move: &Self::Move) -> Result<(), GameError>;
+
+    /// Apply a move to a snapshot of the state, leaving the original untouched
+    fn update(&self, state: &Self::State, player: PlayerSymbol, game_move: &Self::Move) -> Result<Self::State, GameError> {
+        let mut new_state = state.clone();
+        self.apply(&mut new_state, player, game_move)?;
+        Ok(new_state)
+    }
+
+    /// How a finished game went, such as "Checkmate in 31 moves", None while it is still going
+    fn summary(&self, state: &Self::State) -> Option<String>;
+
+    /// Every move `apply` would accept from the player right now, empty while it is not their turn
+    fn legal_moves(&self, state: &Self::State, player: PlayerSymbol) -> Vec<Self::Move>;
+
+    /// How many players sit at a match, each move names their seat counted from 1
+    fn seats(&self) -> usize {
+        2
+    }
+
+    /// The seat a move is made from, rejecting symbols no one sits at
+    fn seat(&self, player: PlayerSymbol) -> Result<Seat, GameError> {
+        Seat::from_symbol(player)
+            .filter(|seat| seat.number() <= self.seats())
+            .ok_or(GameError::InvalidPlayer)
+    }
+
+    /// What happens to a match when a player drops out of it
+    fn disconnect_policy(&self) -> DisconnectPolicy {
+        DisconnectPolicy::default()
+    }
+
+    /// Time the player to move has in this state, None leaves it to the server's turn time limit
+    fn turn_limit(&self, _state: &Self::State) -> Option<Duration> {
+        None
+    }
+
+    /// The move played for a player who ran out of time, None forfeits the match instead
+    fn timeout_move(&self, _state: &Self::State, _player: PlayerSymbol) -> Option<Self::Move> {
+        None
+    }
+}
+
+/// How long a player who dropped out of a match has to come back, and what happens if they don't
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisconnectPolicy {
+    pub grace: Duration,
+    pub on_timeout: TimeoutOutcome,
+    /// Whether the time away counts towards turn reminders and idle forfeits
+    pub clocks_keep_running: bool,
+}
+
+impl Default for DisconnectPolicy {
+    fn default() -> Self {
+        Self {
+            grace: Duration::from_secs(30),
+            on_timeout: TimeoutOutcome::Draw,
+            clocks_keep_running: false,
+        }
+    }
+}
+
+/// How a match ends when a player does not come back in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeoutOutcome {
+    Draw,
+    /// The player who left loses
+    Forfeit,
+}
 
 /// Errors that can occur during game operations
 #[derive(Debug, Clone, PartialEq)]
@@ -30,3 +110,23 @@ impl fmt::Display for GameError {
 }
 
 impl std::error::Error for GameError {}
+
+impl GameError {
+    /// The error as sent to the player who made the move
+    pub fn to_server_message(&self) -> ServerMessage {
+        match self {
+            GameError::IllegalMove(reason) => ServerMessage::error(ErrorCode::IllegalMove, &[("reason", reason)]),
+            GameError::GameNotInProgress => ServerMessage::error(ErrorCode::GameNotInProgress, &[]),
+            GameError::WrongTurn => ServerMessage::error(ErrorCode::NotYourTurn, &[]),
+            GameError::InvalidPlayer => ServerMessage::error(ErrorCode::InvalidPlayer, &[]),
+        }
+    }
+
+    /// The error of `game_router::normalize_game_options`, as sent to the player who asked for the options
+    pub fn to_options_message(&self) -> ServerMessage {
+        match self {
+            GameError::IllegalMove(reason) => ServerMessage::error(ErrorCode::InvalidOptions, &[("reason", reason)]),
+            other => other.to_server_message(),
+        }
+    }
+}
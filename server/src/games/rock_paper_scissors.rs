@@ -1,67 +1,113 @@
-use battld_common::games::{players::PlayerSymbol, rock_paper_scissors::{RockPaperScissorsGameState, RockPaperScissorsMove}};
+use battld_common::games::{players::{PlayerSymbol, Seat}, rock_paper_scissors::{RockPaperScissorsGameState, RockPaperScissorsMove}};
 
-use super::GameError;
+use super::{DisconnectPolicy, GameEngine, GameError, TimeoutOutcome};
+use std::time::Duration;
 
 /// Stateless RockPaperScissors game engine
 pub struct RockPaperScissorsEngine;
 
-impl RockPaperScissorsEngine {
-    /// Update the game state with a player's move
-    pub fn update(
+impl GameEngine for RockPaperScissorsEngine {
+    type State = RockPaperScissorsGameState;
+    type Move = RockPaperScissorsMove;
+
+    /// Record a player's move for the current round
+    fn apply(
         &self,
-        state: &RockPaperScissorsGameState,
+        state: &mut RockPaperScissorsGameState,
         player: PlayerSymbol,
-        move_choice: RockPaperScissorsMove,
-    ) -> Result<RockPaperScissorsGameState, GameError> {
+        move_choice: &RockPaperScissorsMove,
+    ) -> Result<(), GameError> {
+        let move_choice = *move_choice;
+
         // Check game is not already finished
         if state.is_finished() {
             return Err(GameError::GameNotInProgress);
         }
 
+        if move_choice == RockPaperScissorsMove::Redacted {
+            return Err(GameError::IllegalMove("Invalid move".to_string()));
+        }
+        if move_choice.is_lizard_spock() && !state.lizard_spock {
+            return Err(GameError::IllegalMove("Lizard and Spock are not allowed in this match".to_string()));
+        }
+
         // Get current round (last in the list)
         let current_round_idx = state.rounds.len() - 1;
-        let current_round = &state.rounds[current_round_idx];
+        let current_round = &mut state.rounds[current_round_idx];
 
         // Check if player has already submitted a move for this round
-        let player_already_moved = match player {
-            1 => current_round.0.is_some(),
-            2 => current_round.1.is_some(),
-            _ => return Err(GameError::InvalidPlayer),
+        let player_move = match self.seat(player)? {
+            Seat::FIRST => &mut current_round.0,
+            _ => &mut current_round.1,
         };
 
-        if player_already_moved {
+        if player_move.is_some() {
             return Err(GameError::IllegalMove(
                 "You have already submitted a move for this round".to_string(),
             ));
         }
 
-        // Create new state with the move
-        let mut new_state = state.clone();
-        let new_round = match player {
-            1 => (Some(move_choice), current_round.1),
-            2 => (current_round.0, Some(move_choice)),
-            _ => return Err(GameError::InvalidPlayer),
-        };
-
-        new_state.rounds[current_round_idx] = new_round;
+        *player_move = Some(move_choice);
 
         // If both players have now submitted moves, check if we need a new round
-        if let (Some(_), Some(_)) = new_round {
+        if let (Some(_), Some(_)) = *current_round {
             // Both moves are in - round is complete
             // Check if game is finished
-            if !new_state.is_finished() {
+            if !state.is_finished() {
                 // Game continues - add a new round
-                new_state.rounds.push((None, None));
+                state.rounds.push((None, None));
             }
         }
 
-        Ok(new_state)
+        Ok(())
+    }
+
+    fn summary(&self, state: &RockPaperScissorsGameState) -> Option<String> {
+        state.get_winner()?;
+        let (p1_wins, p2_wins) = state.get_score();
+        let rounds = state.rounds.iter().filter(|(p1_move, p2_move)| p1_move.is_some() && p2_move.is_some()).count();
+        Some(format!("Won {}–{} in {rounds} rounds", p1_wins.max(p2_wins), p1_wins.min(p2_wins)))
+    }
+
+    fn legal_moves(&self, state: &RockPaperScissorsGameState, player: PlayerSymbol) -> Vec<RockPaperScissorsMove> {
+        let submitted = match (state.rounds.last(), player) {
+            (Some((p1_move, _)), 1) => p1_move.is_some(),
+            (Some((_, p2_move)), 2) => p2_move.is_some(),
+            _ => return vec![],
+        };
+        if submitted || state.is_finished() {
+            return vec![];
+        }
+        let mut moves = vec![RockPaperScissorsMove::Rock, RockPaperScissorsMove::Paper, RockPaperScissorsMove::Scissors];
+        if state.lizard_spock {
+            moves.extend([RockPaperScissorsMove::Lizard, RockPaperScissorsMove::Spock]);
+        }
+        moves
+    }
+
+    /// Rounds are quick, so waiting long for a player who left makes little sense
+    fn disconnect_policy(&self) -> DisconnectPolicy {
+        DisconnectPolicy {
+            grace: Duration::from_secs(15),
+            on_timeout: TimeoutOutcome::Draw,
+            clocks_keep_running: true,
+        }
+    }
+
+    /// Running out of time gives up the round, with whatever loses to the opponent's choice
+    fn timeout_move(&self, state: &RockPaperScissorsGameState, player: PlayerSymbol) -> Option<RockPaperScissorsMove> {
+        let opponent_move = match (state.rounds.last()?, player) {
+            ((_, opponent_move), 1) | ((opponent_move, _), 2) => (*opponent_move)?,
+            _ => return None,
+        };
+        self.legal_moves(state, player).into_iter().find(|choice| opponent_move.defeats(choice))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use battld_common::games::rock_paper_scissors::RockPaperScissorsOptions;
 
     #[test]
     fn test_rock_paper_scissors_move_beats() {
@@ -83,6 +129,63 @@ mod tests {
         assert_eq!(RockPaperScissorsMove::Scissors.beats(&RockPaperScissorsMove::Scissors), None);
     }
 
+    #[test]
+    fn test_lizard_spock_beats() {
+        use RockPaperScissorsMove::*;
+
+        let moves = [Rock, Paper, Scissors, Lizard, Spock];
+        for a in moves {
+            let wins = moves.iter().filter(|b| a.defeats(b)).count();
+            assert_eq!(wins, 2, "{a:?} should beat exactly two moves");
+            for b in moves {
+                assert!(!(a.defeats(&b) && b.defeats(&a)));
+            }
+        }
+
+        assert_eq!(Lizard.beats(&Spock), Some(Lizard));
+        assert_eq!(Spock.beats(&Scissors), Some(Spock));
+        assert_eq!(Paper.beats(&Spock), Some(Paper));
+        assert_eq!(Rock.beats(&Lizard), Some(Rock));
+    }
+
+    #[test]
+    fn test_timeout_move_loses_the_round() {
+        let engine = RockPaperScissorsEngine;
+        let mut state = RockPaperScissorsGameState::new();
+        assert_eq!(engine.timeout_move(&state, 1), None);
+
+        state.rounds[0].1 = Some(RockPaperScissorsMove::Rock);
+        assert_eq!(engine.timeout_move(&state, 1), Some(RockPaperScissorsMove::Scissors));
+        assert_eq!(engine.timeout_move(&state, 2), None);
+    }
+
+    #[test]
+    fn test_lizard_spock_moves_require_mode() {
+        let engine = RockPaperScissorsEngine;
+
+        let classic = RockPaperScissorsGameState::new();
+        assert!(matches!(engine.update(&classic, 1, &RockPaperScissorsMove::Lizard), Err(GameError::IllegalMove(_))));
+        assert!(matches!(engine.update(&classic, 1, &RockPaperScissorsMove::Redacted), Err(GameError::IllegalMove(_))));
+
+        let extended = RockPaperScissorsGameState::from_options(&RockPaperScissorsOptions { lizard_spock: true, ..Default::default() });
+        let state = engine.update(&extended, 1, &RockPaperScissorsMove::Lizard).unwrap();
+        let state = engine.update(&state, 2, &RockPaperScissorsMove::Spock).unwrap();
+        assert_eq!(state.get_score(), (1, 0));
+        assert!(state.redact_for_player(2).lizard_spock);
+        assert!(state.redact_for_spectator().lizard_spock);
+    }
+
+    #[test]
+    fn test_best_of_one() {
+        let engine = RockPaperScissorsEngine;
+        let state = RockPaperScissorsGameState::from_options(&RockPaperScissorsOptions { best_of: 1, ..Default::default() });
+
+        let state = engine.update(&state, 1, &RockPaperScissorsMove::Rock).unwrap();
+        let state = engine.update(&state, 2, &RockPaperScissorsMove::Scissors).unwrap();
+        assert!(state.is_finished());
+        assert_eq!(state.get_winner(), Some(1));
+    }
+
     #[test]
     fn test_new_game_state() {
         let state = RockPaperScissorsGameState::new();
@@ -99,7 +202,7 @@ mod tests {
         let state = RockPaperScissorsGameState::new();
         let engine = RockPaperScissorsEngine;
 
-        let new_state = engine.update(&state, 1, RockPaperScissorsMove::Rock).unwrap();
+        let new_state = engine.update(&state, 1, &RockPaperScissorsMove::Rock).unwrap();
 
         assert_eq!(new_state.rounds[0].0, Some(RockPaperScissorsMove::Rock));
         assert_eq!(new_state.rounds[0].1, None);
@@ -112,7 +215,7 @@ mod tests {
         state.rounds[0].0 = Some(RockPaperScissorsMove::Rock); // Player 1 already moved
 
         let engine = RockPaperScissorsEngine;
-        let new_state = engine.update(&state, 2, RockPaperScissorsMove::Scissors).unwrap();
+        let new_state = engine.update(&state, 2, &RockPaperScissorsMove::Scissors).unwrap();
 
         assert_eq!(new_state.rounds[0].0, Some(RockPaperScissorsMove::Rock));
         assert_eq!(new_state.rounds[0].1, Some(RockPaperScissorsMove::Scissors));
@@ -128,7 +231,7 @@ mod tests {
         state.rounds[0].0 = Some(RockPaperScissorsMove::Rock); // Player 1 already moved
 
         let engine = RockPaperScissorsEngine;
-        let result = engine.update(&state, 1, RockPaperScissorsMove::Paper);
+        let result = engine.update(&state, 1, &RockPaperScissorsMove::Paper);
 
         assert!(matches!(result, Err(GameError::IllegalMove(_))));
     }
@@ -139,11 +242,11 @@ mod tests {
         let engine = RockPaperScissorsEngine;
 
         // Player 1 moves
-        let state = engine.update(&state, 1, RockPaperScissorsMove::Rock).unwrap();
+        let state = engine.update(&state, 1, &RockPaperScissorsMove::Rock).unwrap();
         assert_eq!(state.rounds.len(), 1);
 
         // Player 2 moves - round completes
-        let state = engine.update(&state, 2, RockPaperScissorsMove::Scissors).unwrap();
+        let state = engine.update(&state, 2, &RockPaperScissorsMove::Scissors).unwrap();
         assert_eq!(state.rounds.len(), 2); // New round added
         assert_eq!(state.rounds[1], (None, None));
     }
@@ -154,8 +257,8 @@ mod tests {
         let engine = RockPaperScissorsEngine;
 
         // Both players choose rock - draw
-        let state = engine.update(&state, 1, RockPaperScissorsMove::Rock).unwrap();
-        let state = engine.update(&state, 2, RockPaperScissorsMove::Rock).unwrap();
+        let state = engine.update(&state, 1, &RockPaperScissorsMove::Rock).unwrap();
+        let state = engine.update(&state, 2, &RockPaperScissorsMove::Rock).unwrap();
 
         assert_eq!(state.get_score(), (0, 0)); // No one gets a point
         assert_eq!(state.rounds.len(), 2); // New round added
@@ -168,14 +271,14 @@ mod tests {
         let engine = RockPaperScissorsEngine;
 
         // Round 1: Player 1 wins (rock beats scissors)
-        let state = engine.update(&state, 1, RockPaperScissorsMove::Rock).unwrap();
-        let state = engine.update(&state, 2, RockPaperScissorsMove::Scissors).unwrap();
+        let state = engine.update(&state, 1, &RockPaperScissorsMove::Rock).unwrap();
+        let state = engine.update(&state, 2, &RockPaperScissorsMove::Scissors).unwrap();
         assert_eq!(state.get_score(), (1, 0));
         assert!(!state.is_finished());
 
         // Round 2: Player 1 wins (paper beats rock)
-        let state = engine.update(&state, 1, RockPaperScissorsMove::Paper).unwrap();
-        let state = engine.update(&state, 2, RockPaperScissorsMove::Rock).unwrap();
+        let state = engine.update(&state, 1, &RockPaperScissorsMove::Paper).unwrap();
+        let state = engine.update(&state, 2, &RockPaperScissorsMove::Rock).unwrap();
         assert_eq!(state.get_score(), (2, 0));
         assert!(state.is_finished());
         assert_eq!(state.get_winner(), Some(1));
@@ -190,18 +293,18 @@ mod tests {
         let engine = RockPaperScissorsEngine;
 
         // Round 1: Player 1 wins
-        let state = engine.update(&state, 1, RockPaperScissorsMove::Rock).unwrap();
-        let state = engine.update(&state, 2, RockPaperScissorsMove::Scissors).unwrap();
+        let state = engine.update(&state, 1, &RockPaperScissorsMove::Rock).unwrap();
+        let state = engine.update(&state, 2, &RockPaperScissorsMove::Scissors).unwrap();
         assert_eq!(state.get_score(), (1, 0));
 
         // Round 2: Player 2 wins
-        let state = engine.update(&state, 1, RockPaperScissorsMove::Scissors).unwrap();
-        let state = engine.update(&state, 2, RockPaperScissorsMove::Rock).unwrap();
+        let state = engine.update(&state, 1, &RockPaperScissorsMove::Scissors).unwrap();
+        let state = engine.update(&state, 2, &RockPaperScissorsMove::Rock).unwrap();
         assert_eq!(state.get_score(), (1, 1));
 
         // Round 3: Player 2 wins (gets 2 total)
-        let state = engine.update(&state, 1, RockPaperScissorsMove::Rock).unwrap();
-        let state = engine.update(&state, 2, RockPaperScissorsMove::Paper).unwrap();
+        let state = engine.update(&state, 1, &RockPaperScissorsMove::Rock).unwrap();
+        let state = engine.update(&state, 2, &RockPaperScissorsMove::Paper).unwrap();
         assert_eq!(state.get_score(), (1, 2));
         assert!(state.is_finished());
         assert_eq!(state.get_winner(), Some(2));
@@ -219,7 +322,7 @@ mod tests {
         assert!(state.is_finished());
 
         let engine = RockPaperScissorsEngine;
-        let result = engine.update(&state, 1, RockPaperScissorsMove::Rock);
+        let result = engine.update(&state, 1, &RockPaperScissorsMove::Rock);
 
         assert!(matches!(result, Err(GameError::GameNotInProgress)));
     }
@@ -229,10 +332,10 @@ mod tests {
         let state = RockPaperScissorsGameState::new();
         let engine = RockPaperScissorsEngine;
 
-        let result = engine.update(&state, 3, RockPaperScissorsMove::Rock);
+        let result = engine.update(&state, 3, &RockPaperScissorsMove::Rock);
         assert!(matches!(result, Err(GameError::InvalidPlayer)));
 
-        let result = engine.update(&state, 0, RockPaperScissorsMove::Rock);
+        let result = engine.update(&state, 0, &RockPaperScissorsMove::Rock);
         assert!(matches!(result, Err(GameError::InvalidPlayer)));
     }
 
@@ -244,7 +347,7 @@ mod tests {
         let original_rounds = state.rounds.clone();
 
         // Make a move
-        let _new_state = engine.update(&state, 1, RockPaperScissorsMove::Rock).unwrap();
+        let _new_state = engine.update(&state, 1, &RockPaperScissorsMove::Rock).unwrap();
 
         // Original state should be unchanged
         assert_eq!(state.rounds, original_rounds);
@@ -276,8 +379,8 @@ mod tests {
         // Create 5 draw rounds
         let mut state = state;
         for _ in 0..5 {
-            state = engine.update(&state, 1, RockPaperScissorsMove::Rock).unwrap();
-            state = engine.update(&state, 2, RockPaperScissorsMove::Rock).unwrap();
+            state = engine.update(&state, 1, &RockPaperScissorsMove::Rock).unwrap();
+            state = engine.update(&state, 2, &RockPaperScissorsMove::Rock).unwrap();
         }
 
         assert_eq!(state.get_score(), (0, 0));
@@ -285,12 +388,12 @@ mod tests {
         assert!(!state.is_finished());
 
         // Now player 1 wins 2 rounds
-        state = engine.update(&state, 1, RockPaperScissorsMove::Rock).unwrap();
-        state = engine.update(&state, 2, RockPaperScissorsMove::Scissors).unwrap();
+        state = engine.update(&state, 1, &RockPaperScissorsMove::Rock).unwrap();
+        state = engine.update(&state, 2, &RockPaperScissorsMove::Scissors).unwrap();
         assert_eq!(state.get_score(), (1, 0));
 
-        state = engine.update(&state, 1, RockPaperScissorsMove::Paper).unwrap();
-        state = engine.update(&state, 2, RockPaperScissorsMove::Rock).unwrap();
+        state = engine.update(&state, 1, &RockPaperScissorsMove::Paper).unwrap();
+        state = engine.update(&state, 2, &RockPaperScissorsMove::Rock).unwrap();
         assert_eq!(state.get_score(), (2, 0));
         assert!(state.is_finished());
         assert_eq!(state.get_winner(), Some(1));
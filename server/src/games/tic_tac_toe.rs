@@ -1,5 +1,6 @@
-use super::GameError;
+use super::{GameEngine, GameError};
 use battld_common::games::players::PlayerSymbol;
+pub use battld_common::games::tic_tac_toe::TicTacToeGameState;
 use serde::{Deserialize, Serialize};
 
 /// Represents a move in tic-tac-toe
@@ -10,76 +11,9 @@ pub struct TicTacToeMove {
 }
 
 impl TicTacToeMove {
-    /// Convert row and column to board index
-    fn to_index(&self) -> Option<usize> {
-        if self.row < 3 && self.col < 3 {
-            Some(self.row * 3 + self.col)
-        } else {
-            None
-        }
-    }
-}
-
-/// Represents the complete state of a tic-tac-toe game
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct TicTacToeGameState {
-    /// The 3x3 board as a flat array of 9 cells (0 = empty, 1 = player1, 2 = player2)
-    pub board: [i32; 9],
-    /// Which player's turn it is (1 or 2)
-    pub current_player: PlayerSymbol,
-    /// Winner if game is finished (None if no winner or game in progress)
-    pub winner: Option<PlayerSymbol>,
-    /// Whether the game has finished
-    pub is_finished: bool,
-}
-
-impl TicTacToeGameState {
-    /// Create a new game with empty board, player 1 to start
-    pub fn new() -> Self {
-        Self {
-            board: [0; 9],
-            current_player: 1,
-            winner: None,
-            is_finished: false,
-        }
-    }
-
-    /// Redact game state for a specific player
-    /// TicTacToe doesn't need redaction (all info is public), so returns clone
-    pub fn redact_for_player(&self, _player: PlayerSymbol) -> Self {
-        self.clone()
-    }
-
-    /// Check if the board is full (draw condition if no winner)
-    fn is_full(&self) -> bool {
-        self.board.iter().all(|&cell| cell != 0)
-    }
-
-    /// Check if there's a winner. Returns Some(player_num) if there's a winner, None otherwise
-    fn check_winner(&self) -> Option<PlayerSymbol> {
-        // Winning combinations
-        let wins = [
-            [0, 1, 2], [3, 4, 5], [6, 7, 8], // rows
-            [0, 3, 6], [1, 4, 7], [2, 5, 8], // columns
-            [0, 4, 8], [2, 4, 6],            // diagonals
-        ];
-
-        for win in &wins {
-            let [a, b, c] = *win;
-            if self.board[a] != 0
-                && self.board[a] == self.board[b]
-                && self.board[b] == self.board[c]
-            {
-                return Some(self.board[a]);
-            }
-        }
-        None
-    }
-}
-
-impl Default for TicTacToeGameState {
-    fn default() -> Self {
-        Self::new()
+    /// Convert row and column to an index on the given board
+    fn to_index(&self, state: &TicTacToeGameState) -> Option<usize> {
+        state.coords_to_index(self.row, self.col)
     }
 }
 
@@ -92,30 +26,30 @@ impl TicTacToeEngine {
     pub fn new() -> Self {
         Self
     }
+}
 
-    /// Update the game state with a new move
-    ///
-    /// This is a pure function that takes the current state and returns a new state.
-    /// The old state is not modified.
+impl GameEngine for TicTacToeEngine {
+    type State = TicTacToeGameState;
+    type Move = TicTacToeMove;
+
+    /// Place the player's symbol on the board
     ///
     /// # Arguments
-    /// * `state` - The current game state
+    /// * `state` - The current game state, updated in place
     /// * `player` - The player making the move (1 or 2)
     /// * `game_move` - The move to make
     ///
     /// # Returns
-    /// * `Ok(new_state)` - The new game state after applying the move
-    /// * `Err(GameError)` - If the move is invalid
-    pub fn update(
+    /// * `Ok(())` - The move was applied
+    /// * `Err(GameError)` - If the move is invalid, the state is left unchanged
+    fn apply(
         &self,
-        state: &TicTacToeGameState,
+        state: &mut TicTacToeGameState,
         player: PlayerSymbol,
         game_move: &TicTacToeMove,
-    ) -> Result<TicTacToeGameState, GameError> {
+    ) -> Result<(), GameError> {
         // Validate player number first
-        if player != 1 && player != 2 {
-            return Err(GameError::InvalidPlayer);
-        }
+        let seat = self.seat(player)?;
 
         // Check if game is still in progress
         if state.is_finished {
@@ -129,7 +63,7 @@ impl TicTacToeEngine {
 
         // Convert move to index
         let index = game_move
-            .to_index()
+            .to_index(state)
             .ok_or_else(|| GameError::IllegalMove("Invalid coordinates".to_string()))?;
 
         // Check if cell is empty
@@ -137,24 +71,43 @@ impl TicTacToeEngine {
             return Err(GameError::IllegalMove("Cell already occupied".to_string()));
         }
 
-        // Create new state with the move applied
-        let mut new_state = state.clone();
-        new_state.board[index] = player;
+        state.board[index] = player;
 
         // Check for winner
-        if let Some(winner) = new_state.check_winner() {
-            new_state.winner = Some(winner);
-            new_state.is_finished = true;
-        } else if new_state.is_full() {
+        if let Some(winner) = state.check_winner() {
+            state.winner = Some(winner);
+            state.is_finished = true;
+        } else if state.is_full() {
             // Draw - no winner but board is full
-            new_state.winner = None;
-            new_state.is_finished = true;
+            state.winner = None;
+            state.is_finished = true;
         } else {
             // Game continues - switch to next player
-            new_state.current_player = if player == 1 { 2 } else { 1 };
+            state.current_player = seat.opponent().symbol();
         }
 
-        Ok(new_state)
+        Ok(())
+    }
+
+    fn summary(&self, state: &TicTacToeGameState) -> Option<String> {
+        if !state.is_finished {
+            return None;
+        }
+        let moves = state.board.iter().filter(|cell| **cell != 0).count();
+        Some(match state.winner {
+            Some(_) => format!("{} in a row in {moves} moves", state.win_length),
+            None => format!("Drawn after {moves} moves"),
+        })
+    }
+
+    fn legal_moves(&self, state: &TicTacToeGameState, player: PlayerSymbol) -> Vec<TicTacToeMove> {
+        if state.is_finished || state.current_player != player {
+            return vec![];
+        }
+        (0..state.board.len())
+            .filter(|index| state.board[*index] == 0)
+            .map(|index| TicTacToeMove { row: index / state.board_size, col: index % state.board_size })
+            .collect()
     }
 }
 
@@ -171,7 +124,7 @@ mod tests {
     #[test]
     fn test_new_game_state() {
         let state = TicTacToeGameState::new();
-        assert_eq!(state.board, [0; 9]);
+        assert_eq!(state.board, vec![0; 9]);
         assert_eq!(state.current_player, 1);
         assert_eq!(state.winner, None);
         assert!(!state.is_finished);
@@ -234,7 +187,7 @@ mod tests {
         // X X _
         // O O _
         // _ _ _
-        state.board = [1, 1, 0, 2, 2, 0, 0, 0, 0];
+        state.board = vec![1, 1, 0, 2, 2, 0, 0, 0, 0];
         state.current_player = 1;
 
         let game_move = TicTacToeMove { row: 0, col: 2 };
@@ -253,7 +206,7 @@ mod tests {
         // O X X
         // O X _
         // _ _ _
-        state.board = [2, 1, 1, 2, 1, 0, 0, 0, 0];
+        state.board = vec![2, 1, 1, 2, 1, 0, 0, 0, 0];
         state.current_player = 2;
 
         let game_move = TicTacToeMove { row: 2, col: 0 };
@@ -272,7 +225,7 @@ mod tests {
         // X O _
         // O X _
         // _ _ _
-        state.board = [1, 2, 0, 2, 1, 0, 0, 0, 0];
+        state.board = vec![1, 2, 0, 2, 1, 0, 0, 0, 0];
         state.current_player = 1;
 
         let game_move = TicTacToeMove { row: 2, col: 2 };
@@ -282,6 +235,26 @@ mod tests {
         assert!(new_state.is_finished);
     }
 
+    #[test]
+    fn test_connect_k_on_larger_board() {
+        let engine = TicTacToeEngine::new();
+        let mut state = TicTacToeGameState::with_size(5, 4);
+
+        // Player 1 has three in a row on the second row
+        state.board[5 + 1] = 1;
+        state.board[5 + 2] = 1;
+        state.board[5 + 3] = 1;
+
+        let out_of_bounds = TicTacToeMove { row: 5, col: 0 };
+        assert!(matches!(engine.update(&state, 1, &out_of_bounds), Err(GameError::IllegalMove(_))));
+
+        let game_move = TicTacToeMove { row: 1, col: 4 };
+        let new_state = engine.update(&state, 1, &game_move).unwrap();
+
+        assert_eq!(new_state.winner, Some(1));
+        assert!(new_state.is_finished);
+    }
+
     #[test]
     fn test_draw_condition() {
         let engine = TicTacToeEngine::new();
@@ -291,7 +264,7 @@ mod tests {
         // X O X
         // X O O
         // O X _
-        state.board = [1, 2, 1, 1, 2, 2, 2, 1, 0];
+        state.board = vec![1, 2, 1, 1, 2, 2, 2, 1, 0];
         state.current_player = 1;
 
         let game_move = TicTacToeMove { row: 2, col: 2 };
@@ -323,7 +296,7 @@ mod tests {
         let _new_state = engine.update(&state, 1, &game_move).unwrap();
 
         // Original state should be unchanged
-        assert_eq!(state.board, [0; 9]);
+        assert_eq!(state.board, vec![0; 9]);
         assert_eq!(state.current_player, 1);
         assert!(!state.is_finished);
     }
@@ -0,0 +1,4 @@
+//! Game engines and move routing, shared by the server binary and the benchmarks
+
+pub mod game_router;
+pub mod games;
@@ -0,0 +1,100 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    Json,
+};
+use futures::stream::{self, Stream};
+use std::convert::Infallible;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use battld_common::{games::matches::Match, LeaderboardResponse, LiveMatch};
+
+use crate::spectators::SpectatorEvent;
+use crate::{game_router, stats, AppState};
+
+const LIVE_MATCHES_LIMIT: i64 = 50;
+const LEADERBOARD_SIZE: i64 = 10;
+
+/// List matches currently being played
+pub async fn get_live_matches(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<LiveMatch>>, StatusCode> {
+    let records = state.db.get_live_matches(LIVE_MATCHES_LIMIT)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(records.iter().filter_map(|r| r.to_live_match()).collect()))
+}
+
+/// Current state of a match, redacted for spectators
+pub async fn get_live_match(
+    State(state): State<AppState>,
+    Path(match_id): Path<i64>,
+) -> Result<Json<Match>, StatusCode> {
+    load_spectator_match(&state, match_id).await.map(Json)
+}
+
+/// Top of the leaderboard, no login required
+pub async fn get_live_leaderboard(
+    State(state): State<AppState>,
+) -> Result<Json<LeaderboardResponse>, StatusCode> {
+    stats::fetch_leaderboard(&state.db, LEADERBOARD_SIZE, 0)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Server-sent events with every state update (`state`) and move (`move`) of a match
+/// Sends the current state first and closes once the match is over
+pub async fn stream_live_match(
+    State(state): State<AppState>,
+    Path(match_id): Path<i64>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    // Subscribe before loading so no update is lost in between
+    let rx = state.registry.spectators().subscribe();
+    let current = load_spectator_match(&state, match_id).await?;
+
+    let stream = stream::unfold(
+        (Some(SpectatorEvent::State(current)), Some(rx)),
+        move |(pending, rx)| async move {
+            let mut rx = rx?;
+            let update = match pending {
+                Some(update) => update,
+                None => next_update(&mut rx, match_id).await?,
+            };
+            let (event, rx) = match update {
+                SpectatorEvent::State(match_data) => {
+                    let rx = if match_data.in_progress { Some(rx) } else { None };
+                    (Event::default().event("state").json_data(&match_data), rx)
+                }
+                SpectatorEvent::Move { match_id, seq, description } => {
+                    let data = serde_json::json!({ "match_id": match_id, "seq": seq, "description": description });
+                    (Event::default().event("move").json_data(&data), Some(rx))
+                }
+            };
+            Some((Ok(event.unwrap_or_default()), (None, rx)))
+        },
+    );
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+async fn next_update(rx: &mut broadcast::Receiver<SpectatorEvent>, match_id: i64) -> Option<SpectatorEvent> {
+    loop {
+        match rx.recv().await {
+            Ok(update) if update.match_id() == match_id => return Some(update),
+            Ok(_) | Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+async fn load_spectator_match(state: &AppState, match_id: i64) -> Result<Match, StatusCode> {
+    let match_data = state.db.get_match_by_id(match_id)
+        .await
+        .and_then(|record| record.to_match())
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(game_router::redact_match_for_spectator(&match_data))
+}
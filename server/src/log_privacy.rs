@@ -0,0 +1,171 @@
+use axum::http::Uri;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+
+const REDACTED: &str = "[redacted]";
+
+/// Fields holding player names, logged as a fingerprint
+const NAME_FIELDS: &[&str] = &[
+    "name",
+    "player_name",
+    "player1_name",
+    "player2_name",
+    "challenger_name",
+    "challenged_name",
+];
+
+/// Fields holding credentials, never logged
+const SECRET_FIELDS: &[&str] = &[
+    "token",
+    "session_token",
+    "public_key",
+    "public_key_hint",
+    "signature",
+    "nonce",
+];
+
+/// Player names, keys and tokens are kept out of logs unless LOG_PII=true,
+/// which is only meant for debugging a local instance
+pub fn pii_logging_enabled() -> bool {
+    std::env::var("LOG_PII").ok().as_deref() == Some("true")
+}
+
+/// Player name as it should appear in logs
+pub fn name(value: &str) -> String {
+    redact_name(value, pii_logging_enabled())
+}
+
+/// Credential as it should appear in logs
+pub fn secret(value: &str) -> String {
+    redact_secret(value, pii_logging_enabled())
+}
+
+/// JSON rendering of a message with sensitive fields redacted
+pub fn message<T: Serialize>(message: &T) -> String {
+    match serde_json::to_value(message) {
+        Ok(value) => redact_json(value, pii_logging_enabled()).to_string(),
+        Err(_) => "<unserializable message>".to_string(),
+    }
+}
+
+/// Request path with sensitive query parameters redacted
+pub fn uri(uri: &Uri) -> String {
+    redact_uri(uri, pii_logging_enabled())
+}
+
+/// Short stable stand-in for a value, so log lines about the same player can still be correlated
+pub fn fingerprint(value: &str) -> String {
+    let digest = Sha256::digest(value.as_bytes());
+    let hex: String = digest.iter().take(4).map(|byte| format!("{byte:02x}")).collect();
+    format!("#{hex}")
+}
+
+fn redact_name(value: &str, pii: bool) -> String {
+    if pii { value.to_string() } else { fingerprint(value) }
+}
+
+fn redact_secret(value: &str, pii: bool) -> String {
+    if pii { value.to_string() } else { REDACTED.to_string() }
+}
+
+fn redact_json(value: JsonValue, pii: bool) -> JsonValue {
+    if pii {
+        return value;
+    }
+
+    match value {
+        JsonValue::Object(map) => JsonValue::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        JsonValue::String(s) if NAME_FIELDS.contains(&key.as_str()) => JsonValue::String(fingerprint(&s)),
+                        _ if SECRET_FIELDS.contains(&key.as_str()) => JsonValue::String(REDACTED.to_string()),
+                        other => redact_json(other, pii),
+                    };
+                    (key, value)
+                })
+                .collect(),
+        ),
+        JsonValue::Array(items) => JsonValue::Array(items.into_iter().map(|item| redact_json(item, pii)).collect()),
+        other => other,
+    }
+}
+
+fn redact_uri(uri: &Uri, pii: bool) -> String {
+    let Some(query) = uri.query().filter(|_| !pii) else {
+        return uri.to_string();
+    };
+
+    let query: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, _)) if SECRET_FIELDS.contains(&key) => format!("{key}={REDACTED}"),
+            Some((key, value)) if NAME_FIELDS.contains(&key) => format!("{key}={}", fingerprint(value)),
+            _ => pair.to_string(),
+        })
+        .collect();
+    format!("{}?{}", uri.path(), query.join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::{ArenaStanding, ClientMessage, LiveMatch, ServerMessage, games::game_type::GameType};
+
+    #[test]
+    fn test_token_never_logged_by_default() {
+        let msg = ClientMessage::Authenticate { token: "super-secret".to_string() };
+        let value = redact_json(serde_json::to_value(&msg).unwrap(), false);
+
+        assert!(!value.to_string().contains("super-secret"));
+        assert_eq!(value["token"], "[redacted]");
+    }
+
+    #[test]
+    fn test_names_are_fingerprinted() {
+        let live = LiveMatch {
+            match_id: 1,
+            game_type: GameType::Chess,
+            player1_id: 1,
+            player1_name: "alice".to_string(),
+            player2_id: 2,
+            player2_name: "bob".to_string(),
+        };
+        let value = redact_json(serde_json::to_value(&live).unwrap(), false);
+
+        assert_eq!(value["player1_name"], fingerprint("alice"));
+        assert_eq!(value["player2_id"], 2);
+        assert_ne!(fingerprint("alice"), fingerprint("bob"));
+    }
+
+    #[test]
+    fn test_arena_standings_names_are_fingerprinted() {
+        let msg = ServerMessage::ArenaEnded {
+            arena_id: 1,
+            standings: vec![ArenaStanding { rank: 1, player_id: 3, player_name: "carol".to_string(), points: 4, games: 2, wins: 2, streak: 2 }],
+        };
+        let value = redact_json(serde_json::to_value(&msg).unwrap(), false);
+
+        assert!(!value.to_string().contains("carol"));
+        assert_eq!(value["standings"][0]["player_name"], fingerprint("carol"));
+        assert_eq!(value["standings"][0]["points"], 4);
+    }
+
+    #[test]
+    fn test_debug_override_keeps_values() {
+        let value = serde_json::json!({ "name": "alice", "token": "abc" });
+        assert_eq!(redact_json(value.clone(), true), value);
+        assert_eq!(redact_name("alice", true), "alice");
+    }
+
+    #[test]
+    fn test_uri_query_redaction() {
+        let uri: Uri = "/stats?player_id=3&token=abc&name=alice".parse().unwrap();
+        assert_eq!(
+            redact_uri(&uri, false),
+            format!("/stats?player_id=3&token=[redacted]&name={}", fingerprint("alice"))
+        );
+        assert_eq!(redact_uri(&"/leaderboard".parse().unwrap(), false), "/leaderboard");
+    }
+}
@@ -10,24 +10,57 @@ use std::{sync::Arc, path::PathBuf, net::SocketAddr};
 use tower_http::services::ServeDir;
 use tower_http::cors::{CorsLayer, Any};
 
+mod arena;
 mod auth;
 mod auth_endpoints;
+mod bots;
+mod capacity;
+mod catalog;
+mod challenges;
+mod chaos;
+mod chess_analysis;
+mod collusion;
 mod csrf_protection;
 mod database;
+mod events;
+mod export;
 mod game_logic;
-mod game_router;
-mod games;
+mod live;
+mod log_privacy;
 mod log_requests;
+mod match_endpoints;
+mod matchmaking;
 mod nonce_cache;
+mod outbox;
+mod parties;
 mod players;
+mod query_metrics;
+mod quests;
+mod quick_play;
 mod rate_limit;
+mod rating;
+mod ready_check;
+mod rematches;
+mod reminders;
+mod replays;
 mod repository;
+mod retention;
+mod seasons;
+mod self_test;
+mod series;
 mod server_init;
 mod session_cache;
+mod settings;
+mod spectators;
 mod stats;
+mod storage;
+mod tiers;
+mod transfer;
+mod turn_clock;
 mod websocket;
 
 use database::Database;
+use server::game_router;
 use log_requests::log_request_middleware;
 use websocket::ConnectionRegistry;
 
@@ -39,12 +72,26 @@ pub struct AppState {
     pub registry: Arc<ConnectionRegistry>,
     pub nonce_cache: Arc<nonce_cache::NonceCache>,
     pub session_cache: Arc<session_cache::SessionCache>,
+    pub challenge_config: Arc<settings::Swap<challenges::ChallengeConfig>>,
+    pub capacity: Arc<capacity::Capacity>,
+    pub parties: Arc<parties::PartyRegistry>,
+    pub ready_checks: Arc<ready_check::ReadyChecks>,
+    pub quick_play: Arc<quick_play::QuickPlay>,
+    pub retention: Arc<retention::RetentionConfig>,
+    pub rate_limiter: Arc<rate_limit::RateLimiter>,
+    pub arenas: Arc<arena::Arenas>,
+    pub rematches: Arc<rematches::RematchOffers>,
+    pub storage: Arc<storage::Storage>,
 }
 
 async fn serve_index() -> Html<&'static str> {
     Html(include_str!("../static/index.html"))
 }
 
+async fn serve_replay() -> Html<&'static str> {
+    Html(include_str!("../static/replay.html"))
+}
+
 async fn redirect_to_https(Host(host): Host, uri: Uri) -> impl IntoResponse {
     // Remove port from host if present (we'll use standard HTTPS port 443)
     let host = host.split(':').next().unwrap_or(&host);
@@ -89,9 +136,32 @@ async fn main() {
 
     let (http_addr, https_addr) = parse_server_addrs();
 
+    if std::env::args().nth(1).as_deref() == Some("--self-test") {
+        if let Err(e) = self_test::run().await {
+            eprintln!("Self-test failed: {e}");
+            std::process::exit(1);
+        }
+        println!("Self-test passed");
+        return;
+    }
+
     let db = Database::new(DATABASE_URL).await.expect("Failed to connect to database");
     db.initialize().await.expect("Failed to initialize database schema");
     println!("Database initialized successfully");
+    game_logic::recover_scores(&db).await;
+
+    let storage = Arc::new(storage::Storage::from_env());
+
+    // `server export [file]` and `server import <file>` move players and finished matches between instances,
+    // `server backup` copies the database to storage
+    if let Some(command) = std::env::args().nth(1) {
+        let path = std::env::args().nth(2);
+        if let Err(e) = transfer::run_command(&db, &storage, &command, path.as_deref()).await {
+            eprintln!("{command} failed: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
 
     // Optionally seed fake users and matches for development/testing
     if std::env::var("SEED_DATABASE").ok().as_deref() == Some("true") {
@@ -123,11 +193,53 @@ async fn main() {
 
     let state = AppState {
         db: Arc::new(db),
-        registry: Arc::new(ConnectionRegistry::new()),
+        registry: Arc::new(ConnectionRegistry::with_settings(settings::ServerSettings::from_env())),
         nonce_cache,
         session_cache,
+        challenge_config: Arc::new(settings::Swap::new(challenges::ChallengeConfig::from_env())),
+        capacity: Arc::new(capacity::Capacity::new(capacity::CapacityConfig::from_env())),
+        parties: Arc::new(parties::PartyRegistry::new()),
+        ready_checks: Arc::new(ready_check::ReadyChecks::from_env()),
+        quick_play: Arc::new(quick_play::QuickPlay::from_env()),
+        retention: Arc::new(retention::RetentionConfig::from_env()),
+        rate_limiter: Arc::new(rate_limit::RateLimiter::from_env()),
+        arenas: Arc::new(arena::Arenas::new()),
+        rematches: Arc::new(rematches::RematchOffers::new()),
+        storage,
     };
 
+    events::spawn_subscriber(state.registry.events(), "match_log", events::log_match_events);
+    replays::spawn_recorder(state.registry.events(), state.db.clone());
+    retention::spawn_maintenance(state.db.clone(), (*state.retention).clone(), state.storage.clone());
+    chess_analysis::spawn_chess_analysis(state.registry.events(), state.db.clone(), chess_analysis::AnalysisConfig::from_env());
+    collusion::spawn_analysis(state.db.clone());
+    websocket::restore_disconnect_timers(state.db.clone(), state.registry.clone()).await;
+    turn_clock::spawn_expiry(state.db.clone(), state.registry.clone());
+    series::spawn_series(state.db.clone(), state.registry.clone());
+    bots::spawn_bots(state.db.clone(), state.registry.clone());
+    tiers::spawn_ladder(state.db.clone(), state.registry.clone());
+    stats::spawn_stats_deltas(state.db.clone(), state.registry.clone());
+    quests::spawn_quests(state.db.clone(), state.registry.clone());
+    seasons::spawn_rollover(state.db.clone(), seasons::SeasonConfig::from_env());
+    arena::spawn_arenas(state.arenas.clone(), state.capacity.clone(), state.db.clone(), state.registry.clone());
+    matchmaking::spawn_sweep(state.capacity.clone(), state.ready_checks.clone(), state.db.clone(), state.registry.clone());
+    ready_check::spawn_expiry(state.ready_checks.clone(), state.capacity.clone(), state.db.clone(), state.registry.clone());
+    reminders::spawn_reminders(state.db.clone(), state.registry.clone(), reminders::ReminderConfig::from_env());
+    #[cfg(unix)]
+    settings::spawn_reload_on_hangup(state.clone());
+
+    // Start expiry task for challenges (every 30s)
+    let db_clone = state.db.clone();
+    let registry_clone = state.registry.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+            let now = battld_common::time() as i64;
+            let messages = challenges::expire_challenges_logic(now, &db_clone).await;
+            registry_clone.send_messages(messages).await;
+        }
+    });
+
     let static_dir = std::env::var("STATIC_DIR").unwrap_or_else(|_| "static".to_string());
 
     // Create rate-limited API routes
@@ -135,16 +247,51 @@ async fn main() {
         // New auth endpoints
         .route("/auth/challenge", post(auth_endpoints::request_challenge))
         .route("/auth/verify", post(auth_endpoints::verify_challenge))
+        .route("/auth/recover", post(auth_endpoints::recover_account))
         .route("/auth/logout", post(auth_endpoints::logout))
         // Existing endpoints
         .route("/player", post(auth::create_player))
         .route("/player", get(players::get_player))
         .route("/player/current", get(players::post_player))
+        .route("/player/key", post(auth_endpoints::rebind_key))
+        .route("/player/time-preferences", get(players::get_time_preferences).post(players::set_time_preferences))
+        .route("/player/quiet-hours", get(players::get_quiet_hours).post(players::set_quiet_hours))
         .route("/player/:id", get(players::get_player_by_id))
+        .route("/players", get(players::search_players))
         .route("/matches/active", get(players::get_active_matches))
+        .route("/matches/:id", get(match_endpoints::get_match_state))
+        .route("/matches/:id/moves", post(match_endpoints::post_move))
+        .route("/matches/:id/simulate", post(match_endpoints::post_simulated_move))
+        .route("/matches/:id/replay", get(replays::get_match_replay))
+        .route("/matches/:id/export", get(export::get_match_export))
+        .route("/challenges", get(challenges::get_pending_challenges))
+        .route("/party", get(parties::get_party))
+        .route("/arenas", get(arena::get_arenas))
+        .route("/arenas/:id", get(arena::get_arena))
+        .route("/friends/:id", post(players::add_friend).delete(players::remove_friend))
         .route("/stats", get(stats::get_stats))
         .route("/leaderboard", get(stats::get_leaderboard))
-        .layer(rate_limit::create_rate_limiter())
+        .route("/quests", get(quests::get_quests))
+        .route("/capacity", get(capacity::get_capacity))
+        .route("/metrics/database", get(query_metrics::get_query_metrics))
+        .route("/games", get(catalog::get_games))
+        .route("/admin/maintenance", post(retention::post_maintenance))
+        .route("/admin/audit", get(collusion::get_audit_findings))
+        .route("/admin/slow-queries", get(query_metrics::get_slow_queries))
+        .route("/admin/export", get(transfer::get_export))
+        .route("/admin/import", post(transfer::post_import))
+        .route("/admin/reload", post(settings::post_reload))
+        .route("/admin/arenas", post(arena::post_arena))
+        .route("/admin/players/:id/setting-changes", get(players::get_setting_changes))
+        .route("/replays", get(replays::get_replay_settings))
+        .route("/replays/privacy", post(replays::set_replay_privacy))
+        .route("/replays/:token", get(replays::get_replay))
+        // Public endpoints for the live status page
+        .route("/live/matches", get(live::get_live_matches))
+        .route("/live/matches/:id", get(live::get_live_match))
+        .route("/live/matches/:id/events", get(live::stream_live_match))
+        .route("/live/leaderboard", get(live::get_live_leaderboard))
+        .layer(middleware::from_fn_with_state(state.clone(), rate_limit::rate_limit_middleware))
         .with_state(state.clone());
 
     // Configure CORS to allow all origins (for "bring your own client" architecture)
@@ -155,6 +302,7 @@ async fn main() {
 
     let app = Router::new()
         .route("/", get(serve_index))
+        .route("/replay/:token", get(serve_replay))
         .merge(api_routes)
         .route("/ws", get(websocket::ws_handler))
         .nest_service("/static", ServeDir::new(static_dir))
@@ -0,0 +1,319 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use battld_common::{games::matches::{Match, MatchStatus}, MakeMoveRequest, MatchStateResponse, ServerMessage, SimulatedMoveResponse};
+use serde::Deserialize;
+
+use crate::{auth, database::Database, events::EventBus, game_logic::{self, OutgoingMessage}, game_router, AppState};
+
+#[derive(Deserialize)]
+pub struct MatchStateQuery {
+    pub since_seq: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct SimulateQuery {
+    /// Replay frame to play from, counted from 0, required once the match is over
+    pub frame: Option<usize>,
+}
+
+/// Current state of one of the caller's matches
+/// Answers 304 when nothing changed after `since_seq`
+pub async fn get_match_state(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(match_id): Path<i64>,
+    Query(query): Query<MatchStateQuery>,
+) -> Result<Response, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+
+    match load_match_state(&state.db, player_id, match_id, query.since_seq).await? {
+        Some(match_state) => Ok(Json(match_state).into_response()),
+        None => Ok(StatusCode::NOT_MODIFIED.into_response()),
+    }
+}
+
+/// Play a move without a WebSocket, opponents with a socket are notified as usual
+pub async fn post_move(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(match_id): Path<i64>,
+    Json(request): Json<MakeMoveRequest>,
+) -> Result<Json<MatchStateResponse>, (StatusCode, String)> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers)
+        .await
+        .map_err(|status| (status, "Not authenticated".to_string()))?;
+
+    let (match_state, messages) = make_move_logic(&state.db, player_id, match_id, state.registry.events(), request).await?;
+    state.registry.send_messages(messages).await;
+    Ok(Json(match_state))
+}
+
+/// Run a move through the game engine without saving it, to preview where it leads
+/// Only where that gives no edge: matches against a bot, and replay frames of matches that are over
+pub async fn post_simulated_move(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(match_id): Path<i64>,
+    Query(query): Query<SimulateQuery>,
+    Json(request): Json<MakeMoveRequest>,
+) -> Result<Json<SimulatedMoveResponse>, (StatusCode, String)> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers)
+        .await
+        .map_err(|status| (status, "Not authenticated".to_string()))?;
+
+    Ok(Json(simulate_move_logic(&state.db, player_id, match_id, query.frame, request).await?))
+}
+
+/// None if the match did not change after `since_seq`
+async fn load_match_state(
+    db: &Database,
+    player_id: i64,
+    match_id: i64,
+    since_seq: Option<i64>,
+) -> Result<Option<MatchStateResponse>, StatusCode> {
+    let record = db.get_match_by_id(match_id).await.ok_or(StatusCode::NOT_FOUND)?;
+    if record.player1_id != player_id && record.player2_id != player_id {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if since_seq.is_some_and(|seq| record.seq <= seq) {
+        return Ok(None);
+    }
+
+    let match_data = record.to_match().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Some(MatchStateResponse {
+        seq: record.seq,
+        match_data: game_router::redact_match_for_player(&match_data, player_id),
+    }))
+}
+
+/// Returns the caller's view after the move and the messages for everyone else
+async fn make_move_logic(
+    db: &Database,
+    player_id: i64,
+    match_id: i64,
+    events: &EventBus,
+    request: MakeMoveRequest,
+) -> Result<(MatchStateResponse, Vec<OutgoingMessage>), (StatusCode, String)> {
+    let active_match_id = db.get_active_match_for_player(player_id).await.map(|record| record.id);
+    if active_match_id != Some(match_id) {
+        load_match_state(db, player_id, match_id, None)
+            .await
+            .map_err(|status| (status, "Match not found".to_string()))?;
+        return Err((StatusCode::CONFLICT, "Match is not in progress".to_string()));
+    }
+
+    let mut messages = game_logic::handle_make_move_logic(player_id, request.move_data, events, db).await;
+
+    let error = messages.iter().position(|msg| {
+        msg.player_id == player_id && matches!(msg.message, ServerMessage::Error { .. })
+    });
+    if let Some(index) = error {
+        let ServerMessage::Error { message, .. } = messages.remove(index).message else {
+            unreachable!()
+        };
+        return Err((StatusCode::BAD_REQUEST, message));
+    }
+
+    let match_state = load_match_state(db, player_id, match_id, None)
+        .await
+        .map_err(|status| (status, "Match not found".to_string()))?
+        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Match state unavailable".to_string()))?;
+
+    messages.retain(|msg| msg.player_id != player_id);
+    Ok((match_state, messages))
+}
+
+async fn simulate_move_logic(
+    db: &Database,
+    player_id: i64,
+    match_id: i64,
+    frame: Option<usize>,
+    request: MakeMoveRequest,
+) -> Result<SimulatedMoveResponse, (StatusCode, String)> {
+    let record = db.get_match_by_id(match_id)
+        .await
+        .filter(|record| record.player1_id == player_id || record.player2_id == player_id)
+        .ok_or((StatusCode::NOT_FOUND, "Match not found".to_string()))?;
+    let mut match_data = record.to_match().ok_or((StatusCode::NOT_FOUND, "Match not found".to_string()))?;
+    if !allows_simulation(&match_data) {
+        return Err((StatusCode::FORBIDDEN, "Moves can only be simulated against a bot or once the match is over".to_string()));
+    }
+    if match_data.status == MatchStatus::Finished {
+        // A finished state takes no moves, so play from the position the player picked in the replay
+        let frame = frame.ok_or((StatusCode::BAD_REQUEST, "Pick a replay frame to simulate from".to_string()))?;
+        let frames = db.get_match_frames(match_id)
+            .await
+            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Replay unavailable".to_string()))?;
+        let game_state = frames.get(frame).and_then(|record| serde_json::from_str(&record.game_state).ok())
+            .ok_or((StatusCode::NOT_FOUND, format!("Replay frame {frame} not found")))?;
+        match_data.game_state = game_state;
+        match_data.status = MatchStatus::Active;
+        match_data.in_progress = true;
+        match_data.outcome = None;
+    } else if frame.is_some() {
+        return Err((StatusCode::BAD_REQUEST, "Replay frames can only be picked once the match is over".to_string()));
+    }
+
+    let result = game_router::handle_game_move(&match_data, player_id, request.move_data)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    match_data.game_state = result.new_state;
+    if result.is_finished && match_data.transition_to(MatchStatus::Finished).is_ok() {
+        match_data.outcome = result.outcome;
+    }
+    Ok(SimulatedMoveResponse {
+        match_data: game_router::redact_match_for_player(&match_data, player_id),
+        description: result.description,
+    })
+}
+
+/// Casual matches against a bot, or finished ones being looked back at from a replay frame
+fn allows_simulation(match_data: &Match) -> bool {
+    match_data.status == MatchStatus::Finished || match_data.players.iter().any(|player| player.bot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::game_type::GameType;
+    use sqlx::SqlitePool;
+
+    use crate::capacity::Capacity;
+    use crate::matchmaking::RatingWindow;
+    use crate::ready_check::ReadyChecks;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str) -> i64 {
+        db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name).await.unwrap()
+    }
+
+    /// Two players in a fresh Tic-Tac-Toe match, returned as (match_id, first, second)
+    async fn start_match(db: &Database) -> (i64, i64, i64) {
+        let p1 = create_test_player(db, "player1").await;
+        let p2 = create_test_player(db, "player2").await;
+        game_logic::handle_join_matchmaking_logic(p1, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), db).await;
+        game_logic::handle_join_matchmaking_logic(p2, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), db).await;
+
+        let record = db.get_active_match_for_player(p1).await.unwrap();
+        let state: serde_json::Value = serde_json::from_str(&record.game_state).unwrap();
+        let first = if state["current_player"] == 1 { p1 } else { p2 };
+        let second = if first == p1 { p2 } else { p1 };
+        (record.id, first, second)
+    }
+
+    fn move_request(row: usize, col: usize) -> MakeMoveRequest {
+        MakeMoveRequest { move_data: serde_json::json!({ "row": row, "col": col }) }
+    }
+
+    #[tokio::test]
+    async fn test_move_over_http_notifies_opponent() {
+        let db = create_test_db().await;
+        let (match_id, first, second) = start_match(&db).await;
+
+        let (match_state, messages) = make_move_logic(&db, first, match_id, &EventBus::new(), move_request(1, 1)).await.unwrap();
+
+        assert_eq!(match_state.match_data.id, match_id);
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| m.player_id == second));
+        assert!(matches!(messages[0].message, ServerMessage::MoveApplied { .. }));
+        assert!(matches!(messages[1].message, ServerMessage::GameStateUpdate { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_illegal_move_over_http() {
+        let db = create_test_db().await;
+        let (match_id, first, second) = start_match(&db).await;
+
+        let (status, _) = make_move_logic(&db, second, match_id, &EventBus::new(), move_request(1, 1)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = make_move_logic(&db, first, match_id + 1, &EventBus::new(), move_request(1, 1)).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_poll_since_seq() {
+        let db = create_test_db().await;
+        let (match_id, first, second) = start_match(&db).await;
+
+        let initial = load_match_state(&db, second, match_id, None).await.unwrap().unwrap();
+        assert!(load_match_state(&db, second, match_id, Some(initial.seq)).await.unwrap().is_none());
+
+        make_move_logic(&db, first, match_id, &EventBus::new(), move_request(0, 0)).await.unwrap();
+
+        let updated = load_match_state(&db, second, match_id, Some(initial.seq)).await.unwrap().unwrap();
+        assert!(updated.seq > initial.seq);
+    }
+
+    #[tokio::test]
+    async fn test_simulated_move_is_not_saved() {
+        let db = create_test_db().await;
+        let (match_id, first, second) = start_match(&db).await;
+
+        // Previews would be an edge in a rated match against a person
+        let (status, _) = simulate_move_logic(&db, first, match_id, None, move_request(1, 1)).await.unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        sqlx::query("UPDATE players SET is_bot = 1 WHERE id = ?").bind(second).execute(db.pool()).await.unwrap();
+        let before = load_match_state(&db, first, match_id, None).await.unwrap().unwrap();
+
+        let simulated = simulate_move_logic(&db, first, match_id, None, move_request(1, 1)).await.unwrap();
+        assert_ne!(simulated.match_data.game_state["board"][4], before.match_data.game_state["board"][4]);
+        let (status, _) = simulate_move_logic(&db, second, match_id, None, move_request(0, 0)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let after = load_match_state(&db, first, match_id, None).await.unwrap().unwrap();
+        assert_eq!(after.seq, before.seq);
+        assert_eq!(after.match_data.game_state, before.match_data.game_state);
+    }
+
+    #[tokio::test]
+    async fn test_finished_match_is_simulated_from_a_replay_frame() {
+        let db = create_test_db().await;
+        let (match_id, first, second) = start_match(&db).await;
+        let initial = load_match_state(&db, first, match_id, None).await.unwrap().unwrap();
+        db.add_match_frame(match_id, None, None, &initial.match_data.game_state.to_string()).await.unwrap();
+        let (status, _) = simulate_move_logic(&db, first, match_id, Some(0), move_request(1, 1)).await.unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        make_move_logic(&db, first, match_id, &EventBus::new(), move_request(1, 1)).await.unwrap();
+        let record = db.get_match_by_id(match_id).await.unwrap();
+        db.update_match(match_id, &record.game_state, MatchStatus::Finished, None).await.unwrap();
+        let finished = load_match_state(&db, first, match_id, None).await.unwrap().unwrap();
+
+        let (status, _) = simulate_move_logic(&db, first, match_id, None, move_request(0, 0)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = simulate_move_logic(&db, first, match_id, Some(1), move_request(0, 0)).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = simulate_move_logic(&db, second, match_id, Some(0), move_request(0, 0)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // From the first frame the center is still free, whatever was played after it
+        let simulated = simulate_move_logic(&db, first, match_id, Some(0), move_request(0, 0)).await.unwrap();
+        assert_eq!(simulated.match_data.status, MatchStatus::Active);
+        assert_eq!(simulated.match_data.game_state["board"][4], initial.match_data.game_state["board"][4]);
+        assert_ne!(simulated.match_data.game_state["board"][0], initial.match_data.game_state["board"][0]);
+
+        let after = load_match_state(&db, first, match_id, None).await.unwrap().unwrap();
+        assert_eq!(after.seq, finished.seq);
+        assert_eq!(after.match_data.game_state, finished.match_data.game_state);
+    }
+
+    #[tokio::test]
+    async fn test_poll_requires_participant() {
+        let db = create_test_db().await;
+        let (match_id, _, _) = start_match(&db).await;
+        let outsider = create_test_player(&db, "outsider").await;
+
+        assert_eq!(load_match_state(&db, outsider, match_id, None).await.unwrap_err(), StatusCode::NOT_FOUND);
+    }
+}
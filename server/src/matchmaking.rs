@@ -0,0 +1,311 @@
+use battld_common::games::game_type::GameType;
+use battld_common::games::matches::MatchStatus;
+use battld_common::ServerMessage;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::bots;
+use crate::capacity::{self, Capacity};
+use crate::database::{Database, WaitingPlayer};
+use crate::events::EventBus;
+use crate::game_logic::{self, OutgoingMessage};
+use crate::game_router;
+use crate::ready_check::{self, ReadyChecks};
+use crate::websocket::SharedRegistry;
+
+const DEFAULT_INITIAL_WINDOW: f64 = 100.0;
+const DEFAULT_GROWTH_PER_SEC: f64 = 10.0;
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How far apart in rating two players may be to be paired, widening the longer someone waits
+#[derive(Debug, Clone, PartialEq)]
+pub struct RatingWindow {
+    pub initial: f64,
+    pub growth_per_sec: f64,
+}
+
+impl Default for RatingWindow {
+    fn default() -> Self {
+        Self { initial: DEFAULT_INITIAL_WINDOW, growth_per_sec: DEFAULT_GROWTH_PER_SEC }
+    }
+}
+
+impl RatingWindow {
+    /// Read from MATCHMAKING_RATING_WINDOW and MATCHMAKING_WINDOW_GROWTH (points per second waited)
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().and_then(|value| value.trim().parse::<f64>().ok()).filter(|value| *value >= 0.0);
+        let default = Self::default();
+        Self {
+            initial: var("MATCHMAKING_RATING_WINDOW").unwrap_or(default.initial),
+            growth_per_sec: var("MATCHMAKING_WINDOW_GROWTH").unwrap_or(default.growth_per_sec),
+        }
+    }
+
+    /// Widest rating gap accepted after waiting `waited_secs`
+    pub fn width(&self, waited_secs: i64) -> f64 {
+        self.initial + self.growth_per_sec * waited_secs.max(0) as f64
+    }
+}
+
+/// Closer in rating to `rating` first, the longest waiting on ties
+fn by_closeness(rating: f64, a: &WaitingPlayer, b: &WaitingPlayer) -> std::cmp::Ordering {
+    (a.rating - rating).abs().total_cmp(&(b.rating - rating).abs())
+        .then(a.queued_at.cmp(&b.queued_at))
+        .then(a.match_id.cmp(&b.match_id))
+}
+
+/// Closest in rating to `rating`, the longest waiting on ties
+fn closest<'a>(rating: f64, candidates: impl Iterator<Item = &'a WaitingPlayer>) -> Option<&'a WaitingPlayer> {
+    candidates.min_by(|a, b| by_closeness(rating, a, b))
+}
+
+/// Players a match of the queued game type takes, two for games this server doesn't know
+fn seats_for(game_type: &str) -> usize {
+    serde_json::from_str::<GameType>(game_type).map(|game_type| game_router::seat_count(&game_type)).unwrap_or(2)
+}
+
+/// The waiting player closest in rating whose window already reaches `rating`
+pub fn pick_opponent<'a>(
+    rating: f64,
+    candidates: impl IntoIterator<Item = &'a WaitingPlayer>,
+    window: &RatingWindow,
+    now: i64,
+) -> Option<&'a WaitingPlayer> {
+    closest(
+        rating,
+        candidates
+            .into_iter()
+            .filter(|waiting| (waiting.rating - rating).abs() <= window.width(now - waiting.queued_at)),
+    )
+}
+
+/// Tables of waiting players whose rating gap the longest wait among them now covers, longest waiting first
+/// `seats` is how many players a match of a game type takes, two for every game so far
+/// The longest waiting players pick first, the closest in rating, and each one is seated at most once
+pub fn group_waiting(
+    pool: &[WaitingPlayer],
+    window: &RatingWindow,
+    now: i64,
+    seats: impl Fn(&str) -> usize,
+) -> Vec<Vec<WaitingPlayer>> {
+    let mut queue: Vec<&WaitingPlayer> = pool.iter().collect();
+    queue.sort_by_key(|waiting| (waiting.queued_at, waiting.match_id));
+
+    let mut groups = vec![];
+    while !queue.is_empty() {
+        let first = queue.remove(0);
+        let reach = window.width(now - first.queued_at);
+        let mut candidates: Vec<&WaitingPlayer> = queue
+            .iter()
+            .copied()
+            .filter(|other| {
+                other.player_id != first.player_id
+                    && other.game_type == first.game_type
+                    && other.game_options == first.game_options
+                    && other.series_length == first.series_length
+                    && (other.rating - first.rating).abs() <= reach
+            })
+            .collect();
+        let wanted = seats(&first.game_type).max(2) - 1;
+        if candidates.len() < wanted {
+            continue;
+        }
+        candidates.sort_by(|a, b| by_closeness(first.rating, a, b));
+        candidates.truncate(wanted);
+        queue.retain(|other| candidates.iter().all(|chosen| chosen.match_id != other.match_id));
+        groups.push(std::iter::once(first).chain(candidates).cloned().collect());
+    }
+    groups
+}
+
+/// Seat players left waiting together once their rating windows meet - returns messages to send
+/// The others leave their own waiting matches and join the one of the player who waited longest
+pub async fn sweep_logic(
+    window: &RatingWindow,
+    now: i64,
+    capacity: &Capacity,
+    ready_checks: &ReadyChecks,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let pool: Vec<WaitingPlayer> = db
+        .get_matchmaking_pool()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|waiting| !ready_checks.is_pending(waiting.player_id))
+        .collect();
+
+    let mut messages = vec![];
+    for group in group_waiting(&pool, window, now, seats_for) {
+        let (waiting, joining) = (&group[0], &group[1..]);
+        let Ok(game_type) = serde_json::from_str::<GameType>(&waiting.game_type) else { continue };
+        let options: serde_json::Value = serde_json::from_str(&waiting.game_options).unwrap_or_default();
+        let seats: Vec<i64> = group.iter().map(|player| player.player_id).collect();
+        let load = capacity::load_matches(db).await.unwrap_or_default();
+        if capacity.check_new_match(&seats, &game_type, &load).is_err() {
+            continue;
+        }
+        let mut left = vec![];
+        for other in joining {
+            if !matches!(db.transition_match(other.match_id, MatchStatus::Waiting, MatchStatus::Aborted).await, Ok(true)) {
+                break;
+            }
+            left.push(other);
+        }
+        if left.len() < joining.len() {
+            requeue(&left, db).await;
+            continue;
+        }
+
+        let names: Vec<String> = seats.iter().map(|id| id.to_string()).collect();
+        println!("Matching waiting players {} for game type: {game_type}", names.join(" and "));
+        let started = match joining {
+            [opponent] if ready_checks.is_enabled() => {
+                let sent = ready_check::start_ready_check_logic(
+                    waiting.match_id, waiting.player_id, opponent.player_id, game_type, options, ready_checks, db,
+                ).await;
+                sent.iter().any(|m| matches!(m.message, ServerMessage::ReadyCheck { .. })).then_some(sent)
+            }
+            _ => game_logic::start_matched_game(waiting.match_id, &seats, &game_type, &options, events, db).await,
+        };
+        match started {
+            Some(sent) => messages.extend(sent),
+            // The other match was taken meanwhile, keep the others in the queue
+            None => requeue(&left, db).await,
+        }
+    }
+    messages
+}
+
+/// Queue players again after the match they were to join fell through
+async fn requeue(players: &[&WaitingPlayer], db: &Database) {
+    for player in players {
+        let _ = db.create_waiting_match(player.player_id, &player.game_type, &player.game_options, player.series_length).await;
+    }
+}
+
+/// Pair waiting players again every few seconds, as their rating windows widen,
+/// and seat bots against those who waited too long for anyone
+pub fn spawn_sweep(
+    capacity: Arc<Capacity>,
+    ready_checks: Arc<ReadyChecks>,
+    db: Arc<Database>,
+    registry: SharedRegistry,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let settings = registry.settings().load();
+            let now = battld_common::time() as i64;
+            let messages = sweep_logic(&settings.matchmaking, now, &capacity, &ready_checks, registry.events(), &db).await;
+            registry.send_messages(messages).await;
+            if let Some(after) = settings.bot_after {
+                let messages = bots::backfill_logic(after, now, &capacity, &ready_checks, registry.events(), &db).await;
+                registry.send_messages(messages).await;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    fn waiting(match_id: i64, rating: f64, queued_at: i64) -> WaitingPlayer {
+        WaitingPlayer {
+            match_id,
+            player_id: match_id * 10,
+            game_type: "\"TicTacToe\"".to_string(),
+            game_options: "null".to_string(),
+            rating,
+            queued_at,
+            series_length: None,
+        }
+    }
+
+    #[test]
+    fn test_window_widens_while_waiting() {
+        let window = RatingWindow { initial: 100.0, growth_per_sec: 10.0 };
+        assert_eq!(window.width(0), 100.0);
+        assert_eq!(window.width(30), 400.0);
+        assert_eq!(window.width(-5), 100.0);
+    }
+
+    #[test]
+    fn test_pick_prefers_the_closest_rating_within_the_window() {
+        let window = RatingWindow { initial: 100.0, growth_per_sec: 10.0 };
+        let pool = vec![waiting(1, 1900.0, 1_000), waiting(2, 1560.0, 1_000), waiting(3, 1480.0, 1_000)];
+
+        assert_eq!(pick_opponent(1500.0, &pool, &window, 1_000).unwrap().match_id, 3);
+        assert!(pick_opponent(2200.0, &pool, &window, 1_000).is_none(), "Nobody within 100 points yet");
+        assert_eq!(pick_opponent(2200.0, &pool, &window, 1_030).unwrap().match_id, 1, "The window reached 400 points");
+    }
+
+    #[test]
+    fn test_pick_breaks_ties_by_waiting_time() {
+        let window = RatingWindow::default();
+        let pool = vec![waiting(1, 1550.0, 1_010), waiting(2, 1450.0, 1_000)];
+        assert_eq!(pick_opponent(1500.0, &pool, &window, 1_020).unwrap().match_id, 2);
+    }
+
+    #[test]
+    fn test_waiting_players_are_paired_once_the_window_covers_them() {
+        let window = RatingWindow { initial: 100.0, growth_per_sec: 10.0 };
+        let mut chess = waiting(4, 1210.0, 1_000);
+        chess.game_type = "\"Chess\"".to_string();
+        let pool = vec![waiting(1, 1200.0, 1_000), waiting(2, 1500.0, 1_000), waiting(3, 1380.0, 1_000), chess];
+        let pair_ids = |now| -> Vec<(i64, i64)> {
+            group_waiting(&pool, &window, now, |_| 2).iter().map(|group| (group[0].match_id, group[1].match_id)).collect()
+        };
+
+        assert!(pair_ids(1_000).is_empty());
+        assert_eq!(pair_ids(1_010), vec![(1, 3)], "Other game types and players still out of range wait");
+        assert_eq!(pair_ids(1_030), vec![(1, 3)], "Each player is paired once, the closest first");
+    }
+
+    #[test]
+    fn test_the_longest_wait_decides_the_window() {
+        let window = RatingWindow { initial: 100.0, growth_per_sec: 10.0 };
+        let pool = vec![waiting(2, 1500.0, 1_025), waiting(1, 1200.0, 1_000)];
+
+        let pairs = group_waiting(&pool, &window, 1_010, |_| 2);
+        assert!(pairs.is_empty());
+        let pairs = group_waiting(&pool, &window, 1_020, |_| 2);
+        assert_eq!((pairs[0][0].match_id, pairs[0][1].match_id), (1, 2));
+    }
+
+    #[test]
+    fn test_tables_wait_until_every_seat_is_taken() {
+        let window = RatingWindow { initial: 100.0, growth_per_sec: 10.0 };
+        let mut pool = vec![waiting(1, 1500.0, 1_000), waiting(2, 1520.0, 1_000), waiting(3, 1450.0, 1_000)];
+        assert!(group_waiting(&pool, &window, 1_000, |_| 4).is_empty());
+
+        pool.extend([waiting(4, 1490.0, 1_005), waiting(5, 1700.0, 1_000)]);
+        let groups = group_waiting(&pool, &window, 1_000, |_| 4);
+        let ids: Vec<i64> = groups[0].iter().map(|player| player.match_id).collect();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(ids, vec![1, 4, 2, 3], "The longest waiting player first, then the closest in rating");
+    }
+
+    #[tokio::test]
+    async fn test_sweep_starts_a_match_between_waiting_players() {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        let p1 = db.create_player("player1_hint", "player1_key", "player1").await.unwrap();
+        let p2 = db.create_player("player2_hint", "player2_key", "player2").await.unwrap();
+        let game_type = serde_json::to_string(&GameType::TicTacToe).unwrap();
+        let first = db.create_waiting_match(p1, &game_type, "null", None).await.unwrap();
+        let second = db.create_waiting_match(p2, &game_type, "null", None).await.unwrap();
+
+        let now = battld_common::time() as i64;
+        let messages = sweep_logic(&RatingWindow::default(), now, &Capacity::default(), &ReadyChecks::default(), &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| matches!(m.message, ServerMessage::MatchFound { .. })));
+        assert_eq!(db.get_match_by_id(first).await.unwrap().status, "active");
+        assert_eq!(db.get_match_by_id(second).await.unwrap().status, "aborted");
+        assert!(db.get_matchmaking_pool().await.unwrap().is_empty());
+    }
+}
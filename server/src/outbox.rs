@@ -0,0 +1,223 @@
+use battld_common::{games::matches::Match, ServerMessage};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+enum Queued {
+    Message(ServerMessage),
+    /// Stands in for whatever state is in the pending slot once the send task gets here,
+    /// unless the slot was flushed and refilled since
+    LatestState(u64),
+}
+
+#[derive(Default)]
+struct PendingState {
+    generation: u64,
+    match_data: Option<Match>,
+    /// Legal moves and turn timer of the pending state, going out and being replaced along with it
+    follow_ups: Vec<ServerMessage>,
+}
+
+/// Sending half of a player's connection
+/// A `GameStateUpdate` queued right behind another one for the same match replaces it, together with
+/// the legal moves and turn timer that followed it, so a connection that fell behind only gets the latest state
+#[derive(Clone)]
+pub struct Outbox {
+    tx: mpsc::UnboundedSender<Queued>,
+    pending: Arc<Mutex<PendingState>>,
+}
+
+pub struct OutboxReceiver {
+    rx: mpsc::UnboundedReceiver<Queued>,
+    pending: Arc<Mutex<PendingState>>,
+    /// Follow-ups of the state just returned, sent before anything else
+    ready: VecDeque<ServerMessage>,
+}
+
+/// Match whose latest state a message goes with
+fn follow_up_of(message: &ServerMessage) -> Option<i64> {
+    match message {
+        ServerMessage::LegalMoves { match_id, .. } | ServerMessage::TurnTimer { match_id, .. } => Some(*match_id),
+        _ => None,
+    }
+}
+
+pub fn channel() -> (Outbox, OutboxReceiver) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let pending = Arc::new(Mutex::new(PendingState::default()));
+    (Outbox { tx, pending: pending.clone() }, OutboxReceiver { rx, pending, ready: VecDeque::new() })
+}
+
+impl Outbox {
+    /// The pending slot only holds a state while its `LatestState` is the last thing queued
+    pub fn send(&self, message: ServerMessage) -> Result<(), String> {
+        let mut pending = self.pending.lock().unwrap();
+        match message {
+            ServerMessage::GameStateUpdate { match_data } => {
+                if pending.match_data.as_ref().is_some_and(|queued| queued.id == match_data.id) {
+                    pending.match_data = Some(match_data);
+                    pending.follow_ups.clear();
+                    return Ok(());
+                }
+                self.flush(&mut pending)?;
+                pending.generation += 1;
+                pending.match_data = Some(match_data);
+                self.enqueue(Queued::LatestState(pending.generation))
+            }
+            message if follow_up_of(&message).is_some_and(|match_id| pending.match_data.as_ref().is_some_and(|queued| queued.id == match_id)) => {
+                pending.follow_ups.retain(|queued| std::mem::discriminant(queued) != std::mem::discriminant(&message));
+                pending.follow_ups.push(message);
+                Ok(())
+            }
+            message => {
+                self.flush(&mut pending)?;
+                self.enqueue(Queued::Message(message))
+            }
+        }
+    }
+
+    /// Queue the pending state as a regular message, anything sent next goes after it
+    fn flush(&self, pending: &mut PendingState) -> Result<(), String> {
+        if let Some(match_data) = pending.match_data.take() {
+            self.enqueue(Queued::Message(ServerMessage::GameStateUpdate { match_data }))?;
+        }
+        for message in std::mem::take(&mut pending.follow_ups) {
+            self.enqueue(Queued::Message(message))?;
+        }
+        Ok(())
+    }
+
+    fn enqueue(&self, queued: Queued) -> Result<(), String> {
+        self.tx.send(queued).map_err(|_| "Connection closed".to_string())
+    }
+}
+
+impl OutboxReceiver {
+    /// Next message to write to the socket, None once every `Outbox` is gone
+    pub async fn recv(&mut self) -> Option<ServerMessage> {
+        if let Some(message) = self.ready.pop_front() {
+            return Some(message);
+        }
+        loop {
+            match self.rx.recv().await? {
+                Queued::Message(message) => return Some(message),
+                Queued::LatestState(generation) => {
+                    let mut pending = self.pending.lock().unwrap();
+                    if pending.generation != generation {
+                        continue;
+                    }
+                    if let Some(match_data) = pending.match_data.take() {
+                        self.ready.extend(std::mem::take(&mut pending.follow_ups));
+                        return Some(ServerMessage::GameStateUpdate { match_data });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::{game_type::GameType, matches::MatchStatus};
+
+    fn state(match_id: i64, round: i64) -> ServerMessage {
+        ServerMessage::GameStateUpdate {
+            match_data: Match {
+                id: match_id,
+                player1_id: 1,
+                player2_id: 2,
+                seats: vec![],
+                in_progress: true,
+                status: MatchStatus::Active,
+                outcome: None,
+                game_type: GameType::RockPaperScissors,
+                game_state: serde_json::json!({ "round": round }),
+                players: vec![],
+                ephemeral: false,
+                draw_offered_by: None,
+                seq: 0,
+            },
+        }
+    }
+
+    fn round(message: Option<ServerMessage>) -> i64 {
+        match message {
+            Some(ServerMessage::GameStateUpdate { match_data }) => match_data.game_state["round"].as_i64().unwrap(),
+            other => panic!("Expected GameStateUpdate, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_back_to_back_states_coalesce() {
+        let (outbox, mut receiver) = channel();
+        outbox.send(state(1, 1)).unwrap();
+        outbox.send(state(1, 2)).unwrap();
+        outbox.send(state(1, 3)).unwrap();
+        outbox.send(ServerMessage::Pong).unwrap();
+
+        assert_eq!(round(receiver.recv().await), 3);
+        assert!(matches!(receiver.recv().await, Some(ServerMessage::Pong)));
+        drop(outbox);
+        assert!(receiver.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_keeps_order_around_other_messages() {
+        let (outbox, mut receiver) = channel();
+        outbox.send(state(1, 1)).unwrap();
+        outbox.send(ServerMessage::Pong).unwrap();
+        outbox.send(state(1, 2)).unwrap();
+        outbox.send(state(2, 1)).unwrap();
+
+        assert_eq!(round(receiver.recv().await), 1);
+        assert!(matches!(receiver.recv().await, Some(ServerMessage::Pong)));
+        assert_eq!(round(receiver.recv().await), 2);
+        assert_eq!(round(receiver.recv().await), 1);
+        drop(outbox);
+        assert!(receiver.recv().await.is_none());
+    }
+
+    fn legal_moves(match_id: i64, count: usize) -> ServerMessage {
+        ServerMessage::LegalMoves { match_id, moves: vec![serde_json::json!({}); count] }
+    }
+
+    fn move_count(message: Option<ServerMessage>) -> usize {
+        match message {
+            Some(ServerMessage::LegalMoves { moves, .. }) => moves.len(),
+            other => panic!("Expected LegalMoves, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_states_coalesce_with_their_legal_moves_and_timer() {
+        let (outbox, mut receiver) = channel();
+        outbox.send(state(1, 1)).unwrap();
+        outbox.send(legal_moves(1, 3)).unwrap();
+        outbox.send(ServerMessage::TurnTimer { match_id: 1, players: vec![1], expires_in: 30 }).unwrap();
+        outbox.send(state(1, 2)).unwrap();
+        outbox.send(legal_moves(1, 2)).unwrap();
+        outbox.send(legal_moves(2, 5)).unwrap();
+
+        assert_eq!(round(receiver.recv().await), 2);
+        assert_eq!(move_count(receiver.recv().await), 2);
+        assert_eq!(move_count(receiver.recv().await), 5);
+
+        // Once the state went out its legal moves simply follow it
+        outbox.send(state(1, 3)).unwrap();
+        assert_eq!(round(receiver.recv().await), 3);
+        outbox.send(legal_moves(1, 1)).unwrap();
+        outbox.send(ServerMessage::Pong).unwrap();
+        assert_eq!(move_count(receiver.recv().await), 1);
+        assert!(matches!(receiver.recv().await, Some(ServerMessage::Pong)));
+    }
+
+    #[tokio::test]
+    async fn test_sent_state_is_not_replaced() {
+        let (outbox, mut receiver) = channel();
+        outbox.send(state(1, 1)).unwrap();
+        assert_eq!(round(receiver.recv().await), 1);
+        outbox.send(state(1, 2)).unwrap();
+        assert_eq!(round(receiver.recv().await), 2);
+    }
+}
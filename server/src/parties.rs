@@ -0,0 +1,446 @@
+use axum::{extract::State, http::{HeaderMap, StatusCode}, Json};
+use battld_common::{games::game_type::GameType, ErrorCode, PartyMember, PartyStatus, ServerMessage};
+use std::{collections::{HashMap, HashSet}, sync::Mutex};
+
+use crate::capacity::{self, Capacity};
+use crate::database::{self, Database};
+use crate::events::{EventBus, MatchEvent};
+use crate::game_logic::{self, OutgoingMessage};
+use crate::{auth, game_router, AppState};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PartyError {
+    SelfInvite,
+    AlreadyInParty,
+    InviteNotFound,
+}
+
+impl PartyError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            PartyError::SelfInvite => ErrorCode::SelfInvite,
+            PartyError::AlreadyInParty => ErrorCode::AlreadyInParty,
+            PartyError::InviteNotFound => ErrorCode::InviteNotFound,
+        }
+    }
+}
+
+#[derive(Default)]
+struct PartyState {
+    partners: HashMap<i64, i64>,
+    /// Invitee -> players who invited them
+    invites: HashMap<i64, HashSet<i64>>,
+}
+
+/// Pairs of players who agreed to queue together
+/// Parties live in memory and outlast single matches, so a pair can keep rematching
+/// Games are all two-player for now, so a party is always matched against itself
+#[derive(Default)]
+pub struct PartyRegistry {
+    state: Mutex<PartyState>,
+}
+
+impl PartyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn partner(&self, player_id: i64) -> Option<i64> {
+        self.state.lock().unwrap().partners.get(&player_id).copied()
+    }
+
+    /// Players who invited `player_id` and are still waiting for an answer
+    pub fn invites_for(&self, player_id: i64) -> Vec<i64> {
+        let state = self.state.lock().unwrap();
+        let mut inviters: Vec<i64> = state.invites.get(&player_id).into_iter().flatten().copied().collect();
+        inviters.sort_unstable();
+        inviters
+    }
+
+    pub fn invite(&self, inviter_id: i64, invitee_id: i64) -> Result<(), PartyError> {
+        if inviter_id == invitee_id {
+            return Err(PartyError::SelfInvite);
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.partners.contains_key(&inviter_id) || state.partners.contains_key(&invitee_id) {
+            return Err(PartyError::AlreadyInParty);
+        }
+        state.invites.entry(invitee_id).or_default().insert(inviter_id);
+        Ok(())
+    }
+
+    /// Form the party, dropping any other invite involving the two players
+    pub fn accept(&self, invitee_id: i64, inviter_id: i64) -> Result<(), PartyError> {
+        let mut state = self.state.lock().unwrap();
+        let invited = state.invites.get(&invitee_id).is_some_and(|inviters| inviters.contains(&inviter_id));
+        if !invited {
+            return Err(PartyError::InviteNotFound);
+        }
+        if state.partners.contains_key(&inviter_id) || state.partners.contains_key(&invitee_id) {
+            return Err(PartyError::AlreadyInParty);
+        }
+
+        state.invites.remove(&invitee_id);
+        state.invites.remove(&inviter_id);
+        for inviters in state.invites.values_mut() {
+            inviters.remove(&invitee_id);
+            inviters.remove(&inviter_id);
+        }
+        state.invites.retain(|_, inviters| !inviters.is_empty());
+
+        state.partners.insert(invitee_id, inviter_id);
+        state.partners.insert(inviter_id, invitee_id);
+        Ok(())
+    }
+
+    pub fn decline(&self, invitee_id: i64, inviter_id: i64) -> Result<(), PartyError> {
+        let mut state = self.state.lock().unwrap();
+        let removed = state.invites.get_mut(&invitee_id).is_some_and(|inviters| inviters.remove(&inviter_id));
+        if !removed {
+            return Err(PartyError::InviteNotFound);
+        }
+        state.invites.retain(|_, inviters| !inviters.is_empty());
+        Ok(())
+    }
+
+    /// Returns the partner left behind, if any
+    pub fn leave(&self, player_id: i64) -> Option<i64> {
+        let mut state = self.state.lock().unwrap();
+        let partner_id = state.partners.remove(&player_id)?;
+        state.partners.remove(&partner_id);
+        Some(partner_id)
+    }
+}
+
+fn error(player_id: i64, code: ErrorCode, params: &[(&str, &str)]) -> Vec<OutgoingMessage> {
+    vec![OutgoingMessage {
+        player_id,
+        message: ServerMessage::error(code, params),
+    }]
+}
+
+async fn party_member(db: &Database, player_id: i64) -> Option<PartyMember> {
+    db.get_player_by_id(player_id).await.map(|player| PartyMember {
+        player_id,
+        name: player.name,
+    })
+}
+
+/// Handle a party invite - returns messages to send
+/// Invites live in memory, so only online players can be invited
+pub async fn handle_invite_to_party_logic(
+    inviter_id: i64,
+    invitee_id: i64,
+    invitee_online: bool,
+    parties: &PartyRegistry,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    if inviter_id == invitee_id {
+        return error(inviter_id, PartyError::SelfInvite.code(), &[]);
+    }
+    let (Some(inviter), Some(invitee)) = (party_member(db, inviter_id).await, party_member(db, invitee_id).await) else {
+        return error(inviter_id, ErrorCode::PlayerNotFound, &[]);
+    };
+    if !invitee_online {
+        return error(inviter_id, ErrorCode::PlayerOffline, &[("name", &invitee.name)]);
+    }
+    if let Err(e) = parties.invite(inviter_id, invitee_id) {
+        return error(inviter_id, e.code(), &[]);
+    }
+
+    println!("Player {inviter_id} invited player {invitee_id} to a party");
+    vec![OutgoingMessage {
+        player_id: invitee_id,
+        message: ServerMessage::PartyInvite { from: inviter },
+    }]
+}
+
+/// Handle an answer to a party invite - returns messages to send
+pub async fn handle_respond_to_party_invite_logic(
+    player_id: i64,
+    inviter_id: i64,
+    accept: bool,
+    parties: &PartyRegistry,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    if !accept {
+        if let Err(e) = parties.decline(player_id, inviter_id) {
+            return error(player_id, e.code(), &[]);
+        }
+        return vec![OutgoingMessage {
+            player_id: inviter_id,
+            message: ServerMessage::PartyInviteDeclined { player_id },
+        }];
+    }
+
+    let (Some(member), Some(inviter)) = (party_member(db, player_id).await, party_member(db, inviter_id).await) else {
+        return error(player_id, ErrorCode::PlayerNotFound, &[]);
+    };
+    if let Err(e) = parties.accept(player_id, inviter_id) {
+        return error(player_id, e.code(), &[]);
+    }
+
+    println!("Players {inviter_id} and {player_id} formed a party");
+    vec![
+        OutgoingMessage {
+            player_id,
+            message: ServerMessage::PartyFormed { partner: inviter },
+        },
+        OutgoingMessage {
+            player_id: inviter_id,
+            message: ServerMessage::PartyFormed { partner: member },
+        },
+    ]
+}
+
+/// Handle a player leaving their party - both members are told
+pub fn handle_leave_party_logic(player_id: i64, parties: &PartyRegistry) -> Vec<OutgoingMessage> {
+    let Some(partner_id) = parties.leave(player_id) else {
+        return error(player_id, ErrorCode::NotInParty, &[]);
+    };
+
+    println!("Player {player_id} left the party with player {partner_id}");
+    [player_id, partner_id]
+        .into_iter()
+        .map(|id| OutgoingMessage {
+            player_id: id,
+            message: ServerMessage::PartyDisbanded { player_id },
+        })
+        .collect()
+}
+
+/// Matchmaking for a party member - returns messages to send
+/// The first member to queue reserves a match only their partner can join,
+/// skipping the queue limit so rematches are never stuck behind strangers
+pub async fn handle_join_matchmaking_as_party_logic(
+    player_id: i64,
+    partner_id: i64,
+    game_type: GameType,
+    options: serde_json::Value,
+    capacity: &Capacity,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    if let Some(messages) = game_logic::queued_or_playing_logic(player_id, db).await {
+        return messages;
+    }
+
+    let options = match game_router::normalize_game_options(&game_type, &options) {
+        Ok(options) => options,
+        Err(e) => return vec![OutgoingMessage { player_id, message: e.to_options_message() }],
+    };
+    let game_type_json = serde_json::to_string(&game_type).unwrap();
+    let options_json = options.to_string();
+
+    let Some(reserved) = db.find_reserved_match(partner_id, player_id, &game_type_json, &options_json).await else {
+        if database::with_retry(|| db.create_reserved_match(player_id, partner_id, &game_type_json, &options_json)).await.is_err() {
+            return error(player_id, ErrorCode::MatchmakingFailed, &[]);
+        }
+        println!("Player {player_id} is waiting for party partner {partner_id} to play {game_type}");
+        return vec![
+            OutgoingMessage {
+                player_id,
+                message: ServerMessage::WaitingForOpponent,
+            },
+            OutgoingMessage {
+                player_id: partner_id,
+                message: ServerMessage::PartyQueued { game_type, options },
+            },
+        ];
+    };
+
+    let load = capacity::load_matches(db).await.unwrap_or_default();
+    if let Err(limit) = capacity.check_new_match(&[partner_id, player_id], &game_type, &load) {
+        println!("Party of {partner_id} and {player_id} turned away: {limit:?}");
+        return game_logic::matchmaking_rejected(player_id, capacity.full_rejection());
+    }
+
+    // Seats alternate across a series, so nobody is always X or White
+    let previous = db.get_last_match_between(partner_id, player_id).await;
+    let (player1_id, player2_id) = match &previous {
+        Some(previous) if previous.player1_id == partner_id => (player_id, partner_id),
+        _ => (partner_id, player_id),
+    };
+    let rematch_of = previous.map(|previous| previous.id);
+
+    let game_state_json = game_router::initialize_game_state(&game_type, &options);
+    if database::with_retry(|| db.start_rematch(reserved.id, player1_id, player2_id, &game_state_json, rematch_of)).await.is_err() {
+        return error(player_id, ErrorCode::MatchmakingFailed, &[]);
+    }
+    let Some(match_info) = db.get_match_by_id(reserved.id).await.and_then(|record| record.to_match()) else {
+        return error(player_id, ErrorCode::MatchUnavailable, &[]);
+    };
+
+    println!("Party of {partner_id} and {player_id} started match {}", match_info.id);
+    events.publish(MatchEvent::MatchStarted { match_data: match_info.clone() });
+    [partner_id, player_id]
+        .into_iter()
+        .map(|id| OutgoingMessage {
+            player_id: id,
+            message: ServerMessage::MatchFound {
+                match_data: game_router::redact_match_for_player(&match_info, id),
+            },
+        })
+        .collect()
+}
+
+/// Party and pending invites of the authenticated player
+pub async fn get_party(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<PartyStatus>, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+
+    let partner = match state.parties.partner(player_id) {
+        Some(partner_id) => party_member(&state.db, partner_id).await,
+        None => None,
+    };
+    let mut invites = Vec::new();
+    for inviter_id in state.parties.invites_for(player_id) {
+        if let Some(member) = party_member(&state.db, inviter_id).await {
+            invites.push(member);
+        }
+    }
+
+    Ok(Json(PartyStatus { partner, invites }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::matches::MatchStatus;
+    use crate::matchmaking::RatingWindow;
+    use crate::ready_check::ReadyChecks;
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str) -> i64 {
+        db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name).await.unwrap()
+    }
+
+    #[test]
+    fn test_party_lifecycle() {
+        let parties = PartyRegistry::new();
+
+        assert_eq!(parties.invite(1, 1), Err(PartyError::SelfInvite));
+        parties.invite(1, 2).unwrap();
+        parties.invite(3, 2).unwrap();
+        assert_eq!(parties.invites_for(2), vec![1, 3]);
+
+        parties.accept(2, 1).unwrap();
+        assert_eq!(parties.partner(1), Some(2));
+        assert_eq!(parties.partner(2), Some(1));
+        assert!(parties.invites_for(2).is_empty());
+        assert_eq!(parties.invite(3, 1), Err(PartyError::AlreadyInParty));
+
+        assert_eq!(parties.leave(2), Some(1));
+        assert_eq!(parties.partner(1), None);
+        assert_eq!(parties.leave(2), None);
+    }
+
+    #[test]
+    fn test_decline_invite() {
+        let parties = PartyRegistry::new();
+        parties.invite(1, 2).unwrap();
+
+        parties.decline(2, 1).unwrap();
+        assert_eq!(parties.decline(2, 1), Err(PartyError::InviteNotFound));
+        assert_eq!(parties.accept(2, 1), Err(PartyError::InviteNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_invite_requires_online_player() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "alice").await;
+        let p2 = create_test_player(&db, "bob").await;
+        let parties = PartyRegistry::new();
+
+        let messages = handle_invite_to_party_logic(p1, p2, false, &parties, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { .. }));
+
+        let messages = handle_invite_to_party_logic(p1, p2, true, &parties, &db).await;
+        assert_eq!(messages[0].player_id, p2);
+        match &messages[0].message {
+            ServerMessage::PartyInvite { from } => assert_eq!(from.name, "alice"),
+            other => panic!("Expected PartyInvite, got {other:?}"),
+        }
+
+        let messages = handle_respond_to_party_invite_logic(p2, p1, true, &parties, &db).await;
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| matches!(m.message, ServerMessage::PartyFormed { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_party_members_are_matched_together() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "alice").await;
+        let p2 = create_test_player(&db, "bob").await;
+        let stranger = create_test_player(&db, "carol").await;
+
+        let messages = handle_join_matchmaking_as_party_logic(
+            p1, p2, GameType::TicTacToe, serde_json::Value::Null, &Capacity::default(), &EventBus::new(), &db,
+        ).await;
+        assert!(matches!(messages[0].message, ServerMessage::WaitingForOpponent));
+        assert_eq!(messages[1].player_id, p2);
+        assert!(matches!(messages[1].message, ServerMessage::PartyQueued { .. }));
+
+        // A stranger never gets the reserved match
+        let messages = game_logic::handle_join_matchmaking_logic(
+            stranger, GameType::TicTacToe, serde_json::Value::Null, None, &Capacity::default(), &RatingWindow::default(), &ReadyChecks::default(), &EventBus::new(), &db,
+        ).await;
+        assert!(matches!(messages[0].message, ServerMessage::WaitingForOpponent));
+
+        let messages = handle_join_matchmaking_as_party_logic(
+            p2, p1, GameType::TicTacToe, serde_json::Value::Null, &Capacity::default(), &EventBus::new(), &db,
+        ).await;
+        assert_eq!(messages.len(), 2);
+        match &messages[0].message {
+            ServerMessage::MatchFound { match_data } => {
+                assert_eq!(match_data.player1_id, p1);
+                assert_eq!(match_data.player2_id, p2);
+            }
+            other => panic!("Expected MatchFound, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rematches_alternate_seats() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "alice").await;
+        let p2 = create_test_player(&db, "bob").await;
+
+        let mut previous = None;
+        for (first_to_queue, second_to_queue, expected_player1) in [(p1, p2, p1), (p1, p2, p2), (p2, p1, p1)] {
+            handle_join_matchmaking_as_party_logic(
+                first_to_queue, second_to_queue, GameType::Chess, serde_json::Value::Null, &Capacity::default(), &EventBus::new(), &db,
+            ).await;
+            handle_join_matchmaking_as_party_logic(
+                second_to_queue, first_to_queue, GameType::Chess, serde_json::Value::Null, &Capacity::default(), &EventBus::new(), &db,
+            ).await;
+
+            let record = db.get_active_match_for_player(p1).await.unwrap();
+            assert_eq!(record.player1_id, expected_player1);
+            assert_eq!(record.rematch_of, previous);
+
+            db.update_match(record.id, &record.game_state, MatchStatus::Finished, None).await.unwrap();
+            previous = Some(record.id);
+        }
+    }
+
+    #[test]
+    fn test_leave_party_notifies_both() {
+        let parties = PartyRegistry::new();
+        parties.invite(1, 2).unwrap();
+        parties.accept(2, 1).unwrap();
+
+        let messages = handle_leave_party_logic(1, &parties);
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| matches!(m.message, ServerMessage::PartyDisbanded { player_id: 1 })));
+    }
+}
@@ -1,8 +1,9 @@
 use axum::{
-    extract::{State, Json},
+    extract::{State, Json, Query},
     http::{StatusCode, HeaderMap},
 };
 use battld_common::{games::matches::Match, *};
+use serde::Deserialize;
 
 use crate::{repository, auth, AppState};
 
@@ -53,6 +54,42 @@ pub async fn get_player_by_id(
     }
 }
 
+#[derive(Deserialize)]
+pub struct PlayerSearch {
+    pub name: String,
+}
+
+/// Players with exactly the name searched for, ignoring case, so clients can refer to players by name
+pub async fn search_players(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(search): Query<PlayerSearch>,
+) -> Result<Json<Vec<Player>>, StatusCode> {
+    auth::authenticate_request(&state.session_cache, &headers).await?;
+    repository::find_players(&state.db, &search.name)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// History of a player's setting changes, newest first, admins only
+pub async fn get_setting_changes(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::extract::Path(id): axum::extract::Path<i64>
+) -> Result<Json<Vec<SettingChange>>, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+    if !state.capacity.is_admin(player_id) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    state.db.get_player_by_id(id).await.ok_or(StatusCode::NOT_FOUND)?;
+
+    state.db.get_setting_changes(id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
 pub async fn get_active_matches(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -83,3 +120,93 @@ pub async fn get_active_matches(
     Ok(Json(vec![]))
 }
 
+/// Add a player to the caller's friends
+pub async fn add_friend(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::extract::Path(friend_id): axum::extract::Path<i64>
+) -> Result<StatusCode, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+    if friend_id == player_id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    state.db.get_player_by_id(friend_id).await.ok_or(StatusCode::NOT_FOUND)?;
+
+    state.db.add_friend(player_id, friend_id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Remove a player from the caller's friends
+pub async fn remove_friend(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::extract::Path(friend_id): axum::extract::Path<i64>
+) -> Result<StatusCode, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+
+    state.db.remove_friend(player_id, friend_id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Time zones run from UTC-12:00 to UTC+14:00
+const UTC_OFFSET_MINUTES: std::ops::RangeInclusive<i32> = -12 * 60..=14 * 60;
+
+pub async fn get_time_preferences(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<TimePreferences>, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+
+    state.db.get_time_preferences(player_id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn set_time_preferences(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(preferences): Json<TimePreferences>,
+) -> Result<StatusCode, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+    if !UTC_OFFSET_MINUTES.contains(&preferences.utc_offset_minutes) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    state.db.set_time_preferences(player_id, preferences, player_id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn get_quiet_hours(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Option<QuietHours>>, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+
+    state.db.get_quiet_hours(player_id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn set_quiet_hours(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(quiet_hours): Json<Option<QuietHours>>,
+) -> Result<StatusCode, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+    if quiet_hours.as_ref().is_some_and(|quiet_hours| !quiet_hours.is_valid()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    state.db.set_quiet_hours(player_id, quiet_hours.as_ref(), player_id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
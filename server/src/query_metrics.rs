@@ -0,0 +1,354 @@
+use axum::{extract::State, http::{HeaderMap, StatusCode}, Json};
+use futures::{future::BoxFuture, stream::BoxStream, FutureExt, StreamExt};
+use serde::Serialize;
+use sqlx::{sqlite::SqliteQueryResult, Describe, Either, Execute, Executor, Sqlite, SqlitePool};
+use sqlx::sqlite::{SqliteRow, SqliteStatement, SqliteTypeInfo};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{auth, AppState};
+
+const DEFAULT_SLOW_QUERY_MS: u64 = 100;
+/// Slow queries kept for the admin endpoint, the oldest are dropped first
+const RECENT_SLOW_QUERIES: usize = 50;
+/// Upper bounds of the latency histogram buckets, anything slower goes in a last open-ended one
+const BUCKETS_MS: [u64; 10] = [1, 2, 5, 10, 25, 50, 100, 250, 500, 1000];
+/// Longest query text logged or listed
+const MAX_SQL_LEN: usize = 300;
+
+#[derive(Debug, Default)]
+struct Family {
+    count: u64,
+    total: Duration,
+    max: Duration,
+    /// Per bucket of `BUCKETS_MS`, then the open-ended one
+    buckets: [u64; BUCKETS_MS.len() + 1],
+}
+
+#[derive(Debug, Default)]
+struct Recorded {
+    families: BTreeMap<String, Family>,
+    slow: VecDeque<SlowQuery>,
+}
+
+/// Latency of every query run through a `MeteredPool`, grouped by statement and table
+#[derive(Debug, Clone)]
+pub struct QueryMetrics {
+    slow_threshold: Duration,
+    recorded: Arc<Mutex<Recorded>>,
+}
+
+impl Default for QueryMetrics {
+    fn default() -> Self {
+        Self::with_threshold(Duration::from_millis(DEFAULT_SLOW_QUERY_MS))
+    }
+}
+
+/// A query that took longer than the threshold, with its parameters left out
+#[derive(Debug, Clone, Serialize)]
+pub struct SlowQuery {
+    pub family: String,
+    pub sql: String,
+    pub parameters: usize,
+    pub elapsed_ms: f64,
+    pub at: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencyBucket {
+    /// None for the bucket of everything slower than the last bound
+    pub le_ms: Option<u64>,
+    /// Queries at least as fast as `le_ms`, counting those of the faster buckets
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueryFamilyStats {
+    pub family: String,
+    pub count: u64,
+    pub total_ms: f64,
+    pub max_ms: f64,
+    pub buckets: Vec<LatencyBucket>,
+}
+
+impl QueryMetrics {
+    /// Queries slower than `SLOW_QUERY_MS` milliseconds are logged, 100 by default
+    pub fn from_env() -> Self {
+        let slow_query_ms = std::env::var("SLOW_QUERY_MS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_SLOW_QUERY_MS);
+        Self::with_threshold(Duration::from_millis(slow_query_ms))
+    }
+
+    pub fn with_threshold(slow_threshold: Duration) -> Self {
+        Self { slow_threshold, recorded: Default::default() }
+    }
+
+    fn record(&self, sql: &str, elapsed: Duration) {
+        let family = family(sql);
+        let mut recorded = self.recorded.lock().unwrap();
+        let stats = recorded.families.entry(family.clone()).or_default();
+        stats.count += 1;
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+        let bucket = BUCKETS_MS.iter().position(|bound| elapsed <= Duration::from_millis(*bound)).unwrap_or(BUCKETS_MS.len());
+        stats.buckets[bucket] += 1;
+
+        if elapsed < self.slow_threshold {
+            return;
+        }
+        let slow = SlowQuery {
+            family,
+            sql: redact(sql),
+            parameters: sql.matches('?').count(),
+            elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+            at: battld_common::time() as i64,
+        };
+        println!("DB: Slow query ({:.1} ms, {} parameters redacted): {}", slow.elapsed_ms, slow.parameters, slow.sql);
+        if recorded.slow.len() == RECENT_SLOW_QUERIES {
+            recorded.slow.pop_front();
+        }
+        recorded.slow.push_back(slow);
+    }
+
+    /// Latency histogram of each query family, by family name
+    pub fn families(&self) -> Vec<QueryFamilyStats> {
+        self.recorded
+            .lock()
+            .unwrap()
+            .families
+            .iter()
+            .map(|(family, stats)| QueryFamilyStats {
+                family: family.clone(),
+                count: stats.count,
+                total_ms: stats.total.as_secs_f64() * 1000.0,
+                max_ms: stats.max.as_secs_f64() * 1000.0,
+                buckets: stats.buckets
+                    .iter()
+                    .scan(0, |cumulative, count| {
+                        *cumulative += count;
+                        Some(*cumulative)
+                    })
+                    .enumerate()
+                    .map(|(i, count)| LatencyBucket { le_ms: BUCKETS_MS.get(i).copied(), count })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// The recent slow queries, slowest first
+    pub fn slowest(&self) -> Vec<SlowQuery> {
+        let mut slow: Vec<SlowQuery> = self.recorded.lock().unwrap().slow.iter().cloned().collect();
+        slow.sort_by(|a, b| b.elapsed_ms.total_cmp(&a.elapsed_ms));
+        slow
+    }
+}
+
+/// Statement and main table of a query, such as `SELECT matches`, so its variants are counted together
+fn family(sql: &str) -> String {
+    let mut depth = 0;
+    let mut tokens = sql.split_whitespace();
+    let verb = tokens.next().unwrap_or_default().to_uppercase();
+    let mut previous = verb.clone();
+    for token in tokens {
+        if depth == 0 && matches!(previous.as_str(), "FROM" | "INTO" | "UPDATE" | "TABLE") {
+            let table: String = token.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+            if !table.is_empty() {
+                return format!("{verb} {table}");
+            }
+        }
+        depth += token.matches('(').count() as i32 - token.matches(')').count() as i32;
+        previous = token.to_uppercase();
+    }
+    verb
+}
+
+/// Query text on one line, with any literal strings masked and cut to a readable length
+fn redact(sql: &str) -> String {
+    let mut redacted = String::new();
+    let mut in_literal = false;
+    for c in sql.split_whitespace().collect::<Vec<_>>().join(" ").chars() {
+        if c == '\'' {
+            if !in_literal {
+                redacted.push_str("'…'");
+            }
+            in_literal = !in_literal;
+        } else if !in_literal {
+            redacted.push(c);
+        }
+    }
+    match redacted.char_indices().nth(MAX_SQL_LEN) {
+        Some((end, _)) => format!("{}…", &redacted[..end]),
+        None => redacted,
+    }
+}
+
+/// Records a query's latency once its results are consumed or dropped
+struct Timing {
+    metrics: QueryMetrics,
+    sql: String,
+    started: Instant,
+}
+
+impl Drop for Timing {
+    fn drop(&mut self) {
+        self.metrics.record(&self.sql, self.started.elapsed());
+    }
+}
+
+/// Connection pool timing every query run directly on it, transactions go through `begin` untimed
+#[derive(Debug, Clone)]
+pub struct MeteredPool {
+    pool: SqlitePool,
+    metrics: QueryMetrics,
+}
+
+impl MeteredPool {
+    pub fn new(pool: SqlitePool, metrics: QueryMetrics) -> Self {
+        Self { pool, metrics }
+    }
+
+    pub fn inner(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    pub fn metrics(&self) -> &QueryMetrics {
+        &self.metrics
+    }
+
+    pub async fn begin(&self) -> Result<sqlx::Transaction<'static, Sqlite>, sqlx::Error> {
+        self.pool.begin().await
+    }
+
+    fn timing(&self, sql: &str) -> Timing {
+        Timing { metrics: self.metrics.clone(), sql: sql.to_string(), started: Instant::now() }
+    }
+}
+
+impl<'p> Executor<'p> for &'p MeteredPool {
+    type Database = Sqlite;
+
+    fn fetch_many<'e, 'q: 'e, E>(self, query: E) -> BoxStream<'e, Result<Either<SqliteQueryResult, SqliteRow>, sqlx::Error>>
+    where
+        'p: 'e,
+        E: 'q + Execute<'q, Sqlite>,
+    {
+        let timing = self.timing(query.sql());
+        self.pool
+            .fetch_many(query)
+            .map(move |step| {
+                let _ = &timing;
+                step
+            })
+            .boxed()
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(self, query: E) -> BoxFuture<'e, Result<Option<SqliteRow>, sqlx::Error>>
+    where
+        'p: 'e,
+        E: 'q + Execute<'q, Sqlite>,
+    {
+        let timing = self.timing(query.sql());
+        let fetched = self.pool.fetch_optional(query);
+        async move {
+            let row = fetched.await;
+            drop(timing);
+            row
+        }
+        .boxed()
+    }
+
+    fn prepare_with<'e, 'q: 'e>(self, sql: &'q str, parameters: &'e [SqliteTypeInfo]) -> BoxFuture<'e, Result<SqliteStatement<'q>, sqlx::Error>>
+    where
+        'p: 'e,
+    {
+        self.pool.prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(self, sql: &'q str) -> BoxFuture<'e, Result<Describe<Sqlite>, sqlx::Error>>
+    where
+        'p: 'e,
+    {
+        self.pool.describe(sql)
+    }
+}
+
+/// Latency histograms of the database queries, by family, for monitoring
+pub async fn get_query_metrics(State(state): State<AppState>) -> Json<Vec<QueryFamilyStats>> {
+    Json(state.db.pool().metrics().families())
+}
+
+/// The slowest of the recent slow queries, admins only
+pub async fn get_slow_queries(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<SlowQuery>>, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+    if !state.capacity.is_admin(player_id) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(Json(state.db.pool().metrics().slowest()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_family_names_statement_and_main_table() {
+        assert_eq!(family("SELECT COUNT(*) FROM players"), "SELECT players");
+        assert_eq!(family("INSERT INTO players (name) VALUES (?)"), "INSERT players");
+        assert_eq!(family("update matches SET seq = ? WHERE id = ?"), "UPDATE matches");
+        assert_eq!(
+            family("SELECT m.*, (SELECT GROUP_CONCAT(s.player_id) FROM (SELECT player_id FROM match_seats) s) AS seat_ids\n FROM matches m"),
+            "SELECT matches"
+        );
+        assert_eq!(family("PRAGMA foreign_keys"), "PRAGMA");
+    }
+
+    #[test]
+    fn test_redact_masks_literals() {
+        assert_eq!(redact("SELECT id FROM players\n   WHERE name = 'alice' AND id = ?"), "SELECT id FROM players WHERE name = '…' AND id = ?");
+        assert!(redact(&"x".repeat(1000)).chars().count() <= MAX_SQL_LEN + 1);
+    }
+
+    #[test]
+    fn test_histogram_and_slow_queries() {
+        let metrics = QueryMetrics::with_threshold(Duration::from_millis(100));
+        metrics.record("SELECT * FROM players WHERE id = ?", Duration::from_millis(3));
+        metrics.record("SELECT * FROM players WHERE name = ?", Duration::from_millis(150));
+        metrics.record("SELECT * FROM players", Duration::from_secs(2));
+
+        let families = metrics.families();
+        assert_eq!(families.len(), 1);
+        assert_eq!(families[0].family, "SELECT players");
+        assert_eq!(families[0].count, 3);
+        let cumulative: Vec<u64> = families[0].buckets.iter().map(|bucket| bucket.count).collect();
+        assert_eq!(cumulative, vec![0, 0, 1, 1, 1, 1, 1, 2, 2, 2, 3]);
+        assert_eq!(families[0].buckets.last().unwrap().le_ms, None);
+
+        let slowest = metrics.slowest();
+        assert_eq!(slowest.len(), 2);
+        assert_eq!(slowest[0].sql, "SELECT * FROM players");
+        assert_eq!(slowest[1].parameters, 1);
+    }
+
+    #[tokio::test]
+    async fn test_metered_pool_times_queries() {
+        let pool = MeteredPool::new(SqlitePool::connect(":memory:").await.unwrap(), QueryMetrics::with_threshold(Duration::ZERO));
+        sqlx::query("CREATE TABLE players (id INTEGER PRIMARY KEY)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO players (id) VALUES (?)").bind(1).execute(&pool).await.unwrap();
+        let row: Option<(i64,)> = sqlx::query_as("SELECT id FROM players WHERE id = ?").bind(1).fetch_optional(&pool).await.unwrap();
+        assert_eq!(row, Some((1,)));
+
+        let families: Vec<(String, u64)> = pool.metrics().families().into_iter().map(|stats| (stats.family, stats.count)).collect();
+        assert_eq!(families, vec![
+            ("CREATE players".to_string(), 1),
+            ("INSERT players".to_string(), 1),
+            ("SELECT players".to_string(), 1),
+        ]);
+        assert_eq!(pool.metrics().slowest().len(), 3);
+    }
+}
@@ -0,0 +1,281 @@
+use axum::{extract::State, http::{HeaderMap, StatusCode}, Json};
+use battld_common::games::{game_type::GameType, matches::{Match, MatchOutcome}, players::Seat, rock_paper_scissors::RockPaperScissorsGameState};
+use battld_common::{Quest, QuestPeriod};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::database::Database;
+use crate::events::MatchEvent;
+use crate::websocket::SharedRegistry;
+use crate::{auth, AppState};
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+/// 1970-01-01 was a Thursday, weeks start on the Monday before it
+const WEEK_START_OFFSET_DAYS: i64 = 3;
+const DAILY_QUESTS: usize = 2;
+
+/// What a quest counts
+#[derive(Debug, Clone, PartialEq)]
+enum Goal {
+    /// Finished matches, of one game or any
+    Play(Option<GameType>),
+    Win(Option<GameType>),
+    /// Rounds won in Rock-Paper-Scissors matches
+    WinRounds,
+}
+
+struct QuestTemplate {
+    key: &'static str,
+    goal: Goal,
+    target: u32,
+}
+
+const DAILY: [QuestTemplate; 5] = [
+    QuestTemplate { key: "play-3", goal: Goal::Play(None), target: 3 },
+    QuestTemplate { key: "win-rps-rounds-3", goal: Goal::WinRounds, target: 3 },
+    QuestTemplate { key: "win-1", goal: Goal::Win(None), target: 1 },
+    QuestTemplate { key: "play-ttt-2", goal: Goal::Play(Some(GameType::TicTacToe)), target: 2 },
+    QuestTemplate { key: "win-briscola-1", goal: Goal::Win(Some(GameType::Briscola)), target: 1 },
+];
+
+const WEEKLY: [QuestTemplate; 3] = [
+    QuestTemplate { key: "play-5", goal: Goal::Play(None), target: 5 },
+    QuestTemplate { key: "win-5", goal: Goal::Win(None), target: 5 },
+    QuestTemplate { key: "win-rps-rounds-15", goal: Goal::WinRounds, target: 15 },
+];
+
+/// A quest running now, its id naming the day or week so progress starts over with every rotation
+struct ActiveQuest {
+    id: String,
+    template: &'static QuestTemplate,
+    period: QuestPeriod,
+    ends_at: i64,
+}
+
+impl ActiveQuest {
+    fn to_quest(&self, progress: u32) -> Quest {
+        Quest {
+            id: self.id.clone(),
+            description: describe(&self.template.goal, self.template.target, self.period),
+            period: self.period,
+            progress,
+            target: self.template.target,
+            ends_at: self.ends_at,
+        }
+    }
+}
+
+/// Quests of the day and of the week at `now`, in UTC, the same for every player
+fn active_quests(now: i64) -> Vec<ActiveQuest> {
+    let day = now.div_euclid(DAY_SECS);
+    let week = (day + WEEK_START_OFFSET_DAYS).div_euclid(7);
+
+    let daily = (0..DAILY_QUESTS).map(|i| {
+        let template = &DAILY[(day as usize * DAILY_QUESTS + i) % DAILY.len()];
+        ActiveQuest {
+            id: format!("d{day}-{}", template.key),
+            template,
+            period: QuestPeriod::Daily,
+            ends_at: (day + 1) * DAY_SECS,
+        }
+    });
+    let weekly_template = &WEEKLY[week as usize % WEEKLY.len()];
+    let weekly = ActiveQuest {
+        id: format!("w{week}-{}", weekly_template.key),
+        template: weekly_template,
+        period: QuestPeriod::Weekly,
+        ends_at: ((week + 1) * 7 - WEEK_START_OFFSET_DAYS) * DAY_SECS,
+    };
+    daily.chain(std::iter::once(weekly)).collect()
+}
+
+fn describe(goal: &Goal, target: u32, period: QuestPeriod) -> String {
+    let matches = if target == 1 { "match" } else { "matches" };
+    let what = match goal {
+        Goal::Play(None) => format!("Play {target} {matches}"),
+        Goal::Play(Some(game_type)) => format!("Play {target} {game_type} {matches}"),
+        Goal::Win(None) => format!("Win {target} {matches}"),
+        Goal::Win(Some(game_type)) => format!("Win {target} {game_type} {matches}"),
+        Goal::WinRounds => format!("Win {target} {} round{}", GameType::RockPaperScissors, if target == 1 { "" } else { "s" }),
+    };
+    match period {
+        QuestPeriod::Daily => format!("{what} today"),
+        QuestPeriod::Weekly => format!("{what} this week"),
+    }
+}
+
+/// How much a finished match moves `player_id` towards `goal`
+fn progress_from(goal: &Goal, match_data: &Match, player_id: i64) -> u32 {
+    let won = match match_data.outcome {
+        Some(MatchOutcome::Player1Win) => match_data.player1_id == player_id,
+        Some(MatchOutcome::Player2Win) => match_data.player2_id == player_id,
+        Some(MatchOutcome::Draw) => false,
+        None => return 0,
+    };
+    let counts = |game_type: &Option<GameType>| game_type.as_ref().is_none_or(|game_type| *game_type == match_data.game_type);
+    match goal {
+        Goal::Play(game_type) => counts(game_type) as u32,
+        Goal::Win(game_type) => (won && counts(game_type)) as u32,
+        Goal::WinRounds if match_data.game_type == GameType::RockPaperScissors => {
+            let Ok(state) = serde_json::from_value::<RockPaperScissorsGameState>(match_data.game_state.clone()) else {
+                return 0;
+            };
+            let (player1_rounds, player2_rounds) = state.get_score();
+            match match_data.seat_of(player_id) {
+                Some(Seat::FIRST) => player1_rounds as u32,
+                Some(Seat::SECOND) => player2_rounds as u32,
+                _ => 0,
+            }
+        }
+        Goal::WinRounds => 0,
+    }
+}
+
+/// Count a finished match towards the running quests of its players, guests and bots have none
+pub async fn record_quest_progress_logic(match_data: &Match, now: i64, db: &Database) {
+    if match_data.ephemeral {
+        return;
+    }
+    let quests = active_quests(now);
+    for player_id in match_data.seat_ids() {
+        if match_data.player(player_id).is_some_and(|player| player.bot) {
+            continue;
+        }
+        for quest in &quests {
+            let amount = progress_from(&quest.template.goal, match_data, player_id);
+            if amount == 0 {
+                continue;
+            }
+            if let Err(e) = db.add_quest_progress(player_id, &quest.id, amount, quest.template.target).await {
+                println!("Failed to record progress of player {player_id} in quest {}: {e}", quest.id);
+            }
+        }
+    }
+}
+
+/// Move the players of every finished match along their quests
+pub fn spawn_quests(db: Arc<Database>, registry: SharedRegistry) -> tokio::task::JoinHandle<()> {
+    let mut rx = registry.events().subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(MatchEvent::MatchFinished { match_data }) => {
+                    record_quest_progress_logic(&match_data, battld_common::time() as i64, &db).await;
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    println!("Quests fell behind, {missed} events skipped");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// The quests running now and how far the player got in each
+pub async fn quests_logic(player_id: i64, now: i64, db: &Database) -> Result<Vec<Quest>, sqlx::Error> {
+    let mut quests = vec![];
+    for quest in active_quests(now) {
+        let progress = db.get_quest_progress(player_id, &quest.id).await?;
+        quests.push(quest.to_quest(progress));
+    }
+    Ok(quests)
+}
+
+pub async fn get_quests(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Quest>>, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+    quests_logic(player_id, battld_common::time() as i64, &state.db)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::{matches::MatchStatus, rock_paper_scissors::RockPaperScissorsMove};
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    fn finished_match(game_type: GameType, game_state: serde_json::Value, outcome: MatchOutcome) -> Match {
+        Match {
+            id: 1,
+            player1_id: 1,
+            player2_id: 2,
+            seats: vec![],
+            in_progress: false,
+            status: MatchStatus::Finished,
+            outcome: Some(outcome),
+            game_type,
+            game_state,
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
+        }
+    }
+
+    #[test]
+    fn test_quests_rotate_daily_and_weekly() {
+        // Monday 2025-11-17
+        let monday = 20409 * DAY_SECS;
+        let quests = active_quests(monday + 3600);
+        assert_eq!(quests.len(), DAILY_QUESTS + 1);
+        assert_ne!(quests[0].id, quests[1].id);
+        assert_eq!(quests[0].ends_at, monday + DAY_SECS);
+        assert_eq!(quests[2].ends_at, monday + 7 * DAY_SECS);
+
+        let next_day = active_quests(monday + DAY_SECS);
+        assert_ne!(next_day[0].id, quests[0].id);
+        assert_eq!(next_day[2].id, quests[2].id);
+        assert_ne!(active_quests(monday + 7 * DAY_SECS)[2].id, quests[2].id);
+        assert_ne!(active_quests(monday - 1)[2].id, quests[2].id);
+    }
+
+    #[test]
+    fn test_quest_descriptions() {
+        assert_eq!(describe(&Goal::WinRounds, 3, QuestPeriod::Daily), "Win 3 Rock-Paper-Scissors rounds today");
+        assert_eq!(describe(&Goal::Play(None), 5, QuestPeriod::Weekly), "Play 5 matches this week");
+        assert_eq!(describe(&Goal::Win(Some(GameType::Briscola)), 1, QuestPeriod::Daily), "Win 1 Briscola match today");
+    }
+
+    #[test]
+    fn test_progress_from_a_finished_match() {
+        use RockPaperScissorsMove::*;
+        let mut state = RockPaperScissorsGameState::new();
+        state.rounds = vec![(Some(Rock), Some(Scissors)), (Some(Paper), Some(Scissors)), (Some(Rock), Some(Scissors))];
+        let rps = finished_match(GameType::RockPaperScissors, serde_json::to_value(&state).unwrap(), MatchOutcome::Player1Win);
+
+        assert_eq!(progress_from(&Goal::WinRounds, &rps, 1), 2);
+        assert_eq!(progress_from(&Goal::WinRounds, &rps, 2), 1);
+        assert_eq!(progress_from(&Goal::Win(None), &rps, 1), 1);
+        assert_eq!(progress_from(&Goal::Win(None), &rps, 2), 0);
+        assert_eq!(progress_from(&Goal::Play(None), &rps, 2), 1);
+        assert_eq!(progress_from(&Goal::Play(Some(GameType::TicTacToe)), &rps, 2), 0);
+    }
+
+    #[tokio::test]
+    async fn test_progress_is_kept_until_the_target() {
+        let db = create_test_db().await;
+        let player_id = db.create_player("alice_hint", "alice_key", "alice").await.unwrap();
+        let quest = active_quests(0).into_iter().next().unwrap();
+        let target = quest.template.target;
+
+        assert_eq!(quests_logic(player_id, 0, &db).await.unwrap()[0].progress, 0);
+        db.add_quest_progress(player_id, &quest.id, 1, target).await.unwrap();
+        assert_eq!(db.add_quest_progress(player_id, &quest.id, target + 5, target).await.unwrap(), target);
+
+        let quests = quests_logic(player_id, 0, &db).await.unwrap();
+        assert!(quests[0].is_complete());
+        assert_eq!(quests[1].progress, 0);
+    }
+}
@@ -0,0 +1,395 @@
+use battld_common::games::{game_type::GameType, matches::{Match, MatchEndReason, MatchPlayer, MatchStatus}};
+use battld_common::{ErrorCode, ServerMessage};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::database::{Database, GuestMatch};
+use crate::game_logic::OutgoingMessage;
+use crate::repository;
+use server::game_router;
+
+/// Matches between guests that live in memory only, for demos where nobody has an account
+/// Nothing about them reaches the database, so they stay out of stats, ratings and replays
+/// Guests and their matches get negative ids, which never clash with stored ones
+/// The matches a guest finished are only stored if they register before leaving
+#[derive(Default)]
+pub struct QuickPlay {
+    enabled: bool,
+    state: Mutex<QuickPlayState>,
+}
+
+#[derive(Default)]
+struct QuickPlayState {
+    last_id: i64,
+    /// Guest waiting for an opponent, per game type
+    waiting: HashMap<GameType, i64>,
+    matches: HashMap<i64, Match>,
+    /// Match each guest is playing
+    playing: HashMap<i64, i64>,
+    /// Matches each guest finished, in case they register
+    finished: HashMap<i64, Vec<GuestMatch>>,
+}
+
+impl QuickPlayState {
+    fn next_id(&mut self) -> i64 {
+        self.last_id -= 1;
+        self.last_id
+    }
+
+    /// Forget a finished or abandoned match and free its players
+    fn remove_match(&mut self, match_id: i64) -> Option<Match> {
+        let match_data = self.matches.remove(&match_id)?;
+        self.playing.remove(&match_data.player1_id);
+        self.playing.remove(&match_data.player2_id);
+        Some(match_data)
+    }
+}
+
+impl QuickPlay {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, state: Mutex::default() }
+    }
+
+    /// Read from QUICK_PLAY, off unless set to true
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("QUICK_PLAY").is_ok_and(|value| value == "true"))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Id for a new guest, valid until their connection closes
+    pub fn new_guest(&self) -> i64 {
+        self.state.lock().unwrap().next_id()
+    }
+}
+
+fn guest_name(guest_id: i64) -> String {
+    format!("Guest {}", -guest_id)
+}
+
+fn error(guest_id: i64, code: ErrorCode) -> Vec<OutgoingMessage> {
+    vec![OutgoingMessage {
+        player_id: guest_id,
+        message: ServerMessage::error(code, &[]),
+    }]
+}
+
+/// The same message to both players, each with their own view of the match
+fn to_both(match_data: &Match, message: impl Fn(Match) -> ServerMessage) -> Vec<OutgoingMessage> {
+    [match_data.player1_id, match_data.player2_id]
+        .into_iter()
+        .map(|player_id| OutgoingMessage {
+            player_id,
+            message: message(game_router::redact_match_for_player(match_data, player_id)),
+        })
+        .collect()
+}
+
+/// Queue a guest for a quick match with default options, or start one against the guest already waiting
+pub fn handle_join_quick_play_logic(guest_id: i64, game_type: GameType, quick_play: &QuickPlay) -> Vec<OutgoingMessage> {
+    let mut state = quick_play.state.lock().unwrap();
+    if state.playing.contains_key(&guest_id) {
+        return error(guest_id, ErrorCode::AlreadyInMatch);
+    }
+    state.waiting.retain(|_, waiting_id| *waiting_id != guest_id);
+
+    let Some(opponent_id) = state.waiting.remove(&game_type) else {
+        state.waiting.insert(game_type, guest_id);
+        return vec![OutgoingMessage { player_id: guest_id, message: ServerMessage::WaitingForOpponent }];
+    };
+
+    let options = game_router::normalize_game_options(&game_type, &JsonValue::Null).unwrap_or_default();
+    let game_state = serde_json::from_str(&game_router::initialize_game_state(&game_type, &options)).unwrap_or_default();
+    let match_data = Match {
+        id: state.next_id(),
+        player1_id: opponent_id,
+        player2_id: guest_id,
+        seats: vec![],
+        in_progress: true,
+        status: MatchStatus::Active,
+        outcome: None,
+        game_type,
+        game_state,
+        players: [opponent_id, guest_id]
+            .into_iter()
+            .map(|id| MatchPlayer { id, name: guest_name(id), score: 0, bot: false, rating: None })
+            .collect(),
+        ephemeral: true,
+        draw_offered_by: None,
+        seq: 0,
+    };
+    println!("Quick play match {} started between guests {opponent_id} and {guest_id}", match_data.id);
+
+    state.playing.insert(opponent_id, match_data.id);
+    state.playing.insert(guest_id, match_data.id);
+    let messages = to_both(&match_data, |match_data| ServerMessage::MatchFound { match_data });
+    state.matches.insert(match_data.id, match_data);
+    messages
+}
+
+/// Apply a guest's move to their match, which is dropped from memory once it ends
+pub fn handle_quick_play_move_logic(guest_id: i64, move_data: JsonValue, quick_play: &QuickPlay) -> Vec<OutgoingMessage> {
+    let mut state = quick_play.state.lock().unwrap();
+    let Some(match_id) = state.playing.get(&guest_id).copied() else {
+        return error(guest_id, ErrorCode::NoActiveMatch);
+    };
+    let Some(match_data) = state.matches.get_mut(&match_id) else {
+        return error(guest_id, ErrorCode::NoActiveMatch);
+    };
+
+    let result = match game_router::handle_game_move(match_data, guest_id, move_data) {
+        Ok(result) => result,
+        Err(e) => return vec![OutgoingMessage { player_id: guest_id, message: e.to_server_message() }],
+    };
+    match_data.game_state = result.new_state;
+    if !result.is_finished {
+        return to_both(match_data, |match_data| ServerMessage::GameStateUpdate { match_data });
+    }
+
+    match_data.status = MatchStatus::Finished;
+    match_data.in_progress = false;
+    match_data.outcome = result.outcome;
+    let Some(match_data) = state.remove_match(match_id) else {
+        return vec![];
+    };
+    println!("Quick play match {match_id} finished");
+    let finished_at = battld_common::time() as i64;
+    for player_id in [match_data.player1_id, match_data.player2_id] {
+        state.finished.entry(player_id).or_default().push(GuestMatch { match_data: match_data.clone(), finished_at });
+    }
+
+    let summary = game_router::match_summary(&match_data);
+    let mut messages = to_both(&match_data, |match_data| ServerMessage::GameStateUpdate { match_data });
+    messages.extend(to_both(&match_data, |_| ServerMessage::MatchEnded {
+        reason: MatchEndReason::Ended,
+        summary: summary.clone(),
+    }));
+    messages
+}
+
+/// Latest state of the guest's match, answered like `RequestState` for stored matches
+pub fn handle_quick_play_state_logic(guest_id: i64, match_id: i64, quick_play: &QuickPlay) -> Vec<OutgoingMessage> {
+    let state = quick_play.state.lock().unwrap();
+    match state.matches.get(&match_id) {
+        Some(match_data) if state.playing.get(&guest_id) == Some(&match_id) => vec![OutgoingMessage {
+            player_id: guest_id,
+            message: ServerMessage::GameStateUpdate {
+                match_data: game_router::redact_match_for_player(match_data, guest_id),
+            },
+        }],
+        _ => error(guest_id, ErrorCode::MatchNotFound),
+    }
+}
+
+/// A guest who leaves can't come back, so their match ends at once
+pub fn handle_quick_play_leave_logic(guest_id: i64, quick_play: &QuickPlay) -> Vec<OutgoingMessage> {
+    let mut state = quick_play.state.lock().unwrap();
+    state.waiting.retain(|_, waiting_id| *waiting_id != guest_id);
+    state.finished.remove(&guest_id);
+    let Some(match_id) = state.playing.get(&guest_id).copied() else {
+        return vec![];
+    };
+    let Some(match_data) = state.remove_match(match_id) else {
+        return vec![];
+    };
+    println!("Quick play match {match_id} abandoned by guest {guest_id}");
+
+    let opponent_id = if match_data.player1_id == guest_id { match_data.player2_id } else { match_data.player1_id };
+    vec![
+        OutgoingMessage { player_id: opponent_id, message: ServerMessage::PlayerDisconnected { player_id: guest_id, match_id, seq: 0 } },
+        OutgoingMessage {
+            player_id: opponent_id,
+            message: ServerMessage::MatchEnded { reason: MatchEndReason::Disconnection, summary: None },
+        },
+    ]
+}
+
+/// Register the guest with their own key, storing the matches they finished along with the score and ratings earned
+pub async fn handle_upgrade_guest_logic(
+    guest_id: i64,
+    name: Option<String>,
+    public_key_hint: &str,
+    public_key: &str,
+    quick_play: &QuickPlay,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let matches = {
+        let state = quick_play.state.lock().unwrap();
+        if state.playing.contains_key(&guest_id) || state.waiting.values().any(|waiting_id| *waiting_id == guest_id) {
+            return error(guest_id, ErrorCode::AlreadyInMatch);
+        }
+        state.finished.get(&guest_id).cloned().unwrap_or_default()
+    };
+    if db.get_player_by_public_key(public_key).await.is_some() {
+        return error(guest_id, ErrorCode::KeyInUse);
+    }
+
+    let name = name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| guest_name(guest_id));
+    let player_id = match db.upgrade_guest(guest_id, public_key_hint, public_key, &name, &matches).await {
+        Ok(player_id) => player_id,
+        Err(e) => {
+            println!("Failed to register guest {guest_id}: {e}");
+            return error(guest_id, ErrorCode::ServerError);
+        }
+    };
+    quick_play.state.lock().unwrap().finished.remove(&guest_id);
+    println!("Guest {guest_id} registered as player {player_id} with {} matches", matches.len());
+
+    let Some(player) = repository::fetch_player(db, player_id).await else {
+        return error(guest_id, ErrorCode::ServerError);
+    };
+    vec![OutgoingMessage {
+        player_id: guest_id,
+        message: ServerMessage::GuestUpgraded { player, matches: matches.len() },
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::rock_paper_scissors::RockPaperScissorsMove;
+    use battld_common::games::matches::MatchOutcome;
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    fn start_match(quick_play: &QuickPlay) -> (i64, i64, Match) {
+        let (first, second) = (quick_play.new_guest(), quick_play.new_guest());
+        let messages = handle_join_quick_play_logic(first, GameType::RockPaperScissors, quick_play);
+        assert!(matches!(messages[0].message, ServerMessage::WaitingForOpponent));
+
+        let messages = handle_join_quick_play_logic(second, GameType::RockPaperScissors, quick_play);
+        assert_eq!(messages.len(), 2);
+        let ServerMessage::MatchFound { match_data } = &messages[0].message else {
+            panic!("Expected MatchFound, got {:?}", messages[0].message);
+        };
+        (first, second, match_data.clone())
+    }
+
+    /// Two rounds of Rock against Scissors, won by the first guest
+    fn win_match(quick_play: &QuickPlay, first: i64, second: i64) -> Vec<OutgoingMessage> {
+        let mut messages = vec![];
+        for _ in 0..2 {
+            handle_quick_play_move_logic(first, serde_json::json!({ "choice": RockPaperScissorsMove::Rock }), quick_play);
+            messages = handle_quick_play_move_logic(second, serde_json::json!({ "choice": RockPaperScissorsMove::Scissors }), quick_play);
+        }
+        messages
+    }
+
+    async fn count(db: &Database, table: &str) -> i64 {
+        let (count,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM {table}")).fetch_one(db.pool()).await.unwrap();
+        count
+    }
+
+    #[test]
+    fn test_guests_play_a_match_kept_in_memory() {
+        let quick_play = QuickPlay::new(true);
+        let (first, second, match_data) = start_match(&quick_play);
+        assert!(match_data.ephemeral);
+        assert!(match_data.id < 0 && first < 0 && second < 0);
+        assert_eq!(match_data.players[0].name, guest_name(first));
+
+        let messages = win_match(&quick_play, first, second);
+        let ended: Vec<_> = messages.iter().filter(|m| matches!(m.message, ServerMessage::MatchEnded { .. })).collect();
+        assert_eq!(ended.len(), 2);
+        let final_state = messages.iter().find_map(|m| match &m.message {
+            ServerMessage::GameStateUpdate { match_data } => Some(match_data),
+            _ => None,
+        });
+        assert_eq!(final_state.unwrap().outcome, Some(MatchOutcome::Player1Win));
+
+        assert!(quick_play.state.lock().unwrap().matches.is_empty());
+        let messages = handle_quick_play_move_logic(first, serde_json::json!({ "choice": RockPaperScissorsMove::Rock }), &quick_play);
+        assert!(matches!(messages[0].message, ServerMessage::Error { .. }));
+    }
+
+    #[test]
+    fn test_leaving_ends_the_match_for_the_opponent() {
+        let quick_play = QuickPlay::new(true);
+        let (first, second, match_data) = start_match(&quick_play);
+
+        assert_eq!(handle_quick_play_state_logic(second, match_data.id, &quick_play).len(), 1);
+
+        let messages = handle_quick_play_leave_logic(first, &quick_play);
+        assert!(messages.iter().all(|m| m.player_id == second));
+        assert!(matches!(messages[1].message, ServerMessage::MatchEnded { reason: MatchEndReason::Disconnection, .. }));
+        assert!(matches!(handle_quick_play_state_logic(second, match_data.id, &quick_play)[0].message, ServerMessage::Error { .. }));
+
+        let messages = handle_join_quick_play_logic(second, GameType::Chess, &quick_play);
+        assert!(matches!(messages[0].message, ServerMessage::WaitingForOpponent));
+        assert!(handle_quick_play_leave_logic(second, &quick_play).is_empty());
+        assert!(quick_play.state.lock().unwrap().waiting.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_registered_guests_keep_their_matches() {
+        let db = create_test_db().await;
+        let quick_play = QuickPlay::new(true);
+        let (first, second, _) = start_match(&quick_play);
+        win_match(&quick_play, first, second);
+
+        handle_join_quick_play_logic(first, GameType::RockPaperScissors, &quick_play);
+        let messages = handle_upgrade_guest_logic(first, None, "hint", "key", &quick_play, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { code: ErrorCode::AlreadyInMatch, .. }));
+        handle_quick_play_leave_logic(first, &quick_play);
+
+        let messages = handle_upgrade_guest_logic(second, Some(" alice ".to_string()), "hint", "key", &quick_play, &db).await;
+        let ServerMessage::GuestUpgraded { player, matches } = &messages[0].message else {
+            panic!("Expected GuestUpgraded, got {:?}", messages[0].message);
+        };
+        assert_eq!((player.name.as_str(), player.score, *matches), ("alice", -1, 1));
+
+        let stored = db.get_last_finished_match_for_player(player.id).await.unwrap();
+        assert_eq!(stored.player2_id, player.id);
+        assert_eq!(stored.outcome(), Some(MatchOutcome::Player1Win));
+        assert_eq!(stored.player1_name, Some(guest_name(first)));
+        assert!(db.find_players_by_name(&guest_name(first)).await.unwrap().is_empty());
+        assert_eq!(db.get_rating(player.id).await.unwrap().games, 1);
+        assert_eq!(db.get_rating(stored.player1_id).await.unwrap().games, 0);
+
+        let messages = handle_upgrade_guest_logic(quick_play.new_guest(), None, "hint", "key", &quick_play, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { code: ErrorCode::KeyInUse, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_failed_registration_stores_nothing() {
+        let db = create_test_db().await;
+        let quick_play = QuickPlay::new(true);
+        let (first, second, _) = start_match(&quick_play);
+        win_match(&quick_play, first, second);
+        handle_join_quick_play_logic(first, GameType::RockPaperScissors, &quick_play);
+        handle_join_quick_play_logic(second, GameType::RockPaperScissors, &quick_play);
+        win_match(&quick_play, first, second);
+
+        sqlx::query(
+            "CREATE TRIGGER fail_second_match BEFORE INSERT ON matches WHEN (SELECT COUNT(*) FROM matches) > 0
+             BEGIN SELECT RAISE(ABORT, 'disk full'); END"
+        )
+        .execute(db.pool())
+        .await
+        .unwrap();
+        let messages = handle_upgrade_guest_logic(first, None, "hint", "key", &quick_play, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { code: ErrorCode::ServerError, .. }));
+        assert_eq!((count(&db, "players").await, count(&db, "matches").await), (0, 0));
+        assert_eq!(count(&db, "player_game_ratings").await, 0);
+
+        sqlx::query("DROP TRIGGER fail_second_match").execute(db.pool()).await.unwrap();
+        let messages = handle_upgrade_guest_logic(first, None, "hint", "key", &quick_play, &db).await;
+        let ServerMessage::GuestUpgraded { player, matches } = &messages[0].message else {
+            panic!("Expected GuestUpgraded, got {:?}", messages[0].message);
+        };
+        assert_eq!((player.name.clone(), player.score, *matches), (guest_name(first), 6, 2));
+        assert_eq!((count(&db, "players").await, count(&db, "matches").await), (2, 2));
+    }
+}
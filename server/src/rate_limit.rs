@@ -1,25 +1,82 @@
-use std::sync::Arc;
-use tower_governor::{governor::GovernorConfigBuilder, GovernorLayer, key_extractor::PeerIpKeyExtractor};
-use governor::middleware::NoOpMiddleware;
-use governor::clock::QuantaInstant;
-
-/// Creates a rate limiting layer for API endpoints
-/// Default: 10 requests per second per IP address
-pub fn create_rate_limiter() -> GovernorLayer<PeerIpKeyExtractor, NoOpMiddleware<QuantaInstant>> {
-    let requests_per_second = std::env::var("RATE_LIMIT_RPS")
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter as Governor};
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+
+use crate::settings::Swap;
+use crate::AppState;
+
+const DEFAULT_REQUESTS_PER_SECOND: u32 = 10;
+
+/// Per IP address rate limit for API endpoints, rebuilt when the configuration is reloaded
+pub struct RateLimiter(Swap<DefaultKeyedRateLimiter<IpAddr>>);
+
+impl RateLimiter {
+    pub fn new(requests_per_second: u32) -> Self {
+        Self(Swap::new(keyed_limiter(requests_per_second)))
+    }
+
+    /// Read from RATE_LIMIT_RPS, default 10 requests per second per IP address
+    pub fn from_env() -> Self {
+        Self::new(requests_per_second_from_env())
+    }
+
+    /// Apply a new RATE_LIMIT_RPS, which also forgets the requests counted so far
+    pub fn reload_from_env(&self) {
+        self.0.store(keyed_limiter(requests_per_second_from_env()));
+    }
+
+    pub fn check(&self, ip: IpAddr) -> bool {
+        self.0.load().check_key(&ip).is_ok()
+    }
+}
+
+fn requests_per_second_from_env() -> u32 {
+    std::env::var("RATE_LIMIT_RPS")
         .ok()
-        .and_then(|v| v.parse::<u64>().ok())
-        .unwrap_or(10);
-
-    let governor_conf = Arc::new(
-        GovernorConfigBuilder::default()
-            .per_second(requests_per_second)
-            .burst_size((requests_per_second * 2) as u32)
-            .finish()
-            .expect("Failed to build rate limiter config"),
-    );
-
-    GovernorLayer {
-        config: governor_conf,
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_REQUESTS_PER_SECOND)
+}
+
+/// Bursts of up to twice the rate are allowed
+fn keyed_limiter(requests_per_second: u32) -> DefaultKeyedRateLimiter<IpAddr> {
+    let rate = NonZeroU32::new(requests_per_second).unwrap_or(NonZeroU32::MIN);
+    let burst = rate.saturating_mul(NonZeroU32::new(2).unwrap());
+    Governor::keyed(Quota::per_second(rate).allow_burst(burst))
+}
+
+/// Turn away requests from addresses over the limit
+pub async fn rate_limit_middleware(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.rate_limiter.check(addr.ip()) {
+        return (StatusCode::TOO_MANY_REQUESTS, "Too Many Requests!").into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload_applies_the_new_rate() {
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let limiter = RateLimiter::new(1);
+        assert!(limiter.check(ip));
+        assert!(limiter.check(ip));
+        assert!(!limiter.check(ip));
+
+        limiter.0.store(keyed_limiter(5));
+        assert!((0..10).all(|_| limiter.check(ip)));
+        assert!(!limiter.check(ip));
     }
 }
@@ -0,0 +1,112 @@
+use std::f64::consts::{LN_10, PI};
+
+pub const INITIAL_RATING: f64 = 1500.0;
+pub const INITIAL_DEVIATION: f64 = 350.0;
+const MIN_DEVIATION: f64 = 30.0;
+/// Players with fewer rated games than this are provisional
+pub const PROVISIONAL_GAMES: i64 = 10;
+/// Share of the usual rating change caused by a game against a provisional player
+const PROVISIONAL_OPPONENT_WEIGHT: f64 = 0.5;
+
+const Q: f64 = LN_10 / 400.0;
+
+/// Glicko rating of a player
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub games: i64,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            rating: INITIAL_RATING,
+            deviation: INITIAL_DEVIATION,
+            games: 0,
+        }
+    }
+}
+
+impl Rating {
+    pub fn is_provisional(&self) -> bool {
+        self.games < PROVISIONAL_GAMES
+    }
+
+    /// Rating after one game against `opponent`, `score` is 1 for a win, 0.5 for a draw and 0 for a loss
+    pub fn update(&self, opponent: &Rating, score: f64) -> Rating {
+        let g = g(opponent.deviation);
+        let expected = 1.0 / (1.0 + 10f64.powf(-g * (self.rating - opponent.rating) / 400.0));
+        let d_squared = 1.0 / (Q * Q * g * g * expected * (1.0 - expected));
+        let precision = 1.0 / (self.deviation * self.deviation) + 1.0 / d_squared;
+
+        let weight = if opponent.is_provisional() { PROVISIONAL_OPPONENT_WEIGHT } else { 1.0 };
+        let change = weight * Q / precision * g * (score - expected);
+
+        Rating {
+            rating: self.rating + change,
+            deviation: (1.0 / precision).sqrt().max(MIN_DEVIATION),
+            games: self.games + 1,
+        }
+    }
+}
+
+/// How much a game against an opponent with deviation `rd` can tell
+fn g(rd: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * Q * Q * rd * rd / (PI * PI)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn established(rating: f64) -> Rating {
+        Rating { rating, deviation: 50.0, games: 40 }
+    }
+
+    #[test]
+    fn test_glicko_reference_example() {
+        // First player and opponent of the example in Glickman's paper
+        let player = Rating { rating: 1500.0, deviation: 200.0, games: 20 };
+        let opponent = Rating { rating: 1400.0, deviation: 30.0, games: 20 };
+
+        let updated = player.update(&opponent, 1.0);
+        assert!((updated.rating - 1563.6).abs() < 0.5, "{}", updated.rating);
+        assert!((updated.deviation - 175.2).abs() < 0.5, "{}", updated.deviation);
+        assert_eq!(updated.games, 21);
+    }
+
+    #[test]
+    fn test_results_move_ratings_the_right_way() {
+        let player = established(1500.0);
+        let opponent = established(1500.0);
+
+        assert!(player.update(&opponent, 1.0).rating > 1500.0);
+        assert!(player.update(&opponent, 0.0).rating < 1500.0);
+        assert!((player.update(&opponent, 0.5).rating - 1500.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_deviation_shrinks_with_games() {
+        let mut player = Rating::default();
+        assert!(player.is_provisional());
+
+        for _ in 0..PROVISIONAL_GAMES {
+            player = player.update(&established(1500.0), 0.5);
+        }
+        assert!(!player.is_provisional());
+        assert!(player.deviation < INITIAL_DEVIATION / 2.0);
+        assert!(player.deviation >= MIN_DEVIATION);
+    }
+
+    #[test]
+    fn test_provisional_opponents_count_less() {
+        let player = established(1500.0);
+        let provisional = Rating { games: 3, ..established(1500.0) };
+
+        let against_established = player.update(&established(1500.0), 1.0).rating - 1500.0;
+        let against_provisional = player.update(&provisional, 1.0).rating - 1500.0;
+        assert!(against_provisional > 0.0);
+        assert!(against_provisional < against_established);
+    }
+}
@@ -0,0 +1,363 @@
+use battld_common::games::{game_type::GameType, matches::MatchStatus};
+use battld_common::{ErrorCode, ServerMessage};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::capacity::Capacity;
+use crate::database::Database;
+use crate::events::EventBus;
+use crate::game_logic::{self, OutgoingMessage};
+use crate::matchmaking::RatingWindow;
+use crate::settings::Swap;
+use crate::websocket::SharedRegistry;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A waiting match held for the opponent who found it, until both players accept
+struct PendingCheck {
+    player1_id: i64,
+    player2_id: i64,
+    game_type: GameType,
+    options: serde_json::Value,
+    expires_at: i64,
+    accepted: HashSet<i64>,
+}
+
+impl PendingCheck {
+    fn players(&self) -> [i64; 2] {
+        [self.player1_id, self.player2_id]
+    }
+}
+
+/// Matches found by matchmaking that wait on both players to accept, keyed by match id
+/// Disabled unless a timeout is set, matches then start as soon as an opponent is found
+#[derive(Default)]
+pub struct ReadyChecks {
+    timeout_secs: Swap<Option<u64>>,
+    pending: Mutex<HashMap<i64, PendingCheck>>,
+}
+
+impl ReadyChecks {
+    pub fn new(timeout_secs: Option<u64>) -> Self {
+        Self { timeout_secs: Swap::new(timeout_secs.filter(|secs| *secs > 0)), pending: Mutex::default() }
+    }
+
+    /// Read from READY_CHECK_SECS, unset or 0 disables the ready check
+    pub fn from_env() -> Self {
+        Self::new(timeout_secs_from_env())
+    }
+
+    /// Apply a new READY_CHECK_SECS to matches found from now on, pending checks keep their deadline
+    pub fn reload_from_env(&self) {
+        self.timeout_secs.store(timeout_secs_from_env().filter(|secs| *secs > 0));
+    }
+
+    fn timeout_secs(&self) -> Option<u64> {
+        *self.timeout_secs.load()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.timeout_secs().is_some()
+    }
+
+    pub fn is_pending(&self, player_id: i64) -> bool {
+        self.pending.lock().unwrap().values().any(|check| check.players().contains(&player_id))
+    }
+}
+
+fn timeout_secs_from_env() -> Option<u64> {
+    std::env::var("READY_CHECK_SECS").ok().and_then(|v| v.parse().ok())
+}
+
+/// Who failed a ready check and who gets back in the queue
+struct FailedCheck {
+    match_id: i64,
+    check: PendingCheck,
+    requeued: Vec<i64>,
+}
+
+/// Expire ready checks every second
+pub fn spawn_expiry(
+    ready_checks: Arc<ReadyChecks>,
+    capacity: Arc<Capacity>,
+    db: Arc<Database>,
+    registry: SharedRegistry,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let now = battld_common::time() as i64;
+            let messages = expire_ready_checks_logic(now, &capacity, &registry.settings().load().matchmaking, &ready_checks, registry.events(), &db).await;
+            registry.send_messages(messages).await;
+        }
+    })
+}
+
+/// Hold the waiting match of `player1_id` for `player2_id` and ask both to accept it
+pub async fn start_ready_check_logic(
+    match_id: i64,
+    player1_id: i64,
+    player2_id: i64,
+    game_type: GameType,
+    options: serde_json::Value,
+    ready_checks: &ReadyChecks,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let timeout_secs = ready_checks.timeout_secs().unwrap_or_default();
+    if !matches!(db.reserve_waiting_match(match_id, player2_id).await, Ok(true)) {
+        return vec![OutgoingMessage {
+            player_id: player2_id,
+            message: ServerMessage::error(ErrorCode::OpponentUnavailable, &[]),
+        }];
+    }
+
+    ready_checks.pending.lock().unwrap().insert(match_id, PendingCheck {
+        player1_id,
+        player2_id,
+        game_type,
+        options,
+        expires_at: battld_common::time() as i64 + timeout_secs as i64,
+        accepted: HashSet::new(),
+    });
+
+    [player1_id, player2_id]
+        .into_iter()
+        .map(|player_id| OutgoingMessage {
+            player_id,
+            message: ServerMessage::ReadyCheck { match_id, expires_in: timeout_secs },
+        })
+        .collect()
+}
+
+/// Start the match once both players accepted it, a decline sends the other player back to matchmaking
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_accept_match_logic(
+    player_id: i64,
+    match_id: i64,
+    accept: bool,
+    capacity: &Capacity,
+    window: &RatingWindow,
+    ready_checks: &ReadyChecks,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let decided = {
+        let mut pending = ready_checks.pending.lock().unwrap();
+        let Some(check) = pending.get_mut(&match_id).filter(|check| check.players().contains(&player_id)) else {
+            return vec![OutgoingMessage {
+                player_id,
+                message: ServerMessage::error(ErrorCode::ReadyCheckClosed, &[]),
+            }];
+        };
+        if accept {
+            check.accepted.insert(player_id);
+        }
+        match (accept, check.accepted.len()) {
+            (true, 1) => return vec![],
+            _ => pending.remove(&match_id).unwrap(),
+        }
+    };
+
+    if !accept {
+        println!("Player {player_id} declined match {match_id}");
+        let requeued = decided.players().into_iter().filter(|id| *id != player_id).collect();
+        return fail_check_logic(FailedCheck { match_id, check: decided, requeued }, capacity, window, ready_checks, events, db).await;
+    }
+
+    println!("Both players accepted match {match_id}");
+    let started = game_logic::start_matched_game(
+        match_id,
+        &[decided.player1_id, decided.player2_id],
+        &decided.game_type,
+        &decided.options,
+        events,
+        db,
+    ).await;
+    match started {
+        Some(messages) => messages,
+        // The first player left matchmaking after accepting
+        None => {
+            let requeued = vec![decided.player2_id];
+            fail_check_logic(FailedCheck { match_id, check: decided, requeued }, capacity, window, ready_checks, events, db).await
+        }
+    }
+}
+
+/// Drop players who did not accept in time, those who did go back to matchmaking
+pub async fn expire_ready_checks_logic(
+    now: i64,
+    capacity: &Capacity,
+    window: &RatingWindow,
+    ready_checks: &ReadyChecks,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let expired: Vec<FailedCheck> = {
+        let mut pending = ready_checks.pending.lock().unwrap();
+        let match_ids: Vec<i64> = pending.iter().filter(|(_, check)| check.expires_at <= now).map(|(id, _)| *id).collect();
+        match_ids
+            .into_iter()
+            .filter_map(|match_id| pending.remove(&match_id).map(|check| (match_id, check)))
+            .map(|(match_id, check)| {
+                let requeued = check.accepted.iter().copied().collect();
+                FailedCheck { match_id, check, requeued }
+            })
+            .collect()
+    };
+
+    let mut messages = vec![];
+    for failed in expired {
+        println!("Ready check for match {} expired", failed.match_id);
+        messages.extend(fail_check_logic(failed, capacity, window, ready_checks, events, db).await);
+    }
+    messages
+}
+
+/// Close the held match and queue the players who were ready again, ahead of anyone who joined since
+async fn fail_check_logic(
+    failed: FailedCheck,
+    capacity: &Capacity,
+    window: &RatingWindow,
+    ready_checks: &ReadyChecks,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    if let Err(e) = db.transition_match(failed.match_id, MatchStatus::Waiting, MatchStatus::Aborted).await {
+        println!("Failed to abort match {} after its ready check: {e}", failed.match_id);
+    }
+
+    let series_length = db.get_series_length(failed.match_id).await;
+    let mut messages = vec![];
+    for player_id in failed.check.players() {
+        let requeued = failed.requeued.contains(&player_id);
+        messages.push(OutgoingMessage { player_id, message: ServerMessage::ReadyCheckFailed { requeued } });
+        if requeued {
+            messages.extend(game_logic::handle_join_matchmaking_logic(
+                player_id,
+                failed.check.game_type.clone(),
+                failed.check.options.clone(),
+                series_length,
+                capacity,
+                window,
+                ready_checks,
+                events,
+                db,
+            ).await);
+        }
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::MatchmakingRejection;
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str) -> i64 {
+        db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name).await.unwrap()
+    }
+
+    async fn join(player_id: i64, ready_checks: &ReadyChecks, db: &Database) -> Vec<OutgoingMessage> {
+        game_logic::handle_join_matchmaking_logic(
+            player_id,
+            GameType::TicTacToe,
+            serde_json::Value::Null,
+            None,
+            &Capacity::default(),
+            &RatingWindow::default(),
+            ready_checks,
+            &EventBus::new(),
+            db,
+        ).await
+    }
+
+    async fn answer(player_id: i64, match_id: i64, accept: bool, ready_checks: &ReadyChecks, db: &Database) -> Vec<OutgoingMessage> {
+        handle_accept_match_logic(player_id, match_id, accept, &Capacity::default(), &RatingWindow::default(), ready_checks, &EventBus::new(), db).await
+    }
+
+    /// Two players matched and asked to accept, returns the match id
+    async fn start_check(p1: i64, p2: i64, ready_checks: &ReadyChecks, db: &Database) -> i64 {
+        join(p1, ready_checks, db).await;
+        let messages = join(p2, ready_checks, db).await;
+        assert_eq!(messages.len(), 2);
+        match messages[0].message {
+            ServerMessage::ReadyCheck { match_id, expires_in: 15 } => match_id,
+            ref other => panic!("Expected ReadyCheck, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_match_starts_once_both_accept() {
+        let db = create_test_db().await;
+        let ready_checks = ReadyChecks::new(Some(15));
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let p3 = create_test_player(&db, "player3").await;
+        let match_id = start_check(p1, p2, &ready_checks, &db).await;
+
+        // The held match is not offered to anyone else
+        assert!(matches!(join(p3, &ready_checks, &db).await[0].message, ServerMessage::WaitingForOpponent));
+        assert!(matches!(
+            join(p2, &ready_checks, &db).await[0].message,
+            ServerMessage::MatchmakingRejected { reason: MatchmakingRejection::ReadyCheckPending }
+        ));
+
+        assert!(answer(p2, match_id, true, &ready_checks, &db).await.is_empty());
+        let messages = answer(p1, match_id, true, &ready_checks, &db).await;
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| matches!(m.message, ServerMessage::MatchFound { .. })));
+        assert_eq!(db.get_active_match_for_player(p2).await.unwrap().id, match_id);
+        assert!(!ready_checks.is_pending(p1));
+    }
+
+    #[tokio::test]
+    async fn test_decline_requeues_opponent_first() {
+        let db = create_test_db().await;
+        let ready_checks = ReadyChecks::new(Some(15));
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let p3 = create_test_player(&db, "player3").await;
+        let match_id = start_check(p1, p2, &ready_checks, &db).await;
+        join(p3, &ready_checks, &db).await;
+
+        let messages = answer(p1, match_id, false, &ready_checks, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::ReadyCheckFailed { requeued: false }));
+        assert_eq!(messages[1].player_id, p2);
+        assert!(matches!(messages[1].message, ServerMessage::ReadyCheckFailed { requeued: true }));
+        // p2 goes straight to the player who was queued meanwhile
+        assert!(matches!(messages[2].message, ServerMessage::ReadyCheck { .. }));
+        assert!(ready_checks.is_pending(p3));
+
+        assert_eq!(db.get_match_by_id(match_id).await.unwrap().status, MatchStatus::Aborted.as_str());
+        assert!(db.get_waiting_match_for_player(p1).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_no_show_is_dropped() {
+        let db = create_test_db().await;
+        let ready_checks = ReadyChecks::new(Some(15));
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let match_id = start_check(p1, p2, &ready_checks, &db).await;
+        answer(p2, match_id, true, &ready_checks, &db).await;
+
+        let now = battld_common::time() as i64;
+        assert!(expire_ready_checks_logic(now, &Capacity::default(), &RatingWindow::default(), &ready_checks, &EventBus::new(), &db).await.is_empty());
+
+        let messages = expire_ready_checks_logic(now + 15, &Capacity::default(), &RatingWindow::default(), &ready_checks, &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(messages[0].message, ServerMessage::ReadyCheckFailed { requeued: false }));
+        assert!(matches!(messages[2].message, ServerMessage::WaitingForOpponent));
+        assert_eq!(db.get_waiting_match_for_player(p2).await.unwrap().player1_id, p2);
+        assert!(db.get_waiting_match_for_player(p1).await.is_none());
+    }
+}
@@ -0,0 +1,201 @@
+use battld_common::games::matches::MatchStatus;
+use battld_common::{ErrorCode, ServerMessage};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::capacity::{self, Capacity};
+use crate::database::Database;
+use crate::events::{EventBus, MatchEvent};
+use crate::game_logic::{self, OutgoingMessage};
+use crate::game_router;
+
+/// How long a rematch request waits for the opponent to ask too
+const OFFER_SECS: i64 = 60;
+
+struct RematchOffer {
+    player_id: i64,
+    expires_at: i64,
+}
+
+/// Rematch requests waiting for the opponent, keyed by the finished match
+#[derive(Default)]
+pub struct RematchOffers {
+    offers: Mutex<HashMap<i64, RematchOffer>>,
+}
+
+impl RematchOffers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn offer(&self, match_id: i64, player_id: i64, now: i64) {
+        let mut offers = self.offers.lock().unwrap();
+        offers.retain(|_, offer| offer.expires_at > now);
+        offers.insert(match_id, RematchOffer { player_id, expires_at: now + OFFER_SECS });
+    }
+
+    /// Take the request `player_id` made for a match, if it is still standing
+    fn take(&self, match_id: i64, player_id: i64, now: i64) -> bool {
+        let mut offers = self.offers.lock().unwrap();
+        let standing = offers.get(&match_id).is_some_and(|offer| offer.player_id == player_id && offer.expires_at > now);
+        if standing {
+            offers.remove(&match_id);
+        }
+        standing
+    }
+}
+
+fn error(player_id: i64, code: ErrorCode, params: &[(&str, &str)]) -> Vec<OutgoingMessage> {
+    vec![OutgoingMessage {
+        player_id,
+        message: ServerMessage::error(code, params),
+    }]
+}
+
+/// Ask to play a finished match again - returns messages to send
+/// The rematch starts once both players asked for it, with the seats swapped so the other player goes first
+pub async fn handle_request_rematch_logic(
+    player_id: i64,
+    match_id: i64,
+    offers: &RematchOffers,
+    capacity: &Capacity,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    let finished = db.get_match_by_id(match_id).await.and_then(|record| record.to_match()).filter(|match_data| {
+        match_data.status == MatchStatus::Finished
+            && !match_data.ephemeral
+            && (match_data.player1_id == player_id || match_data.player2_id == player_id)
+    });
+    let Some(finished) = finished else {
+        return error(player_id, ErrorCode::RematchUnavailable, &[]);
+    };
+    if let Some(messages) = game_logic::current_match_logic(player_id, db).await {
+        return messages;
+    }
+    let opponent_id = if finished.player1_id == player_id { finished.player2_id } else { finished.player1_id };
+    if db.get_active_match_for_player(opponent_id).await.is_some() {
+        let name = finished.players.iter().find(|player| player.id == opponent_id).map(|player| player.name.clone()).unwrap_or_default();
+        return error(player_id, ErrorCode::PlayerBusy, &[("name", &name)]);
+    }
+
+    let now = battld_common::time() as i64;
+    if !offers.take(match_id, opponent_id, now) {
+        println!("Player {player_id} asked for a rematch of match {match_id}");
+        offers.offer(match_id, player_id, now);
+        return [player_id, opponent_id]
+            .into_iter()
+            .map(|id| OutgoingMessage {
+                player_id: id,
+                message: ServerMessage::RematchOffered { match_id, player_id },
+            })
+            .collect();
+    }
+
+    let load = capacity::load_matches(db).await.unwrap_or_default();
+    if let Err(limit) = capacity.check_new_match(&[player_id, opponent_id], &finished.game_type, &load) {
+        println!("Rematch of match {match_id} could not start: {limit:?}");
+        return vec![OutgoingMessage { player_id, message: capacity.busy_message() }];
+    }
+
+    let options = db.get_match_options(match_id).await.unwrap_or_default();
+    let game_state_json = game_router::initialize_game_state(&finished.game_type, &options);
+    let new_match_id = match db.create_rematch(match_id, finished.player2_id, finished.player1_id, &game_state_json).await {
+        Ok(id) => id,
+        Err(e) => {
+            println!("Failed to create the rematch of match {match_id}: {e}");
+            return error(player_id, ErrorCode::MatchmakingFailed, &[]);
+        }
+    };
+    let Some(match_info) = db.get_match_by_id(new_match_id).await.and_then(|record| record.to_match()) else {
+        return error(player_id, ErrorCode::MatchUnavailable, &[]);
+    };
+
+    println!("Rematch of match {match_id} started as match {new_match_id}");
+    events.publish(MatchEvent::MatchStarted { match_data: match_info.clone() });
+    let mut messages = vec![];
+    for id in [match_info.player1_id, match_info.player2_id] {
+        messages.push(OutgoingMessage {
+            player_id: id,
+            message: ServerMessage::RematchAccepted { match_id, new_match_id },
+        });
+        messages.push(OutgoingMessage {
+            player_id: id,
+            message: ServerMessage::MatchFound {
+                match_data: game_router::redact_match_for_player(&match_info, id),
+            },
+        });
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::game_type::GameType;
+    use battld_common::games::matches::{MatchEndReason, MatchOutcome};
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str) -> i64 {
+        db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name).await.unwrap()
+    }
+
+    async fn create_finished_match(db: &Database, p1: i64, p2: i64) -> i64 {
+        let game_state = game_router::initialize_game_state(&GameType::TicTacToe, &serde_json::Value::Null);
+        let match_id = db.create_match(p1, p2, &game_state, &serde_json::to_string(&GameType::TicTacToe).unwrap()).await.unwrap();
+        let match_data = db.get_match_by_id(match_id).await.unwrap().to_match().unwrap();
+        game_logic::finish_match_logic(match_data, MatchOutcome::Player1Win, MatchEndReason::Resignation, None, &EventBus::new(), db).await;
+        match_id
+    }
+
+    #[tokio::test]
+    async fn test_rematch_starts_once_both_ask() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let match_id = create_finished_match(&db, p1, p2).await;
+        let offers = RematchOffers::new();
+        let capacity = Capacity::default();
+
+        let messages = handle_request_rematch_logic(p1, match_id, &offers, &capacity, &EventBus::new(), &db).await;
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| matches!(m.message, ServerMessage::RematchOffered { player_id, .. } if player_id == p1)));
+
+        // Asking twice does not start it
+        let messages = handle_request_rematch_logic(p1, match_id, &offers, &capacity, &EventBus::new(), &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::RematchOffered { .. }));
+
+        let messages = handle_request_rematch_logic(p2, match_id, &offers, &capacity, &EventBus::new(), &db).await;
+        let rematch = messages.iter().find_map(|m| match &m.message {
+            ServerMessage::MatchFound { match_data } => Some(match_data.clone()),
+            _ => None,
+        }).unwrap();
+        assert_eq!((rematch.player1_id, rematch.player2_id), (p2, p1), "Seats are swapped");
+        assert_eq!(db.get_match_by_id(rematch.id).await.unwrap().rematch_of, Some(match_id));
+        assert!(messages.iter().any(|m| matches!(m.message, ServerMessage::RematchAccepted { new_match_id, .. } if new_match_id == rematch.id)));
+    }
+
+    #[tokio::test]
+    async fn test_rematch_needs_a_finished_match_of_the_player() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let p3 = create_test_player(&db, "player3").await;
+        let match_id = create_finished_match(&db, p1, p2).await;
+        let offers = RematchOffers::new();
+
+        let messages = handle_request_rematch_logic(p3, match_id, &offers, &Capacity::default(), &EventBus::new(), &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::Error { code: ErrorCode::RematchUnavailable, .. }));
+
+        offers.offer(match_id, p1, 1_000);
+        assert!(!offers.take(match_id, p2, 1_010));
+        assert!(!offers.take(match_id, p1, 1_000 + OFFER_SECS));
+    }
+}
@@ -0,0 +1,325 @@
+use battld_common::games::{game_type::GameType, matches::{Match, MatchEndReason, MatchOutcome, MatchStatus}};
+use battld_common::{NotificationChannel, ServerMessage};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::database::Database;
+use crate::events::EventBus;
+use crate::game_logic::{self, OutgoingMessage};
+use crate::game_router;
+use crate::websocket::SharedRegistry;
+
+const DEFAULT_REMINDER_SECS: u64 = 300;
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// When players holding up a match are reminded, and whether they eventually forfeit
+#[derive(Debug, Clone)]
+pub struct ReminderConfig {
+    /// None sends no reminders
+    pub remind_after_secs: Option<u64>,
+    /// Let the opponent know when a reminder goes out
+    pub notify_opponent: bool,
+    /// None never forfeits a match
+    pub forfeit_after_secs: Option<u64>,
+}
+
+impl Default for ReminderConfig {
+    fn default() -> Self {
+        Self {
+            remind_after_secs: Some(DEFAULT_REMINDER_SECS),
+            notify_opponent: false,
+            forfeit_after_secs: None,
+        }
+    }
+}
+
+impl ReminderConfig {
+    /// Read from TURN_REMINDER_SECS, TURN_REMINDER_NOTIFY_OPPONENT and IDLE_FORFEIT_SECS, 0 disables either step
+    pub fn from_env() -> Self {
+        let read = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        Self {
+            remind_after_secs: read("TURN_REMINDER_SECS")
+                .or(Some(DEFAULT_REMINDER_SECS))
+                .filter(|secs| *secs > 0),
+            notify_opponent: std::env::var("TURN_REMINDER_NOTIFY_OPPONENT").ok().as_deref() == Some("true"),
+            forfeit_after_secs: read("IDLE_FORFEIT_SECS").filter(|secs| *secs > 0),
+        }
+    }
+}
+
+/// Since when each active match has been waiting, matches move on when their seq changes
+#[derive(Default)]
+pub struct IdleTracker {
+    matches: Mutex<HashMap<i64, IdleMatch>>,
+}
+
+struct IdleMatch {
+    seq: i64,
+    since: i64,
+    reminded: bool,
+}
+
+/// Check the active matches every 30 seconds
+pub fn spawn_reminders(db: Arc<Database>, registry: SharedRegistry, config: ReminderConfig) -> tokio::task::JoinHandle<()> {
+    let tracker = IdleTracker::default();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let now = battld_common::time() as i64;
+            let messages = check_idle_matches_logic(now, &config, &tracker, registry.events(), &db).await;
+            registry.send_messages(messages).await;
+        }
+    })
+}
+
+/// Remind players who have not moved for a while, forfeiting the matches they held up too long
+/// Reminders wait for the end of the player's quiet hours, the time to a forfeit keeps running meanwhile
+pub async fn check_idle_matches_logic(
+    now: i64,
+    config: &ReminderConfig,
+    tracker: &IdleTracker,
+    events: &EventBus,
+    db: &Database,
+) -> Vec<OutgoingMessage> {
+    // The idle time of paused matches only counts in games whose clocks keep running while a player is away
+    let records = match db.get_matches_in_play().await {
+        Ok(records) => records
+            .into_iter()
+            .filter(|record| {
+                record.status == MatchStatus::Active.as_str()
+                    || serde_json::from_str::<GameType>(&record.game_type)
+                        .is_ok_and(|game_type| game_router::disconnect_policy(&game_type).clocks_keep_running)
+            })
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            println!("Failed to load active matches for turn reminders: {e}");
+            return vec![];
+        }
+    };
+
+    let schedules = db.get_quiet_hours_schedules().await.unwrap_or_else(|e| {
+        println!("Failed to load quiet hours for turn reminders: {e}");
+        HashMap::new()
+    });
+    let silenced = |player_id: &i64, channel: NotificationChannel| {
+        schedules
+            .get(player_id)
+            .is_some_and(|(quiet_hours, utc_offset_minutes)| quiet_hours.silences(channel, now, *utc_offset_minutes))
+    };
+
+    let mut messages = vec![];
+    let mut forfeits = vec![];
+    {
+        let mut matches = tracker.matches.lock().unwrap();
+        matches.retain(|match_id, _| records.iter().any(|record| record.id == *match_id));
+
+        for record in &records {
+            let idle = matches.entry(record.id).or_insert(IdleMatch { seq: record.seq, since: now, reminded: false });
+            if idle.seq != record.seq {
+                *idle = IdleMatch { seq: record.seq, since: now, reminded: false };
+            }
+            let idle_secs = (now - idle.since).max(0) as u64;
+
+            let Some(match_data) = record.to_match() else {
+                continue;
+            };
+            let idle_players = game_router::players_to_move(&match_data);
+            if idle_players.is_empty() {
+                continue;
+            }
+
+            if config.forfeit_after_secs.is_some_and(|limit| idle_secs >= limit) {
+                forfeits.push((match_data, idle_players));
+                continue;
+            }
+            if idle.reminded || config.remind_after_secs.is_none_or(|limit| idle_secs < limit) {
+                continue;
+            }
+            if idle_players.iter().all(|player_id| silenced(player_id, NotificationChannel::TurnReminders)) {
+                continue;
+            }
+            idle.reminded = true;
+
+            let forfeit_in = config.forfeit_after_secs.map(|limit| limit - idle_secs);
+            for player_id in [match_data.player1_id, match_data.player2_id] {
+                let message = if idle_players.contains(&player_id) {
+                    if silenced(&player_id, NotificationChannel::TurnReminders) {
+                        continue;
+                    }
+                    ServerMessage::TurnReminder { match_id: match_data.id, idle_secs, forfeit_in }
+                } else if config.notify_opponent && !silenced(&player_id, NotificationChannel::OpponentIdle) {
+                    ServerMessage::OpponentIdle { match_id: match_data.id, idle_secs }
+                } else {
+                    continue;
+                };
+                messages.push(OutgoingMessage { player_id, message });
+            }
+        }
+    }
+
+    for (match_data, idle_players) in forfeits {
+        messages.extend(forfeit_logic(match_data, &idle_players, events, db).await);
+    }
+    messages
+}
+
+/// The player who held the match up loses it, a draw if both did
+pub async fn forfeit_logic(match_data: Match, idle_players: &[i64], events: &EventBus, db: &Database) -> Vec<OutgoingMessage> {
+    let outcome = match idle_players {
+        [player_id] if *player_id == match_data.player1_id => MatchOutcome::Player2Win,
+        [_] => MatchOutcome::Player1Win,
+        _ => MatchOutcome::Draw,
+    };
+    let match_id = match_data.id;
+    let messages = game_logic::finish_match_logic(match_data, outcome, MatchEndReason::Forfeit, None, events, db).await;
+    if !messages.is_empty() {
+        println!("Match {match_id} forfeited by idle players {idle_players:?}");
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::QuietHours;
+    use server::games::tic_tac_toe::TicTacToeGameState;
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str) -> i64 {
+        db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name).await.unwrap()
+    }
+
+    async fn create_test_match(db: &Database, p1: i64, p2: i64) -> i64 {
+        let game_state = serde_json::to_string(&TicTacToeGameState::new()).unwrap();
+        let game_type = serde_json::to_string(&GameType::TicTacToe).unwrap();
+        db.create_match(p1, p2, &game_state, &game_type).await.unwrap()
+    }
+
+    fn config(notify_opponent: bool, forfeit_after_secs: Option<u64>) -> ReminderConfig {
+        ReminderConfig { remind_after_secs: Some(60), notify_opponent, forfeit_after_secs }
+    }
+
+    #[tokio::test]
+    async fn test_reminds_idle_player_once() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let match_id = create_test_match(&db, p1, p2).await;
+        let tracker = IdleTracker::default();
+        let events = EventBus::new();
+        let config = config(true, None);
+
+        assert!(check_idle_matches_logic(1_000, &config, &tracker, &events, &db).await.is_empty());
+        assert!(check_idle_matches_logic(1_030, &config, &tracker, &events, &db).await.is_empty());
+
+        let messages = check_idle_matches_logic(1_060, &config, &tracker, &events, &db).await;
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].player_id, p1);
+        assert!(matches!(messages[0].message, ServerMessage::TurnReminder { match_id: id, idle_secs: 60, forfeit_in: None } if id == match_id));
+        assert_eq!(messages[1].player_id, p2);
+        assert!(matches!(messages[1].message, ServerMessage::OpponentIdle { idle_secs: 60, .. }));
+
+        assert!(check_idle_matches_logic(1_200, &config, &tracker, &events, &db).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_move_resets_idle_time() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let match_id = create_test_match(&db, p1, p2).await;
+        let tracker = IdleTracker::default();
+        let events = EventBus::new();
+        let config = config(false, None);
+
+        check_idle_matches_logic(1_000, &config, &tracker, &events, &db).await;
+        let mut game_state = TicTacToeGameState::new();
+        game_state.board[0] = 1;
+        game_state.current_player = 2;
+        let game_state = serde_json::to_string(&game_state).unwrap();
+        db.update_match(match_id, &game_state, MatchStatus::Active, None).await.unwrap();
+        assert!(check_idle_matches_logic(1_050, &config, &tracker, &events, &db).await.is_empty());
+
+        let messages = check_idle_matches_logic(1_110, &config, &tracker, &events, &db).await;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].player_id, p2);
+    }
+
+    #[tokio::test]
+    async fn test_forfeits_after_limit() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let match_id = create_test_match(&db, p1, p2).await;
+        let tracker = IdleTracker::default();
+        let events = EventBus::new();
+        let config = config(false, Some(300));
+
+        check_idle_matches_logic(1_000, &config, &tracker, &events, &db).await;
+        let messages = check_idle_matches_logic(1_100, &config, &tracker, &events, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::TurnReminder { forfeit_in: Some(200), .. }));
+
+        let messages = check_idle_matches_logic(1_300, &config, &tracker, &events, &db).await;
+        assert_eq!(messages.len(), 4);
+        assert!(messages.iter().any(|m| matches!(m.message, ServerMessage::MatchEnded { reason: MatchEndReason::Forfeit, .. })));
+
+        let record = db.get_match_by_id(match_id).await.unwrap();
+        assert_eq!(record.status, MatchStatus::Finished.as_str());
+        assert_eq!(record.outcome(), Some(MatchOutcome::Player2Win));
+        assert!(check_idle_matches_logic(1_400, &config, &tracker, &events, &db).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reminders_wait_for_quiet_hours_to_end() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        create_test_match(&db, p1, p2).await;
+        let quiet_hours = QuietHours { start_minute: 0, end_minute: 60, except: vec![] };
+        db.set_quiet_hours(p1, Some(&quiet_hours), p1).await.unwrap();
+        let opponent_quiet_hours = QuietHours { end_minute: 120, except: vec![NotificationChannel::TurnReminders], ..quiet_hours.clone() };
+        db.set_quiet_hours(p2, Some(&opponent_quiet_hours), p2).await.unwrap();
+        let tracker = IdleTracker::default();
+        let events = EventBus::new();
+        let config = config(true, None);
+
+        check_idle_matches_logic(1_000, &config, &tracker, &events, &db).await;
+        assert!(check_idle_matches_logic(1_060, &config, &tracker, &events, &db).await.is_empty());
+
+        // 01:00 UTC, p1's quiet hours are over but p2's still hold back idle notices
+        let messages = check_idle_matches_logic(3_600, &config, &tracker, &events, &db).await;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].player_id, p1);
+        assert!(matches!(messages[0].message, ServerMessage::TurnReminder { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_paused_matches_count_idle_time_only_where_clocks_keep_running() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let tic_tac_toe = create_test_match(&db, p1, p2).await;
+        let game_state = serde_json::to_string(&battld_common::games::rock_paper_scissors::RockPaperScissorsGameState::new()).unwrap();
+        let game_type = serde_json::to_string(&GameType::RockPaperScissors).unwrap();
+        let rock_paper_scissors = db.create_match(p1, p2, &game_state, &game_type).await.unwrap();
+        for match_id in [tic_tac_toe, rock_paper_scissors] {
+            db.transition_match(match_id, MatchStatus::Active, MatchStatus::Paused).await.unwrap();
+        }
+        let tracker = IdleTracker::default();
+        let events = EventBus::new();
+        let config = config(false, None);
+
+        check_idle_matches_logic(1_000, &config, &tracker, &events, &db).await;
+        let messages = check_idle_matches_logic(1_060, &config, &tracker, &events, &db).await;
+        assert!(!messages.is_empty());
+        assert!(messages.iter().all(|m| matches!(m.message, ServerMessage::TurnReminder { match_id, .. } if match_id == rock_paper_scissors)));
+    }
+}
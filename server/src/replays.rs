@@ -0,0 +1,297 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use battld_common::{games::{matches::{Match, MatchStatus}, players::Seat}, MoveAnnotation, ReplayFrame, ReplayLink, ReplayPrivacy, ReplayPrivacyRequest, ReplayResponse, ReplaySettings};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::database::{Database, MatchFrameRecord, MatchRecord};
+use crate::events::{EventBus, MatchEvent};
+use crate::storage::{self, Storage};
+use crate::{auth, game_router, AppState};
+
+const RECENT_REPLAYS: i64 = 10;
+
+/// Everything a replay needs, kept in storage once the match is compacted
+#[derive(Serialize, Deserialize)]
+pub struct ReplayArchive {
+    pub game_state: String,
+    pub frames: Vec<MatchFrameRecord>,
+}
+
+/// Frames and final state archived for a compacted match, if any
+async fn load_archive(storage: &Storage, match_id: i64) -> Option<ReplayArchive> {
+    match storage.get(&storage::replay_key(match_id)).await {
+        Ok(data) => serde_json::from_slice(&data?).ok(),
+        Err(e) => {
+            println!("Failed to load the replay archive of match {match_id}: {e}");
+            None
+        }
+    }
+}
+
+/// Record every state of every match and hand out a replay token once it is finished
+pub fn spawn_recorder(bus: &EventBus, db: Arc<Database>) -> tokio::task::JoinHandle<()> {
+    let mut rx = bus.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => record_event(&db, &event).await,
+                Err(RecvError::Lagged(missed)) => {
+                    println!("Replay recorder fell behind, {missed} events skipped");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+async fn record_event(db: &Database, event: &MatchEvent) {
+    let result = match event {
+        MatchEvent::MatchStarted { match_data } => add_frame(db, match_data, None, None).await,
+        MatchEvent::MoveMade { match_data, player_id, move_data } => {
+            add_frame(db, match_data, Some(*player_id), Some(move_data)).await
+        }
+        MatchEvent::MatchFinished { match_data } => {
+            db.set_replay_token(match_data.id, &Uuid::new_v4().simple().to_string()).await
+        }
+    };
+    if let Err(e) = result {
+        println!("Failed to record replay of match {}: {e}", event.match_data().id);
+    }
+}
+
+async fn add_frame(
+    db: &Database,
+    match_data: &Match,
+    player_id: Option<i64>,
+    move_data: Option<&serde_json::Value>,
+) -> Result<(), sqlx::Error> {
+    let game_state = serde_json::to_string(&match_data.game_state).unwrap();
+    let move_data = move_data.map(|move_data| move_data.to_string());
+    db.add_match_frame(match_data.id, player_id, move_data.as_deref(), &game_state).await
+}
+
+/// Players always see their own matches, anyone else needs both players' privacy to allow it
+pub async fn can_watch(db: &Database, record: &MatchRecord, viewer: Option<i64>) -> Result<bool, sqlx::Error> {
+    for player_id in [record.player1_id, record.player2_id] {
+        if viewer == Some(player_id) {
+            return Ok(true);
+        }
+    }
+    for player_id in [record.player1_id, record.player2_id] {
+        let allowed = match db.get_replay_privacy(player_id).await? {
+            ReplayPrivacy::Public => true,
+            ReplayPrivacy::Friends => match viewer {
+                Some(viewer) => db.is_friend(player_id, viewer).await?,
+                None => false,
+            },
+            ReplayPrivacy::Private => false,
+        };
+        if !allowed {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn build_replay(match_data: &Match, frames: &[MatchFrameRecord], analysis: Vec<MoveAnnotation>) -> ReplayResponse {
+    let frames = frames
+        .iter()
+        .filter_map(|frame| {
+            let game_state = serde_json::from_str(&frame.game_state).ok()?;
+            let redacted = game_router::redact_match_for_spectator(&Match { game_state, ..match_data.clone() });
+            Some(ReplayFrame {
+                player_id: frame.player_id,
+                game_state: redacted.game_state,
+                move_data: frame.move_data.as_deref().and_then(|move_data| serde_json::from_str(move_data).ok()),
+                played_at: frame.created_at,
+            })
+        })
+        .collect();
+
+    ReplayResponse {
+        match_data: game_router::redact_match_for_spectator(match_data),
+        frames,
+        summary: game_router::match_summary(match_data),
+        analysis,
+    }
+}
+
+/// Replay behind a shared link, login is only needed for matches restricted to friends
+pub async fn get_replay(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(token): Path<String>,
+) -> Result<Json<ReplayResponse>, StatusCode> {
+    let viewer = auth::authenticate_request(&state.session_cache, &headers).await.ok();
+    let record = state.db.get_match_by_replay_token(&token).await.ok_or(StatusCode::NOT_FOUND)?;
+
+    if !can_watch(&state.db, &record, viewer).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    load_replay(&state, &record).await.map(Json)
+}
+
+/// Replay of a finished match by its id, for its players and whoever their replay privacy lets watch
+pub async fn get_match_replay(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(match_id): Path<i64>,
+) -> Result<Json<ReplayResponse>, StatusCode> {
+    let viewer = auth::authenticate_request(&state.session_cache, &headers).await?;
+    let record = state.db.get_match_by_id(match_id).await.ok_or(StatusCode::NOT_FOUND)?;
+
+    if !can_watch(&state.db, &record, Some(viewer)).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if MatchStatus::parse(&record.status) != Some(MatchStatus::Finished) {
+        return Err(StatusCode::CONFLICT);
+    }
+    load_replay(&state, &record).await.map(Json)
+}
+
+/// Frames of a match, from storage once it was compacted
+pub async fn load_replay(state: &AppState, record: &MatchRecord) -> Result<ReplayResponse, StatusCode> {
+    let mut match_data = record.to_match().ok_or(StatusCode::NOT_FOUND)?;
+    let mut frames = state.db.get_match_frames(record.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if frames.is_empty() {
+        if let Some(archive) = load_archive(&state.storage, record.id).await {
+            match_data.game_state = serde_json::from_str(&archive.game_state).unwrap_or(match_data.game_state);
+            frames = archive.frames;
+        }
+    }
+    let analysis = state.db.get_match_analysis(record.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(build_replay(&match_data, &frames, analysis))
+}
+
+pub async fn get_replay_settings(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ReplaySettings>, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+
+    let privacy = state.db.get_replay_privacy(player_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let recent = state.db.get_replayable_matches_for_player(player_id, RECENT_REPLAYS)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter_map(|record| {
+            Some(ReplayLink {
+                match_id: record.id,
+                game_type: serde_json::from_str(&record.game_type).ok()?,
+                token: record.replay_token?,
+                seat: if record.player1_id == player_id { Seat::FIRST } else { Seat::SECOND },
+                finished_at: record.finished_at,
+            })
+        })
+        .collect();
+
+    Ok(Json(ReplaySettings { privacy, recent }))
+}
+
+pub async fn set_replay_privacy(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<ReplayPrivacyRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+
+    state.db.set_replay_privacy(player_id, request.privacy, player_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::{game_type::GameType, matches::MatchStatus};
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str) -> i64 {
+        db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name)
+            .await
+            .unwrap()
+    }
+
+    async fn create_test_match(db: &Database, p1: i64, p2: i64) -> Match {
+        let game_type = serde_json::to_string(&GameType::TicTacToe).unwrap();
+        let match_id = db.create_match(p1, p2, r#"{"board":[0,0,0,0,0,0,0,0,0]}"#, &game_type).await.unwrap();
+        db.get_match_by_id(match_id).await.unwrap().to_match().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_records_frames_and_token() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let mut match_data = create_test_match(&db, p1, p2).await;
+
+        record_event(&db, &MatchEvent::MatchStarted { match_data: match_data.clone() }).await;
+        match_data.game_state = serde_json::json!({ "board": [1, 0, 0, 0, 0, 0, 0, 0, 0] });
+        let move_data = serde_json::json!({ "row": 0, "col": 0 });
+        record_event(&db, &MatchEvent::MoveMade { match_data: match_data.clone(), player_id: p1, move_data }).await;
+        assert!(db.get_match_by_id(match_data.id).await.unwrap().replay_token.is_none());
+
+        db.update_match(match_data.id, "{}", MatchStatus::Finished, None).await.unwrap();
+        record_event(&db, &MatchEvent::MatchFinished { match_data: match_data.clone() }).await;
+        let token = db.get_match_by_id(match_data.id).await.unwrap().replay_token.unwrap();
+
+        record_event(&db, &MatchEvent::MatchFinished { match_data: match_data.clone() }).await;
+        let record = db.get_match_by_replay_token(&token).await.unwrap();
+        assert_eq!(record.id, match_data.id);
+
+        let frames = db.get_match_frames(match_data.id).await.unwrap();
+        let replay = build_replay(&record.to_match().unwrap(), &frames, vec![]);
+        assert_eq!(replay.frames.len(), 2);
+        assert_eq!(replay.frames[0].player_id, None);
+        assert_eq!(replay.frames[1].player_id, Some(p1));
+        assert_eq!(replay.frames[1].game_state["board"][0], 1);
+        assert_eq!(replay.frames[0].move_data, None);
+        assert_eq!(replay.frames[1].move_data, Some(serde_json::json!({ "row": 0, "col": 0 })));
+        assert!(replay.frames.iter().all(|frame| frame.played_at.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_privacy() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let friend = create_test_player(&db, "friend").await;
+        let stranger = create_test_player(&db, "stranger").await;
+        let match_data = create_test_match(&db, p1, p2).await;
+        let record = db.get_match_by_id(match_data.id).await.unwrap();
+        db.add_friend(p1, friend).await.unwrap();
+        db.add_friend(p2, friend).await.unwrap();
+
+        assert!(can_watch(&db, &record, None).await.unwrap());
+
+        db.set_replay_privacy(p1, ReplayPrivacy::Friends, p1).await.unwrap();
+        assert!(!can_watch(&db, &record, None).await.unwrap());
+        assert!(!can_watch(&db, &record, Some(stranger)).await.unwrap());
+        assert!(can_watch(&db, &record, Some(friend)).await.unwrap());
+
+        db.set_replay_privacy(p2, ReplayPrivacy::Private, p2).await.unwrap();
+        assert!(!can_watch(&db, &record, Some(friend)).await.unwrap());
+        assert!(can_watch(&db, &record, Some(p1)).await.unwrap());
+        assert!(can_watch(&db, &record, Some(p2)).await.unwrap());
+    }
+}
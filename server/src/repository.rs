@@ -1,5 +1,6 @@
 use battld_common::*;
 use crate::database::{Database, PlayerRecord};
+use crate::log_privacy;
 
 pub async fn fetch_player(database: &Database, player_id: i64) -> Option<Player> {
     println!("Fetching player {player_id} from database");
@@ -9,11 +10,25 @@ pub async fn fetch_player(database: &Database, player_id: i64) -> Option<Player>
         _ => return None
     };
 
-    Some(record.to_player())
+    let mut player = record.to_player();
+    if let Ok((name_changed_at, settings_changed_at)) = database.get_last_setting_changes(player_id).await {
+        player.name_changed_at = name_changed_at;
+        player.settings_changed_at = settings_changed_at;
+    }
+    Some(player)
+}
+
+/// Players named `name`, ignoring case
+pub async fn find_players(database: &Database, name: &str) -> Result<Vec<Player>, sqlx::Error> {
+    Ok(database.find_players_by_name(name).await?.iter().map(PlayerRecord::to_player).collect())
 }
 
 pub async fn create_player(database: &Database, name: &str, public_key_hint: &str, public_key: &str) -> Option<i64> {
-    println!("REPO: Creating player: name='{name}', public_key_hint='{public_key_hint}'");
+    println!(
+        "REPO: Creating player: name='{}', public_key_hint='{}'",
+        log_privacy::name(name),
+        log_privacy::secret(public_key_hint)
+    );
     match database.create_player(public_key_hint, public_key, name).await {
         Some(id) => {
             println!("REPO: Player created successfully with ID: {id}");
@@ -34,6 +49,8 @@ impl PlayerRecord {
             public_key: "redacted".to_string(),
             name: self.name.clone(),
             score: self.score,
+            name_changed_at: None,
+            settings_changed_at: None,
         }
     }
 }
@@ -0,0 +1,227 @@
+use axum::{extract::State, http::{HeaderMap, StatusCode}, Json};
+use battld_common::MaintenanceReport;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::database::Database;
+use crate::replays::ReplayArchive;
+use crate::storage::{self, Storage};
+use crate::{auth, AppState};
+
+const DEFAULT_RETENTION_MONTHS: i64 = 12;
+const SECS_PER_MONTH: i64 = 30 * 24 * 3600;
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+
+/// How long finished matches keep their full state
+#[derive(Debug, Clone)]
+pub struct RetentionConfig {
+    /// None keeps every match as is
+    pub retention_months: Option<i64>,
+}
+
+impl RetentionConfig {
+    /// Read from MATCH_RETENTION_MONTHS, 0 disables compaction
+    pub fn from_env() -> Self {
+        let months = std::env::var("MATCH_RETENTION_MONTHS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_RETENTION_MONTHS);
+        Self {
+            retention_months: (months > 0).then_some(months),
+        }
+    }
+}
+
+/// What is left of a compacted match: who played what, in order
+#[derive(Serialize)]
+struct MatchSummary {
+    compacted: bool,
+    moves: Vec<SummaryMove>,
+}
+
+#[derive(Serialize)]
+struct SummaryMove {
+    player_id: i64,
+    move_data: serde_json::Value,
+}
+
+/// Archive and compact finished matches past the retention period, vacuum if anything changed,
+/// then back the database up and apply the lifecycle rules of the storage
+pub async fn run_maintenance(db: &Database, config: &RetentionConfig, storage: &Storage, now: i64) -> Result<MaintenanceReport, sqlx::Error> {
+    let mut report = MaintenanceReport::default();
+
+    if let Some(months) = config.retention_months {
+        for match_id in db.get_matches_to_compact(now - months * SECS_PER_MONTH).await? {
+            let Some(record) = db.get_match_by_id(match_id).await else { continue };
+            let archive = ReplayArchive { game_state: record.game_state, frames: db.get_match_frames(match_id).await? };
+            if let Err(e) = storage.put(&storage::replay_key(match_id), serde_json::to_vec(&archive).unwrap()).await {
+                println!("Maintenance: match {match_id} not compacted, its replay could not be archived: {e}");
+                continue;
+            }
+
+            let moves = archive.frames
+                .iter()
+                .filter_map(|frame| {
+                    Some(SummaryMove {
+                        player_id: frame.player_id?,
+                        move_data: serde_json::from_str(frame.move_data.as_deref()?).ok()?,
+                    })
+                })
+                .collect();
+            let summary = serde_json::to_string(&MatchSummary { compacted: true, moves }).unwrap();
+            db.compact_match(match_id, &summary).await?;
+            report.compacted_matches += 1;
+        }
+
+        report.vacuumed = report.compacted_matches > 0;
+        if report.vacuumed {
+            db.vacuum().await?;
+        }
+        println!("Maintenance: compacted {} finished matches", report.compacted_matches);
+    }
+
+    match back_up(db, storage, now).await {
+        Ok(key) => report.backup = Some(key),
+        Err(e) => println!("Maintenance: backup failed: {e}"),
+    }
+    match storage.apply_lifecycle(now).await {
+        Ok(expired) => report.expired_objects = expired,
+        Err(e) => println!("Maintenance: lifecycle rules not applied: {e}"),
+    }
+    Ok(report)
+}
+
+/// Copy the database into storage, returns the key of the copy
+pub async fn back_up(db: &Database, storage: &Storage, now: i64) -> Result<String, String> {
+    let path = std::env::temp_dir().join(format!("battld-backup-{}.db", uuid::Uuid::new_v4()));
+    db.backup_into(&path.to_string_lossy()).await.map_err(|e| format!("Failed to copy the database: {e}"))?;
+    let data = tokio::fs::read(&path).await.map_err(|e| format!("Failed to read the copy: {e}"));
+    let _ = tokio::fs::remove_file(&path).await;
+
+    let key = format!("{}game-{now}.db", storage::BACKUPS);
+    storage.put(&key, data?).await?;
+    Ok(key)
+}
+
+/// Run maintenance once a day
+pub fn spawn_maintenance(db: Arc<Database>, config: RetentionConfig, storage: Arc<Storage>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(MAINTENANCE_INTERVAL).await;
+            if let Err(e) = run_maintenance(&db, &config, &storage, battld_common::time() as i64).await {
+                println!("Maintenance failed: {e}");
+            }
+        }
+    })
+}
+
+/// Run maintenance now, admins only
+pub async fn post_maintenance(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<MaintenanceReport>, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+    if !state.capacity.is_admin(player_id) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    run_maintenance(&state.db, &state.retention, &state.storage, battld_common::time() as i64)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::{game_type::GameType, matches::MatchStatus};
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    fn temp_storage() -> (Storage, std::path::PathBuf) {
+        let root = std::env::temp_dir().join(format!("battld-maintenance-{}", uuid::Uuid::new_v4()));
+        (Storage::local(&root, vec![]), root)
+    }
+
+    async fn create_finished_match(db: &Database) -> i64 {
+        let p1 = db.create_player("p1_hint", "p1_key", "player1").await.unwrap();
+        let p2 = db.create_player("p2_hint", "p2_key", "player2").await.unwrap();
+        let game_type = serde_json::to_string(&GameType::TicTacToe).unwrap();
+        let match_id = db.create_match(p1, p2, "{}", &game_type).await.unwrap();
+
+        db.add_match_frame(match_id, None, None, "{}").await.unwrap();
+        db.add_match_frame(match_id, Some(p1), Some(r#"{"row":1,"col":1}"#), r#"{"board":[0,0,0,0,1,0,0,0,0]}"#).await.unwrap();
+        db.update_match(match_id, r#"{"board":[0,0,0,0,1,0,0,0,0]}"#, MatchStatus::Finished, None).await.unwrap();
+        match_id
+    }
+
+    #[tokio::test]
+    async fn test_compacts_old_matches() {
+        let db = create_test_db().await;
+        let match_id = create_finished_match(&db).await;
+        let config = RetentionConfig { retention_months: Some(6) };
+        let (storage, root) = temp_storage();
+        let now = battld_common::time() as i64;
+
+        let report = run_maintenance(&db, &config, &storage, now).await.unwrap();
+        assert_eq!(report.compacted_matches, 0);
+
+        let report = run_maintenance(&db, &config, &storage, now + 7 * SECS_PER_MONTH).await.unwrap();
+        assert_eq!(report.compacted_matches, 1);
+        assert!(report.vacuumed);
+
+        let record = db.get_match_by_id(match_id).await.unwrap();
+        let summary: serde_json::Value = serde_json::from_str(&record.game_state).unwrap();
+        assert_eq!(summary["compacted"], true);
+        assert_eq!(summary["moves"].as_array().unwrap().len(), 1);
+        assert_eq!(summary["moves"][0]["move_data"]["row"], 1);
+        assert!(db.get_match_frames(match_id).await.unwrap().is_empty());
+
+        let archived = storage.get(&storage::replay_key(match_id)).await.unwrap().unwrap();
+        let archive: ReplayArchive = serde_json::from_slice(&archived).unwrap();
+        assert_eq!(archive.frames.len(), 2);
+        assert_eq!(archive.game_state, r#"{"board":[0,0,0,0,1,0,0,0,0]}"#);
+
+        let report = run_maintenance(&db, &config, &storage, now + 7 * SECS_PER_MONTH).await.unwrap();
+        assert_eq!(report.compacted_matches, 0);
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_backs_up_the_database() {
+        let (storage, root) = temp_storage();
+        std::fs::create_dir_all(&root).unwrap();
+        // In-memory databases can't be copied to a file
+        let db = Database::new(&format!("sqlite://{}", root.join("game.db").display())).await.unwrap();
+        db.initialize().await.unwrap();
+        create_finished_match(&db).await;
+
+        let report = run_maintenance(&db, &RetentionConfig { retention_months: None }, &storage, 1_000).await.unwrap();
+        let key = report.backup.unwrap();
+        assert_eq!(key, "backups/game-1000.db");
+
+        let copy = root.join(&key);
+        let backup = Database::new(&format!("sqlite://{}", copy.display())).await.unwrap();
+        assert_eq!(backup.get_player_by_id(1).await.unwrap().name, "player1");
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_retention_keeps_matches() {
+        let db = create_test_db().await;
+        create_finished_match(&db).await;
+
+        let config = RetentionConfig { retention_months: None };
+        let (storage, root) = temp_storage();
+        let report = run_maintenance(&db, &config, &storage, i64::MAX / 2).await.unwrap();
+        assert_eq!(report.compacted_matches, 0);
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::database::{Database, SeasonRecord};
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a season lasts, seasons are off without one
+#[derive(Debug, Clone, Default)]
+pub struct SeasonConfig {
+    pub length_secs: Option<i64>,
+}
+
+impl SeasonConfig {
+    /// Read from SEASON_DAYS, unset or 0 runs no seasons
+    pub fn from_env() -> Self {
+        Self {
+            length_secs: std::env::var("SEASON_DAYS")
+                .ok()
+                .and_then(|days| days.parse::<i64>().ok())
+                .filter(|days| *days > 0)
+                .map(|days| days * DAY_SECS),
+        }
+    }
+}
+
+/// Check every minute whether the season is over, starting the first one right away
+pub fn spawn_rollover(db: Arc<Database>, config: SeasonConfig) -> Option<tokio::task::JoinHandle<()>> {
+    let length_secs = config.length_secs?;
+    Some(tokio::spawn(async move {
+        loop {
+            let now = battld_common::time() as i64;
+            if let Err(e) = rollover_logic(now, length_secs, &db).await {
+                println!("Failed to roll the season over: {e}");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    }))
+}
+
+/// The season being played at `now`, archiving the one that ended and starting the next
+/// A season follows on from the previous one, or starts at `now` when the server was down for longer than a season
+pub async fn rollover_logic(now: i64, length_secs: i64, db: &Database) -> Result<SeasonRecord, sqlx::Error> {
+    let starts_at = match db.get_current_season().await? {
+        Some(season) if now < season.ends_at => return Ok(season),
+        Some(season) => {
+            db.archive_season(season.id).await?;
+            println!("Season {} ended, scores were reset", season.id);
+            if season.ends_at + length_secs > now { season.ends_at } else { now }
+        }
+        None => now,
+    };
+    let season = db.create_season(starts_at, starts_at + length_secs).await?;
+    println!("Season {} started", season.id);
+    Ok(season)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::{game_type::GameType, matches::{MatchOutcome, MatchStatus}};
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str, score: i64) -> i64 {
+        let id = db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name).await.unwrap();
+        sqlx::query("UPDATE players SET score = ? WHERE id = ?")
+            .bind(score)
+            .bind(id)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        id
+    }
+
+    #[tokio::test]
+    async fn test_rollover_archives_standings_and_resets_scores() {
+        let db = create_test_db().await;
+        let alice = create_test_player(&db, "alice", 5).await;
+        let bob = create_test_player(&db, "bob", 9).await;
+        let carol = create_test_player(&db, "carol", -2).await;
+        let dave = create_test_player(&db, "dave", 0).await;
+        create_test_player(&db, "erin", 0).await;
+
+        let first = rollover_logic(1000, 100, &db).await.unwrap();
+        let game_type = serde_json::to_string(&GameType::TicTacToe).unwrap();
+        let match_id = db.create_match(dave, carol, "{}", &game_type).await.unwrap();
+        let outcome = serde_json::to_string(&MatchOutcome::Draw).unwrap();
+        db.update_match(match_id, "{}", MatchStatus::Finished, Some(&outcome)).await.unwrap();
+        assert_eq!((first.id, first.starts_at, first.ends_at), (1, 1000, 1100));
+        assert_eq!(rollover_logic(1099, 100, &db).await.unwrap().id, 1);
+
+        let second = rollover_logic(1150, 100, &db).await.unwrap();
+        assert_eq!((second.id, second.starts_at, second.ends_at), (2, 1100, 1200));
+        assert!(db.get_season(1).await.unwrap().archived);
+
+        let standings: Vec<(i64, i64, i64)> = sqlx::query_as("SELECT player_id, rank, score FROM season_standings WHERE season_id = 1 ORDER BY rank")
+            .fetch_all(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(standings, vec![(bob, 1, 9), (alice, 2, 5), (dave, 3, 0), (carol, 4, -2)]);
+        for player_id in [alice, bob, carol] {
+            assert_eq!(db.get_player_by_id(player_id).await.unwrap().score, 0);
+        }
+
+        let after_downtime = rollover_logic(5000, 100, &db).await.unwrap();
+        assert_eq!((after_downtime.id, after_downtime.starts_at), (3, 5000));
+    }
+}
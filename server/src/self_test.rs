@@ -0,0 +1,211 @@
+use battld_common::games::{
+    briscola::{BriscolaGameState, RoundState},
+    chess::{ChessMove, ChessPosition},
+    game_type::GameType,
+    matches::{Match, MatchOutcome, MatchStatus},
+    rock_paper_scissors::{RockPaperScissorsGameState, RockPaperScissorsMove},
+    tic_tac_toe::TicTacToeGameState,
+};
+use serde_json::{json, Value as JsonValue};
+use std::path::Path;
+
+use crate::database::Database;
+use server::game_router;
+
+/// Longest scripted game, a Briscola deal takes 40 plays
+const MAX_PLIES: usize = 100;
+
+/// Fool's mate, as (from, to) in (row, col)
+const CHESS_SCRIPT: [((u8, u8), (u8, u8)); 4] = [((1, 5), (2, 5)), ((6, 4), (4, 4)), ((1, 6), (3, 6)), ((7, 3), (3, 7))];
+
+/// `server --self-test`: the migrations against a scratch database, a scripted game through every engine
+/// and the redaction of every state it went through, as a gate before going live
+pub async fn run() -> Result<(), String> {
+    let path = std::env::temp_dir().join(format!("battld-self-test-{}.db", std::process::id()));
+    let result = run_against(&path).await;
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+    result
+}
+
+async fn run_against(path: &Path) -> Result<(), String> {
+    let db = Database::new(&format!("sqlite://{}", path.display()))
+        .await
+        .map_err(|e| format!("Opening the scratch database failed: {e}"))?;
+    db.initialize().await.map_err(|e| format!("Migrations failed: {e}"))?;
+    println!("Self-test: migrations ok");
+
+    let mut players = vec![];
+    for name in ["self_test_1", "self_test_2"] {
+        let player_id = db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name)
+            .await
+            .ok_or_else(|| format!("Creating player {name} failed"))?;
+        players.push(player_id);
+    }
+
+    for game_type in GameType::ALL {
+        play_scripted_game(&db, &game_type, players[0], players[1])
+            .await
+            .map_err(|e| format!("{game_type}: {e}"))?;
+        println!("Self-test: {game_type} ok");
+    }
+    Ok(())
+}
+
+/// Play a game to the end through `game_router`, storing every state and checking its redaction
+async fn play_scripted_game(db: &Database, game_type: &GameType, player1_id: i64, player2_id: i64) -> Result<(), String> {
+    let options = game_router::normalize_game_options(game_type, &JsonValue::Null).map_err(|e| e.to_string())?;
+    let game_state = game_router::initialize_game_state(game_type, &options);
+    let game_type_json = serde_json::to_string(game_type).unwrap();
+    let match_id = db.create_match(player1_id, player2_id, &game_state, &game_type_json)
+        .await
+        .map_err(|e| format!("Creating the match failed: {e}"))?;
+
+    let mut outcome = None;
+    for ply in 0..MAX_PLIES {
+        let match_data = load_match(db, match_id).await?;
+        check_redaction(&match_data)?;
+
+        let (player_id, move_data) = scripted_move(&match_data, ply)?;
+        let result = game_router::handle_game_move(&match_data, player_id, move_data)
+            .map_err(|e| format!("Move {ply} was rejected: {e}"))?;
+        let status = if result.is_finished { MatchStatus::Finished } else { MatchStatus::Active };
+        let outcome_json = result.outcome.as_ref().map(|outcome| serde_json::to_string(outcome).unwrap());
+        db.update_match(match_id, &result.new_state.to_string(), status, outcome_json.as_deref())
+            .await
+            .map_err(|e| format!("Storing move {ply} failed: {e}"))?;
+
+        if result.is_finished {
+            outcome = result.outcome;
+            break;
+        }
+    }
+
+    let match_data = load_match(db, match_id).await?;
+    check_redaction(&match_data)?;
+    let Some(outcome) = outcome else {
+        return Err(format!("Not finished after {MAX_PLIES} moves"));
+    };
+    if let Some(expected) = expected_outcome(game_type) {
+        if outcome != expected {
+            return Err(format!("Ended with {outcome:?} instead of {expected:?}"));
+        }
+    }
+
+    let record = db.get_match_by_id(match_id).await.ok_or("The finished match is gone")?;
+    if record.status != MatchStatus::Finished.as_str() || record.outcome() != Some(outcome) {
+        return Err("The finished match was not stored as such".to_string());
+    }
+    db.update_player_scores_from_match(&record)
+        .await
+        .map_err(|e| format!("Updating scores failed: {e}"))
+}
+
+async fn load_match(db: &Database, match_id: i64) -> Result<Match, String> {
+    db.get_match_by_id(match_id)
+        .await
+        .and_then(|record| record.to_match())
+        .ok_or_else(|| format!("Match {match_id} could not be loaded"))
+}
+
+/// The scripts where the winner does not depend on who was drawn to go first
+fn expected_outcome(game_type: &GameType) -> Option<MatchOutcome> {
+    match game_type {
+        GameType::RockPaperScissors => Some(MatchOutcome::Player1Win),
+        GameType::Chess => Some(MatchOutcome::Player2Win),
+        GameType::TicTacToe | GameType::Briscola => None,
+    }
+}
+
+/// Who moves next and what they play
+/// Tic-Tac-Toe takes the first free cell, Briscola plays the first card,
+/// in Rock-Paper-Scissors player 1 always wins, in chess Black mates at once
+fn scripted_move(match_data: &Match, ply: usize) -> Result<(i64, JsonValue), String> {
+    let player_id = *game_router::players_to_move(match_data).first().ok_or("Nobody is left to move")?;
+    let state = match_data.game_state.clone();
+    let invalid = |e: serde_json::Error| format!("Invalid game state: {e}");
+
+    let move_data = match match_data.game_type {
+        GameType::TicTacToe => {
+            let state: TicTacToeGameState = serde_json::from_value(state).map_err(invalid)?;
+            let index = state.board.iter().position(|cell| *cell == 0).ok_or("The board is full")?;
+            json!({ "row": index / state.board_size, "col": index % state.board_size })
+        }
+        GameType::RockPaperScissors => {
+            let choice = if player_id == match_data.player1_id { RockPaperScissorsMove::Rock } else { RockPaperScissorsMove::Scissors };
+            json!({ "choice": choice })
+        }
+        GameType::Briscola => {
+            let state: BriscolaGameState = serde_json::from_value(state).map_err(invalid)?;
+            if state.round_state == RoundState::ChoosingTrump {
+                json!({ "trump_suit": "Coppe" })
+            } else {
+                json!({ "card_index": 0 })
+            }
+        }
+        GameType::Chess => {
+            let ((from_row, from_col), (to_row, to_col)) = *CHESS_SCRIPT.get(ply).ok_or("The chess script ran out")?;
+            let chess_move = ChessMove {
+                from: ChessPosition::new(from_row, from_col).unwrap(),
+                to: ChessPosition::new(to_row, to_col).unwrap(),
+                promotion: None,
+            };
+            serde_json::to_value(chess_move).unwrap()
+        }
+    };
+    Ok((player_id, move_data))
+}
+
+/// Neither the opponent nor spectators may see hidden cards or a move not revealed yet
+fn check_redaction(match_data: &Match) -> Result<(), String> {
+    let views = [
+        (Some(1), game_router::redact_match_for_player(match_data, match_data.player1_id)),
+        (Some(2), game_router::redact_match_for_player(match_data, match_data.player2_id)),
+        (None, game_router::redact_match_for_spectator(match_data)),
+    ];
+
+    for (seat, view) in views {
+        let viewer = seat.map_or("spectators".to_string(), |seat| format!("player {seat}"));
+        let leaked = match match_data.game_type {
+            GameType::Briscola => {
+                let state: BriscolaGameState = serde_json::from_value(view.game_state).map_err(|e| e.to_string())?;
+                let hidden_hands = match seat {
+                    Some(1) => vec![&state.player2_hand],
+                    Some(_) => vec![&state.player1_hand],
+                    None => vec![&state.player1_hand, &state.player2_hand],
+                };
+                !state.deck.is_empty() || hidden_hands.iter().any(|hand| !hand.is_empty())
+            }
+            GameType::RockPaperScissors => {
+                let state: RockPaperScissorsGameState = serde_json::from_value(view.game_state).map_err(|e| e.to_string())?;
+                state.rounds.iter().any(|(p1_move, p2_move)| {
+                    let revealed = |choice: &Option<RockPaperScissorsMove>| {
+                        choice.is_some_and(|choice| choice != RockPaperScissorsMove::Redacted)
+                    };
+                    match (p1_move.is_some() && p2_move.is_some(), seat) {
+                        (true, _) => false,
+                        (false, Some(1)) => revealed(p2_move),
+                        (false, Some(_)) => revealed(p1_move),
+                        (false, None) => revealed(p1_move) || revealed(p2_move),
+                    }
+                })
+            }
+            GameType::TicTacToe | GameType::Chess => view.game_state != match_data.game_state,
+        };
+        if leaked {
+            return Err(format!("Redaction for {viewer} is wrong"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_self_test_passes() {
+        run().await.unwrap();
+    }
+}
@@ -0,0 +1,255 @@
+use battld_common::games::game_type::GameType;
+use battld_common::games::matches::{Match, MatchOutcome};
+use battld_common::{ErrorCode, ServerMessage};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::database::{Database, SeriesRecord};
+use crate::events::{EventBus, MatchEvent};
+use crate::game_logic::OutgoingMessage;
+use crate::game_router;
+use crate::websocket::SharedRegistry;
+
+/// Longest series players can ask for
+pub const MAX_SERIES_LENGTH: u32 = 7;
+
+/// The series length asked for in matchmaking, None for a single match
+/// A series is best of an odd number of games, so someone wins it unless games are drawn
+pub fn validate_length(series_length: Option<u32>) -> Result<Option<u32>, ErrorCode> {
+    match series_length {
+        None | Some(1) => Ok(None),
+        Some(length) if length % 2 == 1 && length <= MAX_SERIES_LENGTH => Ok(Some(length)),
+        Some(_) => Err(ErrorCode::InvalidSeriesLength),
+    }
+}
+
+/// Whether the series is over: someone won more than half of its games, or all of them were played
+pub fn is_decided(series: &SeriesRecord) -> bool {
+    let majority = series.length / 2 + 1;
+    series.player1_wins >= majority || series.player2_wins >= majority || series.games_played() >= series.length
+}
+
+/// The score after the `game`-th match of the series, `match_id`, to both players
+fn to_both(series: &SeriesRecord, match_id: i64, game: u32) -> Vec<OutgoingMessage> {
+    let info = series.to_info(match_id, game);
+    [info.player1_id, info.player2_id]
+        .into_iter()
+        .map(|player_id| OutgoingMessage {
+            player_id,
+            message: ServerMessage::SeriesUpdate { series: info.clone() },
+        })
+        .collect()
+}
+
+/// Open the series a match found in matchmaking starts, if its players asked for one - returns messages to send
+pub async fn start_series_logic(match_id: i64, db: &Database) -> Vec<OutgoingMessage> {
+    match db.start_series(match_id).await {
+        Ok(Some(series)) => {
+            println!("Match {match_id} starts series {} of {} games", series.id, series.length);
+            to_both(&series, match_id, 1)
+        }
+        Ok(None) => vec![],
+        Err(e) => {
+            println!("Failed to start the series of match {match_id}: {e}");
+            vec![]
+        }
+    }
+}
+
+/// Count a finished match towards its series and start the next game, seats swapped, unless the series is over
+pub async fn match_finished_logic(match_data: &Match, events: &EventBus, db: &Database) -> Vec<OutgoingMessage> {
+    let Some(series) = db.get_match_series(match_data.id).await.filter(|series| !series.finished) else {
+        return vec![];
+    };
+    let winner_id = match match_data.outcome {
+        Some(MatchOutcome::Player1Win) => Some(match_data.player1_id),
+        Some(MatchOutcome::Player2Win) => Some(match_data.player2_id),
+        Some(MatchOutcome::Draw) | None => None,
+    };
+    let mut series = match db.record_series_result(series.id, winner_id).await {
+        Ok(series) => series,
+        Err(e) => {
+            println!("Failed to record match {} in series {}: {e}", match_data.id, series.id);
+            return vec![];
+        }
+    };
+
+    let played = series.games_played();
+    if is_decided(&series) {
+        if let Err(e) = db.finish_series(series.id).await {
+            println!("Failed to finish series {}: {e}", series.id);
+        }
+        series.finished = true;
+        println!("Series {} finished {}-{}", series.id, series.player1_wins, series.player2_wins);
+        return to_both(&series, match_data.id, played);
+    }
+
+    let mut messages = to_both(&series, match_data.id, played);
+    messages.extend(start_next_game(&series, match_data, events, db).await);
+    messages
+}
+
+async fn start_next_game(series: &SeriesRecord, previous: &Match, events: &EventBus, db: &Database) -> Vec<OutgoingMessage> {
+    let Ok(game_type) = serde_json::from_str::<GameType>(&series.game_type) else {
+        return vec![];
+    };
+    let options = serde_json::from_str(&series.game_options).unwrap_or_default();
+    let game_state_json = game_router::initialize_game_state(&game_type, &options);
+    let match_id = match db.create_series_match(series.id, previous.player2_id, previous.player1_id, &game_state_json).await {
+        Ok(id) => id,
+        Err(e) => {
+            println!("Failed to start the next game of series {}: {e}", series.id);
+            return vec![];
+        }
+    };
+    let Some(match_info) = db.get_match_by_id(match_id).await.and_then(|record| record.to_match()) else {
+        return vec![];
+    };
+
+    events.publish(MatchEvent::MatchStarted { match_data: match_info.clone() });
+    let mut messages = to_both(series, match_id, series.games_played() + 1);
+    for player_id in [match_info.player1_id, match_info.player2_id] {
+        messages.push(OutgoingMessage {
+            player_id,
+            message: ServerMessage::MatchFound {
+                match_data: game_router::redact_match_for_player(&match_info, player_id),
+            },
+        });
+    }
+    messages
+}
+
+/// Move every series on as its matches finish
+pub fn spawn_series(db: std::sync::Arc<Database>, registry: SharedRegistry) -> tokio::task::JoinHandle<()> {
+    let mut rx = registry.events().subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(MatchEvent::MatchFinished { match_data }) => {
+                    let messages = match_finished_logic(&match_data, registry.events(), &db).await;
+                    registry.send_messages(messages).await;
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    println!("Series fell behind, {missed} events skipped");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capacity::Capacity;
+    use crate::game_logic;
+    use crate::matchmaking::RatingWindow;
+    use crate::ready_check::ReadyChecks;
+    use battld_common::games::matches::MatchEndReason;
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str) -> i64 {
+        db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name).await.unwrap()
+    }
+
+    async fn join(player_id: i64, series_length: Option<u32>, db: &Database) -> Vec<OutgoingMessage> {
+        game_logic::handle_join_matchmaking_logic(
+            player_id,
+            GameType::TicTacToe,
+            serde_json::Value::Null,
+            series_length,
+            &Capacity::default(),
+            &RatingWindow::default(),
+            &ReadyChecks::default(),
+            &EventBus::new(),
+            db,
+        ).await
+    }
+
+    fn series_update(messages: &[OutgoingMessage]) -> Option<battld_common::SeriesInfo> {
+        messages.iter().find_map(|m| match &m.message {
+            ServerMessage::SeriesUpdate { series } => Some(series.clone()),
+            _ => None,
+        })
+    }
+
+    fn found_match(messages: &[OutgoingMessage]) -> Option<Match> {
+        messages.iter().find_map(|m| match &m.message {
+            ServerMessage::MatchFound { match_data } => Some(match_data.clone()),
+            _ => None,
+        })
+    }
+
+    async fn finish(match_data: Match, outcome: MatchOutcome, db: &Database) -> Vec<OutgoingMessage> {
+        let match_data = db.get_match_by_id(match_data.id).await.unwrap().to_match().unwrap();
+        game_logic::finish_match_logic(match_data.clone(), outcome, MatchEndReason::Resignation, None, &EventBus::new(), db).await;
+        let finished = db.get_match_by_id(match_data.id).await.unwrap().to_match().unwrap();
+        match_finished_logic(&finished, &EventBus::new(), db).await
+    }
+
+    #[test]
+    fn test_series_lengths() {
+        assert_eq!(validate_length(None), Ok(None));
+        assert_eq!(validate_length(Some(1)), Ok(None));
+        assert_eq!(validate_length(Some(3)), Ok(Some(3)));
+        assert_eq!(validate_length(Some(4)), Err(ErrorCode::InvalidSeriesLength));
+        assert_eq!(validate_length(Some(MAX_SERIES_LENGTH + 2)), Err(ErrorCode::InvalidSeriesLength));
+    }
+
+    #[tokio::test]
+    async fn test_series_plays_until_a_majority_of_wins() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        let single = create_test_player(&db, "single").await;
+
+        join(p1, Some(3), &db).await;
+        let messages = join(single, None, &db).await;
+        assert!(matches!(messages[0].message, ServerMessage::WaitingForOpponent), "Single matches and series are queued apart");
+
+        let messages = join(p2, Some(3), &db).await;
+        let series = series_update(&messages).unwrap();
+        assert_eq!((series.length, series.game, series.player1_wins), (3, 1, 0));
+        let first = found_match(&messages).unwrap();
+        assert_eq!(series.match_id, first.id);
+
+        let messages = finish(first.clone(), MatchOutcome::Player1Win, &db).await;
+        let series = series_update(&messages).unwrap();
+        assert_eq!((series.game, series.score_for(p1), series.finished), (1, (1, 0), false));
+        let second = found_match(&messages).unwrap();
+        assert_eq!((second.player1_id, second.player2_id), (first.player2_id, first.player1_id), "Seats are swapped");
+        assert_eq!(series_update(&messages[2..]).unwrap().game, 2);
+
+        let messages = finish(second, MatchOutcome::Player2Win, &db).await;
+        let series = series_update(&messages).unwrap();
+        assert!(series.finished);
+        assert_eq!(series.winner_id, Some(p1));
+        assert_eq!(series.score_for(p2), (0, 2));
+        assert!(found_match(&messages).is_none(), "No third game once someone won two");
+    }
+
+    #[tokio::test]
+    async fn test_series_with_draws_ends_after_its_length() {
+        let db = create_test_db().await;
+        let p1 = create_test_player(&db, "player1").await;
+        let p2 = create_test_player(&db, "player2").await;
+        join(p1, Some(3), &db).await;
+        let mut current = found_match(&join(p2, Some(3), &db).await).unwrap();
+
+        for outcome in [MatchOutcome::Draw, MatchOutcome::Player1Win] {
+            current = found_match(&finish(current, outcome, &db).await).unwrap();
+        }
+        let messages = finish(current, MatchOutcome::Player1Win, &db).await;
+        let series = series_update(&messages).unwrap();
+        assert!(series.finished);
+        assert_eq!((series.player1_wins, series.player2_wins, series.draws), (1, 1, 1));
+        assert_eq!(series.winner_id, None);
+    }
+}
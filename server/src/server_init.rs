@@ -1,8 +1,10 @@
 use battld_common::games::game_type::GameType;
 use battld_common::games::matches::MatchOutcome;
-use sqlx::SqlitePool;
 use rand::Rng;
 
+use crate::log_privacy;
+use crate::query_metrics::MeteredPool;
+
 const FAKE_USERS: &[(&str, &str)] = &[
     ("Alice", "alice_pk_hint"),
     ("Bob", "bob_pk_hint"),
@@ -100,7 +102,7 @@ fn check_winner(cells: &[i32; 9]) -> Option<i32> {
     None
 }
 
-pub async fn seed_users(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn seed_users(pool: &MeteredPool) -> Result<(), Box<dyn std::error::Error>> {
     // Check if there are any users
     let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM players")
         .fetch_one(pool)
@@ -130,7 +132,7 @@ pub async fn seed_users(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Err
         let player_id = result.last_insert_rowid();
         player_ids.push(player_id);
 
-        println!("Created user: {name} (ID: {player_id})");
+        println!("Created user: {} (ID: {player_id})", log_privacy::name(name));
     }
 
     println!("Successfully created {} fake users!", FAKE_USERS.len());
@@ -155,8 +157,8 @@ pub async fn seed_users(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Err
         let (game_state, outcome, _) = generate_random_completed_game();
 
         sqlx::query(
-            "INSERT INTO matches (player1_id, player2_id, in_progress, outcome, game_type, game_state)
-             VALUES (?, ?, 0, ?, ?, ?)"
+            "INSERT INTO matches (player1_id, player2_id, in_progress, status, outcome, game_type, game_state)
+             VALUES (?, ?, 0, 'finished', ?, ?, ?)"
         )
         .bind(player1_id)
         .bind(player2_id)
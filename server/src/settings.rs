@@ -0,0 +1,146 @@
+use axum::{extract::State, http::{HeaderMap, StatusCode}};
+use battld_common::games::game_type::GameType;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::{auth, capacity::CapacityConfig, challenges::ChallengeConfig, game_router, matchmaking::RatingWindow, tiers::TierConfig, AppState};
+
+/// A value shared by many tasks and replaced as a whole on reload
+/// Readers keep the copy they loaded, so a reload never changes a value halfway through a request
+#[derive(Debug, Default)]
+pub struct Swap<T>(RwLock<Arc<T>>);
+
+impl<T> Swap<T> {
+    pub fn new(value: T) -> Self {
+        Self(RwLock::new(Arc::new(value)))
+    }
+
+    pub fn load(&self) -> Arc<T> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn store(&self, value: T) {
+        *self.0.write().unwrap() = Arc::new(value);
+    }
+}
+
+/// Server-wide settings that can change without a restart
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServerSettings {
+    /// Shown to players when they sign in
+    pub motd: Option<String>,
+    /// No new matches, challenges or quick play while set, matches being played carry on
+    pub maintenance: bool,
+    /// Grace period for disconnected players in every game, instead of each game's own
+    pub disconnect_grace: Option<Duration>,
+    /// How long players have for each move, None leaves turns untimed
+    pub turn_time_limit: Option<Duration>,
+    /// Ladder tiers, players move between them as their next match finishes
+    pub tiers: TierConfig,
+    /// Rating gap accepted when pairing players in matchmaking
+    pub matchmaking: RatingWindow,
+    /// How long a player waits in matchmaking before a bot takes the other seat, None never seats bots
+    pub bot_after: Option<Duration>,
+}
+
+impl ServerSettings {
+    /// Read from MOTD, MAINTENANCE_MODE (true or false), DISCONNECT_GRACE_SECS, TURN_TIME_LIMIT_SECS, RATING_TIERS
+    /// and the matchmaking rating window and bot backfill, MATCHMAKING_BOT_AFTER_SECS
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+        Self {
+            motd: var("MOTD"),
+            maintenance: var("MAINTENANCE_MODE").is_some_and(|value| value == "true"),
+            disconnect_grace: var("DISCONNECT_GRACE_SECS").and_then(|v| v.parse().ok()).map(Duration::from_secs),
+            turn_time_limit: var("TURN_TIME_LIMIT_SECS")
+                .and_then(|v| v.parse().ok())
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            tiers: TierConfig::from_env(),
+            matchmaking: RatingWindow::from_env(),
+            bot_after: var("MATCHMAKING_BOT_AFTER_SECS")
+                .and_then(|v| v.parse().ok())
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+        }
+    }
+
+    /// How long a disconnected player of the game has to come back
+    pub fn disconnect_grace(&self, game_type: &GameType) -> Duration {
+        self.disconnect_grace.unwrap_or_else(|| game_router::disconnect_policy(game_type).grace)
+    }
+}
+
+/// Read the environment and the .env file again and apply what can change at runtime
+/// RATE_LIMIT_RPS, MOTD, MAINTENANCE_MODE, DISCONNECT_GRACE_SECS, TURN_TIME_LIMIT_SECS, RATING_TIERS, the matchmaking window and bot backfill,
+/// the capacity limits, the challenge expiries and READY_CHECK_SECS are picked up, everything else needs a restart
+pub fn reload(state: &AppState) {
+    if let Err(e) = dotenvy::dotenv_override() {
+        println!("No .env file reloaded: {e}");
+    }
+    state.registry.settings().store(ServerSettings::from_env());
+    state.capacity.reload(CapacityConfig::from_env());
+    state.challenge_config.store(ChallengeConfig::from_env());
+    state.ready_checks.reload_from_env();
+    state.rate_limiter.reload_from_env();
+    println!("Configuration reloaded: {:?}", state.registry.settings().load());
+}
+
+/// Reload the configuration whenever the process gets SIGHUP
+#[cfg(unix)]
+pub fn spawn_reload_on_hangup(state: AppState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut hangups = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                println!("Could not listen for SIGHUP, configuration reloads only via /admin/reload: {e}");
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            println!("SIGHUP received, reloading configuration");
+            reload(&state);
+        }
+    })
+}
+
+/// Reload the configuration, admins only
+pub async fn post_reload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> StatusCode {
+    let player_id = match auth::authenticate_request(&state.session_cache, &headers).await {
+        Ok(player_id) => player_id,
+        Err(status) => return status,
+    };
+    if !state.capacity.is_admin(player_id) {
+        return StatusCode::FORBIDDEN;
+    }
+    reload(&state);
+    StatusCode::NO_CONTENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readers_keep_the_value_they_loaded() {
+        let swap = Swap::new(ServerSettings::default());
+        let before = swap.load();
+
+        swap.store(ServerSettings { maintenance: true, ..Default::default() });
+        assert!(!before.maintenance);
+        assert!(swap.load().maintenance);
+    }
+
+    #[test]
+    fn test_disconnect_grace_override() {
+        let settings = ServerSettings::default();
+        assert_eq!(settings.disconnect_grace(&GameType::Chess), game_router::disconnect_policy(&GameType::Chess).grace);
+
+        let settings = ServerSettings { disconnect_grace: Some(Duration::from_secs(5)), ..Default::default() };
+        assert_eq!(settings.disconnect_grace(&GameType::Chess), Duration::from_secs(5));
+        assert_eq!(settings.disconnect_grace(&GameType::TicTacToe), Duration::from_secs(5));
+    }
+}
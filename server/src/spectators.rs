@@ -0,0 +1,152 @@
+use battld_common::{games::matches::Match, ServerMessage};
+use tokio::sync::broadcast;
+
+use crate::game_logic::OutgoingMessage;
+use crate::game_router;
+
+const CHANNEL_CAPACITY: usize = 256;
+
+/// What spectators receive about a match
+#[derive(Debug, Clone)]
+pub enum SpectatorEvent {
+    State(Match),
+    Move { match_id: i64, seq: i64, description: String },
+}
+
+impl SpectatorEvent {
+    pub fn match_id(&self) -> i64 {
+        match self {
+            SpectatorEvent::State(match_data) => match_data.id,
+            SpectatorEvent::Move { match_id, .. } => *match_id,
+        }
+    }
+}
+
+/// Fan-out of match updates to spectators (e.g. the live status page)
+/// Every published match is already redacted for spectators
+pub struct SpectatorHub {
+    tx: broadcast::Sender<SpectatorEvent>,
+}
+
+impl Default for SpectatorHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpectatorHub {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { tx }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SpectatorEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Publish a match update, dropping it if nobody is watching
+    pub fn publish(&self, match_data: &Match) {
+        let _ = self.tx.send(SpectatorEvent::State(game_router::redact_match_for_spectator(match_data)));
+    }
+
+    /// Publish state and moves carried by outgoing player messages, once per match
+    pub fn publish_messages(&self, messages: &[OutgoingMessage]) {
+        let mut published_moves = vec![];
+        for msg in messages {
+            match &msg.message {
+                ServerMessage::MatchFound { match_data } | ServerMessage::GameStateUpdate { match_data }
+                    if msg.player_id == match_data.player1_id && !match_data.ephemeral =>
+                {
+                    self.publish(match_data);
+                }
+                ServerMessage::MoveApplied { match_id, seq, description } if !published_moves.contains(&(*match_id, *seq)) => {
+                    published_moves.push((*match_id, *seq));
+                    let _ = self.tx.send(SpectatorEvent::Move {
+                        match_id: *match_id,
+                        seq: *seq,
+                        description: description.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::{game_type::GameType, matches::MatchStatus};
+
+    fn test_match(id: i64) -> Match {
+        Match {
+            id,
+            player1_id: 100,
+            player2_id: 200,
+            seats: vec![],
+            in_progress: true,
+            status: MatchStatus::Active,
+            outcome: None,
+            game_type: GameType::TicTacToe,
+            game_state: serde_json::json!({}),
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_messages_once_per_match() {
+        let hub = SpectatorHub::new();
+        let mut rx = hub.subscribe();
+
+        let match_data = test_match(7);
+        let messages = vec![
+            OutgoingMessage {
+                player_id: 100,
+                message: ServerMessage::GameStateUpdate { match_data: match_data.clone() },
+            },
+            OutgoingMessage {
+                player_id: 200,
+                message: ServerMessage::GameStateUpdate { match_data },
+            },
+            OutgoingMessage {
+                player_id: 200,
+                message: ServerMessage::Pong,
+            },
+        ];
+        hub.publish_messages(&messages);
+
+        assert_eq!(rx.recv().await.unwrap().match_id(), 7);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_publish_moves_once() {
+        let hub = SpectatorHub::new();
+        let mut rx = hub.subscribe();
+
+        let move_applied = ServerMessage::MoveApplied { match_id: 7, seq: 3, description: "X at row 1, column 1".to_string() };
+        let messages = vec![
+            OutgoingMessage { player_id: 100, message: move_applied.clone() },
+            OutgoingMessage { player_id: 200, message: move_applied },
+        ];
+        hub.publish_messages(&messages);
+
+        match rx.recv().await.unwrap() {
+            SpectatorEvent::Move { match_id, seq, description } => {
+                assert_eq!((match_id, seq), (7, 3));
+                assert_eq!(description, "X at row 1, column 1");
+            }
+            event => panic!("Unexpected event {event:?}"),
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_publish_without_subscribers() {
+        let hub = SpectatorHub::new();
+        hub.publish(&test_match(1));
+    }
+}
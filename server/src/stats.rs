@@ -4,9 +4,11 @@ use axum::{
     Json,
 };
 use serde::Deserialize;
-use battld_common::{PlayerStats, LeaderboardResponse, LeaderboardEntry};
+use battld_common::{games::{game_type::GameType, matches::Match}, GameRating, PlayerStats, LeaderboardResponse, LeaderboardEntry, SeatStats, ServerMessage};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
 
-use crate::{auth, AppState};
+use crate::{auth, database::{self, Database}, events::MatchEvent, game_logic::OutgoingMessage, rating, websocket::SharedRegistry, AppState};
 
 #[derive(Deserialize)]
 pub struct StatsQuery {
@@ -28,10 +30,10 @@ pub async fn get_stats(
         r#"
         SELECT
             COUNT(*) as total,
-            SUM(CASE WHEN in_progress = 0 AND outcome IS NOT NULL THEN 1 ELSE 0 END) as completed,
-            SUM(CASE WHEN in_progress = 1 AND player2_id IS NOT NULL THEN 1 ELSE 0 END) as dropped
+            SUM(CASE WHEN status = 'finished' AND outcome IS NOT NULL THEN 1 ELSE 0 END) as completed,
+            SUM(CASE WHEN status IN ('active', 'paused') THEN 1 ELSE 0 END) as dropped
         FROM matches
-        WHERE player1_id = ? OR player2_id = ?
+        WHERE (player1_id = ? OR player2_id = ?) AND status != 'aborted'
         "#
     )
     .bind(target_player_id)
@@ -45,7 +47,7 @@ pub async fn get_stats(
         r#"
         SELECT outcome, player1_id, player2_id
         FROM matches
-        WHERE (player1_id = ? OR player2_id = ?) AND in_progress = 0 AND outcome IS NOT NULL
+        WHERE (player1_id = ? OR player2_id = ?) AND status = 'finished' AND outcome IS NOT NULL
         "#
     )
     .bind(target_player_id)
@@ -59,30 +61,56 @@ pub async fn get_stats(
     let mut draw = 0i64;
     let mut score = 0i64;
 
+    let mut first_seat = SeatStats::default();
+    let mut second_seat = SeatStats::default();
+
     for (outcome, player1_id, _player2_id) in outcomes {
         if let Some(outcome) = outcome {
             let is_player1 = player1_id == target_player_id;
+            let seat = if is_player1 { &mut first_seat } else { &mut second_seat };
             match outcome.as_str() {
                 "p1_win" if is_player1 => {
                     won += 1;
                     score += 3;
+                    seat.won += 1;
                 },
                 "p2_win" if !is_player1 => {
                     won += 1;
                     score += 3;
+                    seat.won += 1;
                 },
                 "draw" => {
                     draw += 1;
                     score += 1;
+                    seat.draw += 1;
                 },
                 _ => {
                     lost += 1;
                     score -= 1;
+                    seat.lost += 1;
                 }
             }
         }
     }
 
+    let rating = db.get_rating(target_player_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let game_ratings = db.get_game_ratings(target_player_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .map(|(game_type, rating)| GameRating {
+            game_type,
+            rating: rating.rating.round() as i64,
+            provisional: rating.is_provisional(),
+            games: rating.games,
+        })
+        .collect();
+    let tier = db.get_tier(target_player_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     Ok(Json(PlayerStats {
         player_id: target_player_id,
         won,
@@ -91,13 +119,34 @@ pub async fn get_stats(
         dropped: stats.2,
         total: stats.0,
         score,
+        rating: rating.rating.round() as i64,
+        provisional: rating.is_provisional(),
+        first_seat,
+        second_seat,
+        game_ratings,
+        tier,
     }))
 }
 
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LeaderboardScope {
+    #[default]
+    Global,
+    /// Only the caller and the players they added as friends
+    Friends,
+}
+
 #[derive(Deserialize)]
 pub struct LeaderboardQuery {
     limit: Option<i64>,
     offset: Option<i64>,
+    #[serde(default)]
+    scope: LeaderboardScope,
+    /// Rank by the rating in this game instead of by score
+    game_type: Option<GameType>,
+    /// Final standings of this season instead of the current one
+    season: Option<i64>,
 }
 
 pub async fn get_leaderboard(
@@ -105,30 +154,55 @@ pub async fn get_leaderboard(
     headers: HeaderMap,
     Query(params): Query<LeaderboardQuery>,
 ) -> Result<Json<LeaderboardResponse>, StatusCode> {
-    let _player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
-    let db = &state.db;
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
 
     let limit = params.limit.unwrap_or(10).clamp(1, 100);
     let offset = params.offset.unwrap_or(0).max(0);
 
+    let season = match params.season {
+        Some(season_id) => Some(state.db.get_season(season_id).await.ok_or(StatusCode::NOT_FOUND)?),
+        None => state.db.get_current_season().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+    };
+    let archived = season.as_ref().filter(|season| season.archived).map(|season| season.id);
+    let friends_of = matches!(params.scope, LeaderboardScope::Friends).then_some(player_id);
+
+    let leaderboard = match (archived, params.game_type, params.scope) {
+        // Past seasons only kept the score standings
+        (Some(_), Some(_), _) => return Err(StatusCode::BAD_REQUEST),
+        (Some(season_id), None, _) => fetch_season_leaderboard(&state.db, season_id, friends_of, limit, offset).await,
+        (None, Some(game_type), LeaderboardScope::Global) => fetch_game_leaderboard(&state.db, &game_type, None, limit, offset).await,
+        (None, Some(game_type), LeaderboardScope::Friends) => fetch_game_leaderboard(&state.db, &game_type, Some(player_id), limit, offset).await,
+        (None, None, LeaderboardScope::Global) => fetch_leaderboard(&state.db, limit, offset).await,
+        (None, None, LeaderboardScope::Friends) => fetch_friends_leaderboard(&state.db, player_id, limit, offset).await,
+    };
+
+    leaderboard
+        .map(|leaderboard| Json(LeaderboardResponse { season: season.map(|season| season.to_info()), ..leaderboard }))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Load a page of the leaderboard, ordered by score
+pub async fn fetch_leaderboard(db: &Database, limit: i64, offset: i64) -> Result<LeaderboardResponse, sqlx::Error> {
     // Query players with score - simple select ordered by score
     #[derive(sqlx::FromRow)]
     struct LeaderboardRow {
         id: i64,
         name: String,
         score: i64,
+        rating: f64,
+        rated_games: i64,
+        tier: Option<String>,
     }
 
     // Get total count of players with score > 0
     let total_count: (i64,) = sqlx::query_as("SELECT COUNT(*) as count FROM players WHERE score > 0")
         .fetch_one(db.pool())
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
 
     // Get paginated leaderboard - simple query using pre-calculated scores
     let scores: Vec<LeaderboardRow> = sqlx::query_as(
         r#"
-        SELECT id, name, score
+        SELECT id, name, score, rating, rated_games, tier
         FROM players
         WHERE score > 0
         ORDER BY score DESC, id ASC
@@ -138,8 +212,7 @@ pub async fn get_leaderboard(
     .bind(limit)
     .bind(offset)
     .fetch_all(db.pool())
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     let entries: Vec<LeaderboardEntry> = scores
         .iter()
@@ -149,11 +222,385 @@ pub async fn get_leaderboard(
             player_name: r.name.clone(),
             rank: (offset + idx as i64 + 1),
             score: r.score,
+            rating: r.rating.round() as i64,
+            provisional: r.rated_games < rating::PROVISIONAL_GAMES,
+            tier: r.tier.clone(),
         })
         .collect();
 
-    Ok(Json(LeaderboardResponse {
+    Ok(LeaderboardResponse {
         entries,
         total_count: total_count.0,
-    }))
+        season: None,
+    })
+}
+
+/// Load a page of the leaderboard restricted to a player and their friends
+/// The player is always ranked, even without a score
+pub async fn fetch_friends_leaderboard(
+    db: &Database,
+    player_id: i64,
+    limit: i64,
+    offset: i64,
+) -> Result<LeaderboardResponse, sqlx::Error> {
+    #[derive(sqlx::FromRow)]
+    struct LeaderboardRow {
+        id: i64,
+        name: String,
+        score: i64,
+        rating: f64,
+        rated_games: i64,
+        tier: Option<String>,
+    }
+
+    const FRIENDS_FILTER: &str = "id = ? OR id IN (SELECT friend_id FROM friends WHERE player_id = ?)";
+
+    let total_count: (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) as count FROM players WHERE {FRIENDS_FILTER}"))
+        .bind(player_id)
+        .bind(player_id)
+        .fetch_one(db.pool())
+        .await?;
+
+    let scores: Vec<LeaderboardRow> = sqlx::query_as(&format!(
+        "SELECT id, name, score, rating, rated_games, tier FROM players WHERE {FRIENDS_FILTER} ORDER BY score DESC, id ASC LIMIT ? OFFSET ?"
+    ))
+    .bind(player_id)
+    .bind(player_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(db.pool())
+    .await?;
+
+    let entries: Vec<LeaderboardEntry> = scores
+        .iter()
+        .enumerate()
+        .map(|(idx, r)| LeaderboardEntry {
+            player_id: r.id,
+            player_name: r.name.clone(),
+            rank: (offset + idx as i64 + 1),
+            score: r.score,
+            rating: r.rating.round() as i64,
+            provisional: r.rated_games < rating::PROVISIONAL_GAMES,
+            tier: r.tier.clone(),
+        })
+        .collect();
+
+    Ok(LeaderboardResponse {
+        entries,
+        total_count: total_count.0,
+        season: None,
+    })
+}
+
+/// Load a page of the players who played rated matches of a game, ordered by their rating in it
+/// With `friends_of`, only that player and their friends
+pub async fn fetch_game_leaderboard(
+    db: &Database,
+    game_type: &GameType,
+    friends_of: Option<i64>,
+    limit: i64,
+    offset: i64,
+) -> Result<LeaderboardResponse, sqlx::Error> {
+    #[derive(sqlx::FromRow)]
+    struct LeaderboardRow {
+        id: i64,
+        name: String,
+        score: i64,
+        rating: f64,
+        rated_games: i64,
+        tier: Option<String>,
+    }
+
+    const GAME_FILTER: &str = "g.game_type = ? AND g.rated_games > 0
+        AND (? IS NULL OR p.id = ? OR p.id IN (SELECT friend_id FROM friends WHERE player_id = ?))";
+    let game_type_json = serde_json::to_string(game_type).unwrap();
+
+    let total_count: (i64,) = sqlx::query_as(&format!(
+        "SELECT COUNT(*) FROM player_game_ratings g JOIN players p ON p.id = g.player_id WHERE {GAME_FILTER}"
+    ))
+    .bind(&game_type_json)
+    .bind(friends_of)
+    .bind(friends_of)
+    .bind(friends_of)
+    .fetch_one(db.pool())
+    .await?;
+
+    let ratings: Vec<LeaderboardRow> = sqlx::query_as(&format!(
+        "SELECT p.id, p.name, p.score, g.rating, g.rated_games, p.tier FROM player_game_ratings g JOIN players p ON p.id = g.player_id
+         WHERE {GAME_FILTER} ORDER BY g.rating DESC, p.id ASC LIMIT ? OFFSET ?"
+    ))
+    .bind(&game_type_json)
+    .bind(friends_of)
+    .bind(friends_of)
+    .bind(friends_of)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(db.pool())
+    .await?;
+
+    let entries: Vec<LeaderboardEntry> = ratings
+        .iter()
+        .enumerate()
+        .map(|(idx, r)| LeaderboardEntry {
+            player_id: r.id,
+            player_name: r.name.clone(),
+            rank: (offset + idx as i64 + 1),
+            score: r.score,
+            rating: r.rating.round() as i64,
+            provisional: r.rated_games < rating::PROVISIONAL_GAMES,
+            tier: r.tier.clone(),
+        })
+        .collect();
+
+    Ok(LeaderboardResponse {
+        entries,
+        total_count: total_count.0,
+        season: None,
+    })
+}
+
+/// Load a page of the final standings of a season, as they were when it ended
+/// With `friends_of`, only that player and their friends, keeping the ranks they had among everyone
+pub async fn fetch_season_leaderboard(
+    db: &Database,
+    season_id: i64,
+    friends_of: Option<i64>,
+    limit: i64,
+    offset: i64,
+) -> Result<LeaderboardResponse, sqlx::Error> {
+    #[derive(sqlx::FromRow)]
+    struct StandingRow {
+        id: i64,
+        name: String,
+        rank: i64,
+        score: i64,
+        rating: f64,
+        rated_games: i64,
+        tier: Option<String>,
+    }
+
+    const SEASON_FILTER: &str = "s.season_id = ?
+        AND (? IS NULL OR p.id = ? OR p.id IN (SELECT friend_id FROM friends WHERE player_id = ?))";
+
+    let total_count: (i64,) = sqlx::query_as(&format!(
+        "SELECT COUNT(*) FROM season_standings s JOIN players p ON p.id = s.player_id WHERE {SEASON_FILTER}"
+    ))
+    .bind(season_id)
+    .bind(friends_of)
+    .bind(friends_of)
+    .bind(friends_of)
+    .fetch_one(db.pool())
+    .await?;
+
+    let standings: Vec<StandingRow> = sqlx::query_as(&format!(
+        "SELECT p.id, p.name, s.rank, s.score, s.rating, s.rated_games, s.tier FROM season_standings s JOIN players p ON p.id = s.player_id
+         WHERE {SEASON_FILTER} ORDER BY s.rank ASC LIMIT ? OFFSET ?"
+    ))
+    .bind(season_id)
+    .bind(friends_of)
+    .bind(friends_of)
+    .bind(friends_of)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(db.pool())
+    .await?;
+
+    let entries: Vec<LeaderboardEntry> = standings
+        .into_iter()
+        .map(|r| LeaderboardEntry {
+            player_id: r.id,
+            player_name: r.name,
+            rank: r.rank,
+            score: r.score,
+            rating: r.rating.round() as i64,
+            provisional: r.rated_games < rating::PROVISIONAL_GAMES,
+            tier: r.tier,
+        })
+        .collect();
+
+    Ok(LeaderboardResponse {
+        entries,
+        total_count: total_count.0,
+        season: None,
+    })
+}
+
+/// Each player's new score and leaderboard place after a finished match, so clients need not fetch their stats again
+/// Matches against bots leave scores as they are, their players hear of a change of 0
+pub async fn stats_delta_logic(match_data: &Match, db: &Database) -> Vec<OutgoingMessage> {
+    let Some(outcome) = &match_data.outcome else {
+        return vec![];
+    };
+    let ranked = !match_data.players.iter().any(|player| player.bot);
+    let (player1_delta, player2_delta) = if ranked { database::score_deltas(outcome) } else { (0, 0) };
+
+    let mut messages = vec![];
+    for (player_id, score_change) in [(match_data.player1_id, player1_delta), (match_data.player2_id, player2_delta)] {
+        let Some(player) = db.get_player_by_id(player_id).await else { continue };
+        let new_rank = db.get_score_rank(player_id).await.unwrap_or_default();
+        messages.push(OutgoingMessage {
+            player_id,
+            message: ServerMessage::StatsDelta { score_change, new_score: player.score, new_rank },
+        });
+    }
+    messages
+}
+
+/// Tell both players of every finished match how their stats moved
+pub fn spawn_stats_deltas(db: Arc<Database>, registry: SharedRegistry) -> tokio::task::JoinHandle<()> {
+    let mut rx = registry.events().subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(MatchEvent::MatchFinished { match_data }) => {
+                    let messages = stats_delta_logic(&match_data, &db).await;
+                    registry.send_messages(messages).await;
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    println!("Stats deltas fell behind, {missed} events skipped");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str, score: i64) -> i64 {
+        let id = db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name).await.unwrap();
+        sqlx::query("UPDATE players SET score = ? WHERE id = ?")
+            .bind(score)
+            .bind(id)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        id
+    }
+
+    #[tokio::test]
+    async fn test_friends_leaderboard_ranks_only_friends() {
+        let db = create_test_db().await;
+        let me = create_test_player(&db, "me", 0).await;
+        let friend = create_test_player(&db, "friend", 5).await;
+        let stranger = create_test_player(&db, "stranger", 9).await;
+        db.add_friend(me, friend).await.unwrap();
+
+        let leaderboard = fetch_friends_leaderboard(&db, me, 10, 0).await.unwrap();
+        let ids: Vec<i64> = leaderboard.entries.iter().map(|e| e.player_id).collect();
+        assert_eq!(ids, vec![friend, me]);
+        assert_eq!(leaderboard.total_count, 2);
+        assert_eq!(leaderboard.entries[1].rank, 2);
+
+        let global = fetch_leaderboard(&db, 10, 0).await.unwrap();
+        assert_eq!(global.entries[0].player_id, stranger);
+    }
+
+    #[tokio::test]
+    async fn test_game_leaderboard_ranks_by_rating_in_that_game() {
+        use battld_common::games::matches::{MatchOutcome, MatchStatus};
+
+        let db = create_test_db().await;
+        let me = create_test_player(&db, "me", 0).await;
+        let friend = create_test_player(&db, "friend", 0).await;
+        let stranger = create_test_player(&db, "stranger", 0).await;
+        db.add_friend(me, friend).await.unwrap();
+
+        for (winner, loser, game_type) in [(friend, me, GameType::Chess), (stranger, friend, GameType::Chess), (me, stranger, GameType::TicTacToe)] {
+            let game_type_json = serde_json::to_string(&game_type).unwrap();
+            let match_id = db.create_match(winner, loser, "{}", &game_type_json).await.unwrap();
+            let outcome = serde_json::to_string(&MatchOutcome::Player1Win).unwrap();
+            db.update_match(match_id, "{}", MatchStatus::Finished, Some(&outcome)).await.unwrap();
+            db.update_player_scores_from_match(&db.get_match_by_id(match_id).await.unwrap()).await.unwrap();
+        }
+
+        let chess = fetch_game_leaderboard(&db, &GameType::Chess, None, 10, 0).await.unwrap();
+        assert_eq!(chess.total_count, 3);
+        assert_eq!(chess.entries[0].player_id, stranger);
+        assert_eq!(chess.entries[2].player_id, me);
+        assert!(chess.entries.iter().all(|e| e.provisional));
+
+        let friends_chess = fetch_game_leaderboard(&db, &GameType::Chess, Some(me), 10, 0).await.unwrap();
+        let ids: Vec<i64> = friends_chess.entries.iter().map(|e| e.player_id).collect();
+        assert_eq!(ids, vec![friend, me]);
+
+        let tic_tac_toe = fetch_game_leaderboard(&db, &GameType::TicTacToe, None, 10, 0).await.unwrap();
+        let ids: Vec<i64> = tic_tac_toe.entries.iter().map(|e| e.player_id).collect();
+        assert_eq!(ids, vec![me, stranger]);
+        assert_eq!(fetch_game_leaderboard(&db, &GameType::Briscola, None, 10, 0).await.unwrap().total_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_friendship_is_one_way() {
+        let db = create_test_db().await;
+        let me = create_test_player(&db, "me", 1).await;
+        let friend = create_test_player(&db, "friend", 2).await;
+        db.add_friend(me, friend).await.unwrap();
+        db.add_friend(me, friend).await.unwrap();
+
+        assert_eq!(fetch_friends_leaderboard(&db, friend, 10, 0).await.unwrap().total_count, 1);
+
+        db.remove_friend(me, friend).await.unwrap();
+        assert_eq!(fetch_friends_leaderboard(&db, me, 10, 0).await.unwrap().total_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_stats_delta_follows_a_finished_match() {
+        use battld_common::games::matches::{MatchOutcome, MatchStatus};
+
+        let db = create_test_db().await;
+        let leader = create_test_player(&db, "leader", 10).await;
+        let winner = create_test_player(&db, "winner", 1).await;
+        let loser = create_test_player(&db, "loser", 0).await;
+        assert_eq!(db.get_score_rank(leader).await.unwrap(), Some(1));
+
+        let game_type_json = serde_json::to_string(&GameType::TicTacToe).unwrap();
+        let match_id = db.create_match(winner, loser, "{}", &game_type_json).await.unwrap();
+        let outcome = serde_json::to_string(&MatchOutcome::Player1Win).unwrap();
+        db.update_match(match_id, "{}", MatchStatus::Finished, Some(&outcome)).await.unwrap();
+        let record = db.get_match_by_id(match_id).await.unwrap();
+        db.update_player_scores_from_match(&record).await.unwrap();
+
+        let messages = stats_delta_logic(&record.to_match().unwrap(), &db).await;
+        let deltas: Vec<(i64, i64, i64, Option<i64>)> = messages
+            .iter()
+            .map(|m| match m.message {
+                ServerMessage::StatsDelta { score_change, new_score, new_rank } => (m.player_id, score_change, new_score, new_rank),
+                ref other => panic!("Expected StatsDelta, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(deltas, vec![(winner, 3, 4, Some(2)), (loser, -1, -1, None)]);
+    }
+
+    #[tokio::test]
+    async fn test_season_leaderboard_keeps_final_ranks() {
+        let db = create_test_db().await;
+        let me = create_test_player(&db, "me", 2).await;
+        let friend = create_test_player(&db, "friend", 5).await;
+        let stranger = create_test_player(&db, "stranger", 9).await;
+        db.add_friend(me, friend).await.unwrap();
+        let season = db.create_season(0, 100).await.unwrap();
+        db.archive_season(season.id).await.unwrap();
+        create_test_player(&db, "newcomer", 4).await;
+
+        let leaderboard = fetch_season_leaderboard(&db, season.id, None, 10, 0).await.unwrap();
+        let ranks: Vec<(i64, i64, i64)> = leaderboard.entries.iter().map(|e| (e.player_id, e.rank, e.score)).collect();
+        assert_eq!(ranks, vec![(stranger, 1, 9), (friend, 2, 5), (me, 3, 2)]);
+
+        let friends = fetch_season_leaderboard(&db, season.id, Some(me), 10, 0).await.unwrap();
+        let ranks: Vec<(i64, i64)> = friends.entries.iter().map(|e| (e.player_id, e.rank)).collect();
+        assert_eq!(ranks, vec![(friend, 2), (me, 3)]);
+        assert_eq!(friends.total_count, 2);
+    }
 }
@@ -0,0 +1,188 @@
+use battld_common::ServerMessage;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::database::Database;
+use crate::events::MatchEvent;
+use crate::game_logic::OutgoingMessage;
+use crate::rating::Rating;
+use crate::websocket::SharedRegistry;
+
+const DEFAULT_TIERS: &str = "Bronze:0,Silver:1400,Gold:1600,Platinum:1800,Diamond:2000";
+
+/// How far below a tier's threshold the rating may drop before the player is demoted,
+/// so a single loss right after a promotion does not take it back
+const DEMOTION_MARGIN: f64 = 25.0;
+
+/// A step of the ladder, reached at `min_rating`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tier {
+    pub name: String,
+    pub min_rating: f64,
+}
+
+/// Ladder tiers over the overall rating, lowest first
+#[derive(Debug, Clone, PartialEq)]
+pub struct TierConfig {
+    tiers: Vec<Tier>,
+}
+
+impl Default for TierConfig {
+    fn default() -> Self {
+        Self::parse(DEFAULT_TIERS)
+    }
+}
+
+impl TierConfig {
+    /// From `Name:threshold` pairs separated by commas, such as `Bronze:0,Silver:1400`, invalid pairs are skipped
+    pub fn parse(spec: &str) -> Self {
+        let mut tiers: Vec<Tier> = spec
+            .split(',')
+            .filter_map(|pair| {
+                let (name, min_rating) = pair.split_once(':')?;
+                let name = name.trim();
+                (!name.is_empty()).then_some(())?;
+                Some(Tier { name: name.to_string(), min_rating: min_rating.trim().parse().ok()? })
+            })
+            .collect();
+        tiers.sort_by(|a, b| a.min_rating.total_cmp(&b.min_rating));
+        Self { tiers }
+    }
+
+    /// Read from RATING_TIERS, Bronze from 0 up to Diamond from 2000 by default
+    pub fn from_env() -> Self {
+        std::env::var("RATING_TIERS")
+            .ok()
+            .filter(|spec| !spec.trim().is_empty())
+            .map(|spec| Self::parse(&spec))
+            .unwrap_or_default()
+    }
+
+    /// Tier of a player rated `rating` who is in `current` now, None while provisional or below every threshold
+    /// Reaching a threshold promotes right away, demotion waits until the rating is `DEMOTION_MARGIN` under it
+    pub fn tier_for(&self, rating: &Rating, current: Option<&str>) -> Option<String> {
+        if rating.is_provisional() {
+            return None;
+        }
+        let reached = self.tiers.iter().rposition(|tier| rating.rating >= tier.min_rating);
+        let held = current.and_then(|name| self.rank(name));
+        let kept = match (held, reached) {
+            (Some(held), reached) if reached.is_none_or(|reached| held > reached) => {
+                (rating.rating >= self.tiers[held].min_rating - DEMOTION_MARGIN).then_some(held)
+            }
+            _ => None,
+        };
+        kept.or(reached).map(|index| self.tiers[index].name.clone())
+    }
+
+    /// Position on the ladder, lowest tier first
+    fn rank(&self, name: &str) -> Option<usize> {
+        self.tiers.iter().position(|tier| tier.name == name)
+    }
+}
+
+/// Move both players of every finished match along the ladder
+pub fn spawn_ladder(db: Arc<Database>, registry: SharedRegistry) -> tokio::task::JoinHandle<()> {
+    let mut rx = registry.events().subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(MatchEvent::MatchFinished { match_data }) => {
+                    let settings = registry.settings().load();
+                    let players = [match_data.player1_id, match_data.player2_id];
+                    let messages = update_tiers_logic(&players, &settings.tiers, &db).await;
+                    registry.send_messages(messages).await;
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    println!("Ladder fell behind, {missed} events skipped");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// Save the tier each player's rating puts them in, telling those whose tier changed
+pub async fn update_tiers_logic(player_ids: &[i64], tiers: &TierConfig, db: &Database) -> Vec<OutgoingMessage> {
+    let mut messages = vec![];
+    for &player_id in player_ids {
+        let (Ok(rating), Ok(current)) = (db.get_rating(player_id).await, db.get_tier(player_id).await) else {
+            continue;
+        };
+        let tier = tiers.tier_for(&rating, current.as_deref());
+        if tier == current {
+            continue;
+        }
+        if let Err(e) = db.set_tier(player_id, tier.as_deref()).await {
+            println!("Failed to update the tier of player {player_id}: {e}");
+            continue;
+        }
+        println!("Player {player_id} moved from tier {current:?} to {tier:?}");
+
+        let rank = |name: &Option<String>| name.as_deref().and_then(|name| tiers.rank(name));
+        messages.push(OutgoingMessage {
+            player_id,
+            message: ServerMessage::TierChanged {
+                promoted: rank(&tier) > rank(&current),
+                previous_tier: current,
+                tier,
+            },
+        });
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    fn rated(rating: f64) -> Rating {
+        Rating { rating, deviation: 50.0, games: 20 }
+    }
+
+    #[test]
+    fn test_tier_thresholds_and_demotion_margin() {
+        let tiers = TierConfig::default();
+        assert_eq!(tiers.tier_for(&rated(1500.0), None).as_deref(), Some("Silver"));
+        assert_eq!(tiers.tier_for(&rated(1600.0), Some("Silver")).as_deref(), Some("Gold"));
+        assert_eq!(tiers.tier_for(&Rating::default(), None), None);
+
+        // Just under the threshold keeps the tier, well under it demotes
+        assert_eq!(tiers.tier_for(&rated(1590.0), Some("Gold")).as_deref(), Some("Gold"));
+        assert_eq!(tiers.tier_for(&rated(1570.0), Some("Gold")).as_deref(), Some("Silver"));
+        assert_eq!(tiers.tier_for(&rated(1200.0), Some("Gold")).as_deref(), Some("Bronze"));
+    }
+
+    #[test]
+    fn test_parse_tiers() {
+        let tiers = TierConfig::parse("Gold:1600, Wood:-100,broken,Silver:abc");
+        assert_eq!(tiers.tier_for(&rated(-50.0), None).as_deref(), Some("Wood"));
+        assert_eq!(tiers.tier_for(&rated(1700.0), None).as_deref(), Some("Gold"));
+        assert_eq!(tiers.tier_for(&rated(-500.0), None), None);
+    }
+
+    #[tokio::test]
+    async fn test_tier_change_is_saved_and_announced() {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        let player_id = db.create_player("hint", "key", "player").await.unwrap();
+        sqlx::query("UPDATE players SET rating = 1650, rated_games = 12 WHERE id = ?")
+            .bind(player_id)
+            .execute(db.pool())
+            .await
+            .unwrap();
+
+        let messages = update_tiers_logic(&[player_id], &TierConfig::default(), &db).await;
+        assert!(matches!(
+            &messages[0].message,
+            ServerMessage::TierChanged { previous_tier: None, tier: Some(tier), promoted: true } if tier == "Gold"
+        ));
+        assert_eq!(db.get_tier(player_id).await.unwrap().as_deref(), Some("Gold"));
+
+        // Nothing to announce while the tier stays the same
+        assert!(update_tiers_logic(&[player_id], &TierConfig::default(), &db).await.is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+use axum::{extract::State, http::{HeaderMap, StatusCode}, Json};
+use battld_common::{ExportedGameRating, ExportedMatch, ExportedPlayer, ImportReport, InstanceExport, ReplayPrivacy};
+use std::collections::HashSet;
+
+use crate::database::Database;
+use crate::storage::{self, Storage};
+use crate::{auth, retention, AppState};
+
+/// Bumped whenever the export format changes in a way older importers can't read
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Players with their keys and ratings, and the finished matches between them
+pub async fn export_instance(db: &Database, now: i64) -> Result<InstanceExport, sqlx::Error> {
+    let mut players = vec![];
+    for record in db.get_exported_players().await? {
+        let game_ratings = db.get_game_ratings(record.id)
+            .await?
+            .into_iter()
+            .map(|(game_type, rating)| ExportedGameRating {
+                game_type,
+                rating: rating.rating,
+                rating_deviation: rating.deviation,
+                rated_games: rating.games,
+            })
+            .collect();
+        players.push(ExportedPlayer {
+            id: record.id,
+            name: record.name,
+            public_key_hint: record.public_key_hint,
+            public_key: record.public_key,
+            score: record.score,
+            rating: record.rating,
+            rating_deviation: record.rating_deviation,
+            rated_games: record.rated_games,
+            replay_privacy: ReplayPrivacy::parse(&record.replay_privacy).unwrap_or_default(),
+            game_ratings,
+        });
+    }
+
+    let matches = db.get_exported_matches()
+        .await?
+        .into_iter()
+        .filter_map(|record| {
+            Some(ExportedMatch {
+                id: record.id,
+                player1_id: record.player1_id,
+                player2_id: record.player2_id,
+                game_type: serde_json::from_str(&record.game_type).ok()?,
+                outcome: record.outcome.and_then(|outcome| serde_json::from_str(&outcome).ok()),
+                game_state: serde_json::from_str(&record.game_state).unwrap_or_default(),
+                finished_at: record.finished_at,
+            })
+        })
+        .collect();
+
+    Ok(InstanceExport {
+        format_version: FORMAT_VERSION,
+        exported_at: now,
+        players,
+        matches,
+    })
+}
+
+/// Check an export can be loaded as a whole before touching the database
+pub fn validate(export: &InstanceExport) -> Result<(), String> {
+    if export.format_version > FORMAT_VERSION {
+        return Err(format!(
+            "Export format {} is newer than the supported {FORMAT_VERSION}",
+            export.format_version
+        ));
+    }
+
+    let mut player_ids = HashSet::new();
+    for player in &export.players {
+        if !player_ids.insert(player.id) {
+            return Err(format!("Player {} is exported twice", player.id));
+        }
+    }
+    let mut match_ids = HashSet::new();
+    for exported in &export.matches {
+        if !match_ids.insert(exported.id) {
+            return Err(format!("Match {} is exported twice", exported.id));
+        }
+        for player_id in [exported.player1_id, exported.player2_id] {
+            if !player_ids.contains(&player_id) {
+                return Err(format!("Match {} refers to player {player_id}, missing from the export", exported.id));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn import_instance(db: &Database, export: &InstanceExport) -> Result<ImportReport, String> {
+    validate(export)?;
+    db.import_instance(export).await.map_err(|e| format!("Import failed: {e}"))
+}
+
+/// `server export [file]`, `server import <file>` and `server backup`, run against the local database instead of serving
+/// Exports without a file and backups go to storage
+pub async fn run_command(db: &Database, storage: &Storage, command: &str, path: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let now = battld_common::time() as i64;
+    match (command, path) {
+        ("export", path) => {
+            let export = export_instance(db, now).await?;
+            let json = serde_json::to_string_pretty(&export)?;
+            let destination = match path {
+                Some(path) => {
+                    std::fs::write(path, json)?;
+                    path.to_string()
+                }
+                None => {
+                    let key = format!("{}instance-{now}.json", storage::EXPORTS);
+                    storage.put(&key, json.into_bytes()).await?;
+                    format!("storage as {key}")
+                }
+            };
+            println!("Exported {} players and {} matches to {destination}", export.players.len(), export.matches.len());
+        }
+        ("backup", _) => {
+            let key = retention::back_up(db, storage, now).await?;
+            println!("Backed the database up to storage as {key}");
+        }
+        ("import", Some(path)) => {
+            let export: InstanceExport = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            let report = import_instance(db, &export).await?;
+            println!(
+                "Imported {} players ({} merged) and {} matches from {path}",
+                report.players_imported, report.players_merged, report.matches_imported
+            );
+            if !report.preserved_ids {
+                let mapping = format!("{path}.ids.json");
+                std::fs::write(&mapping, serde_json::to_string_pretty(&report)?)?;
+                println!("The database was not empty, new ids were assigned, see {mapping}");
+            }
+        }
+        ("import", None) => return Err("Usage: server import <file>".into()),
+        _ => return Err(format!("Unknown command {command}, expected export, import or backup").into()),
+    }
+    Ok(())
+}
+
+pub async fn get_export(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<InstanceExport>, StatusCode> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers).await?;
+    if !state.capacity.is_admin(player_id) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    export_instance(&state.db, battld_common::time() as i64)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn post_import(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(export): Json<InstanceExport>,
+) -> Result<Json<ImportReport>, (StatusCode, String)> {
+    let player_id = auth::authenticate_request(&state.session_cache, &headers)
+        .await
+        .map_err(|status| (status, "Not authenticated".to_string()))?;
+    if !state.capacity.is_admin(player_id) {
+        return Err((StatusCode::FORBIDDEN, "Admins only".to_string()));
+    }
+
+    validate(&export).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    import_instance(&state.db, &export)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::matches::{MatchOutcome, MatchStatus};
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str) -> i64 {
+        db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name)
+            .await
+            .unwrap()
+    }
+
+    async fn create_finished_match(db: &Database, p1: i64, p2: i64, outcome: MatchOutcome) -> i64 {
+        let game_type = serde_json::to_string(&battld_common::games::game_type::GameType::TicTacToe).unwrap();
+        let match_id = db.create_match(p1, p2, r#"{"board":[1,1,1,2,2,0,0,0,0]}"#, &game_type).await.unwrap();
+        let outcome = serde_json::to_string(&outcome).unwrap();
+        db.update_match(match_id, r#"{"board":[1,1,1,2,2,0,0,0,0]}"#, MatchStatus::Finished, Some(&outcome)).await.unwrap();
+        match_id
+    }
+
+    async fn populated_db() -> (Database, i64, i64) {
+        let db = create_test_db().await;
+        let alice = create_test_player(&db, "alice").await;
+        let bob = create_test_player(&db, "bob").await;
+        let match_id = create_finished_match(&db, alice, bob, MatchOutcome::Player1Win).await;
+        db.update_player_scores_from_match(&db.get_match_by_id(match_id).await.unwrap()).await.unwrap();
+        create_finished_match(&db, bob, alice, MatchOutcome::Draw).await;
+        db.set_replay_privacy(bob, ReplayPrivacy::Friends, bob).await.unwrap();
+        (db, alice, bob)
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip_into_empty_instance_keeps_ids() {
+        let (source, _, _) = populated_db().await;
+        let export = export_instance(&source, 1_000).await.unwrap();
+        assert_eq!(export.players.len(), 2);
+        assert_eq!(export.matches.len(), 2);
+        assert!(export.players.iter().all(|player| player.game_ratings.len() == 1));
+
+        let target = create_test_db().await;
+        let report = import_instance(&target, &export).await.unwrap();
+        assert!(report.preserved_ids);
+        assert_eq!(report.players_imported, 2);
+        assert_eq!(report.matches_imported, 2);
+        assert!(report.player_ids.iter().all(|(old, new)| old == new));
+
+        let reexported = export_instance(&target, 1_000).await.unwrap();
+        assert_eq!(reexported.players, export.players);
+        assert_eq!(reexported.matches, export.matches);
+    }
+
+    #[tokio::test]
+    async fn test_import_into_used_instance_maps_ids() {
+        let (source, alice, bob) = populated_db().await;
+        let export = export_instance(&source, 1_000).await.unwrap();
+
+        let target = create_test_db().await;
+        let carol = create_test_player(&target, "carol").await;
+        let local_bob = create_test_player(&target, "bob").await;
+        create_finished_match(&target, carol, local_bob, MatchOutcome::Player2Win).await;
+
+        let report = import_instance(&target, &export).await.unwrap();
+        assert!(!report.preserved_ids);
+        assert_eq!(report.players_imported, 1);
+        assert_eq!(report.players_merged, 1);
+        assert_eq!(report.player_ids[&bob], local_bob);
+        assert_ne!(report.player_ids[&alice], alice);
+
+        let imported = target.get_match_by_id(report.match_ids[&1]).await.unwrap();
+        assert_eq!(imported.player1_id, report.player_ids[&alice]);
+        assert_eq!(imported.player2_id, local_bob);
+        assert_eq!(imported.outcome(), Some(MatchOutcome::Player1Win));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_inconsistent_exports() {
+        let (source, _, _) = populated_db().await;
+        let export = export_instance(&source, 1_000).await.unwrap();
+        let target = create_test_db().await;
+
+        let newer = InstanceExport { format_version: FORMAT_VERSION + 1, ..export.clone() };
+        assert!(import_instance(&target, &newer).await.is_err());
+
+        let orphaned = InstanceExport { players: export.players[..1].to_vec(), ..export.clone() };
+        assert!(import_instance(&target, &orphaned).await.is_err());
+        assert!(target.get_exported_players().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+use battld_common::games::matches::MatchStatus;
+use battld_common::ServerMessage;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::database::Database;
+use crate::events::EventBus;
+use crate::game_logic::{self, OutgoingMessage};
+use crate::game_router;
+use crate::reminders;
+use crate::websocket::SharedRegistry;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Deadline of the players to move in a match
+struct TurnClock {
+    players: Vec<i64>,
+    deadline: i64,
+}
+
+/// Turn time limits of the matches in play, keyed by match id
+/// A clock starts with the first state sent after the players to move changed, so resending a state keeps it running
+#[derive(Default)]
+pub struct TurnClocks {
+    clocks: Mutex<HashMap<i64, TurnClock>>,
+}
+
+impl TurnClocks {
+    /// Start or keep the clock of a match whose state is being sent, answering with the time left
+    /// `limit` is the server's, matches setting their own such as blitz Briscola use theirs
+    /// None for other messages, untimed turns and matches that are not being played
+    pub fn observe(&self, message: &OutgoingMessage, limit: Option<Duration>, now: i64) -> Option<OutgoingMessage> {
+        let (ServerMessage::MatchFound { match_data } | ServerMessage::GameStateUpdate { match_data }) = &message.message else {
+            return None;
+        };
+        let mut clocks = self.clocks.lock().unwrap();
+        let players = game_router::players_to_move(match_data);
+        let timed = match_data.status == MatchStatus::Active && !match_data.ephemeral && !players.is_empty();
+        let Some(limit) = game_router::turn_limit(match_data).or(limit).filter(|_| timed) else {
+            clocks.remove(&match_data.id);
+            return None;
+        };
+
+        // Still the same turn while the players to move only got fewer, as in Rock-Paper-Scissors rounds
+        let clock = clocks.entry(match_data.id).or_insert(TurnClock { players: vec![], deadline: 0 });
+        if clock.deadline <= now || !players.iter().all(|player_id| clock.players.contains(player_id)) {
+            clock.deadline = now + limit.as_secs() as i64;
+        }
+        clock.players = players.clone();
+
+        match_data.seat_of(message.player_id).is_some().then(|| OutgoingMessage {
+            player_id: message.player_id,
+            message: ServerMessage::TurnTimer {
+                match_id: match_data.id,
+                players,
+                expires_in: (clock.deadline - now) as u64,
+            },
+        })
+    }
+
+    /// Take the clocks that ran out, with the players who ran out of time
+    fn take_expired(&self, now: i64) -> Vec<(i64, Vec<i64>)> {
+        let mut clocks = self.clocks.lock().unwrap();
+        let expired: Vec<i64> = clocks.iter().filter(|(_, clock)| clock.deadline <= now).map(|(match_id, _)| *match_id).collect();
+        expired
+            .into_iter()
+            .filter_map(|match_id| clocks.remove(&match_id).map(|clock| (match_id, clock.players)))
+            .collect()
+    }
+}
+
+/// Act on expired turn clocks every second
+pub fn spawn_expiry(db: Arc<Database>, registry: SharedRegistry) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let now = battld_common::time() as i64;
+            for (match_id, players) in registry.turn_clocks().take_expired(now) {
+                let messages = handle_turn_timeout_logic(match_id, &players, registry.events(), &db).await;
+                registry.send_messages(messages).await;
+            }
+        }
+    })
+}
+
+/// Play the timeout move of a player who ran out of time, or forfeit the match for them
+/// Nothing happens if the match was paused, ended or moved on meanwhile
+pub async fn handle_turn_timeout_logic(match_id: i64, players: &[i64], events: &EventBus, db: &Database) -> Vec<OutgoingMessage> {
+    let Some(match_data) = db.get_match_by_id(match_id).await.and_then(|record| record.to_match()) else {
+        return vec![];
+    };
+    if match_data.status != MatchStatus::Active || game_router::players_to_move(&match_data) != players {
+        return vec![];
+    }
+    println!("Players {players:?} ran out of time in match {match_id}");
+
+    let mut messages: Vec<OutgoingMessage> = match_data.seat_ids()
+        .into_iter()
+        .map(|player_id| OutgoingMessage {
+            player_id,
+            message: ServerMessage::TurnTimeout { match_id, players: players.to_vec() },
+        })
+        .collect();
+    let timeout_move = match players {
+        [player_id] => game_router::timeout_move(&match_data, *player_id).map(|move_data| (*player_id, move_data)),
+        _ => None,
+    };
+    match timeout_move {
+        Some((player_id, move_data)) => messages.extend(game_logic::handle_make_move_logic(player_id, move_data, events, db).await),
+        None => messages.extend(reminders::forfeit_logic(match_data, players, events, db).await),
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::briscola::{BriscolaGameState, BriscolaOptions, Card, Rank, Suit};
+    use battld_common::games::game_type::GameType;
+    use battld_common::games::matches::{MatchEndReason, MatchOutcome};
+    use battld_common::games::rock_paper_scissors::{RockPaperScissorsGameState, RockPaperScissorsMove};
+    use server::games::tic_tac_toe::TicTacToeGameState;
+    use sqlx::SqlitePool;
+
+    async fn create_test_db() -> Database {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        let db = Database::from_pool(pool);
+        db.initialize().await.unwrap();
+        db
+    }
+
+    async fn create_test_player(db: &Database, name: &str) -> i64 {
+        db.create_player(&format!("{name}_hint"), &format!("{name}_key"), name).await.unwrap()
+    }
+
+    async fn create_test_match(db: &Database, game_type: GameType, game_state: String) -> (i64, i64, i64) {
+        let p1 = create_test_player(db, "player1").await;
+        let p2 = create_test_player(db, "player2").await;
+        let match_id = db.create_match(p1, p2, &game_state, &serde_json::to_string(&game_type).unwrap()).await.unwrap();
+        (p1, p2, match_id)
+    }
+
+    async fn state_update(db: &Database, match_id: i64, player_id: i64) -> OutgoingMessage {
+        let match_data = db.get_match_by_id(match_id).await.unwrap().to_match().unwrap();
+        OutgoingMessage { player_id, message: ServerMessage::GameStateUpdate { match_data } }
+    }
+
+    #[tokio::test]
+    async fn test_clock_runs_until_the_players_to_move_change() {
+        let db = create_test_db().await;
+        let game_state = serde_json::to_string(&TicTacToeGameState::new()).unwrap();
+        let (p1, p2, match_id) = create_test_match(&db, GameType::TicTacToe, game_state).await;
+        let clocks = TurnClocks::default();
+        let limit = Some(Duration::from_secs(30));
+
+        let update = state_update(&db, match_id, p2).await;
+        assert!(clocks.observe(&update, None, 1_000).is_none());
+        let timer = clocks.observe(&update, limit, 1_000).unwrap();
+        assert_eq!(timer.player_id, p2);
+        assert!(matches!(&timer.message, ServerMessage::TurnTimer { players, expires_in: 30, .. } if players == &vec![p1]));
+
+        // Resending the state keeps the deadline
+        let timer = clocks.observe(&update, limit, 1_010).unwrap();
+        assert!(matches!(timer.message, ServerMessage::TurnTimer { expires_in: 20, .. }));
+        assert!(clocks.take_expired(1_029).is_empty());
+        assert_eq!(clocks.take_expired(1_030), vec![(match_id, vec![p1])]);
+        assert!(clocks.take_expired(1_031).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_forfeits_the_match() {
+        let db = create_test_db().await;
+        let game_state = serde_json::to_string(&TicTacToeGameState::new()).unwrap();
+        let (p1, _, match_id) = create_test_match(&db, GameType::TicTacToe, game_state).await;
+
+        let messages = handle_turn_timeout_logic(match_id, &[p1], &EventBus::new(), &db).await;
+        assert!(matches!(&messages[0].message, ServerMessage::TurnTimeout { players, .. } if players == &vec![p1]));
+        assert!(messages.iter().any(|m| matches!(m.message, ServerMessage::MatchEnded { reason: MatchEndReason::Forfeit, .. })));
+        assert_eq!(db.get_match_by_id(match_id).await.unwrap().outcome(), Some(MatchOutcome::Player2Win));
+
+        // A clock that ran out for a turn already played does nothing
+        assert!(handle_turn_timeout_logic(match_id, &[p1], &EventBus::new(), &db).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rock_paper_scissors_timeout_gives_up_the_round() {
+        let db = create_test_db().await;
+        let mut game_state = RockPaperScissorsGameState::new();
+        game_state.rounds[0].0 = Some(RockPaperScissorsMove::Paper);
+        let (_, p2, match_id) = create_test_match(&db, GameType::RockPaperScissors, serde_json::to_string(&game_state).unwrap()).await;
+
+        let messages = handle_turn_timeout_logic(match_id, &[p2], &EventBus::new(), &db).await;
+        assert!(messages.iter().any(|m| matches!(m.message, ServerMessage::MoveApplied { .. })));
+
+        let record = db.get_match_by_id(match_id).await.unwrap();
+        assert_eq!(record.status, MatchStatus::Active.as_str());
+        let game_state: RockPaperScissorsGameState = serde_json::from_str(&record.game_state).unwrap();
+        assert_eq!(game_state.rounds[0], (Some(RockPaperScissorsMove::Paper), Some(RockPaperScissorsMove::Rock)));
+        assert_eq!(game_state.get_score(), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_blitz_briscola_plays_the_lowest_card_in_time() {
+        let db = create_test_db().await;
+        let mut game_state = server::games::briscola::BriscolaGameEngine::new_game(&BriscolaOptions { blitz: true, ..Default::default() });
+        game_state.current_player = 1;
+        game_state.briscola_suit = Suit::Coppe;
+        game_state.player1_hand = vec![
+            Card { suit: Suit::Denari, rank: Rank::Ace },
+            Card { suit: Suit::Coppe, rank: Rank::Two },
+            Card { suit: Suit::Spade, rank: Rank::Four },
+        ];
+        let (p1, p2, match_id) = create_test_match(&db, GameType::Briscola, serde_json::to_string(&game_state).unwrap()).await;
+
+        // Blitz cards have ten seconds, whatever the server's limit
+        let timer = TurnClocks::default().observe(&state_update(&db, match_id, p2).await, Some(Duration::from_secs(60)), 1_000).unwrap();
+        assert!(matches!(timer.message, ServerMessage::TurnTimer { expires_in: 10, .. }));
+
+        let messages = handle_turn_timeout_logic(match_id, &[p1], &EventBus::new(), &db).await;
+        assert!(messages.iter().any(|m| matches!(m.message, ServerMessage::MoveApplied { .. })));
+        let game_state: BriscolaGameState = serde_json::from_str(&db.get_match_by_id(match_id).await.unwrap().game_state).unwrap();
+        assert_eq!(game_state.table, vec![(Card { suit: Suit::Spade, rank: Rank::Four }, 1)]);
+        assert_eq!(game_state.current_player, 2);
+    }
+}
@@ -1,30 +1,39 @@
-use axum::{extract::{ws::{Message, WebSocket, WebSocketUpgrade}, State}, response::Response};
+use axum::{extract::{ws::{Message, WebSocket, WebSocketUpgrade}, State}, http::{HeaderMap, StatusCode}, response::{IntoResponse, Response}};
 use futures::{sink::SinkExt, stream::StreamExt};
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::{mpsc, RwLock};
+use std::{collections::HashMap, sync::{atomic::{AtomicU64, Ordering}, Arc}};
+use tokio::sync::RwLock;
 use tokio::task::AbortHandle;
 use tokio::time::{Duration, sleep};
 
-use battld_common::{games::game_type::{self, GameType}, ClientMessage, ServerMessage};
-use crate::{database::Database, AppState, game_logic};
+use battld_common::{games::{game_type::GameType, matches::{Match, MatchStatus}}, ClientMessage, ErrorCode, MatchmakingRejection, ServerMessage};
+use crate::{arena, capacity::Capacity, challenges::{self, ChallengeConfig}, csrf_protection, database::Database, log_privacy, parties::{self, PartyRegistry}, quick_play, ready_check, rematches, AppState, game_logic};
 use crate::game_logic::OutgoingMessage;
+use crate::events::EventBus;
+use crate::outbox::{self, Outbox};
+use crate::settings::{ServerSettings, Swap};
+use crate::turn_clock::TurnClocks;
+use crate::spectators::SpectatorHub;
 
 /// Connection info including sender and abort handle
 struct ConnectionInfo {
-    tx: mpsc::UnboundedSender<ServerMessage>,
+    /// Tells this connection apart from a newer one of the same player that replaced it
+    id: u64,
+    outbox: Outbox,
     abort_handle: AbortHandle,
-}
-
-/// Tracks a player's disconnection from a match with a timer
-struct DisconnectInfo {
-    match_id: i64,
-    timer_handle: AbortHandle,
+    /// Match id -> sequence of the latest state sent over this connection
+    delivered: std::sync::Mutex<HashMap<i64, i64>>,
 }
 
 /// Connection registry to track active WebSocket connections per player
 pub struct ConnectionRegistry {
     connections: RwLock<HashMap<i64, ConnectionInfo>>,
-    disconnects: RwLock<HashMap<i64, DisconnectInfo>>,
+    last_connection_id: AtomicU64,
+    /// Player id -> timer ending their grace period, which is kept in the database
+    disconnects: RwLock<HashMap<i64, AbortHandle>>,
+    spectators: SpectatorHub,
+    events: EventBus,
+    settings: Swap<ServerSettings>,
+    turn_clocks: TurnClocks,
 }
 
 impl Default for ConnectionRegistry {
@@ -35,39 +44,114 @@ impl Default for ConnectionRegistry {
 
 impl ConnectionRegistry {
     pub fn new() -> Self {
+        Self::with_settings(ServerSettings::default())
+    }
+
+    pub fn with_settings(settings: ServerSettings) -> Self {
         Self {
             connections: RwLock::new(HashMap::new()),
+            last_connection_id: AtomicU64::new(0),
             disconnects: RwLock::new(HashMap::new()),
+            spectators: SpectatorHub::new(),
+            events: EventBus::new(),
+            settings: Swap::new(settings),
+            turn_clocks: TurnClocks::default(),
         }
     }
 
-    /// Register a new connection for a player
-    pub async fn register(&self, player_id: i64, tx: mpsc::UnboundedSender<ServerMessage>, abort_handle: AbortHandle) {
+    pub fn spectators(&self) -> &SpectatorHub {
+        &self.spectators
+    }
+
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    pub fn settings(&self) -> &Swap<ServerSettings> {
+        &self.settings
+    }
+
+    pub fn turn_clocks(&self) -> &TurnClocks {
+        &self.turn_clocks
+    }
+
+    /// Register a new connection for a player, returning its id
+    /// A connection the player still had, such as a half-open one they reconnected from, is closed
+    pub async fn register(&self, player_id: i64, outbox: Outbox, abort_handle: AbortHandle) -> u64 {
+        let id = self.last_connection_id.fetch_add(1, Ordering::Relaxed) + 1;
         let mut connections = self.connections.write().await;
-        connections.insert(player_id, ConnectionInfo { tx, abort_handle });
-        println!("Registered WebSocket connection for player {player_id}");
+        let info = ConnectionInfo { id, outbox, abort_handle, delivered: Default::default() };
+        if let Some(replaced) = connections.insert(player_id, info) {
+            replaced.abort_handle.abort();
+            println!("Replaced WebSocket connection {} of player {player_id}", replaced.id);
+        }
+        println!("Registered WebSocket connection {id} for player {player_id}");
+        id
     }
 
     /// Unregister a connection and force-close the WebSocket
-    pub async fn unregister(&self, player_id: i64) {
+    /// False when the player has a newer connection by now, which is left alone
+    pub async fn unregister(&self, player_id: i64, connection_id: u64) -> bool {
         let mut connections = self.connections.write().await;
+        if connections.get(&player_id).is_none_or(|info| info.id != connection_id) {
+            return false;
+        }
         if let Some(info) = connections.remove(&player_id) {
             // Abort the send task to force-close the WebSocket
             info.abort_handle.abort();
-            println!("Unregistered WebSocket connection for player {player_id}");
+            println!("Unregistered WebSocket connection {connection_id} for player {player_id}");
         }
+        true
+    }
+
+    /// Whether a player currently has an open connection
+    pub async fn is_connected(&self, player_id: i64) -> bool {
+        self.connections.read().await.contains_key(&player_id)
+    }
+
+    /// Players with an open connection, guests included
+    pub async fn connected_players(&self) -> Vec<i64> {
+        self.connections.read().await.keys().copied().collect()
+    }
+
+    pub async fn connection_count(&self) -> usize {
+        self.connections.read().await.len()
     }
 
     /// Send a message to a specific player
     pub async fn send_to_player(&self, player_id: i64, message: ServerMessage) -> Result<(), String> {
+        self.offer_to_player(player_id, message).await.map(|_| ())
+    }
+
+    /// Send a message to a specific player, false when it was dropped as outdated
+    /// A state older than one they already got is dropped, as states held back behind a round result can be overtaken
+    async fn offer_to_player(&self, player_id: i64, message: ServerMessage) -> Result<bool, String> {
         let connections = self.connections.read().await;
         if let Some(info) = connections.get(&player_id) {
-            info.tx.send(message).map_err(|e| format!("Failed to send message: {e}"))
+            if let Some((match_id, seq)) = delivered_seq(&message) {
+                let mut delivered = info.delivered.lock().unwrap();
+                let outdated = delivered.get(&match_id).is_some_and(|latest| *latest > seq);
+                if outdated && matches!(message, ServerMessage::GameStateUpdate { .. }) {
+                    return Ok(false);
+                }
+                delivered.insert(match_id, seq);
+            }
+            info.outbox.send(message).map(|_| true).map_err(|e| format!("Failed to send message: {e}"))
         } else {
             Err(format!("Player {player_id} not connected"))
         }
     }
 
+    /// Sequence of the latest state of a match sent to each connected player
+    pub async fn delivered_seqs(&self, match_id: i64) -> HashMap<i64, i64> {
+        self.connections
+            .read()
+            .await
+            .iter()
+            .filter_map(|(player_id, info)| info.delivered.lock().unwrap().get(&match_id).map(|seq| (*player_id, *seq)))
+            .collect()
+    }
+
     /// Send a message to multiple players
     pub async fn send_to_players(&self, player_ids: &[i64], message: ServerMessage) {
         for player_id in player_ids {
@@ -76,10 +160,43 @@ impl ConnectionRegistry {
     }
 
     /// Send multiple messages (helper for game logic integration)
-    pub async fn send_messages(&self, messages: Vec<OutgoingMessage>) {
+    /// Whatever follows a round result is held back for as long as the result is meant to be shown,
+    /// by a task of its own so the caller goes on right away
+    pub async fn send_messages(self: &Arc<Self>, messages: Vec<OutgoingMessage>) {
+        let mut batches = paced_batches(messages).into_iter();
+        if let Some((_, batch)) = batches.next() {
+            self.deliver(batch).await;
+        }
+        let held_back: Vec<_> = batches.collect();
+        if held_back.is_empty() {
+            return;
+        }
+        let registry = Arc::clone(self);
+        tokio::spawn(async move {
+            for (pause, batch) in held_back {
+                sleep(pause).await;
+                registry.deliver(batch).await;
+            }
+        });
+    }
+
+    /// Match state updates are also forwarded to spectators, and followed by the legal moves and turn timer for players
+    /// A state dropped as outdated has none of these, or it would take the turn clock back to an earlier turn
+    async fn deliver(&self, messages: Vec<OutgoingMessage>) {
+        let (turn_time_limit, now) = (self.settings.load().turn_time_limit, battld_common::time() as i64);
+        let mut sent = vec![];
         for msg in messages {
-            let _ = self.send_to_player(msg.player_id, msg.message).await;
+            if let Ok(false) = self.offer_to_player(msg.player_id, msg.message.clone()).await {
+                continue;
+            }
+            let legal_moves = game_logic::legal_moves_for(&msg);
+            let turn_timer = self.turn_clocks.observe(&msg, turn_time_limit, now);
+            for follow_up in legal_moves.into_iter().chain(turn_timer) {
+                let _ = self.send_to_player(follow_up.player_id, follow_up.message).await;
+            }
+            sent.push(msg);
         }
+        self.spectators.publish_messages(&sent);
     }
 
     pub async fn start_disconnect_timer(
@@ -90,68 +207,140 @@ impl ConnectionRegistry {
         db: Arc<Database>,
         registry: SharedRegistry,
     ) {
-        self.cancel_disconnect_timer(player_id).await;
+        self.cancel_disconnect_timer(player_id, &db).await;
 
-        let config = game_type::get_game_config(&game_type);
-        let timeout_seconds = config.disconnect_timeout_secs;
+        let timeout_seconds = self.settings.load().disconnect_grace(&game_type).as_secs();
+        let deadline = battld_common::time() as i64 + timeout_seconds as i64;
+        if let Err(e) = db.record_disconnect(player_id, match_id, deadline).await {
+            println!("Failed to save the disconnect of player {player_id} from match {match_id}: {e}");
+        }
+        self.run_disconnect_timer(player_id, match_id, Duration::from_secs(timeout_seconds), db, registry).await;
+        println!("Started {timeout_seconds}s disconnect timer for player {player_id} in match {match_id} (game: {game_type:?})");
+    }
 
+    async fn run_disconnect_timer(&self, player_id: i64, match_id: i64, grace: Duration, db: Arc<Database>, registry: SharedRegistry) {
         let timer_task = tokio::spawn(async move {
-            sleep(Duration::from_secs(timeout_seconds)).await;
+            sleep(grace).await;
             println!("Disconnect timer expired for player {player_id} in match {match_id}");
             handle_disconnect_timeout(player_id, match_id, &db, &registry).await;
         });
-
-        let mut disconnects = self.disconnects.write().await;
-        disconnects.insert(player_id, DisconnectInfo {
-            match_id,
-            timer_handle: timer_task.abort_handle(),
-        });
-        println!("Started {timeout_seconds}s disconnect timer for player {player_id} in match {match_id} (game: {game_type:?})");
+        self.disconnects.write().await.insert(player_id, timer_task.abort_handle());
     }
 
-    pub async fn cancel_disconnect_timer(&self, player_id: i64) {
-        let mut disconnects = self.disconnects.write().await;
-        if let Some(info) = disconnects.remove(&player_id) {
-            info.timer_handle.abort();
+    pub async fn cancel_disconnect_timer(&self, player_id: i64, db: &Database) {
+        if let Some(timer_handle) = self.disconnects.write().await.remove(&player_id) {
+            timer_handle.abort();
             println!("Cancelled disconnect timer for player {player_id}");
         }
+        if let Err(e) = db.take_disconnect(player_id, None).await {
+            println!("Failed to clear the disconnect of player {player_id}: {e}");
+        }
     }
 
-    pub async fn get_resumable_match(&self, player_id: i64) -> Option<i64> {
-        let disconnects = self.disconnects.read().await;
-        disconnects.get(&player_id).map(|info| info.match_id)
+    /// The match a player can come back to, also when they dropped out of it before a restart or on another server
+    pub async fn get_resumable_match(&self, player_id: i64, db: &Database) -> Option<i64> {
+        db.get_disconnect(player_id).await.map(|record| record.match_id)
     }
 }
 
 pub type SharedRegistry = Arc<ConnectionRegistry>;
 
+/// Time a new connection has to send `Authenticate` before it is closed
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the server pings every connection
+const PING_INTERVAL: Duration = Duration::from_secs(20);
+/// How long after a ping a silent connection is considered dead
+const PONG_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// WebSocket upgrade handler
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
+    headers: HeaderMap,
     State(state): State<AppState>,
 ) -> Response {
-    ws.on_upgrade(move |socket| handle_socket(socket, state.db, state.registry, state.session_cache))
+    if !csrf_protection::is_websocket_origin_allowed(&headers) {
+        println!("WebSocket upgrade rejected for origin {:?}", headers.get("origin"));
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    ws.on_upgrade(move |socket| handle_socket(socket, state))
+}
+
+/// Messages cut after each round result, each batch with how long to wait before sending it
+fn paced_batches(messages: Vec<OutgoingMessage>) -> Vec<(Duration, Vec<OutgoingMessage>)> {
+    let mut batches = vec![(Duration::ZERO, vec![])];
+    for msg in messages {
+        let pause = match &msg.message {
+            ServerMessage::RoundResult { shown_for_ms, .. } => Some(Duration::from_millis(*shown_for_ms)),
+            _ => None,
+        };
+        batches.last_mut().unwrap().1.push(msg);
+        if let Some(pause) = pause {
+            batches.push((pause, vec![]));
+        }
+    }
+    batches.retain(|(_, batch)| !batch.is_empty());
+    batches
+}
+
+/// The match and sequence a message brings its receiver up to
+fn delivered_seq(message: &ServerMessage) -> Option<(i64, i64)> {
+    match message {
+        ServerMessage::MatchFound { match_data } | ServerMessage::GameStateUpdate { match_data } if !match_data.ephemeral => {
+            Some((match_data.id, match_data.seq))
+        }
+        ServerMessage::PlayerDisconnected { match_id, seq, .. } | ServerMessage::PlayerReconnected { match_id, seq, .. } => {
+            Some((*match_id, *seq))
+        }
+        _ => None,
+    }
+}
+
+/// Pick up the grace periods left running when the server stopped, ending the matches of those already past
+pub async fn restore_disconnect_timers(db: Arc<Database>, registry: SharedRegistry) {
+    let disconnects = match db.get_disconnects().await {
+        Ok(disconnects) => disconnects,
+        Err(e) => {
+            println!("Failed to load pending disconnects: {e}");
+            return;
+        }
+    };
+    let now = battld_common::time() as i64;
+    for record in disconnects {
+        let grace = Duration::from_secs(record.deadline.saturating_sub(now).max(0) as u64);
+        println!("Restoring disconnect timer for player {} in match {}, {}s left", record.player_id, record.match_id, grace.as_secs());
+        registry.run_disconnect_timer(record.player_id, record.match_id, grace, db.clone(), registry.clone()).await;
+    }
 }
 
 /// Handle a single WebSocket connection
-async fn handle_socket(
-    socket: WebSocket,
-    db: Arc<Database>,
-    registry: SharedRegistry,
-    session_cache: Arc<crate::session_cache::SessionCache>,
-) {
+async fn handle_socket(socket: WebSocket, state: AppState) {
+    let AppState { db, registry, session_cache, challenge_config, capacity, parties, ready_checks, quick_play, arenas, rematches, .. } = state.clone();
     let (mut sender, mut receiver) = socket.split();
 
-    // Channel to send messages to this client
-    let (tx, mut rx) = mpsc::unbounded_channel::<ServerMessage>();
+    // Channel to send messages to this client, coalescing state updates it falls behind on
+    let (tx, mut rx) = outbox::channel();
 
-    // Task to forward messages from channel to WebSocket
+    // Task to forward messages from channel to WebSocket, pinging the client meanwhile
     let send_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            println!("[WS SEND] {msg:?}");
-            if let Ok(_json) = serde_json::to_string(&msg) {
-                if sender.send(Message::Text(_json)).await.is_err() {
-                    break;
+        let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + PING_INTERVAL, PING_INTERVAL);
+        loop {
+            tokio::select! {
+                msg = rx.recv() => {
+                    let Some(msg) = msg else {
+                        break;
+                    };
+                    println!("[WS SEND] {}", log_privacy::message(&msg));
+                    if let Ok(_json) = serde_json::to_string(&msg) {
+                        if sender.send(Message::Text(_json)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                _ = ping.tick() => {
+                    if sender.send(Message::Ping(Vec::new())).await.is_err() {
+                        break;
+                    }
                 }
             }
         }
@@ -164,35 +353,77 @@ async fn handle_socket(
     // Handle incoming messages
     let mut player_id: Option<i64> = None;
     let mut session_token: Option<String> = None;
+    // Set instead of `player_id` for guests in quick play
+    let mut guest_id: Option<i64> = None;
+    // Id the registry gave this connection, to leave a newer one of the same player alone on cleanup
+    let mut connection_id: Option<u64> = None;
+
+    loop {
+        // Unauthenticated connections must identify themselves promptly,
+        // authenticated ones at least answer every ping with a pong
+        let identified = player_id.is_some() || guest_id.is_some();
+        let deadline = if identified { PING_INTERVAL + PONG_TIMEOUT } else { AUTH_TIMEOUT };
+        let msg = match tokio::time::timeout(deadline, receiver.next()).await {
+            Ok(msg) => msg,
+            Err(_) => {
+                match player_id.or(guest_id) {
+                    Some(pid) => println!("[WS EVENT] No pong from player {pid}, closing dead connection"),
+                    None => println!("[WS EVENT] Closing connection that did not authenticate in time"),
+                }
+                break;
+            }
+        };
+        let Some(msg) = msg else {
+            break;
+        };
 
-    while let Some(msg) = receiver.next().await {
         match msg {
             Ok(Message::Text(text)) => {
                 if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) {
-                    println!("[WS RECV] {client_msg:?}");
+                    println!("[WS RECV] {}", log_privacy::message(&client_msg));
+
+                    // Nothing but the session token handshake, or quick play where enabled, is accepted before authentication
+                    let handshake = matches!(client_msg, ClientMessage::Authenticate { .. })
+                        || (quick_play.is_enabled() && matches!(client_msg, ClientMessage::JoinQuickPlay { .. }));
+                    if player_id.is_none() && guest_id.is_none() && !handshake {
+                        let _ = tx.send(ServerMessage::AuthFailed {
+                            reason: "Authenticate first".to_string(),
+                        });
+                        break;
+                    }
+
                     match client_msg {
+                        ClientMessage::Authenticate { .. } if guest_id.is_some() => {
+                            let _ = tx.send(ServerMessage::error(ErrorCode::GuestSignIn, &[]));
+                        }
                         ClientMessage::Authenticate { token } => {
                             match authenticate_token(&session_cache, &token).await {
                                 Ok(pid) => {
+                                    // Reconnecting players replace their old connection
+                                    if !registry.is_connected(pid).await {
+                                        if let Err(limit) = capacity.check_connection(pid, registry.connection_count().await) {
+                                            println!("Player {pid} turned away: {limit:?}");
+                                            let _ = tx.send(capacity.busy_message());
+                                            break;
+                                        }
+                                    }
+
+                                    // Signing in again on the same connection keeps its registration
+                                    if player_id != Some(pid) {
+                                        connection_id = Some(registry.register(pid, tx.clone(), send_task.abort_handle()).await);
+                                    }
                                     player_id = Some(pid);
                                     session_token = Some(token.clone());
-                                    registry.register(pid, tx.clone(), send_task.abort_handle()).await;
 
-                                    let response = ServerMessage::AuthSuccess { player_id: pid };
+                                    let response = ServerMessage::AuthSuccess { player_id: pid, motd: registry.settings().load().motd.clone() };
                                     let _ = tx.send(response);
                                     println!("Player {pid} authenticated via WebSocket");
 
-                                    // Check if player has a resumable match
-                                    if let Some(match_id) = registry.get_resumable_match(pid).await {
-                                        if let Some(match_record) = db.get_match_by_id(match_id).await {
-                                            if let Some(match_info) = match_record.to_match() {
-                                                println!("Player {pid} has resumable match {match_id}");
-                                                let _ = tx.send(ServerMessage::ResumableMatch {
-                                                    match_data: match_info,
-                                                });
-                                            }
-                                        }
-                                    }
+                                    reconcile_match_on_login(pid, &db, &registry).await;
+
+                                    // Deliver challenges received while offline
+                                    let messages = challenges::handle_player_online_logic(pid, &challenge_config.load(), &db).await;
+                                    registry.send_messages(messages).await;
                                 }
                                 Err(e) => {
                                     let response = ServerMessage::AuthFailed { reason: e };
@@ -208,34 +439,186 @@ async fn handle_socket(
                             }
                             let _ = tx.send(ServerMessage::Pong);
                         }
-                        ClientMessage::JoinMatchmaking { game_type } => {
+                        ClientMessage::JoinMatchmaking { .. } if in_maintenance(player_id, &registry, &capacity) => {
+                            let _ = tx.send(ServerMessage::MatchmakingRejected { reason: MatchmakingRejection::Maintenance });
+                        }
+                        ClientMessage::ChallengePlayer { .. }
+                        | ClientMessage::JoinQuickPlay { .. }
+                        | ClientMessage::JoinArena { .. }
+                        | ClientMessage::RequestRematch { .. }
+                            if in_maintenance(player_id, &registry, &capacity) =>
+                        {
+                            let _ = tx.send(ServerMessage::error(ErrorCode::Maintenance, &[]));
+                        }
+                        ClientMessage::JoinMatchmaking { game_type, options, series_length } => {
+                            if let Some(pid) = player_id {
+                                handle_join_matchmaking(pid, game_type, options, series_length, &state).await;
+                            } else {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::LeaveMatchmaking => {
                             if let Some(pid) = player_id {
-                                handle_join_matchmaking(pid, game_type, &db, &registry).await;
+                                registry.send_messages(game_logic::handle_leave_matchmaking_logic(pid, &state.ready_checks, &db).await).await;
                             } else {
-                                let _ = tx.send(ServerMessage::Error {
-                                    message: "Not authenticated".to_string(),
-                                });
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
                             }
                         }
                         ClientMessage::ResumeMatch => {
                             if let Some(pid) = player_id {
                                 handle_resume_match(pid, &db, &registry).await;
                             } else {
-                                let _ = tx.send(ServerMessage::Error {
-                                    message: "Not authenticated".to_string(),
-                                });
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::ChallengePlayer { player_id: challenged_id, game_type, options } => {
+                            if let Some(pid) = player_id {
+                                handle_challenge_player(pid, challenged_id, game_type, options, &challenge_config.load(), &db, &registry).await;
+                            } else {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::RespondToChallenge { challenge_id, accept } => {
+                            if let Some(pid) = player_id {
+                                handle_respond_to_challenge(pid, challenge_id, accept, &capacity, &db, &registry).await;
+                            } else {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::InviteToParty { player_id: invitee_id } => {
+                            if let Some(pid) = player_id {
+                                handle_invite_to_party(pid, invitee_id, &parties, &db, &registry).await;
+                            } else {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::RespondToPartyInvite { inviter_id, accept } => {
+                            if let Some(pid) = player_id {
+                                let messages = parties::handle_respond_to_party_invite_logic(pid, inviter_id, accept, &parties, &db).await;
+                                registry.send_messages(messages).await;
+                            } else {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::LeaveParty => {
+                            if let Some(pid) = player_id {
+                                registry.send_messages(parties::handle_leave_party_logic(pid, &parties)).await;
+                            } else {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::JoinArena { arena_id } => {
+                            if let Some(pid) = player_id {
+                                let messages = arena::handle_join_arena_logic(pid, arena_id, &arenas, &capacity, registry.events(), &db).await;
+                                registry.send_messages(messages).await;
+                            } else {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::LeaveArena => {
+                            if let Some(pid) = player_id {
+                                if let Some(arena_id) = arenas.leave(pid) {
+                                    println!("Player {pid} left arena {arena_id}");
+                                }
+                            }
+                        }
+                        ClientMessage::JoinQuickPlay { game_type } => {
+                            if player_id.is_some() || !quick_play.is_enabled() {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::GuestsOnly, &[]));
+                                continue;
+                            }
+                            let gid = match guest_id {
+                                Some(gid) => gid,
+                                None => {
+                                    let gid = quick_play.new_guest();
+                                    if let Err(limit) = capacity.check_connection(gid, registry.connection_count().await) {
+                                        println!("Guest {gid} turned away: {limit:?}");
+                                        let _ = tx.send(capacity.busy_message());
+                                        break;
+                                    }
+                                    connection_id = Some(registry.register(gid, tx.clone(), send_task.abort_handle()).await);
+                                    let _ = tx.send(ServerMessage::GuestSession { player_id: gid });
+                                    println!("Guest {gid} joined quick play");
+                                    guest_id = Some(gid);
+                                    gid
+                                }
+                            };
+                            registry.send_messages(quick_play::handle_join_quick_play_logic(gid, game_type, &quick_play)).await;
+                        }
+                        ClientMessage::UpgradeGuest { name, public_key_hint, public_key } => {
+                            if let Some(gid) = guest_id {
+                                let messages = quick_play::handle_upgrade_guest_logic(gid, name, &public_key_hint, &public_key, &quick_play, &db).await;
+                                registry.send_messages(messages).await;
+                            } else {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAGuest, &[]));
+                            }
+                        }
+                        ClientMessage::MakeMove { move_data } if guest_id.is_some() => {
+                            if let Some(gid) = guest_id {
+                                registry.send_messages(quick_play::handle_quick_play_move_logic(gid, move_data, &quick_play)).await;
+                            }
+                        }
+                        ClientMessage::RequestState { match_id } if guest_id.is_some() => {
+                            if let Some(gid) = guest_id {
+                                registry.send_messages(quick_play::handle_quick_play_state_logic(gid, match_id, &quick_play)).await;
                             }
                         }
                         ClientMessage::MakeMove { move_data } => {
                             if let Some(pid) = player_id {
                                 handle_make_move(pid, move_data, &db, &registry).await;
                             } else {
-                                let _ = tx.send(ServerMessage::Error {
-                                    message: "Not authenticated".to_string(),
-                                });
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::RequestState { match_id } => {
+                            if let Some(pid) = player_id {
+                                let messages = game_logic::handle_request_state_logic(pid, match_id, &db).await;
+                                registry.send_messages(messages).await;
+                            } else {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::AcceptMatch { match_id, accept } => {
+                            if let Some(pid) = player_id {
+                                let messages = ready_check::handle_accept_match_logic(
+                                    pid, match_id, accept, &capacity, &registry.settings().load().matchmaking, &ready_checks, registry.events(), &db,
+                                ).await;
+                                registry.send_messages(messages).await;
+                            } else {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::Resign | ClientMessage::OfferDraw | ClientMessage::AcceptDraw => {
+                            if let Some(pid) = player_id {
+                                let messages = match client_msg {
+                                    ClientMessage::Resign => game_logic::handle_resign_logic(pid, registry.events(), &db).await,
+                                    ClientMessage::OfferDraw => game_logic::handle_offer_draw_logic(pid, registry.events(), &db).await,
+                                    _ => game_logic::handle_accept_draw_logic(pid, registry.events(), &db).await,
+                                };
+                                registry.send_messages(messages).await;
+                            } else {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::RequestRematch { match_id } => {
+                            if let Some(pid) = player_id {
+                                let messages = rematches::handle_request_rematch_logic(
+                                    pid, match_id, &rematches, &capacity, registry.events(), &db,
+                                ).await;
+                                registry.send_messages(messages).await;
+                            } else {
+                                let _ = tx.send(ServerMessage::error(ErrorCode::NotAuthenticated, &[]));
+                            }
+                        }
+                        ClientMessage::Thinking => {
+                            if let Some(pid) = player_id {
+                                let messages = game_logic::handle_thinking_logic(pid, &db).await;
+                                registry.send_messages(messages).await;
                             }
                         }
                     }
+                } else if player_id.is_some() && is_join_matchmaking(&text) {
+                    let _ = tx.send(ServerMessage::MatchmakingRejected { reason: MatchmakingRejection::UnsupportedGame });
                 }
             }
             Ok(Message::Close(_)) => {
@@ -245,15 +628,33 @@ async fn handle_socket(
         }
     }
 
-    // Cleanup on disconnect
-    if let Some(pid) = player_id {
-        handle_disconnect(pid, &db, &registry).await;
-        registry.unregister(pid).await;
+    // Cleanup on disconnect, unless the player already came back on a newer connection
+    if let (Some(pid), Some(connection_id)) = (player_id, connection_id) {
+        if registry.unregister(pid, connection_id).await {
+            arenas.leave(pid);
+            handle_disconnect(pid, &db, &registry).await;
+        } else {
+            println!("Player {pid} reconnected meanwhile, keeping their match going");
+        }
+    }
+    if let (Some(gid), Some(connection_id)) = (guest_id, connection_id) {
+        registry.unregister(gid, connection_id).await;
+        registry.send_messages(quick_play::handle_quick_play_leave_logic(gid, &quick_play)).await;
     }
 
     send_task.abort();
 }
 
+/// A `join_matchmaking` that did not parse, most likely for a game this server does not run
+fn is_join_matchmaking(text: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(text).is_ok_and(|value| value["type"] == "join_matchmaking")
+}
+
+/// New matches are refused during maintenance, except to admins
+fn in_maintenance(player_id: Option<i64>, registry: &ConnectionRegistry, capacity: &Capacity) -> bool {
+    registry.settings().load().maintenance && !player_id.is_some_and(|pid| capacity.is_admin(pid))
+}
+
 async fn authenticate_token(
     session_cache: &crate::session_cache::SessionCache,
     token: &str,
@@ -264,22 +665,121 @@ async fn authenticate_token(
         .map_err(|e| format!("Invalid session: {e}"))
 }
 
-/// Handle resume match request
-async fn handle_resume_match(player_id: i64, db: &Arc<Database>, registry: &SharedRegistry) {
-    let resumable_match_id = registry.get_resumable_match(player_id).await;
+/// The match a player was in when they went offline, even if the server restarted since
+async fn left_behind_match(player_id: i64, db: &Database, registry: &SharedRegistry) -> Option<Match> {
+    let match_id = match registry.get_resumable_match(player_id, db).await {
+        Some(match_id) => match_id,
+        None => db.get_active_match_for_player(player_id).await?.id,
+    };
+    db.get_match_by_id(match_id).await?.to_match().filter(|match_info| match_info.status != MatchStatus::Waiting)
+}
+
+/// Offer to resume the match a player left behind, or tell them how it ended while they were away
+async fn reconcile_match_on_login(player_id: i64, db: &Arc<Database>, registry: &SharedRegistry) {
+    let Some(match_info) = left_behind_match(player_id, db, registry).await else {
+        return;
+    };
+    let opponent_id = if match_info.player1_id == player_id { match_info.player2_id } else { match_info.player1_id };
+    let opponent_present = registry.is_connected(opponent_id).await
+        || registry.get_resumable_match(opponent_id, db).await == Some(match_info.id);
+
+    let grace = registry.settings().load().disconnect_grace(&match_info.game_type);
+    let (messages, absent_opponent) =
+        game_logic::handle_login_reconciliation_logic(player_id, match_info.id, opponent_present, grace, registry.events(), db).await;
+    registry.send_messages(messages).await;
+
+    if let Some(opponent_id) = absent_opponent {
+        registry.start_disconnect_timer(opponent_id, match_info.id, match_info.game_type, db.clone(), registry.clone()).await;
+        return;
+    }
 
-    // Cancel the disconnect timer if exists
-    if resumable_match_id.is_some() {
-        registry.cancel_disconnect_timer(player_id).await;
+    let Some(ended) = db.get_match_by_id(match_info.id).await.and_then(|record| record.to_match()) else {
+        return;
+    };
+    if !ended.status.is_playing() {
+        registry.cancel_disconnect_timer(player_id, db).await;
+        if match_info.status.is_playing() {
+            registry.spectators().publish(&ended);
+        }
     }
+}
 
-    let messages = game_logic::handle_resume_match_logic(player_id, resumable_match_id, db).await;
+/// Handle resume match request
+async fn handle_resume_match(player_id: i64, db: &Arc<Database>, registry: &SharedRegistry) {
+    let resumable_match_id = match registry.get_resumable_match(player_id, db).await {
+        Some(match_id) => {
+            registry.cancel_disconnect_timer(player_id, db).await;
+            Some(match_id)
+        }
+        None => left_behind_match(player_id, db, registry).await.map(|match_info| match_info.id),
+    };
+
+    let delivered = match resumable_match_id {
+        Some(match_id) => registry.delivered_seqs(match_id).await,
+        None => HashMap::new(),
+    };
+    let messages = game_logic::handle_resume_match_logic(player_id, resumable_match_id, &delivered, db).await;
     registry.send_messages(messages).await;
 }
 
 /// Handle matchmaking request
-async fn handle_join_matchmaking(player_id: i64, game_type: GameType, db: &Arc<Database>, registry: &SharedRegistry) {
-    let messages = game_logic::handle_join_matchmaking_logic(player_id, game_type, db).await;
+/// Parties always play single matches, the series length only applies to players queueing alone
+async fn handle_join_matchmaking(player_id: i64, game_type: GameType, options: serde_json::Value, series_length: Option<u32>, state: &AppState) {
+    let (capacity, db, events) = (&state.capacity, &state.db, state.registry.events());
+    let messages = match state.parties.partner(player_id) {
+        Some(partner_id) => {
+            parties::handle_join_matchmaking_as_party_logic(player_id, partner_id, game_type, options, capacity, events, db).await
+        }
+        None => {
+            let window = &state.registry.settings().load().matchmaking;
+            game_logic::handle_join_matchmaking_logic(player_id, game_type, options, series_length, capacity, window, &state.ready_checks, events, db).await
+        }
+    };
+    state.registry.send_messages(messages).await;
+}
+
+/// Handle a party invite
+async fn handle_invite_to_party(
+    player_id: i64,
+    invitee_id: i64,
+    parties: &PartyRegistry,
+    db: &Arc<Database>,
+    registry: &SharedRegistry,
+) {
+    let invitee_online = registry.is_connected(invitee_id).await;
+    let messages = parties::handle_invite_to_party_logic(player_id, invitee_id, invitee_online, parties, db).await;
+    registry.send_messages(messages).await;
+}
+
+/// Handle a challenge to another player
+async fn handle_challenge_player(
+    player_id: i64,
+    challenged_id: i64,
+    game_type: GameType,
+    options: serde_json::Value,
+    config: &ChallengeConfig,
+    db: &Arc<Database>,
+    registry: &SharedRegistry,
+) {
+    let challenged_online = registry.is_connected(challenged_id).await;
+    let messages = challenges::handle_challenge_player_logic(player_id, challenged_id, game_type, options, challenged_online, config, db).await;
+    registry.send_messages(messages).await;
+}
+
+/// Handle an answer to a challenge
+async fn handle_respond_to_challenge(
+    player_id: i64,
+    challenge_id: i64,
+    accept: bool,
+    capacity: &Capacity,
+    db: &Arc<Database>,
+    registry: &SharedRegistry,
+) {
+    let challenger_online = match db.get_challenge_by_id(challenge_id).await {
+        Some(challenge) => registry.is_connected(challenge.challenger_id).await,
+        None => false,
+    };
+    let messages = challenges::handle_respond_to_challenge_logic(player_id, challenge_id, accept, challenger_online, capacity, registry.events(), db).await;
     registry.send_messages(messages).await;
 }
 
@@ -290,7 +790,7 @@ async fn handle_make_move(
     db: &Arc<Database>,
     registry: &SharedRegistry,
 ) {
-    let messages = game_logic::handle_make_move_logic(player_id, move_data, db).await;
+    let messages = game_logic::handle_make_move_logic(player_id, move_data, registry.events(), db).await;
     registry.send_messages(messages).await;
 }
 
@@ -323,12 +823,111 @@ async fn handle_disconnect_timeout(
     db: &Arc<Database>,
     registry: &SharedRegistry,
 ) {
-    {
-        let mut disconnects = registry.disconnects.write().await;
-        disconnects.remove(&player_id);
-        println!("Removed player {player_id} from disconnects map (timer expired)");
+    registry.disconnects.write().await.remove(&player_id);
+    match db.take_disconnect(player_id, Some(match_id)).await {
+        Ok(true) => println!("Removed player {player_id} from disconnects (timer expired)"),
+        Ok(false) => return,
+        Err(e) => println!("Failed to clear the disconnect of player {player_id}: {e}"),
     }
 
-    let messages = game_logic::handle_disconnect_timeout_logic(player_id, match_id, db).await;
+    let messages = game_logic::handle_disconnect_timeout_logic(player_id, match_id, registry.events(), db).await;
     registry.send_messages(messages).await;
-}
\ No newline at end of file
+
+    if let Some(match_info) = db.get_match_by_id(match_id).await.and_then(|record| record.to_match()) {
+        registry.spectators().publish(&match_info);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battld_common::games::{rock_paper_scissors::RockPaperScissorsMove, tic_tac_toe::TicTacToeGameState};
+    use std::time::Instant;
+
+    /// Tic-Tac-Toe being played at `seq`, with player 1 to move on even ones
+    fn state_update(seq: i64) -> OutgoingMessage {
+        let mut game_state = TicTacToeGameState::new();
+        game_state.current_player = if seq % 2 == 0 { 1 } else { 2 };
+        let match_data = Match {
+            id: 1,
+            player1_id: 1,
+            player2_id: 2,
+            seats: vec![],
+            in_progress: true,
+            status: MatchStatus::Active,
+            outcome: None,
+            game_type: GameType::TicTacToe,
+            game_state: serde_json::to_value(game_state).unwrap(),
+            players: vec![],
+            ephemeral: false,
+            draw_offered_by: None,
+            seq,
+        };
+        OutgoingMessage { player_id: 1, message: ServerMessage::GameStateUpdate { match_data } }
+    }
+
+    #[tokio::test]
+    async fn test_round_result_holds_back_what_follows_without_blocking() {
+        let settings = ServerSettings { turn_time_limit: Some(Duration::from_secs(60)), ..ServerSettings::default() };
+        let registry = Arc::new(ConnectionRegistry::with_settings(settings));
+        let (outbox, mut receiver) = outbox::channel();
+        registry.register(1, outbox, tokio::spawn(async {}).abort_handle()).await;
+        let round_result = OutgoingMessage {
+            player_id: 1,
+            message: ServerMessage::RoundResult {
+                match_id: 1,
+                round: 0,
+                moves: (RockPaperScissorsMove::Rock, RockPaperScissorsMove::Paper),
+                winner: Some(2),
+                shown_for_ms: 200,
+            },
+        };
+
+        let started = Instant::now();
+        registry.send_messages(vec![round_result, state_update(3)]).await;
+        assert!(started.elapsed() < Duration::from_millis(200));
+        assert!(matches!(receiver.recv().await, Some(ServerMessage::RoundResult { .. })));
+
+        // A newer state overtaking the held back one is not undone by it, nor by its legal moves and timer
+        registry.send_messages(vec![state_update(4)]).await;
+        let Some(ServerMessage::GameStateUpdate { match_data }) = receiver.recv().await else {
+            panic!("Expected the newer state");
+        };
+        assert_eq!(match_data.seq, 4);
+        assert!(matches!(receiver.recv().await, Some(ServerMessage::LegalMoves { .. })));
+        assert!(matches!(receiver.recv().await, Some(ServerMessage::TurnTimer { players, .. }) if players == vec![1]));
+        sleep(Duration::from_millis(400)).await;
+        assert!(tokio::time::timeout(Duration::from_millis(50), receiver.recv()).await.is_err());
+        assert_eq!(registry.delivered_seqs(1).await.get(&1), Some(&4));
+    }
+
+    #[tokio::test]
+    async fn test_a_replaced_connection_does_not_unregister_the_new_one() {
+        let registry = ConnectionRegistry::new();
+        let old_task = tokio::spawn(std::future::pending::<()>());
+        let (old_outbox, _old_receiver) = outbox::channel();
+        let old = registry.register(1, old_outbox, old_task.abort_handle()).await;
+
+        let (new_outbox, _new_receiver) = outbox::channel();
+        let new = registry.register(1, new_outbox, tokio::spawn(std::future::pending::<()>()).abort_handle()).await;
+        assert_ne!(old, new);
+        assert!(old_task.await.unwrap_err().is_cancelled(), "The replaced connection is closed");
+
+        assert!(!registry.unregister(1, old).await);
+        assert!(registry.is_connected(1).await);
+        assert!(registry.unregister(1, new).await);
+        assert!(!registry.is_connected(1).await);
+    }
+
+    #[test]
+    fn test_paced_batches() {
+        let round_result = |shown_for_ms| OutgoingMessage {
+            player_id: 1,
+            message: ServerMessage::RoundResult { match_id: 1, round: 0, moves: (RockPaperScissorsMove::Rock, RockPaperScissorsMove::Rock), winner: None, shown_for_ms },
+        };
+        let batches = paced_batches(vec![state_update(1), round_result(100), state_update(2), round_result(300)]);
+        let shape: Vec<(Duration, usize)> = batches.iter().map(|(pause, batch)| (*pause, batch.len())).collect();
+        assert_eq!(shape, vec![(Duration::ZERO, 2), (Duration::from_millis(100), 2)]);
+        assert!(paced_batches(vec![]).is_empty());
+    }
+}
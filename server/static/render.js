@@ -0,0 +1,50 @@
+const GAME_NAMES = {
+    TicTacToe: "Tic-Tac-Toe",
+    RockPaperScissors: "Rock-Paper-Scissors",
+    Briscola: "Briscola",
+    Chess: "Chess",
+};
+const CHESS_SYMBOLS = { Pawn: "p", Rook: "r", Knight: "n", Bishop: "b", Queen: "q", King: "k" };
+
+function renderState(match) {
+    const state = match.game_state;
+    switch (match.game_type) {
+        case "TicTacToe": {
+            const size = state.board_size || 3;
+            const rows = [];
+            for (let row = 0; row < size; row++) {
+                const cells = state.board.slice(row * size, (row + 1) * size)
+                    .map((cell) => ` ${[".", "X", "O"][cell]} `);
+                rows.push(cells.join("|"));
+            }
+            return rows.join(`\n${Array(size).fill("---").join("+")}\n`);
+        }
+        case "RockPaperScissors":
+            return state.rounds
+                .map(([p1, p2], i) => `Round ${i + 1}: ${p1 || "..."} vs ${p2 || "..."}`)
+                .join("\n");
+        case "Briscola": {
+            const table = state.table.map(([card]) => `${card.rank} of ${card.suit}`).join(", ");
+            const [p1, p2] = [state.player1_pile.length, state.player2_pile.length];
+            return `Briscola: ${state.briscola_suit}\n` +
+                `Cards left: ${state.cards_remaining_in_deck}\n` +
+                `Table: ${table || "-"}\n` +
+                `Cards collected: ${p1} vs ${p2}`;
+        }
+        case "Chess": {
+            const rows = [];
+            for (let row = 7; row >= 0; row--) {
+                const cells = state.board[row].map((cell) => {
+                    if (!cell) return ".";
+                    const symbol = CHESS_SYMBOLS[cell.piece];
+                    return cell.player === "White" ? symbol.toUpperCase() : symbol;
+                });
+                rows.push(`${row + 1} ${cells.join(" ")}`);
+            }
+            rows.push("  a b c d e f g h");
+            return rows.join("\n");
+        }
+        default:
+            return JSON.stringify(state, null, 2);
+    }
+}